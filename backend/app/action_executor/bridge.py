@@ -60,8 +60,14 @@ class BridgeActionExecutor(TaskActionExecutor):
             elif name == "type_text":
                 result = await self._bridge.execute("type_text", params, timeout_s=self._timeout_s)
             elif name == "send_keys" or name == "focus_search" or name == "send_or_submit":
-                keys = params.get("keys", "")
-                result = await self._bridge.execute("send_keys", {"keys": keys}, timeout_s=self._timeout_s)
+                if name == "send_keys" and params.get("mode") == "accelerator":
+                    # The collector looks up the element's own shortcut
+                    payload = dict(params)
+                else:
+                    payload = {"keys": params.get("keys", "")}
+                    if "delay_ms" in params:
+                        payload["delay_ms"] = params["delay_ms"]
+                result = await self._bridge.execute("send_keys", payload, timeout_s=self._timeout_s)
             elif name == "compose_text":
                 text = params.get("text", "")
                 if not text and self._ollama and desktop_context:
@@ -10,6 +10,7 @@ from uuid import uuid4
 
 logger = logging.getLogger(__name__)
 
+from . import tracing
 from .orchestrator import TaskOrchestrator
 from .planner import AutonomyPlanner, DeterministicAutonomyPlanner
 from .schemas import (
@@ -268,7 +269,10 @@ class AutonomousRunner:
                     await self._fail_run(run_id, "maximum iteration budget reached")
                     return
 
-                await self._run_cycle(run_id)
+                with tracing.span(
+                    "autonomy.cycle", **{"autonomy.run_id": run_id, "autonomy.iteration": run.iteration + 1}
+                ):
+                    await self._run_cycle(run_id)
 
                 run_after = await self.get_run(run_id)
                 if run_after is None:
@@ -473,6 +477,12 @@ class VisionAutonomousRunner:
                     run.finished_at = _utcnow()
                     self._append_log(run, "vision-agent", f"Completed: {steps[-1].action.reasoning}")
                     outcome = "completed"
+                elif steps and (steps[-1].result or {}).get("error_code") == "UserInterrupted":
+                    run.status = "cancelled"
+                    run.last_error = steps[-1].result.get("error") or "interrupted by the user"
+                    run.finished_at = _utcnow()
+                    self._append_log(run, "vision-agent", "Stopped: the user took over the mouse or keyboard.")
+                    outcome = "user_interrupted"
                 else:
                     run.status = "failed"
                     run.last_error = "max iterations reached without completing"
@@ -9,6 +9,8 @@ from uuid import uuid4
 
 from fastapi import WebSocket
 
+from . import tracing
+
 logger = logging.getLogger(__name__)
 
 
@@ -19,6 +21,10 @@ class CommandBridge:
         self._ws: Optional[WebSocket] = None
         self._pending: Dict[str, asyncio.Future] = {}
         self._default_timeout_s = max(1.0, float(default_timeout_s))
+        # action -> whether the collector's permission manifest allows it
+        self._permitted: Dict[str, bool] = {}
+        # Folder the collector scopes file actions to; sent again on reconnect
+        self._context_directory: Optional[str] = None
 
     @property
     def connected(self) -> bool:
@@ -33,6 +39,7 @@ class CommandBridge:
             logger.info("CommandBridge: stale detach ignored (ws mismatch)")
             return
         self._ws = None
+        self._permitted = {}
         # Cancel all pending futures
         for fut in self._pending.values():
             if not fut.done():
@@ -40,16 +47,88 @@ class CommandBridge:
         self._pending.clear()
         logger.info("CommandBridge: collector detached")
 
+    def set_permissions(self, manifest: Optional[Dict[str, Any]]) -> None:
+        """Adopt the permission manifest the collector sent.
+
+        The collector enforces it regardless; knowing it lets commands it
+        would refuse fail here without a round trip.
+        """
+        permitted: Dict[str, bool] = {}
+        categories = (manifest or {}).get("categories") or {}
+        for category in categories.values():
+            for action in category.get("actions") or []:
+                permitted[action] = bool(category.get("enabled", True))
+        self._permitted = permitted
+        disabled = sorted(name for name, c in categories.items() if not c.get("enabled", True))
+        logger.info("CommandBridge: collector permissions updated, disabled=%s", disabled or "none")
+
+    def permits(self, action: str) -> bool:
+        """Whether the collector's manifest allows ``action`` (unknown actions are allowed)."""
+        return self._permitted.get(action, True)
+
+    @property
+    def context_directory(self) -> Optional[str]:
+        return self._context_directory
+
+    async def set_context_directory(self, path: Optional[str]) -> bool:
+        """Scope the collector's file actions to ``path``; None clears it.
+
+        The folder is remembered and sent again whenever the collector
+        reconnects (see ``sync_context_directory``). Returns whether the
+        collector applied it now.
+        """
+        self._context_directory = path or None
+        return await self.sync_context_directory()
+
+    async def sync_context_directory(self) -> bool:
+        """Send the remembered context directory to the collector."""
+        if self._ws is None:
+            return False
+        try:
+            result = await self.execute(
+                "set_context_directory", {"path": self._context_directory}, timeout_s=5
+            )
+        except (RuntimeError, asyncio.TimeoutError) as exc:
+            logger.warning("CommandBridge: could not set context directory: %s", exc)
+            return False
+        if not result.get("ok"):
+            logger.warning(
+                "CommandBridge: collector refused context directory %s: %s",
+                self._context_directory,
+                result.get("error"),
+            )
+            return False
+        return True
+
     async def execute(
         self,
         action: str,
         parameters: Optional[Dict[str, Any]] = None,
         timeout_s: Optional[float] = None,
+        *,
+        include_screenshot: Optional[bool] = None,
+        include_uia: Optional[bool] = None,
+        uia_depth: Optional[int] = None,
     ) -> Dict[str, Any]:
+        """Run ``action`` on the collector and wait for its result.
+
+        ``include_screenshot``, ``include_uia`` and ``uia_depth`` choose the
+        observation attached to the result; left unset, the collector's
+        configuration decides.
+        """
         if self._ws is None:
             raise RuntimeError("CommandBridge: not connected to collector")
 
         command_id = str(uuid4())
+        if not self.permits(action):
+            return {
+                "type": "command_result",
+                "command_id": command_id,
+                "ok": False,
+                "error": f"PermissionDenied: '{action}' is not permitted on the collector's machine",
+                "error_code": "PermissionDenied",
+                "result": {},
+            }
         timeout = timeout_s if timeout_s is not None else self._default_timeout_s
 
         loop = asyncio.get_running_loop()
@@ -63,22 +142,94 @@ class CommandBridge:
             "parameters": parameters or {},
             "timeout_ms": int(timeout * 1000),
         }
+        for key, value in (
+            ("include_screenshot", include_screenshot),
+            ("include_uia", include_uia),
+            ("uia_depth", uia_depth),
+        ):
+            if value is not None:
+                command[key] = value
+
+        with tracing.span(
+            "bridge.execute", **{"command.action": action, "command.id": command_id}
+        ) as span:
+            # The collector exports its spans for this command under ours
+            parent = tracing.traceparent()
+            if parent:
+                command["traceparent"] = parent
+
+            try:
+                await self._ws.send_json(command)
+            except Exception as exc:
+                self._pending.pop(command_id, None)
+                raise RuntimeError(f"CommandBridge: failed to send command: {exc}") from exc
+
+            try:
+                result = await asyncio.wait_for(future, timeout=timeout)
+            except (asyncio.TimeoutError, asyncio.CancelledError):
+                # Nobody awaits the result any more; stop the collector working on it
+                await self.cancel(command_id)
+                raise
+            finally:
+                self._pending.pop(command_id, None)
+
+            tracing.annotate(
+                span,
+                **{"command.ok": bool(result.get("ok")), "command.error_code": result.get("error_code")},
+            )
+        return result
+
+    async def cancel(self, command_id: str) -> bool:
+        """Ask the collector to abort an in-flight command.
 
+        Best effort: the collector answers a cancelled command with a
+        ``Cancelled`` failure, but one that already finished is unaffected.
+        Returns False if the request could not be sent.
+        """
+        if self._ws is None:
+            return False
         try:
-            await self._ws.send_json(command)
+            await self._ws.send_json({"type": "cancel", "command_id": command_id})
         except Exception as exc:
-            self._pending.pop(command_id, None)
-            raise RuntimeError(f"CommandBridge: failed to send command: {exc}") from exc
+            logger.warning("CommandBridge: failed to send cancel for %s: %s", command_id, exc)
+            return False
+        return True
 
-        try:
-            result = await asyncio.wait_for(future, timeout=timeout)
-        except asyncio.TimeoutError:
-            self._pending.pop(command_id, None)
-            raise
-        finally:
-            self._pending.pop(command_id, None)
+    async def subscribe(
+        self,
+        subscription_id: str,
+        filter_expression: str,
+        duration_s: Optional[float] = None,
+    ) -> bool:
+        """Ask the collector to send only events matching ``filter_expression``.
+
+        Uses the event tap's filter language, e.g.
+        ``type == window_opened and title ~ invoice``. While any subscription
+        is active the collector streams nothing else; it drops them all on
+        reconnect. Returns False if the request could not be sent.
+        """
+        message: Dict[str, Any] = {
+            "type": "subscribe",
+            "subscription_id": subscription_id,
+            "filter": filter_expression,
+        }
+        if duration_s is not None:
+            message["duration_s"] = duration_s
+        return await self._send_control(message)
 
-        return result
+    async def unsubscribe(self, subscription_id: str) -> bool:
+        """End a subscription; with none left the full stream resumes."""
+        return await self._send_control({"type": "unsubscribe", "subscription_id": subscription_id})
+
+    async def _send_control(self, message: Dict[str, Any]) -> bool:
+        if self._ws is None:
+            return False
+        try:
+            await self._ws.send_json(message)
+        except Exception as exc:
+            logger.warning("CommandBridge: failed to send %s: %s", message["type"], exc)
+            return False
+        return True
 
     def handle_result(self, data: Dict[str, Any]) -> bool:
         command_id = data.get("command_id", "")
@@ -94,4 +245,6 @@ class CommandBridge:
         return {
             "connected": self.connected,
             "pending_commands": len(self._pending),
+            "denied_actions": sorted(a for a, ok in self._permitted.items() if not ok),
+            "context_directory": self._context_directory,
         }
@@ -0,0 +1,120 @@
+"""Reassembly of large collector payloads sent in parts over the ingest socket.
+
+The collector splits payloads over ``WS_CHUNK_BYTES`` (full screenshots, deep
+UIA trees) into ``chunk`` messages so pings, commands and small events are not
+stuck behind them (see collector/src/chunking.rs). Each part carries the
+payload id, its index, the part count, the payload's size in bytes and its
+CRC-32. Parts of several payloads may interleave with each other and with
+ordinary messages.
+"""
+
+from __future__ import annotations
+
+import json
+import logging
+import time
+import zlib
+from dataclasses import dataclass, field
+from typing import Any, Callable, Dict, Optional
+
+logger = logging.getLogger(__name__)
+
+# Incomplete payloads are dropped after this long without a new part.
+STALE_AFTER_S = 30.0
+# Upper bounds on what one connection may hold while reassembling.
+MAX_PENDING_PAYLOADS = 32
+MAX_PAYLOAD_BYTES = 64 * 1024 * 1024
+
+
+@dataclass
+class _Pending:
+    count: int
+    size: int
+    checksum: str
+    parts: Dict[int, str] = field(default_factory=dict)
+    received_bytes: int = 0
+    updated_at: float = 0.0
+
+
+class ChunkAssembler:
+    """Collects ``chunk`` messages until a payload is complete."""
+
+    def __init__(self, clock: Callable[[], float] = time.monotonic) -> None:
+        self._clock = clock
+        self._pending: Dict[str, _Pending] = {}
+
+    @property
+    def pending_count(self) -> int:
+        return len(self._pending)
+
+    def add(self, message: Dict[str, Any]) -> Optional[Dict[str, Any]]:
+        """Take one part; return the decoded payload once all parts are in.
+
+        Malformed parts, and payloads failing the size or checksum check, are
+        logged and dropped.
+        """
+        now = self._clock()
+        self._drop_stale(now)
+
+        payload_id = message.get("payload_id")
+        index = message.get("index")
+        count = message.get("count")
+        size = message.get("size")
+        checksum = message.get("checksum")
+        data = message.get("data")
+        if (
+            not isinstance(payload_id, str)
+            or not isinstance(index, int)
+            or not isinstance(count, int)
+            or not isinstance(size, int)
+            or not isinstance(checksum, str)
+            or not isinstance(data, str)
+            or not 0 <= index < count
+            or not 0 <= size <= MAX_PAYLOAD_BYTES
+        ):
+            logger.warning("Dropping malformed payload part %r", payload_id)
+            return None
+
+        pending = self._pending.get(payload_id)
+        if pending is None:
+            if len(self._pending) >= MAX_PENDING_PAYLOADS:
+                logger.warning("Too many payloads in flight; dropping %s", payload_id)
+                return None
+            pending = _Pending(count=count, size=size, checksum=checksum)
+            self._pending[payload_id] = pending
+        elif (pending.count, pending.size, pending.checksum) != (count, size, checksum):
+            logger.warning("Payload %s parts disagree; dropping it", payload_id)
+            del self._pending[payload_id]
+            return None
+
+        if index not in pending.parts:
+            pending.parts[index] = data
+            pending.received_bytes += len(data.encode("utf-8"))
+        pending.updated_at = now
+        if pending.received_bytes > pending.size:
+            logger.warning("Payload %s is larger than announced; dropping it", payload_id)
+            del self._pending[payload_id]
+            return None
+        if len(pending.parts) < pending.count:
+            return None
+
+        del self._pending[payload_id]
+        raw = "".join(pending.parts[i] for i in range(pending.count)).encode("utf-8")
+        if len(raw) != pending.size or f"{zlib.crc32(raw):08x}" != pending.checksum.lower():
+            logger.warning("Payload %s failed its size or checksum check; dropping it", payload_id)
+            return None
+        try:
+            payload = json.loads(raw)
+        except ValueError as exc:
+            logger.warning("Payload %s is not valid JSON: %s", payload_id, exc)
+            return None
+        if not isinstance(payload, dict):
+            logger.warning("Payload %s is not a JSON object; dropping it", payload_id)
+            return None
+        return payload
+
+    def _drop_stale(self, now: float) -> None:
+        stale = [pid for pid, p in self._pending.items() if now - p.updated_at > STALE_AFTER_S]
+        for payload_id in stale:
+            logger.warning("Payload %s incomplete after %.0fs; dropping it", payload_id, STALE_AFTER_S)
+            del self._pending[payload_id]
@@ -5,7 +5,7 @@ from __future__ import annotations
 import asyncio
 from dataclasses import dataclass
 from datetime import datetime
-from typing import Any, Dict, Optional
+from typing import Any, Dict, List, Optional
 
 
 @dataclass
@@ -19,6 +19,14 @@ class CollectorStatus:
     last_heartbeat_at: Optional[datetime] = None
     total_events: int = 0
     uia_events: int = 0
+    collector_id: Optional[str] = None
+    collector_name: Optional[str] = None
+    collector_version: Optional[str] = None
+    collector_theme: Optional[Dict[str, Any]] = None
+    collector_permissions: Optional[Dict[str, Any]] = None
+    collector_hook: Optional[Dict[str, Any]] = None  # foreground WinEvent hook health
+    collector_power: Optional[Dict[str, Any]] = None  # capture profile: AC or battery
+    collector_assets: Optional[List[Dict[str, Any]]] = None  # model downloads and their progress
 
 
 class CollectorStatusStore:
@@ -36,10 +44,46 @@ class CollectorStatusStore:
             self._s.ws_connected = False
             self._s.ws_disconnected_at = now
             self._s.last_heartbeat_at = None
+            self._s.collector_hook = None
+            self._s.collector_power = None
+            self._s.collector_assets = None
 
-    async def note_heartbeat(self, now: datetime) -> None:
+    async def note_heartbeat(
+        self,
+        now: datetime,
+        hook: Optional[Dict[str, Any]] = None,
+        power: Optional[Dict[str, Any]] = None,
+    ) -> None:
         async with self._lock:
             self._s.last_heartbeat_at = now
+            if hook is not None:
+                self._s.collector_hook = hook
+            if power is not None:
+                self._s.collector_power = power
+
+    async def note_hello(
+        self,
+        *,
+        collector_id: Optional[str],
+        collector_name: Optional[str],
+        version: Optional[str],
+        theme: Optional[Dict[str, Any]] = None,
+        permissions: Optional[Dict[str, Any]] = None,
+    ) -> None:
+        async with self._lock:
+            self._s.collector_id = collector_id
+            self._s.collector_name = collector_name
+            self._s.collector_version = version
+            self._s.collector_theme = theme
+            self._s.collector_permissions = permissions
+
+    async def note_assets(self, assets: Optional[List[Dict[str, Any]]]) -> None:
+        async with self._lock:
+            self._s.collector_assets = assets
+
+    async def note_permissions(self, permissions: Optional[Dict[str, Any]]) -> None:
+        async with self._lock:
+            self._s.collector_permissions = permissions
 
     async def note_event(self, now: datetime, *, transport: str, source: str, has_uia: bool) -> None:
         async with self._lock:
@@ -63,4 +107,12 @@ class CollectorStatusStore:
                 "last_source": s.last_source,
                 "total_events": s.total_events,
                 "uia_events": s.uia_events,
+                "collector_id": s.collector_id,
+                "collector_name": s.collector_name,
+                "collector_version": s.collector_version,
+                "collector_theme": s.collector_theme,
+                "collector_permissions": s.collector_permissions,
+                "collector_hook": s.collector_hook,
+                "collector_power": s.collector_power,
+                "collector_assets": s.collector_assets,
             }
@@ -10,6 +10,10 @@ from datetime import datetime, timezone
 from pathlib import Path
 from typing import Any, Optional
 
+_OPPOSITE_SCROLL = {"up": "down", "down": "up", "left": "right", "right": "left"}
+# Parameters that place a scroll over a specific pane; undo scrolls there too.
+_SCROLL_TARGET_KEYS = ("x", "y", "name", "automation_id")
+
 
 def _compute_undo(
     action: str, params: dict[str, Any], prev_window: str | None = None
@@ -40,18 +44,20 @@ def _compute_undo(
     if action == "scroll":
         direction = params.get("direction", "down")
         amount = params.get("amount", 3)
-        opposite = "up" if direction == "down" else "down"
-        return True, "scroll", {"direction": opposite, "amount": amount}
+        opposite = _OPPOSITE_SCROLL.get(direction, "up")
+        undo = {"direction": opposite, "amount": amount}
+        undo.update({k: params[k] for k in _SCROLL_TARGET_KEYS if k in params})
+        return True, "scroll", undo
     if action == "_scroll_in_window":
         direction = params.get("direction", "down")
         amount = params.get("amount", 3)
-        opposite = "up" if direction == "down" else "down"
+        opposite = _OPPOSITE_SCROLL.get(direction, "up")
         return True, "_scroll_in_window", {
             "window": params.get("window", ""),
             "direction": opposite,
             "amount": amount,
         }
-    # click, double_click, right_click, send_keys — not reversible
+    # click, double_click, triple_click, right_click, middle_click, send_keys — not reversible
     return False, None, None
 
 
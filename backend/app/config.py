@@ -150,6 +150,9 @@ class Settings:
     )
     detection_merge_iou: float = float(_env("DETECTION_MERGE_IOU", "0.3"))
 
+    otel_exporter_otlp_endpoint: str = _env("OTEL_EXPORTER_OTLP_ENDPOINT", "")
+    otel_service_name: str = _env("OTEL_SERVICE_NAME", "desktopai-backend")
+
     allowed_origins: List[str] = field(
         default_factory=lambda: [
             origin.strip()
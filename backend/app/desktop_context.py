@@ -54,6 +54,9 @@ class DesktopContext:
 
 
 def _build_uia_summary(uia: UiaSnapshot) -> str:
+    # The collector's outline is already sized for a prompt
+    if uia.outline:
+        return uia.outline
     parts: List[str] = []
     if uia.focused_name:
         parts.append(f"Focused: {uia.focused_name}")
@@ -13,6 +13,7 @@ from fastapi.staticfiles import StaticFiles
 
 # Import deps to initialize singletons (db, store, ollama, etc.)
 from . import deps as _deps
+from . import tracing
 from .auth import TokenAuthMiddleware
 from .config import settings
 from .routes.agent import router as agent_router
@@ -89,6 +90,7 @@ async def _restore_runtime_ollama_model() -> None:
 
 @asynccontextmanager
 async def _lifespan(_app: FastAPI):
+    tracing.setup(settings.otel_exporter_otlp_endpoint, settings.otel_service_name)
     current, events, idle, idle_since = await _deps.db.load_snapshot(settings.event_log_max)
     await _deps.store.hydrate(events, current, idle, idle_since)
     task_records = await _deps.db.list_task_records(limit=500)
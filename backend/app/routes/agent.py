@@ -29,7 +29,15 @@ from ..deps import (
     vision_runner,
 )
 from ..recipes import match_recipe_by_keywords, recipe_to_plan_steps
-from ..schemas import AutonomyStartRequest, ChatRequest, WindowEvent
+from ..schemas import (
+    AutonomyStartRequest,
+    ChatRequest,
+    ContextDirectoryRequest,
+    DiagnosticBundleRequest,
+    EventPreviewRequest,
+    WindowActionRequest,
+    WindowEvent,
+)
 
 logger = logging.getLogger(__name__)
 
@@ -85,9 +93,9 @@ _DIRECT_PATTERNS: list[tuple[re.Pattern, str, Callable[[re.Match], dict[str, Any
     (re.compile(r"^(?:focus|switch\s+to|go\s+to)\s+(.+)$", re.I),
      "focus_window", lambda m: {"title": m.group(1).strip()}),
     # "scroll down in Notepad" — explicit target window (must come before generic scroll)
-    (re.compile(r"^scroll\s+(up|down)\s+(?:in|on)\s+(.+)$", re.I),
+    (re.compile(r"^scroll\s+(up|down|left|right)\s+(?:in|on)\s+(.+)$", re.I),
      "_scroll_in_window", lambda m: {"direction": m.group(1).lower(), "amount": 3, "window": m.group(2).strip()}),
-    (re.compile(r"^scroll\s+(up|down)(?:\s+(\d+))?$", re.I),
+    (re.compile(r"^scroll\s+(up|down|left|right)(?:\s+(\d+))?$", re.I),
      "scroll", lambda m: {"direction": m.group(1).lower(), "amount": int(m.group(2) or 3)}),
     (re.compile(r"^(?:press|send(?:\s+keys?)?)\s+(.+)$", re.I),
      "send_keys", lambda m: {"keys": m.group(1).strip()}),
@@ -96,8 +104,12 @@ _DIRECT_PATTERNS: list[tuple[re.Pattern, str, Callable[[re.Match], dict[str, Any
      "click", lambda m: {"name": m.group(1).strip()}),
     (re.compile(r"^double[- ]?click\s+(?:on\s+)?(?:the\s+)?['\"]?(.+?)['\"]?$", re.I),
      "double_click", lambda m: {"name": m.group(1).strip()}),
+    (re.compile(r"^triple[- ]?click\s+(?:on\s+)?(?:the\s+)?['\"]?(.+?)['\"]?$", re.I),
+     "triple_click", lambda m: {"name": m.group(1).strip()}),
     (re.compile(r"^right[- ]?click\s+(?:on\s+)?(?:the\s+)?['\"]?(.+?)['\"]?$", re.I),
      "right_click", lambda m: {"name": m.group(1).strip()}),
+    (re.compile(r"^middle[- ]?click\s+(?:on\s+)?(?:the\s+)?['\"]?(.+?)['\"]?$", re.I),
+     "middle_click", lambda m: {"name": m.group(1).strip()}),
     # Type patterns (must come after click to avoid "type" matching "click type...")
     (re.compile(r"^type\s+['\"]?(.+?)['\"]?\s+(?:in|into)\s+(.+)$", re.I),
      "_type_in_window", lambda m: {"text": m.group(1), "window": m.group(2).strip()}),
@@ -106,6 +118,11 @@ _DIRECT_PATTERNS: list[tuple[re.Pattern, str, Callable[[re.Match], dict[str, Any
     # Kill switch: stop/kill/cancel/abort all actions
     (re.compile(r"^(?:stop|kill|cancel|abort)(?:\s+(?:all|everything|actions?))?$", re.I),
      "_cancel_all", lambda m: {}),
+    # Ending apps by image name ("quit notepad", "force quit chrome.exe")
+    (re.compile(r"^(?:quit|exit)\s+(.+)$", re.I),
+     "close_application", lambda m: {"name": m.group(1).strip()}),
+    (re.compile(r"^(?:force[- ]quit|kill)\s+(.+)$", re.I),
+     "kill_process", lambda m: {"name": m.group(1).strip()}),
     # Undo: reverse the last reversible direct bridge action
     (re.compile(r"^undo(?:\s+last)?$", re.I),
      "_undo", lambda m: {}),
@@ -554,6 +571,57 @@ async def get_bridge_status() -> dict:
     return bridge.status()
 
 
+@router.post("/api/agent/bridge/preview")
+async def preview_outgoing_events(request: EventPreviewRequest) -> dict:
+    """Privacy preview: start capturing the collector's next outgoing events
+    (with ``count``) and return those captured so far, after redaction."""
+    if not bridge.connected:
+        raise HTTPException(status_code=503, detail="collector bridge not connected")
+    try:
+        return await bridge.execute(
+            "preview_events", request.model_dump(exclude_none=True), timeout_s=5,
+        )
+    except (RuntimeError, asyncio.TimeoutError) as exc:
+        raise HTTPException(status_code=502, detail=str(exc) or "collector did not answer")
+
+
+@router.post("/api/agent/bridge/diagnostic-bundle")
+async def create_diagnostic_bundle(request: DiagnosticBundleRequest) -> dict:
+    """Have the collector write an encrypted diagnostic bundle holding only
+    the categories in ``include``; returns where it was written."""
+    if not bridge.connected:
+        raise HTTPException(status_code=503, detail="collector bridge not connected")
+    try:
+        return await bridge.execute(
+            "create_diagnostic_bundle", request.model_dump(exclude_none=True), timeout_s=30,
+        )
+    except (RuntimeError, asyncio.TimeoutError) as exc:
+        raise HTTPException(status_code=502, detail=str(exc) or "collector did not answer")
+
+
+@router.post("/api/agent/bridge/window-action")
+async def run_window_action(request: WindowActionRequest) -> dict:
+    """Run a window switcher action on the collector for a palette that has
+    no embedded collector; returns the collector's result."""
+    if not bridge.connected:
+        raise HTTPException(status_code=503, detail="collector bridge not connected")
+    try:
+        return await bridge.execute(request.action, request.parameters, timeout_s=8)
+    except (RuntimeError, asyncio.TimeoutError) as exc:
+        raise HTTPException(status_code=502, detail=str(exc) or "collector did not answer")
+
+
+@router.put("/api/agent/bridge/context-directory")
+async def set_context_directory(request: ContextDirectoryRequest) -> dict:
+    """Scope the collector's file actions to the folder the user picked.
+
+    The folder is kept and sent again whenever the collector reconnects, so
+    ``applied`` is False while it is away rather than an error.
+    """
+    applied = await bridge.set_context_directory(request.path)
+    return {"context_directory": bridge.context_directory, "applied": applied}
+
+
 def _build_vision_agent(max_iterations: int = 0):
     """Build a VisionAgent with current settings."""
     from ..vision_agent import VisionAgent
@@ -691,6 +759,13 @@ async def chat_endpoint(request: ChatRequest):  # -> dict | StreamingResponse
     user_ctx = dict(ctx_dict) if ctx_dict else {}
     if request.input_source:
         user_ctx["input_source"] = request.input_source
+    if request.attachments:
+        user_ctx["attachments"] = [a.model_dump() for a in request.attachments]
+    if request.context_directory:
+        user_ctx["context_directory"] = request.context_directory
+        if request.context_directory != bridge.context_directory:
+            # File actions run in the collector, which must scope to it too
+            await bridge.set_context_directory(request.context_directory)
     await chat_memory.save_message(
         conversation_id, "user", message, desktop_context=user_ctx or None
     )
@@ -800,6 +875,8 @@ async def chat_endpoint(request: ChatRequest):  # -> dict | StreamingResponse
             recent_events=(await store.snapshot())[1],
             recent_switches=fg_switches,
             session_context=session_context,
+            attachments=user_ctx.get("attachments"),
+            context_directory=user_ctx.get("context_directory"),
         )
 
         # SSE streaming branch
@@ -875,6 +952,8 @@ def _build_llm_messages(
     recent_events: list,
     recent_switches: Optional[list] = None,
     session_context: Optional[str] = None,
+    attachments: Optional[list[dict]] = None,
+    context_directory: Optional[str] = None,
 ) -> list[dict]:
     """Build the multi-turn messages array for the LLM call."""
     llm_messages: list[dict] = []
@@ -944,10 +1023,29 @@ def _build_llm_messages(
     for msg in history:
         llm_messages.append({"role": msg["role"], "content": msg["content"]})
 
-    llm_messages.append({"role": "user", "content": message})
+    content = message
+    if attachments:
+        content += "\n\n" + _describe_attachments(attachments)
+    if context_directory:
+        content += (
+            f"\n\nWorking folder: {context_directory} "
+            "(\"this folder\" and relative paths refer to it)"
+        )
+    llm_messages.append({"role": "user", "content": content})
     return llm_messages
 
 
+def _describe_attachments(attachments: list[dict]) -> str:
+    """List dropped files for the LLM so the user can refer to 'this file'."""
+    lines = ["Attached by the user:"]
+    for item in attachments:
+        kind = "folder" if item.get("is_dir") else "file"
+        size = item.get("size")
+        detail = f", {size} bytes" if size is not None and not item.get("is_dir") else ""
+        lines.append(f"- {item['path']} ({kind}{detail})")
+    return "\n".join(lines)
+
+
 async def _stream_chat_response(
     *,
     llm_messages: list[dict],
@@ -6,6 +6,7 @@ from datetime import datetime, timezone
 
 from fastapi import APIRouter, WebSocket, WebSocketDisconnect
 
+from ..chunking import ChunkAssembler
 from ..config import settings
 from ..deps import (
     _dump,
@@ -162,17 +163,73 @@ async def ingest_ws(ws: WebSocket) -> None:
     watchdog_task = asyncio.create_task(
         _pong_watchdog(ws, last_recv, pong_timeout_s)
     )
+    chunks = ChunkAssembler()
+    # Held so the context directory resend is not garbage collected mid-flight
+    context_task: asyncio.Task | None = None
     try:
         while True:
             data = await ws.receive_json()
             # Any message from collector proves the connection is alive
             last_recv[0] = asyncio.get_running_loop().time()
             msg_type = data.get("type", "")
+            if msg_type == "chunk":
+                # Part of a large payload; handled once all parts are in
+                data = chunks.add(data)
+                if data is None:
+                    continue
+                msg_type = data.get("type", "")
             if msg_type == "command_result":
                 bridge.handle_result(data)
                 continue
+            if msg_type == "hello":
+                await collector_status.note_hello(
+                    collector_id=data.get("collector_id"),
+                    collector_name=data.get("collector_name"),
+                    version=data.get("version"),
+                    theme=data.get("theme"),
+                    permissions=data.get("permissions"),
+                )
+                bridge.set_permissions(data.get("permissions"))
+                if bridge.context_directory:
+                    # A freshly started collector has no context directory yet
+                    context_task = asyncio.create_task(bridge.sync_context_directory())
+                logger.info(
+                    "Collector hello id=%s name=%s version=%s",
+                    data.get("collector_id"),
+                    data.get("collector_name"),
+                    data.get("version"),
+                )
+                continue
+            if msg_type == "permissions":
+                await collector_status.note_permissions(data.get("permissions"))
+                bridge.set_permissions(data.get("permissions"))
+                continue
+            if msg_type == "asset_status":
+                # Model download progress (see collector/src/assets.rs)
+                await collector_status.note_assets(data.get("assets"))
+                await hub.broadcast_json({"type": "collector_assets", "assets": data.get("assets")})
+                continue
+            if msg_type == "subscription":
+                # Collector's answer to a subscribe/unsubscribe (see bridge.subscribe)
+                if data.get("status") == "error":
+                    logger.warning(
+                        "Collector refused subscription %s: %s",
+                        data.get("subscription_id"),
+                        data.get("error"),
+                    )
+                continue
+            if msg_type == "time_sync":
+                # Echo the collector's send time with ours so it can estimate clock offset
+                await ws.send_json({
+                    "type": "time_sync",
+                    "t0": data.get("t0"),
+                    "server_time_ms": int(datetime.now(timezone.utc).timestamp() * 1000),
+                })
+                continue
             if msg_type in ("pong", "heartbeat"):
-                await collector_status.note_heartbeat(datetime.now(timezone.utc))
+                await collector_status.note_heartbeat(
+                    datetime.now(timezone.utc), hook=data.get("hook"), power=data.get("power")
+                )
                 continue
             event = _parse_event(data)
             await _handle_event(event, transport="ws")
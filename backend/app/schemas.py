@@ -29,6 +29,8 @@ class UiaSnapshot(BaseModel):
     document_text: str = ""
     focused_element: Optional[UiaElement] = None
     window_tree: List[UiaElement] = Field(default_factory=list)
+    # Token-budgeted text outline computed by the collector; may replace window_tree
+    outline: str = ""
 
 
 class WindowEvent(BaseModel):
@@ -167,6 +169,31 @@ class OllamaModelRequest(BaseModel):
     model: str = Field(min_length=1)
 
 
+class EventPreviewRequest(BaseModel):
+    count: Optional[int] = Field(default=None, ge=1, le=20)  # start a new capture
+    redact_pattern: Optional[str] = None  # candidate pattern to try
+    raw: bool = False
+
+
+DiagnosticCategory = Literal["events", "logs", "config", "metrics", "screenshots"]
+
+
+class DiagnosticBundleRequest(BaseModel):
+    include: List[DiagnosticCategory] = Field(min_length=1)  # categories the user consented to
+    password: str = Field(min_length=8)
+    path: Optional[str] = None  # absolute; defaults to the collector's data directory
+
+
+class WindowActionRequest(BaseModel):
+    # Only what the palette's window switcher needs
+    action: Literal["get_window_thumbnail", "list_windows", "focus_window"]
+    parameters: Dict[str, Any] = Field(default_factory=dict)
+
+
+class ContextDirectoryRequest(BaseModel):
+    path: Optional[str] = None  # absolute; None clears the context directory
+
+
 class OllamaProbeRequest(BaseModel):
     prompt: str = Field(default="Respond with exactly: OK", min_length=1, max_length=4000)
     timeout_s: float = Field(default=8.0, ge=1.0, le=60.0)
@@ -229,6 +256,15 @@ class DetectedElement(BaseModel):
     confidence: float
 
 
+class ChatAttachment(BaseModel):
+    """A local file or folder the user dropped onto the desktop app."""
+
+    path: str = Field(min_length=1)
+    name: str = ""
+    size: Optional[int] = None
+    is_dir: bool = False
+
+
 class ChatRequest(BaseModel):
     message: str = Field(min_length=1)
     allow_actions: bool = True
@@ -236,3 +272,5 @@ class ChatRequest(BaseModel):
     input_source: Optional[str] = None  # "keyboard", "voice", "palette"
     personality_mode: Optional[PersonalityMode] = None
     stream: bool = False
+    attachments: List[ChatAttachment] = Field(default_factory=list)
+    context_directory: Optional[str] = None  # folder "this folder" refers to
@@ -34,6 +34,11 @@ class StateStore:
                 )
                 if self._session_start is None:
                     self._session_start = snapshot.timestamp
+            elif snapshot.type == "observation":
+                # Deferred full enrichment of the current window (collector
+                # pacing): upgrade the current snapshot without counting a switch.
+                if self._current is not None and self._current.hwnd == snapshot.hwnd:
+                    self._current = snapshot.model_copy(update={"type": "foreground"})
             elif snapshot.type == "idle":
                 self._idle = True
                 self._idle_since = snapshot.timestamp
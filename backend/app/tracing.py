@@ -0,0 +1,85 @@
+"""Optional OpenTelemetry tracing of agent runs and collector commands.
+
+On when ``OTEL_EXPORTER_OTLP_ENDPOINT`` is set and the OpenTelemetry SDK and
+its OTLP/HTTP exporter are installed (``opentelemetry-sdk``,
+``opentelemetry-exporter-otlp-proto-http``); otherwise every helper here is a
+no-op. Commands sent to the collector carry the current span as a W3C
+``traceparent`` and the collector exports its spans as children of it (see
+collector/src/trace.rs), so one trace follows a request from the plan
+through execution on the desktop and back to its result.
+"""
+
+from __future__ import annotations
+
+import logging
+from contextlib import contextmanager
+from typing import Any, Iterator, Optional
+
+logger = logging.getLogger(__name__)
+
+_tracer: Any = None
+
+
+def setup(endpoint: str, service_name: str) -> bool:
+    """Export spans to ``<endpoint>/v1/traces``; returns whether tracing is on."""
+    global _tracer
+    if not endpoint.strip():
+        return False
+    try:
+        from opentelemetry import trace
+        from opentelemetry.exporter.otlp.proto.http.trace_exporter import OTLPSpanExporter
+        from opentelemetry.sdk.resources import Resource
+        from opentelemetry.sdk.trace import TracerProvider
+        from opentelemetry.sdk.trace.export import BatchSpanProcessor
+    except ImportError:
+        logger.warning("opentelemetry-sdk not installed — tracing unavailable")
+        return False
+
+    url = f"{endpoint.strip().rstrip('/')}/v1/traces"
+    provider = TracerProvider(resource=Resource.create({"service.name": service_name}))
+    provider.add_span_processor(BatchSpanProcessor(OTLPSpanExporter(endpoint=url)))
+    trace.set_tracer_provider(provider)
+    _tracer = trace.get_tracer("desktopai-backend")
+    logger.info("Exporting traces to %s", url)
+    return True
+
+
+@contextmanager
+def span(name: str, **attributes: Any) -> Iterator[Any]:
+    """Run the block in a span; yields it, or None while tracing is off.
+
+    Attribute names may contain dots, so pass them as ``**{"a.b": value}``.
+    ``None`` values are left out.
+    """
+    if _tracer is None:
+        yield None
+        return
+    clean = {key: value for key, value in attributes.items() if value is not None}
+    with _tracer.start_as_current_span(name, attributes=clean) as current:
+        yield current
+
+
+def annotate(current: Any, **attributes: Any) -> None:
+    """Set attributes on a span from ``span``; ignores None."""
+    if current is None:
+        return
+    for key, value in attributes.items():
+        if value is not None:
+            current.set_attribute(key, value)
+
+
+def format_traceparent(trace_id: int, span_id: int, flags: int) -> Optional[str]:
+    """W3C ``traceparent`` header value, or None for an invalid context."""
+    if not trace_id or not span_id:
+        return None
+    return f"00-{trace_id:032x}-{span_id:016x}-{flags & 0xFF:02x}"
+
+
+def traceparent() -> Optional[str]:
+    """The current span as a ``traceparent``, or None while tracing is off."""
+    if _tracer is None:
+        return None
+    from opentelemetry import trace
+
+    context = trace.get_current_span().get_span_context()
+    return format_traceparent(context.trace_id, context.span_id, int(context.trace_flags))
@@ -9,6 +9,8 @@ from dataclasses import dataclass
 from datetime import datetime, timezone
 from typing import Any, Callable, Dict, List, Optional
 
+from . import tracing
+
 logger = logging.getLogger(__name__)
 
 VISION_AGENT_PROMPT = """\
@@ -20,12 +22,20 @@ AVAILABLE ACTIONS (respond with exactly one JSON object):
 - {{"action": "click", "parameters": {{"name": "ButtonName"}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "click", "parameters": {{"automation_id": "btn_id"}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "type_text", "parameters": {{"text": "content to type"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "type_text", "parameters": {{"text": "search terms", "clear": true, "press_enter": true}}, "reasoning": "replace the field's text and submit", "confidence": 0.9}}
+- {{"action": "paste_text", "parameters": {{"text": "a long paragraph or code block"}}, "reasoning": "long text is pasted instead of typed", "confidence": 0.9}}
+- {{"action": "drop_files", "parameters": {{"paths": ["C:/Users/me/report.pdf"], "title": "New Message"}}, "reasoning": "attach the file by dropping it on the window", "confidence": 0.9}}
 - {{"action": "send_keys", "parameters": {{"keys": "ctrl+c"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"keys": "ctrl+a, ctrl+c"}}, "reasoning": "several shortcuts in order", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"mode": "accelerator", "automation_id": "SaveButton"}}, "reasoning": "press the app's own shortcut for Save, whatever the language", "confidence": 0.9}}
 - {{"action": "open_application", "parameters": {{"application": "notepad.exe"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "browser_navigate", "parameters": {{"url": "https://example.com"}}, "reasoning": "open a page through the browser's address bar", "confidence": 0.9}}
 - {{"action": "focus_window", "parameters": {{"title": "Window Title"}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "scroll", "parameters": {{"direction": "down", "amount": 3}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "double_click", "parameters": {{"name": "ItemName"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "triple_click", "parameters": {{"name": "ItemName"}}, "reasoning": "select the whole line or paragraph before editing it", "confidence": 0.9}}
 - {{"action": "right_click", "parameters": {{"name": "ItemName"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "middle_click", "parameters": {{"name": "ItemName"}}, "reasoning": "close this browser tab, or open this link in a new tab", "confidence": 0.9}}
 - {{"action": "wait", "parameters": {{}}, "reasoning": "waiting for UI to update", "confidence": 1.0}}
 - {{"action": "done", "parameters": {{}}, "reasoning": "objective completed because...", "confidence": 0.95}}
 
@@ -64,12 +74,20 @@ AVAILABLE ACTIONS (respond with exactly one JSON object):
 - {{"action": "click", "parameters": {{"element_id": 3}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "type_text", "parameters": {{"text": "content to type"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "type_text", "parameters": {{"text": "search terms", "clear": true, "press_enter": true}}, "reasoning": "replace the field's text and submit", "confidence": 0.9}}
+- {{"action": "paste_text", "parameters": {{"text": "a long paragraph or code block"}}, "reasoning": "long text is pasted instead of typed", "confidence": 0.9}}
+- {{"action": "drop_files", "parameters": {{"paths": ["C:/Users/me/report.pdf"], "title": "New Message"}}, "reasoning": "attach the file by dropping it on the window", "confidence": 0.9}}
 - {{"action": "send_keys", "parameters": {{"keys": "ctrl+c"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"keys": "ctrl+a, ctrl+c"}}, "reasoning": "several shortcuts in order", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"mode": "accelerator", "automation_id": "SaveButton"}}, "reasoning": "press the app's own shortcut for Save, whatever the language", "confidence": 0.9}}
 - {{"action": "open_application", "parameters": {{"application": "notepad.exe"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "browser_navigate", "parameters": {{"url": "https://example.com"}}, "reasoning": "open a page through the browser's address bar", "confidence": 0.9}}
 - {{"action": "focus_window", "parameters": {{"title": "Window Title"}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "scroll", "parameters": {{"direction": "down", "amount": 3}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "double_click", "parameters": {{"element_id": 5}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "triple_click", "parameters": {{"element_id": 5}}, "reasoning": "select the whole line or paragraph before editing it", "confidence": 0.9}}
 - {{"action": "right_click", "parameters": {{"element_id": 5}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "middle_click", "parameters": {{"element_id": 5}}, "reasoning": "close this browser tab, or open this link in a new tab", "confidence": 0.9}}
 - {{"action": "wait", "parameters": {{}}, "reasoning": "waiting for UI to update", "confidence": 1.0}}
 - {{"action": "done", "parameters": {{}}, "reasoning": "objective completed because...", "confidence": 0.95}}
 
@@ -98,10 +116,18 @@ OBJECTIVE: {objective}
 AVAILABLE ACTIONS (respond with exactly one JSON object):
 - {{"action": "click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "double_click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "triple_click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "select the whole line or paragraph before editing it", "confidence": 0.9}}
 - {{"action": "right_click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "middle_click", "parameters": {{"x": 450, "y": 320}}, "reasoning": "close this browser tab, or open this link in a new tab", "confidence": 0.9}}
 - {{"action": "type_text", "parameters": {{"text": "content to type"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "type_text", "parameters": {{"text": "search terms", "clear": true, "press_enter": true}}, "reasoning": "replace the field's text and submit", "confidence": 0.9}}
+- {{"action": "paste_text", "parameters": {{"text": "a long paragraph or code block"}}, "reasoning": "long text is pasted instead of typed", "confidence": 0.9}}
+- {{"action": "drop_files", "parameters": {{"paths": ["C:/Users/me/report.pdf"], "title": "New Message"}}, "reasoning": "attach the file by dropping it on the window", "confidence": 0.9}}
 - {{"action": "send_keys", "parameters": {{"keys": "ctrl+c"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"keys": "ctrl+a, ctrl+c"}}, "reasoning": "several shortcuts in order", "confidence": 0.9}}
+- {{"action": "send_keys", "parameters": {{"mode": "accelerator", "automation_id": "SaveButton"}}, "reasoning": "press the app's own shortcut for Save, whatever the language", "confidence": 0.9}}
 - {{"action": "open_application", "parameters": {{"application": "notepad.exe"}}, "reasoning": "why", "confidence": 0.9}}
+- {{"action": "browser_navigate", "parameters": {{"url": "https://example.com"}}, "reasoning": "open a page through the browser's address bar", "confidence": 0.9}}
 - {{"action": "focus_window", "parameters": {{"title": "Window Title"}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "scroll", "parameters": {{"direction": "down", "amount": 3}}, "reasoning": "why", "confidence": 0.9}}
 - {{"action": "wait", "parameters": {{}}, "reasoning": "waiting for UI to update", "confidence": 1.0}}
@@ -117,7 +143,7 @@ Process: {process_exe}
 {trajectory_section}
 RULES:
 1. Respond with ONLY a JSON object. No markdown, no explanation.
-2. For click/double_click/right_click, use pixel coordinates (x, y) from the screenshot.
+2. For click/double_click/triple_click/right_click/middle_click, use pixel coordinates (x, y) from the screenshot.
 3. Each action should move you closer to the objective.
 4. IMPORTANT: After each action, check if the objective has ALREADY been achieved. If yes, respond with action "done".
 5. Use "wait" if you need the UI to settle after a previous action.
@@ -194,6 +220,22 @@ class VisionAgent:
         self,
         objective: str,
         on_step: Optional[Callable[[AgentStep], Any]] = None,
+    ) -> List[AgentStep]:
+        with tracing.span("vision_agent.run", **{"agent.max_iterations": self._max_iterations}) as span:
+            steps = await self._run_steps(objective, on_step)
+            tracing.annotate(
+                span,
+                **{
+                    "agent.steps": len(steps),
+                    "agent.last_action": steps[-1].action.action if steps else None,
+                },
+            )
+            return steps
+
+    async def _run_steps(
+        self,
+        objective: str,
+        on_step: Optional[Callable[[AgentStep], Any]] = None,
     ) -> List[AgentStep]:
         import asyncio
 
@@ -324,6 +366,13 @@ class VisionAgent:
                 result = await self._act(action)
                 step.result = result
                 consecutive_errors = 0
+                if isinstance(result, dict) and result.get("error_code") == "UserInterrupted":
+                    # The user took over the mouse or keyboard; stop driving the desktop
+                    logger.info("VisionAgent: %s interrupted by the user, stopping", action.action)
+                    steps.append(step)
+                    if on_step:
+                        on_step(step)
+                    break
             except Exception as exc:
                 step.error = str(exc)
                 step.result = {"ok": False, "error": str(exc)}
@@ -357,15 +406,17 @@ class VisionAgent:
         result = await self._bridge.execute("observe", timeout_s=10)
         uia_raw = result.get("uia")
         uia_elements = None
+        uia_summary = json.dumps(uia_raw) if uia_raw else None
         if uia_raw and isinstance(uia_raw, dict):
             uia_elements = uia_raw.get("window_tree", [])
+            uia_summary = uia_raw.get("outline") or uia_summary
         inner = result.get("result", {})
         detections = result.get("detections")
         if detections:
             logger.info("Received %d detections from collector", len(detections))
         return AgentObservation(
             screenshot_b64=result.get("screenshot_b64"),
-            uia_summary=json.dumps(uia_raw) if uia_raw else None,
+            uia_summary=uia_summary,
             window_title=inner.get("window_title", ""),
             process_exe=inner.get("process_exe", ""),
             timestamp=datetime.now(timezone.utc),
@@ -391,13 +442,21 @@ class VisionAgent:
         history: List[AgentStep],
         trajectory_context: str = "",
     ) -> AgentAction:
-        if self._should_use_detection(observation):
-            return await self._reason_detection(
-                objective, observation, history, trajectory_context,
-            )
-        return await self._reason_vlm(
-            objective, observation, history, trajectory_context,
-        )
+        detection = self._should_use_detection(observation)
+        with tracing.span(
+            "vision_agent.reason",
+            **{"agent.step": len(history), "agent.mode": "detection" if detection else "vlm"},
+        ) as span:
+            if detection:
+                action = await self._reason_detection(
+                    objective, observation, history, trajectory_context,
+                )
+            else:
+                action = await self._reason_vlm(
+                    objective, observation, history, trajectory_context,
+                )
+            tracing.annotate(span, **{"agent.action": action.action})
+            return action
 
     async def _reason_detection(
         self,
@@ -456,7 +515,7 @@ class VisionAgent:
         action = self._parse_action(response)
 
         # Resolve element_id → x/y coordinates for click actions
-        if action.action in ("click", "double_click", "right_click"):
+        if action.action in ("click", "double_click", "triple_click", "right_click", "middle_click"):
             params = dict(action.parameters)
             element_id = params.get("element_id")
             if element_id is not None and merged:
@@ -57,6 +57,49 @@ async def test_execute_and_handle_result(bridge):
     assert result["result"]["action"] == "observe"
 
 
+@pytest.mark.asyncio
+async def test_execute_passes_observation_choices(bridge):
+    ws = AsyncMock()
+    bridge.attach(ws)
+
+    async def answer():
+        await asyncio.sleep(0.01)
+        command_id = ws.send_json.call_args[0][0]["command_id"]
+        bridge.handle_result({"type": "command_result", "command_id": command_id, "ok": True})
+
+    task = asyncio.create_task(answer())
+    await bridge.execute("click", {"x": 1, "y": 2}, include_screenshot=False, uia_depth=6)
+    await task
+    command = ws.send_json.call_args[0][0]
+    assert command["include_screenshot"] is False
+    assert command["uia_depth"] == 6
+    assert "include_uia" not in command
+
+    task = asyncio.create_task(answer())
+    await bridge.execute("observe")
+    await task
+    command = ws.send_json.call_args[0][0]
+    assert not {"include_screenshot", "include_uia", "uia_depth"} & command.keys()
+
+
+@pytest.mark.asyncio
+async def test_execute_carries_traceparent(bridge, monkeypatch):
+    ws = AsyncMock()
+    bridge.attach(ws)
+    parent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    monkeypatch.setattr("app.bridge.tracing.traceparent", lambda: parent)
+
+    async def answer():
+        await asyncio.sleep(0.01)
+        command_id = ws.send_json.call_args[0][0]["command_id"]
+        bridge.handle_result({"type": "command_result", "command_id": command_id, "ok": True})
+
+    task = asyncio.create_task(answer())
+    await bridge.execute("observe")
+    await task
+    assert ws.send_json.call_args[0][0]["traceparent"] == parent
+
+
 @pytest.mark.asyncio
 async def test_timeout_raises(bridge):
     ws = AsyncMock()
@@ -66,6 +109,57 @@ async def test_timeout_raises(bridge):
         await bridge.execute("observe", timeout_s=0.05)
 
 
+@pytest.mark.asyncio
+async def test_timeout_cancels_command_on_collector(bridge):
+    ws = AsyncMock()
+    bridge.attach(ws)
+
+    with pytest.raises(asyncio.TimeoutError):
+        await bridge.execute("run_shell", {"program": "slow.exe"}, timeout_s=0.05)
+
+    command, cancel = (call[0][0] for call in ws.send_json.call_args_list)
+    assert cancel == {"type": "cancel", "command_id": command["command_id"]}
+
+
+@pytest.mark.asyncio
+async def test_caller_cancellation_cancels_command_on_collector(bridge):
+    ws = AsyncMock()
+    bridge.attach(ws)
+
+    task = asyncio.create_task(bridge.execute("wait_for_window", timeout_s=2.0))
+    await asyncio.sleep(0.01)
+    task.cancel()
+    with pytest.raises(asyncio.CancelledError):
+        await task
+
+    assert ws.send_json.call_args[0][0]["type"] == "cancel"
+    assert len(bridge._pending) == 0
+
+
+@pytest.mark.asyncio
+async def test_cancel_without_collector(bridge):
+    assert await bridge.cancel("some-id") is False
+
+
+@pytest.mark.asyncio
+async def test_subscribe_and_unsubscribe(bridge):
+    assert await bridge.subscribe("invoice", "title ~ invoice") is False
+
+    ws = AsyncMock()
+    bridge.attach(ws)
+    assert await bridge.subscribe("excel", "type == foreground and process ~ excel", duration_s=300)
+    assert await bridge.unsubscribe("excel")
+
+    subscribe, unsubscribe = (call[0][0] for call in ws.send_json.call_args_list)
+    assert subscribe == {
+        "type": "subscribe",
+        "subscription_id": "excel",
+        "filter": "type == foreground and process ~ excel",
+        "duration_s": 300,
+    }
+    assert unsubscribe == {"type": "unsubscribe", "subscription_id": "excel"}
+
+
 @pytest.mark.asyncio
 async def test_detach_cancels_pending(bridge):
     ws = AsyncMock()
@@ -120,3 +214,54 @@ async def test_matching_detach_works(bridge):
 
     bridge.detach(ws1)
     assert not bridge.connected
+
+
+@pytest.mark.asyncio
+async def test_manifest_denied_action_fails_without_round_trip(bridge):
+    ws = AsyncMock()
+    bridge.attach(ws)
+    bridge.set_permissions({
+        "categories": {
+            "shell": {"enabled": False, "actions": ["run_shell"]},
+            "input": {"enabled": True, "actions": ["click"]},
+        }
+    })
+
+    result = await bridge.execute("run_shell", {"program": "cmd.exe"})
+
+    assert result["ok"] is False
+    assert result["error_code"] == "PermissionDenied"
+    ws.send_json.assert_not_called()
+    assert bridge.permits("click")
+    assert bridge.permits("observe")  # not in the manifest
+    assert bridge.status()["denied_actions"] == ["run_shell"]
+
+    bridge.detach()
+    assert bridge.permits("run_shell")
+
+
+@pytest.mark.asyncio
+async def test_context_directory_is_kept_and_resent(bridge):
+    assert await bridge.set_context_directory(r"C:\Users\me\Invoices") is False
+    assert bridge.context_directory == r"C:\Users\me\Invoices"
+
+    ws = AsyncMock()
+    bridge.attach(ws)
+
+    async def simulate_result():
+        await asyncio.sleep(0.01)
+        command = ws.send_json.call_args[0][0]
+        bridge.handle_result({
+            "type": "command_result",
+            "command_id": command["command_id"],
+            "ok": True,
+        })
+
+    task = asyncio.create_task(simulate_result())
+    assert await bridge.sync_context_directory() is True
+    await task
+
+    command = ws.send_json.call_args[0][0]
+    assert command["action"] == "set_context_directory"
+    assert command["parameters"] == {"path": r"C:\Users\me\Invoices"}
+    assert bridge.status()["context_directory"] == r"C:\Users\me\Invoices"
@@ -51,6 +51,16 @@ async def test_click_via_bridge(executor, mock_bridge):
     )
 
 
+@pytest.mark.asyncio
+async def test_send_keys_accelerator_via_bridge(executor, mock_bridge):
+    mock_bridge.execute.return_value = {"ok": True, "result": {"keys": "ctrl+s", "source": "accelerator_key"}}
+    params = {"mode": "accelerator", "automation_id": "SaveButton", "title": "Editor"}
+    result = await executor.execute(TaskAction(action="send_keys", parameters=params), objective="save")
+
+    assert result.ok
+    mock_bridge.execute.assert_called_once_with("send_keys", params, timeout_s=5)
+
+
 @pytest.mark.asyncio
 async def test_compose_text_with_llm(executor, mock_bridge):
     mock_ollama = AsyncMock()
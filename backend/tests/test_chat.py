@@ -634,6 +634,42 @@ async def test_direct_double_click(client):
     )
 
 
+@pytest.mark.anyio
+async def test_direct_triple_click(client):
+    """'triple-click Introduction' should call triple_click."""
+    ws_patch, exec_patch, mock_exec = _mock_bridge_connected()
+    with ws_patch, exec_patch:
+        resp = await client.post(
+            "/api/chat",
+            json={"message": "triple-click Introduction", "allow_actions": True},
+        )
+
+    assert resp.status_code == 200
+    data = resp.json()
+    assert data["source"] == "direct"
+    mock_exec.assert_called_once_with(
+        "triple_click", {"name": "Introduction"}, timeout_s=5,
+    )
+
+
+@pytest.mark.anyio
+async def test_direct_middle_click(client):
+    """'middle-click Pricing' should call middle_click."""
+    ws_patch, exec_patch, mock_exec = _mock_bridge_connected()
+    with ws_patch, exec_patch:
+        resp = await client.post(
+            "/api/chat",
+            json={"message": "middle-click Pricing", "allow_actions": True},
+        )
+
+    assert resp.status_code == 200
+    data = resp.json()
+    assert data["source"] == "direct"
+    mock_exec.assert_called_once_with(
+        "middle_click", {"name": "Pricing"}, timeout_s=5,
+    )
+
+
 @pytest.mark.anyio
 async def test_direct_right_click(client):
     """'right-click Desktop' should call right_click."""
@@ -1000,6 +1036,16 @@ async def test_stop_command_matches_direct_pattern(client):
         assert result[0] == "_cancel_all"
 
 
+def test_quit_and_kill_patterns_end_applications():
+    """'quit X' closes gracefully, 'kill X' escalates; 'kill all' stays the kill switch."""
+    from app.routes.agent import _match_direct_pattern
+
+    assert _match_direct_pattern("quit Notepad") == ("close_application", {"name": "Notepad"})
+    assert _match_direct_pattern("force quit chrome.exe") == ("kill_process", {"name": "chrome.exe"})
+    assert _match_direct_pattern("kill notepad") == ("kill_process", {"name": "notepad"})
+    assert _match_direct_pattern("kill all")[0] == "_cancel_all"
+
+
 @pytest.mark.anyio
 async def test_stop_command_returns_direct_source(client):
     """'stop' command returns source='direct' response."""
@@ -1613,6 +1659,51 @@ async def test_chat_input_source_defaults_to_none(client):
     assert "conversation_id" in data
 
 
+# ── Dropped file attachments and working folder ─────────────────────────────
+
+
+@pytest.mark.anyio
+async def test_chat_attachments_reach_llm_and_history(client):
+    """Dropped files are listed in the LLM prompt and saved with the message."""
+    attachment = {"path": "C:\\Users\\me\\report.pdf", "name": "report.pdf", "size": 2048}
+    with patch.object(ollama, "available", new_callable=AsyncMock, return_value=True), \
+         patch.object(ollama, "chat", new_callable=AsyncMock, return_value="It is a report.") as mock_chat:
+        resp = await client.post(
+            "/api/chat",
+            json={"message": "what is in this report", "attachments": [attachment]},
+        )
+    assert resp.status_code == 200
+    user_content = mock_chat.call_args[0][0][-1]["content"]
+    assert user_content.startswith("what is in this report")
+    assert "- C:\\Users\\me\\report.pdf (file, 2048 bytes)" in user_content
+
+    messages = await chat_memory.get_messages(resp.json()["conversation_id"], limit=10)
+    user_msg = next(m for m in messages if m["role"] == "user")
+    assert user_msg["content"] == "what is in this report"
+    assert user_msg["desktop_context"]["attachments"][0]["path"] == attachment["path"]
+
+
+@pytest.mark.anyio
+async def test_chat_context_directory_reaches_llm(client):
+    """The palette's working folder is named in the LLM prompt."""
+    with patch.object(ollama, "available", new_callable=AsyncMock, return_value=True), \
+         patch.object(ollama, "chat", new_callable=AsyncMock, return_value="Three of them.") as mock_chat:
+        resp = await client.post(
+            "/api/chat",
+            json={"message": "how many screenshots are here", "context_directory": "C:\\Shots"},
+        )
+    assert resp.status_code == 200
+    user_content = mock_chat.call_args[0][0][-1]["content"]
+    assert "Working folder: C:\\Shots" in user_content
+
+
+def test_describe_attachments_marks_folders():
+    from app.routes.agent import _describe_attachments
+
+    text = _describe_attachments([{"path": "/tmp/project", "is_dir": True, "size": 4096}])
+    assert text == "Attached by the user:\n- /tmp/project (folder)"
+
+
 # ── Command history and undo tests ──────────────────────────────────────────
 
 
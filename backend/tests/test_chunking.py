@@ -0,0 +1,72 @@
+"""Tests for reassembly of chunked collector payloads."""
+
+from __future__ import annotations
+
+import json
+import zlib
+
+from app.chunking import STALE_AFTER_S, ChunkAssembler
+
+
+def _split(payload: dict, part_chars: int, payload_id: str = "p1") -> list[dict]:
+    """Split like the collector does (collector/src/chunking.rs)."""
+    text = json.dumps(payload)
+    raw = text.encode("utf-8")
+    parts = [text[i : i + part_chars] for i in range(0, len(text), part_chars)]
+    return [
+        {
+            "type": "chunk",
+            "payload_id": payload_id,
+            "index": i,
+            "count": len(parts),
+            "size": len(raw),
+            "checksum": f"{zlib.crc32(raw):08x}",
+            "data": part,
+        }
+        for i, part in enumerate(parts)
+    ]
+
+
+def test_reassembles_out_of_order_and_interleaved():
+    assembler = ChunkAssembler()
+    first = {"type": "foreground", "title": "Résumé – draft", "screenshot_b64": "A" * 500}
+    second = {"type": "command_result", "command_id": "c1", "ok": True}
+    a = _split(first, 64, "a")
+    b = _split(second, 16, "b")
+
+    for part in b[:-1]:
+        assert assembler.add(part) is None
+    for part in reversed(a[1:]):
+        assert assembler.add(part) is None
+    assert assembler.add(b[-1]) == second
+    assert assembler.add(a[0]) == first
+    assert assembler.pending_count == 0
+
+
+def test_drops_corrupt_and_malformed_parts():
+    assembler = ChunkAssembler()
+    parts = _split({"type": "foreground", "title": "x" * 100}, 40)
+    parts[1] = {**parts[1], "data": parts[1]["data"].replace("x", "y", 1)}
+    assert [assembler.add(p) for p in parts] == [None] * len(parts)
+    assert assembler.pending_count == 0
+
+    assert assembler.add({"type": "chunk", "payload_id": "p2", "index": 3, "count": 2}) is None
+    assert assembler.pending_count == 0
+
+    parts = _split({"type": "foreground"}, 8, "p3")
+    assembler.add(parts[0])
+    assert assembler.add({**parts[1], "count": parts[1]["count"] + 1}) is None
+    assert assembler.pending_count == 0, "parts disagreeing on the count drop the payload"
+
+
+def test_drops_stale_payloads():
+    now = [0.0]
+    assembler = ChunkAssembler(clock=lambda: now[0])
+    stale = _split({"type": "foreground", "title": "x" * 50}, 20, "old")
+    assembler.add(stale[0])
+    now[0] = STALE_AFTER_S + 1
+    fresh = _split({"type": "heartbeat"}, 100, "new")
+    assert assembler.add(fresh[0]) == {"type": "heartbeat"}
+    assert assembler.pending_count == 0
+    for part in stale[1:]:
+        assert assembler.add(part) is None
@@ -93,3 +93,72 @@ async def test_initial_snapshot_has_heartbeat_field(status_store):
     snap = await status_store.snapshot()
     assert "last_heartbeat_at" in snap
     assert snap["last_heartbeat_at"] is None
+
+
+@pytest.mark.asyncio
+async def test_hello_records_identity(status_store):
+    await status_store.note_hello(collector_id="abc-123", collector_name="LAB-PC", version="0.1.0")
+    snap = await status_store.snapshot()
+    assert snap["collector_id"] == "abc-123"
+    assert snap["collector_name"] == "LAB-PC"
+    assert snap["collector_version"] == "0.1.0"
+    assert snap["collector_theme"] is None
+
+
+@pytest.mark.asyncio
+async def test_hello_records_theme(status_store):
+    theme = {"apps": "dark", "system": "dark", "high_contrast": False}
+    await status_store.note_hello(collector_id="abc-123", collector_name="LAB-PC", version="0.1.0", theme=theme)
+    snap = await status_store.snapshot()
+    assert snap["collector_theme"] == theme
+
+
+@pytest.mark.asyncio
+async def test_permissions_from_hello_and_updates(status_store):
+    manifest = {"categories": {"shell": {"enabled": True, "actions": ["run_shell"]}}}
+    await status_store.note_hello(collector_id="abc-123", collector_name="LAB-PC", version="0.1.0", permissions=manifest)
+    assert (await status_store.snapshot())["collector_permissions"] == manifest
+
+    manifest["categories"]["shell"]["enabled"] = False
+    await status_store.note_permissions(manifest)
+    snap = await status_store.snapshot()
+    assert snap["collector_permissions"]["categories"]["shell"]["enabled"] is False
+
+
+@pytest.mark.asyncio
+async def test_heartbeat_records_hook_health(status_store):
+    now = datetime.now(timezone.utc)
+    hook = {"installed": True, "healthy": True, "restarts": 1, "last_error": "no heartbeat for 20s"}
+    await status_store.note_heartbeat(now, hook=hook)
+    assert (await status_store.snapshot())["collector_hook"] == hook
+
+    # Pongs carry no hook health and keep the last report
+    await status_store.note_heartbeat(now)
+    assert (await status_store.snapshot())["collector_hook"] == hook
+
+    await status_store.note_ws_disconnected(now)
+    assert (await status_store.snapshot())["collector_hook"] is None
+
+
+@pytest.mark.asyncio
+async def test_heartbeat_records_power_profile(status_store):
+    now = datetime.now(timezone.utc)
+    await status_store.note_heartbeat(now, power={"profile": "battery", "battery_percent": 64})
+    assert (await status_store.snapshot())["collector_power"]["profile"] == "battery"
+
+    await status_store.note_heartbeat(now)
+    assert (await status_store.snapshot())["collector_power"]["profile"] == "battery"
+
+    await status_store.note_ws_disconnected(now)
+    assert (await status_store.snapshot())["collector_power"] is None
+
+
+@pytest.mark.asyncio
+async def test_asset_status_recorded_until_disconnect(status_store):
+    assert (await status_store.snapshot())["collector_assets"] is None
+    assets = [{"name": "ui-detr", "state": "downloading", "received_bytes": 10, "total_bytes": 1000, "percent": 1}]
+    await status_store.note_assets(assets)
+    assert (await status_store.snapshot())["collector_assets"] == assets
+
+    await status_store.note_ws_disconnected(datetime.now(timezone.utc))
+    assert (await status_store.snapshot())["collector_assets"] is None
@@ -71,6 +71,16 @@ def test_compute_undo_scroll_up():
     assert undo_params == {"direction": "down", "amount": 2}
 
 
+def test_compute_undo_horizontal_scroll_at_position():
+    """Horizontal scroll is reversed at the same position."""
+    reversible, undo_action, undo_params = _compute_undo(
+        "scroll", {"direction": "right", "amount": 4, "x": 300, "y": 200}
+    )
+    assert reversible is True
+    assert undo_action == "scroll"
+    assert undo_params == {"direction": "left", "amount": 4, "x": 300, "y": 200}
+
+
 def test_compute_undo_send_keys_not_reversible():
     """send_keys is not reversible."""
     reversible, undo_action, undo_params = _compute_undo(
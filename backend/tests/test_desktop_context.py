@@ -46,6 +46,18 @@ def test_from_event_with_uia_snapshot():
     assert "Hello world" in ctx.uia_summary
 
 
+def test_from_event_prefers_collector_outline():
+    uia = UiaSnapshot(
+        focused_name="Subject",
+        window_tree=[UiaElement(name="Raw Tree Node")],
+        outline='focused: edit "Subject"\n[0] window "Inbox"',
+    )
+    ctx = DesktopContext.from_event(_make_event(uia=uia))
+    assert ctx is not None
+    assert ctx.uia_summary == 'focused: edit "Subject"\n[0] window "Inbox"'
+    assert "Raw Tree Node" not in ctx.uia_summary
+
+
 def test_from_event_without_uia_has_empty_summary():
     event = _make_event(uia=None)
     ctx = DesktopContext.from_event(event)
@@ -34,6 +34,39 @@ async def test_bridge_status():
     assert "connected" in data
 
 
+@pytest.mark.asyncio
+async def test_event_preview_requires_collector():
+    async with AsyncClient(transport=ASGITransport(app=app), base_url="http://test") as ac:
+        resp = await ac.post("/api/agent/bridge/preview", json={"count": 5})
+        invalid = await ac.post("/api/agent/bridge/preview", json={"count": 0})
+    assert resp.status_code == 503
+    assert invalid.status_code == 422
+
+
+@pytest.mark.asyncio
+async def test_window_action_is_limited_to_the_switcher():
+    url = "/api/agent/bridge/window-action"
+    async with AsyncClient(transport=ASGITransport(app=app), base_url="http://test") as ac:
+        resp = await ac.post(url, json={"action": "focus_window", "parameters": {"hwnd": "0x1"}})
+        other = await ac.post(url, json={"action": "run_shell", "parameters": {"program": "cmd"}})
+    assert resp.status_code == 503
+    assert other.status_code == 422
+
+
+@pytest.mark.asyncio
+async def test_diagnostic_bundle_requires_consent_and_collector():
+    url = "/api/agent/bridge/diagnostic-bundle"
+    async with AsyncClient(transport=ASGITransport(app=app), base_url="http://test") as ac:
+        resp = await ac.post(url, json={"include": ["logs"], "password": "correct horse"})
+        nothing = await ac.post(url, json={"include": [], "password": "correct horse"})
+        unknown = await ac.post(url, json={"include": ["history"], "password": "correct horse"})
+        weak = await ac.post(url, json={"include": ["logs"], "password": "short"})
+    assert resp.status_code == 503
+    assert nothing.status_code == 422
+    assert unknown.status_code == 422
+    assert weak.status_code == 422
+
+
 @pytest.mark.asyncio
 async def test_vision_agent_run():
     """Vision agent run endpoint returns a run object."""
@@ -167,3 +167,32 @@ def test_recent_switches_empty_when_no_events():
     store = StateStore(max_events=10)
     switches = asyncio.run(store.recent_switches(120))
     assert switches == []
+
+
+def test_observation_upgrades_current_without_switch():
+    store = StateStore(max_events=5)
+    now = datetime.now(timezone.utc)
+    light = WindowEvent(hwnd="0x5", title="Editor", process_exe="C:\\Code.exe", timestamp=now)
+    full = WindowEvent(
+        type="observation",
+        hwnd="0x5",
+        title="Editor",
+        process_exe="C:\\Code.exe",
+        timestamp=now + timedelta(milliseconds=400),
+        screenshot_b64="abc",
+    )
+    other = WindowEvent(type="observation", hwnd="0x9", title="Stale", timestamp=now)
+
+    asyncio.run(store.record(light))
+    asyncio.run(store.record(other))
+    current = asyncio.run(store.current())
+    assert current is not None and current.title == "Editor"
+    assert getattr(current, "screenshot_b64", None) is None
+
+    asyncio.run(store.record(full))
+    current = asyncio.run(store.current())
+    assert current is not None
+    assert current.type == "foreground"
+    assert current.screenshot_b64 == "abc"
+    assert len(store._fg_switches) == 1
+
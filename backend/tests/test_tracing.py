@@ -0,0 +1,22 @@
+"""Tests for the optional OpenTelemetry helpers."""
+
+from __future__ import annotations
+
+from app import tracing
+
+
+def test_format_traceparent():
+    assert (
+        tracing.format_traceparent(0x4BF92F3577B34DA6A3CE929D0E0E4736, 0x00F067AA0BA902B7, 1)
+        == "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    )
+    assert tracing.format_traceparent(0, 0x00F067AA0BA902B7, 1) is None
+    assert tracing.format_traceparent(1, 0, 0) is None
+
+
+def test_helpers_are_no_ops_while_tracing_is_off():
+    assert tracing.setup("", "desktopai-backend") is False
+    with tracing.span("test", **{"a.b": 1}) as span:
+        assert span is None
+        tracing.annotate(span, **{"a.b": 2})
+        assert tracing.traceparent() is None
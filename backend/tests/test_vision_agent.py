@@ -44,6 +44,19 @@ async def test_observe_returns_observation(agent, mock_bridge):
     assert obs.screenshot_b64 == "abc123"
 
 
+@pytest.mark.asyncio
+async def test_observe_uses_uia_outline(agent, mock_bridge):
+    mock_bridge.execute.return_value = {
+        "ok": True,
+        "result": {"window_title": "Outlook", "process_exe": "outlook.exe"},
+        "uia": {"focused_name": "Inbox", "window_tree": [], "outline": '[0] window "Inbox"'},
+    }
+
+    obs = await agent._observe()
+    assert obs.uia_summary == '[0] window "Inbox"'
+    assert obs.uia_elements == []
+
+
 @pytest.mark.asyncio
 async def test_reason_with_screenshot_calls_chat_with_images(agent, mock_bridge, mock_ollama):
     import base64
@@ -195,6 +208,29 @@ async def test_act_error_recorded_in_step(agent, mock_bridge, mock_ollama):
     assert "connection lost" in error_steps[0].error
 
 
+@pytest.mark.asyncio
+async def test_user_interrupt_stops_run(agent, mock_bridge, mock_ollama):
+    async def bridge_execute(action, params=None, timeout_s=None):
+        if action == "observe":
+            return {
+                "ok": True,
+                "result": {"window_title": "Test", "process_exe": "test.exe"},
+            }
+        return {
+            "ok": False,
+            "error": "UserInterrupted: type_text stopped by keyboard input from the user",
+            "error_code": "UserInterrupted",
+        }
+
+    mock_bridge.execute = bridge_execute
+    mock_ollama.chat.return_value = '{"action": "type_text", "parameters": {"text": "hi"}, "reasoning": "type"}'
+
+    steps = await agent.run("type hi")
+
+    assert len(steps) == 1
+    assert steps[0].result["error_code"] == "UserInterrupted"
+
+
 # ── Trajectory-informed planning tests ───────────────────────────────
 
 
@@ -0,0 +1,411 @@
+//! Download manager for model assets too large to ship in the installer.
+//!
+//! With `ASSET_MANIFEST_URL` set, a background worker fetches a JSON
+//! manifest listing the assets:
+//!
+//! ```json
+//! {"assets": [{"name": "ui-detr", "path": "ui-detr/ui-detr-1.onnx",
+//!              "url": "https://.../ui-detr-1.onnx", "sha256": "9f2c...", "bytes": 123456789}]}
+//! ```
+//!
+//! With `ASSET_SIGNING_KEY` (a base64 Ed25519 public key) the manifest must
+//! also carry a valid signature at `<manifest url>.sig` (base64), otherwise
+//! nothing is downloaded. Each asset lands under `DATA_DIR\models\<path>`,
+//! where retention never prunes it (see `datadir`) and where the detection
+//! model is looked for first (see `config`). A file already there with the
+//! right SHA-256 is kept; anything else is downloaded to a `.part` file,
+//! hashed on the way, and moved into place only if size and checksum match.
+//! Failed assets are retried with backoff.
+//!
+//! Progress goes to the backend as `asset_status` messages (see network.rs)
+//! whenever an asset's state or whole-percent progress changes.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Subdirectory of the data directory holding downloaded assets.
+const MODELS_DIR: &str = "models";
+/// First and longest wait before retrying failed downloads.
+const RETRY_MIN: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(1800);
+/// Without a size, progress is reported in steps of this many bytes.
+const PROGRESS_STEP_BYTES: u64 = 4 * 1024 * 1024;
+
+/// One asset in the manifest.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AssetSpec {
+    pub name: String,
+    /// Relative path below `models`, `/`-separated.
+    pub path: String,
+    pub url: String,
+    /// Lowercase hex SHA-256 of the file.
+    pub sha256: String,
+    #[serde(default)]
+    pub bytes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    assets: Vec<AssetSpec>,
+}
+
+/// Where an asset stands.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AssetState {
+    Pending,
+    Downloading {
+        received_bytes: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total_bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percent: Option<u8>,
+    },
+    Ready,
+    Failed {
+        error: String,
+    },
+}
+
+/// An asset's state, as reported to the backend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssetStatus {
+    pub name: String,
+    #[serde(flatten)]
+    pub state: AssetState,
+}
+
+static STATUS: Mutex<Vec<AssetStatus>> = Mutex::new(Vec::new());
+
+/// Whether the download manager runs.
+pub fn enabled(config: &Config) -> bool {
+    !config.asset_manifest_url.is_empty() && !config.data_dir.is_empty()
+}
+
+/// The state of every asset in the manifest; empty before it is read.
+pub fn report() -> Vec<AssetStatus> {
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn set_state(name: &str, state: AssetState) {
+    let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    match status.iter_mut().find(|s| s.name == name) {
+        Some(entry) => entry.state = state,
+        None => status.push(AssetStatus { name: name.to_string(), state }),
+    }
+}
+
+/// Parse the manifest, checking its signature against `signing_key` when
+/// one is configured.
+pub fn parse_manifest(body: &[u8], signature: Option<&str>, signing_key: &str) -> Result<Vec<AssetSpec>, String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    if !signing_key.is_empty() {
+        let key = STANDARD.decode(signing_key.trim()).map_err(|e| format!("invalid ASSET_SIGNING_KEY: {e}"))?;
+        let signature = signature.ok_or("manifest is not signed")?;
+        let signature = STANDARD.decode(signature.trim()).map_err(|e| format!("invalid manifest signature: {e}"))?;
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+            .verify(body, &signature)
+            .map_err(|_| "manifest signature does not verify".to_string())?;
+    }
+    let manifest: Manifest = serde_json::from_slice(body).map_err(|e| format!("invalid manifest: {e}"))?;
+    for asset in &manifest.assets {
+        if relative_path(&asset.path).is_none() {
+            return Err(format!("asset '{}' has an unsafe path '{}'", asset.name, asset.path));
+        }
+        if asset.sha256.len() != 64 || !asset.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("asset '{}' needs a hex sha256", asset.name));
+        }
+    }
+    Ok(manifest.assets)
+}
+
+/// `path` as a relative path that stays below its root, if it is one.
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = path.split('/').collect();
+    let safe = |part: &&str| !part.is_empty() && *part != "." && *part != ".." && !part.contains(['\\', ':']);
+    parts.iter().all(safe).then(|| parts.iter().collect())
+}
+
+/// Where `asset` is stored below `data_dir`.
+pub fn asset_path(data_dir: &str, asset: &AssetSpec) -> Option<PathBuf> {
+    relative_path(&asset.path).map(|rel| Path::new(data_dir).join(MODELS_DIR).join(rel))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Lowercase hex SHA-256 of the file at `path`.
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hex(context.finish().as_ref()));
+        }
+        context.update(&buf[..n]);
+    }
+}
+
+/// Copy `body` to `dest` through a `.part` file, hashing as it goes, and
+/// move it into place only if size and checksum match `asset`. `progress`
+/// is called with the bytes received so far.
+pub fn store(mut body: impl Read, dest: &Path, asset: &AssetSpec, mut progress: impl FnMut(u64)) -> Result<(), String> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
+    }
+    let mut part_name = dest.as_os_str().to_owned();
+    part_name.push(".part");
+    let part = PathBuf::from(part_name);
+    let result = (|| {
+        let mut file = fs::File::create(&part).map_err(|e| format!("cannot write {}: {e}", part.display()))?;
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut received = 0u64;
+        loop {
+            let n = body.read(&mut buf).map_err(|e| format!("download interrupted: {e}"))?;
+            if n == 0 {
+                break;
+            }
+            context.update(&buf[..n]);
+            file.write_all(&buf[..n]).map_err(|e| format!("cannot write {}: {e}", part.display()))?;
+            received += n as u64;
+            if asset.bytes.is_some_and(|total| received > total) {
+                return Err(format!("larger than the {} bytes announced", asset.bytes.unwrap_or_default()));
+            }
+            progress(received);
+        }
+        file.sync_all().map_err(|e| format!("cannot write {}: {e}", part.display()))?;
+        if asset.bytes.is_some_and(|total| received != total) {
+            return Err(format!("got {received} of {} bytes", asset.bytes.unwrap_or_default()));
+        }
+        let digest = hex(context.finish().as_ref());
+        if !digest.eq_ignore_ascii_case(&asset.sha256) {
+            return Err(format!("checksum mismatch: expected {}, got {digest}", asset.sha256));
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => fs::rename(&part, dest).map_err(|e| format!("cannot move into {}: {e}", dest.display())),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
+/// Progress for `received` of `total` bytes, if it moved on from `last`.
+fn progress_step(last: Option<&AssetState>, received: u64, total: Option<u64>) -> Option<AssetState> {
+    let percent = total.filter(|t| *t > 0).map(|t| (received.saturating_mul(100) / t).min(100) as u8);
+    let moved = match last {
+        Some(AssetState::Downloading { received_bytes, percent: last_percent, .. }) => match percent {
+            Some(_) => percent != *last_percent,
+            None => received / PROGRESS_STEP_BYTES != received_bytes / PROGRESS_STEP_BYTES,
+        },
+        _ => true,
+    };
+    moved.then_some(AssetState::Downloading { received_bytes: received, total_bytes: total, percent })
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(15))
+        .timeout_read(Duration::from_secs(60))
+        .build()
+}
+
+fn fetch_manifest(config: &Config) -> Result<Vec<AssetSpec>, String> {
+    let agent = agent();
+    let url = &config.asset_manifest_url;
+    let body = agent
+        .get(url)
+        .call()
+        .map_err(|e| format!("manifest download failed: {e}"))?
+        .into_string()
+        .map_err(|e| format!("manifest download failed: {e}"))?;
+    let signature = if config.asset_signing_key.is_empty() {
+        None
+    } else {
+        let sig_url = format!("{url}.sig");
+        agent.get(&sig_url).call().ok().and_then(|r| r.into_string().ok())
+    };
+    parse_manifest(body.as_bytes(), signature.as_deref(), &config.asset_signing_key)
+}
+
+/// Make sure `asset` is on disk and intact, downloading it if needed.
+fn ensure(config: &Config, asset: &AssetSpec) -> Result<(), String> {
+    let dest = asset_path(&config.data_dir, asset).ok_or("unsafe path")?;
+    if dest.exists() {
+        match sha256_file(&dest) {
+            Ok(digest) if digest.eq_ignore_ascii_case(&asset.sha256) => return Ok(()),
+            _ => log::info!("Asset {} at {} is outdated or damaged; downloading again", asset.name, dest.display()),
+        }
+    }
+    let response = agent().get(&asset.url).call().map_err(|e| format!("download failed: {e}"))?;
+    let total = asset.bytes.or_else(|| response.header("Content-Length").and_then(|v| v.parse().ok()));
+    let mut last: Option<AssetState> = None;
+    store(response.into_reader(), &dest, asset, |received| {
+        if let Some(state) = progress_step(last.as_ref(), received, total) {
+            set_state(&asset.name, state.clone());
+            last = Some(state);
+        }
+    })
+}
+
+/// Background worker: read the manifest, then fetch what is missing,
+/// retrying failures with backoff until every asset is ready.
+pub fn asset_worker(config: Config) {
+    let mut wait = RETRY_MIN;
+    let mut assets = None;
+    loop {
+        if assets.is_none() {
+            match fetch_manifest(&config) {
+                Ok(list) => {
+                    for asset in &list {
+                        set_state(&asset.name, AssetState::Pending);
+                    }
+                    assets = Some(list);
+                }
+                Err(e) => log::warn!("Asset manifest unavailable: {e}"),
+            }
+        }
+        if let Some(list) = &assets {
+            let mut all_ready = true;
+            for asset in list {
+                if report().iter().any(|s| s.name == asset.name && s.state == AssetState::Ready) {
+                    continue;
+                }
+                match ensure(&config, asset) {
+                    Ok(()) => {
+                        log::info!("Asset {} ready", asset.name);
+                        set_state(&asset.name, AssetState::Ready);
+                    }
+                    Err(error) => {
+                        log::warn!("Asset {} failed: {error}", asset.name);
+                        set_state(&asset.name, AssetState::Failed { error });
+                        all_ready = false;
+                    }
+                }
+            }
+            if all_ready {
+                return;
+            }
+        }
+        std::thread::sleep(wait);
+        wait = (wait * 2).min(RETRY_MAX);
+    }
+}
+
+/// Start the download manager if configured.
+pub fn start(config: &Config) {
+    if enabled(config) {
+        let config = config.clone();
+        std::thread::spawn(move || asset_worker(config));
+    }
+}
+
+/// The `asset_status` message for the backend.
+pub fn build_status_message(assets: &[AssetStatus]) -> String {
+    serde_json::json!({ "type": "asset_status", "assets": assets }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn digest(data: &[u8]) -> String {
+        hex(ring::digest::digest(&ring::digest::SHA256, data).as_ref())
+    }
+
+    fn spec(path: &str, data: &[u8]) -> AssetSpec {
+        AssetSpec {
+            name: "model".to_string(),
+            path: path.to_string(),
+            url: "https://example.com/model.onnx".to_string(),
+            sha256: digest(data),
+            bytes: Some(data.len() as u64),
+        }
+    }
+
+    #[test]
+    fn test_manifest_paths_checksums_and_signature() {
+        let body = format!(
+            r#"{{"assets": [{{"name": "ui-detr", "path": "ui-detr/ui-detr-1.onnx", "url": "https://x/m.onnx", "sha256": "{}"}}]}}"#,
+            digest(b"model")
+        );
+        let assets = parse_manifest(body.as_bytes(), None, "").unwrap();
+        assert_eq!(assets[0].path, "ui-detr/ui-detr-1.onnx");
+        assert_eq!(assets[0].bytes, None);
+
+        for path in ["../evil.onnx", "/abs.onnx", "C:/x.onnx", "a\\..\\b", "a//b", ""] {
+            let bad = body.replace("ui-detr/ui-detr-1.onnx", path);
+            assert!(parse_manifest(bad.as_bytes(), None, "").is_err(), "{path}");
+        }
+        let bad = body.replace(&digest(b"model"), "abc");
+        assert!(parse_manifest(bad.as_bytes(), None, "").unwrap_err().contains("sha256"));
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = STANDARD.encode(pair.public_key().as_ref());
+        let signature = STANDARD.encode(pair.sign(body.as_bytes()).as_ref());
+        assert!(parse_manifest(body.as_bytes(), Some(&signature), &key).is_ok());
+        assert_eq!(parse_manifest(body.as_bytes(), None, &key).unwrap_err(), "manifest is not signed");
+        let tampered = body.replace("https://x", "https://evil");
+        assert!(parse_manifest(tampered.as_bytes(), Some(&signature), &key).unwrap_err().contains("does not verify"));
+    }
+
+    #[test]
+    fn test_store_verifies_before_moving_into_place() {
+        let dir = std::env::temp_dir().join(format!("desktopai-assets-{}", uuid::Uuid::new_v4()));
+        let data = vec![7u8; 200_000];
+        let asset = spec("ui-detr/model.onnx", &data);
+        let dest = asset_path(dir.to_str().unwrap(), &asset).unwrap();
+        assert!(dest.ends_with("models/ui-detr/model.onnx"));
+
+        let mut seen = Vec::new();
+        store(&data[..], &dest, &asset, |n| seen.push(n)).unwrap();
+        assert_eq!(sha256_file(&dest).unwrap(), asset.sha256);
+        assert_eq!(seen.last(), Some(&200_000));
+
+        let corrupt = vec![8u8; 200_000];
+        let err = store(&corrupt[..], &dest, &asset, |_| {}).unwrap_err();
+        assert!(err.contains("checksum mismatch"), "{err}");
+        assert!(store(&data[..100], &dest, &asset, |_| {}).unwrap_err().contains("got 100 of 200000"));
+        assert_eq!(sha256_file(&dest).unwrap(), asset.sha256, "a failed download leaves the good copy");
+        assert!(!dest.with_extension("onnx.part").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_progress_reported_in_steps() {
+        let first = progress_step(None, 10, Some(1000)).unwrap();
+        assert_eq!(first, AssetState::Downloading { received_bytes: 10, total_bytes: Some(1000), percent: Some(1) });
+        assert!(progress_step(Some(&first), 19, Some(1000)).is_none());
+        assert!(progress_step(Some(&first), 20, Some(1000)).is_some());
+
+        let no_size = progress_step(None, 1, None).unwrap();
+        assert!(progress_step(Some(&no_size), PROGRESS_STEP_BYTES - 1, None).is_none());
+        assert!(progress_step(Some(&no_size), PROGRESS_STEP_BYTES, None).is_some());
+
+        let status = AssetStatus { name: "ui-detr".to_string(), state: first };
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({"name": "ui-detr", "state": "downloading", "received_bytes": 10, "total_bytes": 1000, "percent": 1})
+        );
+        assert_eq!(
+            serde_json::to_value(AssetStatus { name: "x".into(), state: AssetState::Failed { error: "e".into() } }).unwrap(),
+            serde_json::json!({"name": "x", "state": "failed", "error": "e"})
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Synthetic Win32 window for the collector's Windows integration tests.
+//!
+//! Shows one of each control the command bridge automates, all with fixed
+//! control ids (which UIA exposes as automation ids, see `tests/windows_harness.rs`):
+//! a push button, an edit field, a check box, a list box, a drop-down combo
+//! box, a button opening a modal dialog and a scrolled multi-line edit.
+//!
+//! Every state change is printed to stdout as one `key=value` line, found by
+//! polling the controls, so tests can verify an action's effect without
+//! trusting the collector's own report of it. The first line is
+//! `hwnd=0x...`. The window title is the first argument, if given.
+
+#[cfg(windows)]
+mod harness {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::io::Write;
+
+    use windows::core::{w, PCWSTR};
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::Controls::EM_GETFIRSTVISIBLELINE;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    pub const SUBMIT_ID: i32 = 41001;
+    pub const NAME_ID: i32 = 41002;
+    pub const TOGGLE_ID: i32 = 41003;
+    pub const LIST_ID: i32 = 41004;
+    pub const COMBO_ID: i32 = 41005;
+    pub const DIALOG_ID: i32 = 41006;
+    pub const LOG_ID: i32 = 41007;
+
+    const LIST_ITEMS: &[&str] = &["Alpha", "Beta", "Gamma"];
+    const COMBO_ITEMS: &[&str] = &["Red", "Green", "Blue"];
+    const POLL_TIMER: usize = 1;
+    const POLL_MS: u32 = 50;
+
+    thread_local! {
+        static CLICKS: RefCell<u32> = const { RefCell::new(0) };
+        static DIALOG_OPEN: RefCell<bool> = const { RefCell::new(false) };
+        static REPORTED: RefCell<BTreeMap<&'static str, String>> = const { RefCell::new(BTreeMap::new()) };
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn send(hwnd: HWND, id: i32, msg: u32, wparam: usize, lparam: isize) -> isize {
+        unsafe { SendMessageW(GetDlgItem(hwnd, id), msg, WPARAM(wparam), LPARAM(lparam)) }.0
+    }
+
+    fn control_text(hwnd: HWND, id: i32) -> String {
+        let mut buffer = [0u16; 512];
+        let len = unsafe { GetDlgItemTextW(hwnd, id, &mut buffer) } as usize;
+        String::from_utf16_lossy(&buffer[..len])
+    }
+
+    /// Text of the selected entry of a list or combo box; empty for none.
+    fn selected(hwnd: HWND, id: i32, items: &[&str], get_selection: u32) -> String {
+        let index = send(hwnd, id, get_selection, 0, 0);
+        usize::try_from(index).ok().and_then(|i| items.get(i)).map(|s| s.to_string()).unwrap_or_default()
+    }
+
+    /// Print every piece of observable state that changed since last time.
+    fn report(hwnd: HWND) {
+        let state = [
+            ("clicks", CLICKS.with(|c| c.borrow().to_string())),
+            ("name", control_text(hwnd, NAME_ID)),
+            ("toggle", send(hwnd, TOGGLE_ID, BM_GETCHECK, 0, 0).to_string()),
+            ("list", selected(hwnd, LIST_ID, LIST_ITEMS, LB_GETCURSEL)),
+            ("combo", selected(hwnd, COMBO_ID, COMBO_ITEMS, CB_GETCURSEL)),
+            ("combo_open", send(hwnd, COMBO_ID, CB_GETDROPPEDSTATE, 0, 0).to_string()),
+            ("dialog", DIALOG_OPEN.with(|d| if *d.borrow() { "open" } else { "closed" }).to_string()),
+            ("log_line", send(hwnd, LOG_ID, EM_GETFIRSTVISIBLELINE, 0, 0).to_string()),
+        ];
+        let mut out = std::io::stdout().lock();
+        REPORTED.with(|reported| {
+            let mut reported = reported.borrow_mut();
+            for (key, value) in state {
+                if reported.get(key) != Some(&value) {
+                    let _ = writeln!(out, "{key}={value}");
+                    reported.insert(key, value);
+                }
+            }
+        });
+        let _ = out.flush();
+    }
+
+    unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match msg {
+            WM_COMMAND => {
+                let (id, code) = ((wparam.0 & 0xFFFF) as i32, ((wparam.0 >> 16) & 0xFFFF) as u32);
+                if code == BN_CLICKED && id == SUBMIT_ID {
+                    CLICKS.with(|c| *c.borrow_mut() += 1);
+                } else if code == BN_CLICKED && id == DIALOG_ID {
+                    DIALOG_OPEN.with(|d| *d.borrow_mut() = true);
+                    report(hwnd);
+                    // Modal: its loop keeps dispatching our poll timer
+                    MessageBoxW(hwnd, w!("Harness dialog body"), w!("Harness Dialog"), MB_OK);
+                    DIALOG_OPEN.with(|d| *d.borrow_mut() = false);
+                }
+                LRESULT(0)
+            }
+            WM_TIMER => {
+                report(hwnd);
+                LRESULT(0)
+            }
+            WM_DESTROY => {
+                PostQuitMessage(0);
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    pub fn run(title: &str) {
+        unsafe {
+            let instance = GetModuleHandleW(None).unwrap_or_default();
+            let class = w!("DesktopAITestWindow");
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(wndproc),
+                hInstance: instance.into(),
+                lpszClassName: class,
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                ..Default::default()
+            };
+            RegisterClassW(&wc);
+            let title = wide(title);
+            let hwnd = CreateWindowExW(
+                WS_EX_TOPMOST,
+                class,
+                PCWSTR(title.as_ptr()),
+                WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+                80,
+                80,
+                520,
+                480,
+                None,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 == 0 {
+                eprintln!("CreateWindowExW failed");
+                std::process::exit(1);
+            }
+
+            let child = |class: PCWSTR, text: &str, style: i32, id: i32, rect: [i32; 4]| {
+                let text = wide(text);
+                CreateWindowExW(
+                    WINDOW_EX_STYLE(0),
+                    class,
+                    PCWSTR(text.as_ptr()),
+                    WS_CHILD | WS_VISIBLE | WS_TABSTOP | WINDOW_STYLE(style as u32),
+                    rect[0],
+                    rect[1],
+                    rect[2],
+                    rect[3],
+                    hwnd,
+                    HMENU(id as isize),
+                    instance,
+                    None,
+                )
+            };
+            child(w!("BUTTON"), "Submit", BS_PUSHBUTTON, SUBMIT_ID, [16, 16, 120, 30]);
+            child(w!("BUTTON"), "Open dialog", BS_PUSHBUTTON, DIALOG_ID, [150, 16, 120, 30]);
+            child(w!("EDIT"), "", WS_BORDER.0 as i32 | ES_AUTOHSCROLL, NAME_ID, [16, 60, 470, 26]);
+            child(w!("BUTTON"), "Enable feature", BS_AUTOCHECKBOX, TOGGLE_ID, [16, 96, 200, 24]);
+            let list = child(w!("LISTBOX"), "", WS_BORDER.0 as i32 | LBS_NOTIFY, LIST_ID, [16, 130, 150, 80]);
+            for item in LIST_ITEMS {
+                let item = wide(item);
+                SendMessageW(list, LB_ADDSTRING, WPARAM(0), LPARAM(item.as_ptr() as isize));
+            }
+            let combo = child(w!("COMBOBOX"), "", CBS_DROPDOWNLIST, COMBO_ID, [190, 130, 150, 120]);
+            for item in COMBO_ITEMS {
+                let item = wide(item);
+                SendMessageW(combo, CB_ADDSTRING, WPARAM(0), LPARAM(item.as_ptr() as isize));
+            }
+            let log: Vec<String> = (1..=200).map(|n| format!("Line {n}")).collect();
+            child(
+                w!("EDIT"),
+                &log.join("\r\n"),
+                (WS_BORDER | WS_VSCROLL).0 as i32 | ES_MULTILINE | ES_AUTOVSCROLL | ES_READONLY,
+                LOG_ID,
+                [16, 260, 470, 160],
+            );
+
+            println!("hwnd=0x{:x}", hwnd.0);
+            report(hwnd);
+            SetTimer(hwnd, POLL_TIMER, POLL_MS, None);
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn main() {
+    let title = std::env::args().nth(1).unwrap_or_else(|| "DesktopAI Test Window".to_string());
+    harness::run(&title);
+}
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("desktopai-test-window requires Windows");
+    std::process::exit(1);
+}
@@ -0,0 +1,162 @@
+//! Command cancellation.
+//!
+//! The backend aborts an in-flight command with
+//! `{"type": "cancel", "command_id": ...}`. Every executing command is
+//! registered here under its id with a `CancelToken`; cancelling sets the
+//! token. Handlers that wait or loop (menu and search waits, process
+//! shutdown, gestures and drags, `run_shell`) check `cancelled()` between
+//! steps, undo what is half done (release buttons, lift contacts, kill the
+//! child) and answer with a `Cancelled` failure. Handlers made of a single
+//! blocking call simply finish.
+//!
+//! The token of the command running on the current thread is found through
+//! a thread-local, so handlers need no extra parameter.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Longest a cancellable sleep goes without checking its token.
+const SLICE: Duration = Duration::from_millis(20);
+
+/// Shared flag set when a command is cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Sleep for `duration`, waking early if cancelled. Returns false when
+    /// cancelled.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let end = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let left = end.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return true;
+            }
+            std::thread::sleep(left.min(SLICE));
+        }
+    }
+
+    fn same(&self, other: &CancelToken) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+static RUNNING: Mutex<Option<HashMap<String, CancelToken>>> = Mutex::new(None);
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Keeps a command registered while it executes on this thread.
+pub struct Registration {
+    command_id: String,
+    token: CancelToken,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(map) = running.as_mut() {
+            // A newer command may have reused the id; leave its token alone
+            if map.get(&self.command_id).is_some_and(|t| self.token.same(t)) {
+                map.remove(&self.command_id);
+            }
+        }
+        CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            if current.as_ref().is_some_and(|t| self.token.same(t)) {
+                *current = None;
+            }
+        });
+    }
+}
+
+/// Register `command_id` as executing on this thread until the returned
+/// guard is dropped.
+pub fn register(command_id: &str) -> Registration {
+    let token = CancelToken::default();
+    RUNNING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(command_id.to_string(), token.clone());
+    CURRENT.with(|current| *current.borrow_mut() = Some(token.clone()));
+    Registration { command_id: command_id.to_string(), token }
+}
+
+/// Cancel the executing command with this id. Returns false if no such
+/// command is executing.
+pub fn cancel(command_id: &str) -> bool {
+    let running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    match running.as_ref().and_then(|map| map.get(command_id)) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Token of the command executing on this thread; a token nobody can
+/// cancel outside of a command.
+pub fn current() -> CancelToken {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_default()
+}
+
+/// Whether the command executing on this thread has been cancelled.
+pub fn cancelled() -> bool {
+    CURRENT.with(|current| current.borrow().as_ref().is_some_and(CancelToken::is_cancelled))
+}
+
+/// Sleep within the current command; false if it was cancelled.
+pub fn pause(duration: Duration) -> bool {
+    current().sleep(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_reaches_the_running_command_only() {
+        assert!(!cancel("cancel-test-missing"));
+        let registration = register("cancel-test-a");
+        assert!(!cancelled());
+
+        let from_backend = std::thread::spawn(|| cancel("cancel-test-a"));
+        assert!(from_backend.join().unwrap());
+        assert!(cancelled());
+        let started = Instant::now();
+        assert!(!pause(Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        drop(registration);
+        assert!(!cancelled());
+        assert!(!cancel("cancel-test-a"), "finished commands are unregistered");
+        assert!(pause(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_reused_id_keeps_the_newer_token() {
+        let older = std::thread::spawn(|| register("cancel-test-b")).join().unwrap();
+        let newer = register("cancel-test-b");
+        drop(older);
+        assert!(cancel("cancel-test-b"));
+        assert!(cancelled());
+        drop(newer);
+    }
+}
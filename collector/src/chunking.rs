@@ -0,0 +1,198 @@
+//! Application-level chunking of large WebSocket payloads.
+//!
+//! A full-resolution screenshot or a deep UIA tree can run to megabytes;
+//! sent as one frame it holds the socket until it is through, and pings,
+//! command results and small events queue behind it. Payloads over
+//! `WS_CHUNK_BYTES` are split into `chunk` frames instead:
+//!
+//! ```json
+//! {"type": "chunk", "payload_id": "...", "index": 0, "count": 3,
+//!  "size": 1048576, "checksum": "1c291ca3", "data": "{\"type\":\"foreground\",..."}
+//! ```
+//!
+//! `data` is a slice of the payload's JSON text, cut on a character
+//! boundary; `size` is the payload's length in bytes and `checksum` its
+//! CRC-32 in hex. The network loop sends one part per turn, so other
+//! messages go out between parts and may overtake a large payload. The
+//! backend joins the parts in index order, checks size and checksum, and
+//! handles the result like a message that arrived whole. While
+//! `MAX_PENDING` payloads are queued a further large event is dropped, but a
+//! command result is replaced by a small failure sent whole, so the backend
+//! never waits on a result that will not come.
+
+use std::collections::VecDeque;
+
+use crate::event::WindowEvent;
+
+/// Large payloads waiting at most; `Outbox::push` refuses more.
+const MAX_PENDING: usize = 16;
+
+/// Split `payload` into chunk frames of at most `chunk_bytes` of payload
+/// text each.
+pub fn split(payload: &str, chunk_bytes: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let mut end = chunk_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // A chunk size below one character still has to make progress
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (part, tail) = rest.split_at(end);
+        parts.push(part);
+        rest = tail;
+    }
+
+    let payload_id = uuid::Uuid::new_v4().simple().to_string();
+    let checksum = format!("{:08x}", crc32fast::hash(payload.as_bytes()));
+    let count = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            serde_json::json!({
+                "type": "chunk",
+                "payload_id": payload_id,
+                "index": index,
+                "count": count,
+                "size": payload.len(),
+                "checksum": checksum,
+                "data": data,
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+/// A large payload on its way out.
+struct Pending {
+    frames: VecDeque<String>,
+    /// The event it carries, re-sent over HTTP if the socket goes away.
+    event: Option<WindowEvent>,
+}
+
+/// Large payloads waiting to go out a part at a time.
+pub struct Outbox {
+    chunk_bytes: usize,
+    pending: VecDeque<Pending>,
+}
+
+impl Outbox {
+    /// `chunk_bytes` of zero sends every payload whole.
+    pub fn new(chunk_bytes: usize) -> Self {
+        Self { chunk_bytes, pending: VecDeque::new() }
+    }
+
+    /// Whether `payload` is too large for a single frame.
+    pub fn too_large(&self, payload: &str) -> bool {
+        self.chunk_bytes > 0 && payload.len() > self.chunk_bytes
+    }
+
+    /// Queue `payload` to go out in parts; `event` is what it carries, if an
+    /// event. False, with nothing queued, when `MAX_PENDING` payloads are
+    /// already waiting: the caller decides what to send instead.
+    #[must_use]
+    pub fn push(&mut self, payload: &str, event: Option<WindowEvent>) -> bool {
+        if self.pending.len() >= MAX_PENDING {
+            return false;
+        }
+        self.pending.push_back(Pending { frames: split(payload, self.chunk_bytes).into(), event });
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The next part to send.
+    pub fn front(&self) -> Option<&str> {
+        self.pending.front().and_then(|p| p.frames.front()).map(String::as_str)
+    }
+
+    /// Mark the part returned by `front` as sent.
+    pub fn advance(&mut self) {
+        if let Some(pending) = self.pending.front_mut() {
+            pending.frames.pop_front();
+            if pending.frames.is_empty() {
+                self.pending.pop_front();
+            }
+        }
+    }
+
+    /// Give up on everything queued (the socket is gone), returning the
+    /// events so they can go another way. The backend drops the parts it
+    /// already has once they go stale.
+    pub fn abandon(&mut self) -> Vec<WindowEvent> {
+        self.pending.drain(..).filter_map(|p| p.event).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn parse(frames: &[String]) -> Vec<Value> {
+        frames.iter().map(|f| serde_json::from_str(f).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_split_reassembles_with_metadata() {
+        let payload = format!("{{\"title\":\"{}\"}}", "é€x".repeat(100));
+        let frames = parse(&split(&payload, 64));
+        assert!(frames.len() > 1);
+        let count = frames.len();
+        let mut joined = String::new();
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(frame["type"], "chunk");
+            assert_eq!(frame["index"], i);
+            assert_eq!(frame["count"], count);
+            assert_eq!(frame["payload_id"], frames[0]["payload_id"]);
+            assert_eq!(frame["size"], payload.len());
+            let data = frame["data"].as_str().unwrap();
+            assert!(data.len() <= 64);
+            joined.push_str(data);
+        }
+        assert_eq!(joined, payload);
+        assert_eq!(frames[0]["checksum"], format!("{:08x}", crc32fast::hash(payload.as_bytes())));
+
+        // Each payload gets its own id
+        assert_ne!(parse(&split(&payload, 64))[0]["payload_id"], frames[0]["payload_id"]);
+        // Narrower than a character still terminates
+        assert_eq!(split("€€", 1).len(), 2);
+    }
+
+    #[test]
+    fn test_outbox_sends_parts_in_order_and_abandons_to_fallback() {
+        let mut outbox = Outbox::new(8);
+        assert!(!outbox.too_large("12345678"));
+        assert!(outbox.too_large("123456789"));
+        assert!(!Outbox::new(0).too_large(&"x".repeat(1 << 20)), "zero disables chunking");
+
+        assert!(outbox.push("first payload", None));
+        assert!(outbox.push("second payload", Some(crate::event::build_activity_event("foreground", 0))));
+        let mut sent = Vec::new();
+        while let Some(frame) = outbox.front() {
+            sent.push(frame.to_string());
+            outbox.advance();
+            if sent.len() == 3 {
+                break;
+            }
+        }
+        let frames = parse(&sent);
+        assert_eq!(frames[0]["data"], "first pa");
+        assert_eq!(frames[1]["data"], "yload");
+        assert_eq!(frames[2]["data"], "second p");
+        assert_eq!(outbox.abandon().len(), 1, "the event half way out goes another way");
+        assert!(outbox.is_empty());
+
+        for _ in 0..MAX_PENDING {
+            assert!(outbox.push("a large payload", None));
+        }
+        assert!(!outbox.push("one too many", None), "a full outbox refuses, so the caller can send something else");
+        assert_eq!(outbox.pending.len(), MAX_PENDING);
+    }
+}
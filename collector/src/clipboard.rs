@@ -0,0 +1,242 @@
+//! Clipboard access for `paste_text` and for workflow inputs read from the
+//! clipboard (see workflow.rs).
+//!
+//! Pasting puts the text on the clipboard, so the user's clipboard is
+//! snapshotted first and put back afterwards. Only formats whose data is a
+//! global memory block can be copied out; bitmaps, palettes, metafiles and
+//! private handles are left behind (a DIB copy of a bitmap usually survives
+//! and the system synthesizes the bitmap from it again).
+//!
+//! Text the collector places is flagged so clipboard history and cloud
+//! clipboard do not keep it.
+
+/// Highest format id whose handle is a plain global memory block.
+const CF_MAX_STANDARD: u32 = 0x11;
+/// Standard formats holding GDI handles instead of memory blocks:
+/// CF_BITMAP, CF_METAFILEPICT, CF_PALETTE and CF_ENHMETAFILE.
+const GDI_FORMATS: [u32; 4] = [2, 3, 9, 14];
+/// Registered (named) formats start here; their data is global memory.
+const CF_REGISTERED_FIRST: u32 = 0xC000;
+
+/// Whether data in `format` is global memory the snapshot can copy.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn restorable(format: u32) -> bool {
+    match format {
+        1..=CF_MAX_STANDARD => !GDI_FORMATS.contains(&format),
+        _ => format >= CF_REGISTERED_FIRST,
+    }
+}
+
+/// Clipboard contents copied out by `snapshot`.
+#[cfg(windows)]
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    formats: Vec<(u32, Vec<u8>)>,
+    /// Formats present that could not be copied.
+    pub skipped: usize,
+}
+
+#[cfg(windows)]
+impl Snapshot {
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+}
+
+/// Attempts and spacing when another process holds the clipboard open.
+#[cfg(windows)]
+const OPEN_ATTEMPTS: u32 = 10;
+#[cfg(windows)]
+const OPEN_RETRY: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// The clipboard, open until dropped.
+#[cfg(windows)]
+struct Open;
+
+#[cfg(windows)]
+impl Open {
+    fn new() -> Result<Self, String> {
+        use windows::Win32::System::DataExchange::OpenClipboard;
+
+        let mut last = None;
+        for _ in 0..OPEN_ATTEMPTS {
+            match unsafe { OpenClipboard(None) } {
+                Ok(()) => return Ok(Self),
+                Err(e) => last = Some(e),
+            }
+            std::thread::sleep(OPEN_RETRY);
+        }
+        Err(format!("cannot open the clipboard: {}", last.map(|e| e.to_string()).unwrap_or_default()))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Open {
+    fn drop(&mut self) {
+        let _ = unsafe { windows::Win32::System::DataExchange::CloseClipboard() };
+    }
+}
+
+/// Copy of a global memory block.
+#[cfg(windows)]
+fn read_global(handle: windows::Win32::Foundation::HANDLE) -> Option<Vec<u8>> {
+    use windows::Win32::Foundation::HGLOBAL;
+    use windows::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+
+    let memory = HGLOBAL(handle.0 as *mut std::ffi::c_void);
+    unsafe {
+        let size = GlobalSize(memory);
+        let data = GlobalLock(memory) as *const u8;
+        if data.is_null() {
+            return None;
+        }
+        let bytes = std::slice::from_raw_parts(data, size).to_vec();
+        let _ = GlobalUnlock(memory);
+        Some(bytes)
+    }
+}
+
+/// Hand `bytes` to the open clipboard as `format`; the clipboard owns the
+/// memory once this succeeds.
+#[cfg(windows)]
+fn write_global(format: u32, bytes: &[u8]) -> Result<(), String> {
+    use windows::Win32::Foundation::{GlobalFree, HANDLE};
+    use windows::Win32::System::DataExchange::SetClipboardData;
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+
+    unsafe {
+        let memory = GlobalAlloc(GMEM_MOVEABLE, bytes.len().max(1)).map_err(|e| format!("GlobalAlloc failed: {e}"))?;
+        let data = GlobalLock(memory) as *mut u8;
+        if data.is_null() {
+            let _ = GlobalFree(memory);
+            return Err("GlobalLock failed".to_string());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+        let _ = GlobalUnlock(memory);
+        if let Err(e) = SetClipboardData(format, HANDLE(memory.0 as isize)) {
+            let _ = GlobalFree(memory);
+            return Err(format!("SetClipboardData failed: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// Copy every restorable format currently on the clipboard.
+#[cfg(windows)]
+pub fn snapshot() -> Result<Snapshot, String> {
+    use windows::Win32::System::DataExchange::{EnumClipboardFormats, GetClipboardData};
+
+    let _open = Open::new()?;
+    let mut snapshot = Snapshot::default();
+    let mut format = 0;
+    loop {
+        format = unsafe { EnumClipboardFormats(format) };
+        if format == 0 {
+            break;
+        }
+        let data = restorable(format)
+            .then(|| unsafe { GetClipboardData(format) }.ok())
+            .flatten()
+            .and_then(read_global);
+        match data {
+            Some(bytes) => snapshot.formats.push((format, bytes)),
+            None => snapshot.skipped += 1,
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Replace the clipboard with `text`, kept out of clipboard history.
+/// Returns the clipboard sequence number after the change.
+#[cfg(windows)]
+pub fn set_text(text: &str) -> Result<u32, String> {
+    use windows::core::w;
+    use windows::Win32::System::DataExchange::{EmptyClipboard, GetClipboardSequenceNumber, RegisterClipboardFormatW};
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+    let wide: Vec<u16> = text.encode_utf16().chain(Some(0)).collect();
+    let bytes: Vec<u8> = wide.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+    let open = Open::new()?;
+    unsafe { EmptyClipboard() }.map_err(|e| format!("EmptyClipboard failed: {e}"))?;
+    write_global(u32::from(CF_UNICODETEXT.0), &bytes)?;
+    for flag in [w!("ExcludeClipboardContentFromMonitorProcessing"), w!("CanIncludeInClipboardHistory"), w!("CanUploadToCloudClipboard")] {
+        let format = unsafe { RegisterClipboardFormatW(flag) };
+        if format != 0 {
+            // A zero DWORD for the two "Can..." formats means no
+            let _ = write_global(format, &0u32.to_le_bytes());
+        }
+    }
+    drop(open);
+    Ok(unsafe { GetClipboardSequenceNumber() })
+}
+
+/// Text of a CF_UNICODETEXT block: UTF-16LE up to the first NUL.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn decode_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// The text on the clipboard, `None` when it holds no text.
+#[cfg(windows)]
+pub fn get_text() -> Result<Option<String>, String> {
+    use windows::Win32::System::DataExchange::{GetClipboardData, IsClipboardFormatAvailable};
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+    let format = u32::from(CF_UNICODETEXT.0);
+    if unsafe { IsClipboardFormatAvailable(format) }.is_err() {
+        return Ok(None);
+    }
+    let _open = Open::new()?;
+    let bytes = unsafe { GetClipboardData(format) }.ok().and_then(read_global);
+    Ok(bytes.map(|bytes| decode_text(&bytes)))
+}
+
+/// Put a snapshot back, or leave the clipboard empty if it was.
+#[cfg(windows)]
+pub fn restore(snapshot: &Snapshot) -> Result<(), String> {
+    use windows::Win32::System::DataExchange::EmptyClipboard;
+
+    let _open = Open::new()?;
+    unsafe { EmptyClipboard() }.map_err(|e| format!("EmptyClipboard failed: {e}"))?;
+    for (format, bytes) in &snapshot.formats {
+        write_global(*format, bytes)?;
+    }
+    Ok(())
+}
+
+/// Current clipboard sequence number; it changes whenever anyone writes.
+#[cfg(windows)]
+pub fn sequence_number() -> u32 {
+    unsafe { windows::Win32::System::DataExchange::GetClipboardSequenceNumber() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_text() {
+        let bytes: Vec<u8> = "Größe 12\0junk".encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+        assert_eq!(decode_text(&bytes), "Größe 12");
+        assert_eq!(decode_text(&[0x41, 0x00, 0x42]), "A");
+        assert_eq!(decode_text(&[]), "");
+    }
+
+    #[test]
+    fn test_restorable_formats() {
+        // CF_TEXT, CF_DIB, CF_UNICODETEXT, CF_HDROP, CF_LOCALE, CF_DIBV5
+        for format in [1, 8, 13, 15, 16, 17] {
+            assert!(restorable(format), "format {format}");
+        }
+        // CF_BITMAP, CF_PALETTE, CF_ENHMETAFILE, owner display, private, GDI object
+        for format in [0, 2, 9, 14, 0x80, 0x200, 0x300] {
+            assert!(!restorable(format), "format {format}");
+        }
+        assert!(restorable(0xC0A1));
+    }
+}
@@ -0,0 +1,165 @@
+//! Event ordering and clock synchronization.
+//!
+//! Wall-clock timestamps can jump (NTP corrections, DST, manual changes), so
+//! every event also carries a process-wide sequence number and a monotonic
+//! millisecond counter. The network worker periodically exchanges `time_sync`
+//! messages with the backend and estimates the offset between the two clocks
+//! NTP-style; events then also carry a timestamp in the backend's time domain.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Number of recent sync samples kept; the lowest-RTT one wins.
+const MAX_SAMPLES: usize = 8;
+
+/// Samples with a round trip longer than this are too noisy to trust.
+const MAX_SAMPLE_RTT_MS: i64 = 5_000;
+
+static SEQ: AtomicU64 = AtomicU64::new(0);
+static START: OnceLock<Instant> = OnceLock::new();
+static CLOCK: Mutex<ClockSync> = Mutex::new(ClockSync::new());
+
+/// Next event sequence number (starts at 1, strictly increasing per process).
+pub fn next_seq() -> u64 {
+    SEQ.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Milliseconds since the collector started, from a monotonic clock.
+pub fn monotonic_ms() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// Current wall-clock time in epoch milliseconds.
+pub fn wall_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// One offset measurement from a request/response round trip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncSample {
+    /// Backend clock minus local clock, in milliseconds.
+    pub offset_ms: i64,
+    pub rtt_ms: i64,
+}
+
+/// Sliding window of offset samples against the backend clock.
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    samples: VecDeque<SyncSample>,
+}
+
+impl ClockSync {
+    pub const fn new() -> Self {
+        Self { samples: VecDeque::new() }
+    }
+
+    /// Record a round trip: `t0` local send time, `server_ms` backend time
+    /// when it answered, `t3` local receive time (all epoch milliseconds).
+    /// Assumes symmetric network delay. Returns the sample if it was accepted.
+    pub fn add_sample(&mut self, t0: i64, server_ms: i64, t3: i64) -> Option<SyncSample> {
+        let rtt_ms = t3 - t0;
+        if !(0..=MAX_SAMPLE_RTT_MS).contains(&rtt_ms) {
+            return None;
+        }
+        let sample = SyncSample {
+            offset_ms: server_ms - (t0 + rtt_ms / 2),
+            rtt_ms,
+        };
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        Some(sample)
+    }
+
+    /// Best offset estimate: the sample with the smallest round trip.
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.samples.iter().min_by_key(|s| s.rtt_ms).map(|s| s.offset_ms)
+    }
+}
+
+/// Build a `time_sync` request stamped with the local send time.
+pub fn build_sync_request() -> String {
+    serde_json::json!({ "type": "time_sync", "t0": wall_ms() }).to_string()
+}
+
+/// Feed a backend `time_sync` reply (`t0` echoed, `server_time_ms` added)
+/// into the global estimator.
+pub fn handle_sync_reply(value: &serde_json::Value) -> Option<SyncSample> {
+    let t0 = value.get("t0")?.as_i64()?;
+    let server_ms = value.get("server_time_ms")?.as_i64()?;
+    let t3 = wall_ms();
+    let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+    clock.add_sample(t0, server_ms, t3)
+}
+
+/// Current backend-minus-local offset, if any sync has completed.
+pub fn offset_ms() -> Option<i64> {
+    CLOCK.lock().unwrap_or_else(|e| e.into_inner()).offset_ms()
+}
+
+/// `now` translated into the backend's time domain, if an offset is known.
+pub fn synced_timestamp(now: DateTime<Utc>) -> Option<String> {
+    let offset = offset_ms()?;
+    let synced = now + chrono::Duration::milliseconds(offset);
+    Some(synced.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seq_strictly_increasing() {
+        let a = next_seq();
+        let b = next_seq();
+        assert!(b > a);
+    }
+
+    #[test]
+    fn test_monotonic_never_decreases() {
+        let a = monotonic_ms();
+        let b = monotonic_ms();
+        assert!(b >= a);
+    }
+
+    #[test]
+    fn test_offset_from_symmetric_round_trip() {
+        let mut clock = ClockSync::new();
+        // Local sends at 1000, backend (2s ahead) answers at 3050, reply lands at 1100
+        let sample = clock.add_sample(1000, 3050, 1100).unwrap();
+        assert_eq!(sample.rtt_ms, 100);
+        assert_eq!(sample.offset_ms, 2000);
+        assert_eq!(clock.offset_ms(), Some(2000));
+    }
+
+    #[test]
+    fn test_lowest_rtt_sample_wins() {
+        let mut clock = ClockSync::new();
+        clock.add_sample(0, 600, 1000); // rtt 1000, offset 100
+        clock.add_sample(0, 30, 20); // rtt 20, offset 20
+        clock.add_sample(0, 400, 500); // rtt 500, offset 150
+        assert_eq!(clock.offset_ms(), Some(20));
+    }
+
+    #[test]
+    fn test_rejects_bad_samples() {
+        let mut clock = ClockSync::new();
+        assert!(clock.add_sample(1000, 1000, 900).is_none()); // clock stepped back
+        assert!(clock.add_sample(0, 0, MAX_SAMPLE_RTT_MS + 1).is_none());
+        assert_eq!(clock.offset_ms(), None);
+    }
+
+    #[test]
+    fn test_window_is_bounded() {
+        let mut clock = ClockSync::new();
+        clock.add_sample(0, 5, 10); // best sample, evicted below
+        for _ in 0..MAX_SAMPLES {
+            clock.add_sample(0, 100, 50);
+        }
+        assert_eq!(clock.offset_ms(), Some(75));
+    }
+}
@@ -1,15 +1,35 @@
 //! Command bridge: receives desktop automation commands from the backend and executes them.
 //! Supports: observe, click, type_text, send_keys, open_application, focus_window,
-//! scroll, double_click, right_click. Uses UIA (UI Automation) for element resolution
-//! and SendInput for mouse/keyboard actions on Windows.
+//! scroll, double_click, triple_click, right_click, middle_click, hover, close_window,
+//! minimize_window, maximize_window, restore_window, move_window, resize_window,
+//! get_element_tree, select_item, expand, collapse, scroll_into_view,
+//! set_range_value, invoke_menu, screenshot_region, ocr, move_to_recycle_bin,
+//! empty_recycle_bin, run_shell, set_context_directory, export_state,
+//! import_state, find_elements, purge_data, kill_process, close_application,
+//! self_test, switch_desktop, swipe, flick, pinch, paste_text,
+//! preview_events, if_exists, show_element_labels, hide_element_labels,
+//! set_event_profile, get_element_text, click_detection,
+//! get_window_thumbnail, wait_for_window, wait_for_ui_idle,
+//! create_diagnostic_bundle, list_windows, drop_files, browser_navigate,
+//! list_monitors, get_window_rect, list_workflows, run_workflow. Uses UIA
+//! (UI Automation) for element resolution, SendInput for mouse/keyboard actions,
+//! synthetic pointer input for touch and pen (`pointer` on click,
+//! double_click, triple_click, right_click, swipe and flick), the clipboard for
+//! paste_text and WM_DROPFILES or OLE drag-and-drop for drop_files on
+//! Windows.
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::config::Config;
+use crate::diff::ScreenDiff;
+#[cfg(windows)]
+use crate::pointer::PointerKind;
+#[cfg(windows)]
+use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
 
 /// A command received from the backend for desktop automation.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Command {
     pub command_id: String,
     pub action: String,
@@ -17,6 +37,30 @@ pub struct Command {
     pub parameters: HashMap<String, serde_json::Value>,
     #[serde(default = "default_timeout_ms")]
     pub timeout_ms: u64,
+    /// Capture the screen before and after the action and attach a cheap
+    /// block-luminance diff score to the result.
+    #[serde(default)]
+    pub verify_diff: bool,
+    /// Attach the pre-action frame as `screenshot_before_b64`, next to the
+    /// post-action screenshot.
+    #[serde(default)]
+    pub capture_before: bool,
+    /// Attach the post-action screenshot; unset follows `ENABLE_SCREENSHOT`.
+    /// Cannot turn screenshots on where the configuration disables them.
+    #[serde(default)]
+    pub include_screenshot: Option<bool>,
+    /// Attach a UIA snapshot of the foreground window to any action, or
+    /// (`false`) leave it off `observe`; unset keeps the action's default.
+    /// Cannot turn UIA on where the configuration disables it.
+    #[serde(default)]
+    pub include_uia: Option<bool>,
+    /// UIA walk depth for this command, overriding `UIA_MAX_DEPTH`.
+    #[serde(default)]
+    pub uia_depth: Option<usize>,
+    /// W3C trace context of the backend request behind the command; the
+    /// collector's spans join that trace (see trace.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
 }
 
 fn default_timeout_ms() -> u64 {
@@ -24,7 +68,8 @@ fn default_timeout_ms() -> u64 {
 }
 
 /// Result of executing a command, sent back to the backend. Optionally includes
-/// a post-action screenshot and UIA snapshot for the agent's verification loop.
+/// a post-action screenshot (and the pre-action one) and UIA snapshot for the
+/// agent's verification loop.
 #[derive(Debug, Serialize, Clone)]
 pub struct CommandResult {
     #[serde(rename = "type")]
@@ -34,12 +79,21 @@ pub struct CommandResult {
     pub result: HashMap<String, serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub screenshot_b64: Option<String>,
+    /// Screen just before the action's input, with `capture_before`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot_before_b64: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uia: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Machine-readable failure category (e.g. `PolicyDenied`) for errors the
+    /// backend should handle differently from ordinary action failures.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub detections: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen_diff: Option<ScreenDiff>,
 }
 
 impl CommandResult {
@@ -50,9 +104,12 @@ impl CommandResult {
             ok: true,
             result,
             screenshot_b64: None,
+            screenshot_before_b64: None,
             uia: None,
             error: None,
+            error_code: None,
             detections: None,
+            screen_diff: None,
         }
     }
 
@@ -63,42 +120,533 @@ impl CommandResult {
             ok: false,
             result: HashMap::new(),
             screenshot_b64: None,
+            screenshot_before_b64: None,
             uia: None,
             error: Some(error.to_string()),
+            error_code: None,
             detections: None,
+            screen_diff: None,
+        }
+    }
+
+    /// Failure for an action blocked by local execution policy.
+    pub fn denied(command_id: &str, reason: &str) -> Self {
+        let mut result = Self::failure(command_id, &format!("PolicyDenied: {reason}"));
+        result.error_code = Some("PolicyDenied".to_string());
+        result
+    }
+
+    /// Failure for an action the machine's permission manifest disables.
+    pub fn not_permitted(command_id: &str, reason: &str) -> Self {
+        let mut result = Self::failure(command_id, &format!("PermissionDenied: {reason}"));
+        result.error_code = Some("PermissionDenied".to_string());
+        result
+    }
+
+    /// Failure for a command that did not finish within its `timeout_ms`.
+    pub fn timed_out(command_id: &str, action: &str, timeout_ms: u64) -> Self {
+        let mut result = Self::failure(command_id, &format!("{action} timed out after {timeout_ms}ms"));
+        result.error_code = Some("Timeout".to_string());
+        result
+    }
+
+    /// Failure for a command aborted by a `cancel` message.
+    pub fn cancelled(command_id: &str, action: &str) -> Self {
+        let mut result = Self::failure(command_id, &format!("{action} was cancelled"));
+        result.error_code = Some("Cancelled".to_string());
+        result
+    }
+
+    /// Failure for an input command stopped because the user moved the
+    /// mouse or pressed a key while it ran (see `interrupt`).
+    pub fn user_interrupted(command_id: &str, action: &str, source: &str) -> Self {
+        let mut result = Self::failure(
+            command_id,
+            &format!("UserInterrupted: {action} stopped by {source} input from the user"),
+        );
+        result.error_code = Some("UserInterrupted".to_string());
+        result
+    }
+}
+
+/// Execute a command, wrapping the action handler with the permission
+/// manifest and local execution policy checks and optional before/after
+/// screen verification. The outcome is counted for opt-in telemetry and
+/// traced as `collector.execute`.
+pub fn execute_command(cmd: &Command, config: &Config) -> CommandResult {
+    let started = std::time::Instant::now();
+    let mut span = crate::trace::Span::start("collector.execute", crate::trace::SpanKind::Internal, cmd.traceparent.as_deref());
+    span.set("command.action", cmd.action.as_str());
+    span.set("command.id", cmd.command_id.as_str());
+    let _registration = crate::cancel::register(&cmd.command_id);
+    crate::recorder::capture_tree(cmd, config);
+    let result = execute_checked(cmd, config);
+    crate::telemetry::record(config, &cmd.action, &result, started.elapsed());
+    span.set("command.ok", result.ok);
+    if let Some(code) = &result.error_code {
+        span.set("command.error_code", code.as_str());
+    }
+    if let Some(error) = &result.error {
+        span.fail(error);
+    }
+    result
+}
+
+fn execute_checked(cmd: &Command, config: &Config) -> CommandResult {
+    if let Some(reason) = crate::permissions::check_action(&cmd.action, config) {
+        log::info!("Refused command {} (id={}): {reason}", cmd.action, cmd.command_id);
+        return CommandResult::not_permitted(&cmd.command_id, &reason);
+    }
+    if let Some(reason) = crate::policy::check_command(cmd, config) {
+        log::info!("Denied command {} (id={}): {reason}", cmd.action, cmd.command_id);
+        return CommandResult::denied(&cmd.command_id, &reason);
+    }
+
+    #[cfg(windows)]
+    if FOREGROUND_INPUT_ACTIONS.contains(&cmd.action.as_str()) {
+        let fg = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+        if let Some(denied) = deny_self_target(cmd, config, fg) {
+            return denied;
+        }
+    }
+
+    let observation = observation_config(cmd, config);
+    let config = observation.as_ref().unwrap_or(config);
+
+    // Input actions take the pre-action frame as late as possible, when they
+    // first inject input; others just before they start
+    #[cfg(windows)]
+    let wants_before = cmd.verify_diff || (cmd.capture_before && config.enable_screenshot);
+    #[cfg(windows)]
+    let injects_input = crate::permissions::category(&cmd.action) == Some("input");
+    #[cfg(windows)]
+    let mut before = if wants_before && !injects_input {
+        crate::screenshot::capture_raw_pixels(windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    #[cfg(windows)]
+    let armed = (wants_before && injects_input).then(PreInputFrame::arm);
+
+    let interrupt = crate::interrupt::watch(cmd, config);
+    let mut result = dispatch_action(cmd, config);
+    #[cfg(windows)]
+    if let Some(armed) = armed {
+        before = armed.take();
+    }
+    if let Some(source) = interrupt.and_then(|guard| guard.interrupted()) {
+        log::info!("Command {} (id={}) interrupted by the user", cmd.action, cmd.command_id);
+        let partial = std::mem::take(&mut result.result);
+        result = CommandResult::user_interrupted(&cmd.command_id, &cmd.action, source);
+        result.result = partial;
+    }
+
+    #[cfg(windows)]
+    if let Some((bw, bh, before_px)) = before {
+        if result.ok && cmd.verify_diff {
+            if let Some((aw, ah, after_px)) =
+                crate::screenshot::capture_raw_pixels(windows::Win32::Foundation::HWND(0))
+            {
+                result.screen_diff = Some(crate::diff::compare_frames(
+                    (bw, bh, &before_px),
+                    (aw, ah, &after_px),
+                    3,
+                ));
+            }
+        }
+        if cmd.capture_before && config.enable_screenshot {
+            result.screenshot_before_b64 = crate::screenshot::encode_raw_to_base64(config, bw, bh, before_px);
+        }
+    }
+
+    #[cfg(windows)]
+    if cmd.include_uia == Some(true) && result.uia.is_none() {
+        let fg = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+        result.uia = crate::uia::uia_snapshot(fg, config).and_then(|snapshot| serde_json::to_value(&snapshot).ok());
+    }
+
+    result
+}
+
+/// The configuration `cmd` runs under when it asks for more or less
+/// observation than the defaults, or `None` when it does not. A requested
+/// UIA snapshot skips the snapshot throttle.
+fn observation_config(cmd: &Command, config: &Config) -> Option<Config> {
+    if cmd.include_screenshot.is_none() && cmd.include_uia.is_none() && cmd.uia_depth.is_none() {
+        return None;
+    }
+    let mut observed = config.clone();
+    observed.enable_screenshot &= cmd.include_screenshot.unwrap_or(true);
+    match cmd.include_uia {
+        Some(false) => observed.uia_enabled = false,
+        Some(true) => observed.uia_throttle = std::time::Duration::ZERO,
+        None => {}
+    }
+    if let Some(depth) = cmd.uia_depth {
+        observed.uia_max_depth = depth;
+    }
+    Some(observed)
+}
+
+/// State of the pre-action frame on the command's thread.
+#[cfg(windows)]
+enum PreInput {
+    Idle,
+    Armed,
+    Taken(Option<(u32, u32, Vec<u8>)>),
+}
+
+#[cfg(windows)]
+thread_local! {
+    static PRE_INPUT: std::cell::RefCell<PreInput> = const { std::cell::RefCell::new(PreInput::Idle) };
+}
+
+/// Arms the capture of the pre-action frame for an input action; disarms
+/// when dropped.
+#[cfg(windows)]
+struct PreInputFrame;
+
+#[cfg(windows)]
+impl PreInputFrame {
+    fn arm() -> Self {
+        PRE_INPUT.with(|state| *state.borrow_mut() = PreInput::Armed);
+        Self
+    }
+
+    /// The frame taken by `before_input`, if the action got that far.
+    fn take(self) -> Option<(u32, u32, Vec<u8>)> {
+        PRE_INPUT.with(|state| match std::mem::replace(&mut *state.borrow_mut(), PreInput::Idle) {
+            PreInput::Taken(frame) => frame,
+            _ => None,
+        })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for PreInputFrame {
+    fn drop(&mut self) {
+        PRE_INPUT.with(|state| *state.borrow_mut() = PreInput::Idle);
+    }
+}
+
+/// Called right before input is injected (SendInput, pointer injection,
+/// UIA Invoke or SetValue): takes the pre-action frame if one is wanted and
+/// not yet taken.
+#[cfg(windows)]
+pub(crate) fn before_input() {
+    PRE_INPUT.with(|state| {
+        let mut state = state.borrow_mut();
+        if matches!(*state, PreInput::Armed) {
+            *state = PreInput::Taken(crate::screenshot::capture_raw_pixels(windows::Win32::Foundation::HWND(0)));
         }
+    })
+}
+
+/// Actions that deliver input to whatever window is in the foreground.
+#[cfg(windows)]
+const FOREGROUND_INPUT_ACTIONS: &[&str] = &["type_text", "send_keys", "scroll", "paste_text"];
+
+/// Policy denial if `hwnd` (or its top-level ancestor) is one of DesktopAI's
+/// own windows and the command did not opt in with `allow_self`.
+#[cfg(windows)]
+fn deny_self_target(cmd: &Command, config: &Config, hwnd: windows::Win32::Foundation::HWND) -> Option<CommandResult> {
+    use windows::Win32::UI::WindowsAndMessaging::{GetAncestor, GA_ROOT};
+
+    if hwnd.0 == 0 || crate::policy::self_targeting_allowed(cmd, config) {
+        return None;
+    }
+    let root = unsafe { GetAncestor(hwnd, GA_ROOT) };
+    let root = if root.0 == 0 { hwnd } else { root };
+    if !crate::windows::is_self_window(root, config) {
+        return None;
     }
+    log::info!("Denied command {} (id={}): target is a DesktopAI window", cmd.action, cmd.command_id);
+    Some(CommandResult::denied(
+        &cmd.command_id,
+        &format!("'{}' targets a DesktopAI window; set allow_self to permit it", cmd.action),
+    ))
+}
+
+/// Same as `deny_self_target` for the window under screen point (x, y).
+#[cfg(windows)]
+fn deny_self_target_at(cmd: &Command, config: &Config, x: i32, y: i32) -> Option<CommandResult> {
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::UI::WindowsAndMessaging::WindowFromPoint;
+
+    let hwnd = unsafe { WindowFromPoint(POINT { x, y }) };
+    deny_self_target(cmd, config, hwnd)
 }
 
 /// Dispatch a command to the appropriate handler.
 /// On non-Windows, only returns errors (the real handlers use Win32 APIs).
-pub fn execute_command(cmd: &Command, _config: &Config) -> CommandResult {
+fn dispatch_action(cmd: &Command, _config: &Config) -> CommandResult {
     match cmd.action.as_str() {
         "observe" => handle_observe(cmd, _config),
         "click" => handle_click(cmd, _config),
         "type_text" => handle_type_text(cmd, _config),
         "send_keys" => handle_send_keys(cmd, _config),
+        "paste_text" => handle_paste_text(cmd, _config),
         "open_application" => handle_open_application(cmd, _config),
+        "kill_process" | "close_application" => handle_end_process(cmd, _config),
+        "self_test" => handle_self_test(cmd, _config),
         "focus_window" => handle_focus_window(cmd, _config),
         "scroll" => handle_scroll(cmd, _config),
-        "double_click" => handle_double_click(cmd, _config),
+        "double_click" => handle_multi_click(cmd, _config, 2, MouseButton::Left),
+        "triple_click" => handle_multi_click(cmd, _config, 3, MouseButton::Left),
+        "middle_click" => handle_multi_click(cmd, _config, 1, MouseButton::Middle),
         "right_click" => handle_right_click(cmd, _config),
+        "swipe" | "flick" | "pinch" => handle_gesture(cmd, _config),
+        "set_safe_mode" => handle_set_safe_mode(cmd, _config),
+        "set_context_directory" => handle_set_context_directory(cmd, _config),
+        "set_event_profile" => handle_set_event_profile(cmd, _config),
+        "export_state" => handle_export_state(cmd, _config),
+        "preview_events" => handle_preview_events(cmd, _config),
+        "create_diagnostic_bundle" => handle_create_diagnostic_bundle(cmd, _config),
+        "find_elements" => handle_find_elements(cmd, _config),
+        "get_element_text" => handle_get_element_text(cmd, _config),
+        "click_detection" => handle_click_detection(cmd, _config),
+        "get_window_thumbnail" => handle_get_window_thumbnail(cmd, _config),
+        "wait_for_window" => handle_wait_for_window(cmd, _config),
+        "list_windows" => handle_list_windows(cmd, _config),
+        "list_monitors" => handle_list_monitors(cmd, _config),
+        "get_window_rect" => handle_get_window_rect(cmd, _config),
+        "list_workflows" => handle_list_workflows(cmd, _config),
+        "run_workflow" => handle_run_workflow(cmd, _config),
+        "wait_for_ui_idle" => handle_wait_for_ui_idle(cmd, _config),
+        "if_exists" => handle_if_exists(cmd, _config),
+        "show_element_labels" => handle_show_element_labels(cmd, _config),
+        "hide_element_labels" => handle_hide_element_labels(cmd, _config),
+        "import_state" => handle_import_state(cmd, _config),
+        "purge_data" => handle_purge_data(cmd, _config),
+        "get_taskbar_state" => handle_get_taskbar_state(cmd, _config),
+        "start_menu_search" => handle_start_menu_search(cmd, _config),
+        "hover" => handle_hover(cmd, _config),
+        "close_window" | "minimize_window" | "maximize_window" | "restore_window" => handle_window_state(cmd, _config),
+        "move_window" | "resize_window" => handle_window_geometry(cmd, _config),
+        "switch_desktop" => handle_switch_desktop(cmd, _config),
+        "get_element_tree" => handle_get_element_tree(cmd, _config),
+        "select_item" => handle_select_item(cmd, _config),
+        "expand" | "collapse" => handle_expand_collapse(cmd, _config),
+        "scroll_into_view" => handle_scroll_into_view(cmd, _config),
+        "set_range_value" => handle_set_range_value(cmd, _config),
+        "invoke_menu" => handle_invoke_menu(cmd, _config),
+        "screenshot_region" => handle_screenshot_region(cmd, _config),
+        "ocr" => handle_ocr(cmd, _config),
+        "move_to_recycle_bin" => handle_move_to_recycle_bin(cmd, _config),
+        "empty_recycle_bin" => handle_empty_recycle_bin(cmd, _config),
+        "drop_files" => handle_drop_files(cmd, _config),
+        "browser_navigate" => handle_browser_navigate(cmd, _config),
+        "run_shell" => handle_run_shell(cmd, _config),
         _ => CommandResult::failure(&cmd.command_id, &format!("unknown action: {}", cmd.action)),
     }
 }
 
+/// Toggle the runtime safe mode flag. Safe mode stays on while the tray's
+/// marker file exists, regardless of what the backend requests.
+fn handle_set_safe_mode(cmd: &Command, config: &Config) -> CommandResult {
+    let Some(enabled) = cmd.parameters.get("enabled").and_then(|v| v.as_bool()) else {
+        return CommandResult::failure(&cmd.command_id, "set_safe_mode requires boolean 'enabled' parameter");
+    };
+    if !enabled && config.safe_mode {
+        return CommandResult::denied(&cmd.command_id, "safe mode is on in the local configuration (SAFE_MODE)");
+    }
+    crate::policy::set_safe_mode(enabled);
+    log::info!("Safe mode flag set to {enabled}");
+
+    let mut result = HashMap::new();
+    result.insert("safe_mode".to_string(), serde_json::json!(crate::policy::safe_mode_active(config)));
+    result.insert("forced_by_user".to_string(), serde_json::json!(crate::policy::safe_mode_forced(config)));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Scope later file operations to the directory in `path`; a missing, null
+/// or empty `path` clears the scope.
+fn handle_set_context_directory(cmd: &Command, _config: &Config) -> CommandResult {
+    let path = cmd.parameters.get("path").and_then(|v| v.as_str()).map(str::trim).filter(|p| !p.is_empty());
+    let dir = match path.map(std::path::PathBuf::from) {
+        Some(dir) if !dir.is_absolute() => {
+            return CommandResult::failure(&cmd.command_id, &format!("path must be absolute: {}", dir.display()))
+        }
+        Some(dir) if !dir.is_dir() => {
+            return CommandResult::failure(&cmd.command_id, &format!("not a directory: {}", dir.display()))
+        }
+        dir => dir,
+    };
+    log::info!("Context directory set to {dir:?}");
+    let mut result = HashMap::new();
+    result.insert(
+        "context_directory".to_string(),
+        serde_json::json!(dir.as_ref().map(|d| d.to_string_lossy().into_owned())),
+    );
+    crate::policy::set_context_directory(dir);
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Switch the profile tag added to events (see routing.rs). A string sets
+/// it, an empty one turns it off; a missing or null `profile` returns to
+/// `EVENT_PROFILE`.
+fn handle_set_event_profile(cmd: &Command, config: &Config) -> CommandResult {
+    let profile = match cmd.parameters.get("profile") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(profile)) => Some(profile.trim().to_string()),
+        Some(_) => return CommandResult::failure(&cmd.command_id, "profile must be a string or null"),
+    };
+    crate::routing::set_profile(profile);
+    let profile = crate::routing::profile(config);
+    log::info!("Event profile set to {profile:?}");
+    let mut result = HashMap::new();
+    result.insert("profile".to_string(), serde_json::json!(profile));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Return the collector's portable settings as a versioned archive (see
+/// state.rs), also written to `path` when one is given. The path is scoped
+/// to the context directory like other file operations (see
+/// `policy::scope_new_path`) and must not exist yet.
+fn handle_export_state(cmd: &Command, config: &Config) -> CommandResult {
+    use std::io::Write;
+
+    let archive = match crate::state::export(config) {
+        Ok(archive) => archive,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let mut result = HashMap::new();
+    if let Some(path) = cmd.parameters.get("path").and_then(|v| v.as_str()).map(str::trim).filter(|p| !p.is_empty()) {
+        let context = crate::policy::context_directory();
+        let path = match crate::policy::scope_new_path(std::path::Path::new(path), context.as_deref()) {
+            Ok(path) => path,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        };
+        let text = serde_json::to_string_pretty(&archive).unwrap_or_default();
+        let written = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(text.as_bytes()));
+        if let Err(e) = written {
+            return CommandResult::failure(&cmd.command_id, &format!("cannot write {}: {e}", path.display()));
+        }
+        result.insert("path".to_string(), serde_json::json!(path.to_string_lossy()));
+    }
+    result.insert("archive".to_string(), serde_json::json!(archive));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Privacy preview (see preview.rs): `count` starts capturing the next
+/// outgoing events; every call returns those captured so far, with
+/// `redact_pattern` tried on them and the full events when `raw`.
+fn handle_preview_events(cmd: &Command, _config: &Config) -> CommandResult {
+    let mut result = HashMap::new();
+    if let Some(count) = cmd.parameters.get("count") {
+        let Some(count) = count.as_u64().filter(|&count| count > 0) else {
+            return CommandResult::failure(&cmd.command_id, "count must be a positive integer");
+        };
+        result.insert("armed".to_string(), serde_json::json!(crate::preview::arm(count as usize)));
+    }
+    let pattern = cmd.parameters.get("redact_pattern").and_then(|v| v.as_str()).filter(|p| !p.is_empty());
+    let candidate = match pattern.map(crate::pipeline::RedactionStage::new) {
+        Some(None) => return CommandResult::failure(&cmd.command_id, "redact_pattern is not a valid regular expression"),
+        candidate => candidate.flatten(),
+    };
+    let raw = cmd.parameters.get("raw").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let (events, remaining) = crate::preview::captured();
+    let entries: Vec<_> = events.iter().map(|event| crate::preview::summarize(event, candidate.as_ref(), raw)).collect();
+    result.insert("events".to_string(), serde_json::json!(entries));
+    result.insert("remaining".to_string(), serde_json::json!(remaining));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Write an encrypted diagnostic bundle (see diagnostics.rs) holding only
+/// the categories listed in `include`, protected by `password`.
+fn handle_create_diagnostic_bundle(cmd: &Command, config: &Config) -> CommandResult {
+    let summary = match crate::diagnostics::BundleRequest::parse(&cmd.parameters, config)
+        .and_then(|request| crate::diagnostics::create(&request, config))
+    {
+        Ok(summary) => summary,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let mut result = HashMap::new();
+    result.insert("path".to_string(), serde_json::json!(summary.path));
+    result.insert("included".to_string(), serde_json::json!(summary.included));
+    result.insert("files".to_string(), serde_json::json!(summary.files));
+    result.insert("bytes".to_string(), serde_json::json!(summary.bytes));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Securely delete the collector's data directory (see datadir.rs) and, on
+/// Windows, wipe the in-memory screenshots. Needs `confirm: true`; `dry_run`
+/// only reports what would be removed.
+fn handle_purge_data(cmd: &Command, config: &Config) -> CommandResult {
+    let dry_run = cmd.parameters.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+    let confirmed = cmd.parameters.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false);
+    if !dry_run && !confirmed {
+        return CommandResult::failure(&cmd.command_id, "purge_data deletes all collector data; pass confirm: true");
+    }
+    match crate::datadir::purge(config, dry_run) {
+        Ok(summary) => {
+            let mut result = HashMap::new();
+            result.insert("dry_run".to_string(), serde_json::json!(dry_run));
+            result.insert("directories".to_string(), serde_json::json!(summary.directories));
+            result.insert("files".to_string(), serde_json::json!(summary.files));
+            result.insert("bytes".to_string(), serde_json::json!(summary.bytes));
+            #[cfg(windows)]
+            if !dry_run {
+                let wiped = crate::screenshot::wipe_screenshot_buffer();
+                result.insert("screenshots_wiped".to_string(), serde_json::json!(wiped));
+            }
+            CommandResult::success(&cmd.command_id, result)
+        }
+        Err(e) => CommandResult::failure(&cmd.command_id, &e),
+    }
+}
+
+/// Save the settings in an archive (`archive` object, or the file at `path`,
+/// scoped like other file operations) for the next start; safe mode from
+/// the archive applies immediately.
+fn handle_import_state(cmd: &Command, _config: &Config) -> CommandResult {
+    let archive = if let Some(archive) = cmd.parameters.get("archive") {
+        serde_json::from_value::<crate::state::StateArchive>(archive.clone()).map_err(|e| format!("invalid archive: {e}"))
+    } else if let Some(path) = cmd.parameters.get("path").and_then(|v| v.as_str()) {
+        let context = crate::policy::context_directory();
+        let path = match crate::policy::scope_path(std::path::Path::new(path), context.as_deref()) {
+            Ok(path) => path,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| format!("invalid archive in {}: {e}", path.display())))
+    } else {
+        Err("import_state requires 'archive' or 'path' parameter".to_string())
+    };
+    let archive = match archive.and_then(|archive| crate::state::validate(&archive).map(|_| archive)) {
+        Ok(archive) => archive,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let Some(settings_path) = crate::state::settings_path() else {
+        return CommandResult::failure(&cmd.command_id, "no settings location (set COLLECTOR_SETTINGS_PATH)");
+    };
+    let summary = match crate::state::save(&archive, &settings_path) {
+        Ok(summary) => summary,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    if let Some(safe_mode) = summary.settings.get("SAFE_MODE") {
+        crate::policy::set_safe_mode(matches!(safe_mode.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"));
+    }
+    log::info!("Imported {} settings to {}", summary.settings.len(), settings_path.display());
+
+    let mut result = HashMap::new();
+    result.insert("saved_to".to_string(), serde_json::json!(settings_path.to_string_lossy()));
+    result.insert("settings".to_string(), serde_json::json!(summary.settings.keys().collect::<Vec<_>>()));
+    result.insert("ignored".to_string(), serde_json::json!(summary.ignored));
+    result.insert("restart_required".to_string(), serde_json::json!(true));
+    CommandResult::success(&cmd.command_id, result)
+}
+
 // --- Platform-gated action handlers ---
 
 #[cfg(windows)]
 fn handle_observe(cmd: &Command, config: &Config) -> CommandResult {
-    #[cfg(feature = "detection")]
-    use std::sync::OnceLock;
-    #[cfg(feature = "detection")]
-    use crate::detection::Detector;
-
-    #[cfg(feature = "detection")]
-    static DETECTOR: OnceLock<Option<Detector>> = OnceLock::new();
-
     let mut result = HashMap::new();
     result.insert("action".to_string(), serde_json::Value::String("observe".to_string()));
 
@@ -121,17 +669,14 @@ fn handle_observe(cmd: &Command, config: &Config) -> CommandResult {
     // Run UI element detection on raw pixels (if model is available)
     #[cfg(feature = "detection")]
     let detections = if config.detection_enabled {
-        let detector = DETECTOR.get_or_init(|| {
-            let d = Detector::new(&config.detection_model_path, config.detection_confidence, config.detection_input_size);
-            if d.is_none() {
-                log::warn!("Detection model not loaded from '{}' — detection disabled", config.detection_model_path);
-            }
-            d
-        });
-        if let (Some(det), Some((w, h, ref pixels))) = (detector.as_ref(), &raw_pixels) {
+        let detector = crate::detection::shared_detector(config);
+        if let (Some(det), Some((w, h, ref pixels))) = (detector, &raw_pixels) {
             let t0 = std::time::Instant::now();
             let dets = det.detect(pixels, *w, *h, 3); // 3-channel BGR
             let elapsed_ms = t0.elapsed().as_millis();
+            if let Some(monitor) = crate::screenshot::monitor_rect(windows::Win32::Foundation::HWND(0)) {
+                crate::detections::remember(monitor, dets.iter().map(|d| [d.x, d.y, d.width, d.height]).collect());
+            }
             if !dets.is_empty() {
                 log::info!("Detection: {} elements in {}ms", dets.len(), elapsed_ms);
                 serde_json::to_value(&dets).ok()
@@ -163,7 +708,11 @@ fn handle_observe(cmd: &Command, config: &Config) -> CommandResult {
     let uia = if config.uia_enabled {
         use crate::uia::uia_snapshot;
         match uia_snapshot(hwnd, config) {
-            Some(snapshot) => serde_json::to_value(&snapshot).ok(),
+            Some(mut snapshot) => {
+                let (tokens, keep_tree) = outline_params(cmd, config);
+                crate::outline::attach(&mut snapshot, tokens, keep_tree);
+                serde_json::to_value(&snapshot).ok()
+            }
             None => None,
         }
     } else {
@@ -186,6 +735,19 @@ fn handle_observe(cmd: &Command, config: &Config) -> CommandResult {
     cmd_result
 }
 
+/// Outline budget and whether to keep the raw tree: the `outline_tokens`
+/// and `include_tree` parameters, defaulting to the configuration.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn outline_params(cmd: &Command, config: &Config) -> (usize, bool) {
+    let tokens = cmd
+        .parameters
+        .get("outline_tokens")
+        .and_then(|v| v.as_u64())
+        .map_or(config.uia_outline_tokens, |v| v as usize);
+    let keep_tree = cmd.parameters.get("include_tree").and_then(|v| v.as_bool()).unwrap_or(!config.uia_outline_only);
+    (tokens, keep_tree)
+}
+
 #[cfg(not(windows))]
 fn handle_observe(cmd: &Command, _config: &Config) -> CommandResult {
     CommandResult::failure(&cmd.command_id, "observe requires Windows")
@@ -204,26 +766,58 @@ fn bstr_to_variant(s: &str) -> windows::Win32::System::Variant::VARIANT {
     }
 }
 
+/// Click the element `name`/`automation_id`/`control_type` (and `index`)
+/// picks, through InvokePattern when it has one, else at its center; or the
+/// point `x`/`y`. An element click reports what it hit as `element` (see
+/// `acted_element`). A `click_count` above 1 clicks that often in a row and
+/// a `button` other than left presses that one (see `handle_multi_click`;
+/// a single right click is `right_click`).
 #[cfg(windows)]
 fn handle_click(cmd: &Command, config: &Config) -> CommandResult {
     use windows::Win32::UI::Accessibility::*;
     use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
 
-    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
-    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+    // A multi-click or another button is real input at one point, never an Invoke
+    let count = match click_count(cmd, 1) {
+        Ok(count) => count,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    match MouseButton::from_param(cmd.parameters.get("button"), MouseButton::Left) {
+        Ok(MouseButton::Left) if count == 1 => {}
+        Ok(MouseButton::Right) if count == 1 => return handle_right_click(cmd, config),
+        Ok(_) => return handle_multi_click(cmd, config, 1, MouseButton::Left),
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    }
+
+    let (name, automation_id) = element_target(cmd);
+    let control_type = if cmd.parameters.get("relative_to").and_then(|v| v.as_str()) == Some("element") {
+        ""
+    } else {
+        cmd.parameters.get("control_type").and_then(|v| v.as_str()).unwrap_or("")
+    };
+    let pointer = match PointerKind::from_param(cmd.parameters.get("pointer")) {
+        Ok(pointer) => pointer,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
 
     // If no UIA identifier provided, fall back to x/y pixel coordinates
-    if name.is_empty() && automation_id.is_empty() {
-        let x = cmd.parameters.get("x").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        let y = cmd.parameters.get("y").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        if x < 0 || y < 0 {
-            return CommandResult::failure(&cmd.command_id, "click requires 'name', 'automation_id', or 'x'/'y' parameters");
+    if name.is_empty() && automation_id.is_empty() && control_type.is_empty() {
+        let (x, y) = match point_param(cmd, config) {
+            Ok(Some(point)) => point,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, "click requires 'name', 'automation_id', 'control_type', 'label', or 'x'/'y' parameters"),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        };
+        if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+            return denied;
+        }
+        if let Err(e) = click_or_tap(pointer, x, y) {
+            return CommandResult::failure(&cmd.command_id, &e);
         }
-        click_at(x, y);
         let mut result = HashMap::new();
         result.insert("x".to_string(), serde_json::json!(x));
         result.insert("y".to_string(), serde_json::json!(y));
         result.insert("method".to_string(), serde_json::Value::String("coordinate".to_string()));
+        result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
         let mut cmd_result = CommandResult::success(&cmd.command_id, result);
         cmd_result.screenshot_b64 = if config.enable_screenshot {
             crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
@@ -238,60 +832,85 @@ fn handle_click(cmd: &Command, config: &Config) -> CommandResult {
         let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
     }
 
-    let uia: windows::Win32::UI::Accessibility::IUIAutomation = unsafe {
-        match windows::Win32::System::Com::CoCreateInstance(
-            &CUIAutomation,
-            None,
-            windows::Win32::System::Com::CLSCTX_INPROC_SERVER,
-        ) {
-            Ok(u) => u,
-            Err(e) => return CommandResult::failure(&cmd.command_id, &format!("UIA init failed: {e}")),
+    // With `index` or `control_type`, click that match among all of them
+    // (see find_elements)
+    let index = cmd.parameters.get("index").and_then(|v| v.as_u64());
+    let element = if cmd.parameters.contains_key("index") || !control_type.is_empty() {
+        match select_uia_element(cmd, config) {
+            Ok(element) => element,
+            Err(failed) => return *failed,
         }
-    };
+    } else {
+        let uia: windows::Win32::UI::Accessibility::IUIAutomation = unsafe {
+            match windows::Win32::System::Com::CoCreateInstance(
+                &CUIAutomation,
+                None,
+                windows::Win32::System::Com::CLSCTX_INPROC_SERVER,
+            ) {
+                Ok(u) => u,
+                Err(e) => return CommandResult::failure(&cmd.command_id, &format!("UIA init failed: {e}")),
+            }
+        };
 
-    let root = unsafe {
-        match uia.GetRootElement() {
-            Ok(r) => r,
-            Err(e) => return CommandResult::failure(&cmd.command_id, &format!("GetRootElement failed: {e}")),
-        }
-    };
+        let root = match uia_search_root(cmd, config, &uia) {
+            Ok(root) => root,
+            Err(failed) => return *failed,
+        };
 
-    // Build condition: prefer automation_id, fallback to name
-    let condition = if !automation_id.is_empty() {
-        let prop = UIA_AutomationIdPropertyId;
-        let val = bstr_to_variant(automation_id);
-        unsafe { uia.CreatePropertyCondition(prop, val) }
-    } else {
-        let prop = UIA_NamePropertyId;
-        let val = bstr_to_variant(name);
-        unsafe { uia.CreatePropertyCondition(prop, val) }
-    };
+        // Build condition: prefer automation_id, fallback to name
+        let condition = if !automation_id.is_empty() {
+            let prop = UIA_AutomationIdPropertyId;
+            let val = bstr_to_variant(automation_id);
+            unsafe { uia.CreatePropertyCondition(prop, val) }
+        } else {
+            let prop = UIA_NamePropertyId;
+            let val = bstr_to_variant(name);
+            unsafe { uia.CreatePropertyCondition(prop, val) }
+        };
 
-    let condition = match condition {
-        Ok(c) => c,
-        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("CreatePropertyCondition failed: {e}")),
-    };
+        let condition = match condition {
+            Ok(c) => c,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &format!("CreatePropertyCondition failed: {e}")),
+        };
 
-    let element = unsafe {
-        match root.FindFirst(TreeScope_Descendants, &condition) {
-            Ok(e) => e,
-            Err(e) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {e}")),
+        unsafe {
+            match root.FindFirst(TreeScope_Descendants, &condition) {
+                Ok(e) => e,
+                Err(e) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {e}")),
+            }
         }
     };
 
-    // Try InvokePattern
+    let owner_pid = unsafe { element.CurrentProcessId() }.unwrap_or(0);
+    if owner_pid > 0
+        && !crate::policy::self_targeting_allowed(cmd, config)
+        && crate::policy::is_self_process(&crate::windows::process_path(owner_pid as u32), config)
+    {
+        return CommandResult::denied(
+            &cmd.command_id,
+            "'click' targets a DesktopAI window; set allow_self to permit it",
+        );
+    }
+    // Described before the click, which may make it go away
+    let described = acted_element(&element, config);
+
+    // Try InvokePattern; a touch or pen click taps the element instead
     let invoke_result: Result<IUIAutomationInvokePattern, _> = unsafe {
         element.GetCurrentPatternAs(UIA_InvokePatternId)
     };
 
-    if let Ok(invoke) = invoke_result {
+    if let (Ok(invoke), PointerKind::Mouse) = (invoke_result, pointer) {
+        before_input();
         if let Err(e) = unsafe { invoke.Invoke() } {
             return CommandResult::failure(&cmd.command_id, &format!("Invoke failed: {e}"));
         }
         let mut result = HashMap::new();
-        let clicked_name = if !name.is_empty() { name } else { automation_id };
-        result.insert("clicked".to_string(), serde_json::Value::String(clicked_name.to_string()));
+        result.insert("clicked".to_string(), serde_json::Value::String(clicked_name(name, automation_id, control_type)));
         result.insert("method".to_string(), serde_json::Value::String("invoke".to_string()));
+        result.insert("element".to_string(), described);
+        if let Some(index) = index {
+            result.insert("index".to_string(), serde_json::json!(index));
+        }
 
         let mut cmd_result = CommandResult::success(&cmd.command_id, result);
         // Capture post-action state
@@ -303,19 +922,35 @@ fn handle_click(cmd: &Command, config: &Config) -> CommandResult {
         return cmd_result;
     }
 
-    // Fallback: click at bounding rect center via SendInput
+    // Fallback: click at bounding rect center via SendInput. An off-screen
+    // element's rect is stale, so scroll it into view first.
+    let scrolled = if cmd.parameters.get("scroll_into_view").and_then(|v| v.as_bool()).unwrap_or(true) {
+        scroll_element_into_view(&element).filter(|method| *method != "already_visible")
+    } else {
+        None
+    };
+    let described = if scrolled.is_some() { acted_element(&element, config) } else { described };
     let rect = unsafe { element.CurrentBoundingRectangle() };
     match rect {
         Ok(r) => {
             let center_x = (r.left + r.right) / 2;
             let center_y = (r.top + r.bottom) / 2;
-            click_at(center_x, center_y);
+            if let Err(e) = click_or_tap(pointer, center_x, center_y) {
+                return CommandResult::failure(&cmd.command_id, &e);
+            }
             let mut result = HashMap::new();
-            let clicked_name = if !name.is_empty() { name } else { automation_id };
-            result.insert("clicked".to_string(), serde_json::Value::String(clicked_name.to_string()));
+            result.insert("clicked".to_string(), serde_json::Value::String(clicked_name(name, automation_id, control_type)));
             result.insert("method".to_string(), serde_json::Value::String("coordinate".to_string()));
+            result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
             result.insert("x".to_string(), serde_json::json!(center_x));
             result.insert("y".to_string(), serde_json::json!(center_y));
+            result.insert("element".to_string(), described);
+            if let Some(index) = index {
+                result.insert("index".to_string(), serde_json::json!(index));
+            }
+            if let Some(method) = scrolled {
+                result.insert("scrolled".to_string(), serde_json::json!(method));
+            }
 
             let mut cmd_result = CommandResult::success(&cmd.command_id, result);
             cmd_result.screenshot_b64 = if config.enable_screenshot {
@@ -329,97 +964,362 @@ fn handle_click(cmd: &Command, config: &Config) -> CommandResult {
     }
 }
 
+/// The virtual screen spanning every monitor, `[left, top, width, height]`.
+/// Its origin is negative when a monitor sits left of or above the primary.
 #[cfg(windows)]
-fn click_at(x: i32, y: i32) {
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+fn virtual_screen() -> [i32; 4] {
+    use windows::Win32::UI::WindowsAndMessaging::*;
+    unsafe {
+        [
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        ]
+    }
+}
 
-    let screen_w = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CXSCREEN) };
-    let screen_h = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CYSCREEN) };
+/// Whether screen point (x, y) lies on the virtual `screen`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn on_screen(x: i64, y: i64, screen: [i32; 4]) -> bool {
+    let [left, top, width, height] = screen.map(i64::from);
+    (left..left + width).contains(&x) && (top..top + height).contains(&y)
+}
 
-    let norm_x = (x as i64 * 65535 / screen_w as i64) as i32;
-    let norm_y = (y as i64 * 65535 / screen_h as i64) as i32;
+/// SendInput absolute coordinates of screen point (x, y) for
+/// MOUSEEVENTF_VIRTUALDESK: 0..=65535 across the virtual `screen`, clamped
+/// to its edges. Rounds up so Windows maps the value back onto (x, y).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn absolute_coords(x: i32, y: i32, screen: [i32; 4]) -> (i32, i32) {
+    let axis = |value: i32, origin: i32, extent: i32| {
+        let extent = i64::from(extent.max(1));
+        let offset = (i64::from(value) - i64::from(origin)).clamp(0, extent - 1);
+        ((offset * 65536 + extent - 1) / extent).min(65535) as i32
+    };
+    (axis(x, screen[0], screen[2]), axis(y, screen[1], screen[3]))
+}
 
-    let inputs = [
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x,
-                    dy: norm_y,
-                    mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTDOWN,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x,
-                    dy: norm_y,
-                    mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTUP,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
+/// Mouse input that moves to screen point (x, y) on any monitor and then
+/// performs `flags` (button down/up), or just moves for empty flags.
+#[cfg(windows)]
+fn mouse_input_at(
+    x: i32,
+    y: i32,
+    flags: windows::Win32::UI::Input::KeyboardAndMouse::MOUSE_EVENT_FLAGS,
+) -> windows::Win32::UI::Input::KeyboardAndMouse::INPUT {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let (dx, dy) = absolute_coords(x, y, virtual_screen());
+    INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx,
+                dy,
+                mouseData: 0,
+                dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK | MOUSEEVENTF_MOVE | flags,
+                time: 0,
+                dwExtraInfo: 0,
             },
         },
-    ];
-
-    unsafe {
-        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
     }
 }
 
-#[cfg(not(windows))]
-fn handle_click(cmd: &Command, _config: &Config) -> CommandResult {
-    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
-    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
-    if name.is_empty() && automation_id.is_empty() {
-        let x = cmd.parameters.get("x").and_then(|v| v.as_i64()).unwrap_or(-1);
-        let y = cmd.parameters.get("y").and_then(|v| v.as_i64()).unwrap_or(-1);
-        if x < 0 || y < 0 {
-            return CommandResult::failure(&cmd.command_id, "click requires 'name', 'automation_id', or 'x'/'y' parameters");
+/// Screen point of `x`/`y` in `frame` (`[x, y, width, height]`), or of
+/// plain screen pixels without one. Normalized points are fractions (0..1)
+/// of the frame. Points outside the frame are rejected rather than clamped,
+/// since they would land on some other window.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn frame_point(x: f64, y: f64, frame: Option<[i32; 4]>, normalized: bool) -> Result<(i64, i64), String> {
+    if !x.is_finite() || !y.is_finite() {
+        return Err("'x' and 'y' must be finite numbers".to_string());
+    }
+    let Some([fx, fy, fw, fh]) = frame else {
+        if normalized {
+            return Err("normalized points need relative_to 'window', 'element' or 'monitor'".to_string());
+        }
+        return Ok((x.round() as i64, y.round() as i64));
+    };
+    let (dx, dy) = if normalized {
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+            return Err("normalized 'x' and 'y' must be between 0 and 1".to_string());
         }
+        // 1.0 is the far edge, whose last pixel is one less
+        ((x * f64::from(fw)).floor().min(f64::from(fw - 1)), (y * f64::from(fh)).floor().min(f64::from(fh - 1)))
+    } else {
+        (x.round(), y.round())
+    };
+    if dx < 0.0 || dy < 0.0 || dx >= f64::from(fw) || dy >= f64::from(fh) {
+        return Err(format!("point ({x}, {y}) is outside the {fw}x{fh} frame"));
     }
-    CommandResult::failure(&cmd.command_id, "click requires Windows")
+    Ok((i64::from(fx) + dx as i64, i64::from(fy) + dy as i64))
+}
+
+/// Index into `names` (primary monitor first) picked by a `monitor`
+/// parameter: a 0-based index, `"primary"`, or a device name such as
+/// `"DISPLAY2"` or `"\\.\DISPLAY2"`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn monitor_index(spec: &serde_json::Value, names: &[&str]) -> Option<usize> {
+    if let Some(index) = spec.as_u64() {
+        return usize::try_from(index).ok().filter(|i| *i < names.len());
+    }
+    let wanted = spec.as_str()?.trim();
+    if wanted.eq_ignore_ascii_case("primary") {
+        return (!names.is_empty()).then_some(0);
+    }
+    let short = |name: &str| name.trim_start_matches(['\\', '.']).to_string();
+    names.iter().position(|name| name.eq_ignore_ascii_case(wanted) || short(name).eq_ignore_ascii_case(&short(wanted)))
 }
 
+/// `name`/`automation_id` of the element a pointer command acts on. Both
+/// are empty when they only anchor `x`/`y` (`relative_to: "element"`).
 #[cfg(windows)]
-fn handle_type_text(cmd: &Command, config: &Config) -> CommandResult {
-    let text = cmd.parameters.get("text").and_then(|v| v.as_str()).unwrap_or("");
-    if text.is_empty() {
-        return CommandResult::failure(&cmd.command_id, "type_text requires 'text' parameter");
+fn element_target(cmd: &Command) -> (&str, &str) {
+    if cmd.parameters.get("relative_to").and_then(|v| v.as_str()) == Some("element") {
+        return ("", "");
     }
+    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+    (name, automation_id)
+}
 
-    // Try to find target element and use ValuePattern
-    let target = cmd.parameters.get("automation_id").and_then(|v| v.as_str());
+/// How a click result names its target: the element's name, else its
+/// automation ID, else its control type.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn clicked_name(name: &str, automation_id: &str, control_type: &str) -> String {
+    [name, automation_id, control_type].into_iter().find(|s| !s.is_empty()).unwrap_or_default().to_string()
+}
 
-    if let Some(target_id) = target {
-        if !target_id.is_empty() {
-            if let Some(_typed) = try_set_value(target_id, text) {
-                let mut result = HashMap::new();
-                result.insert("typed".to_string(), serde_json::Value::String(text.to_string()));
-                result.insert("method".to_string(), serde_json::Value::String("value_pattern".to_string()));
-                result.insert("target".to_string(), serde_json::Value::String(target_id.to_string()));
-                let mut cmd_result = CommandResult::success(&cmd.command_id, result);
-                cmd_result.screenshot_b64 = if config.enable_screenshot {
-                    crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
-                } else {
-                    None
-                };
-                return cmd_result;
+/// Screen rectangle that `relative_to` makes coordinates relative to:
+/// `None` for `"screen"`; the client area of the `hwnd`/`title`/`process`
+/// window (else the foreground one) for `"window"`; the `name`/
+/// `automation_id` element's bounds (the `index`th match) for `"element"`;
+/// the `monitor` display (else the window's) for `"monitor"`.
+#[cfg(windows)]
+fn origin_frame(cmd: &Command, config: &Config) -> Result<Option<[i32; 4]>, String> {
+    use windows::Win32::Foundation::{POINT, RECT};
+    use windows::Win32::Graphics::Gdi::ClientToScreen;
+    use windows::Win32::UI::WindowsAndMessaging::{GetClientRect, GetForegroundWindow};
+
+    let window = || {
+        if has_window_scope(cmd) {
+            resolve_window_target(cmd, config).map_err(|failed| failed.error.unwrap_or_default())
+        } else {
+            Ok(unsafe { GetForegroundWindow() })
+        }
+    };
+    match cmd.parameters.get("relative_to").and_then(|v| v.as_str()).unwrap_or("screen") {
+        "screen" => Ok(None),
+        "window" => {
+            let hwnd = window()?;
+            let mut rect = RECT::default();
+            let mut origin = POINT::default();
+            if hwnd.0 == 0
+                || unsafe { GetClientRect(hwnd, &mut rect) }.is_err()
+                || !unsafe { ClientToScreen(hwnd, &mut origin) }.as_bool()
+            {
+                return Err("could not determine the window's client area".to_string());
             }
+            Ok(Some([origin.x, origin.y, rect.right, rect.bottom]))
+        }
+        "element" => {
+            let matches = find_uia_elements(cmd, config).map_err(|failed| failed.error.unwrap_or_default())?;
+            let index = cmd.parameters.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+            let element = &matches[nth_match(matches.len(), index)?];
+            let r = unsafe { element.CurrentBoundingRectangle() }.map_err(|e| format!("bounding rect failed: {e}"))?;
+            Ok(Some([r.left, r.top, r.right - r.left, r.bottom - r.top]))
+        }
+        "monitor" => {
+            let Some(spec) = cmd.parameters.get("monitor") else {
+                return crate::screenshot::monitor_rect(window()?).map(Some).ok_or_else(|| "could not determine the monitor".to_string());
+            };
+            let monitors = crate::screenshot::monitors();
+            let names: Vec<&str> = monitors.iter().map(|m| m.name.as_str()).collect();
+            match monitor_index(spec, &names) {
+                Some(index) => Ok(Some(monitors[index].rect)),
+                None => Err(format!("no monitor {spec}; displays are {names:?} (0 is the primary)")),
+            }
+        }
+        other => Err(format!("unknown relative_to '{other}' (expected screen, window, element or monitor)")),
+    }
+}
+
+/// The screen point named by the `x_key`/`y_key` parameters: `None` when
+/// neither is given, an error when only one is or the point is off every
+/// monitor. They are screen pixels, which may be negative on monitors left
+/// of or above the primary, unless `relative_to` anchors them to a window,
+/// element or monitor (see `origin_frame`); `normalized` makes them
+/// fractions of it.
+#[cfg(windows)]
+fn resolve_point(cmd: &Command, config: &Config, x_key: &str, y_key: &str) -> Result<Option<(i32, i32)>, String> {
+    let coordinate = |key: &str| cmd.parameters.get(key).and_then(|v| v.as_f64());
+    let (x, y) = match (coordinate(x_key), coordinate(y_key)) {
+        (None, None) if !cmd.parameters.contains_key(x_key) && !cmd.parameters.contains_key(y_key) => return Ok(None),
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(format!("'{x_key}' and '{y_key}' must both be numbers")),
+    };
+    let normalized = cmd.parameters.get("normalized").and_then(|v| v.as_bool()).unwrap_or(false);
+    let (x, y) = frame_point(x, y, origin_frame(cmd, config)?, normalized)?;
+    let screen = virtual_screen();
+    if !on_screen(x, y, screen) {
+        return Err(format!("point ({x}, {y}) is outside the virtual screen {screen:?}"));
+    }
+    Ok(Some((x as i32, y as i32)))
+}
+
+/// The `x`/`y` point of a pointer command (see `resolve_point`), or the
+/// center of the element numbered `label` by `show_element_labels`.
+#[cfg(windows)]
+fn point_param(cmd: &Command, config: &Config) -> Result<Option<(i32, i32)>, String> {
+    if let Some(label) = cmd.parameters.get("label") {
+        let number = label.as_u64().ok_or("label must be a positive integer")?;
+        return crate::labels::center(number as usize)
+            .map(Some)
+            .ok_or_else(|| format!("no element labelled {number} is shown"));
+    }
+    resolve_point(cmd, config, "x", "y")
+}
+
+#[cfg(windows)]
+fn click_at(x: i32, y: i32) {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let inputs = [
+        mouse_input_at(x, y, MOUSEEVENTF_LEFTDOWN),
+        mouse_input_at(x, y, MOUSEEVENTF_LEFTUP),
+    ];
+
+    before_input();
+    unsafe {
+        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+/// Pause between the taps of a touch or pen double click.
+#[cfg(windows)]
+const DOUBLE_TAP_GAP: std::time::Duration = std::time::Duration::from_millis(80);
+/// Press-and-hold long enough for Windows to treat it as a right click.
+#[cfg(windows)]
+const LONG_PRESS: std::time::Duration = std::time::Duration::from_millis(1200);
+
+/// Tap `taps` times at screen point (x, y) with a touch or pen contact.
+#[cfg(windows)]
+fn tap_at(pointer: PointerKind, x: i32, y: i32, taps: usize) -> Result<(), String> {
+    let device = crate::pointer::SyntheticPointer::new(pointer, 1)?;
+    for tap in 0..taps {
+        if tap > 0 {
+            std::thread::sleep(DOUBLE_TAP_GAP);
+        }
+        device.play(&crate::pointer::press(x, y, 1))?;
+    }
+    Ok(())
+}
+
+/// Left-click at screen point (x, y), or tap there for touch and pen.
+#[cfg(windows)]
+fn click_or_tap(pointer: PointerKind, x: i32, y: i32) -> Result<(), String> {
+    if pointer == PointerKind::Mouse {
+        click_at(x, y);
+        Ok(())
+    } else {
+        tap_at(pointer, x, y, 1)
+    }
+}
+
+#[cfg(not(windows))]
+fn handle_click(cmd: &Command, _config: &Config) -> CommandResult {
+    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+    let control_type = cmd.parameters.get("control_type").and_then(|v| v.as_str()).unwrap_or("");
+    let has_point = cmd.parameters.get("x").is_some_and(|v| v.is_number()) && cmd.parameters.get("y").is_some_and(|v| v.is_number());
+    if name.is_empty() && automation_id.is_empty() && control_type.is_empty() && !has_point {
+        return CommandResult::failure(&cmd.command_id, "click requires 'name', 'automation_id', 'control_type', 'label', or 'x'/'y' parameters");
+    }
+    CommandResult::failure(&cmd.command_id, "click requires Windows")
+}
+
+/// Longest per-character `delay_ms` type_text accepts.
+#[cfg(windows)]
+const MAX_TYPE_DELAY_MS: u64 = 1000;
+
+/// Type `text` into `automation_id` or the `index`th `control_type` match
+/// (via ValuePattern when it has one) or else the focused control. `clear: true` empties the field first, `delay_ms` paces typed
+/// keystrokes for apps that drop fast input and `press_enter: true` submits
+/// afterwards, so filling and submitting a field is one command. The result
+/// describes the element that received the text (see `acted_element`).
+#[cfg(windows)]
+fn handle_type_text(cmd: &Command, config: &Config) -> CommandResult {
+    let text = cmd.parameters.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    let clear = cmd.parameters.get("clear").and_then(|v| v.as_bool()).unwrap_or(false);
+    let press_enter = cmd.parameters.get("press_enter").and_then(|v| v.as_bool()).unwrap_or(false);
+    if text.is_empty() && !clear && !press_enter {
+        return CommandResult::failure(&cmd.command_id, "type_text requires 'text' parameter");
+    }
+    let delay = cmd.parameters.get("delay_ms").and_then(|v| v.as_u64()).map(|ms| ms.min(MAX_TYPE_DELAY_MS));
+
+    // Try to find target element and use ValuePattern, which replaces the
+    // whole value and so clears it too. A `control_type` target (with
+    // `index`) must exist; without a settable value it gets focus so the
+    // keystrokes below reach it.
+    let target = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).filter(|id| !id.is_empty());
+    let control_type = cmd.parameters.get("control_type").and_then(|v| v.as_str()).filter(|t| !t.is_empty());
+    let (set_by_pattern, element) = if control_type.is_some() {
+        let element = match select_uia_element(cmd, config) {
+            Ok(element) => element,
+            Err(failed) => return *failed,
+        };
+        let set = set_element_value(&element, text, press_enter);
+        if !set {
+            if let Err(e) = unsafe { element.SetFocus() } {
+                return CommandResult::failure(&cmd.command_id, &format!("cannot focus the target: {e}"));
+            }
+        }
+        (set, Some(element))
+    } else {
+        match target.map(|target_id| try_set_value(cmd, config, target_id, text, press_enter)) {
+            Some(Err(failed)) => return *failed,
+            Some(Ok(element)) => (element.is_some(), element),
+            None => (false, None),
+        }
+    };
+    // What receives the text: the target, else the focused control
+    let described = element
+        .or_else(|| crate::uia::get_uia().and_then(|uia| unsafe { uia.GetFocusedElement() }.ok()))
+        .map(|element| acted_element(&element, config));
+
+    if !set_by_pattern {
+        // Fallback: SendInput key-by-key into the focused control
+        if clear {
+            if let Err(e) = press_keys("ctrl+a").and_then(|_| press_keys("delete")) {
+                return CommandResult::failure(&cmd.command_id, &e);
+            }
+        }
+        send_text_via_input(text, delay.map(std::time::Duration::from_millis));
+    }
+    if press_enter {
+        if let Err(e) = press_keys("enter") {
+            return CommandResult::failure(&cmd.command_id, &e);
         }
     }
 
-    // Fallback: SendInput key-by-key
-    send_text_via_input(text);
     let mut result = HashMap::new();
     result.insert("typed".to_string(), serde_json::Value::String(text.to_string()));
-    result.insert("method".to_string(), serde_json::Value::String("send_input".to_string()));
+    let method = if set_by_pattern { "value_pattern" } else { "send_input" };
+    result.insert("method".to_string(), serde_json::Value::String(method.to_string()));
+    if let Some(control_type) = control_type {
+        result.insert("control_type".to_string(), serde_json::json!(control_type));
+        result.insert("index".to_string(), serde_json::json!(cmd.parameters.get("index").and_then(|v| v.as_u64()).unwrap_or(0)));
+    } else if let Some(target_id) = target.filter(|_| set_by_pattern) {
+        result.insert("target".to_string(), serde_json::Value::String(target_id.to_string()));
+    }
+    if let Some(described) = described {
+        result.insert("element".to_string(), described);
+    }
+    result.insert("cleared".to_string(), serde_json::json!(clear));
+    result.insert("pressed_enter".to_string(), serde_json::json!(press_enter));
+    if let Some(delay) = delay {
+        result.insert("delay_ms".to_string(), serde_json::json!(delay));
+    }
     let mut cmd_result = CommandResult::success(&cmd.command_id, result);
     cmd_result.screenshot_b64 = if config.enable_screenshot {
         crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
@@ -429,36 +1329,64 @@ fn handle_type_text(cmd: &Command, config: &Config) -> CommandResult {
     cmd_result
 }
 
+/// Set the value of the element with `automation_id` (within the window the
+/// command names, if any) through ValuePattern, focusing it afterwards when
+/// `focus` (so a following Enter reaches it). Returns the element, or
+/// `Ok(None)` when it is missing or has no settable value.
 #[cfg(windows)]
-fn try_set_value(automation_id: &str, text: &str) -> Option<bool> {
+fn try_set_value(
+    cmd: &Command,
+    config: &Config,
+    automation_id: &str,
+    text: &str,
+    focus: bool,
+) -> Result<Option<windows::Win32::UI::Accessibility::IUIAutomationElement>, Box<CommandResult>> {
     use windows::Win32::UI::Accessibility::*;
     use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
 
     unsafe { let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED); }
 
-    let uia: IUIAutomation = unsafe {
-        windows::Win32::System::Com::CoCreateInstance(&CUIAutomation, None, windows::Win32::System::Com::CLSCTX_INPROC_SERVER).ok()?
+    let Ok(uia) = (unsafe {
+        windows::Win32::System::Com::CoCreateInstance::<_, IUIAutomation>(&CUIAutomation, None, windows::Win32::System::Com::CLSCTX_INPROC_SERVER)
+    }) else {
+        return Ok(None);
     };
-    let root = unsafe { uia.GetRootElement().ok()? };
+    let root = uia_search_root(cmd, config, &uia)?;
     let prop = UIA_AutomationIdPropertyId;
     let val = bstr_to_variant(automation_id);
-    let condition = unsafe { uia.CreatePropertyCondition(prop, val).ok()? };
-    let element = unsafe { root.FindFirst(TreeScope_Descendants, &condition).ok()? };
+    let found = unsafe { uia.CreatePropertyCondition(prop, val) }
+        .and_then(|condition| unsafe { root.FindFirst(TreeScope_Descendants, &condition) });
+    let Ok(element) = found else {
+        return Ok(None);
+    };
+    Ok(set_element_value(&element, text, focus).then_some(element))
+}
+
+/// Set `element`'s value through ValuePattern, focusing it afterwards when
+/// `focus`. False when it has no settable value.
+#[cfg(windows)]
+fn set_element_value(element: &windows::Win32::UI::Accessibility::IUIAutomationElement, text: &str, focus: bool) -> bool {
+    use windows::Win32::UI::Accessibility::*;
 
     let value_pattern: Result<IUIAutomationValuePattern, _> = unsafe {
         element.GetCurrentPatternAs(UIA_ValuePatternId)
     };
     if let Ok(vp) = value_pattern {
         let bstr = windows::core::BSTR::from(text);
+        before_input();
         if unsafe { vp.SetValue(&bstr) }.is_ok() {
-            return Some(true);
+            if focus {
+                let _ = unsafe { element.SetFocus() };
+            }
+            return true;
         }
     }
-    None
+    false
 }
 
+/// Type `text` with SendInput, waiting `delay` (default 2ms) per character.
 #[cfg(windows)]
-fn send_text_via_input(text: &str) {
+fn send_text_via_input(text: &str, delay: Option<std::time::Duration>) {
     use windows::Win32::UI::Input::KeyboardAndMouse::*;
 
     let chars: Vec<u16> = text.encode_utf16().collect();
@@ -489,11 +1417,15 @@ fn send_text_via_input(text: &str) {
                 },
             },
         ];
+        before_input();
         unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32); }
+        if crate::cancel::cancelled() {
+            break;
+        }
         // Small delay between characters so target apps can process each keystroke.
         // Without this, rapid-fire SendInput can overwhelm WinUI 3 apps (e.g. Win11 Notepad).
         if i + 1 < chars.len() {
-            std::thread::sleep(std::time::Duration::from_millis(2));
+            std::thread::sleep(delay.unwrap_or(std::time::Duration::from_millis(2)));
         }
     }
 }
@@ -503,14 +1435,104 @@ fn handle_type_text(cmd: &Command, _config: &Config) -> CommandResult {
     CommandResult::failure(&cmd.command_id, "type_text requires Windows")
 }
 
+/// Time the target gets to read the clipboard after Ctrl+V before the
+/// previous contents are put back; apps paste asynchronously.
 #[cfg(windows)]
-fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+const DEFAULT_PASTE_RESTORE_MS: u64 = 300;
+#[cfg(windows)]
+const MAX_PASTE_RESTORE_MS: u64 = 5000;
+
+/// Paste `text` with Ctrl+V: much faster than typing long text, and
+/// immune to keyboard layouts and autocomplete. The element matching
+/// `automation_id`/`name` (and `index`) is focused first, else the text goes
+/// to the focused control. The user's clipboard is restored after
+/// `restore_ms` unless something else wrote to it meanwhile.
+#[cfg(windows)]
+fn handle_paste_text(cmd: &Command, config: &Config) -> CommandResult {
+    let text = cmd.parameters.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    if text.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "paste_text requires 'text' parameter");
+    }
+    let press_enter = cmd.parameters.get("press_enter").and_then(|v| v.as_bool()).unwrap_or(false);
+    let restore_ms = cmd
+        .parameters
+        .get("restore_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_PASTE_RESTORE_MS)
+        .min(MAX_PASTE_RESTORE_MS);
+
+    let (name, automation_id) = element_target(cmd);
+    if !name.is_empty() || !automation_id.is_empty() {
+        let matches = match find_uia_elements(cmd, config) {
+            Ok(matches) => matches,
+            Err(failed) => return *failed,
+        };
+        let index = cmd.parameters.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+        let element = match nth_match(matches.len(), index) {
+            Ok(position) => &matches[position],
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        };
+        if let Err(e) = unsafe { element.SetFocus() } {
+            return CommandResult::failure(&cmd.command_id, &format!("cannot focus the target: {e}"));
+        }
+    }
+
+    let previous = match crate::clipboard::snapshot() {
+        Ok(previous) => previous,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let placed = match crate::clipboard::set_text(text) {
+        Ok(sequence) => sequence,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let pasted = press_keys("ctrl+v");
+    std::thread::sleep(std::time::Duration::from_millis(restore_ms));
+
+    // A copy made meanwhile (by the user or the target) wins over the snapshot
+    let restored = if crate::clipboard::sequence_number() != placed {
+        Ok(false)
+    } else {
+        crate::clipboard::restore(&previous).map(|_| true)
+    };
+    if let Err(e) = pasted {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+    let restored = match restored {
+        Ok(restored) => restored,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("pasted, but the clipboard was not restored: {e}")),
+    };
+    if press_enter {
+        if let Err(e) = press_keys("enter") {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+    }
 
-    let keys = cmd.parameters.get("keys").and_then(|v| v.as_str()).unwrap_or("");
-    if keys.is_empty() {
-        return CommandResult::failure(&cmd.command_id, "send_keys requires 'keys' parameter");
+    let mut result = HashMap::new();
+    result.insert("pasted_chars".to_string(), serde_json::json!(text.chars().count()));
+    result.insert("method".to_string(), serde_json::Value::String("clipboard".to_string()));
+    result.insert("clipboard_restored".to_string(), serde_json::json!(restored));
+    if previous.skipped > 0 {
+        result.insert("clipboard_formats_skipped".to_string(), serde_json::json!(previous.skipped));
     }
+    result.insert("pressed_enter".to_string(), serde_json::json!(press_enter));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_paste_text(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "paste_text requires Windows")
+}
+
+/// Modifiers and key of a combo such as "ctrl+c", "alt+f4" or "ctrl+win+d".
+#[cfg(windows)]
+fn parse_chord(keys: &str) -> Result<(Vec<VIRTUAL_KEY>, VIRTUAL_KEY), String> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
 
     // Parse modifier+key combos like "ctrl+c", "alt+f4", "ctrl+shift+s"
     let parts: Vec<&str> = keys.split('+').collect();
@@ -529,10 +1551,20 @@ fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
         }
     }
 
-    let vk = match key_code {
-        Some(k) => k,
-        None => return CommandResult::failure(&cmd.command_id, &format!("unknown key: {keys}")),
-    };
+    match key_code {
+        Some(vk) => Ok((modifiers, vk)),
+        // A modifier on its own, e.g. "alt" opening the menu bar
+        None if parts.len() == 1 && modifiers.len() == 1 => Ok((Vec::new(), modifiers[0])),
+        None => Err(format!("unknown key: {keys}")),
+    }
+}
+
+/// Press a modifier+key combo such as "ctrl+c", "alt+f4" or "ctrl+win+d".
+#[cfg(windows)]
+fn press_keys(keys: &str) -> Result<(), String> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let (modifiers, vk) = parse_chord(keys)?;
 
     // Press modifiers
     for m in &modifiers {
@@ -548,6 +1580,7 @@ fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
                 },
             },
         };
+        before_input();
         unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32); }
     }
 
@@ -576,6 +1609,7 @@ fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
             },
         },
     };
+    before_input();
     unsafe {
         SendInput(&[down], std::mem::size_of::<INPUT>() as i32);
         SendInput(&[up], std::mem::size_of::<INPUT>() as i32);
@@ -595,11 +1629,297 @@ fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
                 },
             },
         };
+        before_input();
         unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32); }
     }
 
+    Ok(())
+}
+
+/// Most chords and pauses one send_keys command may carry.
+const MAX_KEY_STEPS: usize = 50;
+/// Longest pause send_keys accepts, between chords or as a step.
+const MAX_KEY_PAUSE_MS: u64 = 5000;
+/// Pause between chords unless `delay_ms` says otherwise.
+#[cfg(windows)]
+const DEFAULT_CHORD_DELAY_MS: u64 = 30;
+
+/// One step of a send_keys sequence.
+#[derive(Debug, PartialEq)]
+enum KeyStep {
+    Chord(String),
+    Pause(u64),
+}
+
+/// The steps in a send_keys `keys` parameter: comma-separated chords
+/// (`"ctrl+a, delete, ctrl+v"`), or an array of chords and numbers, where a
+/// number pauses that many milliseconds (`["ctrl+a", 200, "delete"]`).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn key_steps(keys: Option<&serde_json::Value>) -> Result<Vec<KeyStep>, String> {
+    let chord = |text: &str| {
+        let text = text.trim();
+        if text.is_empty() {
+            Err("send_keys has an empty chord".to_string())
+        } else {
+            Ok(KeyStep::Chord(text.to_string()))
+        }
+    };
+    let steps = match keys {
+        Some(serde_json::Value::String(text)) if !text.trim().is_empty() => {
+            text.split(',').map(chord).collect::<Result<Vec<_>, _>>()?
+        }
+        Some(serde_json::Value::Array(items)) if !items.is_empty() => items
+            .iter()
+            .map(|item| match item {
+                serde_json::Value::String(text) => chord(text),
+                serde_json::Value::Number(ms) => ms
+                    .as_u64()
+                    .map(|ms| KeyStep::Pause(ms.min(MAX_KEY_PAUSE_MS)))
+                    .ok_or_else(|| format!("invalid pause in send_keys: {ms}")),
+                other => Err(format!("send_keys steps must be chords or pauses, got {other}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err("send_keys requires 'keys' parameter".to_string()),
+    };
+    if steps.len() > MAX_KEY_STEPS {
+        return Err(format!("send_keys takes at most {MAX_KEY_STEPS} steps"));
+    }
+    Ok(steps)
+}
+
+/// Localized key names UIA reports in `AcceleratorKey`/`AccessKey`
+/// (German, French, Spanish, Italian), folded to the names `parse_chord`
+/// knows. Keys are lowercase with spaces and dots removed.
+const LOCALIZED_KEY_NAMES: &[(&str, &str)] = &[
+    ("strg", "ctrl"),
+    ("ctl", "ctrl"),
+    ("umschalt", "shift"),
+    ("umschalttaste", "shift"),
+    ("maj", "shift"),
+    ("mayús", "shift"),
+    ("mayus", "shift"),
+    ("maiusc", "shift"),
+    ("entf", "delete"),
+    ("suppr", "delete"),
+    ("supr", "delete"),
+    ("canc", "delete"),
+    ("einfg", "insert"),
+    ("inser", "insert"),
+    ("pos1", "home"),
+    ("origine", "home"),
+    ("inicio", "home"),
+    ("ende", "end"),
+    ("fin", "end"),
+    ("fine", "end"),
+    ("bildauf", "pageup"),
+    ("pgpréc", "pageup"),
+    ("repág", "pageup"),
+    ("pgsu", "pageup"),
+    ("bildab", "pagedown"),
+    ("pgsuiv", "pagedown"),
+    ("avpág", "pagedown"),
+    ("pggiù", "pagedown"),
+    ("eingabe", "enter"),
+    ("entrée", "enter"),
+    ("entrar", "enter"),
+    ("intro", "enter"),
+    ("invio", "enter"),
+    ("échap", "escape"),
+    ("echap", "escape"),
+    ("rück", "backspace"),
+    ("rücktaste", "backspace"),
+    ("retourarrière", "backspace"),
+    ("retroceso", "backspace"),
+    ("leertaste", "space"),
+    ("leer", "space"),
+    ("espace", "space"),
+    ("espacio", "space"),
+    ("barraspaziatrice", "space"),
+    ("pfeilnachoben", "up"),
+    ("pfeilnachunten", "down"),
+    ("pfeilnachlinks", "left"),
+    ("pfeilnachrechts", "right"),
+    ("+", "plus"),
+    ("-", "minus"),
+];
+
+/// The chords of a UIA `AcceleratorKey` or `AccessKey` such as `"Strg+S"`,
+/// `"Ctrl++"` or `"Alt, F, S"`, in `send_keys` syntax (`"ctrl+s"`).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn accelerator_chords(shortcut: &str) -> Result<Vec<String>, String> {
+    let key_name = |token: &str| {
+        let folded: String = token.chars().filter(|c| !c.is_whitespace() && *c != '.').collect::<String>().to_lowercase();
+        if folded.is_empty() {
+            return Err(format!("malformed shortcut: {shortcut}"));
+        }
+        Ok(LOCALIZED_KEY_NAMES
+            .iter()
+            .find(|(name, _)| *name == folded)
+            .map_or(folded, |(_, key)| key.to_string()))
+    };
+    let chords = shortcut
+        .split(',')
+        .map(|chord| {
+            let chord = chord.trim();
+            // A trailing "+" after a separator is the plus key ("Ctrl++")
+            let (modifiers, key) = match chord.strip_suffix("++") {
+                Some(modifiers) => (Some(modifiers), "+"),
+                None if chord == "+" => (None, "+"),
+                None => match chord.rsplit_once('+') {
+                    Some((modifiers, key)) => (Some(modifiers), key),
+                    None => (None, chord),
+                },
+            };
+            let mut keys = match modifiers {
+                Some(modifiers) => modifiers.split('+').map(key_name).collect::<Result<Vec<_>, _>>()?,
+                None => Vec::new(),
+            };
+            keys.push(key_name(key)?);
+            Ok(keys.join("+"))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if chords.len() > MAX_KEY_STEPS {
+        return Err(format!("shortcut has more than {MAX_KEY_STEPS} chords: {shortcut}"));
+    }
+    Ok(chords)
+}
+
+/// Where an accelerator came from and the chords that trigger it.
+#[cfg(windows)]
+struct Accelerator {
+    source: &'static str,
+    shortcut: String,
+    chords: Vec<String>,
+}
+
+/// The shortcut of the element `select_uia_element` finds: its
+/// `AcceleratorKey` (e.g. `"Strg+S"` on a German build), else its
+/// `AccessKey`, as the application itself reports it. Brings the element's
+/// window to the foreground so the keys reach it.
+#[cfg(windows)]
+fn element_accelerator(cmd: &Command, config: &Config) -> Result<Accelerator, Box<CommandResult>> {
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let fail = |message: &str| Box::new(CommandResult::failure(&cmd.command_id, message));
+    let element = select_uia_element(cmd, config)?;
+    let text = |value: windows::core::Result<windows::core::BSTR>| {
+        value.map(crate::event::bstr_to_string).unwrap_or_default().trim().to_string()
+    };
+    let (source, shortcut) = [
+        ("accelerator_key", text(unsafe { element.CurrentAcceleratorKey() })),
+        ("access_key", text(unsafe { element.CurrentAccessKey() })),
+    ]
+    .into_iter()
+    .find(|(_, shortcut)| !shortcut.is_empty())
+    .ok_or_else(|| fail("element has no accelerator or access key"))?;
+    let chords = accelerator_chords(&shortcut).map_err(|e| fail(&e))?;
+    if let Some(e) = chords.iter().find_map(|chord| parse_chord(chord).err()) {
+        return Err(fail(&format!("cannot press shortcut '{shortcut}': {e}")));
+    }
+
+    if let Some(window) = element_window(&element) {
+        unsafe {
+            if GetForegroundWindow() != window {
+                if IsIconic(window).as_bool() {
+                    let _ = ShowWindow(window, SW_RESTORE);
+                }
+                simulate_alt_key();
+                let _ = SetForegroundWindow(window);
+                std::thread::sleep(std::time::Duration::from_millis(200));
+            }
+        }
+    }
+    Ok(Accelerator { source, shortcut, chords })
+}
+
+/// The top-level window an element belongs to: the root of the nearest
+/// ancestor-or-self with a window handle.
+#[cfg(windows)]
+fn element_window(
+    element: &windows::Win32::UI::Accessibility::IUIAutomationElement,
+) -> Option<windows::Win32::Foundation::HWND> {
+    use windows::Win32::UI::WindowsAndMessaging::{GetAncestor, GA_ROOT};
+
+    let walker = unsafe { crate::uia::get_uia()?.RawViewWalker().ok()? };
+    let mut current = element.clone();
+    loop {
+        if let Ok(hwnd) = unsafe { current.CurrentNativeWindowHandle() } {
+            if hwnd.0 != 0 {
+                let root = unsafe { GetAncestor(hwnd, GA_ROOT) };
+                return Some(if root.0 != 0 { root } else { hwnd });
+            }
+        }
+        current = unsafe { walker.GetParentElement(&current).ok()? };
+    }
+}
+
+/// Press one chord (`"ctrl+c"`) or a sequence of them (see `key_steps`),
+/// waiting `delay_ms` between consecutive chords. Every chord is checked
+/// before any key is pressed. With `mode: "accelerator"` the keys are the
+/// shortcut the application reports for the element named by
+/// `automation_id`/`name`/`control_type` (see `element_accelerator`), so
+/// "Save" triggers whatever the localized build binds it to.
+#[cfg(windows)]
+fn handle_send_keys(cmd: &Command, config: &Config) -> CommandResult {
+    let accelerator = match cmd.parameters.get("mode").and_then(|v| v.as_str()).unwrap_or("keys") {
+        "keys" => None,
+        "accelerator" => match element_accelerator(cmd, config) {
+            Ok(accelerator) => Some(accelerator),
+            Err(failed) => return *failed,
+        },
+        other => {
+            return CommandResult::failure(
+                &cmd.command_id,
+                &format!("unknown send_keys mode: {other} (expected 'keys' or 'accelerator')"),
+            )
+        }
+    };
+    let steps = match &accelerator {
+        Some(accelerator) => accelerator.chords.iter().cloned().map(KeyStep::Chord).collect(),
+        None => match key_steps(cmd.parameters.get("keys")) {
+            Ok(steps) => steps,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        },
+    };
+    for step in &steps {
+        if let KeyStep::Chord(keys) = step {
+            if let Err(e) = parse_chord(keys) {
+                return CommandResult::failure(&cmd.command_id, &e);
+            }
+        }
+    }
+    let delay_ms = cmd.parameters.get("delay_ms").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_CHORD_DELAY_MS).min(MAX_KEY_PAUSE_MS);
+
+    let mut chords = Vec::new();
+    let mut after_chord = false;
+    for step in &steps {
+        match step {
+            KeyStep::Chord(keys) => {
+                if after_chord && !crate::cancel::pause(std::time::Duration::from_millis(delay_ms)) {
+                    return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+                }
+                if let Err(e) = press_keys(keys) {
+                    return CommandResult::failure(&cmd.command_id, &e);
+                }
+                chords.push(keys.as_str());
+                after_chord = true;
+            }
+            KeyStep::Pause(ms) => {
+                if !crate::cancel::pause(std::time::Duration::from_millis(*ms)) {
+                    return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+                }
+                after_chord = false;
+            }
+        }
+    }
+
     let mut result = HashMap::new();
-    result.insert("keys".to_string(), serde_json::Value::String(keys.to_string()));
+    result.insert("keys".to_string(), serde_json::Value::String(chords.join(", ")));
+    result.insert("chords".to_string(), serde_json::json!(chords.len()));
+    if let Some(accelerator) = &accelerator {
+        result.insert("source".to_string(), serde_json::json!(accelerator.source));
+        result.insert("shortcut".to_string(), serde_json::json!(accelerator.shortcut));
+    }
     let mut cmd_result = CommandResult::success(&cmd.command_id, result);
     cmd_result.screenshot_b64 = if config.enable_screenshot {
         crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
@@ -640,7 +1960,22 @@ fn parse_vk(key: &str) -> Option<windows::Win32::UI::Input::KeyboardAndMouse::VI
         "f1" => Some(VK_F1), "f2" => Some(VK_F2), "f3" => Some(VK_F3), "f4" => Some(VK_F4),
         "f5" => Some(VK_F5), "f6" => Some(VK_F6), "f7" => Some(VK_F7), "f8" => Some(VK_F8),
         "f9" => Some(VK_F9), "f10" => Some(VK_F10), "f11" => Some(VK_F11), "f12" => Some(VK_F12),
-        _ => None,
+        "insert" | "ins" => Some(VK_INSERT),
+        "plus" => Some(VK_OEM_PLUS),
+        "minus" => Some(VK_OEM_MINUS),
+        other => {
+            // Any other single character, e.g. "ä", on the current keyboard layout
+            let mut chars = other.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+            let mut units = [0u16; 2];
+            let [unit] = c.encode_utf16(&mut units) else {
+                return None;
+            };
+            let scan = unsafe { VkKeyScanW(*unit) };
+            (scan != -1).then_some(VIRTUAL_KEY((scan & 0xFF) as u16))
+        }
     }
 }
 
@@ -649,6 +1984,9 @@ fn handle_send_keys(cmd: &Command, _config: &Config) -> CommandResult {
     CommandResult::failure(&cmd.command_id, "send_keys requires Windows")
 }
 
+/// Launch `application` through the shell. With `wait_for_window` (a title
+/// pattern) it waits up to `timeout_ms` for that window and reports it,
+/// instead of pausing a fixed half second.
 #[cfg(windows)]
 fn handle_open_application(cmd: &Command, config: &Config) -> CommandResult {
     use std::ffi::OsStr;
@@ -681,10 +2019,21 @@ fn handle_open_application(cmd: &Command, config: &Config) -> CommandResult {
         return CommandResult::failure(&cmd.command_id, &format!("ShellExecute failed with code {code}"));
     }
 
-    // Wait briefly for app to start
-    std::thread::sleep(std::time::Duration::from_millis(500));
-
     let mut res = HashMap::new();
+    match cmd.parameters.get("wait_for_window").and_then(|v| v.as_str()).filter(|t| !t.is_empty()) {
+        // Until the named window shows up, however long the app takes
+        Some(title) => {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_millis(cmd.timeout_ms);
+            let allow_self = crate::policy::self_targeting_allowed(cmd, config);
+            match poll_until(deadline, || Some(find_window(title, "", None, allow_self, config)).filter(|hwnd| hwnd.0 != 0)) {
+                Ok(Some(hwnd)) => res.extend(window_fields(hwnd)),
+                Ok(None) => return CommandResult::timed_out(&cmd.command_id, &cmd.action, cmd.timeout_ms),
+                Err(()) => return CommandResult::cancelled(&cmd.command_id, &cmd.action),
+            }
+        }
+        // Wait briefly for app to start
+        None => std::thread::sleep(std::time::Duration::from_millis(500)),
+    }
     res.insert("started".to_string(), serde_json::Value::String(app.to_string()));
     let mut cmd_result = CommandResult::success(&cmd.command_id, res);
     cmd_result.screenshot_b64 = if config.enable_screenshot {
@@ -700,316 +2049,780 @@ fn handle_open_application(cmd: &Command, _config: &Config) -> CommandResult {
     CommandResult::failure(&cmd.command_id, "open_application requires Windows")
 }
 
-/// Simulate an ALT key press+release via SendInput.
-///
-/// Windows prevents `SetForegroundWindow` from working unless the calling
-/// process already owns the foreground or was the last to receive user input.
-/// By injecting a synthetic ALT keystroke we satisfy the foreground-lock
-/// check so the subsequent `SetForegroundWindow` call actually succeeds.
+/// How often `wait_for_window` looks for the window.
 #[cfg(windows)]
-fn simulate_alt_key() {
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
-
-    let inputs = [
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VK_MENU,
-                    wScan: 0,
-                    dwFlags: KEYBD_EVENT_FLAGS(0),
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VK_MENU,
-                    wScan: 0,
-                    dwFlags: KEYEVENTF_KEYUP,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-    ];
-    unsafe {
-        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+const WINDOW_POLL_MS: u64 = 100;
+
+/// Whether `wait_for_window` waits for the window to close: its `state`
+/// parameter, `open` (the default) or `closed`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn wait_for_closed(cmd: &Command) -> Result<bool, String> {
+    match cmd.parameters.get("state").map(|v| v.as_str()) {
+        None | Some(Some("open")) => Ok(false),
+        Some(Some("closed")) => Ok(true),
+        _ => Err("state must be 'open' or 'closed'".to_string()),
     }
 }
 
+/// List the visible top-level windows with a title, front to back: the
+/// windows `focus_window` and the other window actions choose from. With
+/// `title` (or `window_title`), `process` and/or `pid` only the matches are
+/// listed, best first. DesktopAI's own windows are left out unless
+/// self-targeting is allowed.
 #[cfg(windows)]
-fn handle_focus_window(cmd: &Command, config: &Config) -> CommandResult {
-    use windows::Win32::Foundation::HWND;
-    use windows::Win32::UI::WindowsAndMessaging::*;
-    use windows::core::PCWSTR;
+fn handle_list_windows(cmd: &Command, config: &Config) -> CommandResult {
+    let title = ["title", "window_title"]
+        .iter()
+        .find_map(|key| cmd.parameters.get(*key).and_then(|v| v.as_str()))
+        .unwrap_or("");
+    let process = cmd.parameters.get("process").and_then(|v| v.as_str()).unwrap_or("");
+    let pid = match pid_param(cmd) {
+        Ok(pid) => pid,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let windows = find_windows(title, process, pid, crate::policy::self_targeting_allowed(cmd, config), config);
+    let foreground = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+    let mut result = HashMap::new();
+    result.insert("count".to_string(), serde_json::json!(windows.len()));
+    result.insert("windows".to_string(), serde_json::json!(windows));
+    result.insert("foreground_hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(foreground)));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_list_windows(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "list_windows requires Windows")
+}
+
+/// Scale factor of a DPI, 1.0 at 96 (100%), to two decimals.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn dpi_scale(dpi: u32) -> f64 {
+    (f64::from(dpi) / 96.0 * 100.0).round() / 100.0
+}
 
-    let title_pattern = cmd.parameters.get("title").and_then(|v| v.as_str()).unwrap_or("");
-    let process_pattern = cmd.parameters.get("process").and_then(|v| v.as_str()).unwrap_or("");
+/// The smallest `[x, y, width, height]` rect covering all of `rects`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn rect_union(rects: impl IntoIterator<Item = [i32; 4]>) -> Option<[i32; 4]> {
+    rects
+        .into_iter()
+        .map(|[x, y, w, h]| [x, y, x + w, y + h])
+        .reduce(|a, b| [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])])
+        .map(|[left, top, right, bottom]| [left, top, right - left, bottom - top])
+}
 
-    if title_pattern.is_empty() && process_pattern.is_empty() {
-        return CommandResult::failure(&cmd.command_id, "focus_window requires 'title' or 'process' parameter");
+/// Every display, primary first, indexed as the `monitor` parameter counts
+/// them: device name, screen `rect` and `work_area` (`[x, y, width,
+/// height]`, in the coordinates clicks use), effective `dpi` and `scale`,
+/// plus the `virtual_rect` spanning them all. Normalized detection
+/// coordinates are fractions of the rect of the monitor they were captured on.
+#[cfg(windows)]
+fn handle_list_monitors(cmd: &Command, _config: &Config) -> CommandResult {
+    let monitors = crate::screenshot::monitors();
+    if monitors.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "could not enumerate monitors");
     }
+    let listed: Vec<serde_json::Value> = monitors
+        .iter()
+        .enumerate()
+        .map(|(index, m)| {
+            serde_json::json!({
+                "index": index,
+                "name": m.name,
+                "primary": m.primary,
+                "rect": m.rect,
+                "work_area": m.work_area,
+                "dpi": m.dpi,
+                "scale": dpi_scale(m.dpi),
+            })
+        })
+        .collect();
+    let mut result = HashMap::new();
+    result.insert("count".to_string(), serde_json::json!(listed.len()));
+    result.insert("virtual_rect".to_string(), serde_json::json!(rect_union(monitors.iter().map(|m| m.rect))));
+    result.insert("monitors".to_string(), serde_json::Value::Array(listed));
+    CommandResult::success(&cmd.command_id, result)
+}
 
-    let pattern_lower = title_pattern.to_lowercase();
+#[cfg(not(windows))]
+fn handle_list_monitors(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "list_monitors requires Windows")
+}
 
-    // Iterate visible windows to find the best match.
-    // Score: 2 = word-boundary match (pattern not followed by alphanumeric),
-    //        1 = substring match. Higher score wins; ties broken by shorter title.
-    let mut target = HWND(0);
-    let mut best_score: u8 = 0;
-    let mut best_len = usize::MAX;
-
-    if !title_pattern.is_empty() {
-        let mut buf = [0u16; 512];
-        let mut current = unsafe { FindWindowW(PCWSTR::null(), PCWSTR::null()) };
-        while current.0 != 0 {
-            let len = unsafe { GetWindowTextW(current, &mut buf) };
-            if len > 0 {
-                let title = String::from_utf16_lossy(&buf[..len as usize]);
-                let title_lower = title.to_lowercase();
-                if let Some(pos) = title_lower.find(&pattern_lower) {
-                    if unsafe { IsWindowVisible(current) }.as_bool() {
-                        // Check if pattern ends at a word boundary (not followed by alphanumeric)
-                        let end = pos + pattern_lower.len();
-                        let is_word_boundary = end >= title_lower.len()
-                            || !title_lower[end..].starts_with(|c: char| c.is_alphanumeric() || c == '+');
-                        let score = if is_word_boundary { 2 } else { 1 };
-                        if score > best_score || (score == best_score && title.len() < best_len) {
-                            target = current;
-                            best_score = score;
-                            best_len = title.len();
-                        }
-                    }
-                }
-            }
-            current = unsafe { GetWindow(current, GW_HWNDNEXT) };
-            if current.0 == 0 { break; }
+/// Where the window named by `hwnd`/`title`/`process`/`pid` (see
+/// `resolve_window_target`), else the foreground window, is: its `rect` as
+/// drawn (without the invisible resize borders), `client_rect`, `dpi` and
+/// `scale`, whether it is minimized or maximized, and the `monitor` most of
+/// it is on, indexed as `list_monitors` lists them.
+#[cfg(windows)]
+fn handle_get_window_rect(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::{POINT, RECT};
+    use windows::Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS};
+    use windows::Win32::Graphics::Gdi::ClientToScreen;
+    use windows::Win32::UI::HiDpi::GetDpiForWindow;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let window = if has_window_scope(cmd) {
+        match resolve_window_target(cmd, config) {
+            Ok(window) => window,
+            Err(failed) => return *failed,
         }
+    } else {
+        unsafe { GetForegroundWindow() }
+    };
+    if window.0 == 0 {
+        return CommandResult::failure(&cmd.command_id, "no foreground window");
     }
 
-    if target.0 == 0 {
-        return CommandResult::failure(&cmd.command_id, &format!("window not found matching: {title_pattern}"));
+    let as_rect = |r: RECT| [r.left, r.top, r.right - r.left, r.bottom - r.top];
+    let mut frame = RECT::default();
+    let drawn = unsafe {
+        DwmGetWindowAttribute(
+            window,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut frame as *mut RECT as *mut std::ffi::c_void,
+            std::mem::size_of::<RECT>() as u32,
+        )
+    };
+    if drawn.is_err() && unsafe { GetWindowRect(window, &mut frame) }.is_err() {
+        return CommandResult::failure(&cmd.command_id, "could not read the window rect");
     }
+    let mut client = RECT::default();
+    let mut origin = POINT::default();
+    let client_rect = (unsafe { GetClientRect(window, &mut client) }.is_ok()
+        && unsafe { ClientToScreen(window, &mut origin) }.as_bool())
+    .then_some([origin.x, origin.y, client.right, client.bottom]);
+    let dpi = match unsafe { GetDpiForWindow(window) } {
+        0 => 96,
+        dpi => dpi,
+    };
 
-    // Restore if minimized, then use ALT trick to bypass foreground lock
-    unsafe {
-        if IsIconic(target).as_bool() {
-            let _ = ShowWindow(target, SW_RESTORE);
-        }
-        simulate_alt_key();
-        let _ = SetForegroundWindow(target);
+    let mut result = window_fields(window);
+    result.insert("rect".to_string(), serde_json::json!(as_rect(frame)));
+    result.insert("client_rect".to_string(), serde_json::json!(client_rect));
+    result.insert("dpi".to_string(), serde_json::json!(dpi));
+    result.insert("scale".to_string(), serde_json::json!(dpi_scale(dpi)));
+    result.insert("minimized".to_string(), serde_json::json!(unsafe { IsIconic(window) }.as_bool()));
+    result.insert("maximized".to_string(), serde_json::json!(unsafe { IsZoomed(window) }.as_bool()));
+    let monitors = crate::screenshot::monitors();
+    let name = crate::screenshot::window_monitor_name(window);
+    if let Some((index, m)) = monitors.iter().enumerate().find(|(_, m)| Some(&m.name) == name.as_ref()) {
+        result.insert(
+            "monitor".to_string(),
+            serde_json::json!({
+                "index": index,
+                "name": m.name,
+                "primary": m.primary,
+                "rect": m.rect,
+                "work_area": m.work_area,
+                "dpi": m.dpi,
+                "scale": dpi_scale(m.dpi),
+            }),
+        );
     }
+    CommandResult::success(&cmd.command_id, result)
+}
 
-    std::thread::sleep(std::time::Duration::from_millis(200));
+#[cfg(not(windows))]
+fn handle_get_window_rect(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "get_window_rect requires Windows")
+}
 
-    let mut result = HashMap::new();
-    result.insert("focused".to_string(), serde_json::Value::String(title_pattern.to_string()));
-    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
-    cmd_result.screenshot_b64 = if config.enable_screenshot {
-        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+/// Wait up to `timeout_ms` for a window whose title contains `title` (or
+/// `window_title`) and/or whose process matches `process` to appear (see
+/// `find_window`), or with `state: "closed"` for every such window, or the
+/// one named by `hwnd`, to go away. Returns the window's hwnd, title, pid
+/// and process, and how long the wait took; a `Timeout` failure when the
+/// time runs out first.
+#[cfg(windows)]
+fn handle_wait_for_window(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::IsWindow;
+
+    let closed = match wait_for_closed(cmd) {
+        Ok(closed) => closed,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let title = ["title", "window_title"]
+        .iter()
+        .find_map(|key| cmd.parameters.get(*key).and_then(|v| v.as_str()))
+        .unwrap_or("");
+    let process = cmd.parameters.get("process").and_then(|v| v.as_str()).unwrap_or("");
+    let hwnd = match cmd.parameters.get("hwnd") {
+        Some(value) if !closed => {
+            return CommandResult::failure(&cmd.command_id, &format!("hwnd {value} only applies to state 'closed'"))
+        }
+        Some(value) => match parse_hwnd_param(value) {
+            Some(raw) => Some(HWND(raw)),
+            None => return CommandResult::failure(&cmd.command_id, &format!("invalid hwnd {value}")),
+        },
+        None if title.is_empty() && process.is_empty() => {
+            return CommandResult::failure(&cmd.command_id, "wait_for_window requires 'title', 'process' or 'hwnd' parameter")
+        }
+        None => None,
+    };
+    let allow_self = crate::policy::self_targeting_allowed(cmd, config);
+    let started = std::time::Instant::now();
+    let deadline = started + std::time::Duration::from_millis(cmd.timeout_ms);
+
+    // Some(window) when it appeared, Some(None) when it closed
+    let waited = if closed {
+        poll_until(deadline, || {
+            let open = match hwnd {
+                Some(hwnd) => unsafe { IsWindow(hwnd) }.as_bool(),
+                None => find_window(title, process, None, allow_self, config).0 != 0,
+            };
+            (!open).then_some(None)
+        })
     } else {
-        None
+        poll_until(deadline, || Some(find_window(title, process, None, allow_self, config)).filter(|hwnd| hwnd.0 != 0).map(Some))
     };
-    cmd_result
+    let mut result = match waited {
+        Err(()) => return CommandResult::cancelled(&cmd.command_id, &cmd.action),
+        Ok(None) => return CommandResult::timed_out(&cmd.command_id, &cmd.action, cmd.timeout_ms),
+        Ok(Some(Some(window))) => window_fields(window),
+        Ok(Some(None)) => HashMap::from([("closed".to_string(), serde_json::json!(true))]),
+    };
+    result.insert("waited_ms".to_string(), serde_json::json!(started.elapsed().as_millis() as u64));
+    CommandResult::success(&cmd.command_id, result)
 }
 
 #[cfg(not(windows))]
-fn handle_focus_window(cmd: &Command, _config: &Config) -> CommandResult {
-    CommandResult::failure(&cmd.command_id, "focus_window requires Windows")
+fn handle_wait_for_window(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "wait_for_window requires Windows")
 }
 
-#[cfg(windows)]
-fn handle_scroll(cmd: &Command, config: &Config) -> CommandResult {
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
-    use windows::Win32::UI::WindowsAndMessaging::*;
-    use windows::Win32::Foundation::RECT;
+/// Default and longest quiet period `wait_for_ui_idle` waits for.
+#[cfg_attr(not(windows), allow(dead_code))]
+const UI_IDLE_DEFAULT_QUIET_MS: u64 = 500;
+#[cfg_attr(not(windows), allow(dead_code))]
+const UI_IDLE_MAX_QUIET_MS: u64 = 10_000;
+
+/// The `quiet_ms` parameter of `wait_for_ui_idle`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn ui_idle_quiet(cmd: &Command) -> Result<std::time::Duration, String> {
+    let quiet_ms = match cmd.parameters.get("quiet_ms") {
+        None => UI_IDLE_DEFAULT_QUIET_MS,
+        Some(value) => match value.as_u64() {
+            Some(ms) if (1..=UI_IDLE_MAX_QUIET_MS).contains(&ms) => ms,
+            _ => return Err(format!("quiet_ms must be between 1 and {UI_IDLE_MAX_QUIET_MS}")),
+        },
+    };
+    Ok(std::time::Duration::from_millis(quiet_ms))
+}
 
-    let direction = cmd.parameters.get("direction").and_then(|v| v.as_str()).unwrap_or("down");
-    let amount = cmd.parameters.get("amount").and_then(|v| v.as_i64()).unwrap_or(3) as i32;
+/// Wait up to `timeout_ms` until the foreground window has stopped
+/// changing for `quiet_ms` (default 500): no UI events from it and, with
+/// screenshots enabled, no repaint (see settle.rs). Returns the window's
+/// hwnd, title, pid and process, how long the wait took and which signals
+/// were watched; a `Timeout` failure when the UI is still busy at the end.
+#[cfg(windows)]
+fn handle_wait_for_ui_idle(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
 
-    // WHEEL_DELTA is 120 per "click"; positive = up, negative = down
-    let wheel_delta = match direction {
-        "up" => 120 * amount,
-        "down" => -120 * amount,
-        _ => return CommandResult::failure(&cmd.command_id, &format!("unknown scroll direction: {direction}")),
+    let quiet = match ui_idle_quiet(cmd) {
+        Ok(quiet) => quiet,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
     };
+    let events = crate::settle::UiEvents::start();
+    if events.is_none() {
+        log::warn!("wait_for_ui_idle: UI event hook unavailable, watching the screen only");
+    }
+    let started = std::time::Instant::now();
+    let deadline = started + std::time::Duration::from_millis(cmd.timeout_ms);
+    let mut settle = crate::settle::Settle::new(quiet, started);
+    let mut window = HWND(0);
+
+    let waited = poll_until(deadline, || {
+        window = unsafe { GetForegroundWindow() };
+        let count = events.as_ref().map_or(0, |events| events.pump());
+        let frame = if config.enable_screenshot { window_frame(window) } else { None };
+        settle.update(std::time::Instant::now(), window.0, count, frame).then_some(())
+    });
+    match waited {
+        Err(()) => return CommandResult::cancelled(&cmd.command_id, &cmd.action),
+        Ok(None) => return CommandResult::timed_out(&cmd.command_id, &cmd.action, cmd.timeout_ms),
+        Ok(Some(())) => {}
+    }
+    let mut result = if window.0 != 0 { window_fields(window) } else { HashMap::new() };
+    result.insert("waited_ms".to_string(), serde_json::json!(started.elapsed().as_millis() as u64));
+    result.insert("quiet_ms".to_string(), serde_json::json!(quiet.as_millis() as u64));
+    result.insert("ui_events".to_string(), serde_json::json!(events.is_some()));
+    result.insert("screen_checked".to_string(), serde_json::json!(config.enable_screenshot));
+    CommandResult::success(&cmd.command_id, result)
+}
 
-    // Move cursor to the center of the foreground window first.
-    // MOUSEEVENTF_WHEEL delivers to the window under the cursor, NOT the
-    // focused window, so we must position the cursor over the target.
-    let fg = unsafe { GetForegroundWindow() };
-    if fg.0 != 0 {
-        let mut rect = RECT::default();
-        if unsafe { GetWindowRect(fg, &mut rect) }.is_ok() {
-            let cx = (rect.left + rect.right) / 2;
-            let cy = (rect.top + rect.bottom) / 2;
-            // Convert to absolute coordinates (0..65535 range)
-            let screen_w = unsafe { GetSystemMetrics(SM_CXSCREEN) };
-            let screen_h = unsafe { GetSystemMetrics(SM_CYSCREEN) };
-            if screen_w > 0 && screen_h > 0 {
-                let abs_x = (cx as i64 * 65536 / screen_w as i64) as i32;
-                let abs_y = (cy as i64 * 65536 / screen_h as i64) as i32;
-                let move_input = INPUT {
-                    r#type: INPUT_MOUSE,
-                    Anonymous: INPUT_0 {
-                        mi: MOUSEINPUT {
-                            dx: abs_x,
-                            dy: abs_y,
-                            mouseData: 0,
-                            dwFlags: MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                unsafe { SendInput(&[move_input], std::mem::size_of::<INPUT>() as i32); }
-                std::thread::sleep(std::time::Duration::from_millis(10));
-            }
+#[cfg(not(windows))]
+fn handle_wait_for_ui_idle(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "wait_for_ui_idle requires Windows")
+}
+
+/// A coarse frame of `window` for `wait_for_ui_idle` to compare; `None`
+/// when it is minimized or cannot be captured.
+#[cfg(windows)]
+fn window_frame(window: windows::Win32::Foundation::HWND) -> Option<Vec<u8>> {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetWindowRect, IsIconic};
+
+    let mut rect = RECT::default();
+    if window.0 == 0 || unsafe { IsIconic(window) }.as_bool() || unsafe { GetWindowRect(window, &mut rect) }.is_err() {
+        return None;
+    }
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let (max_width, max_height) = crate::settle::FRAME_SIZE;
+    crate::screenshot::capture_rect_pixels([rect.left, rect.top, width, height], max_width, max_height)
+        .map(|(_, _, pixels)| pixels)
+}
+
+/// Call `check` every `WINDOW_POLL_MS` until it returns something, or
+/// `None` once `deadline` passes. `Err` if the command is cancelled.
+#[cfg(windows)]
+fn poll_until<T>(deadline: std::time::Instant, mut check: impl FnMut() -> Option<T>) -> Result<Option<T>, ()> {
+    loop {
+        if let Some(found) = check() {
+            return Ok(Some(found));
+        }
+        if std::time::Instant::now() >= deadline {
+            return Ok(None);
+        }
+        if !crate::cancel::pause(std::time::Duration::from_millis(WINDOW_POLL_MS)) {
+            return Err(());
         }
     }
+}
 
-    let input = INPUT {
-        r#type: INPUT_MOUSE,
-        Anonymous: INPUT_0 {
-            mi: MOUSEINPUT {
-                dx: 0,
-                dy: 0,
-                mouseData: wheel_delta as u32,
-                dwFlags: MOUSEEVENTF_WHEEL,
-                time: 0,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-    unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32); }
+/// The hwnd, title, pid and process of a top-level window, for results.
+#[cfg(windows)]
+fn window_fields(hwnd: windows::Win32::Foundation::HWND) -> HashMap<String, serde_json::Value> {
+    use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
 
-    let mut result = HashMap::new();
-    result.insert("direction".to_string(), serde_json::Value::String(direction.to_string()));
-    result.insert("amount".to_string(), serde_json::json!(amount));
-    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
-    cmd_result.screenshot_b64 = if config.enable_screenshot {
-        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
-    } else {
-        None
-    };
-    cmd_result
+    let mut pid: u32 = 0;
+    unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    }
+    HashMap::from([
+        ("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(hwnd))),
+        ("title".to_string(), serde_json::json!(crate::windows::window_title(hwnd))),
+        ("pid".to_string(), serde_json::json!(pid)),
+        ("process_exe".to_string(), serde_json::json!(crate::windows::process_path(pid))),
+    ])
 }
 
-#[cfg(not(windows))]
-fn handle_scroll(cmd: &Command, _config: &Config) -> CommandResult {
-    CommandResult::failure(&cmd.command_id, "scroll requires Windows")
+/// Outcome of one `self_test` step, shaped like the backend's selftest checks.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn self_test_check(ok: bool, detail: impl Into<String>) -> serde_json::Value {
+    serde_json::json!({ "ok": ok, "detail": detail.into() })
 }
 
-/// Resolve a UIA element by name or automation_id and return its bounding rect center.
+/// Open the collector's own test window (see selftest.rs), drive it through
+/// the click, type_text and scroll handlers and verify each result, so the
+/// backend can check the collector can automate before a real task. Reports
+/// `checks` per capability and `passed` when all of them succeeded.
 #[cfg(windows)]
-fn resolve_uia_coords(name: &str, automation_id: &str) -> Option<(i32, i32)> {
-    use windows::Win32::UI::Accessibility::*;
-    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+fn handle_self_test(cmd: &Command, config: &Config) -> CommandResult {
+    use crate::selftest::{TestWindow, BUTTON_ID, EDIT_ID, LOG_ID};
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetCursorPos, GetForegroundWindow, SetCursorPos, SetForegroundWindow};
+
+    const TYPED: &str = "DesktopAI self-test";
+    let settle = || std::thread::sleep(std::time::Duration::from_millis(300));
+    let step = |action: &str, parameters: serde_json::Value| {
+        let mut parameters: HashMap<String, serde_json::Value> = serde_json::from_value(parameters).unwrap_or_default();
+        parameters.insert("allow_self".to_string(), serde_json::json!(true));
+        let sub = Command {
+            command_id: format!("{}-{action}", cmd.command_id),
+            action: action.to_string(),
+            parameters,
+            timeout_ms: cmd.timeout_ms,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        // No screenshots for the intermediate steps
+        let quiet = Config { enable_screenshot: false, ..config.clone() };
+        dispatch_action(&sub, &quiet)
+    };
 
-    unsafe { let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED); }
+    let previous_foreground = unsafe { GetForegroundWindow() };
+    let mut cursor = POINT::default();
+    let cursor_saved = unsafe { GetCursorPos(&mut cursor) }.is_ok();
 
-    let uia: IUIAutomation = unsafe {
-        windows::Win32::System::Com::CoCreateInstance(
-            &CUIAutomation, None,
-            windows::Win32::System::Com::CLSCTX_INPROC_SERVER,
-        ).ok()?
+    let mut checks = serde_json::Map::new();
+    let window = match TestWindow::open() {
+        Ok(window) => window,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
     };
-    let root = unsafe { uia.GetRootElement().ok()? };
-
-    let condition = if !automation_id.is_empty() {
-        unsafe { uia.CreatePropertyCondition(UIA_AutomationIdPropertyId, bstr_to_variant(automation_id)).ok()? }
-    } else {
-        unsafe { uia.CreatePropertyCondition(UIA_NamePropertyId, bstr_to_variant(name)).ok()? }
+    let hwnd = crate::event::hwnd_to_hex(window.hwnd());
+    settle();
+    unsafe {
+        simulate_alt_key();
+        let _ = SetForegroundWindow(window.hwnd());
+    }
+    settle();
+    let focused = unsafe { GetForegroundWindow() } == window.hwnd();
+    checks.insert(
+        "focus".to_string(),
+        self_test_check(focused, if focused { "test window is in the foreground" } else { "could not bring the test window to the foreground" }),
+    );
+
+    // UIA: the button must be found inside the test window
+    let found = step("find_elements", serde_json::json!({ "automation_id": BUTTON_ID.to_string(), "hwnd": hwnd }));
+    let count = found.result.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
+    checks.insert(
+        "uia".to_string(),
+        self_test_check(found.ok && count == 1, found.error.clone().unwrap_or_else(|| format!("{count} button(s) found"))),
+    );
+
+    // UIA invoke, then a real mouse click at the button's center
+    let invoked = step("click", serde_json::json!({ "automation_id": BUTTON_ID.to_string(), "hwnd": hwnd, "index": 0 }));
+    settle();
+    let after_invoke = window.clicks();
+    checks.insert(
+        "invoke".to_string(),
+        self_test_check(invoked.ok && after_invoke == 1, invoked.error.clone().unwrap_or_else(|| format!("button saw {after_invoke} click(s)"))),
+    );
+    let mouse = match window.control_center(BUTTON_ID) {
+        Some((x, y)) => {
+            let clicked = step("click", serde_json::json!({ "x": x, "y": y }));
+            settle();
+            let seen = window.clicks() - after_invoke;
+            self_test_check(clicked.ok && seen == 1, clicked.error.unwrap_or_else(|| format!("button saw {seen} click(s)")))
+        }
+        None => self_test_check(false, "button has no screen rect"),
+    };
+    checks.insert("mouse_click".to_string(), mouse);
+
+    // Keyboard: focus the edit with a click, type, read the value back over UIA
+    let typing = match window.control_center(EDIT_ID) {
+        Some((x, y)) => {
+            step("click", serde_json::json!({ "x": x, "y": y }));
+            settle();
+            let typed = step("type_text", serde_json::json!({ "text": TYPED }));
+            settle();
+            let value = crate::selftest::uia_value(window.control(EDIT_ID));
+            let ok = typed.ok && value.as_deref() == Some(TYPED);
+            self_test_check(ok, typed.error.unwrap_or_else(|| format!("edit reads {:?}", value.unwrap_or_default())))
+        }
+        None => self_test_check(false, "edit has no screen rect"),
+    };
+    checks.insert("type".to_string(), typing);
+
+    // Wheel: scroll the log down and confirm it moved
+    let scrolling = match window.control_center(LOG_ID) {
+        Some((x, y)) => {
+            let scrolled = step("scroll", serde_json::json!({ "direction": "down", "amount": 3, "x": x, "y": y }));
+            settle();
+            let line = window.first_visible_line();
+            self_test_check(scrolled.ok && line > 0, scrolled.error.unwrap_or_else(|| format!("first visible line is {line}")))
+        }
+        None => self_test_check(false, "log has no screen rect"),
     };
+    checks.insert("scroll".to_string(), scrolling);
+
+    drop(window);
+    unsafe {
+        if cursor_saved {
+            let _ = SetCursorPos(cursor.x, cursor.y);
+        }
+        if previous_foreground.0 != 0 {
+            let _ = SetForegroundWindow(previous_foreground);
+        }
+    }
 
-    let element = unsafe { root.FindFirst(TreeScope_Descendants, &condition).ok()? };
-    let rect = unsafe { element.CurrentBoundingRectangle().ok()? };
-    Some(((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2))
+    let failed: Vec<String> = checks
+        .iter()
+        .filter(|(_, check)| check["ok"] != true)
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut result = HashMap::new();
+    result.insert("passed".to_string(), serde_json::json!(failed.is_empty()));
+    result.insert("failed".to_string(), serde_json::json!(failed));
+    result.insert("checks".to_string(), serde_json::Value::Object(checks));
+    CommandResult::success(&cmd.command_id, result)
 }
 
-#[cfg(windows)]
-fn handle_double_click(cmd: &Command, config: &Config) -> CommandResult {
-    // Support name-based UIA resolution (same as click), with x/y fallback
-    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
-    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+#[cfg(not(windows))]
+fn handle_self_test(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "self_test requires Windows")
+}
 
-    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
-        match resolve_uia_coords(name, automation_id) {
-            Some(coords) => coords,
-            None => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+/// Processes whose termination crashes or logs off Windows; never ended.
+const PROTECTED_PROCESSES: &[&str] = &[
+    "system",
+    "registry",
+    "smss.exe",
+    "csrss.exe",
+    "wininit.exe",
+    "winlogon.exe",
+    "services.exe",
+    "lsass.exe",
+    "lsaiso.exe",
+    "svchost.exe",
+    "dwm.exe",
+    "fontdrvhost.exe",
+];
+
+/// Grace period before `kill_process` escalates to TerminateProcess.
+const DEFAULT_END_PROCESS_GRACE_MS: u64 = 3000;
+const MAX_END_PROCESS_GRACE_MS: u64 = 60_000;
+/// Part of the command's `timeout_ms` kept back from the grace period for
+/// terminating what is left and reporting.
+const END_PROCESS_HEADROOM_MS: u64 = 1500;
+
+/// The `grace_ms` of an end-process command: how long windows get to close
+/// before escalation, capped so it ends before the command's own deadline.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn end_process_grace_ms(cmd: &Command) -> u64 {
+    cmd.parameters
+        .get("grace_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_END_PROCESS_GRACE_MS)
+        .min(MAX_END_PROCESS_GRACE_MS)
+        .min(cmd.timeout_ms.saturating_sub(END_PROCESS_HEADROOM_MS))
+}
+
+/// Whether `process_exe` is the image `name` (case-insensitive; `.exe` may
+/// be left off).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn image_matches(process_exe: &str, name: &str) -> bool {
+    let file_name = process_exe.rsplit(['\\', '/']).next().unwrap_or(process_exe);
+    let name = name.trim();
+    !name.is_empty()
+        && (file_name.eq_ignore_ascii_case(name)
+            || (!name.contains('.') && file_name.eq_ignore_ascii_case(&format!("{name}.exe"))))
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn is_protected_process(process_exe: &str) -> bool {
+    PROTECTED_PROCESSES.iter().any(|name| image_matches(process_exe, name))
+}
+
+/// End processes picked by `pid` or image `name`: WM_CLOSE to their windows
+/// first, then, once `grace_ms` passes, TerminateProcess for any still
+/// running. `close_application` only escalates with `force: true`;
+/// `kill_process` does unless `force: false`. Processes without windows
+/// cannot be closed gracefully and are terminated straight away. A cancelled
+/// command stops waiting and terminates nothing further.
+#[cfg(windows)]
+fn handle_end_process(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND, LPARAM, WPARAM};
+    use windows::Win32::System::ProcessStatus::EnumProcesses;
+    use windows::Win32::System::Threading::*;
+    use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_CLOSE};
+
+    let force = cmd.parameters.get("force").and_then(|v| v.as_bool()).unwrap_or(cmd.action == "kill_process");
+    let grace_ms = end_process_grace_ms(cmd);
+
+    let pids: Vec<u32> = if let Some(pid) = cmd.parameters.get("pid") {
+        match pid.as_u64().and_then(|pid| u32::try_from(pid).ok()) {
+            Some(pid) => vec![pid],
+            None => return CommandResult::failure(&cmd.command_id, "pid must be a process id"),
         }
     } else {
-        let x = cmd.parameters.get("x").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        let y = cmd.parameters.get("y").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        if x < 0 || y < 0 {
-            return CommandResult::failure(&cmd.command_id, "double_click requires 'name', 'automation_id', or 'x'/'y' parameters");
+        let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        if name.trim().is_empty() {
+            return CommandResult::failure(&cmd.command_id, &format!("{} requires 'pid' or 'name' parameter", cmd.action));
+        }
+        let mut all = vec![0u32; 4096];
+        let mut needed = 0u32;
+        if let Err(e) = unsafe { EnumProcesses(all.as_mut_ptr(), (all.len() * 4) as u32, &mut needed) } {
+            return CommandResult::failure(&cmd.command_id, &format!("EnumProcesses failed: {e}"));
         }
-        (x, y)
+        all.truncate(needed as usize / 4);
+        all.into_iter()
+            .filter(|&pid| pid != 0 && image_matches(&crate::windows::process_path(pid), name))
+            .collect()
     };
+    if pids.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "no running process matches");
+    }
 
-    // Move + double left-click using SendInput
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    let own_pid = unsafe { GetCurrentProcessId() };
+    let allow_self = crate::policy::self_targeting_allowed(cmd, config);
+    let targets: Vec<(u32, String)> = pids.into_iter().map(|pid| (pid, crate::windows::process_path(pid))).collect();
+    for (pid, exe) in &targets {
+        if *pid <= 4 || is_protected_process(exe) {
+            return CommandResult::denied(&cmd.command_id, &format!("'{}' may not end system process {pid}", cmd.action));
+        }
+        if *pid == own_pid || (!allow_self && crate::policy::is_self_process(exe, config)) {
+            return CommandResult::denied(
+                &cmd.command_id,
+                &format!("'{}' targets a DesktopAI process; set allow_self to permit it", cmd.action),
+            );
+        }
+    }
+
+    // Ask every window of the targets to close
+    let mut windowed = std::collections::HashSet::new();
+    for window in crate::windows::enumerate_top_level_windows().unwrap_or_default() {
+        if targets.iter().any(|(pid, _)| *pid == window.pid) {
+            if let Some(raw) = parse_hwnd_param(&serde_json::json!(window.hwnd)) {
+                if unsafe { PostMessageW(HWND(raw), WM_CLOSE, WPARAM(0), LPARAM(0)) }.is_ok() {
+                    windowed.insert(window.pid);
+                }
+            }
+        }
+    }
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(grace_ms);
+    let cancel = crate::cancel::current();
+    let mut processes = Vec::new();
+    let mut all_exited = true;
+    for (pid, exe) in &targets {
+        let access = PROCESS_SYNCHRONIZE | if force { PROCESS_TERMINATE } else { PROCESS_ACCESS_RIGHTS(0) };
+        let handle: Option<HANDLE> = unsafe { OpenProcess(access, false, *pid) }.ok().filter(|h| !h.is_invalid());
+        let status = match handle {
+            None => "access_denied",
+            Some(handle) => {
+                let wait_ms = if windowed.contains(pid) {
+                    deadline.saturating_duration_since(std::time::Instant::now()).as_millis() as u32
+                } else {
+                    0
+                };
+                let status = if wait_for_exit(handle, wait_ms) {
+                    "closed"
+                } else if force && !cancel.is_cancelled() && unsafe { TerminateProcess(handle, 1) }.is_ok() {
+                    let _ = unsafe { WaitForSingleObject(handle, 1000) };
+                    "terminated"
+                } else {
+                    "running"
+                };
+                unsafe {
+                    let _ = CloseHandle(handle);
+                }
+                status
+            }
+        };
+        all_exited &= status == "closed" || status == "terminated";
+        processes.push(serde_json::json!({
+            "pid": pid,
+            "name": exe.rsplit(['\\', '/']).next().unwrap_or(exe),
+            "status": status,
+        }));
+    }
+
+    let mut result = HashMap::new();
+    result.insert("processes".to_string(), serde_json::json!(processes));
+    result.insert("all_exited".to_string(), serde_json::json!(all_exited));
+    if cancel.is_cancelled() {
+        let mut cancelled = CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        cancelled.result = result;
+        return cancelled;
+    }
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+/// Wait up to `wait_ms` for a process handle to be signaled, giving up
+/// early if the command is cancelled.
+#[cfg(windows)]
+fn wait_for_exit(handle: windows::Win32::Foundation::HANDLE, wait_ms: u32) -> bool {
+    use windows::Win32::Foundation::WAIT_OBJECT_0;
+    use windows::Win32::System::Threading::WaitForSingleObject;
+
+    let cancel = crate::cancel::current();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(u64::from(wait_ms));
+    loop {
+        let slice = deadline.saturating_duration_since(std::time::Instant::now()).as_millis().min(50) as u32;
+        if unsafe { WaitForSingleObject(handle, slice) } == WAIT_OBJECT_0 {
+            return true;
+        }
+        if cancel.is_cancelled() || std::time::Instant::now() >= deadline {
+            return false;
+        }
+    }
+}
 
-    let screen_w = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CXSCREEN) };
-    let screen_h = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CYSCREEN) };
+#[cfg(not(windows))]
+fn handle_end_process(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
 
-    let norm_x = (x as i64 * 65535 / screen_w as i64) as i32;
-    let norm_y = (y as i64 * 65535 / screen_h as i64) as i32;
+/// Simulate an ALT key press+release via SendInput.
+///
+/// Windows prevents `SetForegroundWindow` from working unless the calling
+/// process already owns the foreground or was the last to receive user input.
+/// By injecting a synthetic ALT keystroke we satisfy the foreground-lock
+/// check so the subsequent `SetForegroundWindow` call actually succeeds.
+#[cfg(windows)]
+fn simulate_alt_key() {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
 
     let inputs = [
-        // First click
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTDOWN,
-                    time: 0, dwExtraInfo: 0,
-                },
-            },
-        },
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTUP,
-                    time: 0, dwExtraInfo: 0,
-                },
-            },
-        },
-        // Second click
         INPUT {
-            r#type: INPUT_MOUSE,
+            r#type: INPUT_KEYBOARD,
             Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTDOWN,
-                    time: 0, dwExtraInfo: 0,
+                ki: KEYBDINPUT {
+                    wVk: VK_MENU,
+                    wScan: 0,
+                    dwFlags: KEYBD_EVENT_FLAGS(0),
+                    time: 0,
+                    dwExtraInfo: 0,
                 },
             },
         },
         INPUT {
-            r#type: INPUT_MOUSE,
+            r#type: INPUT_KEYBOARD,
             Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_LEFTUP,
-                    time: 0, dwExtraInfo: 0,
+                ki: KEYBDINPUT {
+                    wVk: VK_MENU,
+                    wScan: 0,
+                    dwFlags: KEYEVENTF_KEYUP,
+                    time: 0,
+                    dwExtraInfo: 0,
                 },
             },
         },
     ];
+    before_input();
+    unsafe {
+        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+    }
+}
 
-    unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32); }
+/// Bring a window picked by `hwnd`, `title` (or `window_title`), `process`
+/// and/or `pid` to the foreground (see `resolve_window_target`), restoring
+/// it if minimized. Reports the window and whether it is now in front;
+/// Windows may refuse to hand over the foreground. When several windows
+/// matched, the best one is focused and all of them are listed as
+/// `candidates`, best first, so the caller can pick another by `hwnd`.
+#[cfg(windows)]
+fn handle_focus_window(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::WindowsAndMessaging::*;
 
-    let mut result = HashMap::new();
-    result.insert("x".to_string(), serde_json::json!(x));
-    result.insert("y".to_string(), serde_json::json!(y));
+    let (target, candidates) = match resolve_window_candidates(cmd, config) {
+        Ok(resolved) => resolved,
+        Err(failure) => return *failure,
+    };
+
+    // Restore if minimized, then use ALT trick to bypass foreground lock
+    unsafe {
+        if IsIconic(target).as_bool() {
+            let _ = ShowWindow(target, SW_RESTORE);
+        }
+        simulate_alt_key();
+        let _ = SetForegroundWindow(target);
+    }
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    let mut result = window_fields(target);
+    result.insert("focused".to_string(), result["title"].clone());
+    result.insert("foreground".to_string(), serde_json::json!(unsafe { GetForegroundWindow() } == target));
+    if candidates.len() > 1 {
+        result.insert("candidates".to_string(), serde_json::json!(candidates));
+    }
     let mut cmd_result = CommandResult::success(&cmd.command_id, result);
     cmd_result.screenshot_b64 = if config.enable_screenshot {
         crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
@@ -1020,219 +2833,4430 @@ fn handle_double_click(cmd: &Command, config: &Config) -> CommandResult {
 }
 
 #[cfg(not(windows))]
-fn handle_double_click(cmd: &Command, _config: &Config) -> CommandResult {
-    CommandResult::failure(&cmd.command_id, "double_click requires Windows")
+fn handle_focus_window(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "focus_window requires Windows")
 }
 
+/// Visible top-level windows whose title contains `title_pattern`
+/// (case-insensitive) and/or whose process image name contains
+/// `process_pattern`, limited to process `pid` if given, best match first
+/// (see `rank_windows`). DesktopAI's own windows are skipped unless
+/// `allow_self`.
 #[cfg(windows)]
-fn handle_right_click(cmd: &Command, config: &Config) -> CommandResult {
-    // Support name-based UIA resolution (same as click/double_click), with x/y fallback
-    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
-    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+fn find_windows(
+    title_pattern: &str,
+    process_pattern: &str,
+    pid: Option<u32>,
+    allow_self: bool,
+    config: &Config,
+) -> Vec<crate::inventory::WindowInfo> {
+    let Some(windows) = crate::windows::list_top_level_windows(false) else {
+        return Vec::new();
+    };
+    let windows = windows.into_iter().filter(|w| allow_self || !crate::policy::is_self_process(&w.process_exe, config));
+    rank_windows(windows, title_pattern, process_pattern, pid)
+}
+
+/// The best match of `find_windows`, or HWND(0) when nothing matches.
+#[cfg(windows)]
+fn find_window(
+    title_pattern: &str,
+    process_pattern: &str,
+    pid: Option<u32>,
+    allow_self: bool,
+    config: &Config,
+) -> windows::Win32::Foundation::HWND {
+    let best = find_windows(title_pattern, process_pattern, pid, allow_self, config)
+        .first()
+        .and_then(|w| parse_hwnd_param(&serde_json::json!(w.hwnd)));
+    windows::Win32::Foundation::HWND(best.unwrap_or(0))
+}
+
+/// The windows matching the patterns (see `find_windows`), best first:
+/// by `title_score`, then the shorter title, then front to back as listed.
+/// Without a title pattern they stay front to back.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn rank_windows(
+    windows: impl IntoIterator<Item = crate::inventory::WindowInfo>,
+    title_pattern: &str,
+    process_pattern: &str,
+    pid: Option<u32>,
+) -> Vec<crate::inventory::WindowInfo> {
+    let pattern_lower = title_pattern.to_lowercase();
+    let process_lower = process_pattern.to_lowercase();
+    let mut scored: Vec<(u8, crate::inventory::WindowInfo)> = windows
+        .into_iter()
+        .filter(|w| pid.is_none_or(|pid| pid == w.pid))
+        .filter(|w| {
+            let exe = w.process_exe.rsplit(['\\', '/']).next().unwrap_or(&w.process_exe);
+            process_lower.is_empty() || exe.to_lowercase().contains(&process_lower)
+        })
+        .filter_map(|w| Some((title_score(&w.title.to_lowercase(), &pattern_lower)?, w)))
+        .collect();
+    // Stable, so equal matches keep their z-order; without a title pattern
+    // nothing is ranked
+    if !pattern_lower.is_empty() {
+        scored.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.title.len().cmp(&b.title.len())));
+    }
+    scored.into_iter().map(|(_, w)| w).collect()
+}
+
+/// How well a lowercased window title matches a lowercased pattern: 2 when
+/// the pattern ends at a word boundary (not followed by an alphanumeric or
+/// `+`, so "note" prefers "Note - App" over "Notepad++"), 1 for a plain
+/// substring, `None` for no match. An empty pattern matches any title.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn title_score(title_lower: &str, pattern_lower: &str) -> Option<u8> {
+    if pattern_lower.is_empty() {
+        return Some(2);
+    }
+    let end = title_lower.find(pattern_lower)? + pattern_lower.len();
+    let is_word_boundary = !title_lower[end..].starts_with(|c: char| c.is_alphanumeric() || c == '+');
+    Some(if is_word_boundary { 2 } else { 1 })
+}
+
+/// The `pid` parameter, if given.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn pid_param(cmd: &Command) -> Result<Option<u32>, String> {
+    match cmd.parameters.get("pid") {
+        None => Ok(None),
+        Some(value) => match value.as_u64().and_then(|pid| u32::try_from(pid).ok()) {
+            Some(pid) if pid != 0 => Ok(Some(pid)),
+            _ => Err("pid must be a process id".to_string()),
+        },
+    }
+}
+
+/// Parse an `hwnd` parameter: a hex string as in events (`"0x1a2b"`), a
+/// decimal string, or a number.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_hwnd_param(value: &serde_json::Value) -> Option<isize> {
+    let raw = match value {
+        serde_json::Value::Number(n) => n.as_u64()?,
+        serde_json::Value::String(s) => {
+            let s = s.trim();
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+                None => s.parse().ok()?,
+            }
+        }
+        _ => return None,
+    };
+    (raw != 0).then_some(raw as isize)
+}
+
+/// How long `close_window` waits for the window to go away before reporting.
+#[cfg(windows)]
+const WINDOW_STATE_SETTLE_MS: u64 = 1000;
+
+/// Pause after a virtual desktop switch shortcut for the animation to end.
+#[cfg(windows)]
+const DESKTOP_SWITCH_SETTLE_MS: u64 = 400;
+
+/// Current state of a top-level window: closed, minimized, maximized, hidden
+/// or normal.
+#[cfg(windows)]
+fn window_state(hwnd: windows::Win32::Foundation::HWND) -> &'static str {
+    use windows::Win32::UI::WindowsAndMessaging::{IsIconic, IsWindow, IsWindowVisible, IsZoomed};
+
+    unsafe {
+        if !IsWindow(hwnd).as_bool() {
+            "closed"
+        } else if IsIconic(hwnd).as_bool() {
+            "minimized"
+        } else if IsZoomed(hwnd).as_bool() {
+            "maximized"
+        } else if !IsWindowVisible(hwnd).as_bool() {
+            "hidden"
+        } else {
+            "normal"
+        }
+    }
+}
+
+/// Resolve the window a window-management command targets: `hwnd` if given,
+/// else the best `title` (or `window_title`)/`process` match, limited to the
+/// windows of process `pid` if given. DesktopAI's own windows are denied
+/// unless self-targeting is allowed.
+#[cfg(windows)]
+fn resolve_window_target(cmd: &Command, config: &Config) -> Result<windows::Win32::Foundation::HWND, Box<CommandResult>> {
+    resolve_window_candidates(cmd, config).map(|(target, _)| target)
+}
+
+/// `resolve_window_target`, also returning every window that matched the
+/// patterns, best first; empty when `hwnd` picked the window.
+#[cfg(windows)]
+fn resolve_window_candidates(
+    cmd: &Command,
+    config: &Config,
+) -> Result<(windows::Win32::Foundation::HWND, Vec<crate::inventory::WindowInfo>), Box<CommandResult>> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::IsWindow;
+
+    let mut candidates = Vec::new();
+    let target = if let Some(value) = cmd.parameters.get("hwnd") {
+        match parse_hwnd_param(value) {
+            Some(raw) if unsafe { IsWindow(HWND(raw)) }.as_bool() => HWND(raw),
+            _ => return Err(Box::new(CommandResult::failure(&cmd.command_id, &format!("no window with hwnd {value}")))),
+        }
+    } else {
+        let title = ["title", "window_title"]
+            .iter()
+            .find_map(|key| cmd.parameters.get(*key).and_then(|v| v.as_str()))
+            .unwrap_or("");
+        let process = cmd.parameters.get("process").and_then(|v| v.as_str()).unwrap_or("");
+        let pid = pid_param(cmd).map_err(|e| Box::new(CommandResult::failure(&cmd.command_id, &e)))?;
+        if title.is_empty() && process.is_empty() && pid.is_none() {
+            return Err(Box::new(CommandResult::failure(
+                &cmd.command_id,
+                &format!("{} requires 'hwnd', 'title', 'process' or 'pid' parameter", cmd.action),
+            )));
+        }
+        candidates = find_windows(title, process, pid, crate::policy::self_targeting_allowed(cmd, config), config);
+        let found = HWND(candidates.first().and_then(|w| parse_hwnd_param(&serde_json::json!(w.hwnd))).unwrap_or(0));
+        if found.0 == 0 {
+            let pattern = match pid {
+                Some(pid) if title.is_empty() && process.is_empty() => format!("pid {pid}"),
+                _ if title.is_empty() => process.to_string(),
+                _ => title.to_string(),
+            };
+            return Err(Box::new(CommandResult::failure(&cmd.command_id, &format!("window not found matching: {pattern}"))));
+        }
+        found
+    };
+    match deny_self_target(cmd, config, target) {
+        Some(denied) => Err(Box::new(denied)),
+        None => Ok((target, candidates)),
+    }
+}
+
+/// Parameters naming the window an element search is limited to.
+const WINDOW_SCOPE_KEYS: &[&str] = &["hwnd", "title", "window_title", "process", "pid"];
+
+/// Whether the command limits its element search to one window.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn has_window_scope(cmd: &Command) -> bool {
+    WINDOW_SCOPE_KEYS.iter().any(|key| cmd.parameters.contains_key(*key))
+}
+
+/// Where an element-based action searches: the UIA element of the window
+/// named by `hwnd`/`window_title`/`process`/`pid` (see `resolve_window_target`),
+/// else the whole desktop. A scoped search is faster and cannot land on a
+/// same-named element in another app.
+#[cfg(windows)]
+fn uia_search_root(
+    cmd: &Command,
+    config: &Config,
+    uia: &windows::Win32::UI::Accessibility::IUIAutomation,
+) -> Result<windows::Win32::UI::Accessibility::IUIAutomationElement, Box<CommandResult>> {
+    let fail = |message: String| Box::new(CommandResult::failure(&cmd.command_id, &message));
+    if has_window_scope(cmd) {
+        let target = resolve_window_target(cmd, config)?;
+        unsafe { uia.ElementFromHandle(target) }.map_err(|e| fail(format!("ElementFromHandle failed: {e}")))
+    } else {
+        unsafe { uia.GetRootElement() }.map_err(|e| fail(format!("GetRootElement failed: {e}")))
+    }
+}
+
+/// Close, minimize, maximize or restore a window picked by `hwnd`, `title`
+/// and/or `process`, and report its resulting state. Closing posts
+/// SC_CLOSE, so the app may still prompt (e.g. to save) and stay open.
+#[cfg(windows)]
+fn handle_window_state(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let target = match resolve_window_target(cmd, config) {
+        Ok(target) => target,
+        Err(failed) => return *failed,
+    };
+
+    let title = crate::windows::window_title(target);
+    let sent = unsafe {
+        match cmd.action.as_str() {
+            "close_window" => PostMessageW(target, WM_SYSCOMMAND, WPARAM(SC_CLOSE as usize), LPARAM(0)).is_ok(),
+            "minimize_window" => {
+                let _ = ShowWindow(target, SW_MINIMIZE);
+                true
+            }
+            "maximize_window" => {
+                let _ = ShowWindow(target, SW_MAXIMIZE);
+                true
+            }
+            "restore_window" => {
+                let _ = ShowWindow(target, SW_RESTORE);
+                true
+            }
+            other => return CommandResult::failure(&cmd.command_id, &format!("unknown action: {other}")),
+        }
+    };
+    if !sent {
+        return CommandResult::failure(&cmd.command_id, &format!("failed to send close to '{title}'"));
+    }
+
+    // Let the window animate or handle SC_CLOSE; closing may take a moment
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(WINDOW_STATE_SETTLE_MS);
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let done = cmd.action != "close_window" || window_state(target) == "closed";
+        if done || std::time::Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(target)));
+    result.insert("title".to_string(), serde_json::json!(title));
+    result.insert("state".to_string(), serde_json::json!(window_state(target)));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_window_state(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
+
+/// Move (`move_window`: x, y) or resize (`resize_window`: width, height) a
+/// window picked like `handle_window_state`. Either action also accepts the
+/// other pair, so a single call can set the full rect. Maximized or
+/// minimized windows are restored first. Returns the resulting rect.
+#[cfg(windows)]
+fn handle_window_geometry(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::{HWND, RECT};
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let param = |name: &str| cmd.parameters.get(name).and_then(|v| v.as_i64()).map(|v| v as i32);
+    let position = param("x").zip(param("y"));
+    let size = param("width").zip(param("height"));
+    let (required, missing) = if cmd.action == "move_window" {
+        ("'x' and 'y'", position.is_none())
+    } else {
+        ("'width' and 'height'", size.is_none())
+    };
+    if missing {
+        return CommandResult::failure(&cmd.command_id, &format!("{} requires {required} parameters", cmd.action));
+    }
+    if size.is_some_and(|(w, h)| w <= 0 || h <= 0) {
+        return CommandResult::failure(&cmd.command_id, "width and height must be positive");
+    }
+
+    let target = match resolve_window_target(cmd, config) {
+        Ok(target) => target,
+        Err(failed) => return *failed,
+    };
+
+    unsafe {
+        if IsIconic(target).as_bool() || IsZoomed(target).as_bool() {
+            let _ = ShowWindow(target, SW_RESTORE);
+        }
+    }
+    let (x, y) = position.unwrap_or((0, 0));
+    let (width, height) = size.unwrap_or((0, 0));
+    let mut flags = SWP_NOZORDER | SWP_NOACTIVATE;
+    if position.is_none() {
+        flags |= SWP_NOMOVE;
+    }
+    if size.is_none() {
+        flags |= SWP_NOSIZE;
+    }
+    if let Err(e) = unsafe { SetWindowPos(target, HWND(0), x, y, width, height, flags) } {
+        return CommandResult::failure(&cmd.command_id, &format!("SetWindowPos failed: {e}"));
+    }
+
+    let mut rect = RECT::default();
+    let _ = unsafe { GetWindowRect(target, &mut rect) };
+    let mut result = HashMap::new();
+    result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(target)));
+    result.insert("title".to_string(), serde_json::json!(crate::windows::window_title(target)));
+    result.insert(
+        "rect".to_string(),
+        serde_json::json!([rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top]),
+    );
+    result.insert("state".to_string(), serde_json::json!(window_state(target)));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_window_geometry(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
+
+/// Shell shortcut for a `switch_desktop` direction. Windows exposes no
+/// public API for changing the current virtual desktop, so switching goes
+/// through the same keys a user would press.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn desktop_shortcut(direction: &str) -> Option<&'static str> {
+    match direction.to_lowercase().as_str() {
+        "left" | "previous" => Some("ctrl+win+left"),
+        "right" | "next" => Some("ctrl+win+right"),
+        "new" => Some("ctrl+win+d"),
+        _ => None,
+    }
+}
+
+/// Parse a virtual desktop id such as `"{1D4E2B6C-...}"` (braces optional).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_desktop_id(text: &str) -> Option<u128> {
+    let text = text.trim();
+    let text = text.strip_prefix('{').and_then(|t| t.strip_suffix('}')).unwrap_or(text);
+    let groups: Vec<&str> = text.split('-').collect();
+    let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] || !groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit())) {
+        return None;
+    }
+    u128::from_str_radix(&groups.concat(), 16).ok()
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn format_desktop_id(id: u128) -> String {
+    let hex = format!("{id:032X}");
+    format!("{{{}-{}-{}-{}-{}}}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Id of the virtual desktop on screen, taken from the foreground window or
+/// else any visible top-level window on it. `None` for an empty desktop.
+#[cfg(windows)]
+fn current_desktop_id(manager: &windows::Win32::UI::Shell::IVirtualDesktopManager) -> Option<u128> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    let foreground = unsafe { GetForegroundWindow() };
+    let others = crate::windows::enumerate_top_level_windows()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|info| parse_hwnd_param(&serde_json::json!(info.hwnd)).map(HWND));
+    std::iter::once(foreground).chain(others).find_map(|hwnd| unsafe {
+        if hwnd.0 == 0 || !manager.IsWindowOnCurrentVirtualDesktop(hwnd).ok()?.as_bool() {
+            return None;
+        }
+        Some(manager.GetWindowDesktopId(hwnd).ok()?.to_u128()).filter(|id| *id != 0)
+    })
+}
+
+/// Work on another virtual desktop instead of the user's. `direction`
+/// (`left`, `right` or `new`) switches the desktop on screen; `new` creates
+/// one first. A window picked by `hwnd`, `title` or `process` is moved to
+/// `desktop_id`, or along to the desktop switched to. Windows only lets a
+/// process move its own windows, so moving another app's window may fail
+/// with access denied. Reports the desktop ids involved, which later calls
+/// can pass back as `desktop_id`.
+#[cfg(windows)]
+fn handle_switch_desktop(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::core::GUID;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::{IVirtualDesktopManager, VirtualDesktopManager};
+
+    let direction = cmd.parameters.get("direction").and_then(|v| v.as_str()).unwrap_or("");
+    let shortcut = match direction {
+        "" => None,
+        other => match desktop_shortcut(other) {
+            Some(keys) => Some(keys),
+            None => return CommandResult::failure(&cmd.command_id, &format!("unknown direction: {other} (use left, right or new)")),
+        },
+    };
+    let desktop_id = match cmd.parameters.get("desktop_id").and_then(|v| v.as_str()) {
+        Some(text) => match parse_desktop_id(text) {
+            Some(id) => Some(id),
+            None => return CommandResult::failure(&cmd.command_id, &format!("invalid desktop_id: {text}")),
+        },
+        None => None,
+    };
+    let has_window = has_window_scope(cmd);
+    if shortcut.is_none() && !has_window {
+        return CommandResult::failure(&cmd.command_id, "switch_desktop requires 'direction' or a window to move");
+    }
+    if has_window && shortcut.is_none() && desktop_id.is_none() {
+        return CommandResult::failure(&cmd.command_id, "moving a window requires 'desktop_id' or 'direction'");
+    }
+    let window = if has_window {
+        match resolve_window_target(cmd, config) {
+            Ok(hwnd) => Some(hwnd),
+            Err(failed) => return *failed,
+        }
+    } else {
+        None
+    };
+
+    let manager: IVirtualDesktopManager = match unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        CoCreateInstance(&VirtualDesktopManager, None, CLSCTX_ALL)
+    } {
+        Ok(manager) => manager,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("virtual desktops unavailable: {e}")),
+    };
+
+    let mut result = HashMap::new();
+    let previous = current_desktop_id(&manager);
+    result.insert("previous_desktop_id".to_string(), serde_json::json!(previous.map(format_desktop_id)));
+    if let Some(keys) = shortcut {
+        if let Err(e) = press_keys(keys) {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+        // The switch animates before windows report the new desktop
+        std::thread::sleep(std::time::Duration::from_millis(DESKTOP_SWITCH_SETTLE_MS));
+        result.insert("direction".to_string(), serde_json::json!(direction.to_lowercase()));
+    }
+    let current = current_desktop_id(&manager);
+    result.insert("desktop_id".to_string(), serde_json::json!(current.map(format_desktop_id)));
+
+    if let Some(hwnd) = window {
+        // A fresh desktop holds no window to read its id from
+        let target = match desktop_id.or(current.filter(|_| shortcut.is_some())) {
+            Some(id) => id,
+            None => return CommandResult::failure(&cmd.command_id, "cannot determine the id of the desktop switched to; pass 'desktop_id'"),
+        };
+        if let Err(e) = unsafe { manager.MoveWindowToDesktop(hwnd, &GUID::from_u128(target)) } {
+            return CommandResult::failure(&cmd.command_id, &format!("cannot move window to desktop {}: {e}", format_desktop_id(target)));
+        }
+        result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(hwnd)));
+        result.insert("title".to_string(), serde_json::json!(crate::windows::window_title(hwnd)));
+        result.insert("window_desktop_id".to_string(), serde_json::json!(format_desktop_id(target)));
+    }
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_switch_desktop(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "switch_desktop requires Windows")
+}
+
+/// Deepest `get_element_tree` walk allowed, whatever the caller asks for.
+const MAX_ELEMENT_TREE_DEPTH: usize = 15;
+/// Children visited per element unless `max_children` says otherwise.
+const DEFAULT_ELEMENT_TREE_CHILDREN: usize = 50;
+const MAX_ELEMENT_TREE_CHILDREN: usize = 200;
+
+/// Depth and per-element child limits for `get_element_tree`: `depth`
+/// defaults to `UIA_MAX_DEPTH`, both are clamped to their caps.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn element_tree_limits(cmd: &Command, config: &Config) -> (usize, usize) {
+    let param = |name: &str| cmd.parameters.get(name).and_then(|v| v.as_u64()).map(|v| v as usize);
+    let depth = param("depth").unwrap_or(config.uia_max_depth).min(MAX_ELEMENT_TREE_DEPTH);
+    let children = param("max_children")
+        .unwrap_or(DEFAULT_ELEMENT_TREE_CHILDREN)
+        .clamp(1, MAX_ELEMENT_TREE_CHILDREN);
+    (depth, children)
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn count_elements(element: &crate::event::UiaElement) -> usize {
+    1 + element.children.iter().map(count_elements).sum::<usize>()
+}
+
+/// Dump the UIA tree of a window (`hwnd`, `title` or `process`) on demand,
+/// bypassing the passive snapshot throttle. Names and values are masked
+/// when redaction is enabled, as for observed events.
+#[cfg(windows)]
+fn handle_get_element_tree(cmd: &Command, config: &Config) -> CommandResult {
+    let target = match resolve_window_target(cmd, config) {
+        Ok(target) => target,
+        Err(failed) => return *failed,
+    };
+    let (depth, max_children) = element_tree_limits(cmd, config);
+    let Some(mut tree) = crate::uia::element_tree(target, depth, max_children) else {
+        return CommandResult::failure(&cmd.command_id, "UI Automation tree unavailable for window");
+    };
+    if config.redaction_enabled {
+        if let Some(stage) = crate::pipeline::RedactionStage::new(&config.redact_pattern) {
+            stage.redact_element(&mut tree);
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(target)));
+    result.insert("title".to_string(), serde_json::json!(crate::windows::window_title(target)));
+    result.insert("depth".to_string(), serde_json::json!(depth));
+    result.insert("element_count".to_string(), serde_json::json!(count_elements(&tree)));
+    if let Some(tokens) = cmd.parameters.get("outline_tokens").and_then(|v| v.as_u64()).filter(|&t| t > 0) {
+        let snapshot = crate::event::UiaSnapshot { window_tree: vec![tree], ..Default::default() };
+        result.insert("outline".to_string(), serde_json::json!(crate::outline::outline(&snapshot, tokens as usize)));
+        tree = snapshot.window_tree.into_iter().next().unwrap_or_default();
+    }
+    result.insert("tree".to_string(), serde_json::json!(tree));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_get_element_tree(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "get_element_tree requires Windows")
+}
+
+/// UI tree of the window an element command searches, for the recorder:
+/// the window it names, else the foreground one.
+#[cfg(windows)]
+pub(crate) fn resolution_tree(cmd: &Command, config: &Config) -> Option<crate::event::UiaElement> {
+    let target = if has_window_scope(cmd) {
+        resolve_window_target(cmd, config).ok()?
+    } else {
+        unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() }
+    };
+    crate::uia::element_tree(target, config.uia_max_depth.min(MAX_ELEMENT_TREE_DEPTH), MAX_ELEMENT_TREE_CHILDREN)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn resolution_tree(_cmd: &Command, _config: &Config) -> Option<crate::event::UiaElement> {
+    None
+}
+
+/// Time for an expanded combo box or list to populate its items.
+#[cfg(windows)]
+const EXPAND_SETTLE_MS: u64 = 250;
+
+/// Find the element a pattern-driving command targets by `automation_id`
+/// (preferred) or `name`, within the window the command names, if any.
+/// Elements owned by DesktopAI are denied unless self-targeting is allowed.
+#[cfg(windows)]
+fn resolve_uia_element(
+    cmd: &Command,
+    config: &Config,
+) -> Result<windows::Win32::UI::Accessibility::IUIAutomationElement, Box<CommandResult>> {
+    use windows::Win32::UI::Accessibility::*;
+
+    let fail = |message: &str| Box::new(CommandResult::failure(&cmd.command_id, message));
+    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    if automation_id.is_empty() && name.is_empty() {
+        return Err(fail(&format!("{} requires 'automation_id' or 'name' parameter", cmd.action)));
+    }
+    let uia = crate::uia::get_uia().ok_or_else(|| fail("UIA init failed"))?;
+    let root = uia_search_root(cmd, config, &uia)?;
+    let condition = if !automation_id.is_empty() {
+        unsafe { uia.CreatePropertyCondition(UIA_AutomationIdPropertyId, bstr_to_variant(automation_id)) }
+    } else {
+        unsafe { uia.CreatePropertyCondition(UIA_NamePropertyId, bstr_to_variant(name)) }
+    }
+    .map_err(|e| fail(&format!("CreatePropertyCondition failed: {e}")))?;
+    let element = unsafe { root.FindFirst(TreeScope_Descendants, &condition) }.map_err(|e| fail(&format!("element not found: {e}")))?;
+
+    let owner_pid = unsafe { element.CurrentProcessId() }.unwrap_or(0);
+    if owner_pid > 0
+        && !crate::policy::self_targeting_allowed(cmd, config)
+        && crate::policy::is_self_process(&crate::windows::process_path(owner_pid as u32), config)
+    {
+        return Err(Box::new(CommandResult::denied(
+            &cmd.command_id,
+            &format!("'{}' targets a DesktopAI window; set allow_self to permit it", cmd.action),
+        )));
+    }
+    Ok(element)
+}
+
+/// Matches `find_elements` describes by default; the rest are only counted.
+#[cfg(windows)]
+const DEFAULT_FIND_LIMIT: u64 = 20;
+#[cfg(windows)]
+const MAX_FIND_LIMIT: u64 = 100;
+
+/// Position of match `index` among `count` matches, or why there is none.
+pub(crate) fn nth_match(count: usize, index: u64) -> Result<usize, String> {
+    match usize::try_from(index) {
+        Ok(index) if index < count => Ok(index),
+        _ if count == 0 => Err("element not found".to_string()),
+        _ => Err(format!("index {index} out of range: {count} element(s) match")),
+    }
+}
+
+/// UIA control type names `control_type` accepts (spaces and case
+/// ignored), besides the localized name UIA reports, and their IDs.
+const CONTROL_TYPES: &[(&str, u32)] = &[
+    ("button", 50000),
+    ("calendar", 50001),
+    ("checkbox", 50002),
+    ("combobox", 50003),
+    ("edit", 50004),
+    ("hyperlink", 50005),
+    ("link", 50005),
+    ("image", 50006),
+    ("listitem", 50007),
+    ("list", 50008),
+    ("menu", 50009),
+    ("menubar", 50010),
+    ("menuitem", 50011),
+    ("progressbar", 50012),
+    ("radiobutton", 50013),
+    ("scrollbar", 50014),
+    ("slider", 50015),
+    ("spinner", 50016),
+    ("statusbar", 50017),
+    ("tab", 50018),
+    ("tabitem", 50019),
+    ("text", 50020),
+    ("toolbar", 50021),
+    ("tooltip", 50022),
+    ("tree", 50023),
+    ("treeitem", 50024),
+    ("custom", 50025),
+    ("group", 50026),
+    ("thumb", 50027),
+    ("datagrid", 50028),
+    ("dataitem", 50029),
+    ("document", 50030),
+    ("splitbutton", 50031),
+    ("window", 50032),
+    ("pane", 50033),
+    ("header", 50034),
+    ("headeritem", 50035),
+    ("table", 50036),
+    ("titlebar", 50037),
+    ("separator", 50038),
+    ("semanticzoom", 50039),
+    ("appbar", 50040),
+];
+
+/// UIA control type ID for a name such as `Edit` or `check box`; `None`
+/// for anything else, e.g. a localized name in another language.
+pub(crate) fn control_type_id(name: &str) -> Option<u32> {
+    let wanted: String = name.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+    CONTROL_TYPES.iter().find(|(known, _)| *known == wanted).map(|&(_, id)| id)
+}
+
+/// Every element matching `automation_id`, `name` and `control_type`
+/// (whichever are given, at least one), in UIA tree order, within one
+/// window when the command names it (see `uia_search_root`). A
+/// `control_type` is a UIA control type name (see `CONTROL_TYPES`) or the
+/// localized name; on its own it searches the foreground window unless
+/// another is named, so "the third edit" means the third in that window.
+/// DesktopAI's own elements are left out unless self-targeting is allowed,
+/// so `find_elements` indices and `click`'s `index` agree.
+#[cfg(windows)]
+fn find_uia_elements(
+    cmd: &Command,
+    config: &Config,
+) -> Result<Vec<windows::Win32::UI::Accessibility::IUIAutomationElement>, Box<CommandResult>> {
+    use windows::Win32::UI::Accessibility::*;
+
+    let fail = |message: &str| Box::new(CommandResult::failure(&cmd.command_id, message));
+    let automation_id = cmd.parameters.get("automation_id").and_then(|v| v.as_str()).unwrap_or("");
+    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let control_type = cmd.parameters.get("control_type").and_then(|v| v.as_str()).unwrap_or("");
+    if automation_id.is_empty() && name.is_empty() && control_type.is_empty() {
+        return Err(fail(&format!("{} requires 'automation_id', 'name' or 'control_type' parameter", cmd.action)));
+    }
+    let uia = crate::uia::get_uia().ok_or_else(|| fail("UIA init failed"))?;
+    let scope = if automation_id.is_empty() && name.is_empty() && !has_window_scope(cmd) {
+        let foreground = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+        unsafe { uia.ElementFromHandle(foreground) }.map_err(|e| fail(&format!("ElementFromHandle failed: {e}")))?
+    } else {
+        uia_search_root(cmd, config, &uia)?
+    };
+
+    let type_id = control_type_id(control_type);
+    let mut conditions = Vec::new();
+    if !automation_id.is_empty() {
+        conditions.push(unsafe { uia.CreatePropertyCondition(UIA_AutomationIdPropertyId, bstr_to_variant(automation_id)) });
+    }
+    if !name.is_empty() {
+        conditions.push(unsafe { uia.CreatePropertyCondition(UIA_NamePropertyId, bstr_to_variant(name)) });
+    }
+    if let Some(type_id) = type_id {
+        conditions.push(unsafe { uia.CreatePropertyCondition(UIA_ControlTypePropertyId, i32_to_variant(type_id as i32)) });
+    }
+    let conditions = conditions
+        .into_iter()
+        .collect::<windows::core::Result<Vec<_>>>()
+        .map_err(|e| fail(&format!("CreatePropertyCondition failed: {e}")))?;
+    let mut conditions = conditions.into_iter();
+    let mut condition = match conditions.next() {
+        Some(first) => first,
+        // Only a localized control type, checked below
+        None => unsafe { uia.CreateTrueCondition() }.map_err(|e| fail(&format!("CreateTrueCondition failed: {e}")))?,
+    };
+    for next in conditions {
+        condition = unsafe { uia.CreateAndCondition(&condition, &next) }
+            .map_err(|e| fail(&format!("CreateAndCondition failed: {e}")))?;
+    }
+    let found = unsafe { scope.FindAll(TreeScope_Descendants, &condition) }.map_err(|e| fail(&format!("FindAll failed: {e}")))?;
+
+    let include_self = crate::policy::self_targeting_allowed(cmd, config);
+    let count = unsafe { found.Length() }.unwrap_or(0);
+    Ok((0..count)
+        .filter_map(|i| unsafe { found.GetElement(i) }.ok())
+        .filter(|element| {
+            control_type.is_empty()
+                || type_id.is_some()
+                || unsafe { element.CurrentLocalizedControlType() }
+                    .map(crate::event::bstr_to_string)
+                    .is_ok_and(|t| t.eq_ignore_ascii_case(control_type))
+        })
+        .filter(|element| {
+            let owner_pid = unsafe { element.CurrentProcessId() }.unwrap_or(0);
+            include_self
+                || owner_pid <= 0
+                || !crate::policy::is_self_process(&crate::windows::process_path(owner_pid as u32), config)
+        })
+        .collect())
+}
+
+/// The `index`th (default first) element `find_uia_elements` matches.
+#[cfg(windows)]
+fn select_uia_element(
+    cmd: &Command,
+    config: &Config,
+) -> Result<windows::Win32::UI::Accessibility::IUIAutomationElement, Box<CommandResult>> {
+    let fail = |message: &str| Box::new(CommandResult::failure(&cmd.command_id, message));
+    let index = match cmd.parameters.get("index") {
+        Some(index) => index.as_u64().ok_or_else(|| fail("index must be a non-negative integer"))?,
+        None => 0,
+    };
+    let mut matches = find_uia_elements(cmd, config)?;
+    let position = nth_match(matches.len(), index).map_err(|e| fail(&e))?;
+    Ok(matches.swap_remove(position))
+}
+
+/// List every element matching `automation_id`/`name` (see
+/// `find_uia_elements`) so an ambiguous name can be disambiguated, e.g. by
+/// passing the match's `index` to `click`. Describes up to `limit` matches.
+#[cfg(windows)]
+fn handle_find_elements(cmd: &Command, config: &Config) -> CommandResult {
+    let elements = match find_uia_elements(cmd, config) {
+        Ok(elements) => elements,
+        Err(failed) => return *failed,
+    };
+    let limit = cmd.parameters.get("limit").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_FIND_LIMIT).clamp(1, MAX_FIND_LIMIT);
+    let redaction = config
+        .redaction_enabled
+        .then(|| crate::pipeline::RedactionStage::new(&config.redact_pattern))
+        .flatten();
+
+    let described: Vec<serde_json::Value> = elements
+        .iter()
+        .take(limit as usize)
+        .enumerate()
+        .map(|(index, element)| {
+            let mut described = describe_element(element, redaction.as_ref());
+            described["index"] = serde_json::json!(index);
+            described
+        })
+        .collect();
+
+    let mut result = HashMap::new();
+    result.insert("count".to_string(), serde_json::json!(elements.len()));
+    result.insert("truncated".to_string(), serde_json::json!(elements.len() > described.len()));
+    result.insert("elements".to_string(), serde_json::json!(described));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// An element's name (redacted by `redaction`), automation ID, localized
+/// control type, screen rect `[x, y, width, height]` and enabled state.
+#[cfg(windows)]
+fn describe_element(
+    element: &windows::Win32::UI::Accessibility::IUIAutomationElement,
+    redaction: Option<&crate::pipeline::RedactionStage>,
+) -> serde_json::Value {
+    let text = |value: windows::core::Result<windows::core::BSTR>| value.map(crate::event::bstr_to_string).unwrap_or_default();
+    let mut name = text(unsafe { element.CurrentName() });
+    if let Some(stage) = redaction {
+        stage.redact(&mut name);
+    }
+    let rect = unsafe { element.CurrentBoundingRectangle() }
+        .ok()
+        .map(|r| [r.left, r.top, r.right - r.left, r.bottom - r.top]);
+    serde_json::json!({
+        "name": name,
+        "automation_id": text(unsafe { element.CurrentAutomationId() }),
+        "control_type": text(unsafe { element.CurrentLocalizedControlType() }),
+        "rect": rect,
+        "enabled": unsafe { element.CurrentIsEnabled() }.map(|b| b.as_bool()).unwrap_or(true),
+    })
+}
+
+/// The element a click or type_text acted on, as reported in its result:
+/// `describe_element` plus the pid and executable of the owning process,
+/// so the backend can check the right thing was hit.
+#[cfg(windows)]
+fn acted_element(element: &windows::Win32::UI::Accessibility::IUIAutomationElement, config: &Config) -> serde_json::Value {
+    let redaction = config
+        .redaction_enabled
+        .then(|| crate::pipeline::RedactionStage::new(&config.redact_pattern))
+        .flatten();
+    let mut described = describe_element(element, redaction.as_ref());
+    let pid = u32::try_from(unsafe { element.CurrentProcessId() }.unwrap_or(0)).unwrap_or(0);
+    described["pid"] = serde_json::json!(pid);
+    described["process_exe"] = serde_json::json!(if pid == 0 { String::new() } else { crate::windows::process_path(pid) });
+    described
+}
+
+#[cfg(not(windows))]
+fn handle_find_elements(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "find_elements requires Windows")
+}
+
+/// Most characters `get_element_text` returns, whatever `max_chars` asks.
+#[cfg(windows)]
+const MAX_ELEMENT_TEXT_CHARS: u64 = 100_000;
+
+/// Read the text of the element `automation_id`/`name`/`control_type` and
+/// `index` pick (see `select_uia_element`): its value when it has one, else
+/// its document text, else its name. `max_chars` caps it (default
+/// `UIA_TEXT_MAX_CHARS`); redaction applies when enabled.
+#[cfg(windows)]
+fn handle_get_element_text(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Accessibility::*;
+
+    let element = match select_uia_element(cmd, config) {
+        Ok(element) => element,
+        Err(failed) => return *failed,
+    };
+    let max_chars = cmd
+        .parameters
+        .get("max_chars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(config.uia_text_max as u64)
+        .clamp(1, MAX_ELEMENT_TEXT_CHARS) as usize;
+    let text = |value: windows::core::Result<windows::core::BSTR>| value.map(crate::event::bstr_to_string).ok();
+
+    let value = unsafe { element.GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId) }
+        .ok()
+        .and_then(|pattern| text(unsafe { pattern.CurrentValue() }));
+    // One character past the cap tells whether the document was cut
+    let document = || {
+        let pattern = unsafe { element.GetCurrentPatternAs::<IUIAutomationTextPattern>(UIA_TextPatternId) }.ok()?;
+        let range = unsafe { pattern.DocumentRange() }.ok()?;
+        text(unsafe { range.GetText(max_chars as i32 + 1) })
+    };
+    let mut name = text(unsafe { element.CurrentName() }).unwrap_or_default();
+    let (mut content, source) = match value {
+        Some(value) => (value, "value"),
+        None => match document() {
+            Some(document) => (document, "text"),
+            None => (name.clone(), "name"),
+        },
+    };
+
+    let truncated = content.chars().count() > max_chars;
+    if let Some((cut, _)) = content.char_indices().nth(max_chars) {
+        content.truncate(cut);
+    }
+    if let Some(stage) = config
+        .redaction_enabled
+        .then(|| crate::pipeline::RedactionStage::new(&config.redact_pattern))
+        .flatten()
+    {
+        stage.redact(&mut content);
+        stage.redact(&mut name);
+    }
+
+    let mut result = HashMap::new();
+    result.insert("text".to_string(), serde_json::json!(content));
+    result.insert("source".to_string(), serde_json::json!(source));
+    result.insert("truncated".to_string(), serde_json::json!(truncated));
+    result.insert("name".to_string(), serde_json::json!(name));
+    result.insert("automation_id".to_string(), serde_json::json!(text(unsafe { element.CurrentAutomationId() }).unwrap_or_default()));
+    result.insert("control_type".to_string(), serde_json::json!(text(unsafe { element.CurrentLocalizedControlType() }).unwrap_or_default()));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_get_element_text(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "get_element_text requires Windows")
+}
+
+/// Depth of the UIA walk behind `show_element_labels` unless `depth` says
+/// otherwise; controls usually sit deeper than `UIA_MAX_DEPTH` reaches.
+#[cfg(windows)]
+const LABEL_TREE_DEPTH: usize = 10;
+
+/// Number the interactive elements of a window (`hwnd`/`title`/`process`,
+/// else the foreground window) and badge them on screen until
+/// `hide_element_labels` (see `labels`). `max_labels` caps the count and
+/// `detections` adds detector boxes no UIA element covers. Showing again
+/// renumbers. Names in the result are masked when redaction is enabled.
+#[cfg(windows)]
+fn handle_show_element_labels(cmd: &Command, config: &Config) -> CommandResult {
+    let target = if has_window_scope(cmd) {
+        match resolve_window_target(cmd, config) {
+            Ok(target) => target,
+            Err(failed) => return *failed,
+        }
+    } else {
+        unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() }
+    };
+    if target.0 == 0 {
+        return CommandResult::failure(&cmd.command_id, "no window to label");
+    }
+    let param = |name: &str| cmd.parameters.get(name).and_then(|v| v.as_u64()).map(|v| v as usize);
+    let limit = param("max_labels").unwrap_or(crate::labels::DEFAULT_LABELS).clamp(1, crate::labels::MAX_LABELS);
+    let depth = param("depth").unwrap_or(LABEL_TREE_DEPTH).min(MAX_ELEMENT_TREE_DEPTH);
+    let Some(tree) = crate::uia::element_tree(target, depth, MAX_ELEMENT_TREE_CHILDREN) else {
+        return CommandResult::failure(&cmd.command_id, "UI Automation tree unavailable for window");
+    };
+    #[allow(unused_mut)]
+    let mut labels = crate::labels::from_tree(&tree, limit);
+    #[cfg(feature = "detection")]
+    if config.detection_enabled && cmd.parameters.get("detections").and_then(|v| v.as_bool()).unwrap_or(false) {
+        // Keep the old badges out of the capture the detector sees
+        crate::labels::hide();
+        crate::labels::add_detections(&mut labels, &detected_boxes(target, config), limit);
+    }
+    if let Err(e) = crate::labels::show(labels.clone()) {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+
+    if config.redaction_enabled {
+        if let Some(stage) = crate::pipeline::RedactionStage::new(&config.redact_pattern) {
+            for label in &mut labels {
+                stage.redact(&mut label.name);
+            }
+        }
+    }
+    let mut result = HashMap::new();
+    result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(target)));
+    result.insert("count".to_string(), serde_json::json!(labels.len()));
+    result.insert("labels".to_string(), serde_json::json!(labels));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Screen rectangles of the UI elements the detector finds on `hwnd`'s monitor.
+#[cfg(all(windows, feature = "detection"))]
+fn detected_boxes(hwnd: windows::Win32::Foundation::HWND, config: &Config) -> Vec<[i32; 4]> {
+    let Some(detector) = crate::detection::shared_detector(config) else {
+        return Vec::new();
+    };
+    let (Some(monitor), Some((width, height, pixels))) =
+        (crate::screenshot::monitor_rect(hwnd), crate::screenshot::capture_raw_pixels(hwnd))
+    else {
+        return Vec::new();
+    };
+    let boxes: Vec<[f32; 4]> =
+        detector.detect(&pixels, width, height, 3).iter().map(|d| [d.x, d.y, d.width, d.height]).collect();
+    crate::detections::remember(monitor, boxes.clone());
+    boxes.into_iter().map(|b| crate::detections::to_screen(monitor, b)).collect()
+}
+
+/// Click the center of detection `id` from the last detection pass
+/// (`observe`, or `show_element_labels` with `detections`), or of a
+/// normalized `box` on the monitor that pass captured (the foreground
+/// window's monitor when none ran). `pointer` taps instead, as for `click`.
+#[cfg(windows)]
+fn handle_click_detection(cmd: &Command, config: &Config) -> CommandResult {
+    let pointer = match PointerKind::from_param(cmd.parameters.get("pointer")) {
+        Ok(pointer) => pointer,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let pass = crate::detections::last();
+    let rect = match (cmd.parameters.get("id"), cmd.parameters.get("box")) {
+        (Some(id), _) => {
+            let Some(id) = id.as_u64() else {
+                return CommandResult::failure(&cmd.command_id, "id must be a non-negative integer");
+            };
+            let Some(pass) = &pass else {
+                return CommandResult::failure(&cmd.command_id, "no detection pass has run yet; observe first");
+            };
+            match pass.rect(id as usize) {
+                Some(rect) => rect,
+                None => {
+                    let count = pass.boxes.len();
+                    return CommandResult::failure(
+                        &cmd.command_id,
+                        &format!("no detection {id} in the last pass ({count} detections)"),
+                    );
+                }
+            }
+        }
+        (None, Some(value)) => {
+            let b = match crate::detections::parse_box(value) {
+                Ok(b) => b,
+                Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+            };
+            let monitor = match pass.as_ref().map(|pass| pass.monitor) {
+                Some(monitor) => monitor,
+                None => match crate::screenshot::monitor_rect(windows::Win32::Foundation::HWND(0)) {
+                    Some(monitor) => monitor,
+                    None => return CommandResult::failure(&cmd.command_id, "cannot determine the monitor"),
+                },
+            };
+            crate::detections::to_screen(monitor, b)
+        }
+        (None, None) => return CommandResult::failure(&cmd.command_id, "click_detection requires 'id' or 'box'"),
+    };
+
+    let (x, y) = crate::detections::center(rect);
+    if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+        return denied;
+    }
+    if let Err(e) = click_or_tap(pointer, x, y) {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+    let mut result = HashMap::new();
+    if let Some(id) = cmd.parameters.get("id") {
+        result.insert("id".to_string(), id.clone());
+    }
+    result.insert("rect".to_string(), serde_json::json!(rect));
+    result.insert("x".to_string(), serde_json::json!(x));
+    result.insert("y".to_string(), serde_json::json!(y));
+    result.insert("method".to_string(), serde_json::json!("detection"));
+    result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_click_detection(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "click_detection requires Windows")
+}
+
+#[cfg(not(windows))]
+fn handle_show_element_labels(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "show_element_labels requires Windows")
+}
+
+/// Take the `show_element_labels` badges off the screen.
+#[cfg(windows)]
+fn handle_hide_element_labels(cmd: &Command, _config: &Config) -> CommandResult {
+    let mut result = HashMap::new();
+    result.insert("hidden".to_string(), serde_json::json!(crate::labels::hide()));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_hide_element_labels(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "hide_element_labels requires Windows")
+}
+
+/// Longest `if_exists` waits for the element to appear.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MAX_IF_EXISTS_WAIT_MS: u64 = 30_000;
+
+/// The command an `if_exists` runs: its `then` parameter when the element
+/// exists, `else` when it does not, `None` when that branch is not given.
+/// A branch is an object with an `action` and optional `parameters`,
+/// `timeout_ms`, `verify_diff` and `capture_before`, and runs under the
+/// wrapper's command id.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn branch_command(cmd: &Command, exists: bool) -> Result<Option<Command>, String> {
+    let key = if exists { "then" } else { "else" };
+    let Some(branch) = cmd.parameters.get(key).filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let mut branch = branch.clone();
+    let Some(fields) = branch.as_object_mut() else {
+        return Err(format!("if_exists '{key}' must be an object with an 'action'"));
+    };
+    fields.insert("command_id".to_string(), serde_json::json!(cmd.command_id));
+    serde_json::from_value(branch).map(Some).map_err(|e| format!("invalid if_exists '{key}' command: {e}"))
+}
+
+/// Run `then` if an element matching the `find_elements` selector
+/// (`automation_id`/`name`, `control_type`, `hwnd`/`title`/`process`) is
+/// present, otherwise `else`, without a round trip to the backend. With
+/// `wait_ms` the element gets that long to appear. The branch goes through
+/// the same permission and policy checks as a top-level command; its result
+/// is returned with `exists` and `branch` added.
+#[cfg(windows)]
+fn handle_if_exists(cmd: &Command, config: &Config) -> CommandResult {
+    // Reject malformed branches before touching the desktop
+    for exists in [true, false] {
+        if let Err(e) = branch_command(cmd, exists) {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+    }
+    let wait_ms = cmd.parameters.get("wait_ms").and_then(|v| v.as_u64()).unwrap_or(0).min(MAX_IF_EXISTS_WAIT_MS);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(wait_ms);
+    let exists = loop {
+        let found = match find_uia_elements(cmd, config) {
+            Ok(elements) => !elements.is_empty(),
+            Err(failed) => return *failed,
+        };
+        if found || std::time::Instant::now() >= deadline {
+            break found;
+        }
+        if !crate::cancel::pause(std::time::Duration::from_millis(100)) {
+            return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        }
+    };
+
+    let branch = if exists { "then" } else { "else" };
+    let mut result = match branch_command(cmd, exists) {
+        Ok(Some(inner)) => {
+            log::info!("if_exists (id={}): element {}, running {}", cmd.command_id, if exists { "present" } else { "absent" }, inner.action);
+            let mut result = execute_checked(&inner, config);
+            result.result.insert("action".to_string(), serde_json::json!(inner.action));
+            result
+        }
+        Ok(None) => CommandResult::success(&cmd.command_id, HashMap::new()),
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    result.result.insert("exists".to_string(), serde_json::json!(exists));
+    result.result.insert("branch".to_string(), serde_json::json!(branch));
+    result
+}
+
+#[cfg(not(windows))]
+fn handle_if_exists(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "if_exists requires Windows")
+}
+
+/// The workflows directory, or a failure when there is none.
+fn workflows_dir(cmd: &Command, config: &Config) -> Result<std::path::PathBuf, Box<CommandResult>> {
+    if config.workflows_dir.is_empty() {
+        return Err(Box::new(CommandResult::failure(
+            &cmd.command_id,
+            "no workflows directory (set DATA_DIR or WORKFLOWS_DIR)",
+        )));
+    }
+    Ok(std::path::PathBuf::from(&config.workflows_dir))
+}
+
+/// Describe the workflow files in `WORKFLOWS_DIR` (see workflow.rs): name,
+/// description, inputs (with `required` for those without a default) and
+/// step count. Files that do not parse are listed under `invalid` with the
+/// reason.
+fn handle_list_workflows(cmd: &Command, config: &Config) -> CommandResult {
+    let dir = match workflows_dir(cmd, config) {
+        Ok(dir) => dir,
+        Err(failed) => return *failed,
+    };
+    let mut workflows = Vec::new();
+    let mut invalid = Vec::new();
+    for entry in crate::workflow::list(&dir) {
+        match entry.workflow {
+            Ok(workflow) => {
+                let inputs: Vec<serde_json::Value> = workflow
+                    .inputs
+                    .iter()
+                    .map(|input| {
+                        let mut described = serde_json::json!(input);
+                        described["required"] = serde_json::json!(input.required());
+                        described
+                    })
+                    .collect();
+                workflows.push(serde_json::json!({
+                    "name": entry.name,
+                    "description": workflow.description,
+                    "inputs": inputs,
+                    "steps": workflow.step_count(),
+                }));
+            }
+            Err(error) => invalid.push(serde_json::json!({
+                "name": entry.name,
+                "file": entry.path.to_string_lossy(),
+                "error": error,
+            })),
+        }
+    }
+    let mut result = HashMap::new();
+    result.insert("count".to_string(), serde_json::json!(workflows.len()));
+    result.insert("workflows".to_string(), serde_json::json!(workflows));
+    result.insert("invalid".to_string(), serde_json::json!(invalid));
+    result.insert("directory".to_string(), serde_json::json!(config.workflows_dir));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Runs workflow steps on the collector, each like a top-level command.
+struct WorkflowSteps<'a> {
+    config: &'a Config,
+    /// When the `run_workflow` command's `timeout_ms` runs out.
+    deadline: std::time::Instant,
+}
+
+impl crate::workflow::Executor for WorkflowSteps<'_> {
+    fn execute(&mut self, cmd: &Command) -> CommandResult {
+        execute_checked(cmd, self.config)
+    }
+
+    fn pause(&mut self, duration: std::time::Duration) -> bool {
+        crate::cancel::pause(duration)
+    }
+
+    fn cancelled(&self) -> bool {
+        crate::cancel::cancelled()
+    }
+
+    fn clipboard_text(&mut self) -> Option<String> {
+        #[cfg(windows)]
+        return crate::clipboard::get_text().unwrap_or_else(|e| {
+            log::warn!("Could not read the clipboard for a workflow input: {e}");
+            None
+        });
+        #[cfg(not(windows))]
+        None
+    }
+
+    fn prompt(&mut self, request: &crate::workflow::PromptRequest) -> Result<Option<HashMap<String, String>>, String> {
+        crate::workflow::prompt_user(request)
+    }
+
+    fn time_left(&self) -> std::time::Duration {
+        self.deadline.saturating_duration_since(std::time::Instant::now())
+    }
+}
+
+/// Run the workflow `name` from `WORKFLOWS_DIR` with `inputs` (an object of
+/// input values; others are read from the clipboard or asked for in the
+/// prompt window, see workflow.rs). Each step goes through the permission
+/// and policy checks of a top-level command; the whole run, prompt
+/// included, counts against this command's `timeout_ms` and fails with
+/// `Timeout` when it runs out. The result lists every step taken; a run
+/// ended by a failing step fails with that step's error and error code,
+/// keeping the steps so far.
+fn handle_run_workflow(cmd: &Command, config: &Config) -> CommandResult {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(cmd.timeout_ms);
+    let name = cmd.parameters.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    if name.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "run_workflow requires 'name' parameter");
+    }
+    let given = match cmd.parameters.get("inputs") {
+        None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+        Some(serde_json::Value::Object(given)) => given.clone(),
+        Some(_) => return CommandResult::failure(&cmd.command_id, "'inputs' must be an object"),
+    };
+    let dir = match workflows_dir(cmd, config) {
+        Ok(dir) => dir,
+        Err(failed) => return *failed,
+    };
+    let workflow = match crate::workflow::load(&dir, name) {
+        Ok(workflow) => workflow,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let mut steps = WorkflowSteps { config, deadline };
+    let inputs = match workflow.resolve_inputs(name, &given, &mut steps) {
+        Ok(Some(inputs)) => inputs,
+        Ok(None) => return CommandResult::cancelled(&cmd.command_id, &cmd.action),
+        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("workflow '{name}': {e}")),
+    };
+
+    log::info!("run_workflow (id={}): running '{name}' ({} steps)", cmd.command_id, workflow.step_count());
+    let run = crate::workflow::run(&workflow, &cmd.command_id, &inputs, &mut steps);
+    let mut result = if run.cancelled() {
+        CommandResult::cancelled(&cmd.command_id, &cmd.action)
+    } else if let Some(error) = &run.error {
+        let mut failed = CommandResult::failure(&cmd.command_id, &format!("workflow '{name}' {error}"));
+        failed.error_code = run.error_code.clone();
+        failed
+    } else {
+        CommandResult::success(&cmd.command_id, HashMap::new())
+    };
+    result.result.insert("workflow".to_string(), serde_json::json!(name));
+    result.result.insert("completed".to_string(), serde_json::json!(run.error.is_none()));
+    result.result.insert("steps".to_string(), serde_json::json!(run.steps));
+    result
+}
+
+/// Select the child named `item` (case-insensitive) in a list, combo box or
+/// similar container found by `automation_id`/`name`. Collapsed containers
+/// are expanded first and collapsed again afterwards.
+#[cfg(windows)]
+fn handle_select_item(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Accessibility::*;
+
+    let item = cmd.parameters.get("item").and_then(|v| v.as_str()).unwrap_or("");
+    if item.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "select_item requires 'item' parameter");
+    }
+    let container = match resolve_uia_element(cmd, config) {
+        Ok(element) => element,
+        Err(failed) => return *failed,
+    };
+    let Some(uia) = crate::uia::get_uia() else {
+        return CommandResult::failure(&cmd.command_id, "UIA init failed");
+    };
+
+    let expander: Option<IUIAutomationExpandCollapsePattern> =
+        unsafe { container.GetCurrentPatternAs(UIA_ExpandCollapsePatternId) }.ok();
+    let expanded = expander.as_ref().is_some_and(|pattern| unsafe {
+        pattern.CurrentExpandCollapseState() == Ok(ExpandCollapseState_Collapsed) && pattern.Expand().is_ok()
+    });
+    if expanded {
+        std::thread::sleep(std::time::Duration::from_millis(EXPAND_SETTLE_MS));
+    }
+    let collapse = || {
+        if let Some(pattern) = expander.as_ref().filter(|_| expanded) {
+            if unsafe { pattern.CurrentExpandCollapseState() } == Ok(ExpandCollapseState_Expanded) {
+                let _ = unsafe { pattern.Collapse() };
+            }
+        }
+    };
+
+    let found = unsafe {
+        uia.CreatePropertyConditionEx(UIA_NamePropertyId, bstr_to_variant(item), PropertyConditionFlags_IgnoreCase)
+            .and_then(|condition| container.FindFirst(TreeScope_Descendants, &condition))
+    };
+    let Ok(element) = found else {
+        collapse();
+        return CommandResult::failure(&cmd.command_id, &format!("item not found: {item}"));
+    };
+    let Ok(selection) = (unsafe { element.GetCurrentPatternAs::<IUIAutomationSelectionItemPattern>(UIA_SelectionItemPatternId) }) else {
+        collapse();
+        return CommandResult::failure(&cmd.command_id, &format!("item '{item}' does not support SelectionItemPattern"));
+    };
+    if let Err(e) = unsafe { selection.Select() } {
+        collapse();
+        return CommandResult::failure(&cmd.command_id, &format!("Select failed: {e}"));
+    }
+    collapse();
+
+    let mut result = HashMap::new();
+    result.insert(
+        "item".to_string(),
+        serde_json::json!(unsafe { element.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default()),
+    );
+    result.insert(
+        "is_selected".to_string(),
+        serde_json::json!(unsafe { selection.CurrentIsSelected() }.map(|b| b.as_bool()).unwrap_or(false)),
+    );
+    result.insert("expanded".to_string(), serde_json::json!(expanded));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_select_item(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "select_item requires Windows")
+}
+
+#[cfg(windows)]
+#[allow(non_upper_case_globals)]
+fn expand_collapse_state_name(state: windows::Win32::UI::Accessibility::ExpandCollapseState) -> &'static str {
+    use windows::Win32::UI::Accessibility::*;
+    match state {
+        ExpandCollapseState_Collapsed => "collapsed",
+        ExpandCollapseState_Expanded => "expanded",
+        ExpandCollapseState_PartiallyExpanded => "partially_expanded",
+        ExpandCollapseState_LeafNode => "leaf",
+        _ => "unknown",
+    }
+}
+
+/// Expand or collapse a tree item, menu or similar element found by
+/// `automation_id`/`name` via ExpandCollapsePattern. The state after the
+/// call is returned as `expand_collapse_state`.
+#[cfg(windows)]
+fn handle_expand_collapse(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Accessibility::*;
+
+    let element = match resolve_uia_element(cmd, config) {
+        Ok(element) => element,
+        Err(failed) => return *failed,
+    };
+    let Ok(pattern) = (unsafe { element.GetCurrentPatternAs::<IUIAutomationExpandCollapsePattern>(UIA_ExpandCollapsePatternId) }) else {
+        return CommandResult::failure(&cmd.command_id, "element does not support ExpandCollapsePattern");
+    };
+    let before = unsafe { pattern.CurrentExpandCollapseState() };
+    if before == Ok(ExpandCollapseState_LeafNode) {
+        return CommandResult::failure(&cmd.command_id, &format!("cannot {} a leaf node", cmd.action));
+    }
+    let changed = if cmd.action == "expand" {
+        unsafe { pattern.Expand() }
+    } else {
+        unsafe { pattern.Collapse() }
+    };
+    if let Err(e) = changed {
+        return CommandResult::failure(&cmd.command_id, &format!("{} failed: {e}", cmd.action));
+    }
+
+    let mut result = HashMap::new();
+    result.insert(
+        "name".to_string(),
+        serde_json::json!(unsafe { element.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default()),
+    );
+    if let Ok(state) = before {
+        result.insert("previous_state".to_string(), serde_json::json!(expand_collapse_state_name(state)));
+    }
+    let state = unsafe { pattern.CurrentExpandCollapseState() }.map(expand_collapse_state_name).unwrap_or("unknown");
+    result.insert("expand_collapse_state".to_string(), serde_json::json!(state));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_expand_collapse(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
+
+/// Why `value` cannot be set on a range of `min..=max`, if it cannot.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn range_value_error(value: f64, min: f64, max: f64) -> Option<String> {
+    if !value.is_finite() {
+        Some("value must be a finite number".to_string())
+    } else if value < min || value > max {
+        Some(format!("value {value} is outside the range {min}..{max}"))
+    } else {
+        None
+    }
+}
+
+/// Set a slider/spinner found by `automation_id`/`name` to `value` through
+/// RangeValuePattern. Returns the range and the value read back afterwards.
+#[cfg(windows)]
+fn handle_set_range_value(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Accessibility::*;
+
+    let Some(value) = cmd.parameters.get("value").and_then(|v| v.as_f64()) else {
+        return CommandResult::failure(&cmd.command_id, "missing numeric 'value' parameter");
+    };
+    let element = match resolve_uia_element(cmd, config) {
+        Ok(element) => element,
+        Err(failed) => return *failed,
+    };
+    let Ok(pattern) = (unsafe { element.GetCurrentPatternAs::<IUIAutomationRangeValuePattern>(UIA_RangeValuePatternId) }) else {
+        return CommandResult::failure(&cmd.command_id, "element does not support RangeValuePattern");
+    };
+    if unsafe { pattern.CurrentIsReadOnly() }.map(|b| b.as_bool()).unwrap_or(false) {
+        return CommandResult::failure(&cmd.command_id, "range value is read-only");
+    }
+    let (min, max) = match unsafe { (pattern.CurrentMinimum(), pattern.CurrentMaximum()) } {
+        (Ok(min), Ok(max)) => (min, max),
+        _ => return CommandResult::failure(&cmd.command_id, "failed to read the element's range"),
+    };
+    if let Some(error) = range_value_error(value, min, max) {
+        return CommandResult::failure(&cmd.command_id, &error);
+    }
+    let previous = unsafe { pattern.CurrentValue() }.ok();
+    if let Err(e) = unsafe { pattern.SetValue(value) } {
+        return CommandResult::failure(&cmd.command_id, &format!("SetValue failed: {e}"));
+    }
+
+    let mut result = HashMap::new();
+    result.insert(
+        "name".to_string(),
+        serde_json::json!(unsafe { element.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default()),
+    );
+    result.insert("min".to_string(), serde_json::json!(min));
+    result.insert("max".to_string(), serde_json::json!(max));
+    result.insert("previous".to_string(), serde_json::json!(previous));
+    result.insert("requested".to_string(), serde_json::json!(value));
+    result.insert("current".to_string(), serde_json::json!(unsafe { pattern.CurrentValue() }.ok()));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_set_range_value(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "set_range_value requires Windows")
+}
+
+/// Default time each menu level gets to appear after its parent opens.
+#[cfg(windows)]
+const DEFAULT_MENU_LEVEL_TIMEOUT_MS: u64 = 2000;
+/// Poll interval while waiting for a menu level.
+#[cfg(windows)]
+const MENU_POLL_MS: u64 = 50;
+
+/// Split a menu path (`"File > Save As"` or `["File", "Save As"]`) into its
+/// levels. Empty levels are rejected.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_menu_path(value: Option<&serde_json::Value>) -> Result<Vec<String>, String> {
+    let levels: Vec<String> = match value {
+        Some(serde_json::Value::String(path)) => path.split('>').map(|level| level.trim().to_string()).collect(),
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .map(|v| v.as_str().map(|level| level.trim().to_string()))
+            .collect::<Option<_>>()
+            .ok_or("'path' array must contain only strings")?,
+        _ => return Err("invoke_menu requires 'path' parameter, e.g. \"File > Save As\"".to_string()),
+    };
+    if levels.iter().any(String::is_empty) {
+        return Err("menu path has an empty level".to_string());
+    }
+    Ok(levels)
+}
+
+/// Whether a menu item's name matches a requested level: case-insensitive,
+/// ignoring `&` access-key markers, a trailing ellipsis and a tab-separated
+/// shortcut ("Save &As...\tCtrl+Shift+S" matches "save as").
+#[cfg_attr(not(windows), allow(dead_code))]
+fn menu_name_matches(actual: &str, wanted: &str) -> bool {
+    let normalize = |name: &str| {
+        let label = name.split('\t').next().unwrap_or(name).replace('&', "");
+        label.trim().trim_end_matches("...").trim_end_matches('\u{2026}').trim().to_lowercase()
+    };
+    normalize(actual) == normalize(wanted)
+}
+
+#[cfg(windows)]
+fn i32_to_variant(value: i32) -> windows::Win32::System::Variant::VARIANT {
+    use windows::Win32::System::Variant::*;
+    unsafe {
+        let mut var: VARIANT = std::mem::zeroed();
+        let inner = &mut *var.Anonymous.Anonymous;
+        inner.vt = VT_I4;
+        inner.Anonymous.lVal = value;
+        var
+    }
+}
+
+/// First menu item under `scopes` whose name matches `wanted` and that
+/// belongs to process `pid`.
+#[cfg(windows)]
+fn find_menu_item(
+    scopes: &[windows::Win32::UI::Accessibility::IUIAutomationElement],
+    condition: &windows::Win32::UI::Accessibility::IUIAutomationCondition,
+    wanted: &str,
+    pid: u32,
+) -> Option<windows::Win32::UI::Accessibility::IUIAutomationElement> {
+    use windows::Win32::UI::Accessibility::*;
+
+    for scope in scopes {
+        let Ok(items) = (unsafe { scope.FindAll(TreeScope_Descendants, condition) }) else {
+            continue;
+        };
+        for i in 0..unsafe { items.Length() }.unwrap_or(0) {
+            let Ok(item) = (unsafe { items.GetElement(i) }) else {
+                continue;
+            };
+            let name = unsafe { item.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default();
+            if menu_name_matches(&name, wanted) && unsafe { item.CurrentProcessId() } == Ok(pid as i32) {
+                return Some(item);
+            }
+        }
+    }
+    None
+}
+
+/// Walk a menu path such as `"File > Save As"` in the window picked by
+/// `hwnd`/`title`/`process` (default: the foreground window): each level is
+/// expanded once it appears, waiting up to `level_timeout_ms` per level, and
+/// the final item is invoked. Menus opened on the way are closed again if a
+/// level cannot be found.
+#[cfg(windows)]
+fn handle_invoke_menu(cmd: &Command, config: &Config) -> CommandResult {
+    use std::time::{Duration, Instant};
+    use windows::Win32::UI::Accessibility::*;
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    let levels = match parse_menu_path(cmd.parameters.get("path")) {
+        Ok(levels) => levels,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let level_timeout = Duration::from_millis(
+        cmd.parameters.get("level_timeout_ms").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_MENU_LEVEL_TIMEOUT_MS),
+    );
+    let named_target = has_window_scope(cmd);
+    let target = if named_target {
+        match resolve_window_target(cmd, config) {
+            Ok(target) => target,
+            Err(failed) => return *failed,
+        }
+    } else {
+        let fg = unsafe { GetForegroundWindow() };
+        if let Some(denied) = deny_self_target(cmd, config, fg) {
+            return denied;
+        }
+        fg
+    };
+    let mut pid: u32 = 0;
+    unsafe { GetWindowThreadProcessId(target, Some(&mut pid)) };
+
+    let Some(uia) = crate::uia::get_uia() else {
+        return CommandResult::failure(&cmd.command_id, "UIA init failed");
+    };
+    let setup = unsafe {
+        (|| {
+            let window = uia.ElementFromHandle(target)?;
+            let root = uia.GetRootElement()?;
+            let item = uia.CreatePropertyCondition(UIA_ControlTypePropertyId, i32_to_variant(UIA_MenuItemControlTypeId.0 as i32))?;
+            let menu = uia.CreatePropertyCondition(UIA_ControlTypePropertyId, i32_to_variant(UIA_MenuControlTypeId.0 as i32))?;
+            Ok::<_, windows::core::Error>((window, root, item, menu))
+        })()
+    };
+    let (window, root, item_condition, menu_condition) = match setup {
+        Ok(setup) => setup,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("UIA setup failed: {e}")),
+    };
+
+    let mut opened: Vec<IUIAutomationExpandCollapsePattern> = Vec::new();
+    let close_opened = |opened: &[IUIAutomationExpandCollapsePattern]| {
+        for pattern in opened.iter().rev() {
+            let _ = unsafe { pattern.Collapse() };
+        }
+    };
+    let mut matched = Vec::new();
+    for (index, level) in levels.iter().enumerate() {
+        // Submenus are usually popups at the desktop root, not inside the window
+        let deadline = Instant::now() + level_timeout;
+        let found = loop {
+            let mut scopes = Vec::new();
+            if index > 0 {
+                if let Ok(popups) = unsafe { root.FindAll(TreeScope_Children, &menu_condition) } {
+                    scopes.extend((0..unsafe { popups.Length() }.unwrap_or(0)).filter_map(|i| unsafe { popups.GetElement(i) }.ok()));
+                }
+            }
+            scopes.push(window.clone());
+            if let Some(item) = find_menu_item(&scopes, &item_condition, level, pid) {
+                break Some(item);
+            }
+            if Instant::now() >= deadline {
+                break None;
+            }
+            if !crate::cancel::pause(Duration::from_millis(MENU_POLL_MS)) {
+                close_opened(&opened);
+                return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+            }
+        };
+        let Some(item) = found else {
+            close_opened(&opened);
+            return CommandResult::failure(
+                &cmd.command_id,
+                &format!("menu item not found: '{level}' (level {} of {})", index + 1, levels.len()),
+            );
+        };
+        matched.push(unsafe { item.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default());
+
+        let is_last = index + 1 == levels.len();
+        let expander = (!is_last)
+            .then(|| unsafe { item.GetCurrentPatternAs::<IUIAutomationExpandCollapsePattern>(UIA_ExpandCollapsePatternId) }.ok())
+            .flatten();
+        let outcome = match expander {
+            Some(pattern) => {
+                let expanded = unsafe { pattern.Expand() };
+                opened.push(pattern);
+                expanded
+            }
+            None => match unsafe { item.GetCurrentPatternAs::<IUIAutomationInvokePattern>(UIA_InvokePatternId) } {
+                Ok(invoke) => unsafe { invoke.Invoke() },
+                Err(_) => {
+                    close_opened(&opened);
+                    return CommandResult::failure(&cmd.command_id, &format!("menu item '{level}' cannot be invoked"));
+                }
+            },
+        };
+        if let Err(e) = outcome {
+            close_opened(&opened);
+            return CommandResult::failure(&cmd.command_id, &format!("opening '{level}' failed: {e}"));
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("path".to_string(), serde_json::json!(matched));
+    result.insert("levels".to_string(), serde_json::json!(levels.len()));
+    result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(target)));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_invoke_menu(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "invoke_menu requires Windows")
+}
+
+/// Screen rectangle `[x, y, width, height]` for a `screenshot_region`
+/// request. `frame` is the monitor or window the region is relative to, or
+/// `None` for absolute screen pixels; normalized regions are fractions (0..1)
+/// of the frame. Regions relative to a frame are clipped to it.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn region_rect(region: [f64; 4], frame: Option<[i32; 4]>, normalized: bool) -> Result<[i32; 4], String> {
+    let [x, y, width, height] = region;
+    if region.iter().any(|v| !v.is_finite()) || width <= 0.0 || height <= 0.0 {
+        return Err("region needs finite 'x'/'y' and positive 'width'/'height'".to_string());
+    }
+    let Some([fx, fy, fw, fh]) = frame else {
+        if normalized {
+            return Err("normalized regions need relative_to 'monitor' or 'window'".to_string());
+        }
+        return Ok([x.round() as i32, y.round() as i32, (width.round() as i32).max(1), (height.round() as i32).max(1)]);
+    };
+    let (left, top, right, bottom) = if normalized {
+        if region.iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return Err("normalized region values must be between 0 and 1".to_string());
+        }
+        (x * fw as f64, y * fh as f64, (x + width) * fw as f64, (y + height) * fh as f64)
+    } else {
+        (x, y, x + width, y + height)
+    };
+    let clip = |v: f64, max: i32| (v.round() as i32).clamp(0, max);
+    let (left, top, right, bottom) = (clip(left, fw), clip(top, fh), clip(right, fw), clip(bottom, fh));
+    if right <= left || bottom <= top {
+        return Err("region lies outside the frame".to_string());
+    }
+    Ok([fx + left, fy + top, right - left, bottom - top])
+}
+
+/// Window and screen rectangle a capture command targets. The window is
+/// `hwnd`/`title`/`process`, else the foreground window. The rectangle is
+/// `None` when no `x`/`y`/`width`/`height` are given; otherwise they are
+/// screen pixels, or with `relative_to` "monitor" or "window" offsets into
+/// the window's monitor or the window itself (fractions of it with
+/// `normalized`).
+#[cfg(windows)]
+fn resolve_capture_region(
+    cmd: &Command,
+    config: &Config,
+) -> Result<(windows::Win32::Foundation::HWND, Option<[i32; 4]>), Box<CommandResult>> {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+
+    let fail = |message: &str| Box::new(CommandResult::failure(&cmd.command_id, message));
+    let window = if has_window_scope(cmd) {
+        resolve_window_target(cmd, config)?
+    } else {
+        unsafe { GetForegroundWindow() }
+    };
+    let keys = ["x", "y", "width", "height"];
+    if keys.iter().all(|key| !cmd.parameters.contains_key(*key)) {
+        return Ok((window, None));
+    }
+    let values: Vec<f64> = keys.iter().filter_map(|key| cmd.parameters.get(*key).and_then(|v| v.as_f64())).collect();
+    let Ok(region) = <[f64; 4]>::try_from(values) else {
+        return Err(fail(&format!("{} region requires numeric 'x', 'y', 'width' and 'height'", cmd.action)));
+    };
+    let relative_to = cmd.parameters.get("relative_to").and_then(|v| v.as_str()).unwrap_or("screen");
+    let normalized = cmd.parameters.get("normalized").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let frame = match relative_to {
+        "screen" => None,
+        "monitor" => Some(crate::screenshot::monitor_rect(window).ok_or_else(|| fail("could not determine the monitor"))?),
+        "window" => {
+            let mut rect = RECT::default();
+            if window.0 == 0 || unsafe { GetWindowRect(window, &mut rect) }.is_err() {
+                return Err(fail("could not determine the window rectangle"));
+            }
+            Some([rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top])
+        }
+        other => return Err(fail(&format!("unknown relative_to '{other}' (expected screen, monitor or window)"))),
+    };
+    region_rect(region, frame, normalized).map(|rect| (window, Some(rect))).map_err(|e| fail(&e))
+}
+
+/// Capture a rectangle of the screen (see `resolve_capture_region`) and
+/// return it as base64 JPEG.
+#[cfg(windows)]
+fn handle_screenshot_region(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::HWND;
+
+    if !config.enable_screenshot {
+        return CommandResult::failure(&cmd.command_id, "screenshots are disabled (ENABLE_SCREENSHOT)");
+    }
+    let (window, rect) = match resolve_capture_region(cmd, config) {
+        Ok((window, Some(rect))) => (window, rect),
+        Ok((_, None)) => {
+            return CommandResult::failure(&cmd.command_id, "screenshot_region requires 'x', 'y', 'width' and 'height' parameters")
+        }
+        Err(failed) => return *failed,
+    };
+    let Some((image_width, image_height, image)) = crate::screenshot::capture_region(config, rect) else {
+        return CommandResult::failure(&cmd.command_id, "screen capture failed");
+    };
+
+    let mut result = HashMap::new();
+    result.insert("rect".to_string(), serde_json::json!(rect));
+    result.insert("width".to_string(), serde_json::json!(image_width));
+    result.insert("height".to_string(), serde_json::json!(image_height));
+    if window != HWND(0) {
+        result.insert("hwnd".to_string(), serde_json::json!(crate::event::hwnd_to_hex(window)));
+    }
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = Some(image);
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_screenshot_region(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "screenshot_region requires Windows")
+}
+
+/// Default and largest thumbnail bounds, in pixels.
+#[cfg_attr(not(windows), allow(dead_code))]
+const THUMBNAIL_DEFAULT_SIZE: (u32, u32) = (240, 160);
+#[cfg_attr(not(windows), allow(dead_code))]
+const THUMBNAIL_MAX_SIZE: u32 = 640;
+/// Windows previewed unless the command asks for fewer.
+#[cfg(windows)]
+const THUMBNAIL_DEFAULT_LIMIT: usize = 40;
+
+/// Thumbnail bounds: the `max_width`/`max_height` parameters, kept
+/// between 16 and `THUMBNAIL_MAX_SIZE` pixels.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn thumbnail_bounds(cmd: &Command) -> (u32, u32) {
+    let bound = |key: &str, default: u32| {
+        let value = cmd.parameters.get(key).and_then(|v| v.as_u64()).unwrap_or(default as u64);
+        value.clamp(16, THUMBNAIL_MAX_SIZE as u64) as u32
+    };
+    (bound("max_width", THUMBNAIL_DEFAULT_SIZE.0), bound("max_height", THUMBNAIL_DEFAULT_SIZE.1))
+}
+
+/// Small previews of the open top-level windows (the window named by
+/// `hwnd`/`title`/`process` alone when given), rendered per window with
+/// `PrintWindow` instead of capturing monitors, for window pickers and
+/// cheap backend context. Minimized windows are listed without an image;
+/// cloaked windows (other virtual desktops, suspended apps) and
+/// DesktopAI's own are left out.
+#[cfg(windows)]
+fn handle_get_window_thumbnail(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::WindowsAndMessaging::IsIconic;
+
+    if !config.enable_screenshot {
+        return CommandResult::failure(&cmd.command_id, "screenshots are disabled (ENABLE_SCREENSHOT)");
+    }
+    let targets: Vec<windows::Win32::Foundation::HWND> = if has_window_scope(cmd) {
+        match resolve_window_target(cmd, config) {
+            Ok(hwnd) => vec![hwnd],
+            Err(failed) => return *failed,
+        }
+    } else {
+        let Some(windows) = crate::windows::enumerate_top_level_windows() else {
+            return CommandResult::failure(&cmd.command_id, "could not enumerate windows");
+        };
+        windows
+            .iter()
+            .filter_map(|w| parse_hwnd_param(&serde_json::json!(w.hwnd)).map(windows::Win32::Foundation::HWND))
+            .filter(|&hwnd| !crate::windows::is_self_window(hwnd, config) && !crate::windows::is_cloaked(hwnd))
+            .collect()
+    };
+    let limit = cmd.parameters.get("limit").and_then(|v| v.as_u64()).map_or(THUMBNAIL_DEFAULT_LIMIT, |n| n as usize);
+    let (max_width, max_height) = thumbnail_bounds(cmd);
+
+    let mut thumbnails = Vec::new();
+    for &hwnd in targets.iter().take(limit) {
+        let minimized = unsafe { IsIconic(hwnd) }.as_bool();
+        let mut entry = serde_json::json!({
+            "hwnd": crate::event::hwnd_to_hex(hwnd),
+            "title": crate::windows::window_title(hwnd),
+            "process_exe": crate::windows::window_process_exe(hwnd),
+            "minimized": minimized,
+        });
+        if !minimized {
+            if let Some((width, height, image)) =
+                crate::screenshot::capture_window_thumbnail(hwnd, max_width, max_height, config.screenshot_quality)
+            {
+                entry["width"] = serde_json::json!(width);
+                entry["height"] = serde_json::json!(height);
+                entry["image_b64"] = serde_json::json!(image);
+            }
+        }
+        thumbnails.push(entry);
+    }
+
+    let mut result = HashMap::new();
+    result.insert("count".to_string(), serde_json::json!(thumbnails.len()));
+    result.insert("total".to_string(), serde_json::json!(targets.len()));
+    result.insert("windows".to_string(), serde_json::Value::Array(thumbnails));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_get_window_thumbnail(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "get_window_thumbnail requires Windows")
+}
+
+/// Recognize text on the screen with the on-device Windows OCR engine: the
+/// region given as for `screenshot_region`, else the target window's
+/// monitor. `language` (a BCP-47 tag) picks the engine, defaulting to the
+/// user's profile languages. Returns lines with screen rectangles and the
+/// `detected_language` of the text (see `language`).
+#[cfg(windows)]
+fn handle_ocr(cmd: &Command, config: &Config) -> CommandResult {
+    if !config.enable_screenshot {
+        return CommandResult::failure(&cmd.command_id, "screenshots are disabled (ENABLE_SCREENSHOT)");
+    }
+    let (window, region) = match resolve_capture_region(cmd, config) {
+        Ok(target) => target,
+        Err(failed) => return *failed,
+    };
+    let Some(rect) = region.or_else(|| crate::screenshot::monitor_rect(window)) else {
+        return CommandResult::failure(&cmd.command_id, "could not determine the monitor");
+    };
+    let language = cmd.parameters.get("language").and_then(|v| v.as_str());
+    let lines = match crate::ocr::recognize(rect, language) {
+        Ok(lines) => lines,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+
+    let mut result = HashMap::new();
+    let text = lines.iter().map(|line| line.text.as_str()).collect::<Vec<_>>().join("\n");
+    if config.language_detect_enabled {
+        if let Some(detected) = crate::language::detect(&text) {
+            result.insert("detected_language".to_string(), serde_json::json!(detected));
+        }
+    }
+    result.insert("text".to_string(), serde_json::json!(text));
+    result.insert("lines".to_string(), serde_json::json!(lines));
+    result.insert("rect".to_string(), serde_json::json!(rect));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_ocr(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "ocr requires Windows")
+}
+
+/// Scroll steps tried through a container's ScrollPattern before giving up.
+#[cfg(windows)]
+const MAX_SCROLL_STEPS: usize = 20;
+
+/// Direction to scroll a viewport (`left, top, right, bottom`) so that
+/// `element` moves into it: -1 towards the start, 1 towards the end, 0 when
+/// that axis already fits, as (horizontal, vertical).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn scroll_step(element: [i32; 4], viewport: [i32; 4]) -> (i8, i8) {
+    let axis = |start: i32, end: i32, view_start: i32, view_end: i32| {
+        if start < view_start {
+            -1
+        } else if end > view_end {
+            1
+        } else {
+            0
+        }
+    };
+    (
+        axis(element[0], element[2], viewport[0], viewport[2]),
+        axis(element[1], element[3], viewport[1], viewport[3]),
+    )
+}
+
+/// Bring an off-screen element on screen: ScrollItemPattern when the
+/// element supports it, else page the nearest scrollable ancestor towards
+/// it. Returns how it was done, or `None` if it is still off-screen.
+#[cfg(windows)]
+fn scroll_element_into_view(element: &windows::Win32::UI::Accessibility::IUIAutomationElement) -> Option<&'static str> {
+    use windows::Win32::UI::Accessibility::*;
+
+    let offscreen = || unsafe { element.CurrentIsOffscreen() }.map(|b| b.as_bool()).unwrap_or(false);
+    if !offscreen() {
+        return Some("already_visible");
+    }
+    if let Ok(item) = unsafe { element.GetCurrentPatternAs::<IUIAutomationScrollItemPattern>(UIA_ScrollItemPatternId) } {
+        if unsafe { item.ScrollIntoView() }.is_ok() && !offscreen() {
+            return Some("scroll_item");
+        }
+    }
+
+    let uia = crate::uia::get_uia()?;
+    let walker = unsafe { uia.ControlViewWalker().ok()? };
+    let mut ancestor = unsafe { walker.GetParentElement(element).ok()? };
+    let (container, scroll) = loop {
+        if let Ok(scroll) = unsafe { ancestor.GetCurrentPatternAs::<IUIAutomationScrollPattern>(UIA_ScrollPatternId) } {
+            break (ancestor, scroll);
+        }
+        ancestor = unsafe { walker.GetParentElement(&ancestor).ok()? };
+    };
+    let amount = |step: i8| match step {
+        -1 => ScrollAmount_LargeDecrement,
+        1 => ScrollAmount_LargeIncrement,
+        _ => ScrollAmount_NoAmount,
+    };
+    for _ in 0..MAX_SCROLL_STEPS {
+        let rect = |e: &IUIAutomationElement| unsafe { e.CurrentBoundingRectangle() }.ok().map(|r| [r.left, r.top, r.right, r.bottom]);
+        let (h, v) = scroll_step(rect(element)?, rect(&container)?);
+        if (h, v) == (0, 0) || unsafe { scroll.Scroll(amount(h), amount(v)) }.is_err() {
+            break;
+        }
+        if !offscreen() {
+            return Some("scroll_pattern");
+        }
+    }
+    (!offscreen()).then_some("scroll_pattern")
+}
+
+/// Scroll the element found by `automation_id`/`name` into view and
+/// return its on-screen rect.
+#[cfg(windows)]
+fn handle_scroll_into_view(cmd: &Command, config: &Config) -> CommandResult {
+    let element = match resolve_uia_element(cmd, config) {
+        Ok(element) => element,
+        Err(failed) => return *failed,
+    };
+    let Some(method) = scroll_element_into_view(&element) else {
+        return CommandResult::failure(&cmd.command_id, "element could not be scrolled into view");
+    };
+    let mut result = HashMap::new();
+    result.insert(
+        "name".to_string(),
+        serde_json::json!(unsafe { element.CurrentName() }.map(crate::event::bstr_to_string).unwrap_or_default()),
+    );
+    result.insert("method".to_string(), serde_json::json!(method));
+    if let Ok(r) = unsafe { element.CurrentBoundingRectangle() } {
+        result.insert("rect".to_string(), serde_json::json!([r.left, r.top, r.right - r.left, r.bottom - r.top]));
+    }
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_scroll_into_view(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "scroll_into_view requires Windows")
+}
+
+/// Existing paths named by the command's `path` or `paths` parameters. Each
+/// must be absolute (or relative to the context directory, and inside it).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn existing_paths(cmd: &Command) -> Result<Vec<std::path::PathBuf>, String> {
+    let mut raw: Vec<&str> = Vec::new();
+    if let Some(path) = cmd.parameters.get("path").and_then(|v| v.as_str()) {
+        raw.push(path);
+    }
+    if let Some(paths) = cmd.parameters.get("paths").and_then(|v| v.as_array()) {
+        raw.extend(paths.iter().filter_map(|v| v.as_str()));
+    }
+    if raw.is_empty() {
+        return Err(format!("{} requires 'path' or 'paths' parameter", cmd.action));
+    }
+    let context = crate::policy::context_directory();
+    raw.into_iter()
+        .map(|path| {
+            let path = crate::policy::scope_path(std::path::Path::new(path.trim()), context.as_deref())?;
+            if !path.exists() {
+                return Err(format!("path not found: {}", path.display()));
+            }
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Paths named by a `move_to_recycle_bin` command (see `existing_paths`),
+/// none of which may be a drive or share root.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn recycle_targets(cmd: &Command) -> Result<Vec<std::path::PathBuf>, String> {
+    let paths = existing_paths(cmd)?;
+    if let Some(root) = paths.iter().find(|path| path.parent().is_none()) {
+        return Err(format!("refusing to recycle a root: {}", root.display()));
+    }
+    Ok(paths)
+}
+
+/// Send files or folders to the recycle bin via IFileOperation with
+/// FOF_ALLOWUNDO, so deletions the agent performs stay recoverable.
+#[cfg(windows)]
+fn handle_move_to_recycle_bin(cmd: &Command, _config: &Config) -> CommandResult {
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::{
+        FileOperation, IFileOperation, IShellItem, SHCreateItemFromParsingName, FOFX_EARLYFAILURE,
+        FOFX_RECYCLEONDELETE, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT,
+    };
+
+    let targets = match recycle_targets(cmd) {
+        Ok(targets) => targets,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+
+    let outcome: windows::core::Result<bool> = unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        (|| {
+            let op: IFileOperation = CoCreateInstance(&FileOperation, None, CLSCTX_ALL)?;
+            op.SetOperationFlags(
+                FOF_ALLOWUNDO | FOFX_RECYCLEONDELETE | FOF_NOCONFIRMATION | FOF_SILENT | FOF_NOERRORUI | FOFX_EARLYFAILURE,
+            )?;
+            for path in &targets {
+                let wide: Vec<u16> = path.to_string_lossy().encode_utf16().chain(Some(0)).collect();
+                let item: IShellItem = SHCreateItemFromParsingName(PCWSTR(wide.as_ptr()), None)?;
+                op.DeleteItem(&item, None)?;
+            }
+            op.PerformOperations()?;
+            Ok(!op.GetAnyOperationsAborted()?.as_bool())
+        })()
+    };
+
+    match outcome {
+        Ok(true) => {
+            let paths: Vec<String> = targets.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+            let mut result = HashMap::new();
+            result.insert("count".to_string(), serde_json::json!(paths.len()));
+            result.insert("recycled".to_string(), serde_json::json!(paths));
+            CommandResult::success(&cmd.command_id, result)
+        }
+        Ok(false) => CommandResult::failure(&cmd.command_id, "recycle operation was aborted"),
+        Err(e) => CommandResult::failure(&cmd.command_id, &format!("recycle failed: {e}")),
+    }
+}
+
+#[cfg(not(windows))]
+fn handle_move_to_recycle_bin(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "move_to_recycle_bin requires Windows")
+}
+
+/// Permanently delete the recycle bin's contents (all drives, or `drive`).
+/// Irreversible, so the command must carry `confirm: true`. Reports how many
+/// items and bytes were removed.
+#[cfg(windows)]
+fn handle_empty_recycle_bin(cmd: &Command, _config: &Config) -> CommandResult {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::{
+        SHEmptyRecycleBinW, SHQueryRecycleBinW, SHERB_NOCONFIRMATION, SHERB_NOPROGRESSUI, SHERB_NOSOUND,
+        SHQUERYRBINFO,
+    };
+
+    if cmd.parameters.get("confirm").and_then(|v| v.as_bool()) != Some(true) {
+        return CommandResult::failure(
+            &cmd.command_id,
+            "empty_recycle_bin permanently deletes files; pass confirm: true",
+        );
+    }
+    let drive = cmd.parameters.get("drive").and_then(|v| v.as_str()).unwrap_or("");
+    let drive_w: Vec<u16> = drive.encode_utf16().chain(Some(0)).collect();
+    let root = if drive.is_empty() { PCWSTR::null() } else { PCWSTR(drive_w.as_ptr()) };
+
+    let mut info = SHQUERYRBINFO { cbSize: std::mem::size_of::<SHQUERYRBINFO>() as u32, ..Default::default() };
+    let _ = unsafe { SHQueryRecycleBinW(root, &mut info) };
+
+    let mut result = HashMap::new();
+    result.insert("items".to_string(), serde_json::json!(info.i64NumItems));
+    result.insert("bytes".to_string(), serde_json::json!(info.i64Size));
+    if info.i64NumItems == 0 {
+        result.insert("already_empty".to_string(), serde_json::json!(true));
+        return CommandResult::success(&cmd.command_id, result);
+    }
+    if let Err(e) =
+        unsafe { SHEmptyRecycleBinW(HWND(0), root, SHERB_NOCONFIRMATION | SHERB_NOPROGRESSUI | SHERB_NOSOUND) }
+    {
+        return CommandResult::failure(&cmd.command_id, &format!("failed to empty recycle bin: {e}"));
+    }
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(not(windows))]
+fn handle_empty_recycle_bin(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "empty_recycle_bin requires Windows")
+}
+
+/// How `drop_files` hands the files over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DropMethod {
+    /// WM_DROPFILES where the window accepts it, else OLE.
+    Auto,
+    /// WM_DROPFILES, understood by windows registered with DragAcceptFiles.
+    DropFiles,
+    /// A shell drag-and-drop, for windows registered as OLE drop targets.
+    Ole,
+}
+
+/// The `method` parameter of `drop_files`: `auto` (default), `wm_dropfiles`
+/// or `ole`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn drop_method(cmd: &Command) -> Result<DropMethod, String> {
+    match cmd.parameters.get("method").and_then(|v| v.as_str()).unwrap_or("auto") {
+        "auto" => Ok(DropMethod::Auto),
+        "wm_dropfiles" => Ok(DropMethod::DropFiles),
+        "ole" => Ok(DropMethod::Ole),
+        other => Err(format!("unknown drop method '{other}' (expected auto, wm_dropfiles or ole)")),
+    }
+}
+
+/// The memory block a WM_DROPFILES message carries: a DROPFILES header
+/// (wide paths, dropped at client point `x`, `y`) followed by the
+/// NUL-terminated paths and a final NUL.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn dropfiles_block(paths: &[std::path::PathBuf], x: i32, y: i32) -> Vec<u8> {
+    const HEADER_BYTES: u32 = 20;
+    let mut block = Vec::new();
+    block.extend(HEADER_BYTES.to_le_bytes()); // pFiles
+    block.extend(x.to_le_bytes());
+    block.extend(y.to_le_bytes());
+    block.extend(0i32.to_le_bytes()); // fNC
+    block.extend(1i32.to_le_bytes()); // fWide
+    for path in paths {
+        for unit in path.to_string_lossy().encode_utf16().chain(Some(0)) {
+            block.extend(unit.to_le_bytes());
+        }
+    }
+    block.extend(0u16.to_le_bytes());
+    block
+}
+
+/// `hwnd` or the nearest ancestor up to `top` that accepts WM_DROPFILES.
+#[cfg(windows)]
+fn files_receiver(
+    hwnd: windows::Win32::Foundation::HWND,
+    top: windows::Win32::Foundation::HWND,
+) -> Option<windows::Win32::Foundation::HWND> {
+    use windows::Win32::UI::WindowsAndMessaging::{GetAncestor, GetWindowLongPtrW, GA_PARENT, GWL_EXSTYLE, WS_EX_ACCEPTFILES};
+
+    let mut current = hwnd;
+    while current.0 != 0 {
+        if unsafe { GetWindowLongPtrW(current, GWL_EXSTYLE) } as u32 & WS_EX_ACCEPTFILES.0 != 0 {
+            return Some(current);
+        }
+        if current == top {
+            break;
+        }
+        current = unsafe { GetAncestor(current, GA_PARENT) };
+    }
+    None
+}
+
+/// Post WM_DROPFILES for `paths` to `receiver`, dropped at screen `point`.
+/// The receiver's DragFinish frees the block.
+#[cfg(windows)]
+fn post_dropfiles(
+    receiver: windows::Win32::Foundation::HWND,
+    paths: &[std::path::PathBuf],
+    point: windows::Win32::Foundation::POINT,
+) -> Result<(), String> {
+    use windows::Win32::Foundation::{GlobalFree, LPARAM, WPARAM};
+    use windows::Win32::Graphics::Gdi::ScreenToClient;
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE, GMEM_ZEROINIT};
+    use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_DROPFILES};
+
+    let mut client = point;
+    let _ = unsafe { ScreenToClient(receiver, &mut client) };
+    let block = dropfiles_block(paths, client.x, client.y);
+    unsafe {
+        let memory = GlobalAlloc(GMEM_MOVEABLE | GMEM_ZEROINIT, block.len()).map_err(|e| format!("GlobalAlloc failed: {e}"))?;
+        let data = GlobalLock(memory) as *mut u8;
+        if data.is_null() {
+            let _ = GlobalFree(memory);
+            return Err("GlobalLock failed".to_string());
+        }
+        std::ptr::copy_nonoverlapping(block.as_ptr(), data, block.len());
+        let _ = GlobalUnlock(memory);
+        before_input();
+        if let Err(e) = PostMessageW(receiver, WM_DROPFILES, WPARAM(memory.0 as usize), LPARAM(0)) {
+            let _ = GlobalFree(memory);
+            return Err(format!("PostMessage(WM_DROPFILES) failed: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// Drop `paths` at screen `point` with a shell drag-and-drop. The drag
+/// starts with the cursor already on the point and no button held, so the
+/// default drop source drops straight away. Only copy and link are offered,
+/// never move. Returns whether the target took the files.
+#[cfg(windows)]
+fn ole_drop(paths: &[std::path::PathBuf], point: windows::Win32::Foundation::POINT) -> Result<bool, String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::IDataObject;
+    use windows::Win32::System::Ole::{IDropSource, OleInitialize, DROPEFFECT_COPY, DROPEFFECT_LINK, DROPEFFECT_NONE};
+    use windows::Win32::UI::Shell::Common::ITEMIDLIST;
+    use windows::Win32::UI::Shell::{BHID_DataObject, ILFree, SHCreateShellItemArrayFromIDLists, SHDoDragDrop, SHParseDisplayName};
+    use windows::Win32::UI::WindowsAndMessaging::SetCursorPos;
+
+    unsafe {
+        OleInitialize(None).map_err(|e| format!("OleInitialize failed: {e}"))?;
+        let mut pidls: Vec<*const ITEMIDLIST> = Vec::new();
+        let mut parsed = Ok(());
+        for path in paths {
+            let wide: Vec<u16> = path.to_string_lossy().encode_utf16().chain(Some(0)).collect();
+            let mut pidl: *mut ITEMIDLIST = std::ptr::null_mut();
+            if let Err(e) = SHParseDisplayName(PCWSTR(wide.as_ptr()), None, &mut pidl, 0, None) {
+                parsed = Err(format!("cannot resolve {}: {e}", path.display()));
+                break;
+            }
+            pidls.push(pidl);
+        }
+        let data = parsed.and_then(|_| {
+            SHCreateShellItemArrayFromIDLists(&pidls)
+                .and_then(|items| items.BindToHandler::<_, IDataObject>(None, &BHID_DataObject))
+                .map_err(|e| format!("cannot build the drag data: {e}"))
+        });
+        for pidl in pidls {
+            ILFree(Some(pidl));
+        }
+        let data = data?;
+
+        SetCursorPos(point.x, point.y).map_err(|e| format!("SetCursorPos failed: {e}"))?;
+        before_input();
+        let effect = SHDoDragDrop(HWND(0), &data, None::<&IDropSource>, DROPEFFECT_COPY | DROPEFFECT_LINK)
+            .map_err(|e| format!("drag and drop failed: {e}"))?;
+        Ok(effect != DROPEFFECT_NONE)
+    }
+}
+
+/// Drop files (`path` or `paths`, see `existing_paths`) onto a window picked
+/// like `focus_window`, at screen point `x`, `y` (default: the window's
+/// centre), e.g. to attach them to an email or an upload form. The window is
+/// brought to the front first. `method` picks WM_DROPFILES or an OLE drop;
+/// `auto` uses WM_DROPFILES where the window under the point accepts it.
+#[cfg(windows)]
+fn handle_drop_files(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::Foundation::{POINT, RECT};
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let paths = match existing_paths(cmd) {
+        Ok(paths) => paths,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let method = match drop_method(cmd) {
+        Ok(method) => method,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let target = match resolve_window_target(cmd, config) {
+        Ok(target) => target,
+        Err(failed) => return *failed,
+    };
+
+    unsafe {
+        if IsIconic(target).as_bool() {
+            let _ = ShowWindow(target, SW_RESTORE);
+        }
+        simulate_alt_key();
+        let _ = SetForegroundWindow(target);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    let mut rect = RECT::default();
+    let _ = unsafe { GetWindowRect(target, &mut rect) };
+    let coord = |key: &str, default: i32| cmd.parameters.get(key).and_then(|v| v.as_i64()).map_or(default, |v| v as i32);
+    let point = POINT { x: coord("x", (rect.left + rect.right) / 2), y: coord("y", (rect.top + rect.bottom) / 2) };
+    let under = unsafe { WindowFromPoint(point) };
+    let on_target = under.0 != 0 && unsafe { GetAncestor(under, GA_ROOT) } == target;
+    let receiver = if on_target { files_receiver(under, target) } else { files_receiver(target, target) };
+
+    let method = match method {
+        DropMethod::Auto if receiver.is_some() => DropMethod::DropFiles,
+        DropMethod::Auto => DropMethod::Ole,
+        chosen => chosen,
+    };
+    let dropped = match method {
+        DropMethod::DropFiles => post_dropfiles(receiver.unwrap_or(target), &paths, point).map(|_| true),
+        _ if !on_target => Err(format!("({}, {}) is not on the target window; it may be covered", point.x, point.y)),
+        _ => ole_drop(&paths, point),
+    };
+    match dropped {
+        Ok(true) => {}
+        Ok(false) => return CommandResult::failure(&cmd.command_id, "the window did not accept the files"),
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    }
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let files: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let mut result = window_fields(target);
+    result.insert("method".to_string(), serde_json::json!(if method == DropMethod::DropFiles { "wm_dropfiles" } else { "ole" }));
+    result.insert("x".to_string(), serde_json::json!(point.x));
+    result.insert("y".to_string(), serde_json::json!(point.y));
+    result.insert("count".to_string(), serde_json::json!(files.len()));
+    result.insert("files".to_string(), serde_json::json!(files));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_drop_files(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "drop_files requires Windows")
+}
+
+/// Process image names `browser_navigate` recognizes as browsers.
+const BROWSER_PROCESSES: &[&str] = &["chrome.exe", "msedge.exe", "firefox.exe", "brave.exe", "vivaldi.exe", "opera.exe"];
+/// Longest URL `browser_navigate` types.
+const MAX_URL_CHARS: usize = 4096;
+/// How long `browser_navigate` waits for the title to change unless
+/// `wait_ms` says otherwise, and the most it accepts.
+#[cfg(windows)]
+const DEFAULT_NAVIGATE_WAIT_MS: u64 = 5000;
+#[cfg(windows)]
+const MAX_NAVIGATE_WAIT_MS: u64 = 30_000;
+
+/// Whether a process path or image name belongs to a known browser.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn is_browser_process(process_exe: &str) -> bool {
+    let exe = process_exe.rsplit(['\\', '/']).next().unwrap_or(process_exe);
+    BROWSER_PROCESSES.iter().any(|browser| exe.eq_ignore_ascii_case(browser))
+}
+
+/// The `url` parameter of `browser_navigate`, trimmed. Anything the address
+/// bar accepts goes, search terms included, except script URLs and text
+/// with line breaks or other control characters, which would be typed as
+/// keystrokes of their own.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn navigation_url(cmd: &Command) -> Result<String, String> {
+    let url = cmd.parameters.get("url").and_then(|v| v.as_str()).map(str::trim).unwrap_or("");
+    if url.is_empty() {
+        return Err("browser_navigate requires 'url' parameter".to_string());
+    }
+    if url.chars().count() > MAX_URL_CHARS {
+        return Err(format!("url is longer than {MAX_URL_CHARS} characters"));
+    }
+    if url.chars().any(char::is_control) {
+        return Err("url must not contain control characters".to_string());
+    }
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme.trim().to_lowercase());
+    if matches!(scheme.as_deref(), Some("javascript" | "vbscript")) {
+        return Err(format!("{} URLs are not allowed", scheme.unwrap_or_default()));
+    }
+    Ok(url.to_string())
+}
+
+/// The browser window `browser_navigate` drives: the one the command names
+/// (see `resolve_window_target`), else the foreground window if it is a
+/// browser, else the frontmost browser window.
+#[cfg(windows)]
+fn browser_window(cmd: &Command, config: &Config) -> Result<windows::Win32::Foundation::HWND, Box<CommandResult>> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    if has_window_scope(cmd) {
+        return resolve_window_target(cmd, config);
+    }
+    let foreground = unsafe { GetForegroundWindow() };
+    let mut pid = 0;
+    unsafe { GetWindowThreadProcessId(foreground, Some(&mut pid)) };
+    if foreground.0 != 0 && is_browser_process(&crate::windows::process_path(pid)) {
+        return Ok(foreground);
+    }
+    find_windows("", "", None, crate::policy::self_targeting_allowed(cmd, config), config)
+        .into_iter()
+        .find(|w| is_browser_process(&w.process_exe))
+        .and_then(|w| parse_hwnd_param(&serde_json::json!(w.hwnd)))
+        .map(HWND)
+        .ok_or_else(|| {
+            Box::new(CommandResult::failure(
+                &cmd.command_id,
+                "no browser window found; open one or name it with 'title' or 'process'",
+            ))
+        })
+}
+
+/// The address bar of a browser window: the first edit field above the
+/// page (so never a text box on the page itself), where Chrome, Edge and
+/// Firefox expose it.
+#[cfg(windows)]
+fn address_bar(
+    window: windows::Win32::Foundation::HWND,
+) -> Option<windows::Win32::UI::Accessibility::IUIAutomationElement> {
+    use windows::Win32::UI::Accessibility::*;
+
+    let uia = crate::uia::get_uia()?;
+    let root = unsafe { uia.ElementFromHandle(window) }.ok()?;
+    let of_type = |id: UIA_CONTROLTYPE_ID| unsafe { uia.CreatePropertyCondition(UIA_ControlTypePropertyId, i32_to_variant(id.0 as i32)) };
+    let page_top = of_type(UIA_DocumentControlTypeId)
+        .and_then(|condition| unsafe { root.FindFirst(TreeScope_Descendants, &condition) })
+        .and_then(|document| unsafe { document.CurrentBoundingRectangle() })
+        .map_or(i32::MAX, |rect| rect.top);
+    let edits = of_type(UIA_EditControlTypeId)
+        .and_then(|condition| unsafe { root.FindAll(TreeScope_Descendants, &condition) })
+        .ok()?;
+    let count = unsafe { edits.Length() }.unwrap_or(0);
+    (0..count).filter_map(|i| unsafe { edits.GetElement(i) }.ok()).find(|edit| {
+        let visible = !unsafe { edit.CurrentIsOffscreen() }.map(|b| b.as_bool()).unwrap_or(true);
+        visible && unsafe { edit.CurrentBoundingRectangle() }.is_ok_and(|rect| rect.bottom <= page_top)
+    })
+}
+
+/// Open `url` in a browser window (see `browser_window`): focus it, with
+/// `new_tab: true` open a tab first, put the URL into the address bar (see
+/// `address_bar`; ctrl+L and typing when it cannot be found or set) and
+/// press Enter. Waits up to `wait_ms` for the window title to change and
+/// reports the window with its resulting title, `title_changed` and what
+/// the address bar shows.
+#[cfg(windows)]
+fn handle_browser_navigate(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Accessibility::*;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    let url = match navigation_url(cmd) {
+        Ok(url) => url,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let window = match browser_window(cmd, config) {
+        Ok(window) => window,
+        Err(failed) => return *failed,
+    };
+    let new_tab = cmd.parameters.get("new_tab").and_then(|v| v.as_bool()).unwrap_or(false);
+    let wait_ms = cmd.parameters.get("wait_ms").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_NAVIGATE_WAIT_MS).min(MAX_NAVIGATE_WAIT_MS);
+
+    unsafe {
+        if IsIconic(window).as_bool() {
+            let _ = ShowWindow(window, SW_RESTORE);
+        }
+        simulate_alt_key();
+        let _ = SetForegroundWindow(window);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    if unsafe { GetForegroundWindow() } != window {
+        return CommandResult::failure(&cmd.command_id, "could not bring the browser window to the foreground");
+    }
+    if new_tab {
+        if let Err(e) = press_keys("ctrl+t") {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+        if !crate::cancel::pause(std::time::Duration::from_millis(300)) {
+            return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        }
+    }
+    let before = crate::windows::window_title(window);
+
+    let bar = address_bar(window);
+    let set_by_pattern = bar.as_ref().is_some_and(|bar| set_element_value(bar, &url, true));
+    if !set_by_pattern {
+        if let Err(e) = press_keys("ctrl+l").and_then(|_| press_keys("ctrl+a")) {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+        send_text_via_input(&url, None);
+        if crate::cancel::cancelled() {
+            return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        }
+    }
+    if let Err(e) = press_keys("enter") {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+
+    let started = std::time::Instant::now();
+    let deadline = started + std::time::Duration::from_millis(wait_ms);
+    let changed = match poll_until(deadline, || {
+        let title = crate::windows::window_title(window);
+        (!title.is_empty() && title != before).then_some(())
+    }) {
+        Ok(changed) => changed.is_some(),
+        Err(()) => return CommandResult::cancelled(&cmd.command_id, &cmd.action),
+    };
+
+    let mut result = window_fields(window);
+    result.insert("url".to_string(), serde_json::json!(url));
+    result.insert("method".to_string(), serde_json::json!(if set_by_pattern { "value_pattern" } else { "send_input" }));
+    result.insert("new_tab".to_string(), serde_json::json!(new_tab));
+    result.insert("title_changed".to_string(), serde_json::json!(changed));
+    result.insert("waited_ms".to_string(), serde_json::json!(started.elapsed().as_millis() as u64));
+    let address = address_bar(window)
+        .and_then(|bar| unsafe { bar.GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId) }.ok())
+        .and_then(|value| unsafe { value.CurrentValue() }.ok())
+        .map(crate::event::bstr_to_string);
+    if let Some(address) = address {
+        result.insert("address".to_string(), serde_json::json!(address));
+    }
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_browser_navigate(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "browser_navigate requires Windows")
+}
+
+/// Bytes of stdout/stderr kept per stream in `run_shell` results.
+const MAX_SHELL_OUTPUT: usize = 16 * 1024;
+
+/// Read a child's pipe to the end on a separate thread, keeping the first
+/// `MAX_SHELL_OUTPUT` bytes. Draining fully keeps the child from blocking on
+/// a full pipe.
+fn spawn_output_reader<R: std::io::Read + Send + 'static>(mut pipe: R) -> std::thread::JoinHandle<(Vec<u8>, bool)> {
+    std::thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let room = MAX_SHELL_OUTPUT.saturating_sub(kept.len());
+            kept.extend_from_slice(&buf[..n.min(room)]);
+            truncated |= n > room;
+        }
+        (kept, truncated)
+    })
+}
+
+/// Run a program with arguments (no shell interpretation), wait up to the
+/// command's `timeout_ms`, and return its exit code and truncated output.
+/// Parameters: `program`, optional `args` (array of strings) and `cwd`.
+/// With a context directory set, `cwd` defaults to it and must stay inside it.
+/// A program that outlives the timeout, or whose command is cancelled, is
+/// killed and reported as failed.
+fn handle_run_shell(cmd: &Command, _config: &Config) -> CommandResult {
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let Some(program) = cmd.parameters.get("program").and_then(|v| v.as_str()).filter(|p| !p.is_empty()) else {
+        return CommandResult::failure(&cmd.command_id, "run_shell requires 'program' parameter");
+    };
+    let args: Vec<String> = match cmd.parameters.get("args") {
+        None => Vec::new(),
+        Some(serde_json::Value::Array(items)) => match items.iter().map(|v| v.as_str().map(str::to_string)).collect() {
+            Some(args) => args,
+            None => return CommandResult::failure(&cmd.command_id, "'args' must be an array of strings"),
+        },
+        Some(_) => return CommandResult::failure(&cmd.command_id, "'args' must be an array of strings"),
+    };
+
+    let mut process = std::process::Command::new(program);
+    process.args(&args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let context = crate::policy::context_directory();
+    match (cmd.parameters.get("cwd").and_then(|v| v.as_str()), &context) {
+        (Some(cwd), Some(_)) => match crate::policy::scope_path(std::path::Path::new(cwd), context.as_deref()) {
+            Ok(cwd) => {
+                process.current_dir(cwd);
+            }
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        },
+        (Some(cwd), None) => {
+            process.current_dir(cwd);
+        }
+        (None, Some(dir)) => {
+            process.current_dir(dir);
+        }
+        (None, None) => {}
+    }
+    let started = Instant::now();
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &format!("failed to start {program}: {e}")),
+    };
+    let stdout = child.stdout.take().map(spawn_output_reader);
+    let stderr = child.stderr.take().map(spawn_output_reader);
+
+    let timeout = Duration::from_millis(cmd.timeout_ms);
+    let cancel = crate::cancel::current();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() >= timeout || cancel.is_cancelled() => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &format!("failed to wait for {program}: {e}")),
+        }
+    };
+
+    let mut result = HashMap::new();
+    for (name, reader) in [("stdout", stdout), ("stderr", stderr)] {
+        // After a kill, grandchildren may still hold the pipe open; don't wait on them
+        let reader = reader.filter(|r| {
+            let grace = Instant::now() + Duration::from_millis(500);
+            while status.is_none() && !r.is_finished() && Instant::now() < grace {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            status.is_some() || r.is_finished()
+        });
+        let (bytes, truncated) = reader.and_then(|r| r.join().ok()).unwrap_or_default();
+        result.insert(name.to_string(), serde_json::json!(String::from_utf8_lossy(&bytes)));
+        if truncated {
+            result.insert(format!("{name}_truncated"), serde_json::json!(true));
+        }
+    }
+    result.insert("duration_ms".to_string(), serde_json::json!(started.elapsed().as_millis() as u64));
+
+    let Some(status) = status else {
+        let mut failed = if cancel.is_cancelled() {
+            CommandResult::cancelled(&cmd.command_id, &cmd.action)
+        } else {
+            result.insert("timed_out".to_string(), serde_json::json!(true));
+            CommandResult::failure(&cmd.command_id, &format!("{program} timed out after {}ms", cmd.timeout_ms))
+        };
+        failed.result = result;
+        return failed;
+    };
+    result.insert("exit_code".to_string(), serde_json::json!(status.code()));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+/// Wheel event for a scroll `direction`: whether it is horizontal
+/// (MOUSEEVENTF_HWHEEL) and the signed delta, WHEEL_DELTA (120) per notch.
+/// Vertical wheel is positive upward, horizontal positive to the right.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn scroll_wheel(direction: &str, amount: i32) -> Result<(bool, i32), String> {
+    match direction {
+        "up" => Ok((false, 120 * amount)),
+        "down" => Ok((false, -120 * amount)),
+        "right" => Ok((true, 120 * amount)),
+        "left" => Ok((true, -120 * amount)),
+        _ => Err(format!("unknown scroll direction: {direction}")),
+    }
+}
+
+#[cfg(windows)]
+fn handle_scroll(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+    use windows::Win32::Foundation::RECT;
+
+    let direction = cmd.parameters.get("direction").and_then(|v| v.as_str()).unwrap_or("down");
+    let amount = cmd.parameters.get("amount").and_then(|v| v.as_i64()).unwrap_or(3) as i32;
+    let (name, automation_id) = element_target(cmd);
+
+    let (horizontal, wheel_delta) = match scroll_wheel(direction, amount) {
+        Ok(wheel) => wheel,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+
+    // MOUSEEVENTF_WHEEL delivers to the window under the cursor, NOT the
+    // focused window, so we must position the cursor over the target: the
+    // named element, the given x/y, or else the foreground window's center.
+    let target = if !name.is_empty() || !automation_id.is_empty() {
+        match resolve_uia_coords(cmd, config, name, automation_id) {
+            Ok(Some(coords)) => Some(coords),
+            Err(failed) => return *failed,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+        }
+    } else {
+        match point_param(cmd, config) {
+            Ok(point) => point,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        }
+    };
+    let point = match target {
+        Some((x, y)) => {
+            if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+                return denied;
+            }
+            Some((x, y))
+        }
+        None => {
+            let fg = unsafe { GetForegroundWindow() };
+            let mut rect = RECT::default();
+            (fg.0 != 0 && unsafe { GetWindowRect(fg, &mut rect) }.is_ok())
+                .then(|| ((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2))
+        }
+    };
+    if let Some((x, y)) = point {
+        let move_input = mouse_input_at(x, y, MOUSE_EVENT_FLAGS(0));
+        before_input();
+        unsafe { SendInput(&[move_input], std::mem::size_of::<INPUT>() as i32); }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let input = INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx: 0,
+                dy: 0,
+                mouseData: wheel_delta as u32,
+                dwFlags: if horizontal { MOUSEEVENTF_HWHEEL } else { MOUSEEVENTF_WHEEL },
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    };
+    before_input();
+    unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32); }
+
+    let mut result = HashMap::new();
+    result.insert("direction".to_string(), serde_json::Value::String(direction.to_string()));
+    result.insert("amount".to_string(), serde_json::json!(amount));
+    if let Some((x, y)) = point {
+        result.insert("x".to_string(), serde_json::json!(x));
+        result.insert("y".to_string(), serde_json::json!(y));
+    }
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_scroll(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "scroll requires Windows")
+}
+
+/// Resolve a UIA element by name or automation_id (within the window the
+/// command names, if any) and return its bounding rect center; `None` when
+/// no element matches.
+#[cfg(windows)]
+fn resolve_uia_coords(cmd: &Command, config: &Config, name: &str, automation_id: &str) -> Result<Option<(i32, i32)>, Box<CommandResult>> {
+    use windows::Win32::UI::Accessibility::*;
+    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+    unsafe { let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED); }
+
+    let Ok(uia) = (unsafe {
+        windows::Win32::System::Com::CoCreateInstance::<_, IUIAutomation>(
+            &CUIAutomation, None,
+            windows::Win32::System::Com::CLSCTX_INPROC_SERVER,
+        )
+    }) else {
+        return Ok(None);
+    };
+    let root = uia_search_root(cmd, config, &uia)?;
+
+    let condition = if !automation_id.is_empty() {
+        unsafe { uia.CreatePropertyCondition(UIA_AutomationIdPropertyId, bstr_to_variant(automation_id)) }
+    } else {
+        unsafe { uia.CreatePropertyCondition(UIA_NamePropertyId, bstr_to_variant(name)) }
+    };
+    let center = condition
+        .and_then(|condition| unsafe { root.FindFirst(TreeScope_Descendants, &condition) })
+        .and_then(|element| unsafe { element.CurrentBoundingRectangle() })
+        .ok()
+        .map(|rect| ((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2));
+    Ok(center)
+}
+
+/// The `button` of a click command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// Back in browsers and file managers.
+    X1,
+    /// Forward.
+    X2,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl MouseButton {
+    /// The `button` parameter, `default` when absent.
+    fn from_param(value: Option<&serde_json::Value>, default: Self) -> Result<Self, String> {
+        match value.map(|v| v.as_str().unwrap_or_default().to_lowercase()).as_deref() {
+            None => Ok(default),
+            Some("left") => Ok(Self::Left),
+            Some("right") => Ok(Self::Right),
+            Some("middle") => Ok(Self::Middle),
+            Some("x1") => Ok(Self::X1),
+            Some("x2") => Ok(Self::X2),
+            Some(_) => Err("button must be \"left\", \"right\", \"middle\", \"x1\" or \"x2\"".to_string()),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Middle => "middle",
+            Self::X1 => "x1",
+            Self::X2 => "x2",
+        }
+    }
+
+    /// SendInput press and release at screen point (x, y); the X buttons
+    /// say which one in `mouseData`.
+    #[cfg(windows)]
+    fn press_at(self, x: i32, y: i32) -> [windows::Win32::UI::Input::KeyboardAndMouse::INPUT; 2] {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        use windows::Win32::UI::WindowsAndMessaging::{XBUTTON1, XBUTTON2};
+
+        let (down, up, data) = match self {
+            Self::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, 0),
+            Self::Right => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, 0),
+            Self::Middle => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, 0),
+            Self::X1 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, u32::from(XBUTTON1)),
+            Self::X2 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, u32::from(XBUTTON2)),
+        };
+        let mut press = [mouse_input_at(x, y, down), mouse_input_at(x, y, up)];
+        for input in &mut press {
+            input.Anonymous.mi.mouseData = data;
+        }
+        press
+    }
+}
+
+/// Most clicks `click_count` may ask for.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MAX_CLICK_COUNT: u64 = 5;
+
+/// Longest pause between the clicks of a multi-click.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MAX_CLICK_GAP_MS: u32 = 50;
+
+/// `click_count` of a click command, `default` when not given.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn click_count(cmd: &Command, default: u64) -> Result<u64, String> {
+    match cmd.parameters.get("click_count") {
+        None | Some(serde_json::Value::Null) => Ok(default),
+        Some(value) => value
+            .as_u64()
+            .filter(|count| (1..=MAX_CLICK_COUNT).contains(count))
+            .ok_or_else(|| format!("click_count must be 1 to {MAX_CLICK_COUNT}")),
+    }
+}
+
+/// Pause between the clicks of a `clicks`-fold click so that all of them
+/// land within half the system double-click time (`GetDoubleClickTime`),
+/// which apps use to tell a triple click from two separate ones.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn click_gap_ms(double_click_ms: u32, clicks: u64) -> u32 {
+    let gaps = clicks.saturating_sub(1).max(1) as u32;
+    (double_click_ms / 2 / gaps).clamp(1, MAX_CLICK_GAP_MS)
+}
+
+/// Click `click_count` times in a row (default 2 for `double_click`, 3 for
+/// `triple_click`, e.g. to select a word, line or paragraph) on the
+/// `name`/`automation_id` element or the `x`/`y` point. Each click is its
+/// own SendInput press and release at the same point, paced by
+/// `click_gap_ms` so the app counts them as one multi-click; touch and pen
+/// tap instead. `button` picks the mouse button (default middle for
+/// `middle_click`, e.g. to close a browser tab or open a link in a new
+/// one; `x1`/`x2` are back and forward). `click` with a `click_count`
+/// above 1 or another button lands here too.
+#[cfg(windows)]
+fn handle_multi_click(cmd: &Command, config: &Config, default_count: u64, default_button: MouseButton) -> CommandResult {
+    // Support name-based UIA resolution (same as click), with x/y fallback
+    let (name, automation_id) = element_target(cmd);
+    let pointer = match PointerKind::from_param(cmd.parameters.get("pointer")) {
+        Ok(pointer) => pointer,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let count = match click_count(cmd, default_count) {
+        Ok(count) => count,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    let button = match MouseButton::from_param(cmd.parameters.get("button"), default_button) {
+        Ok(button) => button,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+    if button != MouseButton::Left && pointer != PointerKind::Mouse {
+        return CommandResult::failure(
+            &cmd.command_id,
+            &format!("button '{}' needs the mouse pointer", button.as_str()),
+        );
+    }
+
+    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
+        match resolve_uia_coords(cmd, config, name, automation_id) {
+            Ok(Some(coords)) => coords,
+            Err(failed) => return *failed,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+        }
+    } else {
+        match point_param(cmd, config) {
+            Ok(Some(point)) => point,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("{} requires 'name', 'automation_id', 'label', or 'x'/'y' parameters", cmd.action)),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        }
+    };
+    if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+        return denied;
+    }
+
+    let mut gap_ms = None;
+    if pointer == PointerKind::Mouse {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+        let gap = click_gap_ms(unsafe { GetDoubleClickTime() }, count);
+        let press = button.press_at(x, y);
+        before_input();
+        for click in 0..count {
+            if click > 0 {
+                std::thread::sleep(std::time::Duration::from_millis(u64::from(gap)));
+            }
+            unsafe { SendInput(&press, std::mem::size_of::<INPUT>() as i32); }
+        }
+        gap_ms = Some(gap);
+    } else if let Err(e) = tap_at(pointer, x, y, count as usize) {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+
+    let mut result = HashMap::new();
+    result.insert("x".to_string(), serde_json::json!(x));
+    result.insert("y".to_string(), serde_json::json!(y));
+    result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
+    result.insert("click_count".to_string(), serde_json::json!(count));
+    result.insert("button".to_string(), serde_json::json!(button.as_str()));
+    if let Some(gap) = gap_ms {
+        result.insert("interval_ms".to_string(), serde_json::json!(gap));
+    }
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_multi_click(cmd: &Command, _config: &Config, _default_count: u64, _default_button: MouseButton) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
+
+#[cfg(windows)]
+fn handle_right_click(cmd: &Command, config: &Config) -> CommandResult {
+    // Support name-based UIA resolution (same as click/double_click), with x/y fallback
+    let (name, automation_id) = element_target(cmd);
+    let pointer = match PointerKind::from_param(cmd.parameters.get("pointer")) {
+        Ok(pointer) => pointer,
+        Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+    };
+
+    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
+        match resolve_uia_coords(cmd, config, name, automation_id) {
+            Ok(Some(coords)) => coords,
+            Err(failed) => return *failed,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+        }
+    } else {
+        match point_param(cmd, config) {
+            Ok(Some(point)) => point,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, "right_click requires 'name', 'automation_id', 'label', or 'x'/'y' parameters"),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        }
+    };
+    if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+        return denied;
+    }
+
+    if pointer == PointerKind::Mouse {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+        let inputs = [
+            mouse_input_at(x, y, MOUSEEVENTF_RIGHTDOWN),
+            mouse_input_at(x, y, MOUSEEVENTF_RIGHTUP),
+        ];
+
+        before_input();
+        unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32); }
+    } else {
+        // Touch and pen right-click by pressing and holding
+        let hold = crate::pointer::press(x, y, crate::pointer::frame_count(LONG_PRESS));
+        if let Err(e) = crate::pointer::SyntheticPointer::new(pointer, 1).and_then(|device| device.play(&hold)) {
+            return CommandResult::failure(&cmd.command_id, &e);
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("x".to_string(), serde_json::json!(x));
+    result.insert("y".to_string(), serde_json::json!(y));
+    result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_right_click(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "right_click requires Windows")
+}
+
+#[cfg(windows)]
+const DEFAULT_SWIPE_DISTANCE: i64 = 300;
+#[cfg(windows)]
+const DEFAULT_SWIPE_MS: u64 = 300;
+#[cfg(windows)]
+const DEFAULT_FLICK_MS: u64 = 100;
+#[cfg(windows)]
+const DEFAULT_PINCH_DISTANCE: i64 = 200;
+#[cfg(windows)]
+const DEFAULT_PINCH_MS: u64 = 400;
+#[cfg(windows)]
+const MAX_GESTURE_MS: u64 = 5000;
+
+/// Gestures: `swipe` drags one contact from the element or `x`/`y` to
+/// `to_x`/`to_y`, or `distance` pixels toward `direction`, easing in and
+/// out; `flick` does the same quickly and lifts at full speed so the target
+/// keeps scrolling; `pinch` moves two contacts centred there from
+/// `distance` pixels apart to `distance * scale` (above 1 zooms in, below 1
+/// zooms out). `duration_ms` sets the speed. Swipes and flicks use touch
+/// unless `pointer` picks `pen` or `mouse` (a left-button drag); pinches
+/// are touch only.
+#[cfg(windows)]
+fn handle_gesture(cmd: &Command, config: &Config) -> CommandResult {
+    use crate::pointer::{ease_in, ease_in_out, frame_count, pinch, swipe, swipe_end, SyntheticPointer};
+
+    let (name, automation_id) = element_target(cmd);
+    let pointer = match cmd.parameters.get("pointer") {
+        None => PointerKind::Touch,
+        value => match PointerKind::from_param(value) {
+            Ok(PointerKind::Mouse | PointerKind::Pen) if cmd.action == "pinch" => {
+                return CommandResult::failure(&cmd.command_id, "pinch requires a touch pointer")
+            }
+            Ok(pointer) => pointer,
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        },
+    };
+    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
+        match resolve_uia_coords(cmd, config, name, automation_id) {
+            Ok(Some(coords)) => coords,
+            Err(failed) => return *failed,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+        }
+    } else {
+        match point_param(cmd, config) {
+            Ok(Some(point)) => point,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("{} requires 'name', 'automation_id', 'label', or 'x'/'y' parameters", cmd.action)),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        }
+    };
+    if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+        return denied;
+    }
+    let default_ms = match cmd.action.as_str() {
+        "pinch" => DEFAULT_PINCH_MS,
+        "flick" => DEFAULT_FLICK_MS,
+        _ => DEFAULT_SWIPE_MS,
+    };
+    let duration_ms = cmd.parameters.get("duration_ms").and_then(|v| v.as_u64()).unwrap_or(default_ms).min(MAX_GESTURE_MS);
+    let frames = frame_count(std::time::Duration::from_millis(duration_ms));
+
+    let mut result = HashMap::new();
+    let (gesture, contacts) = if cmd.action == "pinch" {
+        let Some(scale) = cmd.parameters.get("scale").and_then(|v| v.as_f64()).filter(|s| *s > 0.0) else {
+            return CommandResult::failure(&cmd.command_id, "pinch requires a positive 'scale' parameter");
+        };
+        let start = cmd.parameters.get("distance").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_PINCH_DISTANCE).clamp(10, 4000) as i32;
+        let end = (f64::from(start) * scale).round().clamp(10.0, 4000.0) as i32;
+        result.insert("scale".to_string(), serde_json::json!(scale));
+        result.insert("distance".to_string(), serde_json::json!([start, end]));
+        (pinch((x, y), start, end, frames), 2)
+    } else {
+        let to = match resolve_point(cmd, config, "to_x", "to_y") {
+            Ok(Some(to)) => Some(to),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+            Ok(None) => {
+                let direction = cmd.parameters.get("direction").and_then(|v| v.as_str()).unwrap_or("");
+                let distance = cmd.parameters.get("distance").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_SWIPE_DISTANCE).clamp(1, 10_000) as i32;
+                swipe_end((x, y), direction, distance)
+            }
+        };
+        let Some(to) = to else {
+            return CommandResult::failure(
+                &cmd.command_id,
+                &format!("{} requires 'to_x'/'to_y' or 'direction' (left, right, up or down)", cmd.action),
+            );
+        };
+        result.insert("to".to_string(), serde_json::json!([to.0, to.1]));
+        let ease = if cmd.action == "flick" { ease_in } else { ease_in_out };
+        (swipe((x, y), to, frames, ease), 1)
+    };
+    let screen = virtual_screen();
+    if let Some(&(px, py)) = gesture.iter().flatten().find(|(px, py)| !on_screen(i64::from(*px), i64::from(*py), screen)) {
+        return CommandResult::failure(&cmd.command_id, &format!("{} leaves the screen at ({px}, {py})", cmd.action));
+    }
+    if pointer == PointerKind::Mouse {
+        drag_mouse(&gesture);
+    } else if let Err(e) = SyntheticPointer::new(pointer, contacts).and_then(|device| device.play(&gesture)) {
+        return CommandResult::failure(&cmd.command_id, &e);
+    }
+    if crate::cancel::cancelled() {
+        return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+    }
+
+    result.insert("x".to_string(), serde_json::json!(x));
+    result.insert("y".to_string(), serde_json::json!(y));
+    result.insert("pointer".to_string(), serde_json::json!(pointer.as_str()));
+    result.insert("duration_ms".to_string(), serde_json::json!(duration_ms));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+/// Replay a one-contact gesture as a left-button mouse drag, one frame
+/// every `pointer::FRAME_INTERVAL`. A cancelled command releases the button
+/// where the drag has got to.
+#[cfg(windows)]
+fn drag_mouse(gesture: &[crate::pointer::Frame]) {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let points: Vec<(i32, i32)> = gesture.iter().filter_map(|frame| frame.first().copied()).collect();
+    let (Some(&(first_x, first_y)), Some(&(last_x, last_y))) = (points.first(), points.last()) else {
+        return;
+    };
+    let size = std::mem::size_of::<INPUT>() as i32;
+    before_input();
+    unsafe { SendInput(&[mouse_input_at(first_x, first_y, MOUSEEVENTF_LEFTDOWN)], size) };
+    let (mut up_x, mut up_y) = (last_x, last_y);
+    for (i, &(x, y)) in points.iter().enumerate().skip(1) {
+        if !crate::cancel::pause(crate::pointer::FRAME_INTERVAL) {
+            (up_x, up_y) = points[i - 1];
+            break;
+        }
+        unsafe { SendInput(&[mouse_input_at(x, y, MOUSE_EVENT_FLAGS(0))], size) };
+    }
+    unsafe { SendInput(&[mouse_input_at(up_x, up_y, MOUSEEVENTF_LEFTUP)], size) };
+}
+
+#[cfg(not(windows))]
+fn handle_gesture(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, &format!("{} requires Windows", cmd.action))
+}
+
+/// Default dwell after moving the cursor, long enough for standard tooltips.
+#[cfg(windows)]
+const DEFAULT_HOVER_MS: u64 = 800;
+
+#[cfg(windows)]
+fn handle_hover(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        FindWindowExW, GetForegroundWindow, IsWindowVisible,
+    };
+
+    // Same target resolution as double_click/right_click: UIA element, else x/y
+    let (name, automation_id) = element_target(cmd);
+    let duration_ms = cmd.parameters.get("duration_ms").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_HOVER_MS);
+    let capture_uia = cmd.parameters.get("capture_uia").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
+        match resolve_uia_coords(cmd, config, name, automation_id) {
+            Ok(Some(coords)) => coords,
+            Err(failed) => return *failed,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
+        }
+    } else {
+        match point_param(cmd, config) {
+            Ok(Some(point)) => point,
+            Ok(None) => return CommandResult::failure(&cmd.command_id, "hover requires 'name', 'automation_id', 'label', or 'x'/'y' parameters"),
+            Err(e) => return CommandResult::failure(&cmd.command_id, &e),
+        }
+    };
+    if let Some(denied) = deny_self_target_at(cmd, config, x, y) {
+        return denied;
+    }
+
+    let move_input = mouse_input_at(x, y, MOUSE_EVENT_FLAGS(0));
+    before_input();
+    unsafe { SendInput(&[move_input], std::mem::size_of::<INPUT>() as i32); }
+
+    std::thread::sleep(std::time::Duration::from_millis(duration_ms));
+
+    // Tooltips are their own top-level windows; report any that are showing
+    let class_w: Vec<u16> = "tooltips_class32".encode_utf16().chain(Some(0)).collect();
+    let mut tooltips = Vec::new();
+    let mut hwnd = HWND(0);
+    loop {
+        hwnd = unsafe { FindWindowExW(HWND(0), hwnd, PCWSTR(class_w.as_ptr()), PCWSTR::null()) };
+        if hwnd.0 == 0 {
+            break;
+        }
+        if unsafe { IsWindowVisible(hwnd) }.as_bool() {
+            let text = crate::windows::window_title(hwnd);
+            if !text.is_empty() {
+                tooltips.push(serde_json::Value::String(text));
+            }
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("x".to_string(), serde_json::json!(x));
+    result.insert("y".to_string(), serde_json::json!(y));
+    result.insert("duration_ms".to_string(), serde_json::json!(duration_ms));
+    result.insert("tooltips".to_string(), serde_json::Value::Array(tooltips));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    if capture_uia && config.uia_enabled {
+        let fg = unsafe { GetForegroundWindow() };
+        cmd_result.uia = crate::uia::uia_snapshot(fg, config).and_then(|snapshot| serde_json::to_value(&snapshot).ok());
+    }
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+#[cfg(not(windows))]
+fn handle_hover(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "hover requires Windows")
+}
+
+/// Which part of the taskbar a UIA element belongs to, from its class name or
+/// automation id. Covers both the Win32 (Windows 10) and XAML (Windows 11)
+/// shells. `None` means "same region as the parent container".
+#[cfg_attr(not(windows), allow(dead_code))]
+fn taskbar_region(class_name: &str, automation_id: &str) -> Option<&'static str> {
+    match class_name {
+        "Start" | "TrayClockWClass" | "TrayShowDesktopButtonWClass" | "TrayButton" => return Some("system"),
+        "MSTaskListWClass" | "MSTaskSwWClass" | "Taskbar.TaskListButtonAutomationPeer" => return Some("apps"),
+        "TrayNotifyWnd" | "SysPager" | "NotifyIconOverflowWindow" | "SystemTray.NormalButton" => return Some("tray"),
+        _ => {}
+    }
+    match automation_id {
+        "StartButton" | "SearchButton" | "TaskViewButton" | "WidgetsButton" | "SystemTrayIcon" => Some("system"),
+        id if id.starts_with("Appid:") => Some("apps"),
+        id if id.starts_with("NotifyItemIcon") => Some("tray"),
+        _ => None,
+    }
+}
+
+/// Enumerate taskbar buttons and notification-area icons (including the
+/// overflow flyout if open) with their screen positions.
+#[cfg(windows)]
+fn handle_get_taskbar_state(cmd: &Command, _config: &Config) -> CommandResult {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::FindWindowExW;
+
+    let Some(uia) = crate::uia::get_uia() else {
+        return CommandResult::failure(&cmd.command_id, "UIA init failed");
+    };
+
+    let mut buttons = Vec::new();
+    for class in ["Shell_TrayWnd", "Shell_SecondaryTrayWnd", "NotifyIconOverflowWindow", "TopLevelWindowForOverflowXamlIsland"] {
+        let class_w: Vec<u16> = class.encode_utf16().chain(Some(0)).collect();
+        let mut hwnd = HWND(0);
+        loop {
+            hwnd = unsafe { FindWindowExW(HWND(0), hwnd, PCWSTR(class_w.as_ptr()), PCWSTR::null()) };
+            if hwnd.0 == 0 {
+                break;
+            }
+            if let Ok(root) = unsafe { uia.ElementFromHandle(hwnd) } {
+                let region = if class.starts_with("Shell_") { "system" } else { "tray" };
+                collect_taskbar_buttons(&uia, &root, region, 0, &mut buttons);
+            }
+        }
+    }
+
+    if buttons.is_empty() {
+        return CommandResult::failure(&cmd.command_id, "taskbar not found or exposes no buttons");
+    }
+
+    let mut by_region: HashMap<&str, Vec<serde_json::Value>> = HashMap::new();
+    for (region, button) in buttons {
+        by_region.entry(region).or_default().push(button);
+    }
+    let mut result = HashMap::new();
+    result.insert("taskbar_buttons".to_string(), serde_json::json!(by_region.remove("apps").unwrap_or_default()));
+    result.insert("tray_icons".to_string(), serde_json::json!(by_region.remove("tray").unwrap_or_default()));
+    result.insert("system_buttons".to_string(), serde_json::json!(by_region.remove("system").unwrap_or_default()));
+    CommandResult::success(&cmd.command_id, result)
+}
+
+#[cfg(windows)]
+fn collect_taskbar_buttons(
+    uia: &windows::Win32::UI::Accessibility::IUIAutomation,
+    element: &windows::Win32::UI::Accessibility::IUIAutomationElement,
+    inherited: &'static str,
+    depth: usize,
+    out: &mut Vec<(&'static str, serde_json::Value)>,
+) {
+    use windows::Win32::UI::Accessibility::*;
+
+    const MAX_DEPTH: usize = 8;
+
+    let text = |r: windows::core::Result<windows::core::BSTR>| r.map(|b| b.to_string()).unwrap_or_default();
+    let class_name = text(unsafe { element.CurrentClassName() });
+    let automation_id = text(unsafe { element.CurrentAutomationId() });
+    let region = taskbar_region(&class_name, &automation_id).unwrap_or(inherited);
+
+    let control_type = unsafe { element.CurrentControlType() }.unwrap_or_default();
+    let is_button = control_type == UIA_ButtonControlTypeId
+        || control_type == UIA_MenuItemControlTypeId
+        || control_type == UIA_SplitButtonControlTypeId;
+    let name = text(unsafe { element.CurrentName() });
+    if is_button && !name.is_empty() {
+        if let Ok(r) = unsafe { element.CurrentBoundingRectangle() } {
+            let (w, h) = (r.right - r.left, r.bottom - r.top);
+            if w > 0 && h > 0 {
+                out.push((
+                    region,
+                    serde_json::json!({
+                        "name": name,
+                        "automation_id": automation_id,
+                        "class_name": class_name,
+                        "bounding_rect": [r.left, r.top, w, h],
+                        "center_x": r.left + w / 2,
+                        "center_y": r.top + h / 2,
+                    }),
+                ));
+            }
+        }
+    }
+
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let Ok(condition) = (unsafe { uia.CreateTrueCondition() }) else {
+        return;
+    };
+    if let Ok(children) = unsafe { element.FindAll(TreeScope_Children, &condition) } {
+        let count = unsafe { children.Length() }.unwrap_or(0);
+        for i in 0..count {
+            if let Ok(child) = unsafe { children.GetElement(i) } {
+                collect_taskbar_buttons(uia, &child, region, depth + 1, out);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn handle_get_taskbar_state(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "get_taskbar_state requires Windows")
+}
+
+/// Open Start, type `query`, wait for search results via UIA and optionally
+/// launch the top result with Enter — one atomic launcher command instead of
+/// separate send_keys/type_text/wait/click steps.
+#[cfg(windows)]
+fn handle_start_menu_search(cmd: &Command, config: &Config) -> CommandResult {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{VK_ESCAPE, VK_LWIN, VK_RETURN};
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    let query = cmd.parameters.get("query").and_then(|v| v.as_str()).unwrap_or("");
+    if query.trim().is_empty() {
+        return CommandResult::failure(&cmd.command_id, "start_menu_search requires 'query' parameter");
+    }
+    let activate = cmd.parameters.get("activate").and_then(|v| v.as_bool()).unwrap_or(false);
+    let wait_ms = cmd.parameters.get("wait_ms").and_then(|v| v.as_u64()).unwrap_or(3000);
+
+    let Some(uia) = crate::uia::get_uia() else {
+        return CommandResult::failure(&cmd.command_id, "UIA init failed");
+    };
+
+    tap_key(VK_LWIN);
+    // Give the Start/Search host time to take focus before typing
+    std::thread::sleep(std::time::Duration::from_millis(400));
+    send_text_via_input(query, None);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(wait_ms);
+    let mut results = Vec::new();
+    while std::time::Instant::now() < deadline {
+        let host = unsafe { GetForegroundWindow() };
+        if let Ok(root) = unsafe { uia.ElementFromHandle(host) } {
+            results = search_result_items(&uia, &root);
+            if !results.is_empty() {
+                break;
+            }
+        }
+        if !crate::cancel::pause(std::time::Duration::from_millis(150)) {
+            tap_key(VK_ESCAPE);
+            return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        }
+    }
+
+    if results.is_empty() {
+        // Close Start rather than leave the query typed into it
+        tap_key(VK_ESCAPE);
+        return CommandResult::failure(&cmd.command_id, &format!("no search results for: {query}"));
+    }
+
+    let top_result = results[0].get("name").cloned().unwrap_or_default();
+    if activate {
+        // The top result is pre-selected; Enter launches it the same way a user would
+        tap_key(VK_RETURN);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+
+    let mut result = HashMap::new();
+    result.insert("query".to_string(), serde_json::Value::String(query.to_string()));
+    result.insert("top_result".to_string(), top_result);
+    result.insert("activated".to_string(), serde_json::json!(activate));
+    result.insert("results".to_string(), serde_json::json!(results));
+    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
+    cmd_result.screenshot_b64 = if config.enable_screenshot {
+        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
+    } else {
+        None
+    };
+    cmd_result
+}
+
+/// Named list items under the search host window, in UI order (top result first).
+#[cfg(windows)]
+fn search_result_items(
+    uia: &windows::Win32::UI::Accessibility::IUIAutomation,
+    root: &windows::Win32::UI::Accessibility::IUIAutomationElement,
+) -> Vec<serde_json::Value> {
+    use windows::Win32::UI::Accessibility::*;
+
+    const MAX_RESULTS: usize = 8;
+
+    let mut items = Vec::new();
+    let Ok(condition) = (unsafe { uia.CreateTrueCondition() }) else {
+        return items;
+    };
+    let Ok(found) = (unsafe { root.FindAll(TreeScope_Descendants, &condition) }) else {
+        return items;
+    };
+    let count = unsafe { found.Length() }.unwrap_or(0);
+    for i in 0..count {
+        let Ok(element) = (unsafe { found.GetElement(i) }) else {
+            continue;
+        };
+        if unsafe { element.CurrentControlType() }.unwrap_or_default() != UIA_ListItemControlTypeId {
+            continue;
+        }
+        let name = unsafe { element.CurrentName() }.map(|b| b.to_string()).unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+        let mut item = serde_json::json!({ "name": name });
+        if let Ok(r) = unsafe { element.CurrentBoundingRectangle() } {
+            item["bounding_rect"] = serde_json::json!([r.left, r.top, r.right - r.left, r.bottom - r.top]);
+        }
+        items.push(item);
+        if items.len() >= MAX_RESULTS {
+            break;
+        }
+    }
+    items
+}
+
+/// Press and release a single key.
+#[cfg(windows)]
+fn tap_key(vk: windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY) {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let inputs = [KEYBD_EVENT_FLAGS(0), KEYEVENTF_KEYUP].map(|flags| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                wScan: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    });
+    before_input();
+    unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32); }
+}
+
+#[cfg(not(windows))]
+fn handle_start_menu_search(cmd: &Command, _config: &Config) -> CommandResult {
+    CommandResult::failure(&cmd.command_id, "start_menu_search requires Windows")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_parse_from_json() {
+        let json = r#"{"command_id": "abc-123", "action": "observe", "parameters": {}, "timeout_ms": 3000}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.command_id, "abc-123");
+        assert_eq!(cmd.action, "observe");
+        assert_eq!(cmd.timeout_ms, 3000);
+        assert!(cmd.parameters.is_empty());
+    }
+
+    #[test]
+    fn test_command_parse_with_parameters() {
+        let json = r#"{"command_id": "def-456", "action": "click", "parameters": {"name": "Send", "automation_id": "btn_send"}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "click");
+        assert_eq!(cmd.parameters["name"], "Send");
+        assert_eq!(cmd.parameters["automation_id"], "btn_send");
+        assert_eq!(cmd.timeout_ms, 5000); // default
+    }
+
+    #[test]
+    fn test_command_result_success_serialize() {
+        let mut result = HashMap::new();
+        result.insert("clicked".to_string(), serde_json::Value::String("Send".to_string()));
+        let cr = CommandResult::success("abc-123", result);
+
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["type"], "command_result");
+        assert_eq!(json["command_id"], "abc-123");
+        assert_eq!(json["ok"], true);
+        assert_eq!(json["result"]["clicked"], "Send");
+        assert!(json.get("error").is_none());
+        assert!(json.get("screenshot_b64").is_none());
+    }
+
+    #[test]
+    fn test_command_result_failure_serialize() {
+        let cr = CommandResult::failure("abc-123", "element not found");
+
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["type"], "command_result");
+        assert_eq!(json["command_id"], "abc-123");
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["error"], "element not found");
+    }
+
+    #[test]
+    fn test_command_result_with_screenshot() {
+        let mut cr = CommandResult::success("test-id", HashMap::new());
+        cr.screenshot_b64 = Some("base64data".to_string());
+        cr.uia = Some(serde_json::json!({"focused_name": "Button"}));
+
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["screenshot_b64"], "base64data");
+        assert_eq!(json["uia"]["focused_name"], "Button");
+    }
+
+    #[test]
+    fn test_unknown_action_returns_error() {
+        let cmd = Command {
+            command_id: "test-id".to_string(),
+            action: "nonexistent".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        let config = Config::from_env();
+        let result = execute_command(&cmd, &config);
+        assert!(!result.ok);
+        assert!(result.error.as_ref().unwrap().contains("unknown action"));
+    }
+
+    #[test]
+    fn test_command_parse_minimal() {
+        let json = r#"{"command_id": "x", "action": "observe"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.command_id, "x");
+        assert_eq!(cmd.action, "observe");
+        assert!(cmd.parameters.is_empty());
+        assert_eq!(cmd.timeout_ms, 5000);
+    }
+
+    #[test]
+    fn test_scroll_command_parse() {
+        let json = r#"{"command_id": "s1", "action": "scroll", "parameters": {"direction": "up", "amount": 5}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "scroll");
+        assert_eq!(cmd.parameters["direction"], "up");
+        assert_eq!(cmd.parameters["amount"], 5);
+    }
+
+    #[test]
+    fn test_click_count_and_gap() {
+        let mut cmd: Command =
+            serde_json::from_str(r#"{"command_id": "tc1", "action": "triple_click", "parameters": {"x": 10, "y": 20}}"#).unwrap();
+        assert_eq!(click_count(&cmd, 3), Ok(3));
+        cmd.parameters.insert("click_count".to_string(), serde_json::json!(4));
+        assert_eq!(click_count(&cmd, 3), Ok(4));
+        for bad in [serde_json::json!(0), serde_json::json!(6), serde_json::json!("2"), serde_json::json!(1.5)] {
+            cmd.parameters.insert("click_count".to_string(), bad);
+            assert!(click_count(&cmd, 3).unwrap_err().contains("1 to 5"));
+        }
+
+        // Default 500ms double-click time: the cap; fast settings squeeze the gaps
+        assert_eq!(click_gap_ms(500, 3), MAX_CLICK_GAP_MS);
+        assert_eq!(click_gap_ms(200, 5), 25);
+        assert_eq!(click_gap_ms(200, 1), MAX_CLICK_GAP_MS);
+        assert_eq!(click_gap_ms(0, 3), 1);
+    }
+
+    #[test]
+    fn test_mouse_button_param() {
+        assert_eq!(MouseButton::from_param(None, MouseButton::Middle), Ok(MouseButton::Middle));
+        for (name, button) in [("left", MouseButton::Left), ("Right", MouseButton::Right), ("MIDDLE", MouseButton::Middle), ("x1", MouseButton::X1), ("x2", MouseButton::X2)] {
+            assert_eq!(MouseButton::from_param(Some(&serde_json::json!(name)), MouseButton::Left), Ok(button));
+            assert_eq!(button.as_str(), name.to_lowercase());
+        }
+        for bad in [serde_json::json!("wheel"), serde_json::json!(3), serde_json::Value::Null] {
+            assert!(MouseButton::from_param(Some(&bad), MouseButton::Left).unwrap_err().contains("\"x2\""));
+        }
+    }
+
+    #[test]
+    fn test_double_click_command_parse() {
+        let json = r#"{"command_id": "dc1", "action": "double_click", "parameters": {"x": 100, "y": 200}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "double_click");
+        assert_eq!(cmd.parameters["x"], 100);
+        assert_eq!(cmd.parameters["y"], 200);
+    }
+
+    #[test]
+    fn test_right_click_command_parse() {
+        let json = r#"{"command_id": "rc1", "action": "right_click", "parameters": {"x": 50, "y": 75}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "right_click");
+        assert_eq!(cmd.parameters["x"], 50);
+        assert_eq!(cmd.parameters["y"], 75);
+    }
+
+    #[test]
+    fn test_double_click_name_based_parse() {
+        let json = r#"{"command_id": "dc2", "action": "double_click", "parameters": {"name": "Submit"}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "double_click");
+        assert_eq!(cmd.parameters["name"], "Submit");
+    }
+
+    #[test]
+    fn test_right_click_name_based_parse() {
+        let json = r#"{"command_id": "rc2", "action": "right_click", "parameters": {"name": "FileItem", "automation_id": "file_1"}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(cmd.action, "right_click");
+        assert_eq!(cmd.parameters["name"], "FileItem");
+        assert_eq!(cmd.parameters["automation_id"], "file_1");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_recycle_targets_validation() {
+        let mut params = HashMap::new();
+        let mut cmd = Command {
+            command_id: "test".to_string(),
+            action: "move_to_recycle_bin".to_string(),
+            parameters: params.clone(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert!(recycle_targets(&cmd).unwrap_err().contains("requires"));
+
+        params.insert("path".to_string(), serde_json::json!("relative/file.txt"));
+        cmd.parameters = params.clone();
+        assert!(recycle_targets(&cmd).unwrap_err().contains("absolute"));
+
+        let root = std::env::temp_dir().ancestors().last().unwrap().to_string_lossy().into_owned();
+        params.insert("path".to_string(), serde_json::json!(root));
+        cmd.parameters = params.clone();
+        assert!(recycle_targets(&cmd).unwrap_err().contains("root"));
+
+        let file = std::env::temp_dir().join(format!("desktopai_recycle_{}.txt", std::process::id()));
+        std::fs::write(&file, b"x").unwrap();
+        params.remove("path");
+        params.insert("paths".to_string(), serde_json::json!([file.to_string_lossy()]));
+        cmd.parameters = params;
+        assert_eq!(recycle_targets(&cmd).unwrap(), vec![file.clone()]);
+        std::fs::remove_file(&file).unwrap();
+        assert!(recycle_targets(&cmd).unwrap_err().contains("not found"));
+    }
+
+    #[test]
+    fn test_browser_navigate_parameters() {
+        let mut cmd = Command {
+            command_id: "nav".to_string(),
+            action: "browser_navigate".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        let mut url = |value: &str| {
+            cmd.parameters.insert("url".to_string(), serde_json::json!(value));
+            navigation_url(&cmd)
+        };
+        assert_eq!(url("  https://example.com/a?b=c "), Ok("https://example.com/a?b=c".to_string()));
+        assert_eq!(url("weather in paris"), Ok("weather in paris".to_string()));
+        assert!(url("").unwrap_err().contains("requires 'url'"));
+        assert!(url("https://example.com\nhttps://other.test").unwrap_err().contains("control characters"));
+        assert!(url("a\u{7}b").unwrap_err().contains("control characters"));
+        assert!(url("JavaScript:alert(1)").unwrap_err().contains("not allowed"));
+        assert!(url(&"a".repeat(MAX_URL_CHARS + 1)).unwrap_err().contains("longer"));
+
+        assert!(is_browser_process("C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe"));
+        assert!(is_browser_process("MSEDGE.EXE"));
+        assert!(!is_browser_process("notepad.exe"));
+        assert!(!is_browser_process("chrome.exe.bak"));
+    }
+
+    #[test]
+    fn test_drop_files_parameters() {
+        let mut cmd = Command {
+            command_id: "drop".to_string(),
+            action: "drop_files".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(existing_paths(&cmd).unwrap_err(), "drop_files requires 'path' or 'paths' parameter");
+        assert_eq!(drop_method(&cmd), Ok(DropMethod::Auto));
+        cmd.parameters.insert("method".to_string(), serde_json::json!("ole"));
+        assert_eq!(drop_method(&cmd), Ok(DropMethod::Ole));
+        cmd.parameters.insert("method".to_string(), serde_json::json!("wm_dropfiles"));
+        assert_eq!(drop_method(&cmd), Ok(DropMethod::DropFiles));
+        cmd.parameters.insert("method".to_string(), serde_json::json!("paste"));
+        assert!(drop_method(&cmd).unwrap_err().contains("unknown drop method"));
+
+        let paths = [std::path::PathBuf::from("C:\\a.txt"), std::path::PathBuf::from("C:\\é")];
+        let block = dropfiles_block(&paths, 10, -2);
+        let word = |at: usize| i32::from_le_bytes(block[at..at + 4].try_into().unwrap());
+        assert_eq!((word(0), word(4), word(8), word(12), word(16)), (20, 10, -2, 0, 1));
+        let units: Vec<u16> = block[20..].chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        let expected: Vec<u16> = "C:\\a.txt\0C:\\é\0\0".encode_utf16().collect();
+        assert_eq!(units, expected);
+    }
+
+    fn run_shell_command(params: serde_json::Value, timeout_ms: u64) -> CommandResult {
+        let cmd = Command {
+            command_id: "shell".to_string(),
+            action: "run_shell".to_string(),
+            parameters: serde_json::from_value(params).unwrap(),
+            timeout_ms,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        handle_run_shell(&cmd, &Config::from_env())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_shell_captures_output_and_exit_code() {
+        let result = run_shell_command(
+            serde_json::json!({"program": "sh", "args": ["-c", "echo out; echo err >&2; exit 3"]}),
+            5000,
+        );
+        assert!(result.ok);
+        assert_eq!(result.result["stdout"], "out\n");
+        assert_eq!(result.result["stderr"], "err\n");
+        assert_eq!(result.result["exit_code"], 3);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_shell_timeout_and_truncation() {
+        let result = run_shell_command(serde_json::json!({"program": "sleep", "args": ["5"]}), 100);
+        assert!(!result.ok);
+        assert_eq!(result.result["timed_out"], true);
+
+        let result = run_shell_command(serde_json::json!({"program": "head", "args": ["-c", "40000", "/dev/zero"]}), 5000);
+        assert!(result.ok);
+        assert_eq!(result.result["stdout"].as_str().unwrap().len(), MAX_SHELL_OUTPUT);
+        assert_eq!(result.result["stdout_truncated"], true);
+    }
+
+    #[test]
+    fn test_run_shell_rejects_bad_parameters() {
+        assert!(!run_shell_command(serde_json::json!({}), 1000).ok);
+        assert!(!run_shell_command(serde_json::json!({"program": "x", "args": "not-a-list"}), 1000).ok);
+        let missing = run_shell_command(serde_json::json!({"program": "desktopai-no-such-program"}), 1000);
+        assert!(missing.error.unwrap().contains("failed to start"));
+    }
+
+    #[test]
+    fn test_parse_hwnd_param() {
+        assert_eq!(parse_hwnd_param(&serde_json::json!("0x1a2b")), Some(0x1a2b));
+        assert_eq!(parse_hwnd_param(&serde_json::json!("6699")), Some(6699));
+        assert_eq!(parse_hwnd_param(&serde_json::json!(6699)), Some(6699));
+        assert_eq!(parse_hwnd_param(&serde_json::json!("0x0")), None);
+        assert_eq!(parse_hwnd_param(&serde_json::json!("notepad")), None);
+        assert_eq!(parse_hwnd_param(&serde_json::json!(true)), None);
+    }
+
+    #[test]
+    fn test_element_tree_limits() {
+        let mut config = Config::from_env();
+        config.uia_max_depth = 3;
+        let mut cmd = Command {
+            command_id: "tree".to_string(),
+            action: "get_element_tree".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(element_tree_limits(&cmd, &config), (3, DEFAULT_ELEMENT_TREE_CHILDREN));
+
+        cmd.parameters.insert("depth".to_string(), serde_json::json!(8));
+        cmd.parameters.insert("max_children".to_string(), serde_json::json!(0));
+        assert_eq!(element_tree_limits(&cmd, &config), (8, 1));
+
+        cmd.parameters.insert("depth".to_string(), serde_json::json!(100));
+        cmd.parameters.insert("max_children".to_string(), serde_json::json!(10_000));
+        assert_eq!(element_tree_limits(&cmd, &config), (MAX_ELEMENT_TREE_DEPTH, MAX_ELEMENT_TREE_CHILDREN));
+    }
+
+    #[test]
+    fn test_window_scope_parameters() {
+        let mut cmd = Command {
+            command_id: "scoped".to_string(),
+            action: "click".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        cmd.parameters.insert("name".to_string(), serde_json::json!("Save"));
+        assert!(!has_window_scope(&cmd));
+        for key in ["hwnd", "title", "window_title", "process"] {
+            let mut scoped = cmd.clone();
+            scoped.parameters.insert(key.to_string(), serde_json::json!("notepad"));
+            assert!(has_window_scope(&scoped), "{key} scopes the search");
+        }
+    }
+
+    #[test]
+    fn test_if_exists_branch_command() {
+        let mut cmd = Command {
+            command_id: "cond".to_string(),
+            action: "if_exists".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        cmd.parameters.insert(
+            "then".to_string(),
+            serde_json::json!({"action": "click", "parameters": {"name": "Don't save"}}),
+        );
+        let then = branch_command(&cmd, true).unwrap().unwrap();
+        assert_eq!(then.command_id, "cond");
+        assert_eq!(then.action, "click");
+        assert_eq!(then.parameters["name"], "Don't save");
+        assert_eq!(then.timeout_ms, 5000);
+        assert!(branch_command(&cmd, false).unwrap().is_none(), "no else branch");
+
+        cmd.parameters.insert("else".to_string(), serde_json::json!("click"));
+        assert!(branch_command(&cmd, false).unwrap_err().contains("must be an object"));
+        cmd.parameters.insert("else".to_string(), serde_json::json!({"parameters": {}}));
+        assert!(branch_command(&cmd, false).unwrap_err().contains("invalid if_exists 'else' command"));
+    }
+
+    #[test]
+    fn test_run_workflow() {
+        let dir = std::env::temp_dir().join(format!("desktopai_run_workflow_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("tidy.yaml"),
+            "inputs: [{name: listing, default: true}, {name: label}]\nsteps:\n  - if: {input: listing}\n    then: [{action: list_workflows}]\n  - {action: no_such_action, continue_on_error: true}\n  - {action: list_workflows, name: recount, parameters: {tag: '{{label}}'}}",
+        )
+        .unwrap();
+        let mut config = Config::from_env();
+        config.workflows_dir = dir.to_string_lossy().into_owned();
+        let run = |params: serde_json::Value| {
+            let cmd = Command {
+                command_id: "flow".to_string(),
+                action: "run_workflow".to_string(),
+                parameters: serde_json::from_value(params).unwrap(),
+                timeout_ms: 5000,
+                verify_diff: false,
+                capture_before: false,
+                include_screenshot: None,
+                include_uia: None,
+                uia_depth: None,
+                traceparent: None,
+            };
+            execute_command(&cmd, &config)
+        };
 
-    let (x, y) = if !name.is_empty() || !automation_id.is_empty() {
-        match resolve_uia_coords(name, automation_id) {
-            Some(coords) => coords,
-            None => return CommandResult::failure(&cmd.command_id, &format!("element not found: {}", if !name.is_empty() { name } else { automation_id })),
-        }
-    } else {
-        let x = cmd.parameters.get("x").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        let y = cmd.parameters.get("y").and_then(|v| v.as_i64()).unwrap_or(-1) as i32;
-        if x < 0 || y < 0 {
-            return CommandResult::failure(&cmd.command_id, "right_click requires 'name', 'automation_id', or 'x'/'y' parameters");
-        }
-        (x, y)
-    };
+        let missing = run(serde_json::json!({"name": "tidy"}));
+        assert_eq!(missing.error.as_deref(), Some("workflow 'tidy': missing input 'label'"));
+        assert_eq!(run(serde_json::json!({"name": "other"})).error.as_deref(), Some("workflow not found: other"));
+        assert!(!run(serde_json::json!({"name": "tidy", "inputs": [1]})).ok);
 
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        let result = run(serde_json::json!({"name": "tidy", "inputs": {"label": "again"}}));
+        assert!(result.ok, "{:?}", result.error);
+        let steps = result.result["steps"].as_array().unwrap();
+        let taken: Vec<(&str, bool)> = steps.iter().map(|s| (s["step"].as_str().unwrap(), s["ok"].as_bool().unwrap())).collect();
+        assert_eq!(taken, vec![("1", true), ("1.then.1", true), ("2", false), ("3", true)]);
+        assert_eq!(steps[1]["result"]["count"], 1);
+        assert_eq!(steps[3]["name"], "recount");
 
-    let screen_w = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CXSCREEN) };
-    let screen_h = unsafe { windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CYSCREEN) };
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
-    let norm_x = (x as i64 * 65535 / screen_w as i64) as i32;
-    let norm_y = (y as i64 * 65535 / screen_h as i64) as i32;
+    #[test]
+    fn test_outline_params() {
+        let mut config = Config::from_env();
+        config.uia_outline_tokens = 800;
+        config.uia_outline_only = true;
+        let mut cmd = Command {
+            command_id: "obs".to_string(),
+            action: "observe".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(outline_params(&cmd, &config), (800, false));
 
-    let inputs = [
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_RIGHTDOWN,
-                    time: 0, dwExtraInfo: 0,
-                },
-            },
-        },
-        INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx: norm_x, dy: norm_y, mouseData: 0,
-                    dwFlags: MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_RIGHTUP,
-                    time: 0, dwExtraInfo: 0,
-                },
-            },
-        },
-    ];
+        cmd.parameters.insert("outline_tokens".to_string(), serde_json::json!(0));
+        cmd.parameters.insert("include_tree".to_string(), serde_json::json!(true));
+        assert_eq!(outline_params(&cmd, &config), (0, true));
+    }
 
-    unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32); }
+    #[test]
+    fn test_scroll_step_towards_element() {
+        let viewport = [0, 100, 400, 500];
+        assert_eq!(scroll_step([10, 120, 200, 140], viewport), (0, 0));
+        assert_eq!(scroll_step([10, 600, 200, 620], viewport), (0, 1));
+        assert_eq!(scroll_step([10, 40, 200, 60], viewport), (0, -1));
+        assert_eq!(scroll_step([450, 120, 500, 140], viewport), (1, 0));
+        assert_eq!(scroll_step([-80, 600, -20, 620], viewport), (-1, 1));
+    }
 
-    let mut result = HashMap::new();
-    result.insert("x".to_string(), serde_json::json!(x));
-    result.insert("y".to_string(), serde_json::json!(y));
-    let mut cmd_result = CommandResult::success(&cmd.command_id, result);
-    cmd_result.screenshot_b64 = if config.enable_screenshot {
-        crate::screenshot::capture_screenshot(config, windows::Win32::Foundation::HWND(0))
-    } else {
-        None
-    };
-    cmd_result
-}
+    #[test]
+    fn test_menu_path_and_name_matching() {
+        let path = serde_json::json!("File > Save As");
+        assert_eq!(parse_menu_path(Some(&path)).unwrap(), vec!["File", "Save As"]);
+        let path = serde_json::json!(["View", " Zoom ", "Zoom In"]);
+        assert_eq!(parse_menu_path(Some(&path)).unwrap(), vec!["View", "Zoom", "Zoom In"]);
+        assert!(parse_menu_path(None).unwrap_err().contains("requires"));
+        assert!(parse_menu_path(Some(&serde_json::json!("File >> Save"))).unwrap_err().contains("empty level"));
+        assert!(parse_menu_path(Some(&serde_json::json!(["File", 3]))).is_err());
+
+        assert!(menu_name_matches("Save &As...\tCtrl+Shift+S", "save as"));
+        assert!(menu_name_matches("Open\u{2026}", "Open"));
+        assert!(menu_name_matches("&File", "File"));
+        assert!(!menu_name_matches("Save", "Save As"));
+    }
 
-#[cfg(not(windows))]
-fn handle_right_click(cmd: &Command, _config: &Config) -> CommandResult {
-    CommandResult::failure(&cmd.command_id, "right_click requires Windows")
-}
+    #[test]
+    fn test_range_value_must_be_in_range() {
+        assert_eq!(range_value_error(50.0, 0.0, 100.0), None);
+        assert_eq!(range_value_error(0.0, 0.0, 100.0), None);
+        assert!(range_value_error(101.0, 0.0, 100.0).unwrap().contains("outside the range"));
+        assert!(range_value_error(f64::NAN, 0.0, 100.0).unwrap().contains("finite"));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_region_rect() {
+        // Absolute screen pixels pass through, including negative (left monitor) coordinates
+        assert_eq!(region_rect([-1920.0, 10.0, 300.0, 200.0], None, false), Ok([-1920, 10, 300, 200]));
+        assert!(region_rect([0.0, 0.0, 0.5, 0.5], None, true).unwrap_err().contains("normalized"));
+
+        let monitor = Some([1920, 0, 2560, 1440]);
+        assert_eq!(region_rect([0.0, 0.9, 1.0, 0.1], monitor, true), Ok([1920, 1296, 2560, 144]));
+        assert_eq!(region_rect([100.0, 50.0, 200.0, 100.0], monitor, false), Ok([2020, 50, 200, 100]));
+        // Clipped to the frame
+        assert_eq!(region_rect([2500.0, 1400.0, 200.0, 200.0], monitor, false), Ok([4420, 1400, 60, 40]));
+
+        assert!(region_rect([3000.0, 0.0, 10.0, 10.0], monitor, false).unwrap_err().contains("outside"));
+        assert!(region_rect([0.5, 0.5, 1.5, 0.1], monitor, true).unwrap_err().contains("between 0 and 1"));
+        assert!(region_rect([0.0, 0.0, 0.0, 10.0], None, false).unwrap_err().contains("positive"));
+    }
 
     #[test]
-    fn test_command_parse_from_json() {
-        let json = r#"{"command_id": "abc-123", "action": "observe", "parameters": {}, "timeout_ms": 3000}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.command_id, "abc-123");
-        assert_eq!(cmd.action, "observe");
-        assert_eq!(cmd.timeout_ms, 3000);
-        assert!(cmd.parameters.is_empty());
+    fn test_absolute_coords_span_the_virtual_screen() {
+        // 1920x1080 primary with a 1280x1024 monitor to its left
+        let screen = [-1280, 0, 3200, 1080];
+        assert_eq!(absolute_coords(-1280, 0, screen), (0, 0));
+        assert_eq!(absolute_coords(0, 540, screen), (26215, 32768));
+        assert_eq!(absolute_coords(1919, 1079, screen), (65516, 65476));
+        // Off-screen points clamp to the nearest edge
+        assert_eq!(absolute_coords(5000, -10, screen), absolute_coords(1919, 0, screen));
+        // Every pixel maps back onto itself (Windows: pixel = value * extent / 65536)
+        for x in [-1280, -1, 0, 1, 1919] {
+            let (dx, _) = absolute_coords(x, 0, screen);
+            assert_eq!(-1280 + (i64::from(dx) * 3200 / 65536) as i32, x);
+        }
     }
 
     #[test]
-    fn test_command_parse_with_parameters() {
-        let json = r#"{"command_id": "def-456", "action": "click", "parameters": {"name": "Send", "automation_id": "btn_send"}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "click");
-        assert_eq!(cmd.parameters["name"], "Send");
-        assert_eq!(cmd.parameters["automation_id"], "btn_send");
-        assert_eq!(cmd.timeout_ms, 5000); // default
+    fn test_key_steps() {
+        use serde_json::json;
+        let chord = |keys: &str| KeyStep::Chord(keys.to_string());
+        assert_eq!(key_steps(Some(&json!("ctrl+c"))), Ok(vec![chord("ctrl+c")]));
+        assert_eq!(
+            key_steps(Some(&json!("ctrl+a, delete ,ctrl+v"))),
+            Ok(vec![chord("ctrl+a"), chord("delete"), chord("ctrl+v")])
+        );
+        assert_eq!(
+            key_steps(Some(&json!(["ctrl+a", 200, "delete", 999999]))),
+            Ok(vec![chord("ctrl+a"), KeyStep::Pause(200), chord("delete"), KeyStep::Pause(MAX_KEY_PAUSE_MS)])
+        );
+        assert!(key_steps(Some(&json!("ctrl+a,,delete"))).unwrap_err().contains("empty chord"));
+        assert!(key_steps(Some(&json!(["ctrl+a", -5]))).unwrap_err().contains("invalid pause"));
+        assert!(key_steps(Some(&json!([true]))).is_err());
+        assert!(key_steps(Some(&json!([]))).unwrap_err().contains("requires 'keys'"));
+        assert!(key_steps(None).unwrap_err().contains("requires 'keys'"));
+        assert!(key_steps(Some(&json!(vec!["a"; MAX_KEY_STEPS + 1]))).is_err());
     }
 
     #[test]
-    fn test_command_result_success_serialize() {
-        let mut result = HashMap::new();
-        result.insert("clicked".to_string(), serde_json::Value::String("Send".to_string()));
-        let cr = CommandResult::success("abc-123", result);
+    fn test_accelerator_chords() {
+        let chords = |shortcut: &str| accelerator_chords(shortcut).map(|c| c.join(", "));
+        assert_eq!(chords("Ctrl+S"), Ok("ctrl+s".to_string()));
+        assert_eq!(chords("Strg+Umschalt+S"), Ok("ctrl+shift+s".to_string()));
+        assert_eq!(chords("Maj+Suppr"), Ok("shift+delete".to_string()));
+        assert_eq!(chords("Ctrl + Bild ab"), Ok("ctrl+pagedown".to_string()));
+        assert_eq!(chords("Ctrl++"), Ok("ctrl+plus".to_string()));
+        assert_eq!(chords("Ctrl+-"), Ok("ctrl+minus".to_string()));
+        assert_eq!(chords("Alt, F, S"), Ok("alt, f, s".to_string()));
+        assert_eq!(chords("Alt+Ä"), Ok("alt+ä".to_string()));
+        assert!(chords("Ctrl+").unwrap_err().contains("malformed"));
+        assert!(chords("Alt, , S").is_err());
+    }
 
-        let json = serde_json::to_value(&cr).unwrap();
-        assert_eq!(json["type"], "command_result");
-        assert_eq!(json["command_id"], "abc-123");
-        assert_eq!(json["ok"], true);
-        assert_eq!(json["result"]["clicked"], "Send");
-        assert!(json.get("error").is_none());
-        assert!(json.get("screenshot_b64").is_none());
+    #[test]
+    fn test_desktop_ids_and_shortcuts() {
+        let id = parse_desktop_id("{1d4e2b6c-0a1b-4c2d-9e8f-0123456789AB}").unwrap();
+        assert_eq!(format_desktop_id(id), "{1D4E2B6C-0A1B-4C2D-9E8F-0123456789AB}");
+        assert_eq!(parse_desktop_id("1D4E2B6C-0A1B-4C2D-9E8F-0123456789AB"), Some(id));
+        assert_eq!(parse_desktop_id("1D4E2B6C0A1B4C2D9E8F0123456789AB"), None);
+        assert_eq!(parse_desktop_id("{1D4E2B6C-0A1B-4C2D-9E8F-0123456789AZ}"), None);
+
+        assert_eq!(desktop_shortcut("Right"), Some("ctrl+win+right"));
+        assert_eq!(desktop_shortcut("new"), Some("ctrl+win+d"));
+        assert_eq!(desktop_shortcut("up"), None);
     }
 
     #[test]
-    fn test_command_result_failure_serialize() {
-        let cr = CommandResult::failure("abc-123", "element not found");
+    fn test_frame_point() {
+        // Client area of a window on a monitor left of the primary
+        let window = Some([-1200, 100, 800, 600]);
+        assert_eq!(frame_point(10.0, 20.0, window, false), Ok((-1190, 120)));
+        assert_eq!(frame_point(0.5, 0.5, window, true), Ok((-800, 400)));
+        assert_eq!(frame_point(1.0, 1.0, window, true), Ok((-401, 699)));
+        assert!(frame_point(800.0, 0.0, window, false).unwrap_err().contains("outside the 800x600 frame"));
+        assert!(frame_point(-1.0, 0.0, window, false).is_err());
+        assert!(frame_point(1.5, 0.0, window, true).unwrap_err().contains("between 0 and 1"));
+        // Screen pixels pass through; fractions of the screen are not supported
+        assert_eq!(frame_point(-50.4, 30.6, None, false), Ok((-50, 31)));
+        assert!(frame_point(0.5, 0.5, None, true).unwrap_err().contains("relative_to"));
+        assert!(frame_point(f64::NAN, 0.0, None, false).is_err());
+    }
 
-        let json = serde_json::to_value(&cr).unwrap();
-        assert_eq!(json["type"], "command_result");
-        assert_eq!(json["command_id"], "abc-123");
-        assert_eq!(json["ok"], false);
-        assert_eq!(json["error"], "element not found");
+    #[test]
+    fn test_dpi_scale_and_rect_union() {
+        assert_eq!(dpi_scale(96), 1.0);
+        assert_eq!(dpi_scale(144), 1.5);
+        assert_eq!(dpi_scale(120), 1.25);
+        assert_eq!(rect_union([]), None);
+        assert_eq!(rect_union([[0, 0, 1920, 1080]]), Some([0, 0, 1920, 1080]));
+        assert_eq!(
+            rect_union([[0, 0, 1920, 1080], [-1280, 200, 1280, 1024]]),
+            Some([-1280, 0, 3200, 1224])
+        );
     }
 
     #[test]
-    fn test_command_result_with_screenshot() {
-        let mut cr = CommandResult::success("test-id", HashMap::new());
-        cr.screenshot_b64 = Some("base64data".to_string());
-        cr.uia = Some(serde_json::json!({"focused_name": "Button"}));
+    fn test_monitor_index() {
+        use serde_json::json;
+        let names = ["\\\\.\\DISPLAY2", "\\\\.\\DISPLAY1"];
+        assert_eq!(monitor_index(&json!(1), &names), Some(1));
+        assert_eq!(monitor_index(&json!(2), &names), None);
+        assert_eq!(monitor_index(&json!("primary"), &names), Some(0));
+        assert_eq!(monitor_index(&json!("display1"), &names), Some(1));
+        assert_eq!(monitor_index(&json!("\\\\.\\DISPLAY2"), &names), Some(0));
+        assert_eq!(monitor_index(&json!("DISPLAY3"), &names), None);
+        assert_eq!(monitor_index(&json!(true), &names), None);
+    }
 
-        let json = serde_json::to_value(&cr).unwrap();
-        assert_eq!(json["screenshot_b64"], "base64data");
-        assert_eq!(json["uia"]["focused_name"], "Button");
+    #[test]
+    fn test_on_screen() {
+        let screen = [-1280, -200, 3200, 1280];
+        assert!(on_screen(-1280, -200, screen));
+        assert!(on_screen(1919, 1079, screen));
+        assert!(!on_screen(1920, 0, screen));
+        assert!(!on_screen(0, -201, screen));
     }
 
     #[test]
-    fn test_unknown_action_returns_error() {
+    fn test_purge_data_requires_confirm() {
+        let config = Config::from_env();
         let cmd = Command {
-            command_id: "test-id".to_string(),
-            action: "nonexistent".to_string(),
+            command_id: "purge".to_string(),
+            action: "purge_data".to_string(),
             parameters: HashMap::new(),
             timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
         };
-        let config = Config::from_env();
-        let result = execute_command(&cmd, &config);
+        let result = handle_purge_data(&cmd, &config);
         assert!(!result.ok);
-        assert!(result.error.as_ref().unwrap().contains("unknown action"));
+        assert!(result.error.unwrap().contains("confirm: true"));
     }
 
     #[test]
-    fn test_command_parse_minimal() {
-        let json = r#"{"command_id": "x", "action": "observe"}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.command_id, "x");
-        assert_eq!(cmd.action, "observe");
-        assert!(cmd.parameters.is_empty());
-        assert_eq!(cmd.timeout_ms, 5000);
+    fn test_preview_events_rejects_bad_parameters() {
+        let config = Config::from_env();
+        for (key, value) in [("count", serde_json::json!(0)), ("redact_pattern", serde_json::json!("(unclosed"))] {
+            let cmd = Command {
+                command_id: "preview".to_string(),
+                action: "preview_events".to_string(),
+                parameters: HashMap::from([(key.to_string(), value)]),
+                timeout_ms: 5000,
+                verify_diff: false,
+                capture_before: false,
+                include_screenshot: None,
+                include_uia: None,
+                uia_depth: None,
+                traceparent: None,
+            };
+            let result = execute_command(&cmd, &config);
+            assert!(!result.ok, "{key} should be rejected");
+        }
     }
 
     #[test]
-    fn test_scroll_command_parse() {
-        let json = r#"{"command_id": "s1", "action": "scroll", "parameters": {"direction": "up", "amount": 5}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "scroll");
-        assert_eq!(cmd.parameters["direction"], "up");
-        assert_eq!(cmd.parameters["amount"], 5);
+    fn test_image_matches_and_protected_processes() {
+        assert!(image_matches("C:\\Windows\\notepad.exe", "notepad.exe"));
+        assert!(image_matches("C:\\Windows\\notepad.exe", "Notepad"));
+        assert!(!image_matches("C:\\Windows\\notepad.exe", "note"));
+        assert!(!image_matches("C:\\Windows\\notepad.exe", ""));
+        assert!(is_protected_process("C:\\Windows\\System32\\lsass.exe"));
+        assert!(is_protected_process("C:\\Windows\\System32\\SVCHOST.EXE"));
+        assert!(!is_protected_process("C:\\Windows\\explorer.exe"));
     }
 
     #[test]
-    fn test_double_click_command_parse() {
-        let json = r#"{"command_id": "dc1", "action": "double_click", "parameters": {"x": 100, "y": 200}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "double_click");
-        assert_eq!(cmd.parameters["x"], 100);
-        assert_eq!(cmd.parameters["y"], 200);
+    fn test_end_process_grace_ends_before_the_command() {
+        let command = |timeout_ms: u64, parameters: serde_json::Value| -> Command {
+            serde_json::from_value(serde_json::json!({
+                "command_id": "k1", "action": "kill_process", "parameters": parameters, "timeout_ms": timeout_ms,
+            }))
+            .unwrap()
+        };
+        assert_eq!(end_process_grace_ms(&command(10_000, serde_json::json!({}))), DEFAULT_END_PROCESS_GRACE_MS);
+        assert_eq!(end_process_grace_ms(&command(10_000, serde_json::json!({"grace_ms": 500}))), 500);
+        assert_eq!(end_process_grace_ms(&command(5000, serde_json::json!({"grace_ms": 20_000}))), 3500);
+        assert_eq!(end_process_grace_ms(&command(120_000, serde_json::json!({"grace_ms": 90_000}))), MAX_END_PROCESS_GRACE_MS);
+        assert_eq!(end_process_grace_ms(&command(1000, serde_json::json!({}))), 0);
     }
 
     #[test]
-    fn test_right_click_command_parse() {
-        let json = r#"{"command_id": "rc1", "action": "right_click", "parameters": {"x": 50, "y": 75}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "right_click");
-        assert_eq!(cmd.parameters["x"], 50);
-        assert_eq!(cmd.parameters["y"], 75);
+    fn test_nth_match() {
+        assert_eq!(nth_match(3, 0), Ok(0));
+        assert_eq!(nth_match(3, 2), Ok(2));
+        assert_eq!(nth_match(3, 3), Err("index 3 out of range: 3 element(s) match".to_string()));
+        assert_eq!(nth_match(0, 0), Err("element not found".to_string()));
     }
 
     #[test]
-    fn test_double_click_name_based_parse() {
-        let json = r#"{"command_id": "dc2", "action": "double_click", "parameters": {"name": "Submit"}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "double_click");
-        assert_eq!(cmd.parameters["name"], "Submit");
+    fn test_export_and_import_state_commands() {
+        let mut config = Config::from_env();
+        // Other tests may set WEBHOOKS_PATH to a file that does not exist
+        config.webhooks_path.clear();
+        let command = |action: &str, parameters: serde_json::Value| Command {
+            command_id: "test".to_string(),
+            action: action.to_string(),
+            parameters: serde_json::from_value(parameters).unwrap(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+
+        let exported = execute_command(&command("export_state", serde_json::json!({})), &config);
+        assert!(exported.ok);
+        assert_eq!(exported.result["archive"]["format"], crate::state::ARCHIVE_FORMAT);
+
+        let missing = execute_command(&command("import_state", serde_json::json!({})), &config);
+        assert!(missing.error.unwrap().contains("requires 'archive' or 'path'"));
+        let mut newer = exported.result["archive"].clone();
+        newer["version"] = serde_json::json!(99);
+        let rejected = execute_command(&command("import_state", serde_json::json!({"archive": newer})), &config);
+        assert!(rejected.error.unwrap().contains("unsupported archive version"));
+        let relative = execute_command(&command("import_state", serde_json::json!({"path": "state.json"})), &config);
+        assert_eq!(relative.error.as_deref(), Some("path must be absolute: state.json"));
+
+        // Written to a new file only
+        let path = std::env::temp_dir().join(format!("desktopai-state-{}.json", uuid::Uuid::new_v4()));
+        let to_file = || execute_command(&command("export_state", serde_json::json!({"path": path})), &config);
+        assert!(to_file().ok);
+        let again = to_file();
+        assert!(again.error.unwrap().starts_with(&format!("cannot write {}", path.display())));
+        let relative = execute_command(&command("export_state", serde_json::json!({"path": "state.json"})), &config);
+        assert_eq!(relative.error.as_deref(), Some("path must be absolute: state.json"));
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn test_right_click_name_based_parse() {
-        let json = r#"{"command_id": "rc2", "action": "right_click", "parameters": {"name": "FileItem", "automation_id": "file_1"}}"#;
-        let cmd: Command = serde_json::from_str(json).unwrap();
-        assert_eq!(cmd.action, "right_click");
-        assert_eq!(cmd.parameters["name"], "FileItem");
-        assert_eq!(cmd.parameters["automation_id"], "file_1");
+    fn test_create_diagnostic_bundle_command() {
+        let config = Config::from_env();
+        let path = std::env::temp_dir().join(format!("desktopai-bundle-{}.zip", uuid::Uuid::new_v4()));
+        let command = |parameters: serde_json::Value| Command {
+            command_id: "test".to_string(),
+            action: "create_diagnostic_bundle".to_string(),
+            parameters: serde_json::from_value(parameters).unwrap(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+
+        let refused = execute_command(&command(serde_json::json!({"password": "correct horse"})), &config);
+        assert!(refused.error.unwrap().contains("include"));
+        let parameters = serde_json::json!({"include": ["config", "metrics"], "password": "correct horse", "path": path});
+        let created = execute_command(&command(parameters), &config);
+        assert!(created.ok, "{:?}", created.error);
+        assert_eq!(created.result["included"], serde_json::json!(["config", "metrics"]));
+        assert_eq!(created.result["files"], serde_json::json!(["manifest.json", "config.json", "metrics.json"]));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_scroll_wheel_directions() {
+        assert_eq!(scroll_wheel("up", 3), Ok((false, 360)));
+        assert_eq!(scroll_wheel("down", 1), Ok((false, -120)));
+        assert_eq!(scroll_wheel("right", 2), Ok((true, 240)));
+        assert_eq!(scroll_wheel("left", 2), Ok((true, -240)));
+        assert!(scroll_wheel("sideways", 1).unwrap_err().contains("unknown scroll direction"));
     }
 
     #[cfg(not(windows))]
     #[test]
     fn test_new_commands_fail_on_non_windows() {
         let config = Config::from_env();
-        for action in &["scroll", "double_click", "right_click"] {
+        for action in &[
+            "scroll",
+            "double_click",
+            "triple_click",
+            "middle_click",
+            "right_click",
+            "get_taskbar_state",
+            "start_menu_search",
+            "hover",
+            "close_window",
+            "minimize_window",
+            "maximize_window",
+            "restore_window",
+            "move_window",
+            "resize_window",
+            "get_element_tree",
+            "get_element_text",
+            "click_detection",
+            "get_window_thumbnail",
+            "wait_for_window",
+            "wait_for_ui_idle",
+            "list_windows",
+            "list_monitors",
+            "get_window_rect",
+            "select_item",
+            "expand",
+            "collapse",
+            "scroll_into_view",
+            "set_range_value",
+            "invoke_menu",
+            "screenshot_region",
+            "ocr",
+            "move_to_recycle_bin",
+            "empty_recycle_bin",
+            "drop_files",
+            "browser_navigate",
+            "find_elements",
+            "kill_process",
+            "close_application",
+            "self_test",
+            "switch_desktop",
+            "swipe",
+            "flick",
+            "pinch",
+            "paste_text",
+            "if_exists",
+            "show_element_labels",
+            "hide_element_labels",
+        ] {
             let cmd = Command {
                 command_id: "test".to_string(),
                 action: action.to_string(),
                 parameters: HashMap::new(),
                 timeout_ms: 5000,
+                verify_diff: false,
+                capture_before: false,
+                include_screenshot: None,
+                include_uia: None,
+                uia_depth: None,
+                traceparent: None,
             };
             let result = execute_command(&cmd, &config);
             assert!(!result.ok, "{action} should fail on non-Windows");
@@ -1240,6 +7264,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_taskbar_region_classification() {
+        // Windows 10 shell
+        assert_eq!(taskbar_region("MSTaskListWClass", ""), Some("apps"));
+        assert_eq!(taskbar_region("TrayNotifyWnd", ""), Some("tray"));
+        assert_eq!(taskbar_region("TrayClockWClass", ""), Some("system"));
+        // Windows 11 shell
+        assert_eq!(taskbar_region("Taskbar.TaskListButtonAutomationPeer", "Appid:Microsoft.Edge"), Some("apps"));
+        assert_eq!(taskbar_region("", "NotifyItemIcon"), Some("tray"));
+        assert_eq!(taskbar_region("", "StartButton"), Some("system"));
+        // Unknown elements inherit from their container
+        assert_eq!(taskbar_region("ToolbarWindow32", ""), None);
+    }
+
     #[test]
     fn test_click_xy_command_parse() {
         let json = r#"{"command_id": "c1", "action": "click", "parameters": {"x": 300, "y": 450}}"#;
@@ -1258,6 +7296,12 @@ mod tests {
             action: "click".to_string(),
             parameters: HashMap::new(),
             timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
         };
         let result = execute_command(&cmd, &config);
         assert!(!result.ok);
@@ -1276,6 +7320,12 @@ mod tests {
             action: "focus_window".to_string(),
             parameters: params,
             timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
         };
         let result = execute_command(&cmd, &config);
         assert!(!result.ok);
@@ -1292,6 +7342,12 @@ mod tests {
             action: "focus_window".to_string(),
             parameters: HashMap::new(),
             timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
         };
         let result = execute_command(&cmd, &config);
         assert!(!result.ok);
@@ -1322,6 +7378,282 @@ mod tests {
         assert_eq!(dets[0]["score"], 0.95);
     }
 
+    #[test]
+    fn test_command_parse_verify_diff() {
+        let json = r#"{"command_id": "v1", "action": "click", "parameters": {"x": 1, "y": 2}, "verify_diff": true}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(cmd.verify_diff);
+
+        let json = r#"{"command_id": "v2", "action": "click"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(!cmd.verify_diff);
+        assert!(!cmd.capture_before);
+
+        let json = r#"{"command_id": "v3", "action": "click", "capture_before": true}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(cmd.capture_before);
+    }
+
+    #[test]
+    fn test_observation_overrides() {
+        let mut config = Config::from_env();
+        config.enable_screenshot = true;
+        config.uia_enabled = true;
+        config.uia_max_depth = 3;
+        config.uia_throttle = std::time::Duration::from_millis(500);
+
+        let json = r#"{"command_id": "o1", "action": "click"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert_eq!((cmd.include_screenshot, cmd.include_uia, cmd.uia_depth), (None, None, None));
+        assert!(observation_config(&cmd, &config).is_none(), "no overrides, no copy");
+
+        let json = r#"{"command_id": "o2", "action": "observe", "include_screenshot": false, "include_uia": false}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        let observed = observation_config(&cmd, &config).unwrap();
+        assert!(!observed.enable_screenshot);
+        assert!(!observed.uia_enabled);
+
+        let json = r#"{"command_id": "o3", "action": "click", "include_uia": true, "uia_depth": 8}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        let observed = observation_config(&cmd, &config).unwrap();
+        assert!(observed.enable_screenshot && observed.uia_enabled);
+        assert_eq!(observed.uia_max_depth, 8);
+        assert_eq!(observed.uia_throttle, std::time::Duration::ZERO);
+
+        config.enable_screenshot = false;
+        config.uia_enabled = false;
+        let json = r#"{"command_id": "o4", "action": "click", "include_screenshot": true, "include_uia": true}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        let observed = observation_config(&cmd, &config).unwrap();
+        assert!(!observed.enable_screenshot && !observed.uia_enabled, "cannot enable what the config disables");
+    }
+
+    #[test]
+    fn test_command_result_with_before_screenshot() {
+        let mut cr = CommandResult::success("v1", HashMap::new());
+        assert!(serde_json::to_value(&cr).unwrap().get("screenshot_before_b64").is_none());
+        cr.screenshot_before_b64 = Some("before".to_string());
+        cr.screenshot_b64 = Some("after".to_string());
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["screenshot_before_b64"], "before");
+        assert_eq!(json["screenshot_b64"], "after");
+    }
+
+    #[test]
+    fn test_command_result_with_screen_diff() {
+        let mut cr = CommandResult::success("v1", HashMap::new());
+        assert!(serde_json::to_value(&cr).unwrap().get("screen_diff").is_none());
+        cr.screen_diff = Some(crate::diff::compare_signatures(&[0.0], &[0.0]));
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["screen_diff"]["changed_blocks"], 0);
+        assert_eq!(json["screen_diff"]["score"], 0.0);
+    }
+
+    #[test]
+    fn test_command_result_denied() {
+        let cr = CommandResult::denied("p1", "'click' is blocked while safe mode is on");
+        let json = serde_json::to_value(&cr).unwrap();
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["error_code"], "PolicyDenied");
+        assert!(json["error"].as_str().unwrap().starts_with("PolicyDenied:"));
+
+        let ok = serde_json::to_value(CommandResult::success("p2", HashMap::new())).unwrap();
+        assert!(ok.get("error_code").is_none());
+    }
+
+    #[test]
+    fn test_control_type_names() {
+        assert_eq!(control_type_id("Edit"), Some(50004));
+        assert_eq!(control_type_id("check box"), Some(50002));
+        assert_eq!(control_type_id("CheckBox"), Some(50002));
+        assert_eq!(control_type_id(" list item "), Some(50007));
+        assert_eq!(control_type_id("link"), control_type_id("Hyperlink"));
+        assert_eq!(control_type_id("Bearbeiten"), None);
+        assert_eq!(clicked_name("", "", "edit"), "edit");
+        assert_eq!(clicked_name("Send", "sendButton", "button"), "Send");
+    }
+
+    #[test]
+    fn test_title_score() {
+        assert_eq!(title_score("untitled - notepad", "notepad"), Some(2));
+        assert_eq!(title_score("notepad++", "notepad"), Some(1));
+        assert_eq!(title_score("note - app", "note"), Some(2));
+        assert_eq!(title_score("notes", "note"), Some(1));
+        assert_eq!(title_score("calculator", "notepad"), None);
+        assert_eq!(title_score("anything", ""), Some(2));
+    }
+
+    #[test]
+    fn test_rank_windows() {
+        let window = |hwnd: &str, title: &str, process_exe: &str, pid: u32| crate::inventory::WindowInfo {
+            hwnd: hwnd.to_string(),
+            title: title.to_string(),
+            process_exe: process_exe.to_string(),
+            pid,
+        };
+        // Front to back, as EnumWindows lists them
+        let windows = vec![
+            window("0x1", "Notepad++", "C:\\Apps\\notepad++.exe", 10),
+            window("0x2", "notes.txt - Notepad", "C:\\Windows\\notepad.exe", 20),
+            window("0x3", "todo.txt - Notepad", "C:\\Windows\\notepad.exe", 21),
+            window("0x4", "Calculator", "C:\\Windows\\calc.exe", 30),
+        ];
+        let hwnds = |ranked: Vec<crate::inventory::WindowInfo>| ranked.into_iter().map(|w| w.hwnd).collect::<Vec<_>>();
+
+        assert_eq!(hwnds(rank_windows(windows.clone(), "notepad", "", None)), ["0x3", "0x2", "0x1"]);
+        assert_eq!(hwnds(rank_windows(windows.clone(), "", "notepad.exe", None)), ["0x2", "0x3"]);
+        assert_eq!(hwnds(rank_windows(windows.clone(), "", "", Some(21))), ["0x3"]);
+        assert_eq!(hwnds(rank_windows(windows.clone(), "", "", None)), ["0x1", "0x2", "0x3", "0x4"], "z-order");
+        assert!(rank_windows(windows, "paint", "", None).is_empty());
+    }
+
+    #[test]
+    fn test_pid_param() {
+        let mut cmd = Command {
+            command_id: "pid-1".to_string(),
+            action: "focus_window".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(pid_param(&cmd), Ok(None));
+        cmd.parameters.insert("pid".to_string(), serde_json::json!(4242));
+        assert_eq!(pid_param(&cmd), Ok(Some(4242)));
+        for bad in [serde_json::json!(0), serde_json::json!(-1), serde_json::json!("4242"), serde_json::json!(1u64 << 40)] {
+            cmd.parameters.insert("pid".to_string(), bad);
+            assert!(pid_param(&cmd).is_err());
+        }
+    }
+
+    #[test]
+    fn test_wait_for_ui_idle_quiet() {
+        let mut cmd = Command {
+            command_id: "wfui-1".to_string(),
+            action: "wait_for_ui_idle".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(ui_idle_quiet(&cmd), Ok(std::time::Duration::from_millis(500)));
+        cmd.parameters.insert("quiet_ms".to_string(), serde_json::json!(1200));
+        assert_eq!(ui_idle_quiet(&cmd), Ok(std::time::Duration::from_millis(1200)));
+        for bad in [serde_json::json!(0), serde_json::json!(60_000), serde_json::json!("fast")] {
+            cmd.parameters.insert("quiet_ms".to_string(), bad);
+            assert!(ui_idle_quiet(&cmd).is_err());
+        }
+    }
+
+    #[test]
+    fn test_wait_for_window_state() {
+        let mut cmd = Command {
+            command_id: "wfw-1".to_string(),
+            action: "wait_for_window".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(wait_for_closed(&cmd), Ok(false));
+        cmd.parameters.insert("state".to_string(), serde_json::json!("closed"));
+        assert_eq!(wait_for_closed(&cmd), Ok(true));
+        cmd.parameters.insert("state".to_string(), serde_json::json!("gone"));
+        assert!(wait_for_closed(&cmd).is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_bounds() {
+        let mut cmd = Command {
+            command_id: "th-1".to_string(),
+            action: "get_window_thumbnail".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        assert_eq!(thumbnail_bounds(&cmd), THUMBNAIL_DEFAULT_SIZE);
+        cmd.parameters.insert("max_width".to_string(), serde_json::json!(4000));
+        cmd.parameters.insert("max_height".to_string(), serde_json::json!(2));
+        assert_eq!(thumbnail_bounds(&cmd), (THUMBNAIL_MAX_SIZE, 16));
+    }
+
+    #[test]
+    fn test_set_event_profile_rejects_non_strings() {
+        let cmd = Command {
+            command_id: "ep-1".to_string(),
+            action: "set_event_profile".to_string(),
+            parameters: HashMap::from([("profile".to_string(), serde_json::json!(3))]),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        let result = execute_command(&cmd, &Config::from_env());
+        assert!(!result.ok);
+        assert!(result.error.as_ref().unwrap().contains("string or null"));
+    }
+
+    #[test]
+    fn test_set_safe_mode_requires_enabled() {
+        let config = Config::from_env();
+        let cmd = Command {
+            command_id: "sm-1".to_string(),
+            action: "set_safe_mode".to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        let result = execute_command(&cmd, &config);
+        assert!(!result.ok);
+        assert!(result.error.as_ref().unwrap().contains("enabled"));
+    }
+
+    #[test]
+    fn test_set_safe_mode_cannot_go_below_config() {
+        let mut config = Config::from_env();
+        config.safe_mode = true;
+        let cmd = Command {
+            command_id: "sm-2".to_string(),
+            action: "set_safe_mode".to_string(),
+            parameters: HashMap::from([("enabled".to_string(), serde_json::json!(false))]),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        let result = execute_command(&cmd, &config);
+        assert_eq!(result.error_code.as_deref(), Some("PolicyDenied"));
+        assert!(crate::policy::safe_mode_active(&config), "the configured floor holds");
+    }
+
     #[test]
     fn test_command_result_no_detections_omitted() {
         let cr = CommandResult::success("det-2", HashMap::new());
@@ -1,4 +1,5 @@
-//! Configuration from environment variables with sensible defaults.
+//! Configuration from environment variables (or settings saved by `import_state`)
+//! with sensible defaults.
 
 use std::env;
 use std::time::Duration;
@@ -12,54 +13,235 @@ pub struct Config {
     pub idle_enabled: bool,
     pub idle_threshold: Duration,
     pub idle_poll: Duration,
+    /// Longest poll interval while the user is idle (see `idle::next_poll`).
+    pub idle_poll_max: Duration,
     pub uia_enabled: bool,
     pub uia_throttle: Duration,
     pub uia_text_max: usize,
     pub uia_max_depth: usize,
+    /// Token budget of the text outline attached to UIA snapshots (zero disables, see `outline`).
+    pub uia_outline_tokens: usize,
+    /// Send the outline instead of the raw UIA tree.
+    pub uia_outline_only: bool,
+    /// How long an event waits for its UIA snapshot before going without (zero = no limit).
+    pub uia_timeout_ms: u64,
     pub enable_screenshot: bool,
     pub screenshot_max_width: u32,
     pub screenshot_max_height: u32,
     pub screenshot_quality: u8,
+    /// Longest a capture stays in the screenshot ring buffer (zero = not retained).
+    pub screenshot_buffer_ttl: Duration,
+    /// How long an event waits for its screenshot before going without (zero = no limit).
+    pub screenshot_timeout_ms: u64,
     pub command_enabled: bool,
     pub screenshot_format: String,
+    /// `gdi`, or `graphics_capture` to capture and downscale on the GPU (see `graphics_capture`).
+    pub screenshot_backend: String,
     pub uia_cache_ttl_ms: u64,
     pub ws_reconnect_max_ms: u64,
+    /// Payloads larger than this go to the backend in parts (zero = never split, see `chunking`).
+    pub ws_chunk_bytes: usize,
     pub detection_enabled: bool,
     pub detection_model_path: String,
+    /// Manifest of model assets to download into the data directory (empty = off, see `assets`).
+    pub asset_manifest_url: String,
+    /// Base64 Ed25519 public key the asset manifest must be signed with (empty = unsigned).
+    pub asset_signing_key: String,
     pub detection_confidence: f32,
     pub detection_input_size: u32,
+    pub safe_mode: bool,
+    pub safe_mode_flag_path: String,
+    /// Permission manifest edited by the Tauri app (see `permissions`).
+    pub permissions_path: String,
+    /// Directory of workflow files for `run_workflow` (see workflow.rs).
+    pub workflows_dir: String,
+    /// Stable collector ID; empty until resolved from `collector_id_path` at startup.
+    pub collector_id: String,
+    pub collector_id_path: String,
+    pub collector_name: String,
+    /// Seconds between backend clock sync exchanges (0 disables).
+    pub time_sync_interval: Duration,
+    /// Ordered enrichment stage names for foreground events (see `pipeline`).
+    pub enrich_stages: Vec<String>,
+    pub geometry_enabled: bool,
+    pub redaction_enabled: bool,
+    pub redact_pattern: String,
+    /// Run a heavy observation (deep UIA, full-res screenshot, detection) when the user goes idle.
+    pub deep_capture_on_idle: bool,
+    /// Repeat the deep capture this often while still idle (zero = once per idle period).
+    pub deep_capture_interval: Duration,
+    pub deep_uia_max_depth: usize,
+    pub deep_uia_text_max: usize,
+    /// How often to diff the top-level window list (zero disables).
+    pub window_inventory_interval: Duration,
+    /// Executable names of DesktopAI's own UI (avatar, palette); their windows are skipped.
+    pub self_exclude_processes: Vec<String>,
+    /// Let commands act on DesktopAI's own windows without a per-command `allow_self`.
+    pub allow_self_targeting: bool,
+    /// Abort input commands when the user moves the mouse or types (see interrupt.rs).
+    pub user_interrupt_enabled: bool,
+    /// Cap on fully enriched observations per second (0 = no rate cap).
+    pub observe_max_per_sec: f32,
+    /// Share of one core, in percent, heavy enrichment may use on average (0 = no cap).
+    pub observe_cpu_budget_pct: f32,
+    /// Switch to the lighter battery capture profile while unplugged (see power.rs).
+    pub power_profiles_enabled: bool,
+    /// Cap on fully enriched observations per second on battery (0 = no rate cap).
+    pub battery_observe_max_per_sec: f32,
+    /// Screenshot size limit on battery; the smaller of this and the AC limit applies.
+    pub battery_screenshot_max_width: u32,
+    pub battery_screenshot_max_height: u32,
+    /// Record every command and its result as a replay fixture here (empty = off).
+    pub command_record_dir: String,
+    pub text_normalize_enabled: bool,
+    /// Attach the detected language of document text to events (see language.rs).
+    pub language_detect_enabled: bool,
+    /// Attach the window's small icon to events as a PNG.
+    pub icon_enabled: bool,
+    /// Attach a `timings` breakdown (UIA, capture, encode, detection, queue wait) to events.
+    pub capture_timings_enabled: bool,
+    /// Opt-in: upload anonymous usage aggregates (see telemetry.rs).
+    pub telemetry_enabled: bool,
+    /// Endpoint telemetry reports are POSTed to (empty = off).
+    pub telemetry_url: String,
+    /// How often telemetry aggregates are uploaded (at least a minute).
+    pub telemetry_interval: Duration,
+    /// OTLP/HTTP collector base URL traces are exported to, as
+    /// `<endpoint>/v1/traces` (empty = off; see trace.rs).
+    pub otlp_endpoint: String,
+    /// `service.name` of exported traces.
+    pub otel_service_name: String,
+    /// Serve the event tap on 127.0.0.1 at this port (0 = off; see tap.rs).
+    pub event_tap_port: u16,
+    /// Token event tap subscribers must present (empty = tap off).
+    pub event_tap_token: String,
+    /// JSON file of webhooks to POST selected events to (empty = off; see webhook.rs).
+    pub webhooks_path: String,
+    /// JSON file of event tagging and routing rules (empty = untagged, every
+    /// event to every sink; see routing.rs).
+    pub routing_path: String,
+    /// Profile tag added to every event until changed at runtime (empty = none).
+    pub event_profile: String,
+    /// Managed directory for everything the collector writes (see datadir.rs).
+    pub data_dir: String,
+    /// Size quota for prunable files in the data directory, in MB (0 = none).
+    pub data_max_mb: u64,
+    /// Prunable data files older than this many days are deleted (0 = keep).
+    pub data_retention_days: u64,
 }
 
 impl Config {
     pub fn from_env() -> Self {
         let ws_url =
-            env::var("BACKEND_WS_URL").unwrap_or_else(|_| "ws://localhost:8000/ingest".into());
+            setting("BACKEND_WS_URL").unwrap_or_else(|_| "ws://localhost:8000/ingest".into());
         let http_url =
-            env::var("BACKEND_HTTP_URL").unwrap_or_else(|_| "http://localhost:8000/api/events".into());
-        let retry = env::var("WS_RETRY_SECONDS")
+            setting("BACKEND_HTTP_URL").unwrap_or_else(|_| "http://localhost:8000/api/events".into());
+        let retry = setting("WS_RETRY_SECONDS")
             .ok()
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(5);
         let idle_enabled = env_bool("IDLE_ENABLED", true);
         let idle_threshold = Duration::from_millis(env_u64("IDLE_THRESHOLD_MS", 60_000));
         let idle_poll = Duration::from_millis(env_u64("IDLE_POLL_MS", 1000));
+        let idle_poll_max = Duration::from_millis(env_u64("IDLE_POLL_MAX_MS", 10_000));
         let uia_enabled = env_bool("UIA_ENABLED", true);
         let uia_throttle = Duration::from_millis(env_u64("UIA_THROTTLE_MS", 1000));
         let uia_text_max = env_usize("UIA_TEXT_MAX_CHARS", 240);
         let uia_max_depth = env_usize("UIA_MAX_DEPTH", 3);
+        let uia_outline_tokens = env_usize("UIA_OUTLINE_TOKENS", 800);
+        let uia_outline_only = env_bool("UIA_OUTLINE_ONLY", false);
+        let uia_timeout_ms = env_u64("UIA_TIMEOUT_MS", 3000);
         let enable_screenshot = env_bool("ENABLE_SCREENSHOT", true);
         let screenshot_max_width = env_u32("SCREENSHOT_MAX_WIDTH", 1024);
         let screenshot_max_height = env_u32("SCREENSHOT_MAX_HEIGHT", 768);
         let screenshot_quality = env_u8("SCREENSHOT_QUALITY", 85);
+        let screenshot_buffer_ttl = Duration::from_millis(env_u64("SCREENSHOT_BUFFER_TTL_MS", 60_000));
+        let screenshot_timeout_ms = env_u64("SCREENSHOT_TIMEOUT_MS", 2000);
         let command_enabled = env_bool("COMMAND_BRIDGE_ENABLED", true);
-        let screenshot_format = env::var("SCREENSHOT_FORMAT").unwrap_or_else(|_| "jpeg".into());
+        let screenshot_format = setting("SCREENSHOT_FORMAT").unwrap_or_else(|_| "jpeg".into());
+        let screenshot_backend = setting("SCREENSHOT_BACKEND").unwrap_or_else(|_| "gdi".into());
         let uia_cache_ttl_ms = env_u64("UIA_CACHE_TTL_MS", 2000);
         let ws_reconnect_max_ms = env_u64("WS_RECONNECT_MAX_MS", 30_000);
+        let ws_chunk_bytes = env_usize("WS_CHUNK_BYTES", 256 * 1024);
         let detection_enabled = env_bool("DETECTION_ENABLED", true);
-        let detection_model_path = env::var("DETECTION_MODEL_PATH")
-            .unwrap_or_else(|_| "models/ui-detr/ui-detr-1.onnx".into());
         let detection_confidence = env_f32("DETECTION_CONFIDENCE", 0.3);
         let detection_input_size = env_u32("DETECTION_INPUT_SIZE", 576);
+        let safe_mode = env_bool("SAFE_MODE", false);
+        let data_dir = crate::datadir::root().unwrap_or_default();
+        let in_data_dir = |name: &str| if data_dir.is_empty() { String::new() } else { format!("{data_dir}\\{name}") };
+        let asset_manifest_url = setting("ASSET_MANIFEST_URL").unwrap_or_default();
+        let asset_signing_key = setting("ASSET_SIGNING_KEY").unwrap_or_default();
+        // Prefer a model downloaded into the data directory over the source
+        // tree's, including one the asset manager is still fetching
+        let detection_model_path = setting("DETECTION_MODEL_PATH").unwrap_or_else(|_| {
+            let downloaded = in_data_dir("models\\ui-detr\\ui-detr-1.onnx");
+            if !downloaded.is_empty() && (std::path::Path::new(&downloaded).exists() || !asset_manifest_url.is_empty()) {
+                downloaded
+            } else {
+                "models/ui-detr/ui-detr-1.onnx".into()
+            }
+        });
+        // Marker file toggled by the Tauri tray; shared location so both processes agree.
+        let safe_mode_flag_path = setting("SAFE_MODE_FLAG_PATH").unwrap_or_else(|_| in_data_dir("safe_mode"));
+        let permissions_path = setting("PERMISSIONS_PATH").unwrap_or_else(|_| in_data_dir("permissions.json"));
+        let workflows_dir = setting("WORKFLOWS_DIR").unwrap_or_else(|_| in_data_dir("workflows"));
+        let collector_id = setting("COLLECTOR_ID").unwrap_or_default();
+        let collector_id_path = setting("COLLECTOR_ID_PATH").unwrap_or_else(|_| in_data_dir("collector_id"));
+        let collector_name = setting("COLLECTOR_NAME")
+            .ok()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(crate::identity::default_collector_name);
+        let time_sync_interval = Duration::from_secs(env_u64("TIME_SYNC_INTERVAL_S", 60));
+        let enrich_stages = setting("ENRICH_STAGES")
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(|_| {
+                crate::pipeline::DEFAULT_STAGES.iter().map(|s| s.to_string()).collect()
+            });
+        let geometry_enabled = env_bool("GEOMETRY_ENABLED", true);
+        let redaction_enabled = env_bool("REDACTION_ENABLED", false);
+        let redact_pattern = setting("REDACT_PATTERN")
+            .unwrap_or_else(|_| crate::pipeline::DEFAULT_REDACT_PATTERN.into());
+        let deep_capture_on_idle = env_bool("DEEP_CAPTURE_ON_IDLE", false);
+        let deep_capture_interval = Duration::from_secs(env_u64("DEEP_CAPTURE_INTERVAL_S", 300));
+        let deep_uia_max_depth = env_usize("DEEP_UIA_MAX_DEPTH", 12);
+        let deep_uia_text_max = env_usize("DEEP_UIA_TEXT_MAX_CHARS", 4000);
+        let window_inventory_interval = Duration::from_millis(env_u64("WINDOW_INVENTORY_INTERVAL_MS", 5000));
+        let self_exclude_processes = setting("SELF_EXCLUDE_PROCESSES")
+            .unwrap_or_else(|_| "desktopai.exe".into())
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let allow_self_targeting = env_bool("ALLOW_SELF_TARGETING", false);
+        let user_interrupt_enabled = env_bool("USER_INTERRUPT_ENABLED", true);
+        let observe_max_per_sec = env_f32("OBSERVE_MAX_PER_SEC", 2.0);
+        let observe_cpu_budget_pct = env_f32("OBSERVE_CPU_BUDGET_PCT", 10.0);
+        let power_profiles_enabled = env_bool("POWER_PROFILES_ENABLED", true);
+        let battery_observe_max_per_sec = env_f32("BATTERY_OBSERVE_MAX_PER_SEC", 0.5);
+        let battery_screenshot_max_width = env_u32("BATTERY_SCREENSHOT_MAX_WIDTH", 640);
+        let battery_screenshot_max_height = env_u32("BATTERY_SCREENSHOT_MAX_HEIGHT", 480);
+        let command_record_dir = setting("COMMAND_RECORD_DIR").unwrap_or_default();
+        let text_normalize_enabled = env_bool("TEXT_NORMALIZE_ENABLED", true);
+        let language_detect_enabled = env_bool("LANGUAGE_DETECT_ENABLED", true);
+        let icon_enabled = env_bool("ICON_ENABLED", true);
+        let capture_timings_enabled = env_bool("CAPTURE_TIMINGS", false);
+        let telemetry_enabled = env_bool("TELEMETRY_ENABLED", false);
+        let telemetry_url = setting("TELEMETRY_URL").unwrap_or_default();
+        let telemetry_interval = Duration::from_secs(env_u64("TELEMETRY_INTERVAL_S", 3600).max(60));
+        let otlp_endpoint = setting("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_default();
+        let otel_service_name = setting("OTEL_SERVICE_NAME").unwrap_or_else(|_| "desktopai-collector".to_string());
+        let event_tap_port = setting("EVENT_TAP_PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+        let event_tap_token = setting("EVENT_TAP_TOKEN").unwrap_or_default();
+        let webhooks_path = setting("WEBHOOKS_PATH").unwrap_or_default();
+        let routing_path = setting("ROUTING_PATH").unwrap_or_default();
+        let event_profile = setting("EVENT_PROFILE").unwrap_or_default();
+        let data_max_mb = env_u64("DATA_MAX_MB", 512);
+        let data_retention_days = env_u64("DATA_RETENTION_DAYS", 30);
         Self {
             ws_url,
             http_url,
@@ -67,29 +249,125 @@ impl Config {
             idle_enabled,
             idle_threshold,
             idle_poll,
+            idle_poll_max,
             uia_enabled,
             uia_throttle,
             uia_text_max,
             uia_max_depth,
+            uia_outline_tokens,
+            uia_outline_only,
+            uia_timeout_ms,
             enable_screenshot,
             screenshot_max_width,
             screenshot_max_height,
             screenshot_quality,
+            screenshot_buffer_ttl,
+            screenshot_timeout_ms,
             command_enabled,
             screenshot_format,
+            screenshot_backend,
             uia_cache_ttl_ms,
             ws_reconnect_max_ms,
+            ws_chunk_bytes,
             detection_enabled,
             detection_model_path,
+            asset_manifest_url,
+            asset_signing_key,
             detection_confidence,
             detection_input_size,
+            safe_mode,
+            safe_mode_flag_path,
+            permissions_path,
+            workflows_dir,
+            collector_id,
+            collector_id_path,
+            collector_name,
+            time_sync_interval,
+            enrich_stages,
+            geometry_enabled,
+            redaction_enabled,
+            redact_pattern,
+            deep_capture_on_idle,
+            deep_capture_interval,
+            deep_uia_max_depth,
+            deep_uia_text_max,
+            window_inventory_interval,
+            self_exclude_processes,
+            allow_self_targeting,
+            user_interrupt_enabled,
+            observe_max_per_sec,
+            observe_cpu_budget_pct,
+            power_profiles_enabled,
+            battery_observe_max_per_sec,
+            battery_screenshot_max_width,
+            battery_screenshot_max_height,
+            command_record_dir,
+            text_normalize_enabled,
+            language_detect_enabled,
+            icon_enabled,
+            capture_timings_enabled,
+            telemetry_enabled,
+            telemetry_url,
+            telemetry_interval,
+            otlp_endpoint,
+            otel_service_name,
+            event_tap_port,
+            event_tap_token,
+            webhooks_path,
+            routing_path,
+            event_profile,
+            data_dir,
+            data_max_mb,
+            data_retention_days,
+        }
+    }
+
+    /// Variant of this config for idle deep captures: deeper UIA walk, no
+    /// throttle, full-resolution screenshot without a time limit and detection
+    /// appended to the stage list. Redaction and the outline still run last if they were
+    /// configured.
+    pub fn deep_capture(&self) -> Config {
+        let mut deep = self.clone();
+        deep.uia_max_depth = self.deep_uia_max_depth;
+        deep.uia_text_max = self.deep_uia_text_max;
+        deep.uia_throttle = Duration::ZERO;
+        deep.uia_timeout_ms = 0;
+        deep.screenshot_max_width = u32::MAX;
+        deep.screenshot_max_height = u32::MAX;
+        deep.screenshot_timeout_ms = 0;
+        deep.enrich_stages = ["title", "icon", "geometry", "theme", "uia", "screenshot", "detection", "normalize", "language"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        for stage in ["redaction", "outline"] {
+            if self.enrich_stages.iter().any(|s| s == stage) {
+                deep.enrich_stages.push(stage.to_string());
+            }
         }
+        deep
+    }
+
+    /// Variant of this config for capture on battery power: fewer heavy
+    /// observations, smaller screenshots and no detection.
+    pub fn battery_capture(&self) -> Config {
+        let mut battery = self.clone();
+        battery.observe_max_per_sec = self.battery_observe_max_per_sec;
+        battery.screenshot_max_width = self.screenshot_max_width.min(self.battery_screenshot_max_width);
+        battery.screenshot_max_height = self.screenshot_max_height.min(self.battery_screenshot_max_height);
+        battery.detection_enabled = false;
+        battery
     }
 }
 
+/// Read a setting: the environment wins, then the settings file written by
+/// `import_state` (see state.rs).
+pub fn setting(name: &str) -> Result<String, env::VarError> {
+    env::var(name).or_else(|missing| crate::state::saved_setting(name).ok_or(missing))
+}
+
 /// Parse a boolean from the environment (accepts 1/true/yes/on and 0/false/no/off).
 pub fn env_bool(name: &str, default: bool) -> bool {
-    let raw = env::var(name).ok();
+    let raw = setting(name).ok();
     match raw.as_deref().map(|v| v.trim().to_lowercase()) {
         Some(v) if v == "1" || v == "true" || v == "yes" || v == "on" => true,
         Some(v) if v == "0" || v == "false" || v == "no" || v == "off" => false,
@@ -98,35 +376,35 @@ pub fn env_bool(name: &str, default: bool) -> bool {
 }
 
 pub fn env_u64(name: &str, default: u64) -> u64 {
-    env::var(name)
+    setting(name)
         .ok()
         .and_then(|v| v.parse::<u64>().ok())
         .unwrap_or(default)
 }
 
 pub fn env_usize(name: &str, default: usize) -> usize {
-    env::var(name)
+    setting(name)
         .ok()
         .and_then(|v| v.parse::<usize>().ok())
         .unwrap_or(default)
 }
 
 pub fn env_u32(name: &str, default: u32) -> u32 {
-    env::var(name)
+    setting(name)
         .ok()
         .and_then(|v| v.parse::<u32>().ok())
         .unwrap_or(default)
 }
 
 pub fn env_f32(name: &str, default: f32) -> f32 {
-    env::var(name)
+    setting(name)
         .ok()
         .and_then(|v| v.parse::<f32>().ok())
         .unwrap_or(default)
 }
 
 pub fn env_u8(name: &str, default: u8) -> u8 {
-    env::var(name)
+    setting(name)
         .ok()
         .and_then(|v| v.parse::<u8>().ok())
         .unwrap_or(default)
@@ -403,24 +681,75 @@ mod tests {
         env::remove_var("IDLE_ENABLED");
         env::remove_var("IDLE_THRESHOLD_MS");
         env::remove_var("IDLE_POLL_MS");
+        env::remove_var("IDLE_POLL_MAX_MS");
         env::remove_var("UIA_ENABLED");
         env::remove_var("UIA_THROTTLE_MS");
         env::remove_var("UIA_TEXT_MAX_CHARS");
         env::remove_var("UIA_MAX_DEPTH");
+        env::remove_var("UIA_TIMEOUT_MS");
         env::remove_var("ENABLE_SCREENSHOT");
         env::remove_var("SCREENSHOT_MAX_WIDTH");
         env::remove_var("SCREENSHOT_MAX_HEIGHT");
         env::remove_var("SCREENSHOT_QUALITY");
+        env::remove_var("SCREENSHOT_TIMEOUT_MS");
         env::remove_var("COMMAND_BRIDGE_ENABLED");
         env::remove_var("SCREENSHOT_FORMAT");
+        env::remove_var("SCREENSHOT_BACKEND");
         env::remove_var("UIA_CACHE_TTL_MS");
         env::remove_var("WS_RECONNECT_MAX_MS");
+        env::remove_var("WS_CHUNK_BYTES");
         env::remove_var("DETECTION_ENABLED");
         env::remove_var("DETECTION_MODEL_PATH");
+        env::remove_var("ASSET_MANIFEST_URL");
+        env::remove_var("ASSET_SIGNING_KEY");
         env::remove_var("DETECTION_CONFIDENCE");
         env::remove_var("DETECTION_INPUT_SIZE");
+        env::remove_var("SAFE_MODE");
+        env::remove_var("SAFE_MODE_FLAG_PATH");
+        env::remove_var("COLLECTOR_ID");
+        env::remove_var("COLLECTOR_ID_PATH");
+        env::remove_var("COLLECTOR_NAME");
+        env::remove_var("TIME_SYNC_INTERVAL_S");
+        env::remove_var("ENRICH_STAGES");
+        env::remove_var("GEOMETRY_ENABLED");
+        env::remove_var("REDACTION_ENABLED");
+        env::remove_var("REDACT_PATTERN");
+        env::remove_var("DEEP_CAPTURE_ON_IDLE");
+        env::remove_var("DEEP_CAPTURE_INTERVAL_S");
+        env::remove_var("DEEP_UIA_MAX_DEPTH");
+        env::remove_var("DEEP_UIA_TEXT_MAX_CHARS");
+        env::remove_var("WINDOW_INVENTORY_INTERVAL_MS");
+        env::remove_var("SELF_EXCLUDE_PROCESSES");
+        env::remove_var("ALLOW_SELF_TARGETING");
+        env::remove_var("USER_INTERRUPT_ENABLED");
+        env::remove_var("OBSERVE_MAX_PER_SEC");
+        env::remove_var("OBSERVE_CPU_BUDGET_PCT");
+        env::remove_var("POWER_PROFILES_ENABLED");
+        env::remove_var("BATTERY_OBSERVE_MAX_PER_SEC");
+        env::remove_var("BATTERY_SCREENSHOT_MAX_WIDTH");
+        env::remove_var("BATTERY_SCREENSHOT_MAX_HEIGHT");
+        env::remove_var("COMMAND_RECORD_DIR");
+        env::remove_var("TEXT_NORMALIZE_ENABLED");
+        env::remove_var("LANGUAGE_DETECT_ENABLED");
+        env::remove_var("ICON_ENABLED");
+        env::remove_var("CAPTURE_TIMINGS");
+        env::remove_var("TELEMETRY_ENABLED");
+        env::remove_var("TELEMETRY_URL");
+        env::remove_var("TELEMETRY_INTERVAL_S");
+        env::remove_var("OTEL_EXPORTER_OTLP_ENDPOINT");
+        env::remove_var("OTEL_SERVICE_NAME");
+        env::remove_var("EVENT_TAP_PORT");
+        env::remove_var("EVENT_TAP_TOKEN");
+        env::remove_var("WEBHOOKS_PATH");
+        env::remove_var("ROUTING_PATH");
+        env::remove_var("EVENT_PROFILE");
+        env::remove_var("DATA_DIR");
+        env::remove_var("DATA_MAX_MB");
+        env::remove_var("DATA_RETENTION_DAYS");
+        env::set_var("LOCALAPPDATA", "C:\\Users\\me\\AppData\\Local");
 
         let config = Config::from_env();
+        env::remove_var("LOCALAPPDATA");
 
         assert_eq!(config.ws_url, "ws://localhost:8000/ingest");
         assert_eq!(config.http_url, "http://localhost:8000/api/events");
@@ -428,22 +757,79 @@ mod tests {
         assert!(config.idle_enabled);
         assert_eq!(config.idle_threshold, Duration::from_millis(60_000));
         assert_eq!(config.idle_poll, Duration::from_millis(1000));
+        assert_eq!(config.idle_poll_max, Duration::from_millis(10_000));
         assert!(config.uia_enabled);
         assert_eq!(config.uia_throttle, Duration::from_millis(1000));
         assert_eq!(config.uia_text_max, 240);
         assert_eq!(config.uia_max_depth, 3);
+        assert_eq!(config.uia_outline_tokens, 800);
+        assert!(!config.uia_outline_only);
+        assert_eq!(config.uia_timeout_ms, 3000);
         assert!(config.enable_screenshot);
         assert_eq!(config.screenshot_max_width, 1024);
         assert_eq!(config.screenshot_max_height, 768);
         assert_eq!(config.screenshot_quality, 85);
+        assert_eq!(config.screenshot_timeout_ms, 2000);
+        assert_eq!(config.screenshot_buffer_ttl, Duration::from_secs(60));
         assert!(config.command_enabled);
         assert_eq!(config.screenshot_format, "jpeg");
+        assert_eq!(config.screenshot_backend, "gdi");
         assert_eq!(config.uia_cache_ttl_ms, 2000);
         assert_eq!(config.ws_reconnect_max_ms, 30_000);
+        assert_eq!(config.ws_chunk_bytes, 256 * 1024);
         assert!(config.detection_enabled);
         assert_eq!(config.detection_model_path, "models/ui-detr/ui-detr-1.onnx");
+        assert_eq!(config.asset_manifest_url, "");
+        assert_eq!(config.asset_signing_key, "");
         assert!((config.detection_confidence - 0.3).abs() < f32::EPSILON);
         assert_eq!(config.detection_input_size, 576);
+        assert!(!config.safe_mode);
+        assert_eq!(config.safe_mode_flag_path, "C:\\Users\\me\\AppData\\Local\\DesktopAI\\safe_mode");
+        assert_eq!(config.permissions_path, "C:\\Users\\me\\AppData\\Local\\DesktopAI\\permissions.json");
+        assert_eq!(config.workflows_dir, "C:\\Users\\me\\AppData\\Local\\DesktopAI\\workflows");
+        assert!(config.collector_id.is_empty());
+        assert_eq!(config.collector_id_path, "C:\\Users\\me\\AppData\\Local\\DesktopAI\\collector_id");
+        assert!(!config.collector_name.is_empty());
+        assert_eq!(config.time_sync_interval, Duration::from_secs(60));
+        assert_eq!(
+            config.enrich_stages,
+            vec!["title", "icon", "geometry", "theme", "uia", "screenshot", "normalize", "language", "redaction", "outline"]
+        );
+        assert!(config.geometry_enabled);
+        assert!(!config.redaction_enabled);
+        assert_eq!(config.redact_pattern, crate::pipeline::DEFAULT_REDACT_PATTERN);
+        assert!(!config.deep_capture_on_idle);
+        assert_eq!(config.deep_capture_interval, Duration::from_secs(300));
+        assert_eq!(config.deep_uia_max_depth, 12);
+        assert_eq!(config.deep_uia_text_max, 4000);
+        assert_eq!(config.window_inventory_interval, Duration::from_millis(5000));
+        assert_eq!(config.self_exclude_processes, vec!["desktopai.exe"]);
+        assert!(!config.allow_self_targeting);
+        assert!(config.user_interrupt_enabled);
+        assert!((config.observe_max_per_sec - 2.0).abs() < f32::EPSILON);
+        assert!((config.observe_cpu_budget_pct - 10.0).abs() < f32::EPSILON);
+        assert!(config.power_profiles_enabled);
+        assert!((config.battery_observe_max_per_sec - 0.5).abs() < f32::EPSILON);
+        assert_eq!(config.battery_screenshot_max_width, 640);
+        assert_eq!(config.battery_screenshot_max_height, 480);
+        assert!(config.command_record_dir.is_empty());
+        assert!(config.text_normalize_enabled);
+        assert!(config.language_detect_enabled);
+        assert!(config.icon_enabled);
+        assert!(!config.capture_timings_enabled);
+        assert!(!config.telemetry_enabled);
+        assert!(config.telemetry_url.is_empty());
+        assert_eq!(config.telemetry_interval, Duration::from_secs(3600));
+        assert!(config.otlp_endpoint.is_empty());
+        assert_eq!(config.otel_service_name, "desktopai-collector");
+        assert_eq!(config.event_tap_port, 0);
+        assert!(config.event_tap_token.is_empty());
+        assert!(config.webhooks_path.is_empty());
+        assert!(config.routing_path.is_empty());
+        assert!(config.event_profile.is_empty());
+        assert_eq!(config.data_dir, "C:\\Users\\me\\AppData\\Local\\DesktopAI");
+        assert_eq!(config.data_max_mb, 512);
+        assert_eq!(config.data_retention_days, 30);
     }
 
     #[test]
@@ -455,22 +841,71 @@ mod tests {
         env::set_var("IDLE_ENABLED", "false");
         env::set_var("IDLE_THRESHOLD_MS", "120000");
         env::set_var("IDLE_POLL_MS", "2000");
+        env::set_var("IDLE_POLL_MAX_MS", "30000");
         env::set_var("UIA_ENABLED", "true");
         env::set_var("UIA_THROTTLE_MS", "500");
         env::set_var("UIA_TEXT_MAX_CHARS", "500");
         env::set_var("UIA_MAX_DEPTH", "10");
+        env::set_var("UIA_TIMEOUT_MS", "5000");
         env::set_var("ENABLE_SCREENSHOT", "true");
         env::set_var("SCREENSHOT_MAX_WIDTH", "1920");
         env::set_var("SCREENSHOT_MAX_HEIGHT", "1080");
         env::set_var("SCREENSHOT_QUALITY", "90");
+        env::set_var("SCREENSHOT_TIMEOUT_MS", "0");
         env::set_var("COMMAND_BRIDGE_ENABLED", "false");
         env::set_var("SCREENSHOT_FORMAT", "webp");
+        env::set_var("SCREENSHOT_BACKEND", "graphics_capture");
         env::set_var("UIA_CACHE_TTL_MS", "5000");
         env::set_var("WS_RECONNECT_MAX_MS", "60000");
+        env::set_var("WS_CHUNK_BYTES", "65536");
         env::set_var("DETECTION_ENABLED", "false");
         env::set_var("DETECTION_MODEL_PATH", "/opt/models/custom.onnx");
+        env::set_var("ASSET_MANIFEST_URL", "https://models.example.com/manifest.json");
+        env::set_var("ASSET_SIGNING_KEY", "c2lnbmluZy1rZXk=");
         env::set_var("DETECTION_CONFIDENCE", "0.5");
         env::set_var("DETECTION_INPUT_SIZE", "640");
+        env::set_var("SAFE_MODE", "true");
+        env::set_var("SAFE_MODE_FLAG_PATH", "/tmp/desktopai_safe_mode");
+        env::set_var("COLLECTOR_ID", "lab-pc-01");
+        env::set_var("COLLECTOR_ID_PATH", "/tmp/desktopai_collector_id");
+        env::set_var("COLLECTOR_NAME", "Lab PC");
+        env::set_var("TIME_SYNC_INTERVAL_S", "0");
+        env::set_var("ENRICH_STAGES", "title, uia ,,detection");
+        env::set_var("GEOMETRY_ENABLED", "false");
+        env::set_var("REDACTION_ENABLED", "true");
+        env::set_var("REDACT_PATTERN", "secret");
+        env::set_var("DEEP_CAPTURE_ON_IDLE", "true");
+        env::set_var("DEEP_CAPTURE_INTERVAL_S", "0");
+        env::set_var("DEEP_UIA_MAX_DEPTH", "20");
+        env::set_var("DEEP_UIA_TEXT_MAX_CHARS", "10000");
+        env::set_var("WINDOW_INVENTORY_INTERVAL_MS", "0");
+        env::set_var("SELF_EXCLUDE_PROCESSES", "desktopai.exe, DesktopAI-dev.exe");
+        env::set_var("ALLOW_SELF_TARGETING", "true");
+        env::set_var("USER_INTERRUPT_ENABLED", "false");
+        env::set_var("OBSERVE_MAX_PER_SEC", "0.5");
+        env::set_var("OBSERVE_CPU_BUDGET_PCT", "0");
+        env::set_var("POWER_PROFILES_ENABLED", "false");
+        env::set_var("BATTERY_OBSERVE_MAX_PER_SEC", "0.2");
+        env::set_var("BATTERY_SCREENSHOT_MAX_WIDTH", "800");
+        env::set_var("BATTERY_SCREENSHOT_MAX_HEIGHT", "600");
+        env::set_var("COMMAND_RECORD_DIR", "/tmp/desktopai_fixtures");
+        env::set_var("TEXT_NORMALIZE_ENABLED", "false");
+        env::set_var("LANGUAGE_DETECT_ENABLED", "false");
+        env::set_var("ICON_ENABLED", "false");
+        env::set_var("CAPTURE_TIMINGS", "true");
+        env::set_var("TELEMETRY_ENABLED", "1");
+        env::set_var("TELEMETRY_URL", "https://telemetry.example/v1/report");
+        env::set_var("TELEMETRY_INTERVAL_S", "10");
+        env::set_var("OTEL_EXPORTER_OTLP_ENDPOINT", "http://otel.example:4318");
+        env::set_var("OTEL_SERVICE_NAME", "desk-7");
+        env::set_var("EVENT_TAP_PORT", "8765");
+        env::set_var("EVENT_TAP_TOKEN", "tap-secret");
+        env::set_var("WEBHOOKS_PATH", "C:\\desktopai\\webhooks.json");
+        env::set_var("ROUTING_PATH", "C:\\desktopai\\routing.json");
+        env::set_var("EVENT_PROFILE", "work");
+        env::set_var("DATA_DIR", "D:\\DesktopAI");
+        env::set_var("DATA_MAX_MB", "64");
+        env::set_var("DATA_RETENTION_DAYS", "7");
 
         let config = Config::from_env();
 
@@ -480,22 +915,71 @@ mod tests {
         assert!(!config.idle_enabled);
         assert_eq!(config.idle_threshold, Duration::from_millis(120000));
         assert_eq!(config.idle_poll, Duration::from_millis(2000));
+        assert_eq!(config.idle_poll_max, Duration::from_millis(30_000));
         assert!(config.uia_enabled);
         assert_eq!(config.uia_throttle, Duration::from_millis(500));
         assert_eq!(config.uia_text_max, 500);
         assert_eq!(config.uia_max_depth, 10);
+        assert_eq!(config.uia_timeout_ms, 5000);
         assert!(config.enable_screenshot);
         assert_eq!(config.screenshot_max_width, 1920);
         assert_eq!(config.screenshot_max_height, 1080);
         assert_eq!(config.screenshot_quality, 90);
+        assert_eq!(config.screenshot_timeout_ms, 0);
         assert!(!config.command_enabled);
         assert_eq!(config.screenshot_format, "webp");
+        assert_eq!(config.screenshot_backend, "graphics_capture");
         assert_eq!(config.uia_cache_ttl_ms, 5000);
         assert_eq!(config.ws_reconnect_max_ms, 60000);
+        assert_eq!(config.ws_chunk_bytes, 65536);
         assert!(!config.detection_enabled);
         assert_eq!(config.detection_model_path, "/opt/models/custom.onnx");
+        assert_eq!(config.asset_manifest_url, "https://models.example.com/manifest.json");
+        assert_eq!(config.asset_signing_key, "c2lnbmluZy1rZXk=");
         assert!((config.detection_confidence - 0.5).abs() < f32::EPSILON);
         assert_eq!(config.detection_input_size, 640);
+        assert!(config.safe_mode);
+        assert_eq!(config.safe_mode_flag_path, "/tmp/desktopai_safe_mode");
+        assert_eq!(config.collector_id, "lab-pc-01");
+        assert_eq!(config.collector_id_path, "/tmp/desktopai_collector_id");
+        assert_eq!(config.collector_name, "Lab PC");
+        assert_eq!(config.time_sync_interval, Duration::ZERO);
+        assert_eq!(config.enrich_stages, vec!["title", "uia", "detection"]);
+        assert!(!config.geometry_enabled);
+        assert!(config.redaction_enabled);
+        assert_eq!(config.redact_pattern, "secret");
+        assert!(config.deep_capture_on_idle);
+        assert_eq!(config.deep_capture_interval, Duration::ZERO);
+        assert_eq!(config.deep_uia_max_depth, 20);
+        assert_eq!(config.deep_uia_text_max, 10000);
+        assert_eq!(config.window_inventory_interval, Duration::ZERO);
+        assert_eq!(config.self_exclude_processes, vec!["desktopai.exe", "DesktopAI-dev.exe"]);
+        assert!(config.allow_self_targeting);
+        assert!(!config.user_interrupt_enabled);
+        assert!((config.observe_max_per_sec - 0.5).abs() < f32::EPSILON);
+        assert!(config.observe_cpu_budget_pct.abs() < f32::EPSILON);
+        assert!(!config.power_profiles_enabled);
+        assert!((config.battery_observe_max_per_sec - 0.2).abs() < f32::EPSILON);
+        assert_eq!(config.battery_screenshot_max_width, 800);
+        assert_eq!(config.battery_screenshot_max_height, 600);
+        assert_eq!(config.command_record_dir, "/tmp/desktopai_fixtures");
+        assert!(!config.text_normalize_enabled);
+        assert!(!config.language_detect_enabled);
+        assert!(!config.icon_enabled);
+        assert!(config.capture_timings_enabled);
+        assert!(config.telemetry_enabled);
+        assert_eq!(config.telemetry_url, "https://telemetry.example/v1/report");
+        assert_eq!(config.telemetry_interval, Duration::from_secs(60));
+        assert_eq!(config.otlp_endpoint, "http://otel.example:4318");
+        assert_eq!(config.otel_service_name, "desk-7");
+        assert_eq!(config.event_tap_port, 8765);
+        assert_eq!(config.event_tap_token, "tap-secret");
+        assert_eq!(config.webhooks_path, "C:\\desktopai\\webhooks.json");
+        assert_eq!(config.routing_path, "C:\\desktopai\\routing.json");
+        assert_eq!(config.event_profile, "work");
+        assert_eq!(config.data_dir, "D:\\DesktopAI");
+        assert_eq!(config.data_max_mb, 64);
+        assert_eq!(config.data_retention_days, 7);
 
         // Cleanup
         env::remove_var("BACKEND_WS_URL");
@@ -504,22 +988,121 @@ mod tests {
         env::remove_var("IDLE_ENABLED");
         env::remove_var("IDLE_THRESHOLD_MS");
         env::remove_var("IDLE_POLL_MS");
+        env::remove_var("IDLE_POLL_MAX_MS");
         env::remove_var("UIA_ENABLED");
         env::remove_var("UIA_THROTTLE_MS");
         env::remove_var("UIA_TEXT_MAX_CHARS");
         env::remove_var("UIA_MAX_DEPTH");
+        env::remove_var("UIA_TIMEOUT_MS");
         env::remove_var("ENABLE_SCREENSHOT");
         env::remove_var("SCREENSHOT_MAX_WIDTH");
         env::remove_var("SCREENSHOT_MAX_HEIGHT");
         env::remove_var("SCREENSHOT_QUALITY");
+        env::remove_var("SCREENSHOT_TIMEOUT_MS");
         env::remove_var("COMMAND_BRIDGE_ENABLED");
         env::remove_var("SCREENSHOT_FORMAT");
+        env::remove_var("SCREENSHOT_BACKEND");
         env::remove_var("UIA_CACHE_TTL_MS");
         env::remove_var("WS_RECONNECT_MAX_MS");
+        env::remove_var("WS_CHUNK_BYTES");
         env::remove_var("DETECTION_ENABLED");
         env::remove_var("DETECTION_MODEL_PATH");
+        env::remove_var("ASSET_MANIFEST_URL");
+        env::remove_var("ASSET_SIGNING_KEY");
         env::remove_var("DETECTION_CONFIDENCE");
         env::remove_var("DETECTION_INPUT_SIZE");
+        env::remove_var("SAFE_MODE");
+        env::remove_var("SAFE_MODE_FLAG_PATH");
+        env::remove_var("COLLECTOR_ID");
+        env::remove_var("COLLECTOR_ID_PATH");
+        env::remove_var("COLLECTOR_NAME");
+        env::remove_var("TIME_SYNC_INTERVAL_S");
+        env::remove_var("ENRICH_STAGES");
+        env::remove_var("GEOMETRY_ENABLED");
+        env::remove_var("REDACTION_ENABLED");
+        env::remove_var("REDACT_PATTERN");
+        env::remove_var("DEEP_CAPTURE_ON_IDLE");
+        env::remove_var("DEEP_CAPTURE_INTERVAL_S");
+        env::remove_var("DEEP_UIA_MAX_DEPTH");
+        env::remove_var("DEEP_UIA_TEXT_MAX_CHARS");
+        env::remove_var("WINDOW_INVENTORY_INTERVAL_MS");
+        env::remove_var("SELF_EXCLUDE_PROCESSES");
+        env::remove_var("ALLOW_SELF_TARGETING");
+        env::remove_var("USER_INTERRUPT_ENABLED");
+        env::remove_var("OBSERVE_MAX_PER_SEC");
+        env::remove_var("OBSERVE_CPU_BUDGET_PCT");
+        env::remove_var("POWER_PROFILES_ENABLED");
+        env::remove_var("BATTERY_OBSERVE_MAX_PER_SEC");
+        env::remove_var("BATTERY_SCREENSHOT_MAX_WIDTH");
+        env::remove_var("BATTERY_SCREENSHOT_MAX_HEIGHT");
+        env::remove_var("COMMAND_RECORD_DIR");
+        env::remove_var("TEXT_NORMALIZE_ENABLED");
+        env::remove_var("LANGUAGE_DETECT_ENABLED");
+        env::remove_var("ICON_ENABLED");
+        env::remove_var("CAPTURE_TIMINGS");
+        env::remove_var("TELEMETRY_ENABLED");
+        env::remove_var("TELEMETRY_URL");
+        env::remove_var("TELEMETRY_INTERVAL_S");
+        env::remove_var("OTEL_EXPORTER_OTLP_ENDPOINT");
+        env::remove_var("OTEL_SERVICE_NAME");
+        env::remove_var("EVENT_TAP_PORT");
+        env::remove_var("EVENT_TAP_TOKEN");
+        env::remove_var("WEBHOOKS_PATH");
+        env::remove_var("ROUTING_PATH");
+        env::remove_var("EVENT_PROFILE");
+        env::remove_var("DATA_DIR");
+        env::remove_var("DATA_MAX_MB");
+        env::remove_var("DATA_RETENTION_DAYS");
+    }
+
+    #[test]
+    fn test_detection_model_path_follows_asset_manager() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        env::remove_var("DETECTION_MODEL_PATH");
+        env::set_var("DATA_DIR", "D:\\DesktopAI");
+        env::set_var("ASSET_MANIFEST_URL", "https://models.example.com/manifest.json");
+        assert_eq!(Config::from_env().detection_model_path, "D:\\DesktopAI\\models\\ui-detr\\ui-detr-1.onnx");
+        env::remove_var("ASSET_MANIFEST_URL");
+        assert_eq!(Config::from_env().detection_model_path, "models/ui-detr/ui-detr-1.onnx", "not downloaded, not coming");
+        env::remove_var("DATA_DIR");
+    }
+
+    #[test]
+    fn test_deep_capture_config() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let mut config = Config::from_env();
+        config.uia_max_depth = 3;
+        config.deep_uia_max_depth = 15;
+        config.enrich_stages = vec!["title".to_string(), "redaction".to_string()];
+
+        let deep = config.deep_capture();
+        assert_eq!(deep.uia_max_depth, 15);
+        assert_eq!(deep.uia_throttle, Duration::ZERO);
+        assert_eq!((deep.uia_timeout_ms, deep.screenshot_timeout_ms), (0, 0));
+        assert_eq!(deep.screenshot_max_width, u32::MAX);
+        assert!(deep.enrich_stages.iter().any(|s| s == "detection"));
+        assert_eq!(deep.enrich_stages.last().map(String::as_str), Some("redaction"));
+        // Cheap path untouched
+        assert_eq!(config.uia_max_depth, 3);
+    }
+
+    #[test]
+    fn test_battery_capture_config() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let mut config = Config::from_env();
+        config.observe_max_per_sec = 2.0;
+        config.battery_observe_max_per_sec = 0.5;
+        config.screenshot_max_width = 1024;
+        config.screenshot_max_height = 400;
+        config.battery_screenshot_max_width = 640;
+        config.battery_screenshot_max_height = 480;
+        config.detection_enabled = true;
+
+        let battery = config.battery_capture();
+        assert!((battery.observe_max_per_sec - 0.5).abs() < f32::EPSILON);
+        assert_eq!((battery.screenshot_max_width, battery.screenshot_max_height), (640, 400));
+        assert!(!battery.detection_enabled);
+        assert!(config.detection_enabled, "AC profile untouched");
     }
 
     #[test]
@@ -0,0 +1,289 @@
+//! Managed data directory: everything the collector keeps on disk.
+//!
+//! `DATA_DIR` (default `%LOCALAPPDATA%\DesktopAI`) holds the collector id,
+//! the safe-mode marker, imported settings and webhooks, downloaded models
+//! and any spill such as logs or command records written below it. Files at
+//! the top level and under `models` and `workflows` are kept; everything
+//! else is pruned once older than `DATA_RETENTION_DAYS`, and oldest first
+//! while the prunable files exceed `DATA_MAX_MB`.
+//!
+//! `purge` removes the whole directory (and `COMMAND_RECORD_DIR` when it
+//! lives elsewhere), overwriting each file with zeros before unlinking it.
+//! Symbolic links are unlinked without writing through them or following
+//! them, so nothing outside the directory is touched. On SSDs and
+//! copy-on-write filesystems the overwrite is best effort.
+
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::Config;
+
+/// How often the retention sweep runs.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Subdirectories whose contents retention never touches.
+const KEPT_DIRS: &[&str] = &["models", "workflows"];
+
+/// The data directory: `DATA_DIR`, else `DesktopAI` under `LOCALAPPDATA`.
+/// Read from the environment only, since saved settings live inside it.
+pub fn root() -> Option<String> {
+    if let Ok(dir) = std::env::var("DATA_DIR") {
+        return Some(dir).filter(|dir| !dir.is_empty());
+    }
+    std::env::var("LOCALAPPDATA").ok().map(|dir| format!("{dir}\\DesktopAI"))
+}
+
+/// One file found in a managed directory.
+#[derive(Debug, Clone)]
+pub struct DataFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub modified: SystemTime,
+    /// Identity, settings, models and workflows: never pruned by retention.
+    pub kept: bool,
+    /// A symbolic link (to a file or directory), never followed.
+    pub link: bool,
+}
+
+/// Every file and symbolic link below `dir`; top-level files and
+/// `KEPT_DIRS` are marked kept.
+pub fn scan(dir: &Path) -> Vec<DataFile> {
+    fn walk(dir: &Path, kept: Option<bool>, files: &mut Vec<DataFile>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if meta.is_dir() {
+                let kept = kept.unwrap_or_else(|| KEPT_DIRS.iter().any(|name| entry.file_name() == *name));
+                walk(&path, Some(kept), files);
+            } else {
+                files.push(DataFile {
+                    path,
+                    bytes: meta.len(),
+                    modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    kept: kept.unwrap_or(true),
+                    link: meta.file_type().is_symlink(),
+                });
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, None, &mut files);
+    files
+}
+
+/// Files retention should delete: prunable ones older than `max_age`, then
+/// the oldest remaining prunable ones until those fit in `max_bytes`.
+pub fn plan_cleanup(
+    files: &[DataFile],
+    now: SystemTime,
+    max_age: Option<Duration>,
+    max_bytes: Option<u64>,
+) -> Vec<PathBuf> {
+    let mut prunable: Vec<&DataFile> = files.iter().filter(|f| !f.kept).collect();
+    prunable.sort_by_key(|f| f.modified);
+    let expired = |f: &DataFile| {
+        max_age.is_some_and(|max| now.duration_since(f.modified).unwrap_or_default() > max)
+    };
+    let mut remaining: u64 = prunable.iter().filter(|f| !expired(f)).map(|f| f.bytes).sum();
+    let mut doomed = Vec::new();
+    for file in prunable {
+        if expired(file) {
+            doomed.push(file.path.clone());
+        } else if max_bytes.is_some_and(|max| remaining > max) {
+            remaining -= file.bytes;
+            doomed.push(file.path.clone());
+        }
+    }
+    doomed
+}
+
+/// Apply retention to the managed directories once. Returns files deleted.
+pub fn sweep(config: &Config) -> usize {
+    let max_age = (config.data_retention_days > 0).then(|| Duration::from_secs(config.data_retention_days * 86_400));
+    let max_bytes = (config.data_max_mb > 0).then(|| config.data_max_mb * 1024 * 1024);
+    let mut deleted = 0;
+    for dir in managed_dirs(config) {
+        for path in plan_cleanup(&scan(&dir), SystemTime::now(), max_age, max_bytes) {
+            match fs::remove_file(&path) {
+                Ok(()) => deleted += 1,
+                Err(e) => log::debug!("Retention could not delete {}: {e}", path.display()),
+            }
+        }
+    }
+    deleted
+}
+
+/// Background thread running `sweep` at startup and then hourly.
+pub fn retention_worker(config: Config) {
+    loop {
+        let deleted = sweep(&config);
+        if deleted > 0 {
+            log::info!("Data retention removed {deleted} file(s)");
+        }
+        std::thread::sleep(SWEEP_INTERVAL);
+    }
+}
+
+pub fn retention_enabled(config: &Config) -> bool {
+    !config.data_dir.is_empty() && (config.data_retention_days > 0 || config.data_max_mb > 0)
+}
+
+/// The data directory plus `COMMAND_RECORD_DIR` when it lives elsewhere.
+fn managed_dirs(config: &Config) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if !config.data_dir.is_empty() {
+        dirs.push(PathBuf::from(&config.data_dir));
+    }
+    if !config.command_record_dir.is_empty() {
+        let records = PathBuf::from(&config.command_record_dir);
+        if !dirs.iter().any(|dir| records.starts_with(dir)) {
+            dirs.push(records);
+        }
+    }
+    dirs
+}
+
+/// What `purge` removed (or would remove, on a dry run).
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeSummary {
+    pub directories: Vec<String>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Overwrite a regular file with zeros, flush it to disk and delete it.
+/// Anything else, such as a symbolic link, is only unlinked.
+fn shred(path: &Path) -> std::io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.file_type().is_symlink() {
+        // Windows removes links to directories as directories
+        return fs::remove_file(path).or_else(|_| fs::remove_dir(path));
+    }
+    if !meta.is_file() {
+        return fs::remove_file(path);
+    }
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 64 * 1024];
+    let mut left = meta.len();
+    while left > 0 {
+        let chunk = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        left -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+/// Securely delete every managed directory. With `dry_run`, only report.
+pub fn purge(config: &Config, dry_run: bool) -> Result<PurgeSummary, String> {
+    let mut summary = PurgeSummary::default();
+    for dir in managed_dirs(config).into_iter().filter(|dir| dir.exists()) {
+        for file in scan(&dir) {
+            if !dry_run {
+                shred(&file.path).map_err(|e| format!("cannot delete {}: {e}", file.path.display()))?;
+            }
+            summary.files += 1;
+            summary.bytes += file.bytes;
+        }
+        if !dry_run {
+            fs::remove_dir_all(&dir).map_err(|e| format!("cannot remove {}: {e}", dir.display()))?;
+        }
+        summary.directories.push(dir.display().to_string());
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, bytes: u64, age_days: u64, kept: bool, now: SystemTime) -> DataFile {
+        DataFile {
+            path: PathBuf::from(name),
+            bytes,
+            modified: now - Duration::from_secs(age_days * 86_400),
+            kept,
+            link: false,
+        }
+    }
+
+    #[test]
+    fn test_plan_cleanup_age_then_quota() {
+        let now = SystemTime::now();
+        let files = [
+            file("collector_id", 36, 400, true, now),
+            file("records/old.json", 10, 40, false, now),
+            file("records/mid.json", 30, 5, false, now),
+            file("records/new.json", 30, 1, false, now),
+            file("models/ui-detr.onnx", 1000, 90, true, now),
+        ];
+        let month = Some(Duration::from_secs(30 * 86_400));
+        assert_eq!(plan_cleanup(&files, now, month, None), vec![PathBuf::from("records/old.json")]);
+        assert_eq!(
+            plan_cleanup(&files, now, month, Some(40)),
+            vec![PathBuf::from("records/old.json"), PathBuf::from("records/mid.json")]
+        );
+        assert!(plan_cleanup(&files, now, None, Some(100)).is_empty());
+    }
+
+    #[test]
+    fn test_scan_and_purge() {
+        let root = std::env::temp_dir().join(format!("desktopai-data-{}", std::process::id()));
+        fs::create_dir_all(root.join("records")).unwrap();
+        fs::create_dir_all(root.join("models").join("ui-detr")).unwrap();
+        fs::create_dir_all(root.join("workflows")).unwrap();
+        fs::write(root.join("collector_id"), "id").unwrap();
+        fs::write(root.join("records").join("a.json"), "{}").unwrap();
+        fs::write(root.join("models").join("ui-detr").join("m.onnx"), "weights").unwrap();
+        fs::write(root.join("workflows").join("w.yaml"), "steps: []").unwrap();
+
+        let mut files = scan(&root);
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let kept: Vec<bool> = files.iter().map(|f| f.kept).collect();
+        assert_eq!(kept, vec![true, true, false, true]);
+
+        let mut config = Config::from_env();
+        config.data_dir = root.to_string_lossy().into_owned();
+        config.command_record_dir = root.join("records").to_string_lossy().into_owned();
+        let planned = purge(&config, true).unwrap();
+        assert_eq!((planned.files, planned.bytes, planned.directories.len()), (4, 20, 1));
+        assert!(root.exists());
+
+        purge(&config, false).unwrap();
+        assert!(!root.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_unlinks_symlinks_without_touching_targets() {
+        let base = std::env::temp_dir().join(format!("desktopai-links-{}", std::process::id()));
+        let (root, outside) = (base.join("data"), base.join("outside"));
+        fs::create_dir_all(root.join("records")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("keep.txt"), "precious").unwrap();
+        std::os::unix::fs::symlink(outside.join("keep.txt"), root.join("records").join("file-link")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("dir-link")).unwrap();
+
+        let mut files = scan(&root);
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let found: Vec<(bool, bool)> = files.iter().map(|f| (f.kept, f.link)).collect();
+        assert_eq!(found, vec![(true, true), (false, true)], "links are listed, not followed");
+
+        let mut config = Config::from_env();
+        config.data_dir = root.to_string_lossy().into_owned();
+        config.command_record_dir = String::new();
+        purge(&config, false).unwrap();
+        assert!(!root.exists());
+        assert_eq!(fs::read_to_string(outside.join("keep.txt")).unwrap(), "precious");
+        let _ = fs::remove_dir_all(&base);
+    }
+}
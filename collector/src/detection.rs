@@ -8,10 +8,34 @@
 use ndarray::Array4;
 use serde::Serialize;
 use std::path::Path;
+use std::sync::OnceLock;
 use std::time::Instant;
 
 use ort::session::Session;
 
+use crate::config::Config;
+
+static SHARED_DETECTOR: OnceLock<Option<Detector>> = OnceLock::new();
+
+/// Process-wide detector, loaded on first use from the configured model path.
+/// Returns `None` if the model could not be loaded (checked only once), or
+/// while the file is not there yet (e.g. still being downloaded, see `assets`).
+pub fn shared_detector(config: &Config) -> Option<&'static Detector> {
+    if SHARED_DETECTOR.get().is_none() && !Path::new(&config.detection_model_path).exists() {
+        log::debug!("Detection model '{}' not present yet", config.detection_model_path);
+        return None;
+    }
+    SHARED_DETECTOR
+        .get_or_init(|| {
+            let d = Detector::new(&config.detection_model_path, config.detection_confidence, config.detection_input_size);
+            if d.is_none() {
+                log::warn!("Detection model not loaded from '{}' — detection disabled", config.detection_model_path);
+            }
+            d
+        })
+        .as_ref()
+}
+
 /// A single detected UI element with normalized coordinates.
 #[derive(Debug, Clone, Serialize)]
 pub struct Detection {
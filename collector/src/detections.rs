@@ -0,0 +1,103 @@
+//! The last detection pass, so detector boxes can be acted on by id.
+//!
+//! The detector reports boxes normalized to the captured monitor, and the
+//! backend refers to them by their position in that list. Each pass is
+//! remembered with the screen rectangle of the monitor it ran on, so
+//! `click_detection` can turn an id (or a normalized box) back into
+//! absolute screen pixels, even after the window has moved to another
+//! monitor.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// One detection pass: the monitor it captured and its boxes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pass {
+    /// Screen rectangle `[x, y, width, height]` of the captured monitor.
+    pub monitor: [i32; 4],
+    /// Normalized `[x, y, width, height]` boxes, indexed by detection id.
+    pub boxes: Vec<[f32; 4]>,
+}
+
+impl Pass {
+    /// Screen rectangle of detection `id`.
+    pub fn rect(&self, id: usize) -> Option<[i32; 4]> {
+        self.boxes.get(id).map(|&b| to_screen(self.monitor, b))
+    }
+}
+
+static LAST: Mutex<Option<Pass>> = Mutex::new(None);
+
+/// Remember a detection pass over `monitor`, replacing the previous one.
+#[cfg_attr(not(all(windows, feature = "detection")), allow(dead_code))]
+pub fn remember(monitor: [i32; 4], boxes: Vec<[f32; 4]>) {
+    *LAST.lock().unwrap_or_else(|e| e.into_inner()) = Some(Pass { monitor, boxes });
+}
+
+/// The most recent detection pass, if any ran.
+pub fn last() -> Option<Pass> {
+    LAST.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Screen rectangle of normalized box `b` on `monitor`.
+pub fn to_screen(monitor: [i32; 4], b: [f32; 4]) -> [i32; 4] {
+    let [mx, my, mw, mh] = monitor;
+    let scale = |fraction: f32, size: i32| (fraction * size as f32).round() as i32;
+    [mx + scale(b[0], mw), my + scale(b[1], mh), scale(b[2], mw), scale(b[3], mh)]
+}
+
+/// Center of screen rectangle `rect`.
+pub fn center(rect: [i32; 4]) -> (i32, i32) {
+    let [x, y, w, h] = rect;
+    (x + w / 2, y + h / 2)
+}
+
+/// Parse a normalized box given as `[x, y, width, height]` or as a
+/// detection object with those fields; it must lie within the monitor.
+pub fn parse_box(value: &serde_json::Value) -> Result<[f32; 4], String> {
+    let fields: Vec<Option<f64>> = match value {
+        serde_json::Value::Array(items) if items.len() == 4 => items.iter().map(|v| v.as_f64()).collect(),
+        serde_json::Value::Object(map) => {
+            ["x", "y", "width", "height"].iter().map(|k| map.get(*k).and_then(|v| v.as_f64())).collect()
+        }
+        _ => vec![None],
+    };
+    let [Some(x), Some(y), Some(w), Some(h)] = fields[..] else {
+        return Err("box must be [x, y, width, height] or an object with those fields".to_string());
+    };
+    let inside = (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) && w > 0.0 && h > 0.0;
+    if !inside || x + w > 1.0 + 1e-3 || y + h > 1.0 + 1e-3 {
+        return Err("box must be normalized to 0..1 with a positive width and height".to_string());
+    }
+    Ok([x as f32, y as f32, w as f32, h as f32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxes_map_to_the_captured_monitor() {
+        // A second monitor left of the primary one
+        let monitor = [-1920, 0, 1920, 1080];
+        assert_eq!(to_screen(monitor, [0.5, 0.5, 0.25, 0.1]), [-960, 540, 480, 108]);
+        assert_eq!(center(to_screen(monitor, [0.0, 0.0, 0.1, 0.1])), (-1824, 54));
+
+        remember(monitor, vec![[0.0, 0.0, 0.1, 0.1], [0.5, 0.5, 0.25, 0.1]]);
+        let pass = last().unwrap();
+        assert_eq!(pass.rect(1), Some([-960, 540, 480, 108]));
+        assert_eq!(pass.rect(2), None);
+    }
+
+    #[test]
+    fn test_parse_box() {
+        assert_eq!(parse_box(&serde_json::json!([0.1, 0.2, 0.3, 0.4])), Ok([0.1, 0.2, 0.3, 0.4]));
+        let detection = serde_json::json!({"x": 0.5, "y": 0.5, "width": 0.1, "height": 0.1, "confidence": 0.9});
+        assert_eq!(parse_box(&detection), Ok([0.5, 0.5, 0.1, 0.1]));
+        assert!(parse_box(&serde_json::json!([0.1, 0.2, 0.3])).is_err());
+        assert!(parse_box(&serde_json::json!([120, 40, 30, 20])).is_err(), "pixels, not fractions");
+        assert!(parse_box(&serde_json::json!([0.9, 0.9, 0.2, 0.05])).is_err());
+        assert!(parse_box(&serde_json::json!([0.1, 0.1, 0.0, 0.1])).is_err());
+    }
+}
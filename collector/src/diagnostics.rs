@@ -0,0 +1,437 @@
+//! Diagnostic bundles for bug reports.
+//!
+//! `create_diagnostic_bundle` packs what a maintainer needs to reproduce a
+//! problem into one AES-256 encrypted zip the user can attach to an issue,
+//! sharing the password separately. Each category goes in only when the
+//! user agreed to it, by naming it in `include`;
+//! `scripts/diagnostic_bundle.py` asks about each one in turn:
+//!
+//! - `events`: the last `RECENT_EVENTS` events the collector handed on,
+//!   without screenshots or icons.
+//! - `logs`: the last `RECENT_LOG_LINES` log lines, kept in memory at info
+//!   level even when `RUST_LOG` prints less.
+//! - `config`: the explicitly set settings, as `export_state` reports them,
+//!   without webhooks (their URLs and headers often carry secrets).
+//! - `metrics`: hook health, the power profile, the telemetry aggregate
+//!   pending upload and counts of the recent events by type.
+//! - `screenshots`: the frames still held in the screenshot ring buffer.
+//!
+//! Event text and log lines are masked with `REDACT_PATTERN` whether or not
+//! redaction is enabled for the live stream. A `manifest.json` records which
+//! categories were included and which were left out. Nothing is kept on
+//! disk beyond the bundle, which is written below the data directory unless
+//! an absolute `path` is given, and is pruned there like other spill.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use zeroize::Zeroizing;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+use crate::pipeline::RedactionStage;
+
+/// Data categories a bundle can hold, in the order they are offered.
+pub const CATEGORIES: &[&str] = &["events", "logs", "config", "metrics", "screenshots"];
+/// Events kept for a bundle.
+const RECENT_EVENTS: usize = 50;
+/// Log lines kept for a bundle.
+const RECENT_LOG_LINES: usize = 1000;
+/// Shortest accepted bundle password.
+const MIN_PASSWORD_CHARS: usize = 8;
+/// Subdirectory of the data directory bundles are written to.
+const BUNDLE_DIR: &str = "diagnostics";
+pub const MANIFEST_FORMAT: &str = "desktopai-diagnostics";
+
+static EVENTS: Mutex<VecDeque<WindowEvent>> = Mutex::new(VecDeque::new());
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+fn push_bounded<T>(ring: &Mutex<VecDeque<T>>, item: T, capacity: usize) {
+    let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+    if ring.len() >= capacity {
+        ring.pop_front();
+    }
+    ring.push_back(item);
+}
+
+/// Keep an outgoing event for a later bundle, without its images.
+pub fn record_event(event: &WindowEvent) {
+    let mut event = event.clone();
+    event.screenshot_b64 = None;
+    event.icon_png_b64 = None;
+    push_bounded(&EVENTS, event, RECENT_EVENTS);
+}
+
+/// Keep a log line for a later bundle.
+pub fn record_log_line(line: String) {
+    push_bounded(&LOG_LINES, line, RECENT_LOG_LINES);
+}
+
+fn recent<T: Clone>(ring: &Mutex<VecDeque<T>>) -> Vec<T> {
+    ring.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+/// Prints what `RUST_LOG` selects, like `env_logger::init`, and keeps info
+/// and above for bundles.
+struct CapturingLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() <= log::Level::Info {
+            let timestamp = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+            record_log_line(format!("{timestamp} {:<5} {}: {}", record.level(), record.target(), record.args()));
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the collector's logger: `env_logger` output plus the in-memory
+/// lines bundles draw on.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn init_logging() {
+    let inner = env_logger::Logger::from_default_env();
+    let max_level = inner.filter().max(log::LevelFilter::Info);
+    if log::set_boxed_logger(Box::new(CapturingLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// A bundle the user asked for.
+#[derive(Debug)]
+pub struct BundleRequest {
+    /// Consented categories, in `CATEGORIES` order.
+    pub include: Vec<&'static str>,
+    pub password: Zeroizing<String>,
+    pub path: PathBuf,
+}
+
+impl BundleRequest {
+    /// Read `include`, `password` and `path` from command parameters.
+    pub fn parse(parameters: &HashMap<String, serde_json::Value>, config: &Config) -> Result<Self, String> {
+        let listed: Vec<&str> = match parameters.get("include").and_then(|v| v.as_array()) {
+            Some(items) => items.iter().map(|v| v.as_str().ok_or("include must list category names")).collect::<Result<_, _>>()?,
+            None => return Err(format!("include must list the categories to bundle: {}", CATEGORIES.join(", "))),
+        };
+        if let Some(unknown) = listed.iter().find(|name| !CATEGORIES.contains(name)) {
+            return Err(format!("unknown category '{unknown}' (expected {})", CATEGORIES.join(", ")));
+        }
+        let include: Vec<&'static str> = CATEGORIES.iter().copied().filter(|c| listed.contains(c)).collect();
+        if include.is_empty() {
+            return Err("nothing to bundle: include at least one category".to_string());
+        }
+
+        let password = Zeroizing::new(parameters.get("password").and_then(|v| v.as_str()).unwrap_or("").to_string());
+        if password.chars().count() < MIN_PASSWORD_CHARS {
+            return Err(format!("password must be at least {MIN_PASSWORD_CHARS} characters"));
+        }
+
+        let path = match parameters.get("path").and_then(|v| v.as_str()).map(str::trim).filter(|p| !p.is_empty()) {
+            Some(path) if Path::new(path).is_absolute() => PathBuf::from(path),
+            Some(path) => return Err(format!("path must be absolute: {path}")),
+            None if !config.data_dir.is_empty() => Path::new(&config.data_dir)
+                .join(BUNDLE_DIR)
+                .join(format!("desktopai-diagnostics-{}.zip", chrono::Utc::now().format("%Y%m%d-%H%M%S"))),
+            None => return Err("no data directory: pass an absolute path".to_string()),
+        };
+        Ok(Self { include, password, path })
+    }
+}
+
+/// What goes into a bundle, before consent and redaction are applied.
+#[derive(Default)]
+pub struct Sources {
+    pub events: Vec<WindowEvent>,
+    pub log_lines: Vec<String>,
+    pub config: Option<crate::state::StateArchive>,
+    pub metrics: serde_json::Value,
+    pub screenshots: Vec<Zeroizing<Vec<u8>>>,
+}
+
+impl Sources {
+    /// What this collector holds now, for the categories in `include`.
+    pub fn collect(config: &Config, include: &[&str]) -> Self {
+        let wanted = |category: &str| include.contains(&category);
+        let events = recent(&EVENTS);
+        let mut sources = Self::default();
+        if wanted("metrics") {
+            let mut event_types: BTreeMap<&str, u64> = BTreeMap::new();
+            for event in &events {
+                *event_types.entry(event.event_type.as_str()).or_default() += 1;
+            }
+            sources.metrics = serde_json::json!({
+                "hook": crate::hook::health(),
+                "power": crate::power::report(config),
+                "telemetry": crate::telemetry::pending_report(),
+                "recent_event_types": event_types,
+            });
+        }
+        if wanted("events") {
+            sources.events = events;
+        }
+        if wanted("logs") {
+            sources.log_lines = recent(&LOG_LINES);
+        }
+        if wanted("config") {
+            sources.config = Some(crate::state::build_archive(|key| crate::config::setting(key).ok(), None));
+        }
+        #[cfg(windows)]
+        if wanted("screenshots") {
+            sources.screenshots = crate::screenshot::retained_frames();
+        }
+        sources
+    }
+}
+
+/// Contents of `manifest.json`.
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    format: &'static str,
+    version: u32,
+    created_at: String,
+    collector_version: &'static str,
+    platform: &'static str,
+    included: &'a [&'static str],
+    excluded: Vec<&'static str>,
+    /// Entries per included category.
+    counts: BTreeMap<&'static str, usize>,
+}
+
+/// A written bundle.
+#[derive(Debug, Serialize)]
+pub struct BundleSummary {
+    pub path: String,
+    pub included: Vec<&'static str>,
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+fn redact_event(stage: Option<&RedactionStage>, event: &mut WindowEvent) {
+    let Some(stage) = stage else {
+        return;
+    };
+    stage.redact_event(event);
+    for change in &mut event.window_changes {
+        stage.redact(&mut change.title);
+        if let Some(previous) = change.previous_title.as_mut() {
+            stage.redact(previous);
+        }
+    }
+}
+
+/// Redact `sources` and write the consented categories to an encrypted zip
+/// at `request.path`; an existing file is never replaced.
+pub fn write_bundle(request: &BundleRequest, sources: Sources, config: &Config) -> Result<BundleSummary, String> {
+    let stage = RedactionStage::new(&config.redact_pattern);
+    let mut entries: Vec<(String, Zeroizing<Vec<u8>>)> = Vec::new();
+    let mut counts = BTreeMap::new();
+    for &category in &request.include {
+        match category {
+            "events" => {
+                let mut lines = String::new();
+                for mut event in sources.events.iter().cloned() {
+                    redact_event(stage.as_ref(), &mut event);
+                    lines.push_str(&serde_json::to_string(&event).unwrap_or_default());
+                    lines.push('\n');
+                }
+                counts.insert(category, sources.events.len());
+                entries.push(("events.jsonl".to_string(), Zeroizing::new(lines.into_bytes())));
+            }
+            "logs" => {
+                let mut text = String::new();
+                for line in &sources.log_lines {
+                    let mut line = line.clone();
+                    if let Some(stage) = stage.as_ref() {
+                        stage.redact(&mut line);
+                    }
+                    text.push_str(&line);
+                    text.push('\n');
+                }
+                counts.insert(category, sources.log_lines.len());
+                entries.push(("logs.txt".to_string(), Zeroizing::new(text.into_bytes())));
+            }
+            "config" => {
+                let text = serde_json::to_vec_pretty(&sources.config).unwrap_or_default();
+                counts.insert(category, usize::from(sources.config.is_some()));
+                entries.push(("config.json".to_string(), Zeroizing::new(text)));
+            }
+            "metrics" => {
+                counts.insert(category, 1);
+                entries.push(("metrics.json".to_string(), Zeroizing::new(serde_json::to_vec_pretty(&sources.metrics).unwrap_or_default())));
+            }
+            "screenshots" => {
+                counts.insert(category, sources.screenshots.len());
+                for (i, frame) in sources.screenshots.iter().enumerate() {
+                    entries.push((format!("screenshots/frame-{}.jpg", i + 1), frame.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let manifest = Manifest {
+        format: MANIFEST_FORMAT,
+        version: 1,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        collector_version: env!("CARGO_PKG_VERSION"),
+        platform: std::env::consts::OS,
+        included: &request.include,
+        excluded: CATEGORIES.iter().copied().filter(|c| !request.include.contains(c)).collect(),
+        counts,
+    };
+    entries.insert(0, ("manifest.json".to_string(), Zeroizing::new(serde_json::to_vec_pretty(&manifest).unwrap_or_default())));
+
+    let path = &request.path;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| format!("cannot create {}: {e}", path.display()))?;
+    let written = write_zip(file, &entries, &request.password);
+    let bytes = match written.and_then(|file| file.metadata().map_err(|e| e.to_string())) {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            let _ = std::fs::remove_file(path);
+            return Err(format!("cannot write {}: {e}", path.display()));
+        }
+    };
+    Ok(BundleSummary {
+        path: path.to_string_lossy().into_owned(),
+        included: request.include.clone(),
+        files: entries.into_iter().map(|(name, _)| name).collect(),
+        bytes,
+    })
+}
+
+fn write_zip(file: std::fs::File, entries: &[(String, Zeroizing<Vec<u8>>)], password: &str) -> Result<std::fs::File, String> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .with_aes_encryption(AesMode::Aes256, password);
+    let mut zip = ZipWriter::new(file);
+    for (name, data) in entries {
+        zip.start_file(name.as_str(), options).map_err(|e| e.to_string())?;
+        zip.write_all(data).map_err(|e| e.to_string())?;
+    }
+    let file = zip.finish().map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+/// Collect and write the bundle `request` describes.
+pub fn create(request: &BundleRequest, config: &Config) -> Result<BundleSummary, String> {
+    let sources = Sources::collect(config, &request.include);
+    let summary = write_bundle(request, sources, config)?;
+    log::info!("Wrote diagnostic bundle {} ({})", summary.path, summary.included.join(", "));
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+    use std::io::Read;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("desktopai-diagnostics-{}-{name}.zip", uuid::Uuid::new_v4()))
+    }
+
+    fn read_entry(path: &Path, name: &str, password: &str) -> String {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut entry = archive.by_name_decrypt(name, password.as_bytes()).unwrap();
+        let mut text = String::new();
+        entry.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_request_needs_consent_and_a_password() {
+        let mut config = Config::from_env();
+        config.data_dir = String::new();
+        let parse = |parameters: serde_json::Value| BundleRequest::parse(&serde_json::from_value(parameters).unwrap(), &config);
+        let path = temp_path("parse");
+        let path = path.to_str().unwrap();
+
+        let request = parse(serde_json::json!({"include": ["logs", "events"], "password": "correct horse", "path": path})).unwrap();
+        assert_eq!(request.include, ["events", "logs"], "offered order");
+        assert!(parse(serde_json::json!({"password": "correct horse", "path": path})).unwrap_err().contains("include"));
+        assert!(parse(serde_json::json!({"include": [], "password": "correct horse", "path": path})).unwrap_err().contains("nothing"));
+        assert!(parse(serde_json::json!({"include": ["history"], "password": "correct horse", "path": path})).unwrap_err().contains("unknown"));
+        assert!(parse(serde_json::json!({"include": ["logs"], "password": "short", "path": path})).unwrap_err().contains("password"));
+        assert!(parse(serde_json::json!({"include": ["logs"], "password": "correct horse", "path": "bundle.zip"})).is_err());
+        assert!(parse(serde_json::json!({"include": ["logs"], "password": "correct horse"})).unwrap_err().contains("data directory"));
+
+        config.data_dir = std::env::temp_dir().to_string_lossy().into_owned();
+        let parameters = serde_json::from_value(serde_json::json!({"include": ["logs"], "password": "correct horse"})).unwrap();
+        let request = BundleRequest::parse(&parameters, &config).unwrap();
+        assert!(request.path.starts_with(std::env::temp_dir().join(BUNDLE_DIR)));
+    }
+
+    #[test]
+    fn test_bundle_holds_only_consented_redacted_data() {
+        let mut config = Config::from_env();
+        config.redact_pattern = "hunter2".to_string();
+        let path = temp_path("write");
+        let request = BundleRequest {
+            include: vec!["events", "logs", "screenshots"],
+            password: Zeroizing::new("correct horse".to_string()),
+            path: path.clone(),
+        };
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "password is hunter2".to_string();
+        let sources = Sources {
+            events: vec![event],
+            log_lines: vec!["INFO typed hunter2".to_string()],
+            config: Some(crate::state::build_archive(|_| None, None)),
+            screenshots: vec![Zeroizing::new(vec![0xFF, 0xD8, 0xFF])],
+            ..Default::default()
+        };
+
+        let summary = write_bundle(&request, sources, &config).unwrap();
+        assert_eq!(summary.files, ["manifest.json", "events.jsonl", "logs.txt", "screenshots/frame-1.jpg"]);
+        assert!(summary.bytes > 0);
+
+        let events = read_entry(&path, "events.jsonl", "correct horse");
+        assert!(events.contains("password is [REDACTED]") && !events.contains("hunter2"));
+        assert_eq!(read_entry(&path, "logs.txt", "correct horse"), "INFO typed [REDACTED]\n");
+        let manifest: serde_json::Value = serde_json::from_str(&read_entry(&path, "manifest.json", "correct horse")).unwrap();
+        assert_eq!(manifest["included"], serde_json::json!(["events", "logs", "screenshots"]));
+        assert_eq!(manifest["excluded"], serde_json::json!(["config", "metrics"]));
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert!(archive.by_name("events.jsonl").is_err(), "entries are encrypted");
+        assert!(archive.by_name_decrypt("events.jsonl", b"wrong password").is_err());
+
+        let again = BundleRequest { include: vec!["logs"], password: request.password.clone(), path: path.clone() };
+        assert!(write_bundle(&again, Sources::default(), &config).is_err(), "never overwrites");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recent_events_drop_images() {
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "diagnostics-ring-test".to_string();
+        event.screenshot_b64 = Some("/9j/".to_string());
+        event.icon_png_b64 = Some("iVBOR".to_string());
+        record_event(&event);
+        let kept = recent(&EVENTS).into_iter().rev().find(|e| e.title == "diagnostics-ring-test").unwrap();
+        assert!(kept.screenshot_b64.is_none() && kept.icon_png_b64.is_none());
+        assert!(recent(&EVENTS).len() <= RECENT_EVENTS);
+    }
+}
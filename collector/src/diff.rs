@@ -0,0 +1,197 @@
+//! Cheap before/after screen comparison for the command verification loop.
+//!
+//! Reduces each frame to a grid of average-luminance blocks and compares the
+//! grids, so the backend can detect "nothing changed" failures without
+//! shipping both screenshots to a model.
+
+use serde::Serialize;
+
+/// Number of blocks per side of the comparison grid (32x32 = 1024 blocks).
+pub const DIFF_GRID: u32 = 32;
+
+/// Per-block luminance delta (0..1) above which a block counts as changed.
+/// ~10/255 — tolerant of JPEG-like noise and cursor blink anti-aliasing.
+pub const BLOCK_CHANGE_THRESHOLD: f32 = 0.04;
+
+/// Result of comparing a pre-action and post-action frame.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScreenDiff {
+    /// Mean absolute luminance difference across blocks (0 = identical, 1 = inverted).
+    pub score: f32,
+    /// Number of blocks whose luminance moved more than the change threshold.
+    pub changed_blocks: u32,
+    pub total_blocks: u32,
+    pub method: String,
+}
+
+impl ScreenDiff {
+    /// True when no block moved past the change threshold.
+    pub fn is_unchanged(&self) -> bool {
+        self.changed_blocks == 0
+    }
+}
+
+/// Reduce raw BGR/BGRA pixels to a `grid` x `grid` signature of average
+/// luminance values (0..255). Samples every other pixel in each block to keep
+/// 4K frames well under a millisecond of work.
+pub fn block_signature(pixels: &[u8], width: u32, height: u32, channels: usize, grid: u32) -> Vec<f32> {
+    let grid = grid.max(1);
+    let mut signature = vec![0.0f32; (grid * grid) as usize];
+    if width == 0 || height == 0 || channels < 3 {
+        return signature;
+    }
+
+    for by in 0..grid {
+        let y0 = by * height / grid;
+        let y1 = ((by + 1) * height / grid).max(y0 + 1).min(height);
+        for bx in 0..grid {
+            let x0 = bx * width / grid;
+            let x1 = ((bx + 1) * width / grid).max(x0 + 1).min(width);
+
+            let mut sum = 0.0f32;
+            let mut count = 0u32;
+            for y in (y0..y1).step_by(2) {
+                for x in (x0..x1).step_by(2) {
+                    let idx = (y as usize * width as usize + x as usize) * channels;
+                    if idx + 2 < pixels.len() {
+                        let b = pixels[idx] as f32;
+                        let g = pixels[idx + 1] as f32;
+                        let r = pixels[idx + 2] as f32;
+                        sum += 0.299 * r + 0.587 * g + 0.114 * b;
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 {
+                signature[(by * grid + bx) as usize] = sum / count as f32;
+            }
+        }
+    }
+
+    signature
+}
+
+/// Compare two block signatures produced with the same grid size.
+/// Mismatched lengths (e.g. the frame moved to a different monitor) are
+/// reported as a full change.
+pub fn compare_signatures(before: &[f32], after: &[f32]) -> ScreenDiff {
+    let total_blocks = before.len().max(after.len()) as u32;
+    if before.len() != after.len() || before.is_empty() {
+        return ScreenDiff {
+            score: 1.0,
+            changed_blocks: total_blocks,
+            total_blocks,
+            method: "block_luma".to_string(),
+        };
+    }
+
+    let mut total_delta = 0.0f32;
+    let mut changed_blocks = 0u32;
+    for (a, b) in before.iter().zip(after.iter()) {
+        let delta = (a - b).abs() / 255.0;
+        total_delta += delta;
+        if delta > BLOCK_CHANGE_THRESHOLD {
+            changed_blocks += 1;
+        }
+    }
+
+    ScreenDiff {
+        score: total_delta / before.len() as f32,
+        changed_blocks,
+        total_blocks,
+        method: "block_luma".to_string(),
+    }
+}
+
+/// Compare two raw frames. Frames of different dimensions are a full change.
+pub fn compare_frames(
+    before: (u32, u32, &[u8]),
+    after: (u32, u32, &[u8]),
+    channels: usize,
+) -> ScreenDiff {
+    let (bw, bh, bpx) = before;
+    let (aw, ah, apx) = after;
+    if bw != aw || bh != ah {
+        return ScreenDiff {
+            score: 1.0,
+            changed_blocks: DIFF_GRID * DIFF_GRID,
+            total_blocks: DIFF_GRID * DIFF_GRID,
+            method: "block_luma".to_string(),
+        };
+    }
+    let sig_before = block_signature(bpx, bw, bh, channels, DIFF_GRID);
+    let sig_after = block_signature(apx, aw, ah, channels, DIFF_GRID);
+    compare_signatures(&sig_before, &sig_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; (width * height * 3) as usize]
+    }
+
+    #[test]
+    fn test_identical_frames_unchanged() {
+        let px = solid(64, 64, 120);
+        let diff = compare_frames((64, 64, &px), (64, 64, &px), 3);
+        assert!(diff.is_unchanged());
+        assert_eq!(diff.score, 0.0);
+        assert_eq!(diff.total_blocks, DIFF_GRID * DIFF_GRID);
+    }
+
+    #[test]
+    fn test_inverted_frames_fully_changed() {
+        let black = solid(64, 64, 0);
+        let white = solid(64, 64, 255);
+        let diff = compare_frames((64, 64, &black), (64, 64, &white), 3);
+        assert_eq!(diff.changed_blocks, diff.total_blocks);
+        assert!((diff.score - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_local_change_detected() {
+        let before = solid(64, 64, 0);
+        let mut after = before.clone();
+        // Paint an 8x8 white square in the top-left corner
+        for y in 0..8 {
+            for x in 0..8 {
+                let idx = (y * 64 + x) * 3;
+                after[idx..idx + 3].copy_from_slice(&[255, 255, 255]);
+            }
+        }
+        let diff = compare_frames((64, 64, &before), (64, 64, &after), 3);
+        assert!(!diff.is_unchanged());
+        assert!(diff.changed_blocks < diff.total_blocks / 10);
+        assert!(diff.score > 0.0 && diff.score < 0.1);
+    }
+
+    #[test]
+    fn test_dimension_mismatch_is_full_change() {
+        let a = solid(64, 64, 10);
+        let b = solid(32, 32, 10);
+        let diff = compare_frames((64, 64, &a), (32, 32, &b), 3);
+        assert_eq!(diff.score, 1.0);
+        assert!(!diff.is_unchanged());
+        assert_eq!(diff.changed_blocks, DIFF_GRID * DIFF_GRID);
+    }
+
+    #[test]
+    fn test_block_signature_bgra() {
+        // 2x2 BGRA frame, pure red: luma = 0.299 * 255
+        let px = [0u8, 0, 255, 255].repeat(4);
+        let sig = block_signature(&px, 2, 2, 4, 1);
+        assert_eq!(sig.len(), 1);
+        assert!((sig[0] - 0.299 * 255.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_screen_diff_serialization() {
+        let diff = compare_signatures(&[10.0, 10.0], &[10.0, 200.0]);
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["changed_blocks"], 1);
+        assert_eq!(json["total_blocks"], 2);
+        assert_eq!(json["method"], "block_luma");
+    }
+}
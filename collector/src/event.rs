@@ -1,10 +1,14 @@
 //! Desktop event types sent from the collector to the backend.
 
 use chrono::Utc;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 use windows::core::BSTR;
 use windows::Win32::Foundation::HWND;
 
+use crate::inventory::WindowChange;
+
 /// A desktop event capturing a foreground window change or idle state transition.
 #[derive(Debug, Serialize, Clone)]
 pub struct WindowEvent {
@@ -13,19 +17,66 @@ pub struct WindowEvent {
     pub hwnd: String,
     pub title: String,
     pub process_exe: String,
+    /// Package family name when the process is a packaged (UWP/MSIX) app.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_family: Option<String>,
+    /// Small app icon of the window as a base64 PNG, cached per process.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_png_b64: Option<String>,
     pub pid: u32,
     pub timestamp: String,
+    /// Per-process event sequence number; orders events even if the wall clock jumps.
+    pub seq: u64,
+    /// Milliseconds since collector start on a monotonic clock.
+    pub monotonic_ms: u64,
+    /// `timestamp` corrected into the backend's clock, once a time sync has completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synced_timestamp: Option<String>,
     pub source: String,
+    /// Stable ID of the collector that produced the event; stamped by the network worker.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub collector_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub collector_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub idle_ms: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uia: Option<UiaSnapshot>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub screenshot_b64: Option<String>,
+    /// Window bounds as [x, y, width, height] in screen coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_rect: Option<[i32; 4]>,
+    /// Effective window theme ("light" or "dark").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detections: Option<serde_json::Value>,
+    /// Per-stage enrichment latency in microseconds, keyed by stage name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub enrich_timings_us: BTreeMap<String, u64>,
+    /// Capture cost breakdown, only with `CAPTURE_TIMINGS` set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<CaptureTimings>,
+    /// Number of top-level windows in the snapshot (`window_inventory` events only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_count: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub window_changes: Vec<WindowChange>,
+    /// Language of the captured text (see language.rs).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<crate::language::TextLanguage>,
+    /// User-defined tags from the routing rules; stamped by the network worker.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Backend subscriptions this event matched (see subscriptions.rs).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subscriptions: Vec<String>,
 }
 
 /// A single UI Automation element in the accessibility tree.
-#[derive(Debug, Serialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct UiaElement {
     pub automation_id: String,
     pub name: String,
@@ -52,6 +103,43 @@ pub struct UiaSnapshot {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub focused_element: Option<UiaElement>,
     pub window_tree: Vec<UiaElement>,
+    /// Compact text form of the tree for prompts (see `outline`); empty when disabled.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub outline: String,
+}
+
+/// Where the time went while capturing one event, in milliseconds. Stages
+/// that did not run are left out.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CaptureTimings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uia_ms: Option<f64>,
+    /// Screen capture only; encoding is reported separately.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encode_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detection_ms: Option<f64>,
+    /// Time between the end of enrichment and the network worker picking the event up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_wait_ms: Option<f64>,
+    #[serde(skip)]
+    pub enriched_at: Option<Instant>,
+}
+
+impl CaptureTimings {
+    /// Fill in `queue_wait_ms` as the event is dequeued for sending.
+    pub fn mark_dequeued(&mut self) {
+        if let Some(at) = self.enriched_at {
+            self.queue_wait_ms = Some(duration_ms(at.elapsed()));
+        }
+    }
+}
+
+/// Duration as fractional milliseconds, rounded to microseconds.
+pub fn duration_ms(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
 }
 
 /// Convert a window handle to a hex string for serialization.
@@ -61,17 +149,35 @@ pub fn hwnd_to_hex(hwnd: HWND) -> String {
 
 /// Build an idle/active activity event (no window context, just the state transition).
 pub fn build_activity_event(event_type: &str, idle_ms: u64) -> WindowEvent {
+    let now = Utc::now();
     WindowEvent {
         event_type: event_type.to_string(),
         hwnd: "0x0".to_string(),
         title: String::new(),
         process_exe: String::new(),
+        package_family: None,
+        icon_png_b64: None,
         pid: 0,
-        timestamp: Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        timestamp: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        seq: crate::clock::next_seq(),
+        monotonic_ms: crate::clock::monotonic_ms(),
+        synced_timestamp: crate::clock::synced_timestamp(now),
         source: "collector".to_string(),
+        collector_id: String::new(),
+        collector_name: String::new(),
         idle_ms: Some(idle_ms),
         uia: None,
         screenshot_b64: None,
+        window_rect: None,
+        theme: None,
+        detections: None,
+        enrich_timings_us: BTreeMap::new(),
+        timings: None,
+        window_count: None,
+        window_changes: Vec::new(),
+        language: None,
+        tags: Vec::new(),
+        subscriptions: Vec::new(),
     }
 }
 
@@ -90,12 +196,29 @@ mod tests {
             hwnd: "0x12345".to_string(),
             title: "Test Window".to_string(),
             process_exe: "test.exe".to_string(),
+            package_family: None,
+            icon_png_b64: None,
             pid: 1234,
             timestamp: "2026-02-09T12:00:00.000Z".to_string(),
+            seq: 1,
+            monotonic_ms: 0,
+            synced_timestamp: None,
             source: "collector".to_string(),
+            collector_id: String::new(),
+            collector_name: String::new(),
             idle_ms: None,
             uia: None,
             screenshot_b64: None,
+            window_rect: None,
+            theme: None,
+            detections: None,
+            enrich_timings_us: BTreeMap::new(),
+            timings: None,
+            window_count: None,
+            window_changes: Vec::new(),
+            language: None,
+            tags: Vec::new(),
+            subscriptions: Vec::new(),
         };
 
         let json = serde_json::to_value(&event).unwrap();
@@ -108,6 +231,33 @@ mod tests {
         assert!(json.get("idle_ms").is_none());
         assert!(json.get("uia").is_none());
         assert!(json.get("screenshot_b64").is_none());
+        assert!(json.get("collector_id").is_none());
+        assert!(json.get("collector_name").is_none());
+        assert_eq!(json["seq"], 1);
+        assert!(json.get("synced_timestamp").is_none());
+        assert!(json.get("window_rect").is_none());
+        assert!(json.get("package_family").is_none());
+        assert!(json.get("icon_png_b64").is_none());
+        assert!(json.get("enrich_timings_us").is_none());
+    }
+
+    #[test]
+    fn test_build_activity_event_seq_increases() {
+        let first = build_activity_event("idle", 0);
+        let second = build_activity_event("active", 0);
+        assert!(second.seq > first.seq);
+        assert!(second.monotonic_ms >= first.monotonic_ms);
+    }
+
+    #[test]
+    fn test_window_event_serialization_with_identity() {
+        let mut event = build_activity_event("idle", 1000);
+        event.collector_id = "3f2b6c1e-0000-4000-8000-000000000001".to_string();
+        event.collector_name = "LAB-PC".to_string();
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["collector_id"], "3f2b6c1e-0000-4000-8000-000000000001");
+        assert_eq!(json["collector_name"], "LAB-PC");
     }
 
     #[test]
@@ -117,12 +267,29 @@ mod tests {
             hwnd: "0x0".to_string(),
             title: String::new(),
             process_exe: String::new(),
+            package_family: None,
+            icon_png_b64: None,
             pid: 0,
             timestamp: "2026-02-09T12:00:00.000Z".to_string(),
+            seq: 1,
+            monotonic_ms: 0,
+            synced_timestamp: None,
             source: "collector".to_string(),
+            collector_id: String::new(),
+            collector_name: String::new(),
             idle_ms: Some(60000),
             uia: None,
             screenshot_b64: None,
+            window_rect: None,
+            theme: None,
+            detections: None,
+            enrich_timings_us: BTreeMap::new(),
+            timings: None,
+            window_count: None,
+            window_changes: Vec::new(),
+            language: None,
+            tags: Vec::new(),
+            subscriptions: Vec::new(),
         };
 
         let json = serde_json::to_value(&event).unwrap();
@@ -137,12 +304,29 @@ mod tests {
             hwnd: "0x12345".to_string(),
             title: "Test".to_string(),
             process_exe: "test.exe".to_string(),
+            package_family: None,
+            icon_png_b64: None,
             pid: 1234,
             timestamp: "2026-02-09T12:00:00.000Z".to_string(),
+            seq: 1,
+            monotonic_ms: 0,
+            synced_timestamp: None,
             source: "collector".to_string(),
+            collector_id: String::new(),
+            collector_name: String::new(),
             idle_ms: None,
             uia: None,
             screenshot_b64: Some("base64data".to_string()),
+            window_rect: None,
+            theme: None,
+            detections: None,
+            enrich_timings_us: BTreeMap::new(),
+            timings: None,
+            window_count: None,
+            window_changes: Vec::new(),
+            language: None,
+            tags: Vec::new(),
+            subscriptions: Vec::new(),
         };
 
         let json = serde_json::to_value(&event).unwrap();
@@ -261,6 +445,7 @@ mod tests {
             document_text: "Sample text".to_string(),
             focused_element: Some(element.clone()),
             window_tree: vec![element],
+            outline: String::new(),
         };
 
         let json = serde_json::to_value(&snapshot).unwrap();
@@ -279,6 +464,7 @@ mod tests {
             document_text: "Click me".to_string(),
             focused_element: None,
             window_tree: vec![],
+            outline: String::new(),
         };
 
         let event = WindowEvent {
@@ -286,12 +472,29 @@ mod tests {
             hwnd: "0x12345".to_string(),
             title: "Test Window".to_string(),
             process_exe: "test.exe".to_string(),
+            package_family: None,
+            icon_png_b64: None,
             pid: 1234,
             timestamp: "2026-02-09T12:00:00.000Z".to_string(),
+            seq: 1,
+            monotonic_ms: 0,
+            synced_timestamp: None,
             source: "collector".to_string(),
+            collector_id: String::new(),
+            collector_name: String::new(),
             idle_ms: None,
             uia: Some(snapshot),
             screenshot_b64: None,
+            window_rect: None,
+            theme: None,
+            detections: None,
+            enrich_timings_us: BTreeMap::new(),
+            timings: None,
+            window_count: None,
+            window_changes: Vec::new(),
+            language: None,
+            tags: Vec::new(),
+            subscriptions: Vec::new(),
         };
 
         let json = serde_json::to_value(&event).unwrap();
@@ -400,6 +603,7 @@ mod tests {
             document_text: "Content".to_string(),
             focused_element: None,
             window_tree: vec![],
+            outline: String::new(),
         };
         let snapshot2 = snapshot1.clone();
 
@@ -447,6 +651,7 @@ mod tests {
             document_text: "Content".to_string(),
             focused_element: None,
             window_tree: vec![],
+            outline: String::new(),
         };
         let debug_str = format!("{:?}", snapshot);
         assert!(debug_str.contains("UiaSnapshot"));
@@ -0,0 +1,272 @@
+//! Screen capture through Windows.Graphics.Capture with a GPU downscale.
+//!
+//! With `SCREENSHOT_BACKEND=graphics_capture` a monitor is captured into a
+//! D3D11 texture and shrunk to the configured maximum by the D3D11 video
+//! processor, so only the already-resized BGRA frame is read back: the CPU
+//! never touches a full-resolution 4K frame before JPEG encoding. Needs
+//! Windows 10 1903 or later and a GPU with a video processor; when any step
+//! fails `screenshot::capture_scaled_pixels` falls back to the GDI blit.
+//! Region captures and window thumbnails always use GDI.
+
+use std::mem::ManuallyDrop;
+use std::time::{Duration, Instant};
+
+use windows::core::ComInterface;
+use windows::Graphics::Capture::{Direct3D11CaptureFrame, Direct3D11CaptureFramePool, GraphicsCaptureItem};
+use windows::Graphics::DirectX::Direct3D11::IDirect3DDevice;
+use windows::Graphics::DirectX::DirectXPixelFormat;
+use windows::Win32::Foundation::{HMODULE, RECT};
+use windows::Win32::Graphics::Direct3D::D3D_DRIVER_TYPE_HARDWARE;
+use windows::Win32::Graphics::Direct3D11::*;
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_RATIONAL, DXGI_SAMPLE_DESC};
+use windows::Win32::Graphics::Dxgi::{IDXGIAdapter, IDXGIDevice};
+use windows::Win32::Graphics::Gdi::HMONITOR;
+use windows::Win32::System::WinRT::Direct3D11::{CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess};
+use windows::Win32::System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop;
+
+/// How long a new capture session may take to deliver its first frame.
+const FRAME_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often the frame pool is checked meanwhile.
+const FRAME_POLL: Duration = Duration::from_millis(5);
+
+/// Capture `monitor`, shrunk on the GPU to fit `max_width` x `max_height`
+/// (0 = no limit on that side). Returns (width, height, packed BGR pixels)
+/// like the GDI capture, or which step failed.
+pub fn capture_monitor(monitor: HMONITOR, max_width: u32, max_height: u32) -> Result<(u32, u32, Vec<u8>), String> {
+    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let (device, context) = create_device()?;
+        let dxgi: IDXGIDevice = device.cast().map_err(fail("get the DXGI device"))?;
+        let winrt_device: IDirect3DDevice = CreateDirect3D11DeviceFromDXGIDevice(&dxgi)
+            .and_then(|inspectable| inspectable.cast())
+            .map_err(fail("wrap the D3D11 device for WinRT"))?;
+
+        let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()
+            .map_err(fail("load Graphics.Capture"))?;
+        let item: GraphicsCaptureItem = interop.CreateForMonitor(monitor).map_err(fail("capture the monitor"))?;
+        let pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+            &winrt_device,
+            DirectXPixelFormat::B8G8R8A8UIntNormalized,
+            1,
+            item.Size().map_err(fail("size the monitor"))?,
+        )
+        .map_err(fail("create the frame pool"))?;
+        let session = pool.CreateCaptureSession(&item).map_err(fail("create the capture session"))?;
+        // Like the GDI blit: no cursor, and no yellow border where Windows allows it
+        let _ = session.SetIsCursorCaptureEnabled(false);
+        let _ = session.SetIsBorderRequired(false);
+
+        // The frame's texture belongs to the pool, so it is resized and
+        // copied out before the session closes
+        let captured = session
+            .StartCapture()
+            .map_err(fail("start capturing"))
+            .and_then(|()| next_frame(&pool))
+            .and_then(|frame| {
+                let scaled = scale_frame(&device, &context, &frame, max_width, max_height);
+                let _ = frame.Close();
+                scaled
+            });
+        let _ = session.Close();
+        let _ = pool.Close();
+        captured
+    }
+}
+
+/// Describe a failed step as "could not <step>: <error>".
+fn fail(step: &'static str) -> impl Fn(windows::core::Error) -> String {
+    move |e| format!("could not {step}: {e}")
+}
+
+/// A hardware D3D11 device that can share textures with Graphics.Capture.
+unsafe fn create_device() -> Result<(ID3D11Device, ID3D11DeviceContext), String> {
+    let (mut device, mut context) = (None, None);
+    D3D11CreateDevice(
+        None::<&IDXGIAdapter>,
+        D3D_DRIVER_TYPE_HARDWARE,
+        HMODULE(0),
+        D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        None,
+        D3D11_SDK_VERSION,
+        Some(&mut device),
+        None,
+        Some(&mut context),
+    )
+    .map_err(fail("create a D3D11 device"))?;
+    device.zip(context).ok_or_else(|| "could not create a D3D11 device: none returned".to_string())
+}
+
+/// The first frame the session delivers, within `FRAME_TIMEOUT`.
+fn next_frame(pool: &Direct3D11CaptureFramePool) -> Result<Direct3D11CaptureFrame, String> {
+    let deadline = Instant::now() + FRAME_TIMEOUT;
+    loop {
+        // An empty pool reports no frame as an error
+        if let Ok(frame) = pool.TryGetNextFrame() {
+            return Ok(frame);
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("no frame within {} ms", FRAME_TIMEOUT.as_millis()));
+        }
+        std::thread::sleep(FRAME_POLL);
+    }
+}
+
+/// Resize `frame` on the GPU when it is larger than the bounds, then read
+/// it back as packed BGR.
+unsafe fn scale_frame(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    frame: &Direct3D11CaptureFrame,
+    max_width: u32,
+    max_height: u32,
+) -> Result<(u32, u32, Vec<u8>), String> {
+    let access: IDirect3DDxgiInterfaceAccess = frame
+        .Surface()
+        .and_then(|surface| surface.cast())
+        .map_err(fail("get the frame surface"))?;
+    let texture: ID3D11Texture2D = access.GetInterface().map_err(fail("get the frame texture"))?;
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    texture.GetDesc(&mut desc);
+
+    let source = (desc.Width, desc.Height);
+    let target = crate::screenshot::scaled_size(source.0, source.1, max_width, max_height);
+    let resized = if target == source { texture } else { downscale(device, context, &texture, source, target)? };
+    let pixels = read_back(device, context, &resized, target.0, target.1)?;
+    Ok((target.0, target.1, pixels))
+}
+
+/// Shrink `texture` from `source` to `target` size with the D3D11 video
+/// processor (filtered, unlike a plain copy), into a new BGRA texture.
+unsafe fn downscale(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+    source: (u32, u32),
+    target: (u32, u32),
+) -> Result<ID3D11Texture2D, String> {
+    let video_device: ID3D11VideoDevice = device.cast().map_err(fail("get the video device"))?;
+    let video_context: ID3D11VideoContext = context.cast().map_err(fail("get the video context"))?;
+
+    let rate = DXGI_RATIONAL { Numerator: 60, Denominator: 1 };
+    let content = D3D11_VIDEO_PROCESSOR_CONTENT_DESC {
+        InputFrameFormat: D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE,
+        InputFrameRate: rate,
+        InputWidth: source.0,
+        InputHeight: source.1,
+        OutputFrameRate: rate,
+        OutputWidth: target.0,
+        OutputHeight: target.1,
+        Usage: D3D11_VIDEO_USAGE_OPTIMAL_QUALITY,
+    };
+    let enumerator = video_device
+        .CreateVideoProcessorEnumerator(&content)
+        .map_err(fail("set up the video processor"))?;
+    let processor = video_device.CreateVideoProcessor(&enumerator, 0).map_err(fail("create the video processor"))?;
+
+    let output = create_texture(device, target, D3D11_USAGE_DEFAULT, D3D11_BIND_RENDER_TARGET.0 as u32, 0)?;
+    let input_desc = D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC {
+        FourCC: 0,
+        ViewDimension: D3D11_VPIV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC_0 { Texture2D: D3D11_TEX2D_VPIV { MipSlice: 0, ArraySlice: 0 } },
+    };
+    let mut input_view = None;
+    video_device
+        .CreateVideoProcessorInputView(texture, &enumerator, &input_desc, Some(&mut input_view))
+        .map_err(fail("view the frame for the video processor"))?;
+    let output_desc = D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC {
+        ViewDimension: D3D11_VPOV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC_0 { Texture2D: D3D11_TEX2D_VPOV { MipSlice: 0 } },
+    };
+    let mut output_view = None;
+    video_device
+        .CreateVideoProcessorOutputView(&output, &enumerator, &output_desc, Some(&mut output_view))
+        .map_err(fail("view the output for the video processor"))?;
+    let output_view = output_view.ok_or("could not view the output for the video processor: none returned")?;
+
+    let source_rect = RECT { left: 0, top: 0, right: source.0 as i32, bottom: source.1 as i32 };
+    let target_rect = RECT { left: 0, top: 0, right: target.0 as i32, bottom: target.1 as i32 };
+    video_context.VideoProcessorSetStreamFrameFormat(&processor, 0, D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE);
+    video_context.VideoProcessorSetStreamSourceRect(&processor, 0, true, Some(&source_rect));
+    video_context.VideoProcessorSetStreamDestRect(&processor, 0, true, Some(&target_rect));
+    video_context.VideoProcessorSetOutputTargetRect(&processor, true, Some(&target_rect));
+
+    let mut stream = D3D11_VIDEO_PROCESSOR_STREAM {
+        Enable: true.into(),
+        pInputSurface: ManuallyDrop::new(input_view),
+        ..Default::default()
+    };
+    let blitted = video_context.VideoProcessorBlt(&processor, &output_view, 0, std::slice::from_ref(&stream));
+    ManuallyDrop::drop(&mut stream.pInputSurface);
+    blitted.map_err(fail("resize the frame"))?;
+    Ok(output)
+}
+
+/// A `size` BGRA texture with the given usage, bind and CPU access flags.
+unsafe fn create_texture(
+    device: &ID3D11Device,
+    size: (u32, u32),
+    usage: D3D11_USAGE,
+    bind_flags: u32,
+    cpu_access: u32,
+) -> Result<ID3D11Texture2D, String> {
+    let desc = D3D11_TEXTURE2D_DESC {
+        Width: size.0,
+        Height: size.1,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+        Usage: usage,
+        BindFlags: bind_flags,
+        CPUAccessFlags: cpu_access,
+        MiscFlags: 0,
+    };
+    let mut texture = None;
+    device.CreateTexture2D(&desc, None, Some(&mut texture)).map_err(fail("create a texture"))?;
+    texture.ok_or_else(|| "could not create a texture: none returned".to_string())
+}
+
+/// Copy `texture` into CPU-readable memory and return it as packed BGR.
+unsafe fn read_back(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, String> {
+    let staging = create_texture(device, (width, height), D3D11_USAGE_STAGING, 0, D3D11_CPU_ACCESS_READ.0 as u32)?;
+    context.CopyResource(&staging, texture);
+    let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+    context
+        .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))
+        .map_err(fail("read the frame back"))?;
+    let pitch = mapped.RowPitch as usize;
+    let len = pitch * (height as usize).saturating_sub(1) + width as usize * 4;
+    let pixels = bgra_to_bgr(std::slice::from_raw_parts(mapped.pData as *const u8, len), pitch, width, height);
+    context.Unmap(&staging, 0);
+    Ok(pixels)
+}
+
+/// Tightly packed BGR from BGRA rows `pitch` bytes apart, the layout the
+/// GDI capture and the JPEG encoder use.
+fn bgra_to_bgr(data: &[u8], pitch: usize, width: u32, height: u32) -> Vec<u8> {
+    let row = width as usize * 4;
+    data.chunks(pitch)
+        .take(height as usize)
+        .flat_map(|line| line[..row].chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bgra_to_bgr_drops_alpha_and_row_padding() {
+        // 2x2 frame, rows padded from 8 to 12 bytes; the last row unpadded
+        let data = [1, 2, 3, 255, 4, 5, 6, 255, 0, 0, 0, 0, 7, 8, 9, 255, 10, 11, 12, 255];
+        assert_eq!(bgra_to_bgr(&data, 12, 2, 2), vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Foreground WinEvent hook on a supervised thread.
+//!
+//! The `SetWinEventHook` callback only fires while its thread pumps
+//! messages, so a stuck loop (a callback that never returns, a wedged UIA
+//! call) silently stops all foreground observation. The hook therefore runs
+//! on its own thread, which also posts itself a timer tick every
+//! `HEARTBEAT` as proof that the loop is still turning. A supervisor
+//! thread watches those ticks: when the hook fails to install, the loop
+//! exits, or no tick arrives for `STALL_AFTER`, it asks the old thread to
+//! quit (it unhooks itself whenever it wakes up) and installs the hook
+//! again on a fresh thread, backing off while restarts keep failing. A
+//! stuck thread cannot be stopped; it is abandoned like a hung command
+//! worker (see `watchdog`).
+//!
+//! `health()` reports the current state; it rides along on the collector's
+//! heartbeat so the backend's collector status shows it.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Interval between heartbeat ticks on the hook thread.
+#[cfg_attr(not(windows), allow(dead_code))]
+const HEARTBEAT: Duration = Duration::from_secs(1);
+/// Silence after which the hook thread counts as stalled.
+const STALL_AFTER: Duration = Duration::from_secs(20);
+/// How often the supervisor checks on the hook thread.
+#[cfg_attr(not(windows), allow(dead_code))]
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// First and longest wait before reinstalling a failed hook.
+#[cfg_attr(not(windows), allow(dead_code))]
+const RESTART_MIN_MS: u64 = 1000;
+#[cfg_attr(not(windows), allow(dead_code))]
+const RESTART_MAX_MS: u64 = 30_000;
+/// A hook that ran this long resets the restart backoff.
+#[cfg_attr(not(windows), allow(dead_code))]
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Snapshot of the hook's health, as reported to the backend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HookHealth {
+    /// The hook is installed and its thread is pumping messages.
+    pub installed: bool,
+    /// Installed and heard from within the stall timeout.
+    pub healthy: bool,
+    /// Times the hook was reinstalled since the collector started.
+    pub restarts: u32,
+    /// Milliseconds since the hook thread's last heartbeat.
+    pub heartbeat_age_ms: Option<u64>,
+    /// Milliseconds since the last foreground event came through the hook.
+    pub last_event_age_ms: Option<u64>,
+    /// Why the hook was last reinstalled.
+    pub last_error: Option<String>,
+}
+
+impl HookHealth {
+    /// Whether anything besides the ages differs from `other`.
+    pub fn changed_from(&self, other: &HookHealth) -> bool {
+        self.installed != other.installed
+            || self.healthy != other.healthy
+            || self.restarts != other.restarts
+            || self.last_error != other.last_error
+    }
+}
+
+struct State {
+    /// Bumped per hook thread, so a retired thread's late ticks are ignored.
+    generation: u64,
+    installed: bool,
+    thread_id: u32,
+    restarts: u32,
+    last_beat: Option<Instant>,
+    last_event: Option<Instant>,
+    last_error: Option<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    generation: 0,
+    installed: false,
+    thread_id: 0,
+    restarts: 0,
+    last_beat: None,
+    last_event: None,
+    last_error: None,
+});
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Current health of the foreground hook.
+pub fn health() -> HookHealth {
+    let state = state();
+    describe(&state, Instant::now())
+}
+
+fn describe(state: &State, now: Instant) -> HookHealth {
+    let age = |at: Option<Instant>| at.map(|at| now.saturating_duration_since(at).as_millis() as u64);
+    HookHealth {
+        installed: state.installed,
+        healthy: state.installed && !stalled(state.last_beat, now),
+        restarts: state.restarts,
+        heartbeat_age_ms: age(state.last_beat),
+        last_event_age_ms: age(state.last_event),
+        last_error: state.last_error.clone(),
+    }
+}
+
+/// No heartbeat within `STALL_AFTER` of `now`.
+fn stalled(last_beat: Option<Instant>, now: Instant) -> bool {
+    last_beat.is_none_or(|at| now.saturating_duration_since(at) >= STALL_AFTER)
+}
+
+/// Record a foreground event delivered by the hook.
+pub fn note_event() {
+    let mut state = state();
+    let now = Instant::now();
+    state.last_event = Some(now);
+    state.last_beat = Some(now);
+}
+
+/// Start a new hook generation; the stall clock starts now.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn begin() -> u64 {
+    let mut state = state();
+    state.generation += 1;
+    state.installed = false;
+    state.thread_id = 0;
+    state.last_beat = Some(Instant::now());
+    state.generation
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn installed(generation: u64, thread_id: u32) {
+    let mut state = state();
+    if state.generation == generation {
+        state.installed = true;
+        state.thread_id = thread_id;
+        state.last_beat = Some(Instant::now());
+    }
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn beat(generation: u64) {
+    let mut state = state();
+    if state.generation == generation {
+        state.last_beat = Some(Instant::now());
+    }
+}
+
+/// Retire the current generation with `reason`. Returns its thread ID
+/// (0 if the hook never installed).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn retire(reason: String) -> u32 {
+    let mut state = state();
+    state.generation += 1;
+    state.installed = false;
+    state.restarts += 1;
+    state.last_error = Some(reason);
+    std::mem::take(&mut state.thread_id)
+}
+
+/// Install the foreground hook on its own supervised thread. Returns
+/// immediately; the hook is reinstalled whenever it fails or stalls.
+#[cfg(windows)]
+pub fn start() {
+    std::thread::spawn(supervise);
+}
+
+#[cfg(windows)]
+fn supervise() {
+    use ::windows::Win32::Foundation::{LPARAM, WPARAM};
+    use ::windows::Win32::UI::WindowsAndMessaging::{PostThreadMessageW, WM_QUIT};
+
+    let mut delay_ms = RESTART_MIN_MS;
+    loop {
+        let generation = begin();
+        let started = Instant::now();
+        let pump = std::thread::spawn(move || pump(generation));
+        let reason = loop {
+            std::thread::sleep(CHECK_INTERVAL);
+            if pump.is_finished() {
+                break match pump.join() {
+                    Ok(Err(err)) => err,
+                    Ok(Ok(())) => "message loop exited".to_string(),
+                    Err(_) => "hook thread panicked".to_string(),
+                };
+            }
+            let last_beat = state().last_beat;
+            if stalled(last_beat, Instant::now()) {
+                break format!("no heartbeat for {}s", STALL_AFTER.as_secs());
+            }
+        };
+
+        log::warn!("Foreground hook failed ({reason}); reinstalling in {delay_ms}ms");
+        let thread_id = retire(reason);
+        if thread_id != 0 {
+            // A stuck thread sees this whenever it wakes and unhooks itself
+            unsafe {
+                let _ = PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+            }
+        }
+        if started.elapsed() >= STABLE_AFTER {
+            delay_ms = RESTART_MIN_MS;
+        }
+        std::thread::sleep(Duration::from_millis(delay_ms));
+        delay_ms = crate::network::calculate_backoff(delay_ms, RESTART_MAX_MS);
+    }
+}
+
+/// Install the hook on the calling thread and pump its message loop until
+/// told to quit.
+#[cfg(windows)]
+fn pump(generation: u64) -> Result<(), String> {
+    use ::windows::Win32::Foundation::HWND;
+    use ::windows::Win32::System::Threading::GetCurrentThreadId;
+    use ::windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent};
+    use ::windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetMessageW, KillTimer, SetTimer, TranslateMessage, EVENT_SYSTEM_FOREGROUND, MSG,
+        WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_TIMER,
+    };
+
+    unsafe {
+        let hook = SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_FOREGROUND,
+            None,
+            Some(crate::windows::win_event_hook),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        );
+        if hook.0 == 0 {
+            return Err("SetWinEventHook failed".to_string());
+        }
+        let timer = SetTimer(HWND(0), 0, HEARTBEAT.as_millis() as u32, None);
+        if timer == 0 {
+            UnhookWinEvent(hook);
+            return Err("SetTimer failed".to_string());
+        }
+        installed(generation, GetCurrentThreadId());
+
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, HWND(0), 0, 0).as_bool() {
+            if msg.message == WM_TIMER && msg.hwnd.0 == 0 {
+                beat(generation);
+                continue;
+            }
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+
+        let _ = KillTimer(HWND(0), timer);
+        UnhookWinEvent(hook);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fresh() -> State {
+        State {
+            generation: 1,
+            installed: false,
+            thread_id: 0,
+            restarts: 0,
+            last_beat: None,
+            last_event: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_stall_detection() {
+        let now = Instant::now();
+        assert!(stalled(None, now));
+        assert!(!stalled(Some(now), now));
+        assert!(!stalled(Some(now), now + STALL_AFTER - Duration::from_millis(1)));
+        assert!(stalled(Some(now), now + STALL_AFTER));
+
+        let mut state = fresh();
+        state.last_beat = Some(now);
+        assert!(!describe(&state, now).healthy, "not installed yet");
+        state.installed = true;
+        let health = describe(&state, now + Duration::from_millis(1500));
+        assert!(health.healthy);
+        assert_eq!(health.heartbeat_age_ms, Some(1500));
+        assert_eq!(health.last_event_age_ms, None);
+        assert!(!describe(&state, now + STALL_AFTER).healthy);
+    }
+
+    #[test]
+    fn test_changed_ignores_ages() {
+        let now = Instant::now();
+        let mut state = fresh();
+        state.installed = true;
+        state.last_beat = Some(now);
+        let before = describe(&state, now);
+        assert!(!describe(&state, now + Duration::from_secs(3)).changed_from(&before));
+
+        state.restarts = 1;
+        state.last_error = Some("no heartbeat for 20s".to_string());
+        assert!(describe(&state, now).changed_from(&before));
+        assert!(describe(&state, now + STALL_AFTER).changed_from(&before), "stalled");
+    }
+}
@@ -0,0 +1,244 @@
+//! Window icons for events: the small icon of the foreground window, encoded
+//! as a tiny PNG so dashboards and the palette can show recognizable app icons
+//! without a lookup service. Icons are cached per process image path.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Per-process cache of base64 PNG icons (`None` = no icon available).
+static ICON_CACHE: Mutex<Option<HashMap<String, Option<String>>>> = Mutex::new(None);
+
+/// Upper bound on cached processes; the cache is simply cleared when full.
+const MAX_CACHED_ICONS: usize = 256;
+
+/// Encode top-down 32-bit BGRA pixels as an RGBA PNG. If every alpha byte is
+/// zero (icons without an alpha channel), alpha comes from `mask` instead:
+/// one byte per pixel, non-zero meaning transparent, as in an AND mask.
+pub fn encode_icon_png(width: u32, height: u32, bgra: &[u8], mask: Option<&[u8]>) -> Option<Vec<u8>> {
+    let pixel_count = (width * height) as usize;
+    if width == 0 || height == 0 || bgra.len() < pixel_count * 4 {
+        return None;
+    }
+    let has_alpha = bgra.chunks_exact(4).take(pixel_count).any(|px| px[3] != 0);
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for (i, px) in bgra.chunks_exact(4).take(pixel_count).enumerate() {
+        let alpha = if has_alpha {
+            px[3]
+        } else {
+            match mask.and_then(|m| m.get(i)) {
+                Some(&transparent) if transparent != 0 => 0,
+                _ => 255,
+            }
+        };
+        rgba.extend_from_slice(&[px[2], px[1], px[0], alpha]);
+    }
+
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().ok()?;
+        writer.write_image_data(&rgba).ok()?;
+    }
+    Some(out)
+}
+
+/// Cached icon for `process_exe`, computing it with `load` on a miss.
+pub fn cached_icon(process_exe: &str, load: impl FnOnce() -> Option<String>) -> Option<String> {
+    if process_exe.is_empty() {
+        return load();
+    }
+    {
+        let cache = ICON_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(hit) = cache.as_ref().and_then(|c| c.get(process_exe)) {
+            return hit.clone();
+        }
+    }
+    let icon = load();
+    let mut cache = ICON_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if cache.len() >= MAX_CACHED_ICONS {
+        cache.clear();
+    }
+    cache.insert(process_exe.to_string(), icon.clone());
+    icon
+}
+
+/// Small icon of `hwnd` as base64 PNG: WM_GETICON, then the window class
+/// icon, then the executable's shell icon.
+#[cfg(windows)]
+pub fn window_icon_b64(hwnd: windows::Win32::Foundation::HWND, process_exe: &str) -> Option<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use windows::Win32::Foundation::{LPARAM, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetClassLongPtrW, SendMessageTimeoutW, GCLP_HICONSM, HICON, ICON_SMALL2, SMTO_ABORTIFHUNG, WM_GETICON,
+    };
+
+    let mut result: usize = 0;
+    unsafe {
+        let _ = SendMessageTimeoutW(
+            hwnd,
+            WM_GETICON,
+            WPARAM(ICON_SMALL2 as usize),
+            LPARAM(0),
+            SMTO_ABORTIFHUNG,
+            100,
+            Some(&mut result),
+        );
+    }
+    if result == 0 {
+        result = unsafe { GetClassLongPtrW(hwnd, GCLP_HICONSM) };
+    }
+    let png = if result != 0 {
+        // Borrowed from the window; must not be destroyed
+        icon_to_png(HICON(result as isize))
+    } else {
+        shell_icon_png(process_exe)
+    }?;
+    Some(STANDARD.encode(png))
+}
+
+#[cfg(windows)]
+fn shell_icon_png(process_exe: &str) -> Option<Vec<u8>> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES;
+    use windows::Win32::UI::Shell::{SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_SMALLICON};
+    use windows::Win32::UI::WindowsAndMessaging::DestroyIcon;
+
+    if process_exe.is_empty() {
+        return None;
+    }
+    let path_w: Vec<u16> = process_exe.encode_utf16().chain(Some(0)).collect();
+    let mut info = SHFILEINFOW::default();
+    let ok = unsafe {
+        SHGetFileInfoW(
+            PCWSTR(path_w.as_ptr()),
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            Some(&mut info),
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_SMALLICON,
+        )
+    };
+    if ok == 0 || info.hIcon.is_invalid() {
+        return None;
+    }
+    let png = icon_to_png(info.hIcon);
+    unsafe {
+        let _ = DestroyIcon(info.hIcon);
+    }
+    png
+}
+
+/// Read an icon's color and mask bitmaps and encode them as PNG.
+#[cfg(windows)]
+fn icon_to_png(icon: windows::Win32::UI::WindowsAndMessaging::HICON) -> Option<Vec<u8>> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::Graphics::Gdi::{
+        DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+        DIB_RGB_COLORS, HBITMAP,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, ICONINFO};
+
+    let mut info = ICONINFO::default();
+    unsafe { GetIconInfo(icon, &mut info).ok()? };
+    let cleanup = |info: &ICONINFO| unsafe {
+        if !info.hbmColor.is_invalid() {
+            let _ = DeleteObject(info.hbmColor);
+        }
+        if !info.hbmMask.is_invalid() {
+            let _ = DeleteObject(info.hbmMask);
+        }
+    };
+    if info.hbmColor.is_invalid() {
+        // Monochrome icon; not worth rendering
+        cleanup(&info);
+        return None;
+    }
+
+    let mut bitmap = BITMAP::default();
+    let got = unsafe {
+        GetObjectW(
+            info.hbmColor,
+            std::mem::size_of::<BITMAP>() as i32,
+            Some(&mut bitmap as *mut BITMAP as *mut std::ffi::c_void),
+        )
+    };
+    if got == 0 || bitmap.bmWidth <= 0 || bitmap.bmHeight <= 0 {
+        cleanup(&info);
+        return None;
+    }
+    let (width, height) = (bitmap.bmWidth as u32, bitmap.bmHeight as u32);
+
+    let read_bits = |hbm: HBITMAP| -> Option<Vec<u8>> {
+        let mut bmi = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                biHeight: -(height as i32), // top-down
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            let hdc = GetDC(HWND(0));
+            let lines = GetDIBits(hdc, hbm, 0, height, Some(pixels.as_mut_ptr() as *mut _), &mut bmi, DIB_RGB_COLORS);
+            let _ = ReleaseDC(HWND(0), hdc);
+            (lines != 0).then_some(pixels)
+        }
+    };
+
+    let color = read_bits(info.hbmColor);
+    // AND mask read as 32-bit: white (transparent) pixels have non-zero bytes
+    let mask: Option<Vec<u8>> = read_bits(info.hbmMask).map(|m| m.chunks_exact(4).map(|px| px[0]).collect());
+    cleanup(&info);
+    encode_icon_png(width, height, &color?, mask.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_icon_png_with_alpha() {
+        // 2x1: opaque red, half-transparent blue (BGRA)
+        let bgra = [0, 0, 255, 255, 255, 0, 0, 128];
+        let png_bytes = encode_icon_png(2, 1, &bgra, None).unwrap();
+        assert_eq!(&png_bytes[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoder = png::Decoder::new(png_bytes.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(&buf[..8], &[255, 0, 0, 255, 0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn test_encode_icon_png_alpha_from_mask() {
+        let bgra = [10, 20, 30, 0, 40, 50, 60, 0];
+        let png_bytes = encode_icon_png(2, 1, &bgra, Some(&[0, 255])).unwrap();
+        let decoder = png::Decoder::new(png_bytes.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf[3], 255);
+        assert_eq!(buf[7], 0);
+    }
+
+    #[test]
+    fn test_encode_icon_png_rejects_short_buffer() {
+        assert!(encode_icon_png(4, 4, &[0; 8], None).is_none());
+        assert!(encode_icon_png(0, 0, &[], None).is_none());
+    }
+
+    #[test]
+    fn test_cached_icon_loads_once() {
+        let exe = format!("C:\\test\\icon_cache_{}.exe", std::process::id());
+        assert_eq!(cached_icon(&exe, || Some("abc".to_string())).as_deref(), Some("abc"));
+        assert_eq!(cached_icon(&exe, || panic!("should be cached")).as_deref(), Some("abc"));
+    }
+}
@@ -0,0 +1,74 @@
+//! Collector identity: a stable per-machine ID plus a human-friendly name,
+//! so backends aggregating several machines can tell their events apart.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Load the persisted collector ID from `path`, or generate a new one and
+/// persist it. An empty `path` yields an ephemeral ID (nothing written).
+pub fn load_or_create_collector_id(path: &str) -> String {
+    if !path.is_empty() {
+        if let Ok(existing) = fs::read_to_string(path) {
+            let existing = existing.trim();
+            if !existing.is_empty() {
+                return existing.to_string();
+            }
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    if !path.is_empty() {
+        if let Some(parent) = Path::new(path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Err(e) = fs::write(path, &id) {
+            log::warn!("Failed to persist collector id to {path}: {e}");
+        }
+    }
+    id
+}
+
+/// Default display name: the machine's hostname, falling back to "collector".
+pub fn default_collector_name() -> String {
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "collector".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collector_id_persisted_and_reused() {
+        let dir = env::temp_dir().join(format!("desktopai_identity_{}", std::process::id()));
+        let path = dir.join("collector_id");
+        let path_str = path.to_string_lossy().into_owned();
+
+        let first = load_or_create_collector_id(&path_str);
+        assert_eq!(first.len(), 36);
+        assert_eq!(fs::read_to_string(&path).unwrap(), first);
+
+        let second = load_or_create_collector_id(&path_str);
+        assert_eq!(first, second);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collector_id_ephemeral_without_path() {
+        let a = load_or_create_collector_id("");
+        let b = load_or_create_collector_id("");
+        assert!(!a.is_empty());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_default_collector_name_not_empty() {
+        assert!(!default_collector_name().is_empty());
+    }
+}
@@ -1,13 +1,19 @@
 //! Idle detection: polls GetLastInputInfo to detect user idle/active transitions.
+//!
+//! Polling is adaptive so an idle machine is not woken every second: while
+//! the user is active the worker sleeps until the threshold could first be
+//! reached, and while idle the interval backs off from `IDLE_POLL_MS` to
+//! `IDLE_POLL_MAX_MS` (see `next_poll`).
 
 use crossbeam_channel::Sender;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::config::Config;
 use crate::event::{build_activity_event, WindowEvent};
 
 #[cfg(windows)]
-use crate::windows::idle_duration_ms;
+use crate::windows::{build_deep_capture, idle_duration_ms};
 
 #[cfg(not(windows))]
 fn idle_duration_ms() -> Option<u64> {
@@ -15,22 +21,84 @@ fn idle_duration_ms() -> Option<u64> {
     None
 }
 
+#[cfg(not(windows))]
+fn build_deep_capture(_config: &Config) -> Option<WindowEvent> {
+    None
+}
+
+/// Whether a deep capture is due: on entering idle, then every
+/// `deep_capture_interval` while idle (never repeated if the interval is zero).
+pub fn deep_capture_due(config: &Config, now_idle: bool, entered_idle: bool, last_deep: Option<Instant>) -> bool {
+    if !config.deep_capture_on_idle || !now_idle {
+        return false;
+    }
+    if entered_idle {
+        return true;
+    }
+    match last_deep {
+        Some(at) => !config.deep_capture_interval.is_zero() && at.elapsed() >= config.deep_capture_interval,
+        None => true,
+    }
+}
+
+/// How long to sleep before the next check, given the last input was
+/// `idle_ms` ago (`None` when unknown). No input can make the user idle
+/// sooner than the threshold after it, so an active user is checked again
+/// only then. While idle, `backoff` doubles from `idle_poll` up to
+/// `idle_poll_max`, bounding how late a return is noticed, and the sleep
+/// ends early for a deep capture due in `until_deep`.
+pub fn next_poll(config: &Config, idle_ms: Option<u64>, backoff: &mut Duration, until_deep: Option<Duration>) -> Duration {
+    let Some(idle_ms) = idle_ms else {
+        *backoff = config.idle_poll;
+        return config.idle_poll;
+    };
+    let idle_for = Duration::from_millis(idle_ms);
+    if idle_for < config.idle_threshold {
+        *backoff = config.idle_poll;
+        return (config.idle_threshold - idle_for).max(config.idle_poll);
+    }
+    let sleep = (*backoff).min(config.idle_poll_max.max(config.idle_poll));
+    *backoff = (sleep * 2).min(config.idle_poll_max.max(config.idle_poll));
+    match until_deep {
+        Some(until) => sleep.min(until.max(config.idle_poll)),
+        None => sleep,
+    }
+}
+
 pub fn idle_worker(tx: Sender<WindowEvent>, config: Config) {
     if !config.idle_enabled {
         return;
     }
     let mut last_state: Option<bool> = None;
+    let mut last_deep: Option<Instant> = None;
+    let mut backoff = config.idle_poll;
     loop {
-        if let Some(idle_ms) = idle_duration_ms() {
+        let idle = idle_duration_ms();
+        if let Some(idle_ms) = idle {
             let now_idle = idle_ms >= config.idle_threshold.as_millis() as u64;
-            if last_state.map(|state| state != now_idle).unwrap_or(true) {
+            let changed = last_state.map(|state| state != now_idle).unwrap_or(true);
+            if changed {
                 let event_type = if now_idle { "idle" } else { "active" };
                 let event = build_activity_event(event_type, idle_ms);
                 let _ = tx.send(event);
                 last_state = Some(now_idle);
             }
+            // Heavy observation only while the user is away, so it never
+            // competes with interactive latency.
+            if deep_capture_due(&config, now_idle, changed, last_deep) {
+                last_deep = Some(Instant::now());
+                if let Some(event) = build_deep_capture(&config) {
+                    let _ = tx.send(event);
+                }
+            }
         }
-        thread::sleep(config.idle_poll);
+        let until_deep = match last_deep {
+            Some(at) if config.deep_capture_on_idle && !config.deep_capture_interval.is_zero() => {
+                Some(config.deep_capture_interval.saturating_sub(at.elapsed()))
+            }
+            _ => None,
+        };
+        thread::sleep(next_poll(&config, idle, &mut backoff, until_deep));
     }
 }
 
@@ -50,22 +118,76 @@ mod tests {
             idle_enabled: false,
             idle_threshold: Duration::from_millis(60000),
             idle_poll: Duration::from_millis(1000),
+            idle_poll_max: Duration::from_millis(10_000),
             uia_enabled: false,
             uia_throttle: Duration::from_millis(1000),
             uia_text_max: 240,
             uia_max_depth: 5,
+            uia_outline_tokens: 800,
+            uia_outline_only: false,
+            uia_timeout_ms: 3000,
             enable_screenshot: false,
             screenshot_max_width: 1920,
             screenshot_max_height: 1080,
             screenshot_quality: 85,
+            screenshot_buffer_ttl: Duration::from_secs(60),
+            screenshot_timeout_ms: 2000,
             command_enabled: true,
             screenshot_format: "jpeg".into(),
+            screenshot_backend: "gdi".into(),
             uia_cache_ttl_ms: 2000,
             ws_reconnect_max_ms: 30_000,
+            ws_chunk_bytes: 256 * 1024,
             detection_enabled: false,
             detection_model_path: String::new(),
+            asset_manifest_url: String::new(),
+            asset_signing_key: String::new(),
             detection_confidence: 0.3,
             detection_input_size: 576,
+            safe_mode: false,
+            safe_mode_flag_path: String::new(),
+            permissions_path: String::new(),
+            workflows_dir: String::new(),
+            collector_id: String::new(),
+            collector_id_path: String::new(),
+            collector_name: "test".into(),
+            time_sync_interval: Duration::from_secs(60),
+            enrich_stages: Vec::new(),
+            geometry_enabled: false,
+            redaction_enabled: false,
+            redact_pattern: String::new(),
+            deep_capture_on_idle: false,
+            deep_capture_interval: Duration::from_secs(300),
+            deep_uia_max_depth: 12,
+            deep_uia_text_max: 4000,
+            window_inventory_interval: Duration::ZERO,
+            self_exclude_processes: vec!["desktopai.exe".into()],
+            allow_self_targeting: false,
+            user_interrupt_enabled: true,
+            observe_max_per_sec: 0.0,
+            observe_cpu_budget_pct: 0.0,
+            power_profiles_enabled: false,
+            battery_observe_max_per_sec: 0.0,
+            battery_screenshot_max_width: 640,
+            battery_screenshot_max_height: 480,
+            command_record_dir: String::new(),
+            text_normalize_enabled: false,
+            language_detect_enabled: false,
+            icon_enabled: false,
+            capture_timings_enabled: false,
+            telemetry_enabled: false,
+            telemetry_url: String::new(),
+            telemetry_interval: Duration::from_secs(3600),
+            otlp_endpoint: String::new(),
+            otel_service_name: "desktopai-collector".to_string(),
+            event_tap_port: 0,
+            event_tap_token: String::new(),
+            webhooks_path: String::new(),
+            routing_path: String::new(),
+            event_profile: String::new(),
+            data_dir: String::new(),
+            data_max_mb: 0,
+            data_retention_days: 0,
         };
 
         // Should return immediately when idle_enabled is false
@@ -76,6 +198,71 @@ mod tests {
         assert!(rx.try_recv().is_err());
     }
 
+    #[test]
+    fn test_deep_capture_due() {
+        let mut config = Config::from_env();
+        config.deep_capture_on_idle = true;
+        config.deep_capture_interval = Duration::from_secs(300);
+
+        // Never while active, always on entering idle
+        assert!(!deep_capture_due(&config, false, true, None));
+        assert!(deep_capture_due(&config, true, true, Some(Instant::now())));
+        // Still idle: wait for the interval
+        assert!(!deep_capture_due(&config, true, false, Some(Instant::now())));
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(301));
+        if long_ago.is_some() {
+            assert!(deep_capture_due(&config, true, false, long_ago));
+        }
+
+        // Zero interval: once per idle period
+        config.deep_capture_interval = Duration::ZERO;
+        assert!(!deep_capture_due(&config, true, false, Some(Instant::now())));
+
+        config.deep_capture_on_idle = false;
+        assert!(!deep_capture_due(&config, true, true, None));
+    }
+
+    #[test]
+    fn test_next_poll_sleeps_to_threshold_then_backs_off() {
+        let mut config = Config::from_env();
+        config.idle_threshold = Duration::from_secs(60);
+        config.idle_poll = Duration::from_secs(1);
+        config.idle_poll_max = Duration::from_secs(10);
+        let mut backoff = config.idle_poll;
+
+        assert_eq!(next_poll(&config, Some(15_000), &mut backoff, None), Duration::from_secs(45));
+        assert_eq!(next_poll(&config, Some(59_900), &mut backoff, None), Duration::from_secs(1), "never below idle_poll");
+        assert_eq!(next_poll(&config, None, &mut backoff, None), Duration::from_secs(1));
+
+        let idle: Vec<u64> = (0..6).map(|_| next_poll(&config, Some(90_000), &mut backoff, None).as_secs()).collect();
+        assert_eq!(idle, [1, 2, 4, 8, 10, 10]);
+        assert_eq!(next_poll(&config, Some(90_000), &mut backoff, Some(Duration::from_secs(3))), Duration::from_secs(3));
+        // Back to active: the next idle period backs off afresh
+        next_poll(&config, Some(0), &mut backoff, None);
+        assert_eq!(next_poll(&config, Some(60_000), &mut backoff, None), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_idle_hour_wakes_far_less_than_fixed_polling() {
+        let mut config = Config::from_env();
+        config.idle_threshold = Duration::from_secs(60);
+        config.idle_poll = Duration::from_secs(1);
+        config.idle_poll_max = Duration::from_secs(10);
+        let mut backoff = config.idle_poll;
+        // Last input at t=0, nothing after it for an hour
+        let (mut now, mut wakeups, mut noticed_idle) = (Duration::ZERO, 0, None);
+        while now < Duration::from_secs(3600) {
+            let idle_ms = now.as_millis() as u64;
+            if noticed_idle.is_none() && now >= config.idle_threshold {
+                noticed_idle = Some(now);
+            }
+            now += next_poll(&config, Some(idle_ms), &mut backoff, None);
+            wakeups += 1;
+        }
+        assert_eq!(noticed_idle, Some(Duration::from_secs(60)), "idle is noticed on time");
+        assert!(wakeups < 400, "{wakeups} wakeups, fixed polling would take 3600");
+    }
+
     #[test]
     fn test_idle_threshold_comparison() {
         let threshold_ms = 60000u64;
@@ -0,0 +1,279 @@
+//! User-interrupt detection while automation injects input.
+//!
+//! While a command from the `input` permission category runs (clicks,
+//! typing, scrolling, gestures; see `permissions`), low-level mouse and
+//! keyboard hooks watch for the user. Input carrying the injected flag is
+//! the collector's own and is ignored. A real key press, mouse button or
+//! wheel turn, or the pointer travelling more than `MOVE_THRESHOLD_PX`,
+//! means the user has taken over: the command is cancelled through its
+//! token (see `cancel`) and answered with a `UserInterrupted` failure, so
+//! the backend stops driving the desktop. Mouse messages promoted from
+//! touch or pen contacts are ignored, because synthetic pointer input
+//! produces them too. `USER_INTERRUPT_ENABLED=false` turns this off.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::command::Command;
+use crate::config::Config;
+
+/// Pointer travel, in pixels, that counts as the user moving the mouse;
+/// below it a bumped desk does not stop a command.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MOVE_THRESHOLD_PX: i32 = 12;
+
+/// Decides when user input amounts to an interruption.
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, Default)]
+struct Detector {
+    /// Last known pointer position, injected moves included.
+    last: Option<(i32, i32)>,
+    /// Distance the user has moved the pointer so far.
+    travel: i32,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl Detector {
+    fn new(cursor: Option<(i32, i32)>) -> Self {
+        Self { last: cursor, travel: 0 }
+    }
+
+    /// A pointer move to (x, y); true once the user's moves add up.
+    fn moved(&mut self, x: i32, y: i32, injected: bool) -> bool {
+        let last = self.last.replace((x, y));
+        if injected {
+            return false;
+        }
+        if let Some((last_x, last_y)) = last {
+            self.travel += (x - last_x).abs() + (y - last_y).abs();
+        }
+        self.travel > MOVE_THRESHOLD_PX
+    }
+}
+
+/// The command being watched; one at a time.
+#[cfg_attr(not(windows), allow(dead_code))]
+struct Watch {
+    command_id: String,
+    detector: Detector,
+    reason: Arc<OnceLock<&'static str>>,
+}
+
+static WATCH: Mutex<Option<Watch>> = Mutex::new(None);
+
+fn watch_state() -> std::sync::MutexGuard<'static, Option<Watch>> {
+    WATCH.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Interrupt the watched command, once, naming the input that did it.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn interrupt(watch: &Watch, source: &'static str) {
+    if watch.reason.set(source).is_ok() {
+        log::info!("User {source} input interrupted command {}", watch.command_id);
+        crate::cancel::cancel(&watch.command_id);
+    }
+}
+
+/// Watches for the user while a command runs; unhooks when dropped.
+pub struct Guard {
+    reason: Arc<OnceLock<&'static str>>,
+    #[cfg(windows)]
+    hook_thread: Option<(u32, std::thread::JoinHandle<()>)>,
+}
+
+impl Guard {
+    /// The kind of user input ("keyboard" or "mouse") that interrupted the
+    /// command, if any did.
+    pub fn interrupted(&self) -> Option<&'static str> {
+        self.reason.get().copied()
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut watch = watch_state();
+        if watch.as_ref().is_some_and(|w| Arc::ptr_eq(&w.reason, &self.reason)) {
+            *watch = None;
+        }
+        drop(watch);
+        #[cfg(windows)]
+        if let Some((thread_id, thread)) = self.hook_thread.take() {
+            use ::windows::Win32::Foundation::{LPARAM, WPARAM};
+            use ::windows::Win32::UI::WindowsAndMessaging::{PostThreadMessageW, WM_QUIT};
+            unsafe {
+                let _ = PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Whether `action` is watched for user interruptions.
+pub fn watched(action: &str, config: &Config) -> bool {
+    config.user_interrupt_enabled && crate::permissions::category(action) == Some("input")
+}
+
+/// Start watching for the user while `cmd` runs. `None` when the action
+/// injects no input, watching is off, another command is already watched
+/// (e.g. the branch of `if_exists`) or the hooks cannot be installed.
+#[cfg(windows)]
+pub fn watch(cmd: &Command, config: &Config) -> Option<Guard> {
+    use ::windows::Win32::Foundation::POINT;
+    use ::windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+    if !watched(&cmd.action, config) {
+        return None;
+    }
+    let reason = Arc::new(OnceLock::new());
+    {
+        let mut watch = watch_state();
+        if watch.is_some() {
+            return None;
+        }
+        let mut cursor = POINT::default();
+        let cursor = unsafe { GetCursorPos(&mut cursor) }.ok().map(|_| (cursor.x, cursor.y));
+        *watch = Some(Watch { command_id: cmd.command_id.clone(), detector: Detector::new(cursor), reason: reason.clone() });
+    }
+    // Keep the guard from here on, so a failed start clears the watch
+    let mut guard = Guard { reason, hook_thread: None };
+
+    let (ready_tx, ready) = crossbeam_channel::bounded(1);
+    let thread = std::thread::spawn(move || hook_loop(ready_tx));
+    match ready.recv_timeout(std::time::Duration::from_secs(1)) {
+        Ok(Ok(thread_id)) => {
+            guard.hook_thread = Some((thread_id, thread));
+            Some(guard)
+        }
+        Ok(Err(e)) => {
+            log::warn!("User interrupt detection unavailable: {e}");
+            None
+        }
+        Err(_) => {
+            log::warn!("User interrupt detection unavailable: hook thread did not start");
+            None
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn watch(_cmd: &Command, _config: &Config) -> Option<Guard> {
+    None
+}
+
+/// Install both hooks on this thread, report its ID and pump messages
+/// until `WM_QUIT`; low-level hooks are called on the installing thread.
+#[cfg(windows)]
+fn hook_loop(ready: crossbeam_channel::Sender<Result<u32, String>>) {
+    use ::windows::Win32::Foundation::{HINSTANCE, HWND};
+    use ::windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use ::windows::Win32::System::Threading::GetCurrentThreadId;
+    use ::windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetMessageW, SetWindowsHookExW, UnhookWindowsHookEx, MSG, WH_KEYBOARD_LL, WH_MOUSE_LL,
+    };
+
+    unsafe {
+        let instance = HINSTANCE(GetModuleHandleW(None).map(|m| m.0).unwrap_or_default());
+        let mouse = match SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), instance, 0) {
+            Ok(hook) => hook,
+            Err(e) => {
+                let _ = ready.send(Err(format!("mouse hook failed: {e}")));
+                return;
+            }
+        };
+        let keyboard = match SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook), instance, 0) {
+            Ok(hook) => hook,
+            Err(e) => {
+                let _ = UnhookWindowsHookEx(mouse);
+                let _ = ready.send(Err(format!("keyboard hook failed: {e}")));
+                return;
+            }
+        };
+        let _ = ready.send(Ok(GetCurrentThreadId()));
+
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, HWND(0), 0, 0).as_bool() {
+            DispatchMessageW(&msg);
+        }
+        let _ = UnhookWindowsHookEx(keyboard);
+        let _ = UnhookWindowsHookEx(mouse);
+    }
+}
+
+/// `dwExtraInfo` signature of mouse messages promoted from touch or pen.
+#[cfg(windows)]
+const PROMOTED_POINTER_SIGNATURE: usize = 0xFF51_5700;
+
+#[cfg(windows)]
+unsafe extern "system" fn mouse_hook(
+    code: i32,
+    wparam: ::windows::Win32::Foundation::WPARAM,
+    lparam: ::windows::Win32::Foundation::LPARAM,
+) -> ::windows::Win32::Foundation::LRESULT {
+    use ::windows::Win32::UI::WindowsAndMessaging::*;
+
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        let injected = info.flags & (LLMHF_INJECTED | LLMHF_LOWER_IL_INJECTED) != 0
+            || info.dwExtraInfo & 0xFFFF_FF00 == PROMOTED_POINTER_SIGNATURE;
+        if let Some(watch) = watch_state().as_mut() {
+            let taken_over = if wparam.0 as u32 == WM_MOUSEMOVE {
+                watch.detector.moved(info.pt.x, info.pt.y, injected)
+            } else {
+                !injected
+            };
+            if taken_over {
+                interrupt(watch, "mouse");
+            }
+        }
+    }
+    CallNextHookEx(HHOOK(0), code, wparam, lparam)
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn keyboard_hook(
+    code: i32,
+    wparam: ::windows::Win32::Foundation::WPARAM,
+    lparam: ::windows::Win32::Foundation::LPARAM,
+) -> ::windows::Win32::Foundation::LRESULT {
+    use ::windows::Win32::UI::WindowsAndMessaging::*;
+
+    if code == HC_ACTION as i32 && matches!(wparam.0 as u32, WM_KEYDOWN | WM_SYSKEYDOWN) {
+        let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let injected = info.flags.0 & (LLKHF_INJECTED.0 | LLKHF_LOWER_IL_INJECTED.0) != 0;
+        if !injected {
+            if let Some(watch) = watch_state().as_ref() {
+                interrupt(watch, "keyboard");
+            }
+        }
+    }
+    CallNextHookEx(HHOOK(0), code, wparam, lparam)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detector_counts_only_user_travel() {
+        let mut detector = Detector::new(Some((100, 100)));
+        // The collector's own moves jump anywhere without interrupting
+        assert!(!detector.moved(800, 600, true));
+        assert!(!detector.moved(805, 600, false), "a nudge is tolerated");
+        assert!(!detector.moved(300, 300, true));
+        assert!(!detector.moved(304, 302, false));
+        assert!(detector.moved(310, 302, false), "travel adds up across moves");
+
+        let mut unknown_start = Detector::new(None);
+        assert!(!unknown_start.moved(50, 50, false));
+        assert!(unknown_start.moved(50, 80, false));
+    }
+
+    #[test]
+    fn test_only_input_actions_are_watched() {
+        let mut config = Config::from_env();
+        config.user_interrupt_enabled = true;
+        assert!(watched("click", &config) && watched("type_text", &config) && watched("swipe", &config));
+        assert!(!watched("observe", &config) && !watched("run_shell", &config) && !watched("focus_window", &config));
+        config.user_interrupt_enabled = false;
+        assert!(!watched("click", &config));
+    }
+}
@@ -0,0 +1,199 @@
+//! Top-level window inventory: periodic snapshots diffed into
+//! opened/closed/renamed events, so the backend's workspace model heals even
+//! when individual WinEvents are missed.
+
+use crossbeam_channel::Sender;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::thread;
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+
+#[cfg(windows)]
+use crate::windows::enumerate_top_level_windows;
+
+#[cfg(not(windows))]
+fn enumerate_top_level_windows() -> Option<Vec<WindowInfo>> {
+    // Stub for non-Windows platforms in tests
+    None
+}
+
+/// A visible, unowned top-level window.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WindowInfo {
+    pub hwnd: String,
+    pub title: String,
+    pub process_exe: String,
+    pub pid: u32,
+}
+
+/// One window that appeared, disappeared or changed title between snapshots.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WindowChange {
+    /// "opened", "closed" or "renamed".
+    pub change: String,
+    pub hwnd: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_title: Option<String>,
+    pub process_exe: String,
+    pub pid: u32,
+}
+
+impl WindowChange {
+    fn new(change: &str, window: &WindowInfo, previous_title: Option<String>) -> Self {
+        Self {
+            change: change.to_string(),
+            hwnd: window.hwnd.clone(),
+            title: window.title.clone(),
+            previous_title,
+            process_exe: window.process_exe.clone(),
+            pid: window.pid,
+        }
+    }
+}
+
+/// Diff two snapshots keyed by hwnd. A handle reused by a different process
+/// is reported as closed + opened rather than renamed.
+pub fn diff_inventory(previous: &HashMap<String, WindowInfo>, current: &[WindowInfo]) -> Vec<WindowChange> {
+    let mut changes = Vec::new();
+    let mut seen = HashSet::with_capacity(current.len());
+
+    for window in current {
+        seen.insert(window.hwnd.as_str());
+        match previous.get(&window.hwnd) {
+            None => changes.push(WindowChange::new("opened", window, None)),
+            Some(old) if old.pid != window.pid => {
+                changes.push(WindowChange::new("closed", old, None));
+                changes.push(WindowChange::new("opened", window, None));
+            }
+            Some(old) if old.title != window.title => {
+                changes.push(WindowChange::new("renamed", window, Some(old.title.clone())));
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Sorted so closed events come out in a stable order
+    let closed: BTreeMap<&String, &WindowInfo> =
+        previous.iter().filter(|(hwnd, _)| !seen.contains(hwnd.as_str())).collect();
+    for window in closed.values() {
+        changes.push(WindowChange::new("closed", window, None));
+    }
+
+    changes
+}
+
+/// Build the `window_inventory` event carrying a batch of changes.
+pub fn build_inventory_event(changes: Vec<WindowChange>, window_count: usize) -> WindowEvent {
+    let mut event = crate::event::build_activity_event("window_inventory", 0);
+    event.idle_ms = None;
+    event.window_count = Some(window_count as u32);
+    event.window_changes = changes;
+    event
+}
+
+/// Poll the top-level window list every `window_inventory_interval` and send a
+/// diff event whenever something changed. The first snapshot reports every
+/// window as opened so the backend starts from a full picture. DesktopAI's own
+/// windows are left out. A failed enumeration skips that pass only.
+pub fn inventory_worker(tx: Sender<WindowEvent>, config: Config) {
+    if config.window_inventory_interval.is_zero() {
+        return;
+    }
+    let mut previous: HashMap<String, WindowInfo> = HashMap::new();
+    loop {
+        let Some(mut current) = enumerate_top_level_windows() else {
+            // Nothing to enumerate off Windows; on Windows a failed pass is retried
+            if cfg!(not(windows)) {
+                return;
+            }
+            log::warn!("Window inventory: could not enumerate windows; retrying");
+            thread::sleep(config.window_inventory_interval);
+            continue;
+        };
+        current.retain(|w| !crate::policy::is_self_process(&w.process_exe, &config));
+        if config.text_normalize_enabled {
+            for window in &mut current {
+                window.title = crate::text::normalize_line(&window.title);
+            }
+        }
+        let changes = diff_inventory(&previous, &current);
+        if !changes.is_empty() {
+            log::debug!("Window inventory: {} change(s), {} windows", changes.len(), current.len());
+            if tx.send(build_inventory_event(changes, current.len())).is_err() {
+                return;
+            }
+        }
+        previous = current.into_iter().map(|w| (w.hwnd.clone(), w)).collect();
+        thread::sleep(config.window_inventory_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(hwnd: &str, title: &str, pid: u32) -> WindowInfo {
+        WindowInfo {
+            hwnd: hwnd.to_string(),
+            title: title.to_string(),
+            process_exe: "app.exe".to_string(),
+            pid,
+        }
+    }
+
+    fn index(windows: &[WindowInfo]) -> HashMap<String, WindowInfo> {
+        windows.iter().map(|w| (w.hwnd.clone(), w.clone())).collect()
+    }
+
+    #[test]
+    fn test_first_snapshot_all_opened() {
+        let current = vec![window("0x1", "A", 1), window("0x2", "B", 2)];
+        let changes = diff_inventory(&HashMap::new(), &current);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.change == "opened"));
+    }
+
+    #[test]
+    fn test_opened_closed_renamed() {
+        let previous = index(&[window("0x1", "Inbox", 1), window("0x2", "Notes", 2)]);
+        let current = vec![window("0x1", "Inbox (3)", 1), window("0x3", "Calc", 3)];
+        let changes = diff_inventory(&previous, &current);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].change, "renamed");
+        assert_eq!(changes[0].previous_title.as_deref(), Some("Inbox"));
+        assert_eq!(changes[0].title, "Inbox (3)");
+        assert_eq!(changes[1].change, "opened");
+        assert_eq!(changes[1].hwnd, "0x3");
+        assert_eq!(changes[2].change, "closed");
+        assert_eq!(changes[2].hwnd, "0x2");
+    }
+
+    #[test]
+    fn test_unchanged_snapshot_is_empty() {
+        let windows = vec![window("0x1", "A", 1)];
+        assert!(diff_inventory(&index(&windows), &windows).is_empty());
+    }
+
+    #[test]
+    fn test_reused_handle_is_close_and_open() {
+        let previous = index(&[window("0x1", "Old", 1)]);
+        let current = vec![window("0x1", "New", 2)];
+        let changes: Vec<_> = diff_inventory(&previous, &current).into_iter().map(|c| c.change).collect();
+        assert_eq!(changes, vec!["closed", "opened"]);
+    }
+
+    #[test]
+    fn test_inventory_event_serialization() {
+        let event = build_inventory_event(vec![WindowChange::new("opened", &window("0x1", "A", 1), None)], 1);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "window_inventory");
+        assert_eq!(json["window_count"], 1);
+        assert_eq!(json["window_changes"][0]["change"], "opened");
+        assert!(json["window_changes"][0].get("previous_title").is_none());
+        assert!(json.get("idle_ms").is_none());
+    }
+}
@@ -0,0 +1,416 @@
+//! Numbered element labels for "click by number".
+//!
+//! `show_element_labels` numbers the interactive elements of a window (UIA
+//! elements with an Invoke, Toggle or Value pattern, plus detector boxes
+//! that no UIA element covers) and draws a badge with each number over the
+//! screen in a transparent, click-through, topmost window owned by the
+//! collector. The badges stay up until `hide_element_labels`, so the user
+//! and the model can both talk about "element 14" while a plan is debugged
+//! or approved; pointer commands accept `label` in place of `x`/`y`.
+//! The overlay is visible in screenshots on purpose: a vision model reads
+//! the numbers off the capture.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::event::UiaElement;
+
+/// Labels drawn unless the command asks for fewer.
+pub const DEFAULT_LABELS: usize = 99;
+/// Most labels one overlay shows.
+pub const MAX_LABELS: usize = 300;
+
+/// Badge height and per-digit width, in pixels.
+const BADGE_HEIGHT: i32 = 18;
+const BADGE_DIGIT_WIDTH: i32 = 8;
+/// Boxes overlapping this much (intersection over union) are one element.
+const SAME_ELEMENT_OVERLAP: f64 = 0.5;
+
+/// One numbered element on screen.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Label {
+    pub number: usize,
+    /// Screen rectangle `[x, y, width, height]`.
+    pub rect: [i32; 4],
+    pub name: String,
+    pub control_type: String,
+    pub automation_id: String,
+    /// `uia` or `detection`.
+    pub source: &'static str,
+}
+
+impl Label {
+    pub fn center(&self) -> (i32, i32) {
+        let [x, y, w, h] = self.rect;
+        (x + w / 2, y + h / 2)
+    }
+
+    /// Screen rectangle of the badge: over the element's top-left corner,
+    /// moved inside `bounds` when the element reaches past its edge.
+    pub fn badge(&self, bounds: [i32; 4]) -> [i32; 4] {
+        let width = BADGE_DIGIT_WIDTH * self.number.to_string().len() as i32 + BADGE_DIGIT_WIDTH;
+        let [bx, by, bw, bh] = bounds;
+        let x = self.rect[0].clamp(bx, (bx + bw - width).max(bx));
+        let y = self.rect[1].clamp(by, (by + bh - BADGE_HEIGHT).max(by));
+        [x, y, width, BADGE_HEIGHT]
+    }
+}
+
+/// Labels currently on screen, numbered from 1.
+static SHOWN: Mutex<Vec<Label>> = Mutex::new(Vec::new());
+
+/// Whether an element is worth a number: enabled, on screen, with an area
+/// and a pattern a command can drive.
+pub fn is_interactive(element: &UiaElement) -> bool {
+    element.is_enabled
+        && !element.is_offscreen
+        && !element.patterns.is_empty()
+        && element.bounding_rect.is_some_and(|[_, _, w, h]| w > 0 && h > 0)
+}
+
+/// Number the interactive elements of `root` in depth-first order, at most
+/// `limit` of them. An element covering the same box as one already
+/// numbered (a button and its text, say) is skipped.
+pub fn from_tree(root: &UiaElement, limit: usize) -> Vec<Label> {
+    let mut labels = Vec::new();
+    walk(root, limit, &mut labels);
+    labels
+}
+
+fn walk(element: &UiaElement, limit: usize, labels: &mut Vec<Label>) {
+    if labels.len() >= limit {
+        return;
+    }
+    if let Some(rect) = element.bounding_rect.filter(|_| is_interactive(element)) {
+        if !labels.iter().any(|label| overlap(label.rect, rect) >= SAME_ELEMENT_OVERLAP) {
+            labels.push(Label {
+                number: labels.len() + 1,
+                rect,
+                name: element.name.clone(),
+                control_type: element.control_type.clone(),
+                automation_id: element.automation_id.clone(),
+                source: "uia",
+            });
+        }
+    }
+    for child in &element.children {
+        walk(child, limit, labels);
+    }
+}
+
+/// Number detector boxes (screen rectangles) after the UIA elements,
+/// skipping any that an existing label already covers.
+pub fn add_detections(labels: &mut Vec<Label>, boxes: &[[i32; 4]], limit: usize) {
+    for &rect in boxes {
+        if labels.len() >= limit {
+            break;
+        }
+        if rect[2] <= 0 || rect[3] <= 0 || labels.iter().any(|label| overlap(label.rect, rect) >= SAME_ELEMENT_OVERLAP) {
+            continue;
+        }
+        labels.push(Label {
+            number: labels.len() + 1,
+            rect,
+            name: String::new(),
+            control_type: String::new(),
+            automation_id: String::new(),
+            source: "detection",
+        });
+    }
+}
+
+/// Intersection over union of two `[x, y, width, height]` rectangles.
+fn overlap(a: [i32; 4], b: [i32; 4]) -> f64 {
+    let width = (a[0] + a[2]).min(b[0] + b[2]) - a[0].max(b[0]);
+    let height = (a[1] + a[3]).min(b[1] + b[3]) - a[1].max(b[1]);
+    if width <= 0 || height <= 0 {
+        return 0.0;
+    }
+    let intersection = f64::from(width) * f64::from(height);
+    let union = f64::from(a[2]) * f64::from(a[3]) + f64::from(b[2]) * f64::from(b[3]) - intersection;
+    intersection / union
+}
+
+/// Replace the labels on screen.
+pub fn set_shown(labels: Vec<Label>) {
+    *SHOWN.lock().unwrap_or_else(|e| e.into_inner()) = labels;
+}
+
+/// The labels on screen; empty when the overlay is hidden.
+pub fn shown() -> Vec<Label> {
+    SHOWN.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Screen center of label `number`, if it is on screen.
+pub fn center(number: usize) -> Option<(i32, i32)> {
+    SHOWN
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|label| label.number == number)
+        .map(Label::center)
+}
+
+#[cfg(windows)]
+pub use overlay::{hide, show};
+
+#[cfg(windows)]
+mod overlay {
+    use std::sync::Mutex;
+    use std::thread::JoinHandle;
+
+    use windows::core::w;
+    use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, RECT, WPARAM};
+    use windows::Win32::Graphics::Gdi::*;
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    /// Painted where the overlay is see-through.
+    const TRANSPARENT_KEY: COLORREF = COLORREF(0x00FF00FF);
+    const OUTLINE: COLORREF = COLORREF(0x0000A5FF);
+    const BADGE: COLORREF = COLORREF(0x000050E0);
+    const BADGE_TEXT: COLORREF = COLORREF(0x00FFFFFF);
+
+    struct Overlay {
+        hwnd: HWND,
+        thread: JoinHandle<()>,
+    }
+
+    // The handle is only posted to; the window lives on its own thread.
+    unsafe impl Send for Overlay {}
+
+    static OVERLAY: Mutex<Option<Overlay>> = Mutex::new(None);
+
+    fn virtual_screen() -> [i32; 4] {
+        unsafe {
+            [
+                GetSystemMetrics(SM_XVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN),
+                GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                GetSystemMetrics(SM_CYVIRTUALSCREEN),
+            ]
+        }
+    }
+
+    fn client_rect([x, y, w, h]: [i32; 4], origin: [i32; 4]) -> RECT {
+        RECT { left: x - origin[0], top: y - origin[1], right: x - origin[0] + w, bottom: y - origin[1] + h }
+    }
+
+    unsafe fn paint(hwnd: HWND) {
+        let mut ps = PAINTSTRUCT::default();
+        let hdc = BeginPaint(hwnd, &mut ps);
+        let screen = virtual_screen();
+        let background = CreateSolidBrush(TRANSPARENT_KEY);
+        let outline = CreateSolidBrush(OUTLINE);
+        let badge = CreateSolidBrush(BADGE);
+        let font = CreateFontW(
+            -13,
+            0,
+            0,
+            0,
+            FW_BOLD.0 as i32,
+            0,
+            0,
+            0,
+            DEFAULT_CHARSET.0 as u32,
+            OUT_DEFAULT_PRECIS.0 as u32,
+            CLIP_DEFAULT_PRECIS.0 as u32,
+            NONANTIALIASED_QUALITY.0 as u32,
+            0,
+            w!("Segoe UI"),
+        );
+        let previous_font = SelectObject(hdc, font);
+        FillRect(hdc, &ps.rcPaint, background);
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, BADGE_TEXT);
+        for label in super::shown() {
+            FrameRect(hdc, &client_rect(label.rect, screen), outline);
+            let mut rect = client_rect(label.badge(screen), screen);
+            FillRect(hdc, &rect, badge);
+            let mut text: Vec<u16> = label.number.to_string().encode_utf16().collect();
+            DrawTextW(hdc, &mut text, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+        }
+        SelectObject(hdc, previous_font);
+        let _ = DeleteObject(font);
+        let _ = DeleteObject(background);
+        let _ = DeleteObject(outline);
+        let _ = DeleteObject(badge);
+        let _ = EndPaint(hwnd, &ps);
+    }
+
+    unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match msg {
+            WM_PAINT => {
+                paint(hwnd);
+                LRESULT(0)
+            }
+            WM_ERASEBKGND => LRESULT(1),
+            WM_NCHITTEST => LRESULT(HTTRANSPARENT as isize),
+            WM_DESTROY => {
+                PostQuitMessage(0);
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    fn open() -> Result<Overlay, String> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn(move || unsafe {
+            let instance = GetModuleHandleW(None).unwrap_or_default();
+            let class = w!("DesktopAIElementLabels");
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(wndproc),
+                hInstance: instance.into(),
+                lpszClassName: class,
+                ..Default::default()
+            };
+            // Fails harmlessly when an earlier overlay registered it
+            RegisterClassW(&wc);
+
+            let [x, y, width, height] = virtual_screen();
+            let hwnd = CreateWindowExW(
+                WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+                class,
+                w!("DesktopAI Element Labels"),
+                WS_POPUP,
+                x,
+                y,
+                width,
+                height,
+                None,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 != 0 {
+                let _ = SetLayeredWindowAttributes(hwnd, TRANSPARENT_KEY, 0, LWA_COLORKEY);
+                ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+            }
+            let _ = tx.send(hwnd);
+            if hwnd.0 == 0 {
+                return;
+            }
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        });
+        match rx.recv() {
+            Ok(hwnd) if hwnd.0 != 0 => Ok(Overlay { hwnd, thread }),
+            _ => {
+                let _ = thread.join();
+                Err("could not create the label overlay window".to_string())
+            }
+        }
+    }
+
+    /// Draw `labels`, opening the overlay or repainting the open one.
+    pub fn show(labels: Vec<super::Label>) -> Result<(), String> {
+        super::set_shown(labels);
+        let mut overlay = OVERLAY.lock().unwrap_or_else(|e| e.into_inner());
+        match overlay.as_ref() {
+            Some(open) => unsafe {
+                // The desktop may have been rearranged since it opened
+                let [x, y, width, height] = virtual_screen();
+                let _ = SetWindowPos(open.hwnd, HWND_TOPMOST, x, y, width, height, SWP_NOACTIVATE);
+                InvalidateRect(open.hwnd, None, true);
+            },
+            None => *overlay = Some(open()?),
+        }
+        Ok(())
+    }
+
+    /// Close the overlay; false if it was not open.
+    pub fn hide() -> bool {
+        super::set_shown(Vec::new());
+        let Some(open) = OVERLAY.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return false;
+        };
+        unsafe {
+            let _ = PostMessageW(open.hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
+        }
+        let _ = open.thread.join();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(name: &str, rect: [i32; 4], patterns: &[&str], children: Vec<UiaElement>) -> UiaElement {
+        UiaElement {
+            name: name.to_string(),
+            control_type: "button".to_string(),
+            bounding_rect: Some(rect),
+            is_enabled: true,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_labels_from_tree() {
+        let mut disabled = element("Disabled", [300, 0, 50, 20], &["Invoke"], vec![]);
+        disabled.is_enabled = false;
+        let tree = element(
+            "Window",
+            [0, 0, 800, 600],
+            &[],
+            vec![
+                // A button and the text inside it are one element
+                element("OK", [10, 10, 80, 24], &["Invoke"], vec![element("OK", [12, 12, 76, 20], &["Value"], vec![])]),
+                disabled,
+                element("Name", [10, 50, 200, 24], &["Value"], vec![]),
+                element("Empty", [10, 90, 0, 0], &["Invoke"], vec![]),
+            ],
+        );
+
+        let labels = from_tree(&tree, DEFAULT_LABELS);
+        let names: Vec<(usize, &str)> = labels.iter().map(|l| (l.number, l.name.as_str())).collect();
+        assert_eq!(names, vec![(1, "OK"), (2, "Name")]);
+        assert_eq!(labels[1].center(), (110, 62));
+        assert_eq!(from_tree(&tree, 1).len(), 1);
+
+        let mut labels = labels;
+        add_detections(&mut labels, &[[11, 11, 80, 24], [400, 400, 40, 40]], DEFAULT_LABELS);
+        assert_eq!(labels.len(), 3, "the box over OK is not numbered again");
+        assert_eq!((labels[2].number, labels[2].source), (3, "detection"));
+    }
+
+    #[test]
+    fn test_badge_stays_on_screen() {
+        let label = |number, rect| Label {
+            number,
+            rect,
+            name: String::new(),
+            control_type: String::new(),
+            automation_id: String::new(),
+            source: "uia",
+        };
+        let screen = [0, 0, 1920, 1080];
+        assert_eq!(label(7, [100, 200, 50, 20]).badge(screen), [100, 200, 16, 18]);
+        assert_eq!(label(14, [-30, -5, 50, 20]).badge(screen), [0, 0, 24, 18]);
+        assert_eq!(label(120, [1910, 1075, 50, 20]).badge(screen), [1888, 1062, 32, 18]);
+    }
+
+    #[test]
+    fn test_center_of_shown_label() {
+        set_shown(vec![Label {
+            number: 14,
+            rect: [100, 100, 40, 20],
+            name: "Send".to_string(),
+            control_type: "button".to_string(),
+            automation_id: String::new(),
+            source: "uia",
+        }]);
+        assert_eq!(center(14), Some((120, 110)));
+        assert_eq!(center(15), None);
+        set_shown(Vec::new());
+        assert_eq!(center(14), None);
+    }
+}
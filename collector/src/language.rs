@@ -0,0 +1,104 @@
+//! Language detection for captured text.
+//!
+//! A trigram pass (whatlang) over the document text of an event, or the
+//! text OCR read off the screen, names its language so the backend can pick
+//! a prompt or model for it without running inference of its own. Codes
+//! are ISO 639-3 (`eng`, `deu`, `jpn`). Text too short to judge gives no
+//! answer; otherwise the confidence tells a clear result from a close call.
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+use crate::pipeline::{EnrichContext, EnrichStage};
+
+/// Fewer letters than this are not worth a guess.
+const MIN_LETTERS: usize = 12;
+/// Characters examined; the start of a long document decides.
+const SAMPLE_CHARS: usize = 2000;
+
+/// The language of a piece of text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextLanguage {
+    /// ISO 639-3 code.
+    pub code: &'static str,
+    /// Writing system, e.g. `Latin` or `Cyrillic`.
+    pub script: String,
+    /// 0..1, the margin over the runner-up language; closely related
+    /// languages (English and Scots, Spanish and Galician) keep it low,
+    /// and above 0.9 the detector calls it reliable.
+    pub confidence: f64,
+}
+
+/// Detect the language of `text`; `None` when it is too short to judge.
+pub fn detect(text: &str) -> Option<TextLanguage> {
+    let sample: String = text.chars().take(SAMPLE_CHARS).collect();
+    if sample.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+    let info = whatlang::detect(&sample)?;
+    Some(TextLanguage {
+        code: info.lang().code(),
+        script: info.script().name().to_string(),
+        confidence: (info.confidence() * 100.0).round() / 100.0,
+    })
+}
+
+/// Tags the event with the language of its document text, or of the
+/// focused element's value when the window exposes no document.
+pub struct LanguageStage;
+
+impl EnrichStage for LanguageStage {
+    fn name(&self) -> &'static str {
+        "language"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.language_detect_enabled
+    }
+
+    fn run(&self, _ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        let Some(snapshot) = event.uia.as_ref() else {
+            return;
+        };
+        let focused_value = snapshot.focused_element.as_ref().and_then(|e| e.value.as_deref()).unwrap_or("");
+        event.language = detect(&snapshot.document_text).or_else(|| detect(focused_value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{build_activity_event, UiaElement, UiaSnapshot};
+
+    #[test]
+    fn test_detects_common_languages() {
+        let english = detect("The quick brown fox jumps over the lazy dog and keeps running through the field.").unwrap();
+        assert_eq!(english.code, "eng");
+        assert_eq!(english.script, "Latin");
+        assert_eq!(detect("Der schnelle braune Fuchs springt über den faulen Hund und läuft weiter.").unwrap().code, "deu");
+        assert_eq!(detect("Быстрая коричневая лиса прыгает через ленивую собаку.").unwrap().script, "Cyrillic");
+        assert_eq!(detect("OK"), None, "too short");
+        assert_eq!(detect("12345 67890 !!! ### 2024-01-01"), None, "no letters");
+    }
+
+    #[test]
+    fn test_stage_prefers_document_text() {
+        let mut event = build_activity_event("foreground", 0);
+        event.uia = Some(UiaSnapshot {
+            document_text: "Le renard brun rapide saute par-dessus le chien paresseux.".to_string(),
+            focused_element: Some(UiaElement {
+                value: Some("The quick brown fox jumps over the lazy dog.".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let config = Config::from_env();
+        LanguageStage.run(&mut EnrichContext::default(), &mut event, &config);
+        assert_eq!(event.language.as_ref().unwrap().code, "fra");
+
+        event.uia.as_mut().unwrap().document_text.clear();
+        LanguageStage.run(&mut EnrichContext::default(), &mut event, &config);
+        assert_eq!(event.language.as_ref().unwrap().code, "eng");
+    }
+}
@@ -9,6 +9,45 @@ pub mod config;
 pub mod event;
 pub mod network;
 pub mod idle;
+pub mod diff;
+pub mod policy;
+pub mod identity;
+pub mod clock;
+pub mod pipeline;
+pub mod inventory;
+pub mod theme;
+pub mod pacing;
+pub mod recorder;
+pub mod text;
+pub mod icon;
+pub mod local;
+pub mod telemetry;
+pub mod tap;
+pub mod webhook;
+pub mod ocr;
+pub mod state;
+pub mod datadir;
+pub mod pointer;
+pub mod clipboard;
+pub mod preview;
+pub mod watchdog;
+pub mod cancel;
+pub mod permissions;
+pub mod outline;
+pub mod labels;
+pub mod hook;
+pub mod routing;
+pub mod interrupt;
+pub mod detections;
+pub mod language;
+pub mod subscriptions;
+pub mod settle;
+pub mod power;
+pub mod diagnostics;
+pub mod chunking;
+pub mod assets;
+pub mod trace;
+pub mod workflow;
 
 #[cfg(windows)]
 pub mod uia;
@@ -16,6 +55,10 @@ pub mod uia;
 pub mod windows;
 #[cfg(windows)]
 pub mod screenshot;
+#[cfg(windows)]
+pub mod graphics_capture;
+#[cfg(windows)]
+pub mod selftest;
 
 pub mod command;
 #[cfg(feature = "detection")]
@@ -26,6 +69,8 @@ pub use config::{Config, env_bool, env_u64, env_usize, env_u32, env_u8, env_f32}
 pub use event::{WindowEvent, UiaSnapshot, UiaElement, build_activity_event};
 pub use network::{connect_ws, send_http, network_worker};
 pub use idle::idle_worker;
+pub use diff::{ScreenDiff, compare_frames};
+pub use local::LocalCollector;
 
 #[cfg(windows)]
 pub use event::{hwnd_to_hex, bstr_to_string};
@@ -37,47 +82,66 @@ pub use windows::{window_title, process_path, build_event, win_event_hook, idle_
 pub use screenshot::{capture_screenshot, init_screenshot_buffer};
 
 #[cfg(windows)]
-use crossbeam_channel::unbounded;
+use crossbeam_channel::{unbounded, Receiver};
 #[cfg(windows)]
 use std::thread;
 
-#[cfg(windows)]
-use ::windows::Win32::Foundation::HWND;
-#[cfg(windows)]
-use ::windows::Win32::UI::Accessibility::SetWinEventHook;
-#[cfg(windows)]
-use ::windows::Win32::UI::WindowsAndMessaging::{
-    DispatchMessageW, GetMessageW, TranslateMessage, EVENT_SYSTEM_FOREGROUND, MSG,
-    WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
-};
 
 /// Main entry point for the collector library
 #[cfg(windows)]
 pub fn run() {
     println!("=== DesktopAI Collector starting ===");
-    env_logger::init();
-    let config = Config::from_env();
+    diagnostics::init_logging();
+    let mut config = Config::from_env();
+    if config.collector_id.is_empty() {
+        config.collector_id = identity::load_or_create_collector_id(&config.collector_id_path);
+    }
+    println!("Collector: {} ({})", config.collector_name, config.collector_id);
     println!("Backend WS: {}", config.ws_url);
     println!("Command bridge: {}", if config.command_enabled { "enabled" } else { "disabled" });
     println!("Screenshots: {}", if config.enable_screenshot { "enabled" } else { "disabled" });
     println!("UIA: {}", if config.uia_enabled { "enabled" } else { "disabled" });
     println!("Idle detection: {}", if config.idle_enabled { "enabled" } else { "disabled" });
+    println!("Safe mode: {}", if config.safe_mode { "on" } else { "off" });
+    println!("Telemetry: {}", if telemetry::enabled(&config) { "on (anonymous aggregates)" } else { "off" });
+    println!("Event tap: {}", if tap::enabled(&config) { format!("127.0.0.1:{}", config.event_tap_port) } else { "off".to_string() });
+    println!("Data dir: {}", if config.data_dir.is_empty() { "none" } else { config.data_dir.as_str() });
+    println!("Webhooks: {}", if webhook::enabled(&config) { config.webhooks_path.as_str() } else { "off" });
+    println!("Routing: {}", if config.routing_path.is_empty() { "off" } else { config.routing_path.as_str() });
+    println!("Model downloads: {}", if assets::enabled(&config) { config.asset_manifest_url.as_str() } else { "off" });
+    println!("Tracing: {}", if trace::enabled(&config) { config.otlp_endpoint.as_str() } else { "off" });
+
+    let Some(rx) = start_observers(&config) else {
+        return;
+    };
+    hook::start();
+    network_worker(rx, config);
+}
+
+/// Set up global collector state and start the background observers (idle,
+/// window inventory, pacing catch-up). Returns the receiving end of the event
+/// channel; the foreground hook feeds it once `hook::start` runs.
+/// Only one collector can be started per process.
+#[cfg(windows)]
+pub(crate) fn start_observers(config: &Config) -> Option<Receiver<WindowEvent>> {
+    policy::set_safe_mode(config.safe_mode);
+    trace::start(config);
 
     // Initialize screenshot buffer if enabled
     if config.enable_screenshot {
-        init_screenshot_buffer();
+        init_screenshot_buffer(config);
     }
 
     // Initialize global config
     if crate::windows::CONFIG.set(config.clone()).is_err() {
         log::error!("Failed to set global config");
-        return;
+        return None;
     }
 
     let (tx, rx) = unbounded();
     if crate::windows::EVENT_SENDER.set(tx).is_err() {
         log::error!("Failed to set event sender");
-        return;
+        return None;
     }
 
     if config.idle_enabled {
@@ -86,31 +150,33 @@ pub fn run() {
         thread::spawn(move || idle_worker(idle_tx, idle_config));
     }
 
-    thread::spawn(move || network_worker(rx, config));
-
-    unsafe {
-        let hook = SetWinEventHook(
-            EVENT_SYSTEM_FOREGROUND,
-            EVENT_SYSTEM_FOREGROUND,
-            None,
-            Some(win_event_hook),
-            0,
-            0,
-            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
-        );
-        if hook.0 == 0 {
-            log::error!("Failed to install WinEvent hook");
-            return;
-        }
+    if !config.window_inventory_interval.is_zero() {
+        let inventory_tx = crate::windows::EVENT_SENDER.get().unwrap().clone();
+        let inventory_config = config.clone();
+        thread::spawn(move || inventory::inventory_worker(inventory_tx, inventory_config));
     }
 
-    unsafe {
-        let mut msg = MSG::default();
-        while GetMessageW(&mut msg, HWND(0), 0, 0).as_bool() {
-            TranslateMessage(&msg);
-            DispatchMessageW(&msg);
-        }
+    tap::start(config);
+    webhook::start(config);
+    assets::start(config);
+
+    if telemetry::enabled(config) {
+        let telemetry_config = config.clone();
+        thread::spawn(move || telemetry::telemetry_worker(telemetry_config));
     }
+
+    if datadir::retention_enabled(config) {
+        let retention_config = config.clone();
+        thread::spawn(move || datadir::retention_worker(retention_config));
+    }
+
+    if pacing::pacing_enabled(config) {
+        let observation_tx = crate::windows::EVENT_SENDER.get().unwrap().clone();
+        let observation_config = config.clone();
+        thread::spawn(move || pacing::observation_worker(observation_tx, observation_config));
+    }
+
+    Some(rx)
 }
 
 #[cfg(not(windows))]
@@ -0,0 +1,179 @@
+//! Direct local mode: the collector embedded in another process.
+//!
+//! The Tauri app can link this crate (its `local-collector` feature) and run
+//! observation and commands in-process, so a single-process "lite" deployment
+//! works without the collector executable or a backend. Nothing is sent over
+//! the network: the latest window observation is kept for queries and every
+//! event is offered to in-process subscribers.
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::command::{execute_command, Command, CommandResult};
+use crate::config::Config;
+use crate::event::WindowEvent;
+use crate::routing::{Router, Sink};
+
+/// Events queued per subscriber before new ones are dropped for it.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Event types that describe the window in front of the user.
+const WINDOW_EVENT_TYPES: &[&str] = &["foreground", "observation", "deep_capture"];
+
+type Subscribers = Arc<Mutex<Vec<Sender<WindowEvent>>>>;
+
+/// An in-process collector: observation state plus the command API.
+#[derive(Clone)]
+pub struct LocalCollector {
+    config: Config,
+    latest: Arc<Mutex<Option<WindowEvent>>>,
+    subscribers: Subscribers,
+}
+
+impl LocalCollector {
+    /// Start observing the desktop in this process: background observers
+    /// plus the supervised foreground hook thread (see `hook`). Only one collector (local
+    /// or networked) can run per process; returns `None` if one already does.
+    #[cfg(windows)]
+    pub fn start(config: Config) -> Option<Self> {
+        let rx = crate::start_observers(&config)?;
+        crate::hook::start();
+        Some(Self::with_events(config, rx))
+    }
+
+    #[cfg(not(windows))]
+    pub fn start(_config: Config) -> Option<Self> {
+        log::warn!("Local collector requires Windows");
+        None
+    }
+
+    /// Build a collector around an existing event source, e.g. a test feed.
+    pub fn with_events(config: Config, events: Receiver<WindowEvent>) -> Self {
+        let collector = Self {
+            config,
+            latest: Arc::new(Mutex::new(None)),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        };
+        let latest = collector.latest.clone();
+        let subscribers = collector.subscribers.clone();
+        let router = Router::new(&collector.config);
+        thread::spawn(move || dispatch_events(events, router, latest, subscribers));
+        collector
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Most recent foreground/observation event, if any window was seen yet.
+    pub fn current_window(&self) -> Option<WindowEvent> {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Receive every event observed from now on. A subscriber that falls
+    /// behind by `SUBSCRIBER_CAPACITY` events misses newer ones until it
+    /// catches up; dropped receivers are forgotten.
+    pub fn subscribe(&self) -> Receiver<WindowEvent> {
+        let (tx, rx) = bounded(SUBSCRIBER_CAPACITY);
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(tx);
+        rx
+    }
+
+    /// Run a command in-process, with the same policy checks as commands
+    /// from the backend.
+    pub fn execute(&self, cmd: &Command) -> CommandResult {
+        if !self.config.command_enabled {
+            return CommandResult::failure(&cmd.command_id, "command bridge is disabled");
+        }
+        execute_command(cmd, &self.config)
+    }
+
+    /// Cancel a command `execute` is running on another thread. Returns
+    /// false if no command with that id is running.
+    pub fn cancel(&self, command_id: &str) -> bool {
+        crate::cancel::cancel(command_id)
+    }
+
+    /// Convenience wrapper around `execute` for callers without a `Command`.
+    pub fn execute_action(&self, action: &str, parameters: HashMap<String, serde_json::Value>) -> CommandResult {
+        let cmd = Command {
+            command_id: format!("local-{}", uuid::Uuid::new_v4()),
+            action: action.to_string(),
+            parameters,
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        self.execute(&cmd)
+    }
+}
+
+fn dispatch_events(
+    events: Receiver<WindowEvent>,
+    router: Router,
+    latest: Arc<Mutex<Option<WindowEvent>>>,
+    subscribers: Subscribers,
+) {
+    for mut event in events {
+        // Subscribers always see the event; the routes decide the other sinks
+        let sinks = router.route(&mut event);
+        crate::preview::record(&event);
+        if sinks.contains(&Sink::Local) {
+            router.publish_local(&event);
+        }
+        if sinks.contains(&Sink::Webhook) {
+            crate::webhook::publish(&event);
+        }
+        if WINDOW_EVENT_TYPES.contains(&event.event_type.as_str()) {
+            *latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(event.clone());
+        }
+        subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|tx| !matches!(tx.try_send(event.clone()), Err(TrySendError::Disconnected(_))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+    use crossbeam_channel::unbounded;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tracks_current_window_and_fans_out() {
+        let (tx, rx) = unbounded();
+        let collector = LocalCollector::with_events(Config::from_env(), rx);
+        let events = collector.subscribe();
+
+        let mut foreground = build_activity_event("foreground", 0);
+        foreground.title = "Notepad".to_string();
+        tx.send(foreground).unwrap();
+        tx.send(build_activity_event("idle", 1000)).unwrap();
+
+        let first = events.recv_timeout(Duration::from_secs(2)).unwrap();
+        let second = events.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(first.event_type, "foreground");
+        assert_eq!(second.event_type, "idle");
+        // Idle events do not replace the current window
+        assert_eq!(collector.current_window().unwrap().title, "Notepad");
+    }
+
+    #[test]
+    fn test_execute_respects_command_bridge_flag() {
+        let (_tx, rx) = unbounded();
+        let mut config = Config::from_env();
+        config.command_enabled = false;
+        let collector = LocalCollector::with_events(config, rx);
+        let result = collector.execute_action("list_windows", HashMap::new());
+        assert!(!result.ok);
+        assert!(result.command_id.starts_with("local-"));
+    }
+}
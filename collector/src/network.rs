@@ -1,14 +1,27 @@
 //! Network layer: WebSocket connection to backend, event sending, command receiving.
 //! Uses exponential backoff for reconnection and handles ping/pong keep-alive.
+//! Large payloads go out in parts between other messages (see chunking.rs).
 
 use crossbeam_channel::Receiver;
 use socket2::SockRef;
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tungstenite::{connect, Message};
 use url::Url;
 
+use crate::chunking::Outbox;
+use crate::command::{Command, CommandResult};
 use crate::config::Config;
 use crate::event::WindowEvent;
+use crate::routing::{Router, Sink};
+use crate::subscriptions::Subscriptions;
+use crate::trace::{Span, SpanKind};
+use crate::watchdog::CommandWorker;
+
+/// Event wait per loop turn while a command runs, so its result goes out promptly.
+const BUSY_POLL: Duration = Duration::from_millis(5);
+/// How often the permission manifest file is checked for edits.
+const PERMISSIONS_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Attempt a WebSocket connection to the given URL. Returns None on failure.
 pub fn connect_ws(url: &str) -> Option<tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>> {
@@ -30,24 +43,81 @@ pub fn send_http(url: &str, event: &WindowEvent) {
     }
 }
 
+/// Stamp the collector's identity onto an outgoing event.
+pub fn stamp_identity(event: &mut WindowEvent, config: &Config) {
+    event.collector_id.clone_from(&config.collector_id);
+    event.collector_name.clone_from(&config.collector_name);
+}
+
+/// Build the hello handshake sent right after the WebSocket connects.
+pub fn build_hello(config: &Config) -> String {
+    serde_json::json!({
+        "type": "hello",
+        "collector_id": config.collector_id,
+        "collector_name": config.collector_name,
+        "version": env!("CARGO_PKG_VERSION"),
+        "platform": std::env::consts::OS,
+        "theme": crate::theme::current_theme(),
+        "permissions": crate::permissions::load(config).describe(),
+    })
+    .to_string()
+}
+
+/// Tell the backend the permission manifest changed since the hello.
+pub fn build_permissions_update(permissions: &serde_json::Value) -> String {
+    serde_json::json!({ "type": "permissions", "permissions": permissions }).to_string()
+}
+
+/// Build the keepalive heartbeat, carrying the foreground hook's health and
+/// the active capture profile.
+pub fn build_heartbeat(hook: &crate::hook::HookHealth, power: &crate::power::PowerReport) -> String {
+    serde_json::json!({ "type": "heartbeat", "hook": hook, "power": power }).to_string()
+}
+
 /// Calculate backoff duration with exponential increase, capped at max.
 pub fn calculate_backoff(current_ms: u64, max_ms: u64) -> u64 {
     (current_ms.saturating_mul(2)).min(max_ms)
 }
 
 /// Main network loop: sends events from the channel, receives commands, auto-reconnects.
+/// Commands run on a watchdog worker (see watchdog.rs), never on this loop.
 pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
     let mut ws = None;
     let mut last_attempt = Instant::now() - config.ws_retry;
     let mut last_send = Instant::now();
     let poll_timeout = Duration::from_millis(50);
     let keepalive_interval = Duration::from_secs(10);
+    let mut last_sync: Option<Instant> = None;
+    // Manifest the backend last heard about, and when the file was last checked
+    let mut sent_permissions = serde_json::Value::Null;
+    let mut last_permissions_check = Instant::now();
+    // Hook health the backend last heard about (see hook.rs)
+    let mut sent_hook = crate::hook::health();
+    // Capture profile the backend last heard about (see power.rs)
+    let mut sent_power = crate::power::report(&config);
+    // Model download progress the backend last heard about (see assets.rs)
+    let mut sent_assets: Vec<crate::assets::AssetStatus> = Vec::new();
     let mut backoff_ms: u64 = 1000;
     let max_backoff_ms = config.ws_reconnect_max_ms;
+    let mut commands = CommandWorker::new(config.clone(), crate::command::execute_command);
+    let router = Router::new(&config);
+    // What the backend asked to hear about (see subscriptions.rs)
+    let mut subscriptions = Subscriptions::default();
+    let mut outbox = Outbox::new(config.ws_chunk_bytes);
+    // `collector.command` spans of commands not yet answered (see trace.rs)
+    let mut command_spans: HashMap<String, Span> = HashMap::new();
 
     println!("Network worker started, connecting to {}", config.ws_url);
 
     loop {
+        // Parts already sent went down with the old connection; the events
+        // among them go over HTTP instead
+        if ws.is_none() && !outbox.is_empty() {
+            for event in outbox.abandon() {
+                send_http(&config.http_url, &event);
+            }
+        }
+
         // Reconnect if needed (with exponential backoff)
         if ws.is_none() && last_attempt.elapsed() >= Duration::from_millis(backoff_ms) {
             last_attempt = Instant::now();
@@ -68,6 +138,20 @@ pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
                         .with_interval(Duration::from_secs(5));
                     let _ = sock.set_tcp_keepalive(&keepalive);
                 }
+                // Identify this collector before any events flow
+                if let Err(err) = socket.send(Message::Text(build_hello(&config))) {
+                    log::warn!("Hello handshake failed: {err}");
+                    ws = None;
+                } else {
+                    last_send = Instant::now();
+                    sent_permissions = crate::permissions::load(&config).describe();
+                    last_permissions_check = Instant::now();
+                    // Re-sync the clock on every fresh connection
+                    last_sync = None;
+                    // A reconnected backend subscribes afresh
+                    subscriptions.clear();
+                    sent_assets.clear();
+                }
             } else {
                 // Increase backoff on failed connection
                 backoff_ms = calculate_backoff(backoff_ms, max_backoff_ms);
@@ -77,20 +161,42 @@ pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
         }
 
         // Check for outgoing events (with timeout so we can also check for commands)
-        match rx.recv_timeout(poll_timeout) {
-            Ok(event) => {
-                if let Some(socket) = ws.as_mut() {
-                    let payload = serde_json::to_string(&event).unwrap_or_else(|_| "{}".into());
-                    if let Err(err) = socket.send(Message::Text(payload)) {
-                        log::warn!("WebSocket send failed: {err}");
-                        ws = None;
-                        // Fallback to HTTP
-                        send_http(&config.http_url, &event);
+        let busy = !commands.is_idle() || !outbox.is_empty();
+        match rx.recv_timeout(if busy { BUSY_POLL } else { poll_timeout }) {
+            Ok(mut event) => {
+                stamp_identity(&mut event, &config);
+                let sinks = router.route(&mut event);
+                if let Some(timings) = event.timings.as_mut() {
+                    timings.mark_dequeued();
+                }
+                crate::diagnostics::record_event(&event);
+                if sinks.contains(&Sink::Local) {
+                    router.publish_local(&event);
+                }
+                if sinks.contains(&Sink::Webhook) {
+                    crate::webhook::publish(&event);
+                }
+                // Events routed away from the backend, or outside its
+                // subscriptions, stop here (see routing.rs, subscriptions.rs)
+                if sinks.contains(&Sink::Backend) && subscriptions.select(&mut event, Instant::now()) {
+                    crate::preview::record(&event);
+                    if let Some(socket) = ws.as_mut() {
+                        let payload = serde_json::to_string(&event).unwrap_or_else(|_| "{}".into());
+                        if outbox.too_large(&payload) {
+                            if !outbox.push(&payload, Some(event)) {
+                                log::warn!("Large payloads already waiting; dropping an event of {} bytes", payload.len());
+                            }
+                        } else if let Err(err) = socket.send(Message::Text(payload)) {
+                            log::warn!("WebSocket send failed: {err}");
+                            ws = None;
+                            // Fallback to HTTP
+                            send_http(&config.http_url, &event);
+                        } else {
+                            last_send = Instant::now();
+                        }
                     } else {
-                        last_send = Instant::now();
+                        send_http(&config.http_url, &event);
                     }
-                } else {
-                    send_http(&config.http_url, &event);
                 }
             }
             Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
@@ -102,16 +208,78 @@ pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
             }
         }
 
+        // One part of a large payload per turn, so nothing waits long behind it
+        if let (Some(socket), Some(frame)) = (ws.as_mut(), outbox.front().map(str::to_owned)) {
+            if let Err(err) = socket.send(Message::Text(frame)) {
+                log::warn!("WebSocket send of payload part failed: {err}");
+                ws = None;
+            } else {
+                outbox.advance();
+                last_send = Instant::now();
+            }
+        }
+
         // Collector-side keepalive: if we haven't sent anything recently,
         // send a small heartbeat to flush write buffers and detect dead TCP.
+        // A change in hook health or capture profile goes out straight away.
         if let Some(socket) = ws.as_mut() {
-            if last_send.elapsed() >= keepalive_interval {
-                let hb = r#"{"type":"heartbeat"}"#;
-                if let Err(err) = socket.send(Message::Text(hb.to_string())) {
+            let hook = crate::hook::health();
+            let power = crate::power::report(&config);
+            if last_send.elapsed() >= keepalive_interval || hook.changed_from(&sent_hook) || power.changed_from(&sent_power) {
+                if let Err(err) = socket.send(Message::Text(build_heartbeat(&hook, &power))) {
                     log::warn!("Keepalive send failed: {err}");
                     ws = None;
                 } else {
                     last_send = Instant::now();
+                    sent_hook = hook;
+                    sent_power = power;
+                }
+            }
+        }
+
+        // Pass on edits to the permission manifest (see permissions.rs)
+        if last_permissions_check.elapsed() >= PERMISSIONS_CHECK_INTERVAL {
+            last_permissions_check = Instant::now();
+            if let Some(socket) = ws.as_mut() {
+                let permissions = crate::permissions::load(&config).describe();
+                if permissions != sent_permissions {
+                    if let Err(err) = socket.send(Message::Text(build_permissions_update(&permissions))) {
+                        log::warn!("Permissions update failed: {err}");
+                        ws = None;
+                    } else {
+                        log::info!("Permission manifest changed; backend updated");
+                        last_send = Instant::now();
+                        sent_permissions = permissions;
+                    }
+                }
+            }
+        }
+
+        // Report model download progress as it moves (see assets.rs)
+        if let Some(socket) = ws.as_mut() {
+            let assets = crate::assets::report();
+            if assets != sent_assets {
+                if let Err(err) = socket.send(Message::Text(crate::assets::build_status_message(&assets))) {
+                    log::warn!("Asset status send failed: {err}");
+                    ws = None;
+                } else {
+                    last_send = Instant::now();
+                    sent_assets = assets;
+                }
+            }
+        }
+
+        // Periodic clock sync against the backend (NTP-style, see clock.rs)
+        if !config.time_sync_interval.is_zero() {
+            if let Some(socket) = ws.as_mut() {
+                if last_sync.is_none_or(|t| t.elapsed() >= config.time_sync_interval) {
+                    last_sync = Some(Instant::now());
+                    if let Err(err) = socket.send(Message::Text(crate::clock::build_sync_request())) {
+                        log::warn!("Time sync send failed: {err}");
+                        ws = None;
+                    } else {
+                        last_send = Instant::now();
+                    }
                 }
             }
         }
@@ -121,7 +289,7 @@ pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
             if let Some(socket) = ws.as_mut() {
                 match socket.read() {
                     Ok(Message::Text(text)) => {
-                        handle_incoming_message(&text, socket, &config);
+                        handle_incoming_message(&text, socket, &mut commands, &mut subscriptions, &mut command_spans);
                     }
                     Ok(_) => {
                         // Binary/ping/pong frames — tungstenite auto-queues
@@ -142,13 +310,21 @@ pub fn network_worker(rx: Receiver<WindowEvent>, config: Config) {
                 }
             }
         }
+
+        // Answer commands that finished or ran out of time
+        for (cmd, result) in commands.poll() {
+            let span = command_spans.remove(&cmd.command_id);
+            send_command_result(ws.as_mut(), &mut outbox, &cmd, &result, &config, span);
+        }
     }
 }
 
 fn handle_incoming_message(
     text: &str,
     socket: &mut tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>,
-    config: &Config,
+    commands: &mut CommandWorker,
+    subscriptions: &mut Subscriptions,
+    command_spans: &mut HashMap<String, Span>,
 ) {
     // Try to parse as a command
     let parsed: Result<serde_json::Value, _> = serde_json::from_str(text);
@@ -172,12 +348,35 @@ fn handle_incoming_message(
         return;
     }
 
+    if msg_type == "time_sync" {
+        if let Some(sample) = crate::clock::handle_sync_reply(&value) {
+            log::debug!("Clock sync: offset={}ms rtt={}ms", sample.offset_ms, sample.rtt_ms);
+        }
+        return;
+    }
+
+    if msg_type == "cancel" {
+        let command_id = value.get("command_id").and_then(|v| v.as_str()).unwrap_or("");
+        if !commands.cancel(command_id) {
+            log::debug!("Cancel for unknown or finished command {command_id}");
+        }
+        return;
+    }
+
+    if msg_type == "subscribe" || msg_type == "unsubscribe" {
+        let reply = subscriptions.handle(&value, Instant::now());
+        if let Err(err) = socket.send(Message::Text(reply)) {
+            log::warn!("Failed to answer {msg_type}: {err}");
+        }
+        return;
+    }
+
     if msg_type != "command" {
         // Not a command — might be an ack or other message, ignore
         return;
     }
 
-    let cmd: crate::command::Command = match serde_json::from_value(value) {
+    let mut cmd: crate::command::Command = match serde_json::from_value(value) {
         Ok(c) => c,
         Err(e) => {
             log::warn!("Failed to parse command: {e}");
@@ -186,11 +385,60 @@ fn handle_incoming_message(
     };
 
     log::info!("Received command: {} (id={})", cmd.action, cmd.command_id);
-    let result = crate::command::execute_command(&cmd, config);
-    let result_json = serde_json::to_string(&result).unwrap_or_else(|_| "{}".into());
+    let mut span = Span::start("collector.command", SpanKind::Server, cmd.traceparent.as_deref());
+    if let Some(traceparent) = span.traceparent() {
+        span.set("command.action", cmd.action.as_str());
+        span.set("command.id", cmd.command_id.as_str());
+        // Execution continues this span's trace
+        cmd.traceparent = Some(traceparent);
+        command_spans.insert(cmd.command_id.clone(), span);
+    }
+    commands.submit(cmd);
+}
 
+fn send_command_result(
+    socket: Option<&mut tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>>,
+    outbox: &mut Outbox,
+    cmd: &Command,
+    result: &CommandResult,
+    config: &Config,
+    mut span: Option<Span>,
+) {
+    if !config.command_record_dir.is_empty() {
+        if let Err(e) = crate::recorder::record_exchange(&config.command_record_dir, cmd, result) {
+            log::warn!("Failed to record command {}: {e}", cmd.command_id);
+        }
+    }
+    if let Some(span) = span.as_mut() {
+        span.set("command.ok", result.ok);
+    }
+    let Some(socket) = socket else {
+        log::warn!("Dropping result of command {}: backend disconnected", cmd.command_id);
+        if let Some(span) = span.as_mut() {
+            span.fail("backend disconnected");
+        }
+        return;
+    };
+    let mut result_json = serde_json::to_string(result).unwrap_or_else(|_| "{}".into());
+    let chunked = outbox.too_large(&result_json);
+    if let Some(span) = span.as_mut() {
+        span.set("result.bytes", result_json.len());
+        span.set("result.chunked", chunked);
+    }
+    if chunked {
+        if outbox.push(&result_json, None) {
+            return;
+        }
+        // The backend is waiting on this command: a small failure goes out whole
+        log::warn!("Large payloads already waiting; replacing the result of command {}", cmd.command_id);
+        let error = format!("result of {} bytes dropped: too many large payloads waiting", result_json.len());
+        result_json = serde_json::to_string(&CommandResult::failure(&cmd.command_id, &error)).unwrap_or_else(|_| "{}".into());
+    }
     if let Err(err) = socket.send(Message::Text(result_json)) {
         log::warn!("Failed to send command result: {err}");
+        if let Some(span) = span.as_mut() {
+            span.fail(&format!("send failed: {err}"));
+        }
     }
 }
 
@@ -289,4 +537,52 @@ mod tests {
         // Should be valid JSON
         assert!(serde_json::from_str::<serde_json::Value>(&payload).is_ok());
     }
+
+    #[test]
+    fn test_hello_includes_identity() {
+        let mut config = Config::from_env();
+        config.collector_id = "abc-123".to_string();
+        config.collector_name = "LAB-PC".to_string();
+
+        let hello: serde_json::Value = serde_json::from_str(&build_hello(&config)).unwrap();
+        assert_eq!(hello["type"], "hello");
+        assert_eq!(hello["collector_id"], "abc-123");
+        assert_eq!(hello["collector_name"], "LAB-PC");
+        assert_eq!(hello["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(hello["permissions"]["categories"]["input"]["actions"][0], "click");
+    }
+
+    #[test]
+    fn test_heartbeat_carries_hook_health_and_power_profile() {
+        let hook = crate::hook::HookHealth {
+            installed: true,
+            healthy: false,
+            restarts: 2,
+            heartbeat_age_ms: Some(21_000),
+            last_event_age_ms: None,
+            last_error: Some("no heartbeat for 20s".to_string()),
+        };
+        let power = crate::power::PowerReport { profile: crate::power::Profile::Battery, battery_percent: Some(64) };
+        let heartbeat: serde_json::Value = serde_json::from_str(&build_heartbeat(&hook, &power)).unwrap();
+        assert_eq!(heartbeat["type"], "heartbeat");
+        assert_eq!(heartbeat["power"]["profile"], "battery");
+        assert_eq!(heartbeat["power"]["battery_percent"], 64);
+        assert_eq!(heartbeat["hook"]["healthy"], false);
+        assert_eq!(heartbeat["hook"]["restarts"], 2);
+        assert_eq!(heartbeat["hook"]["last_error"], "no heartbeat for 20s");
+    }
+
+    #[test]
+    fn test_stamp_identity() {
+        use crate::event::build_activity_event;
+
+        let mut config = Config::from_env();
+        config.collector_id = "abc-123".to_string();
+        config.collector_name = "LAB-PC".to_string();
+
+        let mut event = build_activity_event("idle", 1000);
+        stamp_identity(&mut event, &config);
+        assert_eq!(event.collector_id, "abc-123");
+        assert_eq!(event.collector_name, "LAB-PC");
+    }
 }
@@ -0,0 +1,100 @@
+//! Text recognition over screen captures with the Windows.Media.Ocr API.
+//!
+//! Runs on-device, so apps that expose nothing through UIA can be read
+//! without sending a screenshot to a remote vision model. Captures larger
+//! than the engine's `MaxImageDimension` are shrunk during the blit and the
+//! word boxes scaled back to screen pixels.
+
+use serde::Serialize;
+
+/// One recognized line of text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OcrLine {
+    pub text: String,
+    /// Screen rectangle `[x, y, width, height]` around the line's words.
+    pub rect: [i32; 4],
+}
+
+/// Screen rectangle around word boxes `[x, y, width, height]` given in image
+/// pixels of a capture taken at screen `origin` and shrunk by `scale`
+/// (screen pixels per image pixel). `None` for a line without words.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn line_rect(words: &[[f32; 4]], origin: (i32, i32), scale: f64) -> Option<[i32; 4]> {
+    let left = words.iter().map(|w| w[0]).reduce(f32::min)?;
+    let top = words.iter().map(|w| w[1]).reduce(f32::min)?;
+    let right = words.iter().map(|w| w[0] + w[2]).reduce(f32::max)?;
+    let bottom = words.iter().map(|w| w[1] + w[3]).reduce(f32::max)?;
+    let to_screen = |v: f32, offset: i32| offset + (v as f64 * scale).round() as i32;
+    let (x, y) = (to_screen(left, origin.0), to_screen(top, origin.1));
+    Some([x, y, to_screen(right, origin.0) - x, to_screen(bottom, origin.1) - y])
+}
+
+/// Recognize the text in a screen rectangle `[x, y, width, height]`.
+/// `language` is a BCP-47 tag; without one the engine follows the user's
+/// profile languages.
+#[cfg(windows)]
+pub fn recognize(rect: [i32; 4], language: Option<&str>) -> Result<Vec<OcrLine>, String> {
+    use windows::core::HSTRING;
+    use windows::Globalization::Language;
+    use windows::Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap};
+    use windows::Media::Ocr::OcrEngine;
+    use windows::Storage::Streams::DataWriter;
+    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+    }
+    let engine = match language {
+        Some(tag) => Language::CreateLanguage(&HSTRING::from(tag))
+            .and_then(|language| OcrEngine::TryCreateFromLanguage(&language))
+            .map_err(|_| format!("no OCR engine for language '{tag}' (is its language pack installed?)"))?,
+        None => OcrEngine::TryCreateFromUserProfileLanguages()
+            .map_err(|_| "no OCR engine for the user's languages (is an OCR language pack installed?)".to_string())?,
+    };
+
+    let max_dimension = OcrEngine::MaxImageDimension().unwrap_or(2600);
+    let (width, height, pixels) = crate::screenshot::capture_rect_pixels(rect, max_dimension, max_dimension)
+        .ok_or("screen capture failed")?;
+    // The engine takes BGRA; the capture is packed BGR
+    let bgra: Vec<u8> = pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect();
+
+    let recognized = (|| {
+        let writer = DataWriter::new()?;
+        writer.WriteBytes(&bgra)?;
+        let bitmap =
+            SoftwareBitmap::CreateCopyFromBuffer(&writer.DetachBuffer()?, BitmapPixelFormat::Bgra8, width as i32, height as i32)?;
+        engine.RecognizeAsync(&bitmap)?.get()?.Lines()
+    })()
+    .map_err(|e| format!("OCR failed: {e}"))?;
+
+    let scale = rect[2] as f64 / width as f64;
+    let mut lines = Vec::new();
+    for line in recognized {
+        let words: Vec<[f32; 4]> = line
+            .Words()
+            .into_iter()
+            .flatten()
+            .filter_map(|word| word.BoundingRect().ok())
+            .map(|r| [r.X, r.Y, r.Width, r.Height])
+            .collect();
+        let (Some(rect), Ok(text)) = (line_rect(&words, (rect[0], rect[1]), scale), line.Text()) else {
+            continue;
+        };
+        lines.push(OcrLine { text: text.to_string_lossy(), rect });
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_rect_maps_words_to_screen() {
+        let words = [[10.0, 20.0, 30.0, 10.0], [50.0, 18.0, 40.0, 14.0]];
+        assert_eq!(line_rect(&words, (0, 0), 1.0), Some([10, 18, 80, 14]));
+        // A capture at (1920, 100) shrunk by half
+        assert_eq!(line_rect(&words, (1920, 100), 2.0), Some([1940, 136, 160, 28]));
+        assert_eq!(line_rect(&[], (0, 0), 1.0), None);
+    }
+}
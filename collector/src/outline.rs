@@ -0,0 +1,291 @@
+//! Compact text outline of a UIA snapshot for language-model prompts.
+//!
+//! The raw `window_tree` can run to megabytes of JSON for a busy window; the
+//! outline says the same thing in a few hundred tokens:
+//!
+//! ```text
+//! focused: edit "Subject"
+//! [0] window "Inbox - Outlook"
+//!   [2] button "Send" #sendButton (disabled)
+//!   [3] edit "Subject" #subject = "Hello" (focused)
+//!   [5] list "Messages" (+23 hidden)
+//! ```
+//!
+//! `[n]` is the element's position in a depth-first walk of `window_tree`
+//! (the focused element's subtree when there is no window tree), so an
+//! index can be resolved against the raw snapshot. Unnamed elements with no
+//! automation ID and no patterns are layout containers: they are left out
+//! and their children move up a level. The output is deterministic for a
+//! given snapshot and stays within the token budget (estimated at four
+//! characters per token) by keeping shallow elements first; whatever is cut
+//! is counted on its nearest shown ancestor.
+
+use crate::event::{UiaElement, UiaSnapshot};
+
+/// Longest name or value quoted on a line, in characters.
+const TEXT_CHARS: usize = 80;
+
+/// Longest document text excerpt in the header, in characters.
+const DOCUMENT_CHARS: usize = 160;
+
+/// An element that gets its own line.
+struct Node<'a> {
+    element: &'a UiaElement,
+    index: usize,
+    depth: usize,
+    /// Position of the nearest shown ancestor in the node list.
+    parent: Option<usize>,
+    focused: bool,
+}
+
+/// Estimated tokens for `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Render `snapshot` as an outline of at most `budget` estimated tokens.
+pub fn outline(snapshot: &UiaSnapshot, budget: usize) -> String {
+    let mut header = Vec::new();
+    if !snapshot.focused_name.is_empty() || !snapshot.control_type.is_empty() {
+        header.push(format!("focused: {}", describe(&snapshot.control_type, &snapshot.focused_name)));
+    }
+    if !snapshot.document_text.trim().is_empty() {
+        header.push(format!("text: {}", quote(snapshot.document_text.trim(), DOCUMENT_CHARS)));
+    }
+
+    let roots: Vec<&UiaElement> = if snapshot.window_tree.is_empty() {
+        snapshot.focused_element.iter().collect()
+    } else {
+        snapshot.window_tree.iter().collect()
+    };
+    let mut nodes = Vec::new();
+    let mut index = 0;
+    for root in roots {
+        collect(root, snapshot.focused_element.as_ref(), 0, None, &mut index, &mut nodes);
+    }
+
+    // Shallow elements first, in tree order within a level.
+    let mut order: Vec<usize> = (0..nodes.len()).collect();
+    order.sort_by_key(|&i| nodes[i].depth);
+
+    let mut used: usize = header.iter().map(|line| estimate_tokens(line) + 1).sum();
+    let mut shown = vec![false; nodes.len()];
+    let mut kept = Vec::new();
+    for &i in &order {
+        let cost = estimate_tokens(&line(&nodes[i], 0)) + 1;
+        if used + cost > budget || nodes[i].parent.is_some_and(|p| !shown[p]) {
+            break;
+        }
+        used += cost;
+        shown[i] = true;
+        kept.push(i);
+    }
+
+    // Hidden counts lengthen lines; give back elements until it fits.
+    loop {
+        let text = render(&header, &nodes, &shown);
+        if estimate_tokens(&text) <= budget || kept.is_empty() {
+            return text;
+        }
+        if let Some(last) = kept.pop() {
+            shown[last] = false;
+        }
+    }
+}
+
+/// Fill in `snapshot.outline` (nothing when `budget` is zero). Without
+/// `keep_tree` the raw trees are dropped once outlined.
+pub fn attach(snapshot: &mut UiaSnapshot, budget: usize, keep_tree: bool) {
+    if budget == 0 {
+        return;
+    }
+    snapshot.outline = outline(snapshot, budget);
+    if !keep_tree {
+        snapshot.window_tree.clear();
+        if let Some(focused) = snapshot.focused_element.as_mut() {
+            focused.children.clear();
+        }
+    }
+}
+
+fn collect<'a>(
+    element: &'a UiaElement,
+    focused: Option<&UiaElement>,
+    depth: usize,
+    parent: Option<usize>,
+    index: &mut usize,
+    nodes: &mut Vec<Node<'a>>,
+) {
+    let own_index = *index;
+    *index += 1;
+    let focused_here = focused.is_some_and(|f| same_element(f, element));
+    let mut child_depth = depth;
+    let mut child_parent = parent;
+    if focused_here || !element.name.is_empty() || !element.automation_id.is_empty() || !element.patterns.is_empty() {
+        child_depth = depth + 1;
+        child_parent = Some(nodes.len());
+        nodes.push(Node { element, index: own_index, depth, parent, focused: focused_here });
+    }
+    for child in &element.children {
+        collect(child, focused, child_depth, child_parent, index, nodes);
+    }
+}
+
+fn same_element(a: &UiaElement, b: &UiaElement) -> bool {
+    a.automation_id == b.automation_id
+        && a.name == b.name
+        && a.control_type == b.control_type
+        && a.bounding_rect == b.bounding_rect
+}
+
+fn render(header: &[String], nodes: &[Node], shown: &[bool]) -> String {
+    let mut hidden = vec![0; nodes.len()];
+    let mut hidden_roots = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if shown[i] {
+            continue;
+        }
+        let mut ancestor = node.parent;
+        while let Some(p) = ancestor.filter(|&p| !shown[p]) {
+            ancestor = nodes[p].parent;
+        }
+        match ancestor {
+            Some(p) => hidden[p] += 1,
+            None => hidden_roots += 1,
+        }
+    }
+
+    let mut lines = header.to_vec();
+    for (i, node) in nodes.iter().enumerate() {
+        if shown[i] {
+            lines.push(line(node, hidden[i]));
+        }
+    }
+    if hidden_roots > 0 {
+        lines.push(format!("(+{hidden_roots} hidden)"));
+    }
+    lines.join("\n")
+}
+
+fn line(node: &Node, hidden: usize) -> String {
+    let element = node.element;
+    let mut text = format!(
+        "{}[{}] {}",
+        "  ".repeat(node.depth),
+        node.index,
+        describe(&element.control_type, &element.name)
+    );
+    if !element.automation_id.is_empty() {
+        text.push_str(" #");
+        text.push_str(&element.automation_id);
+    }
+    if let Some(value) = element.value.as_deref().filter(|v| !v.is_empty() && *v != element.name) {
+        text.push_str(" = ");
+        text.push_str(&quote(value, TEXT_CHARS));
+    }
+
+    let mut states = Vec::new();
+    if !element.is_enabled {
+        states.push("disabled".to_string());
+    }
+    if element.is_offscreen {
+        states.push("offscreen".to_string());
+    }
+    if let Some(toggle) = &element.toggle_state {
+        states.push(toggle.to_lowercase());
+    }
+    if node.focused {
+        states.push("focused".to_string());
+    }
+    if hidden > 0 {
+        states.push(format!("+{hidden} hidden"));
+    }
+    if !states.is_empty() {
+        text.push_str(&format!(" ({})", states.join(", ")));
+    }
+    text
+}
+
+fn describe(role: &str, name: &str) -> String {
+    let role = if role.is_empty() { "element" } else { role };
+    if name.is_empty() {
+        role.to_string()
+    } else {
+        format!("{role} {}", quote(name, TEXT_CHARS))
+    }
+}
+
+/// Quote on one line, cut to `max` characters.
+fn quote(text: &str, max: usize) -> String {
+    let flat: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(max) {
+        Some((cut, _)) => format!("{:?}", format!("{}…", &flat[..cut])),
+        None => format!("{flat:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(control_type: &str, name: &str, children: Vec<UiaElement>) -> UiaElement {
+        UiaElement {
+            control_type: control_type.to_string(),
+            name: name.to_string(),
+            is_enabled: true,
+            children,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot() -> UiaSnapshot {
+        let mut send = element("button", "Send", vec![]);
+        send.automation_id = "sendButton".to_string();
+        send.is_enabled = false;
+        let mut subject = element("edit", "Subject", vec![]);
+        subject.value = Some("Hello\nthere".to_string());
+        subject.patterns = vec!["Value".to_string()];
+        let mut bold = element("check box", "Bold", vec![]);
+        bold.toggle_state = Some("On".to_string());
+        let messages = element("list", "Messages", (0..30).map(|i| element("list item", &format!("Message {i}"), vec![])).collect());
+        let layout = element("pane", "", vec![send, subject.clone(), bold]);
+        UiaSnapshot {
+            focused_name: "Subject".to_string(),
+            control_type: "edit".to_string(),
+            focused_element: Some(subject),
+            window_tree: vec![element("window", "Inbox - Outlook", vec![layout, messages])],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_outline_format() {
+        let text = outline(&snapshot(), 10_000);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "focused: edit \"Subject\"");
+        assert_eq!(lines[1], "[0] window \"Inbox - Outlook\"");
+        // The unnamed pane is skipped but keeps its index
+        assert_eq!(lines[2], "  [2] button \"Send\" #sendButton (disabled)");
+        assert_eq!(lines[3], "  [3] edit \"Subject\" = \"Hello there\" (focused)");
+        assert_eq!(lines[4], "  [4] check box \"Bold\" (on)");
+        assert_eq!(lines[5], "  [5] list \"Messages\"");
+        assert_eq!(lines[6], "    [6] list item \"Message 0\"");
+        assert_eq!(lines.len(), 36);
+        assert_eq!(text, outline(&snapshot(), 10_000), "deterministic");
+    }
+
+    #[test]
+    fn test_outline_budget() {
+        let text = outline(&snapshot(), 60);
+        assert!(estimate_tokens(&text) <= 60, "{text}");
+        // Shallow elements survive; the list items are counted instead
+        assert!(text.contains("[4] check box \"Bold\""));
+        let list_line = text.lines().find(|l| l.contains("list \"Messages\"")).unwrap();
+        let shown_items = text.matches("list item").count();
+        assert!(list_line.ends_with(&format!("(+{} hidden)", 30 - shown_items)), "{text}");
+
+        let tiny = outline(&snapshot(), 10);
+        assert_eq!(tiny, "focused: edit \"Subject\"\n(+35 hidden)");
+        assert_eq!(quote(&"x".repeat(100), 5), "\"xxxxx…\"");
+    }
+}
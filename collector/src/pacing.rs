@@ -0,0 +1,230 @@
+//! Observation pacing.
+//!
+//! Heavy enrichment (UIA walk, screenshot, detection) is kept within a capture
+//! budget: at most `OBSERVE_MAX_PER_SEC` enriched observations per second and
+//! roughly `OBSERVE_CPU_BUDGET_PCT` of one core, measured from the recent cost
+//! of those stages. Foreground events that arrive faster than the budget
+//! allows are sent with only the cheap stages; the latest such window is
+//! remembered and given a full `observation` event once the budget frees up,
+//! provided it is still in the foreground.
+
+use crossbeam_channel::Sender;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+
+#[cfg(windows)]
+use crate::windows::build_observation;
+
+#[cfg(not(windows))]
+fn build_observation(_hwnd: isize, _config: &Config) -> Option<WindowEvent> {
+    // Stub for non-Windows platforms in tests
+    None
+}
+
+/// Weight of the newest cost sample in the moving average.
+const COST_SMOOTHING: f64 = 0.3;
+
+/// How often the catch-up worker checks for a deferred window.
+const PENDING_POLL: Duration = Duration::from_millis(100);
+
+static PACER: Mutex<Option<FramePacer>> = Mutex::new(None);
+static PENDING_HWND: AtomicIsize = AtomicIsize::new(0);
+
+/// Spaces heavy observations so they stay within a rate and CPU budget.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    min_interval: Duration,
+    cpu_budget: f64,
+    last_start: Option<Instant>,
+    avg_cost: Option<Duration>,
+}
+
+impl FramePacer {
+    /// `max_per_sec` caps the observation rate; `cpu_budget` is the share of
+    /// one core (0.0-1.0) heavy stages may use on average. Non-positive values
+    /// disable the respective limit.
+    pub fn new(max_per_sec: f32, cpu_budget: f32) -> Self {
+        Self {
+            min_interval: rate_interval(max_per_sec),
+            cpu_budget: cpu_budget.max(0.0) as f64,
+            last_start: None,
+            avg_cost: None,
+        }
+    }
+
+    /// Change the rate cap, e.g. when the power profile switches (see power.rs).
+    pub fn set_rate(&mut self, max_per_sec: f32) {
+        self.min_interval = rate_interval(max_per_sec);
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.observe_max_per_sec, config.observe_cpu_budget_pct / 100.0)
+    }
+
+    /// Current gap required between observation starts: the rate limit, or
+    /// longer if recent observations were expensive enough to exceed the CPU
+    /// budget.
+    pub fn spacing(&self) -> Duration {
+        let cpu_spacing = match self.avg_cost {
+            Some(cost) if self.cpu_budget > 0.0 => cost.div_f64(self.cpu_budget),
+            _ => Duration::ZERO,
+        };
+        self.min_interval.max(cpu_spacing)
+    }
+
+    /// Whether a full observation may start at `now`.
+    pub fn ready(&self, now: Instant) -> bool {
+        match self.last_start {
+            Some(at) => now.saturating_duration_since(at) >= self.spacing(),
+            None => true,
+        }
+    }
+
+    /// Reserve a slot at `now` if the budget allows it.
+    pub fn try_begin(&mut self, now: Instant) -> bool {
+        if !self.ready(now) {
+            return false;
+        }
+        self.last_start = Some(now);
+        true
+    }
+
+    /// Record how long the heavy stages of an observation took.
+    pub fn record_cost(&mut self, cost: Duration) {
+        self.avg_cost = Some(match self.avg_cost {
+            Some(avg) => avg.mul_f64(1.0 - COST_SMOOTHING) + cost.mul_f64(COST_SMOOTHING),
+            None => cost,
+        });
+    }
+}
+
+/// Gap between observations for a rate cap; non-positive means none.
+fn rate_interval(max_per_sec: f32) -> Duration {
+    if max_per_sec > 0.0 {
+        Duration::from_secs_f64(1.0 / max_per_sec as f64)
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Whether pacing is configured at all, on AC or battery power.
+pub fn pacing_enabled(config: &Config) -> bool {
+    config.observe_max_per_sec > 0.0
+        || config.observe_cpu_budget_pct > 0.0
+        || (config.power_profiles_enabled && config.battery_observe_max_per_sec > 0.0)
+}
+
+/// Reserve a slot for a full observation in the shared pacer. Always granted
+/// when pacing is disabled.
+pub fn try_begin_observation(config: &Config) -> bool {
+    if !pacing_enabled(config) {
+        return true;
+    }
+    let mut pacer = PACER.lock().unwrap_or_else(|e| e.into_inner());
+    let pacer = pacer.get_or_insert_with(|| FramePacer::from_config(config));
+    pacer.set_rate(config.observe_max_per_sec);
+    pacer.try_begin(Instant::now())
+}
+
+/// Feed the heavy-stage cost of a finished observation into the shared pacer.
+pub fn record_observation_cost(cost: Duration) {
+    let mut pacer = PACER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pacer) = pacer.as_mut() {
+        pacer.record_cost(cost);
+    }
+}
+
+/// Remember a window whose heavy stages were skipped; only the latest counts.
+pub fn defer_observation(hwnd: isize) {
+    PENDING_HWND.store(hwnd, Ordering::SeqCst);
+}
+
+/// Send a full `observation` for the most recently deferred window once the
+/// budget allows it.
+pub fn observation_worker(tx: Sender<WindowEvent>, config: Config) {
+    if !pacing_enabled(&config) {
+        return;
+    }
+    loop {
+        thread::sleep(PENDING_POLL);
+        if PENDING_HWND.load(Ordering::SeqCst) == 0 || !try_begin_observation(&crate::power::capture_config(&config)) {
+            continue;
+        }
+        let hwnd = PENDING_HWND.swap(0, Ordering::SeqCst);
+        if hwnd == 0 {
+            continue;
+        }
+        if let Some(event) = build_observation(hwnd, &config) {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_spacing() {
+        let mut pacer = FramePacer::new(2.0, 0.0);
+        let t0 = Instant::now();
+        assert!(pacer.try_begin(t0));
+        assert!(!pacer.try_begin(t0 + Duration::from_millis(200)));
+        assert!(pacer.try_begin(t0 + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_rate_follows_power_profile() {
+        let mut pacer = FramePacer::new(2.0, 0.0);
+        pacer.set_rate(0.5);
+        assert_eq!(pacer.spacing(), Duration::from_secs(2));
+        pacer.set_rate(0.0);
+        assert_eq!(pacer.spacing(), Duration::ZERO);
+
+        let mut config = Config::from_env();
+        config.observe_max_per_sec = 0.0;
+        config.observe_cpu_budget_pct = 0.0;
+        config.battery_observe_max_per_sec = 0.5;
+        config.power_profiles_enabled = true;
+        assert!(pacing_enabled(&config), "paced on battery");
+        config.power_profiles_enabled = false;
+        assert!(!pacing_enabled(&config));
+    }
+
+    #[test]
+    fn test_cpu_budget_widens_spacing() {
+        let mut pacer = FramePacer::new(2.0, 0.25);
+        pacer.record_cost(Duration::from_millis(200));
+        // 200ms of work at a quarter of a core needs 800ms between observations
+        assert_eq!(pacer.spacing(), Duration::from_millis(800));
+        let t0 = Instant::now();
+        assert!(pacer.try_begin(t0));
+        assert!(!pacer.try_begin(t0 + Duration::from_millis(600)));
+        assert!(pacer.try_begin(t0 + Duration::from_millis(800)));
+    }
+
+    #[test]
+    fn test_cost_is_smoothed() {
+        let mut pacer = FramePacer::new(0.0, 1.0);
+        pacer.record_cost(Duration::from_millis(100));
+        pacer.record_cost(Duration::from_millis(200));
+        let spacing = pacer.spacing().as_secs_f64();
+        assert!((spacing - 0.130).abs() < 1e-6, "spacing {spacing}");
+    }
+
+    #[test]
+    fn test_unlimited_pacer_always_ready() {
+        let mut pacer = FramePacer::new(0.0, 0.0);
+        pacer.record_cost(Duration::from_secs(1));
+        let t0 = Instant::now();
+        assert!(pacer.try_begin(t0));
+        assert!(pacer.try_begin(t0));
+    }
+}
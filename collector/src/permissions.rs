@@ -0,0 +1,164 @@
+//! Permission manifest: which categories of actions this machine allows.
+//!
+//! The user's policy lives in a JSON file (`PERMISSIONS_PATH`, by default
+//! `permissions.json` in the data directory) that the Tauri app's
+//! Permissions window edits, e.g. `{"categories": {"shell": false}}`.
+//! Categories it does not mention are allowed, as is everything when the
+//! file is absent; a file that cannot be parsed allows nothing.
+//!
+//! The manifest is authoritative: `execute_command` refuses actions of a
+//! disabled category with a `PermissionDenied` failure whatever the backend
+//! believes. The backend learns the manifest from the hello handshake, and
+//! again from a `permissions` message whenever the file changes, so it can
+//! stop offering what would be refused. Safe mode and the privacy preview
+//! are the user's own controls and belong to no category.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+
+/// Categories in display order, with the actions each one covers.
+pub const CATEGORIES: &[(&str, &[&str])] = &[
+    (
+        "observe",
+        &[
+            "observe", "screenshot_region", "ocr", "find_elements", "get_element_tree", "get_element_text", "get_taskbar_state",
+            "export_state", "if_exists", "run_workflow", "show_element_labels", "hide_element_labels",
+        ],
+    ),
+    (
+        "input",
+        &[
+            "click", "double_click", "triple_click", "right_click", "middle_click", "hover", "type_text", "send_keys", "paste_text",
+            "scroll", "swipe", "flick", "pinch", "click_detection",
+        ],
+    ),
+    ("ui_automation", &["select_item", "expand", "collapse", "scroll_into_view", "set_range_value", "invoke_menu"]),
+    (
+        "windows",
+        &[
+            "focus_window", "close_window", "minimize_window", "maximize_window", "restore_window", "move_window",
+            "resize_window", "switch_desktop",
+        ],
+    ),
+    ("applications", &["open_application", "start_menu_search", "browser_navigate", "kill_process", "close_application"]),
+    ("files", &["move_to_recycle_bin", "empty_recycle_bin", "drop_files", "set_context_directory"]),
+    ("shell", &["run_shell"]),
+    ("maintenance", &["import_state", "purge_data", "self_test", "set_event_profile", "create_diagnostic_bundle"]),
+];
+
+/// Prefixes of read-only actions, which count as `observe`.
+const OBSERVE_PREFIXES: &[&str] = &["get_", "list_", "wait_for_"];
+
+/// The category an action belongs to; `None` for the user's own controls
+/// and unknown actions.
+pub fn category(action: &str) -> Option<&'static str> {
+    CATEGORIES
+        .iter()
+        .find(|(_, actions)| actions.contains(&action))
+        .map(|(name, _)| *name)
+        .or_else(|| OBSERVE_PREFIXES.iter().any(|prefix| action.starts_with(prefix)).then_some("observe"))
+}
+
+/// The manifest file's contents.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub categories: BTreeMap<String, bool>,
+    /// Set when the file could not be read; every category is then denied.
+    #[serde(skip)]
+    pub invalid: bool,
+}
+
+impl Manifest {
+    pub fn allows(&self, category: &str) -> bool {
+        !self.invalid && self.categories.get(category).copied().unwrap_or(true)
+    }
+
+    /// Every category with whether it is enabled and its actions, as sent
+    /// to the backend.
+    pub fn describe(&self) -> serde_json::Value {
+        let categories: serde_json::Map<String, serde_json::Value> = CATEGORIES
+            .iter()
+            .map(|(name, actions)| {
+                (name.to_string(), serde_json::json!({ "enabled": self.allows(name), "actions": actions }))
+            })
+            .collect();
+        serde_json::json!({ "categories": categories })
+    }
+}
+
+/// Read the manifest at `config.permissions_path`.
+pub fn load(config: &Config) -> Manifest {
+    if config.permissions_path.is_empty() {
+        return Manifest::default();
+    }
+    match std::fs::read_to_string(&config.permissions_path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("Invalid permission manifest {}: {e}; denying every category", config.permissions_path);
+            Manifest { invalid: true, ..Manifest::default() }
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Manifest::default(),
+        Err(e) => {
+            log::warn!("Cannot read permission manifest {}: {e}; denying every category", config.permissions_path);
+            Manifest { invalid: true, ..Manifest::default() }
+        }
+    }
+}
+
+/// Returns the denial reason if the manifest does not permit the action.
+pub fn check_action(action: &str, config: &Config) -> Option<String> {
+    let category = category(action)?;
+    let manifest = load(config);
+    if manifest.allows(category) {
+        return None;
+    }
+    Some(if manifest.invalid {
+        format!("'{action}' is not permitted: the permission manifest could not be read")
+    } else {
+        format!("'{action}' is not permitted on this machine (category '{category}' is disabled)")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(category("click"), Some("input"));
+        assert_eq!(category("run_shell"), Some("shell"));
+        assert_eq!(category("wait_for_window"), Some("observe"));
+        assert_eq!(category("set_safe_mode"), None);
+        assert_eq!(category("preview_events"), None);
+        assert_eq!(category("frobnicate"), None);
+        let mut seen = std::collections::HashSet::new();
+        for (_, actions) in CATEGORIES {
+            assert!(actions.iter().all(|action| seen.insert(*action)), "an action is in two categories");
+        }
+    }
+
+    #[test]
+    fn test_manifest_file() {
+        let path = std::env::temp_dir().join(format!("desktopai_permissions_{}.json", std::process::id()));
+        let mut config = Config::from_env();
+        config.permissions_path = path.to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(check_action("run_shell", &config), None, "no file allows everything");
+
+        std::fs::write(&path, r#"{"categories": {"shell": false, "input": true}}"#).unwrap();
+        let reason = check_action("run_shell", &config).unwrap();
+        assert!(reason.contains("category 'shell' is disabled"));
+        assert_eq!(check_action("click", &config), None);
+        assert_eq!(check_action("focus_window", &config), None, "unlisted categories are allowed");
+        let described = load(&config).describe();
+        assert_eq!(described["categories"]["shell"]["enabled"], false);
+        assert_eq!(described["categories"]["shell"]["actions"], serde_json::json!(["run_shell"]));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(check_action("observe", &config).unwrap().contains("could not be read"));
+        assert_eq!(check_action("set_safe_mode", &config), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,649 @@
+//! Event enrichment pipeline.
+//!
+//! A foreground event starts as a bare window handle and is filled in by an
+//! ordered list of stages (title/process, geometry, UIA, screenshot,
+//! detection, text normalization, language detection, redaction, outline). The order comes from `ENRICH_STAGES` and each stage
+//! honours its own enable flag, so new stages plug in here without touching
+//! the WinEvent hook. Per-stage latency is recorded on the event, plus a
+//! `timings` breakdown when `CAPTURE_TIMINGS` is set. Heavy stages can be
+//! skipped when the observation budget is spent (see `pacing`).
+//!
+//! Stages that only read the window (UIA, screenshot) are concurrent: each
+//! works on a thread of its own, so neighbouring concurrent stages overlap
+//! instead of adding up. Their results are applied in stage order once they
+//! arrive; a stage that misses its timeout (`UIA_TIMEOUT_MS`,
+//! `SCREENSHOT_TIMEOUT_MS`) is left out of the event. A stage still stuck
+//! on an earlier window queues one more and skips the rest.
+//!
+//! With tracing on, each run is a `collector.enrich` span with a
+//! `collector.stage` child per stage (see trace.rs).
+
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender};
+use regex::Regex;
+
+use crate::config::Config;
+use crate::event::{duration_ms, CaptureTimings, UiaElement, UiaSnapshot, WindowEvent};
+use crate::text::{normalize_line, normalize_multiline};
+use crate::trace::{Span, SpanKind};
+
+/// Default stage order when `ENRICH_STAGES` is unset. `detection` is opt-in:
+/// running the model on every foreground change is too heavy by default.
+pub const DEFAULT_STAGES: &[&str] =
+    &["title", "icon", "geometry", "theme", "uia", "screenshot", "normalize", "language", "redaction", "outline"];
+
+/// Replacement text for redacted matches.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Default redaction pattern: email addresses and 13-16 digit card numbers.
+pub const DEFAULT_REDACT_PATTERN: &str =
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}|\b(?:\d[ -]?){12,15}\d\b";
+
+/// State shared between stages while enriching a single event.
+#[derive(Default)]
+pub struct EnrichContext {
+    /// Raw window handle the event is about.
+    pub hwnd: isize,
+    /// Full-resolution BGR frame (width, height, pixels), kept for later stages.
+    pub raw_pixels: Option<(u32, u32, Vec<u8>)>,
+    /// Time the screenshot stage spent encoding, reported apart from capture.
+    pub encode_time: Option<Duration>,
+}
+
+/// What a concurrent stage found, applied to the event on the pipeline thread.
+pub type StageOutput = Box<dyn FnOnce(&mut EnrichContext, &mut WindowEvent) + Send>;
+
+/// A single enrichment step.
+pub trait EnrichStage: Send + Sync {
+    /// Name used in `ENRICH_STAGES` and in the timing map.
+    fn name(&self) -> &'static str;
+
+    /// Per-stage enable flag; disabled stages are skipped and not timed.
+    fn enabled(&self, _config: &Config) -> bool {
+        true
+    }
+
+    /// Expensive stages (UIA walk, capture, detection) are subject to
+    /// observation pacing; cheap ones always run.
+    fn heavy(&self) -> bool {
+        false
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config);
+
+    /// Stages that need only the window handle, not the event, run on their
+    /// own thread through `capture`; their `run` just applies it.
+    fn concurrent(&self) -> bool {
+        false
+    }
+
+    /// A concurrent stage's work for window `hwnd`: returns how to fill in
+    /// the event.
+    fn capture(&self, _hwnd: isize, _config: &Config) -> StageOutput {
+        Box::new(|_, _| {})
+    }
+
+    /// How long the pipeline waits for a concurrent stage.
+    fn timeout(&self, _config: &Config) -> Duration {
+        Duration::MAX
+    }
+}
+
+/// A concurrent stage's result and how long it took.
+type StageReply = (StageOutput, Duration);
+
+/// A concurrent stage's request for one window.
+struct Job {
+    hwnd: isize,
+    config: Config,
+    reply: Sender<StageReply>,
+}
+
+/// The thread a concurrent stage runs on. One job can wait behind the
+/// running one; more are refused.
+struct StageWorker {
+    jobs: Sender<Job>,
+}
+
+impl StageWorker {
+    fn spawn(stage: Arc<dyn EnrichStage>) -> Self {
+        let (jobs, queue) = crossbeam_channel::bounded::<Job>(1);
+        std::thread::spawn(move || {
+            for job in queue {
+                let started = Instant::now();
+                let output = stage.capture(job.hwnd, &job.config);
+                // The pipeline may have stopped waiting
+                let _ = job.reply.send((output, started.elapsed()));
+            }
+        });
+        Self { jobs }
+    }
+}
+
+/// An ordered list of enrichment stages.
+pub struct Pipeline {
+    stages: Vec<Arc<dyn EnrichStage>>,
+    /// Started on first use, for concurrent stages only.
+    workers: Vec<OnceLock<StageWorker>>,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Box<dyn EnrichStage>>) -> Self {
+        let stages: Vec<Arc<dyn EnrichStage>> = stages.into_iter().map(Arc::from).collect();
+        let workers = stages.iter().map(|_| OnceLock::new()).collect();
+        Self { stages, workers }
+    }
+
+    /// Build the pipeline declared by `config.enrich_stages`. Unknown or
+    /// platform-unavailable stage names are logged and skipped.
+    pub fn from_config(config: &Config) -> Self {
+        let mut stages = Vec::new();
+        for name in &config.enrich_stages {
+            match builtin_stage(name, config) {
+                Some(stage) => stages.push(stage),
+                None => log::warn!("Unknown or unavailable enrichment stage '{name}', skipping"),
+            }
+        }
+        Self::new(stages)
+    }
+
+    pub fn stage_names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+
+    /// Run every enabled stage in order, recording each stage's latency in
+    /// `event.enrich_timings_us`.
+    pub fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        self.run_stages(ctx, event, config, true);
+    }
+
+    /// Run only the cheap stages, for events arriving faster than the
+    /// observation budget allows.
+    pub fn run_light(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        self.run_stages(ctx, event, config, false);
+    }
+
+    /// Total time the heavy stages took on `event`.
+    pub fn heavy_cost(&self, event: &WindowEvent) -> Duration {
+        let micros = self
+            .stages
+            .iter()
+            .filter(|s| s.heavy())
+            .filter_map(|s| event.enrich_timings_us.get(s.name()))
+            .sum();
+        Duration::from_micros(micros)
+    }
+
+    fn run_stages(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config, include_heavy: bool) {
+        let active: Vec<usize> = (0..self.stages.len())
+            .filter(|&i| self.stages[i].enabled(config) && (include_heavy || !self.stages[i].heavy()))
+            .collect();
+        let mut span = Span::start("collector.enrich", SpanKind::Internal, None);
+        span.set("event.type", event.event_type.as_str());
+        let mut rest = &active[..];
+        while let Some(&first) = rest.first() {
+            let stage = &self.stages[first];
+            if stage.concurrent() {
+                let batch = rest.iter().take_while(|&&i| self.stages[i].concurrent()).count();
+                self.run_concurrent(&rest[..batch], ctx, event, config, &span);
+                rest = &rest[batch..];
+            } else {
+                let _stage_span = stage_span(&span, stage.name());
+                let started = Instant::now();
+                stage.run(ctx, event, config);
+                record_timing(event, stage.name(), started.elapsed());
+                rest = &rest[1..];
+            }
+        }
+        if config.capture_timings_enabled {
+            event.timings = Some(capture_timings(ctx, event));
+        }
+    }
+
+    /// Start the concurrent stages `indices` together, then apply each
+    /// result in order as it arrives or give up on it at its timeout.
+    fn run_concurrent(&self, indices: &[usize], ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config, span: &Span) {
+        let started = Instant::now();
+        let pending: Vec<(usize, Option<Receiver<StageReply>>, Span)> = indices
+            .iter()
+            .map(|&i| {
+                let mut stage_span = stage_span(span, self.stages[i].name());
+                let worker = self.workers[i].get_or_init(|| StageWorker::spawn(Arc::clone(&self.stages[i])));
+                let (reply, result) = crossbeam_channel::bounded(1);
+                match worker.jobs.try_send(Job { hwnd: ctx.hwnd, config: config.clone(), reply }) {
+                    Ok(()) => (i, Some(result), stage_span),
+                    Err(_) => {
+                        log::warn!("Enrich stage {} is still busy with an earlier window, skipping it", self.stages[i].name());
+                        stage_span.fail("busy with an earlier window");
+                        (i, None, stage_span)
+                    }
+                }
+            })
+            .collect();
+        for (i, result, mut stage_span) in pending {
+            let stage = &self.stages[i];
+            let Some(result) = result else {
+                continue;
+            };
+            let deadline = started.checked_add(stage.timeout(config));
+            let received = match deadline {
+                Some(deadline) => result.recv_deadline(deadline).ok(),
+                None => result.recv().ok(),
+            };
+            match received {
+                Some((output, elapsed)) => {
+                    output(ctx, event);
+                    record_timing(event, stage.name(), elapsed);
+                }
+                None => {
+                    log::warn!("Enrich stage {} timed out after {:?}", stage.name(), started.elapsed());
+                    stage_span.fail("timed out");
+                    record_timing(event, stage.name(), started.elapsed());
+                }
+            }
+        }
+    }
+}
+
+/// `collector.stage` span for one stage under the event's `collector.enrich`.
+fn stage_span(parent: &Span, stage: &str) -> Span {
+    let mut span = parent.child("collector.stage");
+    span.set("stage", stage);
+    span
+}
+
+fn record_timing(event: &mut WindowEvent, stage: &str, elapsed: Duration) {
+    let elapsed_us = elapsed.as_micros() as u64;
+    log::trace!("Enrich stage {stage} took {elapsed_us}us");
+    event.enrich_timings_us.insert(stage.to_string(), elapsed_us);
+}
+
+/// Per-event timing breakdown from the stage latencies, with the screenshot
+/// stage split into capture and encode.
+fn capture_timings(ctx: &EnrichContext, event: &WindowEvent) -> CaptureTimings {
+    let stage_ms = |name: &str| event.enrich_timings_us.get(name).map(|&us| us as f64 / 1000.0);
+    let encode_ms = ctx.encode_time.map(duration_ms);
+    CaptureTimings {
+        uia_ms: stage_ms("uia"),
+        screenshot_ms: stage_ms("screenshot").map(|total| (total - encode_ms.unwrap_or(0.0)).max(0.0)),
+        encode_ms,
+        detection_ms: stage_ms("detection"),
+        queue_wait_ms: None,
+        enriched_at: Some(Instant::now()),
+    }
+}
+
+fn builtin_stage(name: &str, config: &Config) -> Option<Box<dyn EnrichStage>> {
+    match name.trim() {
+        "normalize" => Some(Box::new(NormalizeStage)),
+        "language" => Some(Box::new(crate::language::LanguageStage)),
+        "redaction" => RedactionStage::new(&config.redact_pattern).map(|s| Box::new(s) as Box<dyn EnrichStage>),
+        "outline" => Some(Box::new(OutlineStage)),
+        #[cfg(windows)]
+        other => crate::windows::platform_stage(other),
+        #[cfg(not(windows))]
+        _ => None,
+    }
+}
+
+/// Normalizes title and UIA text (NFC, no bidi controls or invisibles, see
+/// `text`) so the same visible text always serializes the same way.
+pub struct NormalizeStage;
+
+impl NormalizeStage {
+    fn normalize_element(element: &mut UiaElement) {
+        element.name = normalize_line(&element.name);
+        if let Some(value) = element.value.as_mut() {
+            *value = normalize_multiline(value);
+        }
+        for child in &mut element.children {
+            Self::normalize_element(child);
+        }
+    }
+}
+
+impl EnrichStage for NormalizeStage {
+    fn name(&self) -> &'static str {
+        "normalize"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.text_normalize_enabled
+    }
+
+    fn run(&self, _ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        event.title = normalize_line(&event.title);
+        if let Some(snapshot) = event.uia.as_mut() {
+            snapshot.focused_name = normalize_line(&snapshot.focused_name);
+            snapshot.document_text = normalize_multiline(&snapshot.document_text);
+            if let Some(focused) = snapshot.focused_element.as_mut() {
+                Self::normalize_element(focused);
+            }
+            for element in &mut snapshot.window_tree {
+                Self::normalize_element(element);
+            }
+        }
+    }
+}
+
+/// Masks sensitive text (titles, UIA names/values/text) matching a regex.
+pub struct RedactionStage {
+    pattern: Regex,
+}
+
+impl RedactionStage {
+    /// Returns `None` (with a warning) if the pattern fails to compile.
+    pub fn new(pattern: &str) -> Option<Self> {
+        match Regex::new(pattern) {
+            Ok(pattern) => Some(Self { pattern }),
+            Err(e) => {
+                log::warn!("Invalid REDACT_PATTERN, redaction disabled: {e}");
+                None
+            }
+        }
+    }
+
+    pub(crate) fn redact(&self, text: &mut String) {
+        if self.pattern.is_match(text) {
+            *text = self.pattern.replace_all(text, REDACTED).into_owned();
+        }
+    }
+
+    pub(crate) fn redact_element(&self, element: &mut UiaElement) {
+        self.redact(&mut element.name);
+        if let Some(value) = element.value.as_mut() {
+            self.redact(value);
+        }
+        for child in &mut element.children {
+            self.redact_element(child);
+        }
+    }
+
+    /// Mask the title and UIA text of an event.
+    pub(crate) fn redact_event(&self, event: &mut WindowEvent) {
+        self.redact(&mut event.title);
+        if let Some(snapshot) = event.uia.as_mut() {
+            self.redact_snapshot(snapshot);
+        }
+    }
+
+    fn redact_snapshot(&self, snapshot: &mut UiaSnapshot) {
+        self.redact(&mut snapshot.focused_name);
+        self.redact(&mut snapshot.document_text);
+        if let Some(focused) = snapshot.focused_element.as_mut() {
+            self.redact_element(focused);
+        }
+        for element in &mut snapshot.window_tree {
+            self.redact_element(element);
+        }
+    }
+}
+
+impl EnrichStage for RedactionStage {
+    fn name(&self) -> &'static str {
+        "redaction"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.redaction_enabled
+    }
+
+    fn run(&self, _ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        self.redact_event(event);
+    }
+}
+
+/// Attaches the text outline of the UIA snapshot (see `outline`). Runs
+/// after redaction so the outline only repeats text that survived it.
+pub struct OutlineStage;
+
+impl EnrichStage for OutlineStage {
+    fn name(&self) -> &'static str {
+        "outline"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.uia_outline_tokens > 0
+    }
+
+    fn run(&self, _ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        if let Some(snapshot) = event.uia.as_mut() {
+            crate::outline::attach(snapshot, config.uia_outline_tokens, !config.uia_outline_only);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+
+    struct TitleSuffix(&'static str);
+
+    impl EnrichStage for TitleSuffix {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn heavy(&self) -> bool {
+            self.0 == "heavy"
+        }
+
+        fn run(&self, _ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+            event.title.push_str(self.0);
+        }
+    }
+
+    /// Concurrent stage that takes the given number of milliseconds.
+    struct Slow(&'static str, u64);
+
+    impl EnrichStage for Slow {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+            (self.capture(ctx.hwnd, config))(ctx, event)
+        }
+
+        fn concurrent(&self) -> bool {
+            true
+        }
+
+        fn capture(&self, _hwnd: isize, _config: &Config) -> StageOutput {
+            std::thread::sleep(Duration::from_millis(self.1));
+            let name = self.0;
+            Box::new(move |_, event| event.title.push_str(name))
+        }
+
+        fn timeout(&self, _config: &Config) -> Duration {
+            Duration::from_millis(400)
+        }
+    }
+
+    fn test_config() -> Config {
+        let mut config = Config::from_env();
+        config.redaction_enabled = true;
+        config
+    }
+
+    #[test]
+    fn test_stages_run_in_order_and_are_timed() {
+        let config = test_config();
+        let pipeline = Pipeline::new(vec![Box::new(TitleSuffix("a")), Box::new(TitleSuffix("b"))]);
+        let mut event = build_activity_event("foreground", 0);
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        assert_eq!(event.title, "ab");
+        assert!(event.enrich_timings_us.contains_key("a"));
+        assert!(event.enrich_timings_us.contains_key("b"));
+    }
+
+    #[test]
+    fn test_concurrent_stages_overlap() {
+        let config = test_config();
+        let pipeline = Pipeline::new(vec![
+            Box::new(TitleSuffix("a")),
+            Box::new(Slow("uia", 150)),
+            Box::new(Slow("screenshot", 150)),
+            Box::new(TitleSuffix("z")),
+        ]);
+        let mut event = build_activity_event("foreground", 0);
+        let started = Instant::now();
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        assert!(started.elapsed() < Duration::from_millis(290), "took {:?}", started.elapsed());
+        assert_eq!(event.title, "auiascreenshotz", "results applied in stage order");
+        assert!(event.enrich_timings_us["uia"] >= 150_000);
+        assert!(event.enrich_timings_us["screenshot"] >= 150_000);
+    }
+
+    #[test]
+    fn test_timed_out_stage_is_left_out() {
+        let config = test_config();
+        let pipeline = Pipeline::new(vec![Box::new(Slow("uia", 700)), Box::new(Slow("screenshot", 10))]);
+        let mut event = build_activity_event("foreground", 0);
+        let started = Instant::now();
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        assert!(started.elapsed() < Duration::from_millis(650), "took {:?}", started.elapsed());
+        assert_eq!(event.title, "screenshot");
+        assert!(event.enrich_timings_us.contains_key("uia"), "the wait is still timed");
+    }
+
+    #[test]
+    fn test_light_run_skips_heavy_stages() {
+        let config = test_config();
+        let pipeline = Pipeline::new(vec![Box::new(TitleSuffix("a")), Box::new(TitleSuffix("heavy"))]);
+        let mut event = build_activity_event("foreground", 0);
+        pipeline.run_light(&mut EnrichContext::default(), &mut event, &config);
+        assert_eq!(event.title, "a");
+        assert!(!event.enrich_timings_us.contains_key("heavy"));
+        assert_eq!(pipeline.heavy_cost(&event), Duration::ZERO);
+
+        event.enrich_timings_us.insert("heavy".to_string(), 1500);
+        assert_eq!(pipeline.heavy_cost(&event), Duration::from_micros(1500));
+    }
+
+    #[test]
+    fn test_capture_timings_behind_flag() {
+        let mut config = test_config();
+        config.capture_timings_enabled = false;
+        let pipeline = Pipeline::new(vec![Box::new(TitleSuffix("uia"))]);
+        let mut event = build_activity_event("foreground", 0);
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        assert!(event.timings.is_none());
+
+        config.capture_timings_enabled = true;
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        let timings = event.timings.as_mut().unwrap();
+        assert!(timings.uia_ms.is_some());
+        assert!(timings.screenshot_ms.is_none());
+        assert!(timings.queue_wait_ms.is_none());
+        timings.mark_dequeued();
+        assert!(timings.queue_wait_ms.is_some());
+    }
+
+    #[test]
+    fn test_capture_timings_split_encode() {
+        let mut event = build_activity_event("foreground", 0);
+        event.enrich_timings_us.insert("screenshot".to_string(), 12_500);
+        event.enrich_timings_us.insert("detection".to_string(), 40_000);
+        let ctx = EnrichContext { encode_time: Some(Duration::from_micros(4_500)), ..Default::default() };
+        let timings = capture_timings(&ctx, &event);
+        assert!((timings.screenshot_ms.unwrap() - 8.0).abs() < 1e-9);
+        assert!((timings.encode_ms.unwrap() - 4.5).abs() < 1e-9);
+        assert!((timings.detection_ms.unwrap() - 40.0).abs() < 1e-9);
+        assert!(timings.uia_ms.is_none());
+    }
+
+    #[test]
+    fn test_disabled_stage_skipped() {
+        let mut config = test_config();
+        config.redaction_enabled = false;
+        let pipeline = Pipeline::new(vec![Box::new(RedactionStage::new(DEFAULT_REDACT_PATTERN).unwrap())]);
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "mail bob@example.com".to_string();
+        pipeline.run(&mut EnrichContext::default(), &mut event, &config);
+        assert_eq!(event.title, "mail bob@example.com");
+        assert!(event.enrich_timings_us.is_empty());
+    }
+
+    #[test]
+    fn test_redaction_masks_title_and_uia() {
+        let config = test_config();
+        let stage = RedactionStage::new(DEFAULT_REDACT_PATTERN).unwrap();
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "Inbox - bob@example.com - Outlook".to_string();
+        event.uia = Some(UiaSnapshot {
+            focused_name: "Card 4111 1111 1111 1111".to_string(),
+            window_tree: vec![UiaElement {
+                name: "alice@example.org".to_string(),
+                value: Some("4111111111111111".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        stage.run(&mut EnrichContext::default(), &mut event, &config);
+
+        assert_eq!(event.title, "Inbox - [REDACTED] - Outlook");
+        let uia = event.uia.unwrap();
+        assert_eq!(uia.focused_name, "Card [REDACTED]");
+        assert_eq!(uia.window_tree[0].name, REDACTED);
+        assert_eq!(uia.window_tree[0].value.as_deref(), Some(REDACTED));
+    }
+
+    #[test]
+    fn test_normalize_stage() {
+        let config = test_config();
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "Re\u{0301}sume\u{200B}.docx\u{200F}".to_string();
+        event.uia = Some(UiaSnapshot {
+            document_text: "line 1\r\nline 2".to_string(),
+            window_tree: vec![UiaElement {
+                name: "\u{2066}Save\u{2069}".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        NormalizeStage.run(&mut EnrichContext::default(), &mut event, &config);
+
+        assert_eq!(event.title, "R\u{00E9}sume.docx");
+        let uia = event.uia.unwrap();
+        assert_eq!(uia.document_text, "line 1\nline 2");
+        assert_eq!(uia.window_tree[0].name, "Save");
+    }
+
+    #[test]
+    fn test_outline_stage() {
+        let mut config = test_config();
+        let mut event = build_activity_event("foreground", 0);
+        event.uia = Some(UiaSnapshot {
+            window_tree: vec![UiaElement { control_type: "button".to_string(), name: "Save".to_string(), is_enabled: true, ..Default::default() }],
+            ..Default::default()
+        });
+
+        config.uia_outline_only = true;
+        OutlineStage.run(&mut EnrichContext::default(), &mut event, &config);
+
+        let uia = event.uia.unwrap();
+        assert_eq!(uia.outline, "[0] button \"Save\"");
+        assert!(uia.window_tree.is_empty(), "outline-only drops the raw tree");
+        config.uia_outline_tokens = 0;
+        assert!(!OutlineStage.enabled(&config));
+    }
+
+    #[test]
+    fn test_invalid_pattern_rejected() {
+        assert!(RedactionStage::new("(unclosed").is_none());
+    }
+
+    #[test]
+    fn test_from_config_skips_unknown_stages() {
+        let mut config = test_config();
+        config.enrich_stages = vec!["bogus".to_string(), "redaction".to_string()];
+        let pipeline = Pipeline::from_config(&config);
+        assert_eq!(pipeline.stage_names(), vec!["redaction"]);
+    }
+}
@@ -0,0 +1,260 @@
+//! Touch and pen input through a synthetic pointer device.
+//!
+//! Touch-first UWP apps and kiosk software often treat synthesized mouse
+//! input differently from a finger: no tap gestures, press-and-hold menus
+//! or manipulations. Commands that take `pointer: "touch"` (or `"pen"`) are
+//! delivered with InjectSyntheticPointerInput instead of SendInput.
+//!
+//! A gesture is a list of frames, each holding one screen point per
+//! contact. The first frame puts the contacts down, later frames move them
+//! and the contacts lift where the last frame left them. Swipes and flicks
+//! can also be replayed as a mouse drag (see `command::drag_mouse`).
+
+use std::time::Duration;
+
+/// Delay between frames of a gesture, about one display refresh.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(10);
+/// Most frames one gesture may take, whatever its duration.
+const MAX_FRAMES: u64 = 500;
+
+/// Input device a command simulates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerKind {
+    Mouse,
+    Touch,
+    Pen,
+}
+
+impl PointerKind {
+    /// The `pointer` parameter: mouse when absent.
+    pub fn from_param(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        match value.map(|v| v.as_str().unwrap_or_default().to_lowercase()).as_deref() {
+            None | Some("mouse") => Ok(Self::Mouse),
+            Some("touch") => Ok(Self::Touch),
+            Some("pen") => Ok(Self::Pen),
+            Some(_) => Err("pointer must be \"mouse\", \"touch\" or \"pen\"".to_string()),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mouse => "mouse",
+            Self::Touch => "touch",
+            Self::Pen => "pen",
+        }
+    }
+}
+
+/// Screen points of every contact at one moment.
+pub type Frame = Vec<(i32, i32)>;
+
+/// Frames a gesture lasting `duration` is split into (at least 1).
+pub fn frame_count(duration: Duration) -> usize {
+    (duration.as_millis() as u64 / FRAME_INTERVAL.as_millis() as u64).clamp(1, MAX_FRAMES) as usize
+}
+
+/// One contact held at (x, y) for `frames` frames.
+pub fn press(x: i32, y: i32, frames: usize) -> Vec<Frame> {
+    vec![vec![(x, y)]; frames.max(1)]
+}
+
+fn lerp(from: i32, to: i32, step: usize, steps: usize) -> i32 {
+    from + ((i64::from(to) - i64::from(from)) * step as i64 / steps as i64) as i32
+}
+
+/// Progress along a swipe (0..=1) at time `t` (0..=1): starts and ends
+/// gently, like a finger dragging content.
+pub fn ease_in_out(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Progress that keeps accelerating, so a flick lifts at full speed and
+/// the target keeps scrolling with inertia.
+pub fn ease_in(t: f64) -> f64 {
+    t * t
+}
+
+/// One contact moving in a straight line from `from` to `to`, its progress
+/// over the frames shaped by `ease`.
+pub fn swipe(from: (i32, i32), to: (i32, i32), frames: usize, ease: fn(f64) -> f64) -> Vec<Frame> {
+    let steps = frames.max(1);
+    let along = |a: i32, b: i32, t: f64| a + (f64::from(b - a) * t).round() as i32;
+    (0..=steps)
+        .map(|step| {
+            let t = ease(step as f64 / steps as f64);
+            vec![(along(from.0, to.0, t), along(from.1, to.1, t))]
+        })
+        .collect()
+}
+
+/// End point of a swipe of `distance` pixels from `from` toward `direction`.
+pub fn swipe_end(from: (i32, i32), direction: &str, distance: i32) -> Option<(i32, i32)> {
+    let (x, y) = from;
+    match direction.to_lowercase().as_str() {
+        "left" => Some((x - distance, y)),
+        "right" => Some((x + distance, y)),
+        "up" => Some((x, y - distance)),
+        "down" => Some((x, y + distance)),
+        _ => None,
+    }
+}
+
+/// Two contacts on a horizontal line through `center`, moving from `start`
+/// to `end` pixels apart: spreading them zooms in, closing them zooms out.
+pub fn pinch(center: (i32, i32), start: i32, end: i32, frames: usize) -> Vec<Frame> {
+    let steps = frames.max(1);
+    (0..=steps)
+        .map(|step| {
+            let half = lerp(start, end, step, steps) / 2;
+            vec![(center.0 - half, center.1), (center.0 + half, center.1)]
+        })
+        .collect()
+}
+
+/// A synthetic touch or pen device; destroyed when dropped.
+#[cfg(windows)]
+pub struct SyntheticPointer {
+    device: windows::Win32::UI::Controls::HSYNTHETICPOINTERDEVICE,
+    kind: PointerKind,
+    contacts: usize,
+}
+
+#[cfg(windows)]
+impl SyntheticPointer {
+    /// A device with room for `contacts` simultaneous contacts; pen
+    /// devices have exactly one.
+    pub fn new(kind: PointerKind, contacts: usize) -> Result<Self, String> {
+        use windows::Win32::UI::Controls::{CreateSyntheticPointerDevice, POINTER_FEEDBACK_DEFAULT};
+        use windows::Win32::UI::WindowsAndMessaging::{PT_PEN, PT_TOUCH};
+
+        let pointer_type = match kind {
+            PointerKind::Touch => PT_TOUCH,
+            PointerKind::Pen if contacts == 1 => PT_PEN,
+            PointerKind::Pen => return Err("a pen has a single contact".to_string()),
+            PointerKind::Mouse => return Err("mouse input does not use a synthetic pointer".to_string()),
+        };
+        let device = unsafe { CreateSyntheticPointerDevice(pointer_type, contacts as u32, POINTER_FEEDBACK_DEFAULT) }
+            .map_err(|e| format!("cannot create a synthetic {} device: {e}", kind.as_str()))?;
+        Ok(Self { device, kind, contacts })
+    }
+
+    /// Inject a gesture, one frame every `FRAME_INTERVAL`, then lift the
+    /// contacts at their last position. A cancelled command lifts them
+    /// early, wherever they are.
+    pub fn play(&self, gesture: &[Frame]) -> Result<(), String> {
+        use windows::Win32::UI::Input::Pointer::*;
+
+        let Some(last) = gesture.last() else {
+            return Ok(());
+        };
+        if gesture.iter().any(|frame| frame.len() != self.contacts) {
+            return Err(format!("every frame needs {} contact(s)", self.contacts));
+        }
+        let contact = POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT;
+        let mut lift = last;
+        for (index, frame) in gesture.iter().enumerate() {
+            let flags = if index == 0 { POINTER_FLAG_DOWN | contact } else { POINTER_FLAG_UPDATE | contact };
+            self.inject(frame, flags)?;
+            if !crate::cancel::pause(FRAME_INTERVAL) {
+                lift = frame;
+                break;
+            }
+        }
+        self.inject(lift, POINTER_FLAG_UP)
+    }
+
+    fn inject(&self, frame: &Frame, flags: windows::Win32::UI::Input::Pointer::POINTER_FLAGS) -> Result<(), String> {
+        crate::command::before_input();
+        use windows::Win32::Foundation::{POINT, RECT};
+        use windows::Win32::UI::Controls::{POINTER_TYPE_INFO, POINTER_TYPE_INFO_0};
+        use windows::Win32::UI::Input::Pointer::*;
+        use windows::Win32::UI::WindowsAndMessaging::*;
+
+        let pressure = if flags.contains(POINTER_FLAG_UP) { 0 } else { 512 };
+        let infos: Vec<POINTER_TYPE_INFO> = frame
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| {
+                let primary = if id == 0 { POINTER_FLAG_PRIMARY } else { POINTER_FLAGS(0) };
+                let pointer_info = POINTER_INFO {
+                    pointerType: if self.kind == PointerKind::Pen { PT_PEN } else { PT_TOUCH },
+                    pointerId: id as u32,
+                    pointerFlags: flags | primary,
+                    ptPixelLocation: POINT { x, y },
+                    ..Default::default()
+                };
+                match self.kind {
+                    PointerKind::Pen => POINTER_TYPE_INFO {
+                        r#type: PT_PEN,
+                        Anonymous: POINTER_TYPE_INFO_0 {
+                            penInfo: POINTER_PEN_INFO {
+                                pointerInfo: pointer_info,
+                                penFlags: PEN_FLAG_NONE,
+                                penMask: PEN_MASK_PRESSURE,
+                                pressure,
+                                ..Default::default()
+                            },
+                        },
+                    },
+                    _ => POINTER_TYPE_INFO {
+                        r#type: PT_TOUCH,
+                        Anonymous: POINTER_TYPE_INFO_0 {
+                            touchInfo: POINTER_TOUCH_INFO {
+                                pointerInfo: pointer_info,
+                                touchFlags: TOUCH_FLAG_NONE,
+                                touchMask: TOUCH_MASK_CONTACTAREA | TOUCH_MASK_ORIENTATION | TOUCH_MASK_PRESSURE,
+                                rcContact: RECT { left: x - 2, top: y - 2, right: x + 2, bottom: y + 2 },
+                                orientation: 90,
+                                pressure,
+                                ..Default::default()
+                            },
+                        },
+                    },
+                }
+            })
+            .collect();
+        unsafe { InjectSyntheticPointerInput(self.device, &infos) }.map_err(|e| format!("pointer injection failed: {e}"))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for SyntheticPointer {
+    fn drop(&mut self) {
+        unsafe { windows::Win32::UI::Controls::DestroySyntheticPointerDevice(self.device) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pointer_kind_from_param() {
+        assert_eq!(PointerKind::from_param(None), Ok(PointerKind::Mouse));
+        assert_eq!(PointerKind::from_param(Some(&json!("Touch"))), Ok(PointerKind::Touch));
+        assert_eq!(PointerKind::from_param(Some(&json!("pen"))), Ok(PointerKind::Pen));
+        assert!(PointerKind::from_param(Some(&json!("stylus"))).is_err());
+        assert!(PointerKind::from_param(Some(&json!(1))).is_err());
+    }
+
+    #[test]
+    fn test_gesture_frames() {
+        assert_eq!(frame_count(Duration::from_millis(300)), 30);
+        assert_eq!(frame_count(Duration::ZERO), 1);
+        assert_eq!(press(5, 6, 0), vec![vec![(5, 6)]]);
+
+        let linear = |t| t;
+        let path = swipe((100, 200), swipe_end((100, 200), "left", 300).unwrap(), 3, linear);
+        assert_eq!(path, vec![vec![(100, 200)], vec![(0, 200)], vec![(-100, 200)], vec![(-200, 200)]]);
+        let eased = swipe((0, 0), (0, 400), 4, ease_in_out);
+        assert_eq!(eased.iter().map(|f| f[0].1).collect::<Vec<_>>(), vec![0, 63, 200, 338, 400]);
+        let flick = swipe((0, 0), (400, 0), 4, ease_in);
+        assert_eq!(flick.iter().map(|f| f[0].0).collect::<Vec<_>>(), vec![0, 25, 100, 225, 400]);
+        assert_eq!(swipe_end((0, 0), "sideways", 10), None);
+
+        let spread = pinch((500, 400), 100, 300, 2);
+        assert_eq!(spread.first().unwrap(), &vec![(450, 400), (550, 400)]);
+        assert_eq!(spread.last().unwrap(), &vec![(350, 400), (650, 400)]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Execution policy: safe mode gating for input-injecting actions.
+//!
+//! Safe mode is active when the runtime flag is set (from `SAFE_MODE` at
+//! startup or the `set_safe_mode` command), `SAFE_MODE` is on in the local
+//! configuration, or the marker file written by the Tauri tray toggle exists.
+//! The configuration and the marker file are the user's local switches: they
+//! are a floor the backend cannot turn safe mode off below.
+//!
+//! Self-exclusion keeps DesktopAI's own windows (avatar, palette) out of
+//! events and away from injected input unless a command opts in.
+//!
+//! A context directory (`set_context_directory`) scopes file operations:
+//! relative paths resolve against it and paths outside it are refused.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::command::Command;
+use crate::config::Config;
+
+static SAFE_MODE: AtomicBool = AtomicBool::new(false);
+static CONTEXT_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Actions that only read desktop state and are allowed in safe mode
+/// (`export_state` only without a `path`, see `is_read_only_command`).
+const READ_ONLY_ACTIONS: &[&str] = &[
+    "observe",
+    "screenshot_region",
+    "ocr",
+    "find_elements",
+    "set_safe_mode",
+    "set_context_directory",
+    "set_event_profile",
+    "export_state",
+    "preview_events",
+    "show_element_labels",
+    "hide_element_labels",
+];
+
+/// Action prefixes that are read-only by convention (`get_*`, `list_*`, `wait_for_*`).
+const READ_ONLY_PREFIXES: &[&str] = &["get_", "list_", "wait_for_"];
+
+/// Set the runtime safe mode flag.
+pub fn set_safe_mode(enabled: bool) {
+    SAFE_MODE.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether the runtime flag (startup config or `set_safe_mode` command) is set.
+pub fn safe_mode_flag() -> bool {
+    SAFE_MODE.load(Ordering::SeqCst)
+}
+
+/// Whether the tray's marker file is present.
+pub fn safe_mode_forced(config: &Config) -> bool {
+    !config.safe_mode_flag_path.is_empty() && Path::new(&config.safe_mode_flag_path).exists()
+}
+
+/// Whether safe mode is currently in effect from any source.
+pub fn safe_mode_active(config: &Config) -> bool {
+    safe_mode_flag() || config.safe_mode || safe_mode_forced(config)
+}
+
+/// Whether an action only reads desktop state (never injects input).
+pub fn is_read_only_action(action: &str) -> bool {
+    READ_ONLY_ACTIONS.contains(&action)
+        || READ_ONLY_PREFIXES.iter().any(|prefix| action.starts_with(prefix))
+}
+
+/// Whether a command only reads state: a read-only action, unless it is
+/// `export_state` writing its archive to a file.
+pub fn is_read_only_command(cmd: &Command) -> bool {
+    is_read_only_action(&cmd.action) && !(cmd.action == "export_state" && cmd.parameters.contains_key("path"))
+}
+
+/// Returns the denial reason if policy blocks the command, or `None` if allowed.
+pub fn check_command(cmd: &Command, config: &Config) -> Option<String> {
+    if is_read_only_command(cmd) || !safe_mode_active(config) {
+        return None;
+    }
+    Some(format!("'{}' is blocked while safe mode is on", cmd.action))
+}
+
+/// Set or clear the directory file operations are scoped to.
+pub fn set_context_directory(dir: Option<PathBuf>) {
+    *CONTEXT_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+/// The current context directory, if one is set.
+pub fn context_directory() -> Option<PathBuf> {
+    CONTEXT_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Resolve a path named by a file operation. Without a context directory it
+/// must be absolute; with one, relative paths are joined to it and the
+/// result must exist inside it (symlinks and `..` are resolved first).
+pub fn scope_path(path: &Path, context: Option<&Path>) -> Result<PathBuf, String> {
+    let Some(context) = context else {
+        if !path.is_absolute() {
+            return Err(format!("path must be absolute: {}", path.display()));
+        }
+        return Ok(path.to_path_buf());
+    };
+    let joined = context.join(path);
+    let real = joined.canonicalize().map_err(|_| format!("path not found: {}", joined.display()))?;
+    let root = context.canonicalize().map_err(|_| format!("context directory not found: {}", context.display()))?;
+    if !real.starts_with(&root) {
+        return Err(format!("{} is outside the context directory {}", joined.display(), context.display()));
+    }
+    Ok(joined)
+}
+
+/// Resolve the path of a file about to be created like `scope_path`: the
+/// directory it goes in must exist (inside the context directory, if set).
+pub fn scope_new_path(path: &Path, context: Option<&Path>) -> Result<PathBuf, String> {
+    let name = path.file_name().ok_or_else(|| format!("not a file path: {}", path.display()))?;
+    if context.is_none() && !path.is_absolute() {
+        return Err(format!("path must be absolute: {}", path.display()));
+    }
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Ok(scope_path(dir, context)?.join(name))
+}
+
+/// Whether `process_exe` (full path or bare file name) is one of DesktopAI's
+/// own processes from `SELF_EXCLUDE_PROCESSES`. Matching is on the file name,
+/// case-insensitively.
+pub fn is_self_process(process_exe: &str, config: &Config) -> bool {
+    let file_name = process_exe.rsplit(['\\', '/']).next().unwrap_or(process_exe);
+    !file_name.is_empty()
+        && config
+            .self_exclude_processes
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(file_name))
+}
+
+/// Whether a command may act on DesktopAI's own windows: globally via
+/// `ALLOW_SELF_TARGETING`, or per command with `"allow_self": true`.
+pub fn self_targeting_allowed(cmd: &Command, config: &Config) -> bool {
+    config.allow_self_targeting
+        || cmd.parameters.get("allow_self").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_actions() {
+        assert!(is_read_only_action("observe"));
+        assert!(is_read_only_action("screenshot_region"));
+        assert!(is_read_only_action("ocr"));
+        assert!(is_read_only_action("find_elements"));
+        assert!(is_read_only_action("show_element_labels"));
+        assert!(is_read_only_action("export_state"));
+        assert!(is_read_only_action("preview_events"));
+        assert!(is_read_only_action("set_safe_mode"));
+        assert!(is_read_only_action("set_context_directory"));
+        assert!(is_read_only_action("set_event_profile"));
+        assert!(is_read_only_action("get_element_tree"));
+        assert!(is_read_only_action("get_text"));
+        assert!(is_read_only_action("list_windows"));
+        assert!(is_read_only_action("list_monitors"));
+        assert!(is_read_only_action("get_window_rect"));
+        assert!(is_read_only_action("list_workflows"));
+        assert!(is_read_only_action("wait_for_window"));
+    }
+
+    #[test]
+    fn test_input_actions_not_read_only() {
+        for action in &[
+            "click",
+            "triple_click",
+            "middle_click",
+            "click_detection",
+            "if_exists",
+            "run_workflow",
+            "type_text",
+            "send_keys",
+            "scroll",
+            "open_application",
+            "focus_window",
+            "start_menu_search",
+            "hover",
+            "close_window",
+            "minimize_window",
+            "move_window",
+            "switch_desktop",
+            "swipe",
+            "flick",
+            "pinch",
+            "paste_text",
+            "select_item",
+            "expand",
+            "collapse",
+            "scroll_into_view",
+            "set_range_value",
+            "invoke_menu",
+            "move_to_recycle_bin",
+            "empty_recycle_bin",
+            "drop_files",
+            "browser_navigate",
+            "run_shell",
+            "import_state",
+            "purge_data",
+            "create_diagnostic_bundle",
+            "kill_process",
+            "close_application",
+            "self_test",
+        ] {
+            assert!(!is_read_only_action(action), "{action} must not be read-only");
+        }
+    }
+
+    #[test]
+    fn test_forced_by_marker_file() {
+        let mut config = Config::from_env();
+        config.safe_mode_flag_path = String::new();
+        assert!(!safe_mode_forced(&config));
+
+        let path = std::env::temp_dir().join(format!("desktopai_safe_mode_{}", std::process::id()));
+        config.safe_mode_flag_path = path.to_string_lossy().into_owned();
+        assert!(!safe_mode_forced(&config));
+        std::fs::write(&path, b"").unwrap();
+        assert!(safe_mode_forced(&config));
+        assert!(safe_mode_active(&config));
+        let command = |action: &str, parameters: serde_json::Value| -> Command {
+            serde_json::from_value(serde_json::json!({"command_id": "c1", "action": action, "parameters": parameters})).unwrap()
+        };
+        assert!(check_command(&command("click", serde_json::json!({})), &config).is_some());
+        assert!(check_command(&command("observe", serde_json::json!({})), &config).is_none());
+        assert!(check_command(&command("export_state", serde_json::json!({})), &config).is_none());
+        assert!(
+            check_command(&command("export_state", serde_json::json!({"path": "C:\\state.json"})), &config).is_some(),
+            "writing a file is not read-only"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_is_self_process() {
+        let mut config = Config::from_env();
+        config.self_exclude_processes = vec!["desktopai.exe".to_string()];
+        assert!(is_self_process("C:\\Program Files\\DesktopAI\\DesktopAI.exe", &config));
+        assert!(is_self_process("desktopai.exe", &config));
+        assert!(!is_self_process("C:\\Windows\\notepad.exe", &config));
+        assert!(!is_self_process("", &config));
+    }
+
+    #[test]
+    fn test_self_targeting_opt_in() {
+        let mut config = Config::from_env();
+        config.allow_self_targeting = false;
+        let mut cmd: Command = serde_json::from_value(serde_json::json!({
+            "command_id": "c1",
+            "action": "click",
+        }))
+        .unwrap();
+        assert!(!self_targeting_allowed(&cmd, &config));
+        cmd.parameters.insert("allow_self".to_string(), serde_json::json!(true));
+        assert!(self_targeting_allowed(&cmd, &config));
+        cmd.parameters.clear();
+        config.allow_self_targeting = true;
+        assert!(self_targeting_allowed(&cmd, &config));
+    }
+
+    #[test]
+    fn test_scope_path_stays_in_context_directory() {
+        let root = std::env::temp_dir().join(format!("desktopai_context_{}", std::process::id()));
+        let shots = root.join("shots");
+        std::fs::create_dir_all(&shots).unwrap();
+        std::fs::write(shots.join("a.png"), b"x").unwrap();
+
+        assert!(scope_path(Path::new("shots/a.png"), None).unwrap_err().contains("absolute"));
+        assert_eq!(scope_path(Path::new("a.png"), Some(&shots)).unwrap(), shots.join("a.png"));
+        assert!(scope_path(Path::new("b.png"), Some(&shots)).unwrap_err().contains("not found"));
+        assert!(scope_path(Path::new(".."), Some(&shots)).unwrap_err().contains("outside"));
+        assert!(scope_path(&root, Some(&shots)).unwrap_err().contains("outside"));
+        assert!(scope_path(&shots.join("a.png"), Some(&shots)).is_ok());
+
+        assert_eq!(scope_new_path(Path::new("b.png"), Some(&shots)).unwrap(), shots.join(".").join("b.png"));
+        assert!(scope_new_path(&shots.join("b.png"), None).is_ok());
+        assert!(scope_new_path(Path::new("b.png"), None).unwrap_err().contains("absolute"));
+        assert!(scope_new_path(Path::new("../b.png"), Some(&shots)).unwrap_err().contains("outside"));
+        assert!(scope_new_path(Path::new("new/b.png"), Some(&shots)).unwrap_err().contains("not found"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+//! Capture profiles tied to the power source.
+//!
+//! On AC power the collector captures at full fidelity. Unplugged, it
+//! switches to the battery profile (`Config::battery_capture`): fewer fully
+//! enriched observations (`BATTERY_OBSERVE_MAX_PER_SEC`), smaller
+//! screenshots (`BATTERY_SCREENSHOT_MAX_WIDTH`/`_HEIGHT`) and no detection.
+//! The power source is read at most every `POLL_INTERVAL`, so plugging in
+//! takes effect on the next capture after that. Desktops without a battery,
+//! or an unknown power state, stay on AC. The active profile rides along on
+//! the heartbeat (see network.rs); `POWER_PROFILES_ENABLED=false` keeps the
+//! AC profile on battery too.
+
+use serde::Serialize;
+use std::borrow::Cow;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// How long a reading of the power source is trusted.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Which capture profile applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    Ac,
+    Battery,
+}
+
+/// The machine's power source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState {
+    pub on_battery: bool,
+    /// Remaining charge in percent, when the system reports it.
+    pub battery_percent: Option<u8>,
+}
+
+/// The active profile, as reported in heartbeats.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PowerReport {
+    pub profile: Profile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_percent: Option<u8>,
+}
+
+impl PowerReport {
+    /// Whether the profile differs from `other`; the charge alone does not count.
+    pub fn changed_from(&self, other: &PowerReport) -> bool {
+        self.profile != other.profile
+    }
+}
+
+static CACHE: Mutex<Option<(Instant, Option<PowerState>)>> = Mutex::new(None);
+
+/// The current power source, `None` when unknown; cached for `POLL_INTERVAL`.
+pub fn state() -> Option<PowerState> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    match *cache {
+        Some((at, state)) if at.elapsed() < POLL_INTERVAL => state,
+        _ => {
+            let state = read();
+            *cache = Some((Instant::now(), state));
+            state
+        }
+    }
+}
+
+#[cfg(windows)]
+fn read() -> Option<PowerState> {
+    use ::windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+    // ACLineStatus: 0 offline, 1 online, 255 unknown; BatteryLifePercent 255 = unknown
+    Some(PowerState {
+        on_battery: status.ACLineStatus == 0,
+        battery_percent: (status.BatteryLifePercent <= 100).then_some(status.BatteryLifePercent),
+    })
+}
+
+#[cfg(not(windows))]
+fn read() -> Option<PowerState> {
+    None
+}
+
+/// The profile for `state` under `config`.
+pub fn profile(config: &Config, state: Option<PowerState>) -> Profile {
+    if config.power_profiles_enabled && state.is_some_and(|s| s.on_battery) {
+        Profile::Battery
+    } else {
+        Profile::Ac
+    }
+}
+
+/// The active profile and remaining charge, for the heartbeat.
+pub fn report(config: &Config) -> PowerReport {
+    let state = state();
+    PowerReport { profile: profile(config, state), battery_percent: state.and_then(|s| s.battery_percent) }
+}
+
+/// `config` adjusted for the active profile.
+pub fn capture_config(config: &Config) -> Cow<'_, Config> {
+    match profile(config, state()) {
+        Profile::Ac => Cow::Borrowed(config),
+        Profile::Battery => Cow::Owned(config.battery_capture()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_follows_power_source() {
+        let mut config = Config::from_env();
+        config.power_profiles_enabled = true;
+        let battery = Some(PowerState { on_battery: true, battery_percent: Some(40) });
+        let plugged = Some(PowerState { on_battery: false, battery_percent: Some(100) });
+        assert_eq!(profile(&config, battery), Profile::Battery);
+        assert_eq!(profile(&config, plugged), Profile::Ac);
+        assert_eq!(profile(&config, None), Profile::Ac, "unknown stays on full fidelity");
+        config.power_profiles_enabled = false;
+        assert_eq!(profile(&config, battery), Profile::Ac);
+    }
+
+    #[test]
+    fn test_report_serialization() {
+        let report = PowerReport { profile: Profile::Battery, battery_percent: Some(40) };
+        assert_eq!(serde_json::to_value(report).unwrap(), serde_json::json!({"profile": "battery", "battery_percent": 40}));
+        let ac = PowerReport { profile: Profile::Ac, battery_percent: None };
+        assert_eq!(serde_json::to_value(ac).unwrap(), serde_json::json!({"profile": "ac"}));
+        assert!(ac.changed_from(&report));
+        assert!(!report.changed_from(&PowerReport { battery_percent: Some(39), ..report }));
+    }
+}
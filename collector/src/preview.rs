@@ -0,0 +1,225 @@
+//! Privacy preview: what the next outgoing events actually contain.
+//!
+//! `preview_events` with `count` starts capturing the next `count` events
+//! the collector hands on, exactly as sent (after every enrichment stage,
+//! redaction included); any later call returns what has been captured so
+//! far. Each event is summarized the way a person would check it: title,
+//! the text snippets it carries, how much was redacted and the screenshot
+//! itself, so the Tauri app's preview window can show it as a thumbnail.
+//!
+//! A `redact_pattern` is applied to the captured events on top of the
+//! configured redaction, so a candidate `REDACT_PATTERN` can be tried on
+//! real events before it is adopted.
+
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::event::{UiaElement, WindowEvent};
+use crate::pipeline::{RedactionStage, REDACTED};
+
+/// Most events one preview captures.
+pub const MAX_PREVIEW_EVENTS: usize = 20;
+/// Characters kept of each text snippet.
+const SNIPPET_CHARS: usize = 160;
+/// Snippets listed per event.
+const MAX_SNIPPETS: usize = 12;
+
+struct Capture {
+    remaining: usize,
+    events: Vec<WindowEvent>,
+}
+
+impl Capture {
+    const fn new() -> Self {
+        Self { remaining: 0, events: Vec::new() }
+    }
+
+    fn arm(&mut self, count: usize) -> usize {
+        self.remaining = count.min(MAX_PREVIEW_EVENTS);
+        self.events.clear();
+        self.remaining
+    }
+
+    fn record(&mut self, event: &WindowEvent) {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.events.push(event.clone());
+        }
+    }
+}
+
+static CAPTURE: Mutex<Capture> = Mutex::new(Capture::new());
+
+/// Drop anything captured and capture the next `count` outgoing events
+/// (at most `MAX_PREVIEW_EVENTS`). Returns the count actually armed.
+pub fn arm(count: usize) -> usize {
+    CAPTURE.lock().unwrap_or_else(|e| e.into_inner()).arm(count)
+}
+
+/// Offer an outgoing event to an armed preview.
+pub fn record(event: &WindowEvent) {
+    CAPTURE.lock().unwrap_or_else(|e| e.into_inner()).record(event);
+}
+
+/// Events captured so far and how many the preview still waits for.
+pub fn captured() -> (Vec<WindowEvent>, usize) {
+    let capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    (capture.events.clone(), capture.remaining)
+}
+
+/// One captured event as the preview window shows it.
+#[derive(Debug, Serialize)]
+pub struct PreviewEntry {
+    pub event_type: String,
+    pub timestamp: String,
+    /// Executable file name.
+    pub process: String,
+    pub title: String,
+    /// Distinct non-empty UIA texts (focused element first), truncated.
+    pub snippets: Vec<String>,
+    pub uia_elements: usize,
+    /// Redaction markers anywhere in the event.
+    pub redactions: usize,
+    /// Markers added by the candidate pattern, when one was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newly_redacted: Option<usize>,
+    /// Size of the event as serialized for the backend.
+    pub bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot_b64: Option<String>,
+    /// The full event, when asked for with `raw`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<WindowEvent>,
+}
+
+fn snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn collect_texts<'a>(element: &'a UiaElement, texts: &mut Vec<&'a str>) {
+    texts.push(&element.name);
+    if let Some(value) = &element.value {
+        texts.push(value);
+    }
+    for child in &element.children {
+        collect_texts(child, texts);
+    }
+}
+
+fn count_elements(elements: &[UiaElement]) -> usize {
+    elements.iter().map(|e| 1 + count_elements(&e.children)).sum()
+}
+
+/// Summarize `event`, first applying `candidate` redaction if given.
+pub fn summarize(event: &WindowEvent, candidate: Option<&RedactionStage>, raw: bool) -> PreviewEntry {
+    let redactions = |event: &WindowEvent| {
+        serde_json::to_string(event).map(|json| json.matches(REDACTED).count()).unwrap_or(0)
+    };
+    let mut event = event.clone();
+    let newly_redacted = candidate.map(|stage| {
+        let before = redactions(&event);
+        stage.redact_event(&mut event);
+        redactions(&event).saturating_sub(before)
+    });
+
+    let mut texts = Vec::new();
+    let mut uia_elements = 0;
+    if let Some(uia) = &event.uia {
+        texts.push(uia.focused_name.as_str());
+        if let Some(focused) = &uia.focused_element {
+            collect_texts(focused, &mut texts);
+        }
+        for element in &uia.window_tree {
+            collect_texts(element, &mut texts);
+        }
+        texts.push(&uia.document_text);
+        uia_elements = count_elements(&uia.window_tree);
+    }
+    let mut snippets: Vec<String> = Vec::new();
+    for text in texts.into_iter().map(str::trim).filter(|t| !t.is_empty()) {
+        let text = snippet(text);
+        if !snippets.contains(&text) {
+            snippets.push(text);
+        }
+        if snippets.len() == MAX_SNIPPETS {
+            break;
+        }
+    }
+
+    let json = serde_json::to_string(&event).unwrap_or_default();
+    PreviewEntry {
+        event_type: event.event_type.clone(),
+        timestamp: event.timestamp.clone(),
+        process: event.process_exe.rsplit(['\\', '/']).next().unwrap_or_default().to_string(),
+        title: event.title.clone(),
+        snippets,
+        uia_elements,
+        redactions: json.matches(REDACTED).count(),
+        newly_redacted,
+        bytes: json.len(),
+        screenshot_b64: event.screenshot_b64.clone(),
+        event: raw.then_some(event),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{build_activity_event, UiaSnapshot};
+
+    fn event(title: &str) -> WindowEvent {
+        let mut event = build_activity_event("foreground", 0);
+        event.title = title.to_string();
+        event
+    }
+
+    #[test]
+    fn test_captures_the_next_events_only() {
+        let mut capture = Capture::new();
+        capture.record(&event("before"));
+        assert_eq!(capture.arm(2), 2);
+        for title in ["one", "two", "three"] {
+            capture.record(&event(title));
+        }
+        let titles: Vec<&str> = capture.events.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!((titles, capture.remaining), (vec!["one", "two"], 0));
+        assert_eq!(capture.arm(100), MAX_PREVIEW_EVENTS);
+        assert!(capture.events.is_empty());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut sent = event("Inbox - [REDACTED] - Outlook");
+        sent.process_exe = r"C:\Program Files\Outlook\OUTLOOK.EXE".to_string();
+        sent.uia = Some(UiaSnapshot {
+            focused_name: "Subject".to_string(),
+            document_text: "x".repeat(SNIPPET_CHARS + 10),
+            window_tree: vec![UiaElement {
+                name: "Subject".to_string(),
+                value: Some("Call Ann at 555-0100".to_string()),
+                children: vec![UiaElement::default()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        let entry = summarize(&sent, None, false);
+        assert_eq!(entry.process, "OUTLOOK.EXE");
+        assert_eq!(entry.snippets[..2], ["Subject", "Call Ann at 555-0100"]);
+        assert_eq!(entry.snippets[2].chars().count(), SNIPPET_CHARS + 1);
+        assert_eq!((entry.uia_elements, entry.redactions, entry.newly_redacted), (2, 1, None));
+        assert!(entry.event.is_none());
+
+        let phones = RedactionStage::new(r"\d{3}-\d{4}").unwrap();
+        let tried = summarize(&sent, Some(&phones), true);
+        assert_eq!(tried.snippets[1], "Call Ann at [REDACTED]");
+        assert_eq!((tried.redactions, tried.newly_redacted), (2, Some(1)));
+        assert!(tried.event.is_some());
+        // The captured event itself is left as it was sent
+        assert_eq!(summarize(&sent, None, false).redactions, 1);
+    }
+}
@@ -0,0 +1,404 @@
+//! Command recorder: captures real backend commands and their results as
+//! replayable JSON fixtures.
+//!
+//! With `COMMAND_RECORD_DIR` set, every executed command is written to that
+//! directory together with its result. Commands that pick a UI element also
+//! record the UI tree they searched (the window they name, else the
+//! foreground one), captured just before they ran. `replay_fixture`
+//! resolves the command's element again against that recorded tree and
+//! reports where the outcome differs, so element-resolution regressions are
+//! checked against scenarios captured on real desktops without touching the
+//! desktop the replay runs on. Fixtures without a tree are not replayed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::command::{Command, CommandResult};
+use crate::config::Config;
+use crate::event::UiaElement;
+
+/// Result fields dropped when recording: large and never stable across runs.
+const VOLATILE_FIELDS: &[&str] = &["screenshot_b64", "screenshot_before_b64", "screen_diff", "uia"];
+
+/// Parameters, at any depth, whose values never reach a fixture (e.g. the
+/// `create_diagnostic_bundle` password).
+const SECRET_PARAMETERS: &[&str] = &["password", "passphrase", "secret", "token", "authorization"];
+
+/// Actions whose element replay can resolve from a recorded tree.
+const RESOLVING_ACTIONS: &[&str] = &[
+    "click",
+    "find_elements",
+    "get_element_text",
+    "paste_text",
+    "select_item",
+    "expand_collapse",
+    "set_range_value",
+    "scroll_into_view",
+];
+
+/// Actions that take the first element with the `automation_id`, else the
+/// first with the `name` (see `resolve_uia_element`); `click` does too
+/// unless it has an `index` or `control_type`.
+const FIRST_MATCH_ACTIONS: &[&str] = &["select_item", "expand_collapse", "set_range_value", "scroll_into_view"];
+
+/// Trees captured for commands still executing, by command id.
+static TREES: Mutex<Option<HashMap<String, UiaElement>>> = Mutex::new(None);
+
+/// One recorded command and the result it produced.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Fixture {
+    /// `std::env::consts::OS` of the recording machine.
+    pub platform: String,
+    pub recorded_at: String,
+    pub command: Command,
+    pub result: serde_json::Value,
+    /// UI tree the command resolved its element in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<UiaElement>,
+}
+
+impl Fixture {
+    pub fn new(cmd: &Command, result: &CommandResult) -> Self {
+        let mut result = serde_json::to_value(result).unwrap_or_default();
+        if let Some(map) = result.as_object_mut() {
+            for field in VOLATILE_FIELDS {
+                map.remove(*field);
+            }
+        }
+        let mut command = cmd.clone();
+        for (key, value) in command.parameters.iter_mut() {
+            redact_secrets(key, value);
+        }
+        Self {
+            platform: std::env::consts::OS.to_string(),
+            recorded_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            command,
+            result,
+            tree: None,
+        }
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    SECRET_PARAMETERS.iter().any(|secret| key.eq_ignore_ascii_case(secret))
+}
+
+/// Mask `value` when `key` names a secret, else any secret nested in it.
+fn redact_secrets(key: &str, value: &mut serde_json::Value) {
+    if is_secret(key) {
+        *value = serde_json::json!(crate::pipeline::REDACTED);
+        return;
+    }
+    match value {
+        serde_json::Value::Object(map) => map.iter_mut().for_each(|(key, value)| redact_secrets(key, value)),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|item| redact_secrets(key, item)),
+        _ => {}
+    }
+}
+
+/// Whether `cmd` picks an element replay can resolve again.
+fn resolves_element(cmd: &Command) -> bool {
+    let given = |key: &str| cmd.parameters.get(key).and_then(|v| v.as_str()).is_some_and(|v| !v.is_empty());
+    RESOLVING_ACTIONS.contains(&cmd.action.as_str())
+        && cmd.parameters.get("relative_to").and_then(|v| v.as_str()) != Some("element")
+        && (given("automation_id") || given("name") || given("control_type"))
+}
+
+/// Before `cmd` executes: keep the tree it will search, when recording.
+pub fn capture_tree(cmd: &Command, config: &Config) {
+    if config.command_record_dir.is_empty() || !resolves_element(cmd) {
+        return;
+    }
+    if let Some(tree) = crate::command::resolution_tree(cmd, config) {
+        TREES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashMap::new)
+            .insert(cmd.command_id.clone(), tree);
+    }
+}
+
+fn take_tree(command_id: &str) -> Option<UiaElement> {
+    TREES.lock().unwrap_or_else(|e| e.into_inner()).as_mut()?.remove(command_id)
+}
+
+/// File name for a fixture: timestamp, action and a filesystem-safe command id.
+fn fixture_file_name(cmd: &Command) -> String {
+    let id: String = cmd
+        .command_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{}-{}.json", crate::clock::wall_ms(), cmd.action, id)
+}
+
+/// Write `cmd`, its `result` and the tree captured for it to `dir` as a
+/// fixture. Returns the file path.
+pub fn record_exchange(dir: &str, cmd: &Command, result: &CommandResult) -> std::io::Result<PathBuf> {
+    let mut fixture = Fixture::new(cmd, result);
+    fixture.tree = take_tree(&cmd.command_id);
+    fs::create_dir_all(dir)?;
+    let path = Path::new(dir).join(fixture_file_name(cmd));
+    let json = serde_json::to_string_pretty(&fixture)?;
+    fs::write(&path, json)?;
+    Ok(path)
+}
+
+/// Load every `*.json` fixture in `dir`, sorted by file name (recording order).
+/// Unreadable files are logged and skipped.
+pub fn load_fixtures(dir: &Path) -> Vec<(PathBuf, Fixture)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str::<Fixture>(&text).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(fixture) => Some((path, fixture)),
+                Err(e) => {
+                    log::warn!("Skipping fixture {}: {e}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Every descendant of `tree` (not `tree` itself), in UIA tree order.
+fn descendants(tree: &UiaElement) -> Vec<&UiaElement> {
+    let mut found = Vec::new();
+    let mut stack: Vec<&UiaElement> = tree.children.iter().rev().collect();
+    while let Some(element) = stack.pop() {
+        found.push(element);
+        stack.extend(element.children.iter().rev());
+    }
+    found
+}
+
+/// Every element in `tree` matching the command's `automation_id`, `name`
+/// and `control_type`, as `find_uia_elements` matches them live.
+fn find_in_tree<'a>(tree: &'a UiaElement, cmd: &Command) -> Vec<&'a UiaElement> {
+    let param = |key: &str| cmd.parameters.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let (automation_id, name, control_type) = (param("automation_id"), param("name"), param("control_type"));
+    let type_id = crate::command::control_type_id(control_type);
+    descendants(tree)
+        .into_iter()
+        .filter(|element| automation_id.is_empty() || element.automation_id == automation_id)
+        .filter(|element| name.is_empty() || element.name == name)
+        .filter(|element| match type_id {
+            _ if control_type.is_empty() => true,
+            Some(id) => crate::command::control_type_id(&element.control_type) == Some(id),
+            None => element.control_type.eq_ignore_ascii_case(control_type),
+        })
+        .collect()
+}
+
+/// The element `cmd` acts on within `tree`, or why there is none.
+fn select_in_tree<'a>(tree: &'a UiaElement, cmd: &Command) -> Result<&'a UiaElement, String> {
+    let first_match = FIRST_MATCH_ACTIONS.contains(&cmd.action.as_str())
+        || (cmd.action == "click" && !cmd.parameters.contains_key("index") && !cmd.parameters.contains_key("control_type"));
+    if first_match {
+        let param = |key: &str| cmd.parameters.get(key).and_then(|v| v.as_str()).unwrap_or("");
+        let (automation_id, name) = (param("automation_id"), param("name"));
+        return descendants(tree)
+            .into_iter()
+            .find(|element| if automation_id.is_empty() { element.name == name } else { element.automation_id == automation_id })
+            .ok_or_else(|| "element not found".to_string());
+    }
+    let index = cmd.parameters.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+    let matches = find_in_tree(tree, cmd);
+    crate::command::nth_match(matches.len(), index).map(|position| matches[position])
+}
+
+/// Differences between an element a result described and `element`, by the
+/// fields redaction leaves alone.
+fn compare_element(label: &str, described: &serde_json::Value, element: &UiaElement) -> Vec<String> {
+    let resolved = [
+        ("automation_id", serde_json::json!(element.automation_id)),
+        ("control_type", serde_json::json!(element.control_type)),
+        ("rect", serde_json::json!(element.bounding_rect)),
+    ];
+    resolved
+        .into_iter()
+        .filter_map(|(field, got)| {
+            let want = described.get(field)?;
+            (want != &got).then(|| format!("{label}.{field}: expected {want}, got {got}"))
+        })
+        .collect()
+}
+
+/// Resolve a fixture's element again in its recorded tree and list
+/// differences from the recorded result; nothing for fixtures without one.
+pub fn replay_fixture(fixture: &Fixture) -> Vec<String> {
+    let Some(tree) = &fixture.tree else {
+        return Vec::new();
+    };
+    let cmd = &fixture.command;
+    let recorded = &fixture.result;
+    let ok = recorded.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
+    let result = recorded.get("result").unwrap_or(&serde_json::Value::Null);
+
+    if cmd.action == "find_elements" {
+        if !ok {
+            return Vec::new();
+        }
+        let matches = find_in_tree(tree, cmd);
+        let mut mismatches = Vec::new();
+        let want = result.get("count").cloned().unwrap_or_default();
+        if want != serde_json::json!(matches.len()) {
+            mismatches.push(format!("result.count: expected {want}, got {}", matches.len()));
+        }
+        let described = result.get("elements").and_then(|v| v.as_array()).map(Vec::as_slice).unwrap_or_default();
+        for (index, (described, element)) in described.iter().zip(&matches).enumerate() {
+            mismatches.extend(compare_element(&format!("result.elements[{index}]"), described, element));
+        }
+        return mismatches;
+    }
+
+    let error = recorded.get("error").and_then(|v| v.as_str()).unwrap_or("");
+    let not_found = error.starts_with("element not found") || error.starts_with("index ");
+    match (select_in_tree(tree, cmd), result.get("element")) {
+        (Ok(element), Some(described)) => compare_element("result.element", described, element),
+        (Ok(_), None) if not_found => vec![format!("error: expected {error:?}, got an element")],
+        (Err(e), _) if ok || !not_found => vec![format!("element: expected one, got {e:?}")],
+        _ => Vec::new(),
+    }
+}
+
+/// Replay every fixture in `dir` that recorded a tree. Returns the fixtures
+/// whose element no longer resolves as recorded, with their differences.
+pub fn replay_dir(dir: &Path) -> Vec<(PathBuf, Vec<String>)> {
+    load_fixtures(dir)
+        .into_iter()
+        .filter_map(|(path, fixture)| {
+            let mismatches = replay_fixture(&fixture);
+            (!mismatches.is_empty()).then_some((path, mismatches))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(action: &str) -> Command {
+        Command {
+            command_id: "cmd/1".to_string(),
+            action: action.to_string(),
+            parameters: HashMap::new(),
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        }
+    }
+
+    fn element(automation_id: &str, name: &str, control_type: &str, children: Vec<UiaElement>) -> UiaElement {
+        UiaElement {
+            automation_id: automation_id.to_string(),
+            name: name.to_string(),
+            control_type: control_type.to_string(),
+            bounding_rect: Some([0, 0, 10, 10]),
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_load_and_replay() {
+        let dir = std::env::temp_dir().join(format!("desktopai_fixtures_{}", std::process::id()));
+        let dir_str = dir.to_string_lossy().into_owned();
+        let mut cmd = command("click");
+        cmd.parameters.insert("name".to_string(), serde_json::json!("Save"));
+        let mut result = CommandResult::success(&cmd.command_id, HashMap::new());
+        result.screenshot_b64 = Some("large".to_string());
+        result.screenshot_before_b64 = Some("large".to_string());
+        result.uia = Some(serde_json::json!({"focused_name": "Save"}));
+        let save = element("save", "Save", "button", Vec::new());
+        result.result.insert("element".to_string(), serde_json::json!({"automation_id": "save", "rect": [0, 0, 10, 10]}));
+        let tree = element("", "Editor", "window", vec![save]);
+        TREES.lock().unwrap().get_or_insert_with(HashMap::new).insert(cmd.command_id.clone(), tree);
+
+        let path = record_exchange(&dir_str, &cmd, &result).unwrap();
+        assert!(path.file_name().unwrap().to_string_lossy().ends_with("-click-cmd_1.json"));
+        assert!(take_tree(&cmd.command_id).is_none(), "the tree went into the fixture");
+
+        let fixtures = load_fixtures(&dir);
+        assert_eq!(fixtures.len(), 1);
+        let fixture = &fixtures[0].1;
+        for field in VOLATILE_FIELDS {
+            assert!(fixture.result.get(*field).is_none(), "{field} was recorded");
+        }
+        assert!(fixture.tree.is_some());
+        assert!(replay_fixture(fixture).is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Regression run over captured scenarios checked in under
+    /// `fixtures/commands`.
+    #[test]
+    fn test_replay_checked_in_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("commands");
+        let replayable = load_fixtures(&dir).iter().filter(|(_, fixture)| fixture.tree.is_some()).count();
+        assert!(replayable > 0, "no replayable fixtures in {}", dir.display());
+        let failures = replay_dir(&dir);
+        assert!(failures.is_empty(), "fixture regressions: {failures:#?}");
+    }
+
+    #[test]
+    fn test_secret_parameters_are_not_recorded() {
+        let mut cmd = command("create_diagnostic_bundle");
+        cmd.parameters.insert("password".to_string(), serde_json::json!("correct horse"));
+        cmd.parameters.insert("path".to_string(), serde_json::json!("C:\\bundle.zip"));
+        cmd.parameters.insert("inputs".to_string(), serde_json::json!({"Token": "abc", "list": [{"secret": 1}]}));
+        let fixture = Fixture::new(&cmd, &CommandResult::success(&cmd.command_id, HashMap::new()));
+        let recorded = serde_json::to_string(&fixture).unwrap();
+        assert!(!recorded.contains("correct horse") && !recorded.contains("abc"));
+        assert_eq!(fixture.command.parameters["password"], crate::pipeline::REDACTED);
+        assert_eq!(fixture.command.parameters["inputs"]["list"][0]["secret"], crate::pipeline::REDACTED);
+        assert_eq!(fixture.command.parameters["path"], "C:\\bundle.zip");
+    }
+
+    #[test]
+    fn test_replay_reports_differences() {
+        let tree = element(
+            "",
+            "Editor",
+            "window",
+            vec![
+                element("", "Name", "edit", Vec::new()),
+                element("", "Options", "group", vec![element("wrap", "Word wrap", "check box", Vec::new())]),
+                element("", "Name", "edit", Vec::new()),
+            ],
+        );
+        let mut cmd = command("find_elements");
+        cmd.parameters.insert("control_type".to_string(), serde_json::json!("CheckBox"));
+        assert_eq!(find_in_tree(&tree, &cmd).len(), 1, "control types match by UIA name");
+
+        cmd.parameters = HashMap::from([("name".to_string(), serde_json::json!("Name"))]);
+        let mut fixture = Fixture::new(&cmd, &CommandResult::success(&cmd.command_id, HashMap::new()));
+        fixture.tree = Some(tree);
+        fixture.result["result"] = serde_json::json!({"count": 3});
+        assert_eq!(replay_fixture(&fixture), ["result.count: expected 3, got 2"]);
+
+        fixture.command.action = "click".to_string();
+        fixture.command.parameters.insert("index".to_string(), serde_json::json!(2));
+        fixture.result["result"] = serde_json::json!({"element": {"control_type": "edit"}});
+        assert_eq!(replay_fixture(&fixture), [r#"element: expected one, got "index 2 out of range: 2 element(s) match""#]);
+    }
+}
@@ -0,0 +1,340 @@
+//! Event tagging and routing: which sinks receive which events.
+//!
+//! `ROUTING_PATH` names a JSON file of tag rules and routes:
+//!
+//! ```json
+//! {
+//!   "tags": [
+//!     {"tag": "work", "processes": ["outlook.exe", "teams.exe"]},
+//!     {"tag": "personal", "processes": ["spotify.exe"]},
+//!     {"tag": "personal", "titles": ["YouTube"]},
+//!     {"tag": "after-hours", "hours": [18, 8]}
+//!   ],
+//!   "routes": [
+//!     {"tags": ["personal", "after-hours"], "sinks": ["local"]},
+//!     {"tags": ["work"], "sinks": ["backend", "webhook"]}
+//!   ],
+//!   "default_sinks": ["local"]
+//! }
+//! ```
+//!
+//! A tag rule applies when all of its conditions hold: `processes` lists
+//! executable names, `titles` title substrings (both case-insensitive) and
+//! `hours` a `[start, end)` range of local hours that may wrap past
+//! midnight; a rule without conditions tags every event. The current
+//! profile (`EVENT_PROFILE`, or `set_event_profile` at runtime) is added as
+//! a tag as well. The first route naming any of an event's tags picks its
+//! sinks; other events go to `default_sinks` (every sink when omitted).
+//!
+//! Sinks: `backend` is the WebSocket/HTTP connection (and the privacy
+//! preview of it), `local` the event tap plus a JSON-lines store under
+//! `events` in the data directory, pruned like any other data file (see
+//! datadir.rs), and `webhook` the configured webhooks. Without a routing
+//! file every event goes to every sink and nothing is stored.
+
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+
+/// Directory of the local store, below the data directory.
+const STORE_DIR: &str = "events";
+
+/// Profile set by `set_event_profile`; `None` falls back to `EVENT_PROFILE`.
+static PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Where an event may go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sink {
+    Backend,
+    Local,
+    Webhook,
+}
+
+const ALL_SINKS: [Sink; 3] = [Sink::Backend, Sink::Local, Sink::Webhook];
+
+fn all_sinks() -> Vec<Sink> {
+    ALL_SINKS.to_vec()
+}
+
+/// Tags events that match its conditions.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TagRule {
+    pub tag: String,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(default)]
+    pub titles: Vec<String>,
+    #[serde(default)]
+    pub hours: Option<[u32; 2]>,
+}
+
+impl TagRule {
+    fn applies(&self, event: &WindowEvent, hour: u32) -> bool {
+        let process = event.process_exe.rsplit(['\\', '/']).next().unwrap_or_default();
+        let title = event.title.to_lowercase();
+        (self.processes.is_empty() || self.processes.iter().any(|p| p.eq_ignore_ascii_case(process)))
+            && (self.titles.is_empty() || self.titles.iter().any(|t| title.contains(&t.to_lowercase())))
+            && self.hours.is_none_or(|[start, end]| in_hours(hour, start, end))
+    }
+}
+
+/// Whether `hour` falls in `[start, end)`, wrapping past midnight when
+/// `end` is not after `start`.
+fn in_hours(hour: u32, start: u32, end: u32) -> bool {
+    if start < end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+/// Sends events carrying any of `tags` to `sinks`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Route {
+    pub tags: Vec<String>,
+    pub sinks: Vec<Sink>,
+}
+
+/// The contents of a routing file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Routing {
+    #[serde(default)]
+    pub tags: Vec<TagRule>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default = "all_sinks")]
+    pub default_sinks: Vec<Sink>,
+}
+
+impl Routing {
+    /// Tags for `event` at local `hour`, in rule order, with `profile` first.
+    pub fn tags_for(&self, event: &WindowEvent, hour: u32, profile: &str) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        let matched = self.tags.iter().filter(|rule| rule.applies(event, hour)).map(|rule| rule.tag.as_str());
+        for tag in std::iter::once(profile).filter(|p| !p.is_empty()).chain(matched) {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        tags
+    }
+
+    /// Sinks for an event carrying `tags`.
+    pub fn sinks_for(&self, tags: &[String]) -> &[Sink] {
+        self.routes
+            .iter()
+            .find(|route| route.tags.iter().any(|tag| tags.contains(tag)))
+            .map_or(&self.default_sinks, |route| &route.sinks)
+    }
+}
+
+/// Read and validate the routing rules in `path`.
+pub fn load_routing(path: &str) -> Result<Routing, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let routing: Routing = serde_json::from_str(&text).map_err(|e| format!("invalid routing rules in {path}: {e}"))?;
+    check_routing(&routing)?;
+    Ok(routing)
+}
+
+/// Reject rules that could never match.
+pub fn check_routing(routing: &Routing) -> Result<(), String> {
+    for rule in &routing.tags {
+        if rule.tag.trim().is_empty() {
+            return Err("tag rule has an empty tag".to_string());
+        }
+        if rule.hours.is_some_and(|[start, end]| start > 23 || end > 24) {
+            return Err(format!("tag '{}' has hours outside 0-24", rule.tag));
+        }
+    }
+    if routing.routes.iter().any(|route| route.tags.is_empty()) {
+        return Err("route lists no tags".to_string());
+    }
+    Ok(())
+}
+
+/// Set the runtime profile; `None` returns to `EVENT_PROFILE`, an empty
+/// string turns the profile tag off.
+pub fn set_profile(profile: Option<String>) {
+    *PROFILE.lock().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+/// The profile tag events currently get (empty = none).
+pub fn profile(config: &Config) -> String {
+    PROFILE.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| config.event_profile.clone())
+}
+
+/// Tags events and decides their sinks, per the configured routing file.
+pub struct Router {
+    config: Config,
+    routing: Option<Routing>,
+}
+
+impl Router {
+    /// Load the configured rules. A missing or invalid file routes every
+    /// event everywhere, with a warning.
+    pub fn new(config: &Config) -> Self {
+        let routing = if config.routing_path.is_empty() {
+            None
+        } else {
+            match load_routing(&config.routing_path) {
+                Ok(routing) => {
+                    log::info!("Routing: {} tag rules, {} routes from {}", routing.tags.len(), routing.routes.len(), config.routing_path);
+                    Some(routing)
+                }
+                Err(e) => {
+                    log::warn!("Routing disabled: {e}");
+                    None
+                }
+            }
+        };
+        Self { config: config.clone(), routing }
+    }
+
+    pub fn with_routing(config: &Config, routing: Option<Routing>) -> Self {
+        Self { config: config.clone(), routing }
+    }
+
+    /// Tag `event` and return the sinks it goes to.
+    pub fn route(&self, event: &mut WindowEvent) -> Vec<Sink> {
+        let hour = chrono::Timelike::hour(&chrono::Local::now());
+        self.route_at(event, hour)
+    }
+
+    fn route_at(&self, event: &mut WindowEvent, hour: u32) -> Vec<Sink> {
+        let profile = profile(&self.config);
+        match &self.routing {
+            Some(routing) => {
+                event.tags = routing.tags_for(event, hour, &profile);
+                routing.sinks_for(&event.tags).to_vec()
+            }
+            None => {
+                event.tags = if profile.is_empty() { Vec::new() } else { vec![profile] };
+                all_sinks()
+            }
+        }
+    }
+
+    /// Hand `event` to the local sink: the event tap, and the store when
+    /// routing rules and a data directory are configured.
+    pub fn publish_local(&self, event: &WindowEvent) {
+        crate::tap::publish(event);
+        if self.routing.is_some() && !self.config.data_dir.is_empty() {
+            if let Err(e) = store(Path::new(&self.config.data_dir), event) {
+                log::warn!("Local event store failed: {e}");
+            }
+        }
+    }
+}
+
+/// Store file for events on the day of `timestamp` (RFC 3339).
+fn store_path(data_dir: &Path, timestamp: &str) -> PathBuf {
+    let day = timestamp.get(..10).filter(|d| d.len() == 10).unwrap_or("undated");
+    data_dir.join(STORE_DIR).join(format!("{day}.jsonl"))
+}
+
+/// Append `event` to the local store below `data_dir`.
+fn store(data_dir: &Path, event: &WindowEvent) -> std::io::Result<()> {
+    let path = store_path(data_dir, &event.timestamp);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(event).map_err(std::io::Error::other)?;
+    line.push('\n');
+    std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+
+    fn event(process: &str, title: &str) -> WindowEvent {
+        let mut event = build_activity_event("foreground", 0);
+        event.process_exe = process.to_string();
+        event.title = title.to_string();
+        event
+    }
+
+    fn routing() -> Routing {
+        serde_json::from_str(
+            r#"{
+                "tags": [
+                    {"tag": "work", "processes": ["OUTLOOK.EXE"]},
+                    {"tag": "personal", "titles": ["youtube"]},
+                    {"tag": "after-hours", "hours": [18, 8]}
+                ],
+                "routes": [
+                    {"tags": ["personal", "after-hours"], "sinks": ["local"]},
+                    {"tags": ["work"], "sinks": ["backend", "webhook"]}
+                ],
+                "default_sinks": ["local"]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tags_and_routes() {
+        let routing = routing();
+        let outlook = event("C:\\Program Files\\Office\\outlook.exe", "Inbox");
+        assert_eq!(routing.tags_for(&outlook, 10, ""), vec!["work"]);
+        assert_eq!(routing.sinks_for(&["work".to_string()]), [Sink::Backend, Sink::Webhook]);
+        assert_eq!(routing.tags_for(&outlook, 22, "acme"), vec!["acme", "work", "after-hours"]);
+        // The first matching route wins, so after hours work stays local
+        assert_eq!(routing.sinks_for(&routing.tags_for(&outlook, 22, "")), [Sink::Local]);
+
+        let video = event("chrome.exe", "Cats - YouTube");
+        assert_eq!(routing.tags_for(&video, 10, ""), vec!["personal"]);
+        assert_eq!(routing.sinks_for(&[]), [Sink::Local], "untagged events use the default");
+
+        let everywhere: Routing = serde_json::from_str(r#"{"tags": [{"tag": "all"}]}"#).unwrap();
+        assert_eq!(everywhere.tags_for(&video, 3, ""), vec!["all"]);
+        assert_eq!(everywhere.sinks_for(&[]), ALL_SINKS);
+
+        assert!(in_hours(9, 9, 17) && !in_hours(17, 9, 17));
+        assert!(in_hours(23, 18, 8) && in_hours(0, 18, 8) && !in_hours(8, 18, 8));
+    }
+
+    #[test]
+    fn test_check_routing() {
+        assert_eq!(check_routing(&routing()), Ok(()));
+        let mut bad = routing();
+        bad.tags[2].hours = Some([25, 8]);
+        assert!(check_routing(&bad).unwrap_err().contains("outside"));
+        let mut bad = routing();
+        bad.routes[0].tags.clear();
+        assert!(check_routing(&bad).unwrap_err().contains("no tags"));
+        assert!(serde_json::from_str::<Routing>(r#"{"routes": [{"tags": ["x"], "sinks": ["cloud"]}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_router_profile_and_store() {
+        let dir = std::env::temp_dir().join(format!("desktopai-routing-{}", std::process::id()));
+        let mut config = Config::from_env();
+        config.event_profile = "work".to_string();
+        config.data_dir = dir.to_string_lossy().into_owned();
+
+        let mut plain = event("notepad.exe", "notes");
+        let sinks = Router::with_routing(&config, None).route_at(&mut plain, 10);
+        assert_eq!(sinks, ALL_SINKS);
+        assert_eq!(plain.tags, vec!["work"]);
+
+        let router = Router::with_routing(&config, Some(routing()));
+        let mut video = event("chrome.exe", "Cats - YouTube");
+        assert_eq!(router.route_at(&mut video, 10), [Sink::Local]);
+        assert_eq!(video.tags, vec!["work", "personal"]);
+
+        video.timestamp = "2026-03-01T10:00:00Z".to_string();
+        router.publish_local(&video);
+        router.publish_local(&video);
+        let stored = std::fs::read_to_string(dir.join("events").join("2026-03-01.jsonl")).unwrap();
+        assert_eq!(stored.lines().count(), 2);
+        assert!(stored.contains("\"tags\":[\"work\",\"personal\"]"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,22 +1,153 @@
+//! Screen capture and the screenshot ring buffer.
+//!
+//! Data lifecycle of captured pixels:
+//!
+//! - Raw pixels and the encoded JPEG are working buffers: they are
+//!   overwritten with zeros once the base64 image has been produced. The
+//!   base64 string travels with its event or command result and is dropped
+//!   once sent.
+//! - Full frames are also retained in a ring of the last `RING_BUFFER_SIZE`
+//!   captures, each for at most `SCREENSHOT_BUFFER_TTL_MS` (about a second
+//!   of slack for the sweeper). A zero TTL retains nothing.
+//! - A retained frame is excluded from Windows Error Reporting crash dumps
+//!   while held, and zeroed when evicted: by age, by a newer capture or by
+//!   `purge_data`.
+//! - Screenshots are never written to disk by the collector; the command
+//!   recorder strips them from what it saves.
+
 use std::collections::VecDeque;
 use std::sync::{Mutex, OnceLock};
-use windows::Win32::Foundation::HWND;
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::{HWND, RECT};
 use windows::Win32::Graphics::Gdi::{
     BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
-    GetDIBits, GetMonitorInfoW, MonitorFromWindow, ReleaseDC, SelectObject, BITMAPINFO,
-    BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, MONITOR_DEFAULTTONEAREST, MONITORINFO, SRCCOPY,
+    GetDIBits, GetMonitorInfoW, MonitorFromWindow, ReleaseDC, SelectObject, SetBrushOrgEx,
+    SetStretchBltMode, StretchBlt, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HALFTONE,
+    HBITMAP, HDC, HMONITOR, MONITOR_DEFAULTTONEAREST, MONITORINFO, SRCCOPY,
 };
-use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+use windows::Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS};
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::config::Config;
 
 const RING_BUFFER_SIZE: usize = 5;
+/// How often retained frames are checked against the TTL.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A retained JPEG frame: excluded from crash dumps while held, zeroed on
+/// drop.
+struct RetainedFrame {
+    jpeg: Vec<u8>,
+    captured: Instant,
+    excluded: bool,
+}
 
-pub static SCREENSHOT_BUFFER: OnceLock<Mutex<VecDeque<Vec<u8>>>> = OnceLock::new();
+impl RetainedFrame {
+    fn new(jpeg: Vec<u8>, captured: Instant) -> Self {
+        use windows::Win32::System::ErrorReporting::WerRegisterExcludedMemoryBlock;
+
+        // Fails before Windows 10 2004; the frame is still wiped on eviction
+        let excluded = !jpeg.is_empty()
+            && u32::try_from(jpeg.len())
+                .is_ok_and(|len| unsafe { WerRegisterExcludedMemoryBlock(jpeg.as_ptr().cast(), len) }.is_ok());
+        Self { jpeg, captured, excluded }
+    }
+}
+
+impl Drop for RetainedFrame {
+    fn drop(&mut self) {
+        use windows::Win32::System::ErrorReporting::WerUnregisterExcludedMemoryBlock;
+
+        self.jpeg.zeroize();
+        if self.excluded {
+            let _ = unsafe { WerUnregisterExcludedMemoryBlock(self.jpeg.as_ptr().cast()) };
+        }
+    }
+}
 
-/// Initialize the screenshot ring buffer
-pub fn init_screenshot_buffer() {
-    SCREENSHOT_BUFFER.get_or_init(|| Mutex::new(VecDeque::with_capacity(RING_BUFFER_SIZE)));
+/// The last few full-frame captures, each kept for at most `ttl`.
+pub struct ScreenshotBuffer {
+    frames: VecDeque<RetainedFrame>,
+    ttl: Duration,
+}
+
+impl ScreenshotBuffer {
+    fn new(ttl: Duration) -> Self {
+        Self { frames: VecDeque::with_capacity(RING_BUFFER_SIZE), ttl }
+    }
+
+    fn push(&mut self, mut jpeg: Vec<u8>, now: Instant) {
+        if self.ttl.is_zero() {
+            jpeg.zeroize();
+            return;
+        }
+        self.evict_expired(now);
+        if self.frames.len() >= RING_BUFFER_SIZE {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(RetainedFrame::new(jpeg, now));
+    }
+
+    /// Drop (and so wipe) frames older than the TTL; returns how many.
+    fn evict_expired(&mut self, now: Instant) -> usize {
+        let before = self.frames.len();
+        self.frames.retain(|frame| now.saturating_duration_since(frame.captured) < self.ttl);
+        before - self.frames.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Wipe every retained frame; returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        let count = self.frames.len();
+        self.frames.clear();
+        count
+    }
+}
+
+pub static SCREENSHOT_BUFFER: OnceLock<Mutex<ScreenshotBuffer>> = OnceLock::new();
+
+/// Initialize the screenshot ring buffer and start evicting frames older
+/// than `screenshot_buffer_ttl`.
+pub fn init_screenshot_buffer(config: &Config) {
+    let ttl = config.screenshot_buffer_ttl;
+    let mut created = false;
+    SCREENSHOT_BUFFER.get_or_init(|| {
+        created = true;
+        Mutex::new(ScreenshotBuffer::new(ttl))
+    });
+    if created && !ttl.is_zero() {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(SWEEP_INTERVAL);
+            if let Some(buffer) = SCREENSHOT_BUFFER.get() {
+                let evicted = buffer.lock().unwrap_or_else(|e| e.into_inner()).evict_expired(Instant::now());
+                if evicted > 0 {
+                    log::debug!("Wiped {evicted} expired screenshot(s)");
+                }
+            }
+        });
+    }
+}
+
+/// Wipe every retained screenshot now; returns how many were wiped.
+pub fn wipe_screenshot_buffer() -> usize {
+    SCREENSHOT_BUFFER.get().map_or(0, |buffer| buffer.lock().unwrap_or_else(|e| e.into_inner()).clear())
+}
+
+/// Copies of the retained screenshots, oldest first, for a diagnostic bundle.
+pub fn retained_frames() -> Vec<Zeroizing<Vec<u8>>> {
+    SCREENSHOT_BUFFER.get().map_or_else(Vec::new, |buffer| {
+        let buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        buffer.frames.iter().map(|frame| Zeroizing::new(frame.jpeg.clone())).collect()
+    })
 }
 
 /// Capture a screenshot of the monitor containing the given window (or the
@@ -28,33 +159,40 @@ pub fn capture_screenshot(config: &Config, hwnd: HWND) -> Option<String> {
         return None;
     }
 
-    // Capture the raw screenshot
-    let pixels = capture_monitor_pixels(hwnd)?;
-
-    // Downscale if needed
-    let (width, height, pixels) = downscale_if_needed(
-        pixels.0,
-        pixels.1,
-        pixels.2,
-        config.screenshot_max_width,
-        config.screenshot_max_height,
-    );
+    // Capture already resized to the configured maximum
+    let (width, height, pixels) = capture_scaled_pixels(config, hwnd)?;
+    let pixels = Zeroizing::new(pixels);
 
-    // Encode as JPEG
+    // Encode as JPEG, then to base64
     let jpeg_data = encode_jpeg(&pixels, width, height, config.screenshot_quality)?;
+    let encoded = base64_encode(&jpeg_data);
 
     // Store in ring buffer
-    store_in_buffer(jpeg_data.clone());
-
-    // Encode to base64
-    Some(base64_encode(&jpeg_data))
+    store_in_buffer(jpeg_data);
+    Some(encoded)
 }
 
 /// Capture raw 24-bit BGR pixels from the monitor containing the given window.
 /// Returns (width, height, pixel_data). Public so `handle_observe` can feed
 /// pixels to the detection module before JPEG encoding.
 pub fn capture_raw_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
-    capture_monitor_pixels(hwnd)
+    capture_monitor_pixels(hwnd, u32::MAX, u32::MAX)
+}
+
+/// Like `capture_raw_pixels`, but the frame is shrunk to fit the configured
+/// maximum before it is read back: on the GPU with the Graphics.Capture
+/// backend (see graphics_capture.rs), otherwise during the GDI blit
+/// (HALFTONE filtering). Either way a 4K monitor is never read back or
+/// downscaled on the CPU at full resolution.
+pub fn capture_scaled_pixels(config: &Config, hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
+    let (max_width, max_height) = (config.screenshot_max_width, config.screenshot_max_height);
+    if config.screenshot_backend == "graphics_capture" {
+        match crate::graphics_capture::capture_monitor(target_monitor(hwnd), max_width, max_height) {
+            Ok(frame) => return Some(frame),
+            Err(e) => log::debug!("Graphics.Capture screenshot failed, using GDI: {e}"),
+        }
+    }
+    capture_monitor_pixels(hwnd, max_width, max_height)
 }
 
 /// Encode raw BGR pixels to base64 JPEG, applying downscale and ring buffer.
@@ -71,15 +209,81 @@ pub fn encode_raw_to_base64(
         config.screenshot_max_width,
         config.screenshot_max_height,
     );
+    let px = Zeroizing::new(px);
     let jpeg_data = encode_jpeg(&px, w, h, config.screenshot_quality)?;
-    store_in_buffer(jpeg_data.clone());
-    Some(base64_encode(&jpeg_data))
+    let encoded = base64_encode(&jpeg_data);
+    store_in_buffer(jpeg_data);
+    Some(encoded)
 }
 
-/// Capture raw pixels from the monitor that contains the given window.
-/// Falls back to the foreground window when `hwnd` is null, and ultimately
-/// to the primary monitor if no foreground window is found.
-fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
+/// One display: its device name (e.g. `\\.\DISPLAY2`), screen rectangle
+/// `[x, y, width, height]`, work area (without the taskbar), effective DPI
+/// (96 at 100% scaling) and whether it is the primary monitor.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    pub rect: [i32; 4],
+    pub work_area: [i32; 4],
+    pub dpi: u32,
+    pub primary: bool,
+}
+
+/// Every display, the primary first and the rest in enumeration order.
+pub fn monitors() -> Vec<Monitor> {
+    use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+    use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR, MONITORINFOEXW};
+    use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+    use windows::Win32::UI::WindowsAndMessaging::MONITORINFOF_PRIMARY;
+
+    unsafe extern "system" fn collect(monitor: HMONITOR, _dc: HDC, _rect: *mut RECT, lparam: LPARAM) -> BOOL {
+        let monitors = &mut *(lparam.0 as *mut Vec<Monitor>);
+        let mut info = MONITORINFOEXW::default();
+        info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+        if GetMonitorInfoW(monitor, &mut info.monitorInfo).as_bool() {
+            let (r, w) = (info.monitorInfo.rcMonitor, info.monitorInfo.rcWork);
+            let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+            let (mut dpi, mut dpi_y) = (0, 0);
+            if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi, &mut dpi_y).is_err() || dpi == 0 {
+                dpi = 96;
+            }
+            monitors.push(Monitor {
+                name: String::from_utf16_lossy(&info.szDevice[..len]),
+                rect: [r.left, r.top, r.right - r.left, r.bottom - r.top],
+                work_area: [w.left, w.top, w.right - w.left, w.bottom - w.top],
+                dpi,
+                primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+            });
+        }
+        BOOL(1)
+    }
+
+    let mut monitors: Vec<Monitor> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(HDC(0), None, Some(collect), LPARAM(&mut monitors as *mut Vec<Monitor> as isize));
+    }
+    monitors.sort_by_key(|m| !m.primary);
+    monitors
+}
+
+/// Device name of the monitor showing most of window `hwnd` (see `Monitor`).
+pub fn window_monitor_name(hwnd: HWND) -> Option<String> {
+    use windows::Win32::Graphics::Gdi::MONITORINFOEXW;
+
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    unsafe {
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        if !GetMonitorInfoW(monitor, &mut info.monitorInfo).as_bool() {
+            return None;
+        }
+    }
+    let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+    Some(String::from_utf16_lossy(&info.szDevice[..len]))
+}
+
+/// The monitor that contains the given window, or the foreground window
+/// when `hwnd` is null.
+fn target_monitor(hwnd: HWND) -> HMONITOR {
     unsafe {
         // Resolve the target window: use provided hwnd, or fall back to foreground
         let target = if hwnd.0 == 0 {
@@ -87,9 +291,15 @@ fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
         } else {
             hwnd
         };
+        MonitorFromWindow(target, MONITOR_DEFAULTTONEAREST)
+    }
+}
 
-        // Get the monitor that contains the target window
-        let hmonitor = MonitorFromWindow(target, MONITOR_DEFAULTTONEAREST);
+/// Screen rectangle `[x, y, width, height]` of the monitor that contains the
+/// given window, or the foreground window when `hwnd` is null.
+pub fn monitor_rect(hwnd: HWND) -> Option<[i32; 4]> {
+    unsafe {
+        let hmonitor = target_monitor(hwnd);
 
         let mut mi = MONITORINFO {
             cbSize: std::mem::size_of::<MONITORINFO>() as u32,
@@ -102,10 +312,48 @@ fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
         }
 
         let mon = mi.rcMonitor;
-        let width = (mon.right - mon.left) as u32;
-        let height = (mon.bottom - mon.top) as u32;
-        let src_x = mon.left;
-        let src_y = mon.top;
+        Some([mon.left, mon.top, mon.right - mon.left, mon.bottom - mon.top])
+    }
+}
+
+/// Capture a screen rectangle `[x, y, width, height]`, shrunk to fit the
+/// configured maximum, as base64 JPEG. Returns (width, height, base64).
+/// Region captures skip the ring buffer, which holds full frames.
+pub fn capture_region(config: &Config, rect: [i32; 4]) -> Option<(u32, u32, String)> {
+    let (width, height, pixels) = capture_rect_pixels(rect, config.screenshot_max_width, config.screenshot_max_height)?;
+    let pixels = Zeroizing::new(pixels);
+    let jpeg_data = Zeroizing::new(encode_jpeg(&pixels, width, height, config.screenshot_quality)?);
+    Some((width, height, base64_encode(&jpeg_data)))
+}
+
+/// Raw 24-bit BGR pixels of a screen rectangle `[x, y, width, height]`,
+/// shrunk to fit `max_width` x `max_height`. Returns (width, height, pixel_data).
+pub fn capture_rect_pixels(rect: [i32; 4], max_width: u32, max_height: u32) -> Option<(u32, u32, Vec<u8>)> {
+    let [x, y, width, height] = rect;
+    capture_screen_pixels(x, y, width as u32, height as u32, max_width, max_height)
+}
+
+/// Capture raw pixels from the monitor that contains the given window,
+/// scaled down to fit the given bounds if it is larger.
+/// Falls back to the foreground window when `hwnd` is null, and ultimately
+/// to the primary monitor if no foreground window is found.
+fn capture_monitor_pixels(hwnd: HWND, max_width: u32, max_height: u32) -> Option<(u32, u32, Vec<u8>)> {
+    let [src_x, src_y, src_width, src_height] = monitor_rect(hwnd)?;
+    capture_screen_pixels(src_x, src_y, src_width as u32, src_height as u32, max_width, max_height)
+}
+
+/// Capture raw pixels from a screen rectangle, scaled down to fit the given
+/// bounds if it is larger.
+fn capture_screen_pixels(
+    src_x: i32,
+    src_y: i32,
+    src_width: u32,
+    src_height: u32,
+    max_width: u32,
+    max_height: u32,
+) -> Option<(u32, u32, Vec<u8>)> {
+    unsafe {
+        let (width, height) = scaled_size(src_width, src_height, max_width, max_height);
 
         let hdc_screen = GetDC(HWND(0));
         if hdc_screen.is_invalid() {
@@ -130,19 +378,28 @@ fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
 
         let old_bitmap = SelectObject(hdc_mem, hbitmap);
 
-        if BitBlt(
-            hdc_mem,
-            0,
-            0,
-            width as i32,
-            height as i32,
-            hdc_screen,
-            src_x,
-            src_y,
-            SRCCOPY,
-        )
-        .is_err()
-        {
+        let blitted = if (width, height) == (src_width, src_height) {
+            BitBlt(hdc_mem, 0, 0, width as i32, height as i32, hdc_screen, src_x, src_y, SRCCOPY).is_ok()
+        } else {
+            // HALFTONE averages source pixels; it needs the brush origin reset
+            SetStretchBltMode(hdc_mem, HALFTONE);
+            let _ = SetBrushOrgEx(hdc_mem, 0, 0, None);
+            StretchBlt(
+                hdc_mem,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                hdc_screen,
+                src_x,
+                src_y,
+                src_width as i32,
+                src_height as i32,
+                SRCCOPY,
+            )
+            .as_bool()
+        };
+        if !blitted {
             let _ = SelectObject(hdc_mem, old_bitmap);
             let _ = DeleteObject(hbitmap);
             let _ = DeleteDC(hdc_mem);
@@ -151,44 +408,7 @@ fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
             return None;
         }
 
-        // Get bitmap data
-        let mut bmi = BITMAPINFO {
-            bmiHeader: BITMAPINFOHEADER {
-                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                biWidth: width as i32,
-                biHeight: -(height as i32), // Negative for top-down DIB
-                biPlanes: 1,
-                biBitCount: 24, // 24-bit RGB
-                biCompression: BI_RGB.0,
-                biSizeImage: 0,
-                biXPelsPerMeter: 0,
-                biYPelsPerMeter: 0,
-                biClrUsed: 0,
-                biClrImportant: 0,
-            },
-            bmiColors: [windows::Win32::Graphics::Gdi::RGBQUAD::default(); 1],
-        };
-
-        let pixel_count = (width * height * 3) as usize;
-        let mut pixels: Vec<u8> = vec![0; pixel_count];
-
-        if GetDIBits(
-            hdc_screen,
-            hbitmap,
-            0,
-            height,
-            Some(pixels.as_mut_ptr() as *mut _),
-            &mut bmi,
-            DIB_RGB_COLORS,
-        ) == 0
-        {
-            let _ = SelectObject(hdc_mem, old_bitmap);
-            let _ = DeleteObject(hbitmap);
-            let _ = DeleteDC(hdc_mem);
-            let _ = ReleaseDC(HWND(0), hdc_screen);
-            log::error!("GetDIBits failed");
-            return None;
-        }
+        let pixels = dib_pixels(hdc_screen, hbitmap, width, height);
 
         // Cleanup
         let _ = SelectObject(hdc_mem, old_bitmap);
@@ -196,10 +416,152 @@ fn capture_monitor_pixels(hwnd: HWND) -> Option<(u32, u32, Vec<u8>)> {
         let _ = DeleteDC(hdc_mem);
         let _ = ReleaseDC(HWND(0), hdc_screen);
 
-        Some((width, height, pixels))
+        Some((width, height, pixels?))
     }
 }
 
+/// Render window `hwnd` with `PrintWindow`, which also draws covered and
+/// off-screen windows, shrunk to fit `max_width` x `max_height`, as base64
+/// JPEG. Returns (width, height, base64). Thumbnails skip the ring buffer.
+pub fn capture_window_thumbnail(hwnd: HWND, max_width: u32, max_height: u32, quality: u8) -> Option<(u32, u32, String)> {
+    let mut rect = RECT::default();
+    unsafe { GetWindowRect(hwnd, &mut rect) }.ok()?;
+    let (src_width, src_height) = (rect.right - rect.left, rect.bottom - rect.top);
+    if src_width <= 0 || src_height <= 0 {
+        return None;
+    }
+    let (width, height) = scaled_size(src_width as u32, src_height as u32, max_width, max_height);
+    let (width, height) = (width.max(1), height.max(1));
+
+    unsafe {
+        let hdc_screen = GetDC(HWND(0));
+        if hdc_screen.is_invalid() {
+            log::error!("Failed to get screen DC");
+            return None;
+        }
+        let hdc_full = CreateCompatibleDC(hdc_screen);
+        let full = CreateCompatibleBitmap(hdc_screen, src_width, src_height);
+        let hdc_thumb = CreateCompatibleDC(hdc_screen);
+        let thumb = CreateCompatibleBitmap(hdc_screen, width as i32, height as i32);
+
+        let pixels = if hdc_full.is_invalid() || full.is_invalid() || hdc_thumb.is_invalid() || thumb.is_invalid() {
+            log::error!("Failed to create thumbnail bitmaps");
+            None
+        } else {
+            let old_full = SelectObject(hdc_full, full);
+            let old_thumb = SelectObject(hdc_thumb, thumb);
+            // PW_RENDERFULLCONTENT: include DirectComposition content
+            // (browsers, UWP apps) that a plain PrintWindow leaves black
+            let printed = PrintWindow(hwnd, hdc_full, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT)).as_bool();
+            let shrunk = printed && {
+                SetStretchBltMode(hdc_thumb, HALFTONE);
+                let _ = SetBrushOrgEx(hdc_thumb, 0, 0, None);
+                StretchBlt(
+                    hdc_thumb,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    hdc_full,
+                    0,
+                    0,
+                    src_width,
+                    src_height,
+                    SRCCOPY,
+                )
+                .as_bool()
+            };
+            let _ = SelectObject(hdc_full, old_full);
+            let _ = SelectObject(hdc_thumb, old_thumb);
+            if shrunk {
+                dib_pixels(hdc_screen, thumb, width, height)
+            } else {
+                log::debug!("PrintWindow failed for window {:?}", hwnd);
+                None
+            }
+        };
+
+        let _ = DeleteObject(thumb);
+        let _ = DeleteDC(hdc_thumb);
+        let _ = DeleteObject(full);
+        let _ = DeleteDC(hdc_full);
+        let _ = ReleaseDC(HWND(0), hdc_screen);
+
+        let pixels = Zeroizing::new(pixels?);
+        let jpeg_data = Zeroizing::new(encode_jpeg(&pixels, width, height, quality)?);
+        Some((width, height, base64_encode(&jpeg_data)))
+    }
+}
+
+/// `PrintWindow` flag for DirectComposition content (Windows 8.1+); the
+/// bindings do not define it.
+const PW_RENDERFULLCONTENT: u32 = 2;
+
+/// Tightly packed 24-bit BGR pixels of `hbitmap`, `width` x `height`.
+unsafe fn dib_pixels(hdc: HDC, hbitmap: HBITMAP, width: u32, height: u32) -> Option<Vec<u8>> {
+    let mut bmi = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            biHeight: -(height as i32), // Negative for top-down DIB
+            biPlanes: 1,
+            biBitCount: 24, // 24-bit RGB
+            biCompression: BI_RGB.0,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        bmiColors: [windows::Win32::Graphics::Gdi::RGBQUAD::default(); 1],
+    };
+
+    // GetDIBits pads each row to a multiple of 4 bytes; widths of
+    // arbitrary regions are not, so read padded rows and pack them after
+    let stride = row_stride(width);
+    let mut pixels: Vec<u8> = vec![0; stride * height as usize];
+
+    if GetDIBits(hdc, hbitmap, 0, height, Some(pixels.as_mut_ptr() as *mut _), &mut bmi, DIB_RGB_COLORS) == 0 {
+        log::error!("GetDIBits failed");
+        return None;
+    }
+    Some(pack_rows(pixels, width, height))
+}
+
+/// Bytes per row of a 24-bit DIB, padded to a multiple of 4.
+fn row_stride(width: u32) -> usize {
+    (width as usize * 3).div_ceil(4) * 4
+}
+
+/// Drop the row padding from a 24-bit DIB, leaving tightly packed BGR.
+fn pack_rows(mut pixels: Vec<u8>, width: u32, height: u32) -> Vec<u8> {
+    let row = width as usize * 3;
+    let stride = row_stride(width);
+    if stride == row {
+        return pixels;
+    }
+    for y in 1..height as usize {
+        pixels.copy_within(y * stride..y * stride + row, y * row);
+    }
+    pixels.truncate(row * height as usize);
+    pixels
+}
+
+/// Size that fits `width` x `height` inside the max bounds, preserving the
+/// aspect ratio. Unchanged if it already fits; a bound of 0 means no limit
+/// on that side.
+pub(crate) fn scaled_size(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let max_width = if max_width == 0 { u32::MAX } else { max_width };
+    let max_height = if max_height == 0 { u32::MAX } else { max_height };
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+    let scale_w = width as f32 / max_width as f32;
+    let scale_h = height as f32 / max_height as f32;
+    let scale = scale_w.max(scale_h);
+    (((width as f32 / scale) as u32).max(1), ((height as f32 / scale) as u32).max(1))
+}
+
 /// Downscale image if it exceeds max dimensions using simple averaging
 fn downscale_if_needed(
     width: u32,
@@ -212,13 +574,8 @@ fn downscale_if_needed(
         return (width, height, pixels);
     }
 
-    // Calculate scale factor
-    let scale_w = width as f32 / max_width as f32;
-    let scale_h = height as f32 / max_height as f32;
-    let scale = scale_w.max(scale_h);
-
-    let new_width = (width as f32 / scale) as u32;
-    let new_height = (height as f32 / scale) as u32;
+    let (new_width, new_height) = scaled_size(width, height, max_width, max_height);
+    let scale = width as f32 / new_width as f32;
 
     let mut new_pixels = vec![0u8; (new_width * new_height * 3) as usize];
 
@@ -263,15 +620,11 @@ fn encode_jpeg(pixels: &[u8], width: u32, height: u32, quality: u8) -> Option<Ve
     Some(output)
 }
 
-/// Store JPEG data in ring buffer
-fn store_in_buffer(data: Vec<u8>) {
-    if let Some(buffer) = SCREENSHOT_BUFFER.get() {
-        if let Ok(mut buf) = buffer.lock() {
-            if buf.len() >= RING_BUFFER_SIZE {
-                buf.pop_front();
-            }
-            buf.push_back(data);
-        }
+/// Store JPEG data in ring buffer; without one, the data is wiped.
+fn store_in_buffer(mut data: Vec<u8>) {
+    match SCREENSHOT_BUFFER.get() {
+        Some(buffer) => buffer.lock().unwrap_or_else(|e| e.into_inner()).push(data, Instant::now()),
+        None => data.zeroize(),
     }
 }
 
@@ -310,6 +663,26 @@ mod tests {
         assert_eq!(new_h, 50);
     }
 
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!(scaled_size(3840, 2160, 1024, 768), (1024, 576));
+        assert_eq!(scaled_size(1280, 720, 1920, 1080), (1280, 720));
+        assert_eq!(scaled_size(3840, 2160, u32::MAX, u32::MAX), (3840, 2160));
+        assert_eq!(scaled_size(3840, 2160, 0, 0), (3840, 2160));
+        assert_eq!(scaled_size(3840, 2160, 0, 1080), (1920, 1080));
+        assert_eq!(scaled_size(4000, 10, 100, 100), (100, 1));
+    }
+
+    #[test]
+    fn test_pack_rows_drops_dib_padding() {
+        assert_eq!(row_stride(4), 12);
+        assert_eq!(row_stride(5), 16);
+        // 1x2 image: each 3-byte row is padded to 4
+        assert_eq!(pack_rows(vec![1, 2, 3, 0, 4, 5, 6, 0], 1, 2), vec![1, 2, 3, 4, 5, 6]);
+        let aligned = vec![7u8; 24];
+        assert_eq!(pack_rows(aligned.clone(), 4, 2), aligned);
+    }
+
     #[test]
     fn test_base64_encode() {
         let data = vec![1, 2, 3, 4, 5];
@@ -320,13 +693,13 @@ mod tests {
 
     #[test]
     fn test_init_screenshot_buffer() {
-        init_screenshot_buffer();
+        init_screenshot_buffer(&Config::from_env());
         assert!(SCREENSHOT_BUFFER.get().is_some());
     }
 
     #[test]
     fn test_store_in_buffer() {
-        init_screenshot_buffer();
+        init_screenshot_buffer(&Config::from_env());
 
         // Add items to buffer
         for i in 0..7 {
@@ -340,4 +713,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_frames_expire_after_ttl() {
+        let start = Instant::now();
+        let mut buffer = ScreenshotBuffer::new(Duration::from_secs(10));
+        buffer.push(vec![1; 100], start);
+        buffer.push(vec![2; 100], start + Duration::from_secs(6));
+        assert_eq!(buffer.evict_expired(start + Duration::from_secs(9)), 0);
+        assert_eq!(buffer.evict_expired(start + Duration::from_secs(10)), 1);
+        assert_eq!(buffer.frames[0].jpeg, vec![2; 100]);
+        // A new capture also evicts what has expired
+        buffer.push(vec![3; 100], start + Duration::from_secs(30));
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.clear(), 1);
+        assert!(buffer.is_empty());
+
+        let mut keep_nothing = ScreenshotBuffer::new(Duration::ZERO);
+        keep_nothing.push(vec![4; 100], start);
+        assert!(keep_nothing.is_empty());
+    }
 }
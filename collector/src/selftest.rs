@@ -0,0 +1,189 @@
+//! Collector-owned window driven by the `self_test` command.
+//!
+//! A small top-level window with a push button, a single-line edit and a
+//! scrolled multi-line edit, pumped on its own thread. `self_test` drives it
+//! through the ordinary click/type_text/scroll handlers and reads the outcome
+//! back here, so the backend can confirm that UIA and input injection work on
+//! this desktop before it attempts a user's task. Win32 controls expose their
+//! control id as the UIA automation id.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::JoinHandle;
+
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Controls::EM_GETFIRSTVISIBLELINE;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+pub const WINDOW_TITLE: &str = "DesktopAI Collector Self-Test";
+pub const BUTTON_ID: i32 = 1001;
+pub const EDIT_ID: i32 = 1002;
+pub const LOG_ID: i32 = 1003;
+
+/// Lines in the multi-line edit; enough to scroll at any window size.
+const LOG_LINES: usize = 200;
+
+static CLICKS: AtomicU32 = AtomicU32::new(0);
+static OPEN: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_COMMAND => {
+            let (id, code) = ((wparam.0 & 0xFFFF) as i32, ((wparam.0 >> 16) & 0xFFFF) as u32);
+            if id == BUTTON_ID && code == BN_CLICKED {
+                CLICKS.fetch_add(1, Ordering::SeqCst);
+            }
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            PostQuitMessage(0);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
+
+/// The open test window; closed when dropped. One exists at a time.
+pub struct TestWindow {
+    hwnd: HWND,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestWindow {
+    /// Create the window on its own thread and wait until it exists.
+    pub fn open() -> Result<Self, String> {
+        if OPEN.swap(true, Ordering::SeqCst) {
+            return Err("a self-test is already running".to_string());
+        }
+        CLICKS.store(0, Ordering::SeqCst);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn(move || unsafe {
+            let instance = GetModuleHandleW(None).unwrap_or_default();
+            let class = w!("DesktopAISelfTest");
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(wndproc),
+                hInstance: instance.into(),
+                lpszClassName: class,
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                ..Default::default()
+            };
+            // Fails harmlessly when a previous self-test registered it
+            RegisterClassW(&wc);
+
+            let title = wide(WINDOW_TITLE);
+            let hwnd = CreateWindowExW(
+                WS_EX_TOPMOST,
+                class,
+                PCWSTR(title.as_ptr()),
+                WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+                100,
+                100,
+                480,
+                360,
+                None,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 != 0 {
+                let child = |class: PCWSTR, text: &str, style: WINDOW_STYLE, id: i32, rect: [i32; 4]| {
+                    let text = wide(text);
+                    CreateWindowExW(
+                        WINDOW_EX_STYLE(0),
+                        class,
+                        PCWSTR(text.as_ptr()),
+                        WS_CHILD | WS_VISIBLE | WS_TABSTOP | style,
+                        rect[0],
+                        rect[1],
+                        rect[2],
+                        rect[3],
+                        hwnd,
+                        HMENU(id as isize),
+                        instance,
+                        None,
+                    )
+                };
+                child(w!("BUTTON"), "Self-test button", WINDOW_STYLE(BS_PUSHBUTTON as u32), BUTTON_ID, [16, 16, 200, 32]);
+                child(w!("EDIT"), "", WS_BORDER | WINDOW_STYLE(ES_AUTOHSCROLL as u32), EDIT_ID, [16, 60, 430, 26]);
+                let log: Vec<String> = (1..=LOG_LINES).map(|n| format!("Line {n}")).collect();
+                child(
+                    w!("EDIT"),
+                    &log.join("\r\n"),
+                    WS_BORDER | WS_VSCROLL | WINDOW_STYLE((ES_MULTILINE | ES_AUTOVSCROLL | ES_READONLY) as u32),
+                    LOG_ID,
+                    [16, 100, 430, 200],
+                );
+            }
+            let _ = tx.send(hwnd);
+            if hwnd.0 == 0 {
+                return;
+            }
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        });
+
+        match rx.recv() {
+            Ok(hwnd) if hwnd.0 != 0 => Ok(Self { hwnd, thread: Some(thread) }),
+            _ => {
+                let _ = thread.join();
+                OPEN.store(false, Ordering::SeqCst);
+                Err("could not create the self-test window".to_string())
+            }
+        }
+    }
+
+    pub fn hwnd(&self) -> HWND {
+        self.hwnd
+    }
+
+    pub fn control(&self, id: i32) -> HWND {
+        unsafe { GetDlgItem(self.hwnd, id) }
+    }
+
+    /// Screen center of a control.
+    pub fn control_center(&self, id: i32) -> Option<(i32, i32)> {
+        let mut rect = RECT::default();
+        unsafe { GetWindowRect(self.control(id), &mut rect) }.ok()?;
+        Some(((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2))
+    }
+
+    /// Times the button has been clicked since the window opened.
+    pub fn clicks(&self) -> u32 {
+        CLICKS.load(Ordering::SeqCst)
+    }
+
+    /// Topmost visible line of the multi-line edit (0 until it scrolls).
+    pub fn first_visible_line(&self) -> usize {
+        unsafe { SendMessageW(self.control(LOG_ID), EM_GETFIRSTVISIBLELINE, WPARAM(0), LPARAM(0)) }.0 as usize
+    }
+}
+
+impl Drop for TestWindow {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = PostMessageW(self.hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        OPEN.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Text of a control as UIA reports it through ValuePattern.
+pub fn uia_value(control: HWND) -> Option<String> {
+    use windows::Win32::UI::Accessibility::{IUIAutomationValuePattern, UIA_ValuePatternId};
+
+    let uia = crate::uia::get_uia()?;
+    let element = unsafe { uia.ElementFromHandle(control) }.ok()?;
+    let pattern: IUIAutomationValuePattern = unsafe { element.GetCurrentPatternAs(UIA_ValuePatternId) }.ok()?;
+    unsafe { pattern.CurrentValue() }.ok().map(crate::event::bstr_to_string)
+}
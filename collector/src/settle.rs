@@ -0,0 +1,178 @@
+//! Deciding when the foreground window has stopped changing.
+//!
+//! After a click that navigates, a page or dialog keeps loading, laying
+//! out and repainting for a while; observing too early reads a half-built
+//! UI. `wait_for_ui_idle` polls two signals and returns once neither has
+//! moved for the requested quiet period:
+//!
+//! - UI events from the foreground window: WinEvents for objects being
+//!   created, destroyed, shown, hidden, reordered, moved, renamed or
+//!   changing value or state, which UIA raises alongside its
+//!   structure-changed and property events. The caret and cursor are
+//!   ignored, so a blinking caret does not count as activity.
+//! - A coarse frame of the window: a tiny downscaled capture, compared
+//!   with a per-channel tolerance so a caret or anti-aliasing flicker is
+//!   not a repaint, while a spinner, progress bar or new content is.
+//!
+//! A change of foreground window also restarts the quiet period.
+
+use std::time::{Duration, Instant};
+
+/// Size of the frame compared between polls.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const FRAME_SIZE: (u32, u32) = (64, 48);
+/// Per-channel difference tolerated between two frames.
+const FRAME_TOLERANCE: u8 = 24;
+
+/// Whether two coarse frames show the same picture.
+pub fn frames_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.abs_diff(*y) <= FRAME_TOLERANCE)
+}
+
+/// Tracks how long the foreground window has been still.
+#[derive(Debug)]
+pub struct Settle {
+    quiet: Duration,
+    since: Instant,
+    window: Option<isize>,
+    events: u64,
+    frame: Option<Vec<u8>>,
+}
+
+impl Settle {
+    /// Start tracking at `now`; the first sample is the baseline.
+    pub fn new(quiet: Duration, now: Instant) -> Self {
+        Self { quiet, since: now, window: None, events: 0, frame: None }
+    }
+
+    /// Record one poll: the foreground window, the UI events seen so far and
+    /// its frame, if captured. True once nothing has changed for the quiet
+    /// period.
+    pub fn update(&mut self, now: Instant, window: isize, events: u64, frame: Option<Vec<u8>>) -> bool {
+        let repainted = match (&self.frame, &frame) {
+            (Some(before), Some(after)) => !frames_match(before, after),
+            _ => false,
+        };
+        let baseline = self.window.is_none();
+        if !baseline && (self.window != Some(window) || events != self.events || repainted) {
+            self.since = now;
+        }
+        self.window = Some(window);
+        self.events = events;
+        if frame.is_some() {
+            self.frame = frame;
+        }
+        now.saturating_duration_since(self.since) >= self.quiet
+    }
+}
+
+/// Counts UI events from the foreground window while alive; unhooks when
+/// dropped. The hook is called on the installing thread, from `pump`.
+#[cfg(windows)]
+pub struct UiEvents {
+    hook: ::windows::Win32::UI::Accessibility::HWINEVENTHOOK,
+}
+
+#[cfg(windows)]
+static UI_EVENT_COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+#[cfg(windows)]
+impl UiEvents {
+    pub fn start() -> Option<Self> {
+        use ::windows::Win32::UI::Accessibility::SetWinEventHook;
+        use ::windows::Win32::UI::WindowsAndMessaging::{
+            EVENT_OBJECT_CREATE, EVENT_OBJECT_VALUECHANGE, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+        };
+
+        let hook = unsafe {
+            SetWinEventHook(
+                EVENT_OBJECT_CREATE,
+                EVENT_OBJECT_VALUECHANGE,
+                None,
+                Some(ui_event_hook),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            )
+        };
+        (hook.0 != 0).then_some(Self { hook })
+    }
+
+    /// Deliver queued events to the hook and return the running count.
+    pub fn pump(&self) -> u64 {
+        use ::windows::Win32::Foundation::HWND;
+        use ::windows::Win32::UI::WindowsAndMessaging::{DispatchMessageW, PeekMessageW, MSG, PM_REMOVE};
+
+        let mut msg = MSG::default();
+        unsafe {
+            while PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE).as_bool() {
+                DispatchMessageW(&msg);
+            }
+        }
+        UI_EVENT_COUNT.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for UiEvents {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ::windows::Win32::UI::Accessibility::UnhookWinEvent(self.hook);
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn ui_event_hook(
+    _hook: ::windows::Win32::UI::Accessibility::HWINEVENTHOOK,
+    _event: u32,
+    hwnd: ::windows::Win32::Foundation::HWND,
+    id_object: i32,
+    _id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    use ::windows::Win32::UI::WindowsAndMessaging::{GetAncestor, GetForegroundWindow, GA_ROOT, OBJID_CARET, OBJID_CURSOR};
+
+    if id_object == OBJID_CARET.0 || id_object == OBJID_CURSOR.0 || hwnd.0 == 0 {
+        return;
+    }
+    if GetAncestor(hwnd, GA_ROOT) == GetForegroundWindow() {
+        UI_EVENT_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_match_within_tolerance() {
+        let frame = vec![200u8; 12];
+        let mut caret = frame.clone();
+        caret[4] = 190;
+        assert!(frames_match(&frame, &caret));
+        let mut repainted = frame.clone();
+        repainted[4] = 40;
+        assert!(!frames_match(&frame, &repainted));
+        assert!(!frames_match(&frame, &frame[..9]), "window resized");
+    }
+
+    #[test]
+    fn test_settles_after_quiet_period() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut settle = Settle::new(Duration::from_millis(300), start);
+        assert!(!settle.update(at(0), 1, 10, Some(vec![0; 4])), "baseline");
+        assert!(!settle.update(at(200), 1, 10, Some(vec![0; 4])));
+        assert!(settle.update(at(300), 1, 10, Some(vec![0; 4])));
+
+        // UI events, a repaint and a new foreground window each restart it
+        assert!(!settle.update(at(400), 1, 12, Some(vec![0; 4])));
+        assert!(!settle.update(at(600), 1, 12, Some(vec![0; 4])));
+        assert!(!settle.update(at(700), 1, 12, Some(vec![255; 4])));
+        assert!(!settle.update(at(900), 2, 12, Some(vec![255; 4])));
+        assert!(!settle.update(at(1100), 2, 12, None), "a failed capture is no change");
+        assert!(settle.update(at(1200), 2, 12, Some(vec![250; 4])));
+    }
+}
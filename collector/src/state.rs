@@ -0,0 +1,299 @@
+//! Export and import of collector settings as one versioned archive.
+//!
+//! `export_state` bundles the portable settings — general config, redaction
+//! rules, app policies — plus the configured webhooks, so a machine can be
+//! migrated or a team can share a baseline. Only settings set explicitly (in
+//! the environment or a previous import) are exported; machine-specific
+//! paths, identity and secrets such as `EVENT_TAP_TOKEN` never are.
+//!
+//! `import_state` validates an archive and saves its settings to the
+//! settings file (`COLLECTOR_SETTINGS_PATH`, default
+//! `collector-settings.json` in the data directory), which `Config`
+//! reads after the environment on the next start. Environment variables
+//! still win over imported settings.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+
+pub const ARCHIVE_FORMAT: &str = "desktopai-collector-state";
+pub const ARCHIVE_VERSION: u32 = 1;
+/// Imported webhooks are saved beside the settings file under this name.
+const WEBHOOKS_FILE: &str = "collector-webhooks.json";
+
+const CONFIG_KEYS: &[&str] = &[
+    "BACKEND_WS_URL",
+    "BACKEND_HTTP_URL",
+    "WS_RETRY_SECONDS",
+    "WS_RECONNECT_MAX_MS",
+    "WS_CHUNK_BYTES",
+    "IDLE_ENABLED",
+    "IDLE_THRESHOLD_MS",
+    "IDLE_POLL_MS",
+    "IDLE_POLL_MAX_MS",
+    "UIA_ENABLED",
+    "UIA_THROTTLE_MS",
+    "UIA_TEXT_MAX_CHARS",
+    "UIA_MAX_DEPTH",
+    "UIA_TIMEOUT_MS",
+    "UIA_CACHE_TTL_MS",
+    "ENABLE_SCREENSHOT",
+    "SCREENSHOT_MAX_WIDTH",
+    "SCREENSHOT_MAX_HEIGHT",
+    "SCREENSHOT_QUALITY",
+    "SCREENSHOT_TIMEOUT_MS",
+    "SCREENSHOT_FORMAT",
+    "SCREENSHOT_BACKEND",
+    "DETECTION_ENABLED",
+    "DETECTION_CONFIDENCE",
+    "DETECTION_INPUT_SIZE",
+    "TIME_SYNC_INTERVAL_S",
+    "ENRICH_STAGES",
+    "GEOMETRY_ENABLED",
+    "DEEP_CAPTURE_ON_IDLE",
+    "DEEP_CAPTURE_INTERVAL_S",
+    "DEEP_UIA_MAX_DEPTH",
+    "DEEP_UIA_TEXT_MAX_CHARS",
+    "WINDOW_INVENTORY_INTERVAL_MS",
+    "OBSERVE_MAX_PER_SEC",
+    "OBSERVE_CPU_BUDGET_PCT",
+    "POWER_PROFILES_ENABLED",
+    "BATTERY_OBSERVE_MAX_PER_SEC",
+    "BATTERY_SCREENSHOT_MAX_WIDTH",
+    "BATTERY_SCREENSHOT_MAX_HEIGHT",
+    "TEXT_NORMALIZE_ENABLED",
+    "LANGUAGE_DETECT_ENABLED",
+    "ICON_ENABLED",
+    "CAPTURE_TIMINGS",
+    "TELEMETRY_ENABLED",
+    "TELEMETRY_URL",
+    "TELEMETRY_INTERVAL_S",
+    "EVENT_TAP_PORT",
+    "DATA_MAX_MB",
+    "DATA_RETENTION_DAYS",
+];
+const REDACTION_KEYS: &[&str] = &["REDACTION_ENABLED", "REDACT_PATTERN"];
+const POLICY_KEYS: &[&str] = &[
+    "SAFE_MODE",
+    "COMMAND_BRIDGE_ENABLED",
+    "SELF_EXCLUDE_PROCESSES",
+    "ALLOW_SELF_TARGETING",
+    "USER_INTERRUPT_ENABLED",
+];
+
+static SAVED: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Portable collector state. Sections map setting (environment variable)
+/// names to values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateArchive {
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default)]
+    pub collector_version: String,
+    #[serde(default)]
+    pub config: BTreeMap<String, String>,
+    #[serde(default)]
+    pub redaction: BTreeMap<String, String>,
+    #[serde(default)]
+    pub policies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<Vec<serde_json::Value>>,
+}
+
+/// What an import saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportSummary {
+    pub settings: BTreeMap<String, String>,
+    /// Archive entries that are not importable settings, skipped.
+    pub ignored: Vec<String>,
+}
+
+fn sections() -> [(&'static str, &'static [&'static str]); 3] {
+    [("config", CONFIG_KEYS), ("redaction", REDACTION_KEYS), ("policies", POLICY_KEYS)]
+}
+
+/// Archive of the settings `lookup` reports as set.
+pub fn build_archive(lookup: impl Fn(&str) -> Option<String>, webhooks: Option<Vec<serde_json::Value>>) -> StateArchive {
+    let section = |keys: &[&str]| -> BTreeMap<String, String> {
+        keys.iter().filter_map(|key| lookup(key).map(|value| (key.to_string(), value))).collect()
+    };
+    StateArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        collector_version: env!("CARGO_PKG_VERSION").to_string(),
+        config: section(CONFIG_KEYS),
+        redaction: section(REDACTION_KEYS),
+        policies: section(POLICY_KEYS),
+        webhooks,
+    }
+}
+
+/// Archive of this collector's explicitly set settings and webhooks.
+pub fn export(config: &Config) -> Result<StateArchive, String> {
+    let webhooks = if config.webhooks_path.is_empty() {
+        None
+    } else {
+        let text = std::fs::read_to_string(&config.webhooks_path)
+            .map_err(|e| format!("cannot read {}: {e}", config.webhooks_path))?;
+        Some(serde_json::from_str(&text).map_err(|e| format!("invalid webhooks in {}: {e}", config.webhooks_path))?)
+    };
+    Ok(build_archive(|key| crate::config::setting(key).ok(), webhooks))
+}
+
+/// Check an archive and collect the settings it would save.
+pub fn validate(archive: &StateArchive) -> Result<ImportSummary, String> {
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(format!("not a collector state archive (format '{}')", archive.format));
+    }
+    if archive.version == 0 || archive.version > ARCHIVE_VERSION {
+        return Err(format!("unsupported archive version {} (this collector reads up to {ARCHIVE_VERSION})", archive.version));
+    }
+    let mut settings = BTreeMap::new();
+    let mut ignored = Vec::new();
+    for (name, keys) in sections() {
+        let entries = match name {
+            "config" => &archive.config,
+            "redaction" => &archive.redaction,
+            _ => &archive.policies,
+        };
+        for (key, value) in entries {
+            if keys.contains(&key.as_str()) {
+                settings.insert(key.clone(), value.clone());
+            } else {
+                ignored.push(format!("{name}.{key}"));
+            }
+        }
+    }
+    if let Some(pattern) = settings.get("REDACT_PATTERN") {
+        regex::Regex::new(pattern).map_err(|e| format!("invalid REDACT_PATTERN: {e}"))?;
+    }
+    if let Some(webhooks) = &archive.webhooks {
+        let hooks: Vec<crate::webhook::Webhook> = serde_json::from_value(serde_json::Value::Array(webhooks.clone()))
+            .map_err(|e| format!("invalid webhooks: {e}"))?;
+        crate::webhook::check_webhooks(&hooks)?;
+    }
+    Ok(ImportSummary { settings, ignored })
+}
+
+/// Validate an archive and save it as the settings file at `path`,
+/// replacing earlier imports. Webhooks are written beside it.
+pub fn save(archive: &StateArchive, path: &Path) -> Result<ImportSummary, String> {
+    let mut summary = validate(archive)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    }
+    if let Some(webhooks) = &archive.webhooks {
+        let webhooks_path = path.with_file_name(WEBHOOKS_FILE);
+        let text = serde_json::to_string_pretty(webhooks).unwrap_or_default();
+        std::fs::write(&webhooks_path, text).map_err(|e| format!("cannot write {}: {e}", webhooks_path.display()))?;
+        summary.settings.insert("WEBHOOKS_PATH".to_string(), webhooks_path.to_string_lossy().into_owned());
+    }
+    let text = serde_json::to_string_pretty(&summary.settings).unwrap_or_default();
+    std::fs::write(path, text).map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+    Ok(summary)
+}
+
+/// Where imported settings are kept; `None` when no location is known.
+pub fn settings_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("COLLECTOR_SETTINGS_PATH") {
+        return Some(PathBuf::from(path)).filter(|p| !p.as_os_str().is_empty());
+    }
+    crate::datadir::root().map(|dir| PathBuf::from(dir).join("collector-settings.json"))
+}
+
+/// Settings saved at `path`; empty if there are none or the file is invalid.
+fn load_saved(path: &Path) -> BTreeMap<String, String> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid settings file {}: {e}", path.display());
+        BTreeMap::new()
+    })
+}
+
+/// A setting saved by an earlier import, read once per process.
+pub(crate) fn saved_setting(name: &str) -> Option<String> {
+    SAVED.get_or_init(|| settings_path().map(|path| load_saved(&path)).unwrap_or_default()).get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> StateArchive {
+        build_archive(
+            |key| match key {
+                "IDLE_THRESHOLD_MS" => Some("120000".to_string()),
+                "REDACT_PATTERN" => Some("(?i)password".to_string()),
+                "SAFE_MODE" => Some("true".to_string()),
+                _ => None,
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_archive_sections_hold_only_portable_settings() {
+        let archive = build_archive(|key| Some(format!("{key}-value")), None);
+        assert_eq!(archive.version, ARCHIVE_VERSION);
+        assert_eq!(archive.redaction.len(), REDACTION_KEYS.len());
+        assert!(archive.policies.contains_key("SELF_EXCLUDE_PROCESSES"));
+        let json = serde_json::to_string(&archive).unwrap();
+        for private in ["EVENT_TAP_TOKEN", "COLLECTOR_ID", "COMMAND_RECORD_DIR", "WEBHOOKS_PATH"] {
+            assert!(!json.contains(private), "archive exports {private}");
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_foreign_or_newer_archives() {
+        let summary = validate(&archive()).unwrap();
+        assert_eq!(summary.settings["IDLE_THRESHOLD_MS"], "120000");
+        assert_eq!(summary.settings["SAFE_MODE"], "true");
+
+        let mut smuggled = archive();
+        smuggled.config.insert("EVENT_TAP_TOKEN".to_string(), "x".to_string());
+        smuggled.policies.insert("REDACT_PATTERN".to_string(), ".*".to_string());
+        let summary = validate(&smuggled).unwrap();
+        assert_eq!(summary.ignored, vec!["config.EVENT_TAP_TOKEN", "policies.REDACT_PATTERN"]);
+        assert!(!summary.settings.contains_key("EVENT_TAP_TOKEN"));
+
+        let mut newer = archive();
+        newer.version = ARCHIVE_VERSION + 1;
+        assert!(validate(&newer).unwrap_err().contains("unsupported archive version"));
+        let mut foreign = archive();
+        foreign.format = "something-else".to_string();
+        assert!(validate(&foreign).is_err());
+        let mut bad_regex = archive();
+        bad_regex.redaction.insert("REDACT_PATTERN".to_string(), "(".to_string());
+        assert!(validate(&bad_regex).unwrap_err().contains("REDACT_PATTERN"));
+    }
+
+    #[test]
+    fn test_save_round_trips_settings_and_webhooks() {
+        let dir = std::env::temp_dir().join(format!("desktopai-state-{}", std::process::id()));
+        let path = dir.join("collector-settings.json");
+        let mut archive = archive();
+        archive.webhooks = Some(vec![serde_json::json!({"url": "https://example.com/hook", "events": ["idle"]})]);
+
+        let summary = save(&archive, &path).unwrap();
+        let saved = load_saved(&path);
+        assert_eq!(saved, summary.settings);
+        assert_eq!(saved["REDACT_PATTERN"], "(?i)password");
+        let hooks = crate::webhook::load_webhooks(&saved["WEBHOOKS_PATH"]).unwrap();
+        assert!(hooks[0].fires_on("idle"));
+
+        archive.webhooks = Some(vec![serde_json::json!({"url": "ftp://example.com", "events": ["idle"]})]);
+        assert!(save(&archive, &path).is_err());
+        assert_eq!(load_saved(&dir.join("missing.json")), BTreeMap::new());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+//! Backend-driven observation subscriptions.
+//!
+//! Instead of taking the full event stream, the backend can say what it
+//! currently cares about:
+//!
+//! ```text
+//! {"type": "subscribe", "subscription_id": "invoice",
+//!  "filter": "type == window_opened and title ~ invoice"}
+//! {"type": "subscribe", "subscription_id": "excel-focus",
+//!  "filter": "type == foreground and process ~ excel", "duration_s": 300}
+//! {"type": "unsubscribe", "subscription_id": "invoice"}
+//! ```
+//!
+//! Filters use the event tap's expression language (see tap.rs) and are
+//! evaluated here, on unredacted events. While any subscription is active,
+//! only events matching one of them go to the backend, each carrying the
+//! ids of the subscriptions it matched; other sinks are unaffected. A
+//! `window_inventory` event is narrowed to the window changes that match,
+//! with each change seen as type `window_opened`, `window_closed` or
+//! `window_renamed` (or `window_inventory`) and its own title and process.
+//! Subscriptions end after `duration_s`, on `unsubscribe`, or when the
+//! connection drops; with none left the full stream resumes. Every
+//! subscribe and unsubscribe is answered with a `subscription` message.
+
+use std::time::{Duration, Instant};
+
+use crate::event::WindowEvent;
+use crate::inventory::WindowChange;
+use crate::tap::{Filter, TapEvent};
+
+/// Subscriptions held at once; more are refused.
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+struct Subscription {
+    id: String,
+    filter: Filter,
+    expires: Option<Instant>,
+}
+
+/// The backend's active subscriptions.
+#[derive(Default)]
+pub struct Subscriptions {
+    active: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    /// Apply a `subscribe` or `unsubscribe` message; returns the reply.
+    pub fn handle(&mut self, message: &serde_json::Value, now: Instant) -> String {
+        let id = message.get("subscription_id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let reply = |status: &str, error: Option<String>| {
+            let mut reply = serde_json::json!({ "type": "subscription", "subscription_id": id, "status": status });
+            if let Some(error) = error {
+                reply["error"] = error.into();
+            }
+            reply.to_string()
+        };
+        if id.is_empty() {
+            return reply("error", Some("subscription_id is required".to_string()));
+        }
+        if message.get("type").and_then(|v| v.as_str()) == Some("unsubscribe") {
+            let before = self.active.len();
+            self.active.retain(|s| s.id != id);
+            let status = if self.active.len() < before { "ended" } else { "unknown" };
+            return reply(status, None);
+        }
+
+        let expression = message.get("filter").and_then(|v| v.as_str()).unwrap_or("");
+        let filter = match Filter::parse(expression) {
+            Ok(filter) => filter,
+            Err(e) => return reply("error", Some(e)),
+        };
+        let expires = match message.get("duration_s") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(value) => match value.as_f64() {
+                Some(seconds) if seconds > 0.0 => Some(now + Duration::from_secs_f64(seconds)),
+                _ => return reply("error", Some("duration_s must be a positive number".to_string())),
+            },
+        };
+        self.active.retain(|s| s.id != id);
+        if self.active.len() >= MAX_SUBSCRIPTIONS {
+            return reply("error", Some(format!("at most {MAX_SUBSCRIPTIONS} subscriptions")));
+        }
+        log::info!("Backend subscribed to '{expression}' as {id}");
+        self.active.push(Subscription { id: id.clone(), filter, expires });
+        reply("active", None)
+    }
+
+    /// Drop every subscription, e.g. when the backend reconnects.
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    /// Whether `event` should go to the backend, narrowing and stamping it
+    /// for the subscriptions it matches. Everything goes while none are
+    /// active.
+    pub fn select(&mut self, event: &mut WindowEvent, now: Instant) -> bool {
+        self.active.retain(|s| s.expires.is_none_or(|at| now < at));
+        if self.active.is_empty() {
+            return true;
+        }
+        let view = TapEvent::from_event(event, None);
+        let mut matched: Vec<String> = Vec::new();
+        if event.window_changes.is_empty() {
+            matched.extend(self.active.iter().filter(|s| s.filter.matches(&view)).map(|s| s.id.clone()));
+        } else {
+            event.window_changes.retain(|change| {
+                let hits: Vec<&Subscription> = self.active.iter().filter(|s| matches_change(s, &view, change)).collect();
+                for hit in &hits {
+                    if !matched.contains(&hit.id) {
+                        matched.push(hit.id.clone());
+                    }
+                }
+                !hits.is_empty()
+            });
+        }
+        event.subscriptions = matched;
+        !event.subscriptions.is_empty()
+    }
+}
+
+/// Whether one window change of an inventory event matches `subscription`.
+fn matches_change(subscription: &Subscription, event: &TapEvent, change: &WindowChange) -> bool {
+    let mut view = event.clone();
+    view.title.clone_from(&change.title);
+    view.process = change.process_exe.rsplit(['\\', '/']).next().unwrap_or_default().to_string();
+    if subscription.filter.matches(&view) {
+        return true;
+    }
+    view.event_type = format!("window_{}", change.change);
+    subscription.filter.matches(&view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+    use crate::inventory::{build_inventory_event, WindowChange};
+
+    fn subscribe(subscriptions: &mut Subscriptions, message: serde_json::Value) -> serde_json::Value {
+        serde_json::from_str(&subscriptions.handle(&message, Instant::now())).unwrap()
+    }
+
+    fn foreground(process: &str, title: &str) -> WindowEvent {
+        let mut event = build_activity_event("foreground", 0);
+        event.process_exe = process.to_string();
+        event.title = title.to_string();
+        event
+    }
+
+    fn change(change: &str, title: &str) -> WindowChange {
+        WindowChange {
+            change: change.to_string(),
+            hwnd: "0x10".to_string(),
+            title: title.to_string(),
+            previous_title: None,
+            process_exe: "C:\\Apps\\acct.exe".to_string(),
+            pid: 42,
+        }
+    }
+
+    #[test]
+    fn test_only_matching_events_go_out_while_subscribed() {
+        let mut subscriptions = Subscriptions::default();
+        let now = Instant::now();
+        assert!(subscriptions.select(&mut foreground("notepad.exe", "x"), now), "no subscriptions, full stream");
+
+        let reply = subscribe(
+            &mut subscriptions,
+            serde_json::json!({"type": "subscribe", "subscription_id": "excel", "filter": "type == foreground and process ~ excel", "duration_s": 300}),
+        );
+        assert_eq!(reply["status"], "active");
+        subscribe(&mut subscriptions, serde_json::json!({"type": "subscribe", "subscription_id": "any-excel", "filter": "title ~ xlsx"}));
+
+        let mut excel = foreground("C:\\Office\\EXCEL.EXE", "Budget.xlsx - Excel");
+        assert!(subscriptions.select(&mut excel, now));
+        assert_eq!(excel.subscriptions, ["excel", "any-excel"]);
+        assert!(!subscriptions.select(&mut foreground("notepad.exe", "notes.txt"), now));
+        assert!(!subscriptions.select(&mut build_activity_event("idle", 5000), now));
+
+        // The timed subscription lapses; the open-ended one stays
+        let mut excel = foreground("excel.exe", "Sheet1 - Excel");
+        assert!(!subscriptions.select(&mut excel, now + Duration::from_secs(301)));
+        let reply = subscribe(&mut subscriptions, serde_json::json!({"type": "unsubscribe", "subscription_id": "any-excel"}));
+        assert_eq!(reply["status"], "ended");
+        assert!(subscriptions.select(&mut build_activity_event("idle", 5000), now), "full stream resumes");
+    }
+
+    #[test]
+    fn test_inventory_narrowed_to_matching_changes() {
+        let mut subscriptions = Subscriptions::default();
+        subscribe(
+            &mut subscriptions,
+            serde_json::json!({"type": "subscribe", "subscription_id": "invoice", "filter": "type == window_opened and title ~ invoice"}),
+        );
+        let mut event = build_inventory_event(
+            vec![change("opened", "Invoice 1042"), change("closed", "Invoice 1041"), change("opened", "Calculator")],
+            12,
+        );
+        assert!(subscriptions.select(&mut event, Instant::now()));
+        assert_eq!(event.window_changes, [change("opened", "Invoice 1042")]);
+        assert_eq!(event.subscriptions, ["invoice"]);
+
+        let mut event = build_inventory_event(vec![change("opened", "Calculator")], 12);
+        assert!(!subscriptions.select(&mut event, Instant::now()));
+    }
+
+    #[test]
+    fn test_bad_subscriptions_are_refused() {
+        let mut subscriptions = Subscriptions::default();
+        let reply = subscribe(&mut subscriptions, serde_json::json!({"type": "subscribe", "subscription_id": "x", "filter": "window == x"}));
+        assert_eq!(reply["status"], "error");
+        assert!(reply["error"].as_str().unwrap().contains("unknown filter field"));
+        let reply = subscribe(&mut subscriptions, serde_json::json!({"type": "subscribe", "filter": "type == idle"}));
+        assert_eq!(reply["status"], "error");
+        let reply =
+            subscribe(&mut subscriptions, serde_json::json!({"type": "subscribe", "subscription_id": "x", "filter": "", "duration_s": 0}));
+        assert_eq!(reply["status"], "error");
+        let reply = subscribe(&mut subscriptions, serde_json::json!({"type": "unsubscribe", "subscription_id": "x"}));
+        assert_eq!(reply["status"], "unknown");
+        assert!(subscriptions.select(&mut build_activity_event("idle", 0), Instant::now()), "nothing was subscribed");
+    }
+}
@@ -0,0 +1,412 @@
+//! Event tap: a local WebSocket feed of the event stream for other apps.
+//!
+//! With `EVENT_TAP_PORT` and `EVENT_TAP_TOKEN` set, the collector listens on
+//! 127.0.0.1 and streams a redacted view of every event to subscribers that
+//! present the token (`Authorization: Bearer <token>` or `?token=`). Each
+//! subscriber may pass `?filter=<expression>`, evaluated here so only
+//! matching events leave the process, e.g.
+//!
+//! ```text
+//! type == foreground and process ~ chrome or type == idle
+//! ```
+//!
+//! Conditions compare `type`, `process` (executable file name), `title`,
+//! `source` or `tag` (any of the event's routing tags, see routing.rs) with
+//! `==`, `!=` or `~` (contains), case-insensitively; `and` binds tighter
+//! than `or`. Tapped events carry no UIA tree, screenshot,
+//! icon, detections or collector identity, and titles pass through the
+//! `REDACT_PATTERN` redaction when it is enabled.
+
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use serde::Serialize;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::Message;
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+use crate::pipeline::RedactionStage;
+
+/// Events queued per subscriber before new ones are dropped for it.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+static TAP: OnceLock<Arc<Tap>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Type,
+    Process,
+    Title,
+    Source,
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: Field,
+    op: Op,
+    /// Lowercased for case-insensitive comparison.
+    value: String,
+}
+
+/// A parsed filter: any of the groups matches when all of its conditions do.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    any_of: Vec<Vec<Condition>>,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some(next) if next == c => break,
+                    Some(next) => quoted.push(next),
+                    None => return Err(format!("unterminated quote in filter: {c}{quoted}")),
+                }
+            }
+            tokens.push(quoted);
+        } else if matches!(c, '=' | '!' | '~') {
+            let mut op = String::new();
+            while let Some(&next) = chars.peek().filter(|next| matches!(next, '=' | '!' | '~')) {
+                op.push(next);
+                chars.next();
+            }
+            tokens.push(op);
+        } else {
+            let mut word = String::new();
+            while let Some(&next) = chars.peek().filter(|next| !next.is_whitespace() && !matches!(next, '=' | '!' | '~')) {
+                word.push(next);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+    Ok(tokens)
+}
+
+impl Filter {
+    /// Parse a filter expression; an empty expression matches everything.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let tokens = tokenize(expression)?;
+        let mut any_of = Vec::new();
+        let mut group = Vec::new();
+        let mut rest = tokens.iter().map(String::as_str);
+        while let Some(field) = rest.next() {
+            let field = match field.to_lowercase().as_str() {
+                "type" => Field::Type,
+                "process" => Field::Process,
+                "title" => Field::Title,
+                "source" => Field::Source,
+                "tag" => Field::Tag,
+                other => return Err(format!("unknown filter field '{other}'")),
+            };
+            let op = match rest.next() {
+                Some("==" | "=") => Op::Eq,
+                Some("!=") => Op::Ne,
+                Some("~") => Op::Contains,
+                other => return Err(format!("expected ==, != or ~ after field, got {other:?}")),
+            };
+            let value = rest.next().ok_or("filter condition is missing a value")?;
+            group.push(Condition { field, op, value: value.to_lowercase() });
+            match rest.next().map(str::to_lowercase).as_deref() {
+                None => break,
+                Some("and") => {}
+                Some("or") => any_of.push(std::mem::take(&mut group)),
+                Some(other) => return Err(format!("expected 'and' or 'or', got '{other}'")),
+            }
+        }
+        if !group.is_empty() {
+            any_of.push(group);
+        } else if !any_of.is_empty() {
+            return Err("filter ends with 'or'".to_string());
+        }
+        Ok(Self { any_of })
+    }
+
+    pub fn matches(&self, event: &TapEvent) -> bool {
+        self.any_of.is_empty()
+            || self.any_of.iter().any(|group| {
+                group.iter().all(|condition| {
+                    let actual = match condition.field {
+                        Field::Type => std::slice::from_ref(&event.event_type),
+                        Field::Process => std::slice::from_ref(&event.process),
+                        Field::Title => std::slice::from_ref(&event.title),
+                        Field::Source => std::slice::from_ref(&event.source),
+                        Field::Tag => event.tags.as_slice(),
+                    };
+                    let mut values = actual.iter().map(|value| value.to_lowercase());
+                    match condition.op {
+                        Op::Eq => values.any(|value| value == condition.value),
+                        Op::Ne => values.all(|value| value != condition.value),
+                        Op::Contains => values.any(|value| value.contains(&condition.value)),
+                    }
+                })
+            })
+    }
+}
+
+/// The redacted view of an event that subscribers receive.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TapEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub timestamp: String,
+    pub seq: u64,
+    /// Executable file name, without its directory.
+    pub process: String,
+    pub title: String,
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl TapEvent {
+    pub fn from_event(event: &WindowEvent, redaction: Option<&RedactionStage>) -> Self {
+        let mut title = event.title.clone();
+        if let Some(stage) = redaction {
+            stage.redact(&mut title);
+        }
+        Self {
+            event_type: event.event_type.clone(),
+            timestamp: event.timestamp.clone(),
+            seq: event.seq,
+            process: event.process_exe.rsplit(['\\', '/']).next().unwrap_or_default().to_string(),
+            title,
+            source: event.source.clone(),
+            idle_ms: event.idle_ms,
+            tags: event.tags.clone(),
+        }
+    }
+}
+
+/// Whether a handshake request presents `token`, as a bearer token or a
+/// `token` query parameter.
+fn authorized(request: &Request, token: &str) -> bool {
+    let bearer = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = query_param(request, "token");
+    [bearer.map(str::to_string), query].iter().flatten().any(|presented| constant_time_eq(presented, token))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn query_param(request: &Request, name: &str) -> Option<String> {
+    let url = url::Url::parse(&format!("ws://localhost{}", request.uri())).ok()?;
+    let value = url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+    value
+}
+
+fn reject(status: u16, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = tungstenite::http::StatusCode::from_u16(status).unwrap_or_default();
+    response
+}
+
+struct Subscriber {
+    filter: Filter,
+    tx: Sender<String>,
+}
+
+/// The running tap: subscribers and the redaction applied for them.
+pub struct Tap {
+    addr: SocketAddr,
+    redaction: Option<RedactionStage>,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Tap {
+    /// Listen on `127.0.0.1:port` (0 picks a free port) and accept
+    /// subscribers presenting `token` on a background thread.
+    pub fn start(port: u16, token: &str, config: &Config) -> std::io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let redaction = config.redaction_enabled.then(|| RedactionStage::new(&config.redact_pattern)).flatten();
+        let tap = Arc::new(Self { addr: listener.local_addr()?, redaction, subscribers: Mutex::new(Vec::new()) });
+        let accepting = tap.clone();
+        let token = token.to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tap = accepting.clone();
+                let token = token.clone();
+                thread::spawn(move || tap.serve(stream, &token));
+            }
+        });
+        Ok(tap)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // The handshake callback's error type is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    fn serve(&self, stream: TcpStream, token: &str) {
+        let mut filter = Filter::default();
+        let handshake = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+            if !authorized(request, token) {
+                return Err(reject(401, "missing or invalid token"));
+            }
+            filter = Filter::parse(&query_param(request, "filter").unwrap_or_default()).map_err(|e| reject(400, &e))?;
+            Ok(response)
+        });
+        let mut socket = match handshake {
+            Ok(socket) => socket,
+            Err(e) => {
+                log::info!("Event tap handshake rejected: {e}");
+                return;
+            }
+        };
+        let (tx, rx) = bounded(SUBSCRIBER_CAPACITY);
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(Subscriber { filter, tx });
+        log::info!("Event tap subscriber connected");
+        for payload in rx {
+            if socket.send(Message::Text(payload)).is_err() {
+                break;
+            }
+        }
+        // Dropping the receiver unsubscribes on the next publish
+    }
+
+    /// Offer an event to every subscriber whose filter matches it.
+    pub fn publish(&self, event: &WindowEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        if subscribers.is_empty() {
+            return;
+        }
+        let tapped = TapEvent::from_event(event, self.redaction.as_ref());
+        let payload = serde_json::to_string(&tapped).unwrap_or_default();
+        subscribers.retain(|subscriber| {
+            !subscriber.filter.matches(&tapped)
+                || !matches!(subscriber.tx.try_send(payload.clone()), Err(TrySendError::Disconnected(_)))
+        });
+    }
+}
+
+pub fn enabled(config: &Config) -> bool {
+    config.event_tap_port != 0 && !config.event_tap_token.is_empty()
+}
+
+/// Start the process-wide tap if configured. Only the first call has effect.
+pub fn start(config: &Config) {
+    if !enabled(config) || TAP.get().is_some() {
+        return;
+    }
+    match Tap::start(config.event_tap_port, &config.event_tap_token, config) {
+        Ok(tap) => {
+            log::info!("Event tap listening on ws://{}", tap.local_addr());
+            let _ = TAP.set(tap);
+        }
+        Err(e) => log::warn!("Event tap could not listen on port {}: {e}", config.event_tap_port),
+    }
+}
+
+/// Publish to the process-wide tap, if one is running.
+pub fn publish(event: &WindowEvent) {
+    if let Some(tap) = TAP.get() {
+        tap.publish(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+    use std::time::Duration;
+
+    fn tapped(event_type: &str, process: &str, title: &str) -> TapEvent {
+        let mut event = build_activity_event(event_type, 0);
+        event.process_exe = process.to_string();
+        event.title = title.to_string();
+        TapEvent::from_event(&event, None)
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let chrome = tapped("foreground", "C:\\Apps\\Chrome.exe", "Inbox - Mail");
+        let idle = tapped("idle", "", "");
+
+        assert!(Filter::parse("").unwrap().matches(&chrome));
+        let filter = Filter::parse("type == foreground and process ~ chrome or type=idle").unwrap();
+        assert!(filter.matches(&chrome) && filter.matches(&idle));
+        assert!(!filter.matches(&tapped("foreground", "notepad.exe", "")));
+        assert!(Filter::parse("title ~ 'inbox - mail'").unwrap().matches(&chrome));
+        assert!(!Filter::parse("type != foreground").unwrap().matches(&chrome));
+
+        let mut tagged = build_activity_event("foreground", 0);
+        tagged.tags = vec!["work".to_string(), "after-hours".to_string()];
+        let tagged = TapEvent::from_event(&tagged, None);
+        assert!(Filter::parse("tag == Work").unwrap().matches(&tagged));
+        assert!(Filter::parse("tag ~ hours and tag != personal").unwrap().matches(&tagged));
+        assert!(!Filter::parse("tag != work").unwrap().matches(&tagged));
+        assert!(!Filter::parse("tag == work").unwrap().matches(&chrome));
+
+        assert!(Filter::parse("window == x").unwrap_err().contains("unknown filter field"));
+        assert!(Filter::parse("type foreground").is_err());
+        assert!(Filter::parse("type == idle or").unwrap_err().contains("ends with"));
+        assert!(Filter::parse("type == idle nand source == x").is_err());
+        assert!(Filter::parse("title ~ 'unclosed").unwrap_err().contains("unterminated"));
+    }
+
+    #[test]
+    fn test_tap_event_is_redacted_projection() {
+        let mut event = build_activity_event("foreground", 0);
+        event.process_exe = "C:\\Program Files\\App\\app.exe".to_string();
+        event.title = "Password: hunter2".to_string();
+        event.screenshot_b64 = Some("AAAA".to_string());
+        event.collector_id = "lab-pc-01".to_string();
+        let stage = RedactionStage::new("hunter2").unwrap();
+
+        let tapped = TapEvent::from_event(&event, Some(&stage));
+        assert_eq!(tapped.process, "app.exe");
+        assert!(!tapped.title.contains("hunter2"));
+        let json = serde_json::to_string(&tapped).unwrap();
+        assert!(!json.contains("AAAA") && !json.contains("lab-pc-01"));
+    }
+
+    #[test]
+    fn test_subscribers_need_token_and_get_filtered_events() {
+        let tap = Tap::start(0, "s3cret", &Config::from_env()).unwrap();
+        let base = format!("ws://{}/", tap.local_addr());
+
+        assert!(tungstenite::connect(format!("{base}?token=wrong")).is_err());
+        assert!(tungstenite::connect(format!("{base}?token=s3cret&filter=window%20%3D%3D%20x")).is_err());
+
+        let (mut socket, _) = tungstenite::connect(format!("{base}?token=s3cret&filter=type%20%3D%3D%20idle")).unwrap();
+        // The subscriber registers after the handshake completes
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while tap.subscribers.lock().unwrap().is_empty() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        tap.publish(&build_activity_event("foreground", 0));
+        tap.publish(&build_activity_event("idle", 1000));
+
+        let Message::Text(text) = socket.read().unwrap() else {
+            panic!("expected a text frame");
+        };
+        let received: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(received["type"], "idle");
+        assert_eq!(received["idle_ms"], 1000);
+    }
+}
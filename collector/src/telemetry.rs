@@ -0,0 +1,243 @@
+//! Opt-in usage telemetry, aggregated and anonymized on this machine.
+//!
+//! Off unless `TELEMETRY_ENABLED` is set and `TELEMETRY_URL` names an
+//! endpoint. Executed commands are folded into counters as they finish —
+//! count, failures by category, and a latency histogram per action — and
+//! only those aggregates are uploaded every `TELEMETRY_INTERVAL_S`.
+//! Parameters, results, error text, window titles and the collector's
+//! id/name never enter the aggregate; a report identifies itself with a
+//! random id that changes every process start.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::command::CommandResult;
+use crate::config::Config;
+
+/// Upper bounds (ms) of the latency histogram buckets; a final bucket holds
+/// everything slower.
+pub const LATENCY_BUCKETS_MS: [u64; 9] = [10, 50, 100, 250, 500, 1000, 2500, 5000, 10_000];
+/// Action names longer than this, or not snake_case, are reported as "other".
+const MAX_ACTION_NAME: usize = 40;
+
+static AGGREGATE: Mutex<Option<Aggregate>> = Mutex::new(None);
+static SESSION_ID: OnceLock<String> = OnceLock::new();
+
+/// Counters for one action.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ActionStats {
+    pub count: u64,
+    pub failed: u64,
+    /// Counts per `LATENCY_BUCKETS_MS` bucket, plus one for slower calls.
+    pub latency_ms_buckets: Vec<u64>,
+}
+
+/// Everything telemetry keeps between uploads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    started: Instant,
+    actions: BTreeMap<String, ActionStats>,
+    failures: BTreeMap<&'static str, u64>,
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self { started: Instant::now(), actions: BTreeMap::new(), failures: BTreeMap::new() }
+    }
+}
+
+/// What is uploaded: aggregates only.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryReport {
+    pub schema: u32,
+    pub session_id: String,
+    pub version: &'static str,
+    pub platform: &'static str,
+    pub period_s: u64,
+    pub latency_bucket_bounds_ms: &'static [u64],
+    pub actions: BTreeMap<String, ActionStats>,
+    pub failures: BTreeMap<&'static str, u64>,
+}
+
+/// Action name as reported: unknown or unusual names collapse to "other".
+fn anonymize_action<'a>(action: &'a str, result: &CommandResult) -> &'a str {
+    let unknown = result.error.as_deref().is_some_and(|e| e.starts_with("unknown action"));
+    let plain = !action.is_empty()
+        && action.len() <= MAX_ACTION_NAME
+        && action.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    if unknown || !plain {
+        "other"
+    } else {
+        action
+    }
+}
+
+/// Fixed category for a failed result; the error text itself is dropped.
+pub fn failure_category(result: &CommandResult) -> &'static str {
+    match result.error_code.as_deref() {
+        Some("PolicyDenied" | "PermissionDenied") => return "denied",
+        Some("Cancelled") => return "cancelled",
+        _ => {}
+    }
+    let error = result.error.as_deref().unwrap_or("").to_lowercase();
+    if error.contains("requires windows") {
+        "unsupported"
+    } else if error.starts_with("unknown action") {
+        "unknown_action"
+    } else if error.contains("not found") || error.contains("no window") {
+        "not_found"
+    } else if error.contains("timed out") || error.contains("timeout") {
+        "timeout"
+    } else if error.contains("requires '") || error.contains("missing") || error.contains("must be") {
+        "invalid_parameters"
+    } else if error.contains("failed") {
+        "platform_error"
+    } else {
+        "other"
+    }
+}
+
+fn bucket_index(elapsed: Duration) -> usize {
+    let ms = elapsed.as_millis() as u64;
+    LATENCY_BUCKETS_MS.iter().position(|&bound| ms <= bound).unwrap_or(LATENCY_BUCKETS_MS.len())
+}
+
+impl Aggregate {
+    pub fn record(&mut self, action: &str, result: &CommandResult, elapsed: Duration) {
+        let stats = self.actions.entry(anonymize_action(action, result).to_string()).or_default();
+        if stats.latency_ms_buckets.is_empty() {
+            stats.latency_ms_buckets = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        }
+        stats.count += 1;
+        stats.latency_ms_buckets[bucket_index(elapsed)] += 1;
+        if !result.ok {
+            stats.failed += 1;
+            *self.failures.entry(failure_category(result)).or_default() += 1;
+        }
+    }
+
+    /// The report for everything recorded so far, or `None` if nothing was.
+    pub fn report(&self, session_id: &str) -> Option<TelemetryReport> {
+        if self.actions.is_empty() {
+            return None;
+        }
+        Some(TelemetryReport {
+            schema: 1,
+            session_id: session_id.to_string(),
+            version: env!("CARGO_PKG_VERSION"),
+            platform: std::env::consts::OS,
+            period_s: self.started.elapsed().as_secs(),
+            latency_bucket_bounds_ms: &LATENCY_BUCKETS_MS,
+            actions: self.actions.clone(),
+            failures: self.failures.clone(),
+        })
+    }
+}
+
+pub fn enabled(config: &Config) -> bool {
+    config.telemetry_enabled && !config.telemetry_url.is_empty()
+}
+
+/// Count a finished command, if telemetry is on.
+pub fn record(config: &Config, action: &str, result: &CommandResult, elapsed: Duration) {
+    if !enabled(config) {
+        return;
+    }
+    let mut aggregate = AGGREGATE.lock().unwrap_or_else(|e| e.into_inner());
+    aggregate.get_or_insert_with(Aggregate::default).record(action, result, elapsed);
+}
+
+/// The report recorded since the last upload, without resetting it.
+pub fn pending_report() -> Option<TelemetryReport> {
+    let aggregate = AGGREGATE.lock().unwrap_or_else(|e| e.into_inner());
+    aggregate.as_ref()?.report(SESSION_ID.get_or_init(|| uuid::Uuid::new_v4().to_string()))
+}
+
+/// Take the pending report and reset the counters.
+fn take_report() -> Option<TelemetryReport> {
+    let aggregate = AGGREGATE.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+    aggregate.report(SESSION_ID.get_or_init(|| uuid::Uuid::new_v4().to_string()))
+}
+
+/// Upload the aggregate every `telemetry_interval`. A failed upload is
+/// dropped rather than retried, so nothing accumulates on disk or in memory.
+pub fn telemetry_worker(config: Config) {
+    if !enabled(&config) {
+        return;
+    }
+    log::info!("Telemetry enabled: aggregates upload to {} every {:?}", config.telemetry_url, config.telemetry_interval);
+    loop {
+        std::thread::sleep(config.telemetry_interval);
+        let Some(report) = take_report() else {
+            continue;
+        };
+        if let Err(e) = ureq::post(&config.telemetry_url).send_json(&report) {
+            log::warn!("Telemetry upload failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn failed(error: &str) -> CommandResult {
+        CommandResult::failure("cmd-1", error)
+    }
+
+    #[test]
+    fn test_aggregate_counts_and_latency_buckets() {
+        let mut aggregate = Aggregate::default();
+        assert_eq!(aggregate.report("s"), None);
+
+        let ok = CommandResult::success("cmd-1", HashMap::new());
+        aggregate.record("click", &ok, Duration::from_millis(5));
+        aggregate.record("click", &ok, Duration::from_millis(300));
+        aggregate.record("click", &failed("element not found: Submit"), Duration::from_millis(20_000));
+        aggregate.record("frobnicate", &failed("unknown action: frobnicate"), Duration::from_millis(1));
+
+        let report = aggregate.report("s").unwrap();
+        let click = &report.actions["click"];
+        assert_eq!((click.count, click.failed), (3, 1));
+        assert_eq!(click.latency_ms_buckets, vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(report.actions["other"].count, 1);
+        assert!(!report.actions.contains_key("frobnicate"));
+        assert_eq!(report.failures["not_found"], 1);
+        assert_eq!(report.failures["unknown_action"], 1);
+    }
+
+    #[test]
+    fn test_failure_categories() {
+        assert_eq!(failure_category(&CommandResult::denied("c", "safe mode")), "denied");
+        assert_eq!(failure_category(&CommandResult::cancelled("c", "run_shell")), "cancelled");
+        assert_eq!(failure_category(&failed("scroll requires Windows")), "unsupported");
+        assert_eq!(failure_category(&failed("window not found matching: Budget.xlsx")), "not_found");
+        assert_eq!(failure_category(&failed("select_item requires 'item' parameter")), "invalid_parameters");
+        assert_eq!(failure_category(&failed("notepad timed out after 5000ms")), "timeout");
+        assert_eq!(failure_category(&failed("SetValue failed: 0x80004005")), "platform_error");
+        assert_eq!(failure_category(&failed("recycle operation was aborted")), "other");
+    }
+
+    #[test]
+    fn test_report_carries_no_content() {
+        let mut aggregate = Aggregate::default();
+        let mut result = HashMap::new();
+        result.insert("text".to_string(), serde_json::json!("Dear Alice, the password is hunter2"));
+        aggregate.record("get_text", &CommandResult::success("cmd-secret-id", result), Duration::from_millis(40));
+        aggregate.record(
+            "type_text",
+            &failed("window not found matching: Alice - Private Chat"),
+            Duration::from_millis(40),
+        );
+        aggregate.record("Read C:\\Users\\alice", &failed("x"), Duration::from_millis(40));
+
+        let json = serde_json::to_string(&aggregate.report("session").unwrap()).unwrap();
+        for leaked in ["Alice", "alice", "hunter2", "cmd-secret-id", "Private", "Users"] {
+            assert!(!json.contains(leaked), "report leaks {leaked}: {json}");
+        }
+        assert!(json.contains("\"get_text\"") && json.contains("\"other\""));
+    }
+}
@@ -0,0 +1,108 @@
+//! Text normalization for titles, UIA names and document text.
+//!
+//! Window text arrives in whatever form the app produced: decomposed accents,
+//! zero-width characters, stray control characters and bidi formatting marks
+//! from mixed RTL/LTR titles. The same visible title can therefore serialize
+//! to different strings, which breaks backend matching and scrambles logs.
+//! Everything is normalized to NFC with invisible formatting removed.
+//! Process image paths get the same treatment for their `\\?\` prefix.
+
+use icu_normalizer::ComposingNormalizerBorrowed;
+
+/// Bidi embedding, override, isolate and mark characters. Unbalanced ones
+/// reorder everything after them when a title is logged or concatenated.
+const BIDI_CONTROLS: &[char] = &[
+    '\u{061C}', // ARABIC LETTER MARK
+    '\u{200E}', // LEFT-TO-RIGHT MARK
+    '\u{200F}', // RIGHT-TO-LEFT MARK
+    '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}', // LRE RLE PDF LRO RLO
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}', // LRI RLI FSI PDI
+];
+
+/// Invisible characters with no meaning for matching. ZWJ/ZWNJ are kept:
+/// they change rendering in Indic and Persian scripts and in emoji sequences.
+const INVISIBLES: &[char] = &[
+    '\u{00AD}', // SOFT HYPHEN
+    '\u{200B}', // ZERO WIDTH SPACE
+    '\u{2060}', // WORD JOINER
+    '\u{FEFF}', // ZERO WIDTH NO-BREAK SPACE / BOM
+];
+
+fn keep_char(c: char, multiline: bool) -> bool {
+    if BIDI_CONTROLS.contains(&c) || INVISIBLES.contains(&c) {
+        return false;
+    }
+    !c.is_control() || (multiline && (c == '\n' || c == '\t'))
+}
+
+/// Normalize single-line text (titles, element names): NFC, bidi controls
+/// and invisibles removed, line breaks and tabs turned into spaces, other
+/// control characters dropped.
+pub fn normalize_line(text: &str) -> String {
+    let text = text.replace("\r\n", " ").replace(['\r', '\n', '\t'], " ");
+    normalize(&text, false)
+}
+
+/// Normalize multi-line text (document text): like `normalize_line`, but
+/// newlines and tabs survive and CRLF becomes LF.
+pub fn normalize_multiline(text: &str) -> String {
+    normalize(&text.replace("\r\n", "\n"), true)
+}
+
+fn normalize(text: &str, multiline: bool) -> String {
+    if text.is_ascii() && text.chars().all(|c| keep_char(c, multiline)) {
+        return text.to_string();
+    }
+    let filtered: String = text.chars().filter(|&c| keep_char(c, multiline)).collect();
+    ComposingNormalizerBorrowed::new_nfc().normalize(&filtered).into_owned()
+}
+
+/// Strip the Win32 verbatim prefix from a path: `\\?\C:\x` becomes `C:\x`
+/// and `\\?\UNC\server\share` becomes `\\server\share`.
+pub fn strip_verbatim_prefix(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{rest}")
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfc_composes_accents() {
+        // "e" + COMBINING ACUTE ACCENT -> "é"
+        assert_eq!(normalize_line("Caf\u{0065}\u{0301}"), "Caf\u{00E9}");
+    }
+
+    #[test]
+    fn test_strips_bidi_and_invisibles() {
+        let title = "\u{2067}\u{05E9}\u{05DC}\u{05D5}\u{05DD}\u{2069} - Note\u{200B}pad\u{200F}";
+        assert_eq!(normalize_line(title), "\u{05E9}\u{05DC}\u{05D5}\u{05DD} - Notepad");
+        assert_eq!(normalize_line("\u{202E}txt.exe"), "txt.exe");
+    }
+
+    #[test]
+    fn test_control_characters() {
+        assert_eq!(normalize_line("Line1\r\nLine2\u{0007}"), "Line1 Line2");
+        assert_eq!(normalize_multiline("a\r\nb\tc\u{0000}"), "a\nb\tc");
+    }
+
+    #[test]
+    fn test_keeps_joiners_and_plain_ascii() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(normalize_line(family), family);
+        assert_eq!(normalize_line("Inbox - Outlook"), "Inbox - Outlook");
+    }
+
+    #[test]
+    fn test_strip_verbatim_prefix() {
+        assert_eq!(strip_verbatim_prefix(r"\\?\C:\Tools\app.exe"), r"C:\Tools\app.exe");
+        assert_eq!(strip_verbatim_prefix(r"\\?\UNC\srv\share\app.exe"), r"\\srv\share\app.exe");
+        assert_eq!(strip_verbatim_prefix(r"C:\Windows\notepad.exe"), r"C:\Windows\notepad.exe");
+    }
+}
@@ -0,0 +1,145 @@
+//! Light/dark theme detection. The detector and OCR behave differently on
+//! dark UIs, so the backend gets the system theme in the hello handshake and
+//! the per-window theme on foreground events.
+
+use serde::Serialize;
+
+/// System-wide theme settings.
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct ThemeInfo {
+    /// Theme apps are asked to use ("light" or "dark").
+    pub apps: String,
+    /// Theme of the shell: taskbar, Start, notifications.
+    pub system: String,
+    pub high_contrast: bool,
+}
+
+/// Map a `*UsesLightTheme` registry DWORD to a theme name. A missing value
+/// means the pre-1809 default, which is light.
+pub fn theme_name(uses_light: Option<u32>) -> &'static str {
+    match uses_light {
+        Some(0) => "dark",
+        _ => "light",
+    }
+}
+
+/// Effective theme of one window: dark if the window opted into a dark
+/// frame via DWM, otherwise whatever apps are asked to use.
+pub fn window_theme_name(dwm_dark: Option<bool>, system: &ThemeInfo) -> String {
+    match dwm_dark {
+        Some(true) => "dark".to_string(),
+        _ => system.apps.clone(),
+    }
+}
+
+/// System theme for the hello handshake; `None` off Windows.
+pub fn current_theme() -> Option<ThemeInfo> {
+    #[cfg(windows)]
+    return Some(system_theme());
+    #[cfg(not(windows))]
+    None
+}
+
+#[cfg(windows)]
+const PERSONALIZE_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+
+#[cfg(windows)]
+fn read_hkcu_dword(subkey: &str, value: &str) -> Option<u32> {
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    let subkey_w: Vec<u16> = subkey.encode_utf16().chain(Some(0)).collect();
+    let value_w: Vec<u16> = value.encode_utf16().chain(Some(0)).collect();
+    let mut data: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            PCWSTR(subkey_w.as_ptr()),
+            PCWSTR(value_w.as_ptr()),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut data as *mut u32 as *mut std::ffi::c_void),
+            Some(&mut size),
+        )
+        .ok()?;
+    }
+    Some(data)
+}
+
+/// Read the current system theme from the registry and accessibility settings.
+#[cfg(windows)]
+pub fn system_theme() -> ThemeInfo {
+    use windows::Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    let mut hc = HIGHCONTRASTW {
+        cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+        ..Default::default()
+    };
+    let high_contrast = unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            hc.cbSize,
+            Some(&mut hc as *mut HIGHCONTRASTW as *mut std::ffi::c_void),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .is_ok()
+    } && (hc.dwFlags.0 & HCF_HIGHCONTRASTON.0) != 0;
+
+    ThemeInfo {
+        apps: theme_name(read_hkcu_dword(PERSONALIZE_KEY, "AppsUseLightTheme")).to_string(),
+        system: theme_name(read_hkcu_dword(PERSONALIZE_KEY, "SystemUsesLightTheme")).to_string(),
+        high_contrast,
+    }
+}
+
+/// Whether the window asked DWM for a dark title bar, or `None` if the
+/// attribute is unsupported (pre-20H1 builds).
+#[cfg(windows)]
+pub fn window_uses_dark_mode(hwnd: windows::Win32::Foundation::HWND) -> Option<bool> {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_USE_IMMERSIVE_DARK_MODE};
+
+    let mut dark = BOOL(0);
+    unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_USE_IMMERSIVE_DARK_MODE,
+            &mut dark as *mut BOOL as *mut std::ffi::c_void,
+            std::mem::size_of::<BOOL>() as u32,
+        )
+        .ok()?;
+    }
+    Some(dark.as_bool())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_name() {
+        assert_eq!(theme_name(Some(0)), "dark");
+        assert_eq!(theme_name(Some(1)), "light");
+        assert_eq!(theme_name(None), "light");
+    }
+
+    #[test]
+    fn test_window_theme_prefers_dwm() {
+        let system = ThemeInfo { apps: "light".to_string(), system: "dark".to_string(), high_contrast: false };
+        assert_eq!(window_theme_name(Some(true), &system), "dark");
+        assert_eq!(window_theme_name(Some(false), &system), "light");
+        assert_eq!(window_theme_name(None, &system), "light");
+    }
+
+    #[test]
+    fn test_theme_info_serialization() {
+        let info = ThemeInfo { apps: "dark".to_string(), system: "dark".to_string(), high_contrast: true };
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["apps"], "dark");
+        assert_eq!(json["high_contrast"], true);
+    }
+}
@@ -0,0 +1,407 @@
+//! OpenTelemetry traces of command execution and capture, exported as
+//! OTLP/HTTP JSON.
+//!
+//! Off unless `OTEL_EXPORTER_OTLP_ENDPOINT` names an OTLP collector; spans
+//! are then POSTed in batches to `<endpoint>/v1/traces`. The backend sends a
+//! W3C `traceparent` with each command, so the collector's spans join the
+//! trace of the backend request that issued it:
+//!
+//! - `collector.command`: from the command's arrival at the network worker
+//!   until its result is sent back.
+//! - `collector.execute`: `execute_command` on the command worker.
+//! - `collector.enrich`: the capture pipeline for one event, with a
+//!   `collector.stage` child per enrichment stage.
+//!
+//! Spans finish when dropped. Without an exporter they are inert and cost
+//! nothing but a check; a full export queue drops spans rather than block.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+
+use crate::config::Config;
+
+/// Finished spans waiting for export at most.
+const QUEUE_CAPACITY: usize = 2048;
+/// Spans per export request at most.
+const BATCH_SIZE: usize = 256;
+/// Finished spans go out at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+static EXPORTER: OnceLock<Sender<SpanData>> = OnceLock::new();
+
+/// OTLP span kinds used here.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    Internal = 1,
+    /// Handling a request from the backend.
+    Server = 2,
+}
+
+/// An attribute value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+/// Trace and span id of a span, as carried in a `traceparent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpanContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+}
+
+impl SpanContext {
+    /// Parse a W3C `traceparent` (`00-<trace id>-<span id>-<flags>`).
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace, span, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version.len() != 2 || version == "ff" || flags.len() != 2 || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        let trace_id: [u8; 16] = decode_hex(trace)?.try_into().ok()?;
+        let span_id: [u8; 8] = decode_hex(span)?.try_into().ok()?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self { trace_id, span_id })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", hex(&self.trace_id), hex(&self.span_id))
+    }
+}
+
+/// A finished (or running) span's contents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanData {
+    pub context: SpanContext,
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: &'static str,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, Value)>,
+    /// Set when the span failed.
+    pub error: Option<String>,
+}
+
+/// A span that is exported when dropped; inert while tracing is off.
+pub struct Span {
+    data: Option<Box<SpanData>>,
+    started: Instant,
+}
+
+impl Span {
+    /// Start a span under the one `traceparent` names, or a new trace when
+    /// it is missing or malformed.
+    pub fn start(name: &'static str, kind: SpanKind, traceparent: Option<&str>) -> Span {
+        if EXPORTER.get().is_none() {
+            return Span::inert();
+        }
+        Span::begin(name, kind, traceparent.and_then(SpanContext::parse))
+    }
+
+    /// Start a child of this span.
+    pub fn child(&self, name: &'static str) -> Span {
+        match &self.data {
+            Some(parent) => Span::begin(name, SpanKind::Internal, Some(parent.context)),
+            None => Span::inert(),
+        }
+    }
+
+    fn inert() -> Span {
+        Span { data: None, started: Instant::now() }
+    }
+
+    fn begin(name: &'static str, kind: SpanKind, parent: Option<SpanContext>) -> Span {
+        let trace_id = parent.map_or_else(|| *uuid::Uuid::new_v4().as_bytes(), |p| p.trace_id);
+        let mut span_id = [0u8; 8];
+        span_id.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..8]);
+        let now = SystemTime::now();
+        Span {
+            data: Some(Box::new(SpanData {
+                context: SpanContext { trace_id, span_id },
+                parent_span_id: parent.map(|p| p.span_id),
+                name,
+                kind,
+                start: now,
+                end: now,
+                attributes: Vec::new(),
+                error: None,
+            })),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.data.is_some()
+    }
+
+    /// The `traceparent` naming this span, for work that continues it
+    /// elsewhere; `None` while tracing is off.
+    pub fn traceparent(&self) -> Option<String> {
+        self.data.as_ref().map(|d| d.context.traceparent())
+    }
+
+    pub fn set(&mut self, key: &'static str, value: impl Into<Value>) {
+        if let Some(data) = self.data.as_mut() {
+            data.attributes.push((key, value.into()));
+        }
+    }
+
+    /// Mark the span failed.
+    pub fn fail(&mut self, message: &str) {
+        if let Some(data) = self.data.as_mut() {
+            data.error = Some(message.to_string());
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(mut data) = self.data.take() else {
+            return;
+        };
+        // The monotonic clock measures the duration; wall time only anchors it
+        data.end = data.start + self.started.elapsed();
+        if let Some(exporter) = EXPORTER.get() {
+            let _ = exporter.try_send(*data);
+        }
+    }
+}
+
+pub fn enabled(config: &Config) -> bool {
+    !config.otlp_endpoint.trim().is_empty()
+}
+
+/// Start exporting spans to the configured endpoint, if any.
+pub fn start(config: &Config) {
+    if !enabled(config) {
+        return;
+    }
+    let (tx, rx) = crossbeam_channel::bounded(QUEUE_CAPACITY);
+    if EXPORTER.set(tx).is_err() {
+        return;
+    }
+    let url = format!("{}/v1/traces", config.otlp_endpoint.trim().trim_end_matches('/'));
+    let resource = resource(config);
+    log::info!("Exporting traces to {url}");
+    std::thread::spawn(move || export_worker(rx, &url, &resource));
+}
+
+fn resource(config: &Config) -> serde_json::Value {
+    let mut attributes = vec![
+        attribute("service.name", &Value::from(config.otel_service_name.as_str())),
+        attribute("service.version", &Value::from(env!("CARGO_PKG_VERSION"))),
+    ];
+    if !config.collector_id.is_empty() {
+        attributes.push(attribute("service.instance.id", &Value::from(config.collector_id.as_str())));
+    }
+    serde_json::json!({ "attributes": attributes })
+}
+
+/// Send finished spans in batches of up to `BATCH_SIZE`, at least every
+/// `FLUSH_INTERVAL`. A failed export is dropped, not retried.
+fn export_worker(rx: Receiver<SpanData>, url: &str, resource: &serde_json::Value) {
+    let mut batch = Vec::new();
+    let mut flushed = Instant::now();
+    loop {
+        let wait = FLUSH_INTERVAL.saturating_sub(flushed.elapsed());
+        match rx.recv_timeout(wait) {
+            Ok(span) => batch.push(span),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        if batch.len() >= BATCH_SIZE || (flushed.elapsed() >= FLUSH_INTERVAL && !batch.is_empty()) {
+            if let Err(e) = ureq::post(url).send_json(build_export(resource, &batch)) {
+                log::warn!("Trace export of {} spans failed: {e}", batch.len());
+            }
+            batch.clear();
+        }
+        if batch.is_empty() {
+            flushed = Instant::now();
+        }
+    }
+}
+
+/// OTLP/JSON `ExportTraceServiceRequest` for `spans`.
+pub fn build_export(resource: &serde_json::Value, spans: &[SpanData]) -> serde_json::Value {
+    serde_json::json!({
+        "resourceSpans": [{
+            "resource": resource,
+            "scopeSpans": [{
+                "scope": { "name": "desktopai-collector", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans.iter().map(span_json).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+fn span_json(span: &SpanData) -> serde_json::Value {
+    let nanos = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+    let mut json = serde_json::json!({
+        "traceId": hex(&span.context.trace_id),
+        "spanId": hex(&span.context.span_id),
+        "name": span.name,
+        "kind": span.kind as u8,
+        "startTimeUnixNano": nanos(span.start),
+        "endTimeUnixNano": nanos(span.end),
+        "attributes": span.attributes.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+        "status": match &span.error {
+            Some(message) => serde_json::json!({ "code": 2, "message": message }),
+            None => serde_json::json!({ "code": 0 }),
+        },
+    });
+    if let Some(parent) = span.parent_span_id {
+        json["parentSpanId"] = serde_json::json!(hex(&parent));
+    }
+    json
+}
+
+fn attribute(key: &str, value: &Value) -> serde_json::Value {
+    let value = match value {
+        Value::Str(s) => serde_json::json!({ "stringValue": s }),
+        Value::Int(i) => serde_json::json!({ "intValue": i.to_string() }),
+        Value::Bool(b) => serde_json::json!({ "boolValue": b }),
+    };
+    serde_json::json!({ "key": key, "value": value })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip_and_validation() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = SpanContext::parse(header).unwrap();
+        assert_eq!(hex(&context.trace_id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(hex(&context.span_id), "00f067aa0ba902b7");
+        assert_eq!(context.traceparent(), header);
+
+        for bad in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-4bf92f35-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(SpanContext::parse(bad), None, "{bad:?} should be rejected");
+        }
+        // Later versions may append fields
+        assert!(SpanContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+    }
+
+    #[test]
+    fn test_spans_are_inert_without_exporter() {
+        let mut span = Span::start("collector.command", SpanKind::Server, None);
+        span.set("command.action", "click");
+        assert!(!span.is_recording());
+        assert_eq!(span.traceparent(), None);
+        assert!(!span.child("collector.execute").is_recording());
+    }
+
+    #[test]
+    fn test_children_continue_the_parent_trace() {
+        let parent = Span::begin(
+            "collector.command",
+            SpanKind::Server,
+            SpanContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let data = parent.data.as_ref().unwrap();
+        assert_eq!(hex(&data.context.trace_id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(data.parent_span_id.map(|id| hex(&id)).as_deref(), Some("00f067aa0ba902b7"));
+
+        let child = parent.child("collector.execute");
+        let child_data = child.data.as_ref().unwrap();
+        assert_eq!(child_data.context.trace_id, data.context.trace_id);
+        assert_eq!(child_data.parent_span_id, Some(data.context.span_id));
+        assert_ne!(child_data.context.span_id, data.context.span_id);
+
+        let root = Span::begin("collector.enrich", SpanKind::Internal, None);
+        assert_ne!(root.data.as_ref().unwrap().context.trace_id, data.context.trace_id);
+        assert_eq!(root.data.as_ref().unwrap().parent_span_id, None);
+    }
+
+    #[test]
+    fn test_export_payload_shape() {
+        let context = SpanContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        let start = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        let span = SpanData {
+            context,
+            parent_span_id: Some([1, 2, 3, 4, 5, 6, 7, 8]),
+            name: "collector.execute",
+            kind: SpanKind::Internal,
+            start,
+            end: start + Duration::from_millis(12),
+            attributes: vec![("command.action", Value::from("click")), ("command.ok", Value::from(false))],
+            error: Some("element not found".to_string()),
+        };
+        let mut config = Config::from_env();
+        config.otel_service_name = "desk-7".to_string();
+        config.collector_id = String::new();
+        let json = build_export(&resource(&config), &[span]);
+
+        let resource = &json["resourceSpans"][0]["resource"]["attributes"];
+        assert_eq!(resource[0]["key"], "service.name");
+        assert_eq!(resource[0]["value"]["stringValue"], "desk-7");
+        let span = &json["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span["parentSpanId"], "0102030405060708");
+        assert_eq!(span["kind"], 1);
+        assert_eq!(span["startTimeUnixNano"], "1700000000000000000");
+        assert_eq!(span["endTimeUnixNano"], "1700000000012000000");
+        assert_eq!(span["attributes"][0]["value"]["stringValue"], "click");
+        assert_eq!(span["attributes"][1]["value"]["boolValue"], false);
+        assert_eq!(span["status"]["code"], 2);
+        assert_eq!(span["status"]["message"], "element not found");
+    }
+}
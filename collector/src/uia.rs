@@ -15,6 +15,9 @@ use windows::Win32::UI::Accessibility::{
 use crate::config::Config;
 use crate::event::{bstr_to_string, UiaElement, UiaSnapshot};
 
+/// Children visited per element in passive snapshots.
+const SNAPSHOT_MAX_CHILDREN: i32 = 20;
+
 pub static UIA_LAST_SNAPSHOT: OnceLock<Mutex<Instant>> = OnceLock::new();
 
 thread_local! {
@@ -69,7 +72,7 @@ fn get_bstr_property(element: &IUIAutomationElement, getter: impl FnOnce(&IUIAut
 }
 
 #[allow(non_upper_case_globals)]
-fn build_uia_element(element: &IUIAutomationElement, depth: usize, max_depth: usize) -> Option<UiaElement> {
+fn build_uia_element(element: &IUIAutomationElement, depth: usize, max_depth: usize, max_children: i32) -> Option<UiaElement> {
     let automation_id = get_bstr_property(element, |e| unsafe { e.CurrentAutomationId() });
     let name = get_bstr_property(element, |e| unsafe { e.CurrentName() });
     let control_type = get_bstr_property(element, |e| unsafe { e.CurrentLocalizedControlType() });
@@ -120,9 +123,9 @@ fn build_uia_element(element: &IUIAutomationElement, depth: usize, max_depth: us
         if let Some(condition) = get_uia().and_then(|uia| unsafe { uia.CreateTrueCondition().ok() }) {
             if let Ok(found) = unsafe { element.FindAll(TreeScope_Children, &condition) } {
                 if let Ok(length) = unsafe { found.Length() } {
-                    for i in 0..length.min(20) {  // Limit to 20 children per element
+                    for i in 0..length.min(max_children) {
                         if let Ok(child) = unsafe { found.GetElement(i) } {
-                            if let Some(child_element) = build_uia_element(&child, depth + 1, max_depth) {
+                            if let Some(child_element) = build_uia_element(&child, depth + 1, max_depth, max_children) {
                                 children.push(child_element);
                             }
                         }
@@ -147,6 +150,15 @@ fn build_uia_element(element: &IUIAutomationElement, depth: usize, max_depth: us
     })
 }
 
+/// Full UIA tree of a window down to `max_depth`, visiting at most
+/// `max_children` children per element. Unlike `uia_snapshot` this ignores
+/// `uia_enabled` and the snapshot throttle; it backs on-demand tree dumps.
+pub fn element_tree(hwnd: HWND, max_depth: usize, max_children: usize) -> Option<UiaElement> {
+    let automation = get_uia()?;
+    let root = unsafe { automation.ElementFromHandle(hwnd).ok()? };
+    build_uia_element(&root, 0, max_depth, max_children.min(i32::MAX as usize) as i32)
+}
+
 pub fn uia_snapshot(hwnd: HWND, config: &Config) -> Option<UiaSnapshot> {
     if !config.uia_enabled {
         return None;
@@ -181,12 +193,12 @@ pub fn uia_snapshot(hwnd: HWND, config: &Config) -> Option<UiaSnapshot> {
     }
 
     // Build focused element details
-    let focused_element = build_uia_element(&element, 0, config.uia_max_depth);
+    let focused_element = build_uia_element(&element, 0, config.uia_max_depth, SNAPSHOT_MAX_CHILDREN);
 
     // Build window tree from the window root
     let mut window_tree = Vec::new();
     if let Ok(window_element) = unsafe { automation.ElementFromHandle(hwnd) } {
-        if let Some(root) = build_uia_element(&window_element, 0, config.uia_max_depth) {
+        if let Some(root) = build_uia_element(&window_element, 0, config.uia_max_depth, SNAPSHOT_MAX_CHILDREN) {
             window_tree.push(root);
         }
     }
@@ -197,6 +209,7 @@ pub fn uia_snapshot(hwnd: HWND, config: &Config) -> Option<UiaSnapshot> {
         document_text,
         focused_element,
         window_tree,
+        outline: String::new(),
     };
     if snapshot.focused_name.is_empty()
         && snapshot.control_type.is_empty()
@@ -0,0 +1,280 @@
+//! Command watchdog: runs backend commands off the socket loop and enforces
+//! their `timeout_ms`.
+//!
+//! Commands execute one at a time on a worker thread, so the network loop
+//! keeps sending events and heartbeats while one runs. A command still
+//! unfinished `timeout_ms` after it started (plus a short grace, so handlers
+//! that honor the timeout themselves report first) is answered with a
+//! `Timeout` failure; time spent queued does not count. The timed-out
+//! command is cancelled (see `cancel`) so it stops driving the desktop at
+//! its next check, but a hung handler cannot be stopped: its worker is
+//! abandoned, exits whenever the handler returns, and its late result is
+//! dropped. The next command starts on a fresh worker.
+//!
+//! A `cancel` for a queued command drops it from the queue and answers
+//! `Cancelled`; for the running one it sets the command's cancel token (see
+//! `cancel`) and the handler answers when it next checks.
+
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command::{Command, CommandResult};
+use crate::config::Config;
+
+/// Extra time past `timeout_ms` before the watchdog answers for a handler.
+const GRACE: Duration = Duration::from_millis(1000);
+
+/// Runs one command; `command::execute_command` outside of tests.
+pub type Executor = fn(&Command, &Config) -> CommandResult;
+
+type Job = (Command, Sender<CommandResult>);
+
+struct Running {
+    cmd: Command,
+    started: Instant,
+    deadline: Instant,
+    result: Receiver<CommandResult>,
+}
+
+/// Queue of commands with at most one executing at a time.
+pub struct CommandWorker {
+    config: Config,
+    execute: Executor,
+    grace: Duration,
+    worker: Option<Sender<Job>>,
+    queue: VecDeque<Command>,
+    running: Option<Running>,
+    cancelled: Vec<Command>,
+}
+
+impl CommandWorker {
+    pub fn new(config: Config, execute: Executor) -> Self {
+        Self { config, execute, grace: GRACE, worker: None, queue: VecDeque::new(), running: None, cancelled: Vec::new() }
+    }
+
+    /// Queue a command; its deadline counts from when it starts.
+    pub fn submit(&mut self, cmd: Command) {
+        self.queue.push_back(cmd);
+    }
+
+    /// No command running or queued.
+    pub fn is_idle(&self) -> bool {
+        self.running.is_none() && self.queue.is_empty() && self.cancelled.is_empty()
+    }
+
+    /// Cancel a queued or running command. Returns false if there is no
+    /// such command.
+    pub fn cancel(&mut self, command_id: &str) -> bool {
+        if let Some(index) = self.queue.iter().position(|cmd| cmd.command_id == command_id) {
+            let cmd = self.queue.remove(index).expect("index is in the queue");
+            log::info!("Cancelled queued command {} (id={command_id})", cmd.action);
+            self.cancelled.push(cmd);
+            return true;
+        }
+        let running = self.running.as_ref().is_some_and(|r| r.cmd.command_id == command_id);
+        if running && crate::cancel::cancel(command_id) {
+            log::info!("Cancelling running command (id={command_id})");
+            return true;
+        }
+        false
+    }
+
+    /// Collect finished and timed-out commands with their results, and start
+    /// the next queued command if the worker is free.
+    pub fn poll(&mut self) -> Vec<(Command, CommandResult)> {
+        let mut done: Vec<_> = self
+            .cancelled
+            .drain(..)
+            .map(|cmd| {
+                let result = CommandResult::cancelled(&cmd.command_id, &cmd.action);
+                crate::telemetry::record(&self.config, &cmd.action, &result, Duration::ZERO);
+                (cmd, result)
+            })
+            .collect();
+        loop {
+            if let Some(running) = &self.running {
+                let result = match running.result.try_recv() {
+                    Ok(result) => result,
+                    Err(TryRecvError::Empty) if Instant::now() < running.deadline => break,
+                    Err(TryRecvError::Empty) => {
+                        log::warn!(
+                            "Command {} (id={}) exceeded its {}ms timeout; cancelling it and abandoning its worker",
+                            running.cmd.action,
+                            running.cmd.command_id,
+                            running.cmd.timeout_ms
+                        );
+                        crate::cancel::cancel(&running.cmd.command_id);
+                        self.worker = None;
+                        self.timed_out(&running.cmd, running.started.elapsed())
+                    }
+                    Err(TryRecvError::Disconnected) => {
+                        self.worker = None;
+                        CommandResult::failure(&running.cmd.command_id, &format!("{} handler panicked", running.cmd.action))
+                    }
+                };
+                let running = self.running.take().expect("a command is running");
+                done.push((running.cmd, result));
+            }
+
+            let Some(cmd) = self.queue.pop_front() else {
+                break;
+            };
+            let (reply, result) = crossbeam_channel::bounded(1);
+            if self.worker().send((cmd.clone(), reply)).is_err() {
+                self.worker = None;
+                let result = CommandResult::failure(&cmd.command_id, "command worker is not running");
+                done.push((cmd, result));
+                continue;
+            }
+            let started = Instant::now();
+            let deadline = started + Duration::from_millis(cmd.timeout_ms) + self.grace;
+            self.running = Some(Running { cmd, started, deadline, result });
+        }
+        done
+    }
+
+    fn worker(&mut self) -> &Sender<Job> {
+        self.worker.get_or_insert_with(|| {
+            let (tx, rx) = unbounded::<Job>();
+            let config = self.config.clone();
+            let execute = self.execute;
+            thread::spawn(move || {
+                for (cmd, reply) in rx {
+                    let _ = reply.send(execute(&cmd, &config));
+                }
+            });
+            tx
+        })
+    }
+
+    fn timed_out(&self, cmd: &Command, elapsed: Duration) -> CommandResult {
+        let result = CommandResult::timed_out(&cmd.command_id, &cmd.action, cmd.timeout_ms);
+        crate::telemetry::record(&self.config, &cmd.action, &result, elapsed);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Ids of "wait" commands that saw their cancellation.
+    static STOPPED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    /// "hang" sleeps well past any test timeout, "wait" until cancelled;
+    /// everything else succeeds.
+    fn fake_execute(cmd: &Command, _config: &Config) -> CommandResult {
+        if cmd.action == "hang" {
+            thread::sleep(Duration::from_millis(500));
+        }
+        let _registration = crate::cancel::register(&cmd.command_id);
+        if cmd.action == "wait" && !crate::cancel::pause(Duration::from_secs(5)) {
+            STOPPED.lock().unwrap().push(cmd.command_id.clone());
+            return CommandResult::cancelled(&cmd.command_id, &cmd.action);
+        }
+        CommandResult::success(&cmd.command_id, HashMap::new())
+    }
+
+    fn command(id: &str, action: &str, timeout_ms: u64) -> Command {
+        Command {
+            command_id: id.to_string(),
+            action: action.to_string(),
+            parameters: HashMap::new(),
+            timeout_ms,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        }
+    }
+
+    fn drain(worker: &mut CommandWorker) -> Vec<(Command, CommandResult)> {
+        let mut done = Vec::new();
+        let give_up = Instant::now() + Duration::from_secs(5);
+        while !worker.is_idle() && Instant::now() < give_up {
+            done.extend(worker.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        done
+    }
+
+    #[test]
+    fn test_runs_commands_in_order() {
+        let mut worker = CommandWorker::new(Config::from_env(), fake_execute);
+        worker.submit(command("a", "observe", 5000));
+        worker.submit(command("b", "observe", 5000));
+        let done = drain(&mut worker);
+        let ids: Vec<&str> = done.iter().map(|(cmd, _)| cmd.command_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert!(done.iter().all(|(_, result)| result.ok));
+    }
+
+    #[test]
+    fn test_hung_command_times_out_and_the_next_one_runs() {
+        let mut worker = CommandWorker::new(Config::from_env(), fake_execute);
+        worker.grace = Duration::ZERO;
+        let started = Instant::now();
+        worker.submit(command("slow", "hang", 50));
+        worker.submit(command("next", "observe", 5000));
+        // Shorter than its wait in the queue, which does not count
+        worker.submit(command("queued", "observe", 40));
+        let done = drain(&mut worker);
+        assert!(started.elapsed() < Duration::from_millis(400), "did not wait for the hung handler");
+
+        let by_id = |id: &str| &done.iter().find(|(cmd, _)| cmd.command_id == id).unwrap().1;
+        let slow = by_id("slow");
+        assert_eq!(slow.error_code.as_deref(), Some("Timeout"));
+        assert_eq!(slow.error.as_deref(), Some("hang timed out after 50ms"));
+        assert!(by_id("next").ok);
+        assert!(by_id("queued").ok);
+    }
+
+    #[test]
+    fn test_timed_out_command_is_cancelled() {
+        let mut worker = CommandWorker::new(Config::from_env(), fake_execute);
+        worker.grace = Duration::ZERO;
+        worker.submit(command("watchdog-overrun", "wait", 50));
+        let done = drain(&mut worker);
+        assert_eq!(done[0].1.error_code.as_deref(), Some("Timeout"));
+
+        // The abandoned handler stops instead of running on unsupervised
+        let give_up = Instant::now() + Duration::from_secs(2);
+        while !STOPPED.lock().unwrap().iter().any(|id| id == "watchdog-overrun") {
+            assert!(Instant::now() < give_up, "timed-out command was not cancelled");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_cancel_running_and_queued_commands() {
+        let mut worker = CommandWorker::new(Config::from_env(), fake_execute);
+        worker.submit(command("watchdog-wait", "wait", 10_000));
+        worker.submit(command("watchdog-queued", "observe", 10_000));
+        worker.submit(command("watchdog-after", "observe", 10_000));
+        assert!(worker.poll().is_empty());
+        assert!(worker.cancel("watchdog-queued"));
+        assert!(!worker.cancel("watchdog-unknown"));
+
+        // The running command registers its token on the worker thread
+        let give_up = Instant::now() + Duration::from_secs(2);
+        while !worker.cancel("watchdog-wait") {
+            assert!(Instant::now() < give_up, "running command never registered");
+            thread::sleep(Duration::from_millis(5));
+        }
+        let started = Instant::now();
+        let done = drain(&mut worker);
+        assert!(started.elapsed() < Duration::from_secs(2), "cancelled command still waited");
+
+        let outcomes: Vec<(&str, Option<&str>)> =
+            done.iter().map(|(cmd, result)| (cmd.command_id.as_str(), result.error_code.as_deref())).collect();
+        assert_eq!(
+            outcomes,
+            [("watchdog-queued", Some("Cancelled")), ("watchdog-wait", Some("Cancelled")), ("watchdog-after", None)]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Webhook sink: POST selected events to user-provided URLs.
+//!
+//! `WEBHOOKS_PATH` names a JSON file listing hooks, each with the event
+//! types it fires on (`"*"` for all) and an optional body template:
+//!
+//! ```json
+//! [
+//!   {"url": "https://hooks.slack.com/services/...", "events": ["idle"],
+//!    "body": {"text": "Away from {{process}} for {{idle_s}}s"}},
+//!   {"url": "http://homeassistant.local:8123/api/webhook/desk", "events": ["idle", "active"],
+//!    "headers": {"X-Source": "desktopai"}}
+//! ]
+//! ```
+//!
+//! `{{name}}` placeholders in template strings are replaced with event
+//! fields (see `template_vars`); a string that is exactly one placeholder
+//! takes the field's JSON value, so `"{{idle_ms}}"` stays a number. Hooks
+//! without a body get `default_body`. Titles pass through the
+//! `REDACT_PATTERN` redaction when it is enabled. Deliveries run on their
+//! own thread and failed ones are logged, not retried.
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::event::WindowEvent;
+use crate::pipeline::RedactionStage;
+
+/// Events waiting for delivery before new ones are dropped.
+const QUEUE_CAPACITY: usize = 256;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+static QUEUE: OnceLock<Sender<WindowEvent>> = OnceLock::new();
+
+/// One configured webhook.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Event types that trigger it; `"*"` matches every type.
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub body: Option<Value>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Webhook {
+    pub fn fires_on(&self, event_type: &str) -> bool {
+        self.events.iter().any(|e| e == "*" || e == event_type)
+    }
+}
+
+/// Read and validate the hooks in `path`.
+pub fn load_webhooks(path: &str) -> Result<Vec<Webhook>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let hooks: Vec<Webhook> = serde_json::from_str(&text).map_err(|e| format!("invalid webhooks in {path}: {e}"))?;
+    check_webhooks(&hooks)?;
+    Ok(hooks)
+}
+
+/// Reject hooks that could never be delivered.
+pub fn check_webhooks(hooks: &[Webhook]) -> Result<(), String> {
+    for hook in hooks {
+        if !hook.url.starts_with("http://") && !hook.url.starts_with("https://") {
+            return Err(format!("webhook url must be http(s): {}", hook.url));
+        }
+        if hook.events.is_empty() {
+            return Err(format!("webhook {} lists no events", hook.url));
+        }
+    }
+    Ok(())
+}
+
+/// Body sent by hooks that do not define one.
+fn default_body() -> Value {
+    serde_json::json!({
+        "type": "{{type}}",
+        "timestamp": "{{timestamp}}",
+        "process": "{{process}}",
+        "title": "{{title}}",
+        "idle_ms": "{{idle_ms}}",
+        "collector": "{{collector_name}}",
+    })
+}
+
+/// Fields available to templates.
+fn template_vars(event: &WindowEvent, redaction: Option<&RedactionStage>) -> BTreeMap<&'static str, Value> {
+    let mut title = event.title.clone();
+    if let Some(stage) = redaction {
+        stage.redact(&mut title);
+    }
+    let process = event.process_exe.rsplit(['\\', '/']).next().unwrap_or_default();
+    BTreeMap::from([
+        ("type", Value::from(event.event_type.as_str())),
+        ("timestamp", Value::from(event.timestamp.as_str())),
+        ("seq", Value::from(event.seq)),
+        ("process", Value::from(process)),
+        ("process_exe", Value::from(event.process_exe.as_str())),
+        ("title", Value::from(title)),
+        ("source", Value::from(event.source.as_str())),
+        ("idle_ms", event.idle_ms.map(Value::from).unwrap_or(Value::Null)),
+        ("idle_s", event.idle_ms.map(|ms| Value::from(ms / 1000)).unwrap_or(Value::Null)),
+        ("collector_id", Value::from(event.collector_id.as_str())),
+        ("collector_name", Value::from(event.collector_name.as_str())),
+        ("tags", Value::from(event.tags.clone())),
+    ])
+}
+
+/// Fill `{{name}}` placeholders throughout a template.
+pub fn render(template: &Value, vars: &BTreeMap<&'static str, Value>) -> Value {
+    match template {
+        Value::String(text) => {
+            let whole = text.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")).map(str::trim);
+            if let Some(value) = whole.and_then(|name| vars.get(name)) {
+                return value.clone();
+            }
+            let mut rendered = text.clone();
+            for (name, value) in vars {
+                let placeholder = format!("{{{{{name}}}}}");
+                if rendered.contains(&placeholder) {
+                    let replacement = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Null => String::new(),
+                        other => other.to_string(),
+                    };
+                    rendered = rendered.replace(&placeholder, &replacement);
+                }
+            }
+            Value::String(rendered)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|item| render(item, vars)).collect()),
+        Value::Object(fields) => {
+            Value::Object(fields.iter().map(|(key, value)| (key.clone(), render(value, vars))).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+fn deliver(hook: &Webhook, body: &Value) {
+    let mut request = ureq::post(&hook.url).timeout(DELIVERY_TIMEOUT);
+    for (name, value) in &hook.headers {
+        request = request.set(name, value);
+    }
+    if let Err(e) = request.send_json(body) {
+        log::warn!("Webhook {} failed: {e}", hook.url);
+    }
+}
+
+fn webhook_worker(rx: Receiver<WindowEvent>, hooks: Vec<Webhook>, redaction: Option<RedactionStage>) {
+    let fallback = default_body();
+    for event in rx {
+        let vars = template_vars(&event, redaction.as_ref());
+        for hook in hooks.iter().filter(|hook| hook.fires_on(&event.event_type)) {
+            deliver(hook, &render(hook.body.as_ref().unwrap_or(&fallback), &vars));
+        }
+    }
+}
+
+pub fn enabled(config: &Config) -> bool {
+    !config.webhooks_path.is_empty()
+}
+
+/// Load the configured hooks and start delivering. Only the first call has
+/// effect; a missing or invalid file disables webhooks with a warning.
+pub fn start(config: &Config) {
+    if !enabled(config) || QUEUE.get().is_some() {
+        return;
+    }
+    let hooks = match load_webhooks(&config.webhooks_path) {
+        Ok(hooks) if !hooks.is_empty() => hooks,
+        Ok(_) => return,
+        Err(e) => {
+            log::warn!("Webhooks disabled: {e}");
+            return;
+        }
+    };
+    log::info!("Webhooks: {} configured from {}", hooks.len(), config.webhooks_path);
+    let redaction = config.redaction_enabled.then(|| RedactionStage::new(&config.redact_pattern)).flatten();
+    let (tx, rx) = bounded(QUEUE_CAPACITY);
+    if QUEUE.set(tx).is_ok() {
+        std::thread::spawn(move || webhook_worker(rx, hooks, redaction));
+    }
+}
+
+/// Queue an event for the webhooks, if any are running. Never blocks: when
+/// deliveries fall behind, the event is dropped.
+pub fn publish(event: &WindowEvent) {
+    if let Some(tx) = QUEUE.get() {
+        if tx.try_send(event.clone()).is_err() {
+            log::debug!("Webhook queue full, dropping {} event", event.event_type);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::build_activity_event;
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let mut event = build_activity_event("idle", 125_000);
+        event.process_exe = "C:\\Windows\\notepad.exe".to_string();
+        event.title = "notes.txt - Notepad".to_string();
+        event.tags = vec!["work".to_string()];
+        let vars = template_vars(&event, None);
+
+        let template = serde_json::json!({
+            "text": "Idle in {{process}} for {{idle_s}}s ({{ title }}){{unknown}}",
+            "ms": "{{idle_ms}}",
+            "tags": ["{{type}}", 1],
+        });
+        let body = render(&template, &vars);
+        assert_eq!(body["text"], "Idle in notepad.exe for 125s ({{ title }}){{unknown}}");
+        assert_eq!(body["ms"], 125_000);
+        assert_eq!(body["tags"], serde_json::json!(["idle", 1]));
+        assert_eq!(render(&serde_json::json!("{{tags}}"), &vars), serde_json::json!(["work"]));
+
+        let body = render(&default_body(), &vars);
+        assert_eq!(body["title"], "notes.txt - Notepad");
+        assert_eq!(body["idle_ms"], 125_000);
+    }
+
+    #[test]
+    fn test_template_titles_are_redacted() {
+        let mut event = build_activity_event("foreground", 0);
+        event.title = "Password: hunter2".to_string();
+        let stage = RedactionStage::new("hunter2").unwrap();
+        let body = render(&serde_json::json!("{{title}}"), &template_vars(&event, Some(&stage)));
+        assert!(!body.as_str().unwrap().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_load_webhooks_validates() {
+        let dir = std::env::temp_dir().join(format!("desktopai-webhooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hooks.json");
+        let path_str = path.to_str().unwrap();
+
+        std::fs::write(&path, r#"[{"url": "https://example.com/hook", "events": ["idle", "active"]}]"#).unwrap();
+        let hooks = load_webhooks(path_str).unwrap();
+        assert!(hooks[0].fires_on("active") && !hooks[0].fires_on("foreground"));
+        assert_eq!(hooks[0].body, None);
+
+        std::fs::write(&path, r#"[{"url": "ftp://example.com", "events": ["*"]}]"#).unwrap();
+        assert!(load_webhooks(path_str).unwrap_err().contains("http(s)"));
+        std::fs::write(&path, r#"[{"url": "https://example.com", "events": []}]"#).unwrap();
+        assert!(load_webhooks(path_str).unwrap_err().contains("no events"));
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load_webhooks(path_str).unwrap_err().contains("invalid webhooks"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
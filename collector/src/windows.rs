@@ -1,9 +1,11 @@
 use chrono::Utc;
 use crossbeam_channel::Sender;
+use std::collections::{BTreeMap, HashMap};
 use std::mem::size_of;
 use std::sync::OnceLock;
+use std::time::Duration;
 use windows::core::PWSTR;
-use windows::Win32::Foundation::{CloseHandle, HWND};
+use windows::Win32::Foundation::{CloseHandle, BOOL, ERROR_INSUFFICIENT_BUFFER, HWND, LPARAM, RECT};
 use windows::Win32::System::SystemInformation::GetTickCount;
 use windows::Win32::System::Threading::{
     OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_FORMAT, PROCESS_QUERY_LIMITED_INFORMATION,
@@ -11,17 +13,22 @@ use windows::Win32::System::Threading::{
 use windows::Win32::UI::Accessibility::{HWINEVENTHOOK};
 use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
 use windows::Win32::UI::WindowsAndMessaging::{
-    GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, EVENT_SYSTEM_FOREGROUND,
+    EnumWindows, GetForegroundWindow, GetWindow, GetWindowRect, GetWindowTextLengthW,
+    GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible, EVENT_SYSTEM_FOREGROUND, GW_OWNER,
     OBJID_WINDOW,
 };
 
 use crate::config::Config;
 use crate::event::{hwnd_to_hex, WindowEvent};
+use crate::inventory::WindowInfo;
+use crate::pipeline::{EnrichContext, EnrichStage, Pipeline, StageOutput};
 use crate::uia::uia_snapshot;
-use crate::screenshot::capture_screenshot;
+use crate::screenshot::{capture_raw_pixels, capture_scaled_pixels, encode_raw_to_base64};
 
 pub static EVENT_SENDER: OnceLock<Sender<WindowEvent>> = OnceLock::new();
 pub static CONFIG: OnceLock<Config> = OnceLock::new();
+static PIPELINE: OnceLock<Pipeline> = OnceLock::new();
+static DEEP_PIPELINE: OnceLock<Pipeline> = OnceLock::new();
 
 pub fn window_title(hwnd: HWND) -> String {
     unsafe {
@@ -38,6 +45,11 @@ pub fn window_title(hwnd: HWND) -> String {
     }
 }
 
+/// Longest path Win32 can return (extended-length `\\?\` paths).
+const MAX_LONG_PATH: usize = 32_768;
+
+/// Full image path of a process. The buffer grows past MAX_PATH for long and
+/// UNC paths, and the `\\?\` verbatim prefix is removed.
 pub fn process_path(pid: u32) -> String {
     unsafe {
         let handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
@@ -48,49 +60,437 @@ pub fn process_path(pid: u32) -> String {
             return String::new();
         }
 
-        let mut buffer = vec![0u16; 260];
-        let mut size: u32 = buffer.len() as u32;
-        let ok = QueryFullProcessImageNameW(
-            handle,
-            PROCESS_NAME_FORMAT(0),
-            PWSTR(buffer.as_mut_ptr()),
-            &mut size as *mut u32,
-        )
-        .is_ok();
+        let mut path = String::new();
+        let mut capacity = 260;
+        loop {
+            let mut buffer = vec![0u16; capacity];
+            let mut size: u32 = buffer.len() as u32;
+            match QueryFullProcessImageNameW(
+                handle,
+                PROCESS_NAME_FORMAT(0),
+                PWSTR(buffer.as_mut_ptr()),
+                &mut size as *mut u32,
+            ) {
+                Ok(()) => {
+                    path = String::from_utf16_lossy(&buffer[..size as usize]);
+                    break;
+                }
+                Err(e) if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() && capacity < MAX_LONG_PATH => {
+                    capacity *= 2;
+                }
+                Err(_) => break,
+            }
+        }
         let _ = CloseHandle(handle);
 
-        if !ok || size == 0 {
-            return String::new();
+        crate::text::strip_verbatim_prefix(&path)
+    }
+}
+
+/// Package family name of a packaged (UWP/MSIX) process, or `None` for
+/// ordinary desktop apps.
+pub fn package_family_name(pid: u32) -> Option<String> {
+    use windows::Win32::Storage::Packaging::Appx::GetPackageFamilyName;
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        // First call sizes the buffer; unpackaged processes fail with
+        // APPMODEL_ERROR_NO_PACKAGE.
+        let mut len: u32 = 0;
+        let _ = GetPackageFamilyName(handle, &mut len, PWSTR::null());
+        let mut name = None;
+        if len > 0 {
+            let mut buffer = vec![0u16; len as usize];
+            if GetPackageFamilyName(handle, &mut len, PWSTR(buffer.as_mut_ptr())).is_ok() {
+                let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+                name = Some(String::from_utf16_lossy(&buffer[..end])).filter(|n| !n.is_empty());
+            }
         }
-        String::from_utf16_lossy(&buffer[..size as usize])
+        let _ = CloseHandle(handle);
+        name
+    }
+}
+
+/// Image path of the process that owns `hwnd`, or empty if unavailable.
+pub fn window_process_exe(hwnd: HWND) -> String {
+    let mut pid: u32 = 0;
+    unsafe {
+        let _ = GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    }
+    if pid == 0 {
+        String::new()
+    } else {
+        process_path(pid)
     }
 }
 
+/// Whether `hwnd` belongs to one of DesktopAI's own processes (see
+/// `policy::is_self_process`).
+pub fn is_self_window(hwnd: HWND, config: &Config) -> bool {
+    hwnd.0 != 0 && crate::policy::is_self_process(&window_process_exe(hwnd), config)
+}
+
+/// Whether DWM hides `hwnd` although it is "visible": windows on other
+/// virtual desktops and suspended UWP apps.
+pub fn is_cloaked(hwnd: HWND) -> bool {
+    use windows::Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED};
+
+    let mut cloaked: u32 = 0;
+    let read = unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut u32 as *mut std::ffi::c_void,
+            std::mem::size_of::<u32>() as u32,
+        )
+    };
+    read.is_ok() && cloaked != 0
+}
+
 pub fn build_event(hwnd: HWND) -> Option<WindowEvent> {
     if hwnd.0 == 0 {
         return None;
     }
-    let title = window_title(hwnd);
-    let mut pid: u32 = 0;
-    unsafe {
-        let _ = GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    if CONFIG.get().is_some_and(|config| is_self_window(hwnd, config)) {
+        return None;
+    }
+    let mut event = empty_event("foreground", hwnd);
+    if let Some(config) = CONFIG.get() {
+        let config = &*crate::power::capture_config(config);
+        let pipeline = PIPELINE.get_or_init(|| Pipeline::from_config(config));
+        let mut ctx = EnrichContext { hwnd: hwnd.0, ..Default::default() };
+        if crate::pacing::try_begin_observation(config) {
+            pipeline.run(&mut ctx, &mut event, config);
+            crate::pacing::record_observation_cost(pipeline.heavy_cost(&event));
+        } else {
+            // Over budget: send the cheap fields now, observe fully later
+            pipeline.run_light(&mut ctx, &mut event, config);
+            crate::pacing::defer_observation(hwnd.0);
+        }
+    }
+    Some(event)
+}
+
+/// Full `observation` of a window whose foreground event was sent without
+/// heavy stages, if it is still the foreground window.
+pub fn build_observation(hwnd: isize, config: &Config) -> Option<WindowEvent> {
+    let hwnd = HWND(hwnd);
+    if unsafe { GetForegroundWindow() } != hwnd {
+        return None;
     }
-    let process_exe = if pid == 0 { String::new() } else { process_path(pid) };
-    let config = CONFIG.get();
-    let uia = config.and_then(|cfg| uia_snapshot(hwnd, cfg));
-    let screenshot_b64 = config.and_then(|cfg| capture_screenshot(cfg, hwnd));
-    Some(WindowEvent {
-        event_type: "foreground".to_string(),
+    let config = &*crate::power::capture_config(config);
+    let pipeline = PIPELINE.get_or_init(|| Pipeline::from_config(config));
+    let mut event = empty_event("observation", hwnd);
+    let mut ctx = EnrichContext { hwnd: hwnd.0, ..Default::default() };
+    pipeline.run(&mut ctx, &mut event, config);
+    crate::pacing::record_observation_cost(pipeline.heavy_cost(&event));
+    Some(event)
+}
+
+/// Heavy observation of the foreground window for idle periods: deep UIA
+/// tree, full-resolution screenshot and detection (see `Config::deep_capture`).
+pub fn build_deep_capture(config: &Config) -> Option<WindowEvent> {
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd.0 == 0 || is_self_window(hwnd, config) {
+        return None;
+    }
+    let deep = crate::power::capture_config(&config.deep_capture()).into_owned();
+    let pipeline = DEEP_PIPELINE.get_or_init(|| Pipeline::from_config(&deep));
+    let mut event = empty_event("deep_capture", hwnd);
+    let mut ctx = EnrichContext { hwnd: hwnd.0, ..Default::default() };
+    pipeline.run(&mut ctx, &mut event, &deep);
+    Some(event)
+}
+
+fn empty_event(event_type: &str, hwnd: HWND) -> WindowEvent {
+    let now = Utc::now();
+    WindowEvent {
+        event_type: event_type.to_string(),
         hwnd: hwnd_to_hex(hwnd),
-        title,
-        process_exe,
-        pid,
-        timestamp: Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        title: String::new(),
+        process_exe: String::new(),
+        package_family: None,
+        icon_png_b64: None,
+        pid: 0,
+        timestamp: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        seq: crate::clock::next_seq(),
+        monotonic_ms: crate::clock::monotonic_ms(),
+        synced_timestamp: crate::clock::synced_timestamp(now),
         source: "collector".to_string(),
+        collector_id: String::new(),
+        collector_name: String::new(),
         idle_ms: None,
-        uia,
-        screenshot_b64,
-    })
+        uia: None,
+        screenshot_b64: None,
+        window_rect: None,
+        theme: None,
+        detections: None,
+        enrich_timings_us: BTreeMap::new(),
+        timings: None,
+        window_count: None,
+        window_changes: Vec::new(),
+        language: None,
+        tags: Vec::new(),
+        subscriptions: Vec::new(),
+    }
+}
+
+/// Windows-backed enrichment stages, looked up by name from `ENRICH_STAGES`.
+pub fn platform_stage(name: &str) -> Option<Box<dyn EnrichStage>> {
+    match name {
+        "title" => Some(Box::new(TitleStage)),
+        "icon" => Some(Box::new(IconStage)),
+        "geometry" => Some(Box::new(GeometryStage)),
+        "theme" => Some(Box::new(ThemeStage)),
+        "uia" => Some(Box::new(UiaStage)),
+        "screenshot" => Some(Box::new(ScreenshotStage)),
+        #[cfg(feature = "detection")]
+        "detection" => Some(Box::new(DetectionStage)),
+        _ => None,
+    }
+}
+
+/// Window title, owning PID and process image path.
+struct TitleStage;
+
+impl EnrichStage for TitleStage {
+    fn name(&self) -> &'static str {
+        "title"
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        let hwnd = HWND(ctx.hwnd);
+        event.title = window_title(hwnd);
+        let mut pid: u32 = 0;
+        unsafe {
+            let _ = GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        }
+        event.pid = pid;
+        event.process_exe = if pid == 0 { String::new() } else { process_path(pid) };
+        event.package_family = if pid == 0 { None } else { package_family_name(pid) };
+    }
+}
+
+/// Small app icon, cached per process image path. Runs after `title`.
+struct IconStage;
+
+impl EnrichStage for IconStage {
+    fn name(&self) -> &'static str {
+        "icon"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.icon_enabled
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        let hwnd = HWND(ctx.hwnd);
+        event.icon_png_b64 = crate::icon::cached_icon(&event.process_exe, || {
+            crate::icon::window_icon_b64(hwnd, &event.process_exe)
+        });
+    }
+}
+
+/// Window bounds in screen coordinates.
+struct GeometryStage;
+
+impl EnrichStage for GeometryStage {
+    fn name(&self) -> &'static str {
+        "geometry"
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.geometry_enabled
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        let mut rect = RECT::default();
+        if unsafe { GetWindowRect(HWND(ctx.hwnd), &mut rect) }.is_ok() {
+            event.window_rect = Some([rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top]);
+        }
+    }
+}
+
+/// Light/dark theme of the window (DWM dark frame, else the system app theme).
+struct ThemeStage;
+
+impl EnrichStage for ThemeStage {
+    fn name(&self) -> &'static str {
+        "theme"
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, _config: &Config) {
+        let system = crate::theme::system_theme();
+        let dwm_dark = crate::theme::window_uses_dark_mode(HWND(ctx.hwnd));
+        event.theme = Some(crate::theme::window_theme_name(dwm_dark, &system));
+    }
+}
+
+struct UiaStage;
+
+impl EnrichStage for UiaStage {
+    fn name(&self) -> &'static str {
+        "uia"
+    }
+
+    fn heavy(&self) -> bool {
+        true
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.uia_enabled
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        (self.capture(ctx.hwnd, config))(ctx, event)
+    }
+
+    fn concurrent(&self) -> bool {
+        true
+    }
+
+    fn capture(&self, hwnd: isize, config: &Config) -> StageOutput {
+        let uia = uia_snapshot(HWND(hwnd), config);
+        Box::new(move |_, event| event.uia = uia)
+    }
+
+    fn timeout(&self, config: &Config) -> Duration {
+        match config.uia_timeout_ms {
+            0 => Duration::MAX,
+            ms => Duration::from_millis(ms),
+        }
+    }
+}
+
+/// Monitor screenshot; keeps the raw frame in the context for detection.
+struct ScreenshotStage;
+
+impl EnrichStage for ScreenshotStage {
+    fn name(&self) -> &'static str {
+        "screenshot"
+    }
+
+    fn heavy(&self) -> bool {
+        true
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.enable_screenshot
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        (self.capture(ctx.hwnd, config))(ctx, event)
+    }
+
+    fn concurrent(&self) -> bool {
+        true
+    }
+
+    fn capture(&self, hwnd: isize, config: &Config) -> StageOutput {
+        let keep_raw = config.detection_enabled && config.enrich_stages.iter().any(|s| s == "detection");
+        // Without detection only the resized frame is needed, so shrink it during capture
+        let captured = if keep_raw {
+            capture_raw_pixels(HWND(hwnd))
+        } else {
+            capture_scaled_pixels(config, HWND(hwnd))
+        };
+        let Some((w, h, pixels)) = captured else {
+            return Box::new(|_, _| {});
+        };
+        let raw = keep_raw.then(|| (w, h, pixels.clone()));
+        let encode_started = std::time::Instant::now();
+        let screenshot_b64 = encode_raw_to_base64(config, w, h, pixels);
+        let encode_time = encode_started.elapsed();
+        Box::new(move |ctx, event| {
+            ctx.raw_pixels = raw;
+            ctx.encode_time = Some(encode_time);
+            event.screenshot_b64 = screenshot_b64;
+        })
+    }
+
+    fn timeout(&self, config: &Config) -> Duration {
+        match config.screenshot_timeout_ms {
+            0 => Duration::MAX,
+            ms => Duration::from_millis(ms),
+        }
+    }
+}
+
+/// UI element detection on the frame captured by the screenshot stage.
+#[cfg(feature = "detection")]
+struct DetectionStage;
+
+#[cfg(feature = "detection")]
+impl EnrichStage for DetectionStage {
+    fn name(&self) -> &'static str {
+        "detection"
+    }
+
+    fn heavy(&self) -> bool {
+        true
+    }
+
+    fn enabled(&self, config: &Config) -> bool {
+        config.detection_enabled
+    }
+
+    fn run(&self, ctx: &mut EnrichContext, event: &mut WindowEvent, config: &Config) {
+        let (Some(detector), Some((w, h, pixels))) = (crate::detection::shared_detector(config), &ctx.raw_pixels) else {
+            return;
+        };
+        let dets = detector.detect(pixels, *w, *h, 3);
+        if !dets.is_empty() {
+            event.detections = serde_json::to_value(&dets).ok();
+        }
+    }
+}
+
+/// Visible top-level windows with a title, front to back. With
+/// `unowned_only`, owned windows (dialogs, tool palettes) are left out,
+/// which gives roughly the Alt+Tab list.
+pub fn list_top_level_windows(unowned_only: bool) -> Option<Vec<WindowInfo>> {
+    struct Walk {
+        unowned_only: bool,
+        handles: Vec<HWND>,
+    }
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let walk = &mut *(lparam.0 as *mut Walk);
+        if IsWindowVisible(hwnd).as_bool() && (!walk.unowned_only || GetWindow(hwnd, GW_OWNER).0 == 0) {
+            walk.handles.push(hwnd);
+        }
+        BOOL(1)
+    }
+
+    let mut walk = Walk { unowned_only, handles: Vec::new() };
+    if let Err(e) = unsafe { EnumWindows(Some(collect), LPARAM(&mut walk as *mut Walk as isize)) } {
+        log::warn!("EnumWindows failed: {e}");
+        return None;
+    }
+
+    let mut exe_cache: HashMap<u32, String> = HashMap::new();
+    let windows = walk
+        .handles
+        .into_iter()
+        .filter_map(|hwnd| {
+            let title = window_title(hwnd);
+            if title.is_empty() {
+                return None;
+            }
+            let mut pid: u32 = 0;
+            unsafe {
+                let _ = GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            }
+            let process_exe = exe_cache.entry(pid).or_insert_with(|| process_path(pid)).clone();
+            Some(WindowInfo { hwnd: hwnd_to_hex(hwnd), title, process_exe, pid })
+        })
+        .collect();
+    Some(windows)
+}
+
+/// Snapshot of visible, unowned, titled top-level windows (roughly the Alt+Tab list).
+pub fn enumerate_top_level_windows() -> Option<Vec<WindowInfo>> {
+    list_top_level_windows(true)
 }
 
 pub fn idle_duration_ms() -> Option<u64> {
@@ -123,6 +523,7 @@ pub unsafe extern "system" fn win_event_hook(
     if id_object != OBJID_WINDOW.0 {
         return;
     }
+    crate::hook::note_event();
     let Some(event) = build_event(hwnd) else {
         return;
     };
@@ -0,0 +1,1093 @@
+//! Declarative workflows: reusable automations the collector runs itself.
+//!
+//! `WORKFLOWS_DIR` (default `workflows` in the data directory) holds one
+//! workflow per `.yaml`, `.yml` or `.json` file, named by the file stem.
+//! `list_workflows` describes them and `run_workflow` runs one step by step
+//! on the collector, without a backend round trip per step, so workflows
+//! also run from the embedded collector (see local.rs) while no backend is
+//! connected.
+//!
+//! ```yaml
+//! description: Export the open document as PDF
+//! inputs:
+//!   - name: file_name
+//!     description: PDF name without extension
+//!   - name: wait_ms
+//!     default: 1500
+//! steps:
+//!   - action: send_keys
+//!     parameters: {keys: "ctrl+p"}
+//!   - wait_ms: 500
+//!   - if: {exists: {name: "Microsoft Print to PDF"}, wait_ms: 3000}
+//!     then:
+//!       - action: click
+//!         parameters: {name: "Print"}
+//!     else:
+//!       - action: select_item
+//!         parameters: {name: "Printer", item: "Microsoft Print to PDF"}
+//!   - action: wait_for_window
+//!     parameters: {title: "Save Print Output As", wait_ms: "{{wait_ms}}"}
+//!   - action: type_text
+//!     parameters: {text: "{{file_name}}.pdf"}
+//!   - action: send_keys
+//!     parameters: {keys: "enter"}
+//!     continue_on_error: true
+//! ```
+//!
+//! A step is a command (`action` with optional `parameters` and
+//! `timeout_ms`), a pause (`wait_ms`) or a conditional (`if` with `then`
+//! and `else` step lists); any step may carry a `name` for the results.
+//! Conditions test that an element matching a `find_elements` selector
+//! `exists`, that a `window` matching a `list_windows` filter is open, or
+//! an `input` (truthy, or `equals` a value); `wait_ms` gives `exists` and
+//! `window` that long to come true and `not` negates.
+//!
+//! `{{name}}` in a string parameter is replaced by that input; a string
+//! that is only a placeholder takes the input's value as is, so numbers
+//! stay numbers. A value given with `run_workflow` always wins; otherwise
+//! an input's `from` sources are tried in order:
+//!
+//! ```yaml
+//! inputs:
+//!   - name: invoice_number
+//!     from: [clipboard, prompt]
+//!     prompt: Invoice number to look up
+//!   - name: note
+//!     from: prompt
+//!     default: ""
+//! ```
+//!
+//! `clipboard` takes the clipboard text when there is any; `prompt` asks
+//! the user, through the prompt window the embedded collector registers
+//! with `set_prompter`, for every such input at once before the first step
+//! runs, prefilled with their defaults. Dismissing the prompt cancels the
+//! run. Clipboard and prompted values are strings. An input left without
+//! a value takes its `default`; inputs with neither a `default` nor a
+//! `from` source are required.
+//!
+//! Every command goes through the same permission and policy checks as one
+//! from the backend. A failing step ends the run unless it sets
+//! `continue_on_error`. Workflows cannot run other workflows.
+//!
+//! The whole run, prompt included, has to fit in the `timeout_ms` of the
+//! `run_workflow` command (5 s unless the caller sets it), so callers give
+//! multi-step workflows a longer one. Each step gets at most the time left
+//! and the run fails with `Timeout` once none is.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::command::{Command, CommandResult};
+
+/// File extensions read as workflows.
+const EXTENSIONS: &[&str] = &["yaml", "yml", "json"];
+
+/// Largest workflow file read.
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+
+/// Longest single pause, or wait for a condition.
+pub const MAX_WAIT_MS: u64 = 60_000;
+
+/// `timeout_ms` of a step command that sets none.
+const DEFAULT_STEP_TIMEOUT_MS: u64 = 5000;
+
+/// How often a waiting condition is checked again.
+const CONDITION_POLL: Duration = Duration::from_millis(250);
+
+/// A named value the caller supplies when running the workflow.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Used when the caller gives no value; without one the input is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Where the value comes from when the caller gives none, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "one_or_many")]
+    pub from: Vec<Source>,
+    /// Label in the prompt window; the description, or else the name, when
+    /// empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prompt: String,
+}
+
+impl Input {
+    /// Whether the caller has to give this input.
+    pub fn required(&self) -> bool {
+        self.default.is_none() && self.from.is_empty()
+    }
+}
+
+/// A place an input's value is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Clipboard,
+    Prompt,
+}
+
+/// `from: prompt` or `from: [clipboard, prompt]`.
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Source>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Source),
+        Many(Vec<Source>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(source) => vec![source],
+        OneOrMany::Many(sources) => sources,
+    })
+}
+
+/// What a conditional step tests.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// `find_elements` selector that must match at least one element.
+    #[serde(default)]
+    pub exists: Option<HashMap<String, serde_json::Value>>,
+    /// `list_windows` filter that must match at least one window.
+    #[serde(default)]
+    pub window: Option<HashMap<String, serde_json::Value>>,
+    /// Input that must be truthy, or equal `equals`.
+    #[serde(default)]
+    pub input: Option<String>,
+    #[serde(default)]
+    pub equals: Option<serde_json::Value>,
+    /// How long `exists` or `window` may take to come true.
+    #[serde(default)]
+    pub wait_ms: u64,
+    #[serde(default)]
+    pub not: bool,
+}
+
+/// One step: a command, a pause or a conditional.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub wait_ms: Option<u64>,
+    #[serde(default, rename = "if")]
+    pub condition: Option<Condition>,
+    #[serde(default)]
+    pub then: Vec<Step>,
+    #[serde(default, rename = "else")]
+    pub otherwise: Vec<Step>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+/// The contents of a workflow file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workflow {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub inputs: Vec<Input>,
+    pub steps: Vec<Step>,
+}
+
+impl Workflow {
+    /// Parse a workflow file's text; `yaml` selects YAML over JSON.
+    pub fn parse(text: &str, yaml: bool) -> Result<Self, String> {
+        let workflow: Self = if yaml {
+            serde_yaml::from_str(text).map_err(|e| e.to_string())?
+        } else {
+            serde_json::from_str(text).map_err(|e| e.to_string())?
+        };
+        workflow.validate()?;
+        Ok(workflow)
+    }
+
+    /// Reject workflows that could only fail once running: malformed steps,
+    /// nested workflows and placeholders naming no input.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names: Vec<&str> = Vec::new();
+        for input in &self.inputs {
+            if input.name.is_empty() || !input.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid input name '{}'", input.name));
+            }
+            if names.contains(&input.name.as_str()) {
+                return Err(format!("input '{}' is declared twice", input.name));
+            }
+            if !input.prompt.is_empty() && !input.from.contains(&Source::Prompt) {
+                return Err(format!("input '{}' has a 'prompt' but is not read 'from: prompt'", input.name));
+            }
+            names.push(&input.name);
+        }
+        if self.steps.is_empty() {
+            return Err("workflow has no steps".to_string());
+        }
+        validate_steps(&self.steps, "", &names)
+    }
+
+    /// Step count, including the steps of every branch.
+    pub fn step_count(&self) -> usize {
+        fn count(steps: &[Step]) -> usize {
+            steps.iter().map(|step| 1 + count(&step.then) + count(&step.otherwise)).sum()
+        }
+        count(&self.steps)
+    }
+
+    /// The inputs to run the workflow `name` with: `given`, then each
+    /// input's `from` sources, then defaults. Fails on a missing input or
+    /// one the workflow does not declare; `None` when the user dismissed
+    /// the prompt.
+    pub fn resolve_inputs(
+        &self,
+        name: &str,
+        given: &serde_json::Map<String, serde_json::Value>,
+        executor: &mut dyn Executor,
+    ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, String> {
+        if let Some(unknown) = given.keys().find(|key| !self.inputs.iter().any(|input| &input.name == *key)) {
+            return Err(format!("unknown input '{unknown}'"));
+        }
+        let mut resolved = serde_json::Map::new();
+        let mut asked = Vec::new();
+        for input in &self.inputs {
+            if let Some(value) = given.get(&input.name) {
+                resolved.insert(input.name.clone(), value.clone());
+                continue;
+            }
+            for source in &input.from {
+                match source {
+                    Source::Clipboard => {
+                        if let Some(text) = executor.clipboard_text().filter(|text| !text.is_empty()) {
+                            resolved.insert(input.name.clone(), serde_json::json!(text));
+                            break;
+                        }
+                    }
+                    Source::Prompt => {
+                        asked.push(input);
+                        break;
+                    }
+                }
+            }
+            if !resolved.contains_key(&input.name) && !asked.contains(&input) {
+                let value = input.default.as_ref().ok_or_else(|| format!("missing input '{}'", input.name))?;
+                resolved.insert(input.name.clone(), value.clone());
+            }
+        }
+        if asked.is_empty() {
+            return Ok(Some(resolved));
+        }
+
+        let request = PromptRequest {
+            workflow: name.to_string(),
+            description: self.description.clone(),
+            fields: asked.iter().map(|input| PromptField::new(input)).collect(),
+            timeout_ms: executor.time_left().as_millis() as u64,
+        };
+        let Some(answers) = executor.prompt(&request)? else {
+            return Ok(None);
+        };
+        for input in asked {
+            let value = match answers.get(&input.name).filter(|answer| !answer.is_empty()) {
+                Some(answer) => serde_json::json!(answer),
+                None => input.default.clone().ok_or_else(|| format!("missing input '{}'", input.name))?,
+            };
+            resolved.insert(input.name.clone(), value);
+        }
+        Ok(Some(resolved))
+    }
+}
+
+fn validate_steps(steps: &[Step], prefix: &str, inputs: &[&str]) -> Result<(), String> {
+    for (index, step) in steps.iter().enumerate() {
+        let label = step_label(prefix, index);
+        let fail = |message: &str| Err(format!("step {label}: {message}"));
+        let kinds = [step.action.is_some(), step.wait_ms.is_some(), step.condition.is_some()];
+        if kinds.iter().filter(|&&kind| kind).count() != 1 {
+            return fail("needs exactly one of 'action', 'wait_ms' or 'if'");
+        }
+        if step.condition.is_none() && !(step.then.is_empty() && step.otherwise.is_empty()) {
+            return fail("'then' and 'else' belong to an 'if' step");
+        }
+        if step.action.is_none() && (!step.parameters.is_empty() || step.timeout_ms.is_some()) {
+            return fail("'parameters' and 'timeout_ms' belong to an 'action' step");
+        }
+        if step.wait_ms.is_some_and(|ms| ms > MAX_WAIT_MS) {
+            return fail(&format!("'wait_ms' is over {MAX_WAIT_MS}"));
+        }
+        if let Some(action) = &step.action {
+            if action.is_empty() {
+                return fail("empty 'action'");
+            }
+            if action == "run_workflow" {
+                return fail("workflows cannot run other workflows");
+            }
+        }
+        if let Some(condition) = &step.condition {
+            let tests = [condition.exists.is_some(), condition.window.is_some(), condition.input.is_some()];
+            if tests.iter().filter(|&&test| test).count() != 1 {
+                return fail("'if' needs exactly one of 'exists', 'window' or 'input'");
+            }
+            if condition.equals.is_some() && condition.input.is_none() {
+                return fail("'equals' belongs to an 'input' condition");
+            }
+            if condition.wait_ms > MAX_WAIT_MS {
+                return fail(&format!("'wait_ms' is over {MAX_WAIT_MS}"));
+            }
+            if let Some(name) = condition.input.as_deref().filter(|name| !inputs.contains(name)) {
+                return fail(&format!("unknown input '{name}'"));
+            }
+        }
+        let mut referenced = Vec::new();
+        for value in step.parameters.values() {
+            placeholders(value, &mut referenced);
+        }
+        if let Some(condition) = &step.condition {
+            for value in condition.exists.iter().chain(&condition.window).flat_map(|selector| selector.values()) {
+                placeholders(value, &mut referenced);
+            }
+        }
+        if let Some(name) = referenced.into_iter().find(|name| !inputs.contains(&name.as_str())) {
+            return fail(&format!("unknown input '{name}'"));
+        }
+        validate_steps(&step.then, &format!("{label}.then."), inputs)?;
+        validate_steps(&step.otherwise, &format!("{label}.else."), inputs)?;
+    }
+    Ok(())
+}
+
+/// 1-based position of a step, e.g. `3.then.1` for the first step of the
+/// third step's `then` branch.
+fn step_label(prefix: &str, index: usize) -> String {
+    format!("{prefix}{}", index + 1)
+}
+
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").expect("valid placeholder pattern"))
+}
+
+/// Names of the inputs `value` refers to.
+fn placeholders(value: &serde_json::Value, names: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(text) => {
+            names.extend(placeholder_pattern().captures_iter(text).map(|c| c[1].to_string()));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| placeholders(item, names)),
+        serde_json::Value::Object(fields) => fields.values().for_each(|field| placeholders(field, names)),
+        _ => {}
+    }
+}
+
+/// `value` with `{{name}}` placeholders replaced by `inputs`.
+pub fn substitute(value: &serde_json::Value, inputs: &serde_json::Map<String, serde_json::Value>) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(text) => {
+            let pattern = placeholder_pattern();
+            if let Some(whole) = pattern.captures(text).filter(|c| c[0].len() == text.len()) {
+                if let Some(input) = inputs.get(&whole[1]) {
+                    return input.clone();
+                }
+            }
+            let replaced = pattern.replace_all(text, |c: &regex::Captures| match inputs.get(&c[1]) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => c[0].to_string(),
+            });
+            serde_json::Value::String(replaced.into_owned())
+        }
+        serde_json::Value::Array(items) => items.iter().map(|item| substitute(item, inputs)).collect(),
+        serde_json::Value::Object(fields) => {
+            fields.iter().map(|(key, field)| (key.clone(), substitute(field, inputs))).collect()
+        }
+        other => other.clone(),
+    }
+}
+
+fn substitute_all(
+    parameters: &HashMap<String, serde_json::Value>,
+    inputs: &serde_json::Map<String, serde_json::Value>,
+) -> HashMap<String, serde_json::Value> {
+    parameters.iter().map(|(key, value)| (key.clone(), substitute(value, inputs))).collect()
+}
+
+/// Whether an input value counts as set: not null, false, 0, "" or empty.
+fn truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        serde_json::Value::String(s) => !s.is_empty(),
+        serde_json::Value::Array(items) => !items.is_empty(),
+        serde_json::Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// Where a workflow's commands go: the collector runs them through
+/// `execute_checked`, tests through a fake.
+pub trait Executor {
+    fn execute(&mut self, cmd: &Command) -> CommandResult;
+    /// Sleep for `duration`; false when the run was cancelled meanwhile.
+    fn pause(&mut self, duration: Duration) -> bool;
+    fn cancelled(&self) -> bool;
+    /// The clipboard text, for inputs read `from: clipboard`.
+    fn clipboard_text(&mut self) -> Option<String>;
+    /// Ask the user for input values (see `prompt_user`).
+    fn prompt(&mut self, request: &PromptRequest) -> Result<Option<HashMap<String, String>>, String>;
+    /// Time left before the run's deadline.
+    fn time_left(&self) -> Duration;
+}
+
+/// Inputs to ask the user for before a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptRequest {
+    pub workflow: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub fields: Vec<PromptField>,
+    /// How long the run can wait for the answer.
+    pub timeout_ms: u64,
+}
+
+/// One value the prompt window asks for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptField {
+    pub name: String,
+    pub label: String,
+    /// The input's default as text, prefilled.
+    pub default: String,
+}
+
+impl PromptField {
+    fn new(input: &Input) -> Self {
+        let label = [&input.prompt, &input.description, &input.name]
+            .into_iter()
+            .find(|text| !text.is_empty())
+            .cloned()
+            .unwrap_or_default();
+        let default = match &input.default {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(text)) => text.clone(),
+            Some(other) => other.to_string(),
+        };
+        Self { name: input.name.clone(), label, default }
+    }
+}
+
+/// Shows a `PromptRequest` and blocks until the user answers, with values
+/// by input name, or dismisses it (`None`).
+pub type Prompter = Arc<dyn Fn(&PromptRequest) -> Option<HashMap<String, String>> + Send + Sync>;
+
+static PROMPTER: Mutex<Option<Prompter>> = Mutex::new(None);
+
+/// Register the window that answers `from: prompt` inputs; `None` removes
+/// it.
+pub fn set_prompter(prompter: Option<Prompter>) {
+    *PROMPTER.lock().unwrap_or_else(|e| e.into_inner()) = prompter;
+}
+
+/// Ask the user through the registered prompter. Fails when none is
+/// registered, e.g. in the standalone collector.
+pub fn prompt_user(request: &PromptRequest) -> Result<Option<HashMap<String, String>>, String> {
+    let prompter = PROMPTER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match prompter {
+        Some(prompter) => Ok(prompter(request)),
+        None => Err("no prompt window to ask for workflow inputs (needs the desktop app)".to_string()),
+    }
+}
+
+/// What one step did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepRecord {
+    /// Position, e.g. `2` or `3.then.1`.
+    pub step: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `action`, `wait` or `if`.
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    pub ok: bool,
+    /// Branch an `if` step took.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub result: HashMap<String, serde_json::Value>,
+    pub elapsed_ms: u64,
+}
+
+/// The outcome of a run: every step taken and, when one ended the run,
+/// why.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Run {
+    pub steps: Vec<StepRecord>,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+}
+
+impl Run {
+    pub fn cancelled(&self) -> bool {
+        self.error_code.as_deref() == Some("Cancelled")
+    }
+}
+
+struct Context<'a> {
+    command_id: &'a str,
+    inputs: &'a serde_json::Map<String, serde_json::Value>,
+    executor: &'a mut dyn Executor,
+    run: Run,
+}
+
+impl Context<'_> {
+    fn command(&self, action: &str, parameters: HashMap<String, serde_json::Value>, timeout_ms: Option<u64>) -> Command {
+        Command {
+            command_id: self.command_id.to_string(),
+            action: action.to_string(),
+            parameters,
+            timeout_ms: timeout_ms.unwrap_or(DEFAULT_STEP_TIMEOUT_MS).min(self.executor.time_left().as_millis() as u64),
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.run.error = Some("workflow was cancelled".to_string());
+        self.run.error_code = Some("Cancelled".to_string());
+    }
+
+    fn time_out(&mut self, step: &str) {
+        self.run.error = Some(format!("ran out of time before step {step}"));
+        self.run.error_code = Some("Timeout".to_string());
+    }
+
+    /// Run `steps`; false once the run has to stop.
+    fn steps(&mut self, steps: &[Step], prefix: &str) -> bool {
+        for (index, step) in steps.iter().enumerate() {
+            if self.executor.cancelled() {
+                self.cancel();
+                return false;
+            }
+            let left = self.executor.time_left();
+            if left.is_zero() || step.wait_ms.is_some_and(|wait_ms| Duration::from_millis(wait_ms) > left) {
+                self.time_out(&step_label(prefix, index));
+                return false;
+            }
+            let started = Instant::now();
+            let mut record = StepRecord {
+                step: step_label(prefix, index),
+                name: step.name.clone(),
+                kind: "action",
+                action: step.action.clone(),
+                ok: true,
+                branch: None,
+                error: None,
+                error_code: None,
+                result: HashMap::new(),
+                elapsed_ms: 0,
+            };
+            let mut nested = None;
+            if let Some(action) = &step.action {
+                let cmd = self.command(action, substitute_all(&step.parameters, self.inputs), step.timeout_ms);
+                let result = self.executor.execute(&cmd);
+                record.ok = result.ok;
+                record.error = result.error;
+                record.error_code = result.error_code;
+                record.result = result.result;
+            } else if let Some(wait_ms) = step.wait_ms {
+                record.kind = "wait";
+                if !self.executor.pause(Duration::from_millis(wait_ms)) {
+                    self.cancel();
+                    return false;
+                }
+            } else if let Some(condition) = &step.condition {
+                record.kind = "if";
+                match self.test(condition) {
+                    Ok(holds) => {
+                        record.branch = Some(if holds { "then" } else { "else" });
+                        nested = Some(if holds { &step.then } else { &step.otherwise });
+                    }
+                    Err(failed) => {
+                        record.ok = false;
+                        record.error = failed.error;
+                        record.error_code = failed.error_code;
+                    }
+                }
+            }
+            record.elapsed_ms = started.elapsed().as_millis() as u64;
+            let branch_prefix = format!("{}.{}.", record.step, record.branch.unwrap_or_default());
+            if !record.ok {
+                if record.error_code.as_deref() == Some("Cancelled") || self.executor.cancelled() {
+                    self.run.steps.push(record);
+                    self.cancel();
+                    return false;
+                }
+                if !step.continue_on_error {
+                    self.run.error = Some(format!("step {} failed: {}", record.step, record.error.as_deref().unwrap_or_default()));
+                    self.run.error_code = record.error_code.clone();
+                    self.run.steps.push(record);
+                    return false;
+                }
+            }
+            self.run.steps.push(record);
+            if let Some(branch) = nested {
+                if !self.steps(branch, &branch_prefix) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Evaluate `condition`, waiting up to its `wait_ms` for it to hold.
+    fn test(&mut self, condition: &Condition) -> Result<bool, Box<CommandResult>> {
+        if let Some(name) = &condition.input {
+            let value = self.inputs.get(name).unwrap_or(&serde_json::Value::Null);
+            let holds = match &condition.equals {
+                Some(expected) => value == expected,
+                None => truthy(value),
+            };
+            return Ok(holds != condition.not);
+        }
+        let (action, selector) = match (&condition.exists, &condition.window) {
+            (Some(selector), _) => ("find_elements", selector),
+            (None, Some(filter)) => ("list_windows", filter),
+            (None, None) => return Ok(!condition.not),
+        };
+        let mut parameters = substitute_all(selector, self.inputs);
+        if action == "find_elements" {
+            parameters.insert("limit".to_string(), serde_json::json!(1));
+        }
+        let cmd = self.command(action, parameters, None);
+        let wait = Duration::from_millis(condition.wait_ms.min(MAX_WAIT_MS)).min(self.executor.time_left());
+        let deadline = Instant::now() + wait;
+        loop {
+            let result = self.executor.execute(&cmd);
+            if !result.ok {
+                return Err(Box::new(result));
+            }
+            let found = result.result.get("count").and_then(|v| v.as_u64()).unwrap_or(0) > 0;
+            if found != condition.not || Instant::now() >= deadline {
+                return Ok(found != condition.not);
+            }
+            if !self.executor.pause(CONDITION_POLL) {
+                return Err(Box::new(CommandResult::cancelled(self.command_id, action)));
+            }
+        }
+    }
+}
+
+/// Run `workflow` with resolved `inputs`, every command under `command_id`.
+pub fn run(
+    workflow: &Workflow,
+    command_id: &str,
+    inputs: &serde_json::Map<String, serde_json::Value>,
+    executor: &mut dyn Executor,
+) -> Run {
+    let mut context = Context { command_id, inputs, executor, run: Run::default() };
+    context.steps(&workflow.steps, "");
+    context.run
+}
+
+/// A workflow file found in the workflows directory.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub path: PathBuf,
+    pub workflow: Result<Workflow, String>,
+}
+
+/// Whether `name` can be a workflow's file stem.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+}
+
+fn read(path: &Path) -> Result<Workflow, String> {
+    let bytes = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if bytes > MAX_FILE_BYTES {
+        return Err(format!("file is over {} KB", MAX_FILE_BYTES / 1024));
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let yaml = path.extension().is_some_and(|ext| ext != "json");
+    Workflow::parse(&text, yaml)
+}
+
+/// Every workflow file in `dir`, by name, parsed or with the reason it
+/// could not be.
+pub fn list(dir: &Path) -> Vec<Entry> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<Entry> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| EXTENSIONS.contains(&ext)))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str().filter(|name| valid_name(name))?.to_string();
+            let workflow = read(&path);
+            Some(Entry { name, path, workflow })
+        })
+        .collect();
+    found.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+    found
+}
+
+/// Load the workflow called `name` from `dir`.
+pub fn load(dir: &Path, name: &str) -> Result<Workflow, String> {
+    if !valid_name(name) {
+        return Err(format!("invalid workflow name '{name}'"));
+    }
+    let path = EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{name}.{ext}")))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("workflow not found: {name}"))?;
+    read(&path).map_err(|e| format!("invalid workflow '{name}': {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every command from a script and records what was run.
+    struct Fake {
+        commands: Vec<Command>,
+        answer: fn(&Command) -> CommandResult,
+        paused_ms: u64,
+        cancel_after: Option<usize>,
+        clipboard: Option<String>,
+        prompted: Vec<PromptRequest>,
+        answers: Option<HashMap<String, String>>,
+        /// The run's time, used up by pauses only.
+        time_ms: u64,
+    }
+
+    impl Fake {
+        fn new(answer: fn(&Command) -> CommandResult) -> Self {
+            Self {
+                commands: Vec::new(),
+                answer,
+                paused_ms: 0,
+                cancel_after: None,
+                clipboard: None,
+                prompted: Vec::new(),
+                answers: None,
+                time_ms: 60_000,
+            }
+        }
+
+        fn actions(&self) -> Vec<&str> {
+            self.commands.iter().map(|cmd| cmd.action.as_str()).collect()
+        }
+    }
+
+    impl Executor for Fake {
+        fn execute(&mut self, cmd: &Command) -> CommandResult {
+            self.commands.push(cmd.clone());
+            (self.answer)(cmd)
+        }
+
+        fn pause(&mut self, duration: Duration) -> bool {
+            self.paused_ms += duration.as_millis() as u64;
+            true
+        }
+
+        fn cancelled(&self) -> bool {
+            self.cancel_after.is_some_and(|n| self.commands.len() >= n)
+        }
+
+        fn clipboard_text(&mut self) -> Option<String> {
+            self.clipboard.clone()
+        }
+
+        fn prompt(&mut self, request: &PromptRequest) -> Result<Option<HashMap<String, String>>, String> {
+            self.prompted.push(request.clone());
+            Ok(self.answers.clone())
+        }
+
+        fn time_left(&self) -> Duration {
+            Duration::from_millis(self.time_ms.saturating_sub(self.paused_ms))
+        }
+    }
+
+    fn answer(cmd: &Command) -> CommandResult {
+        let mut result = HashMap::new();
+        match cmd.action.as_str() {
+            "find_elements" => {
+                let found = cmd.parameters.get("name") == Some(&serde_json::json!("Print"));
+                result.insert("count".to_string(), serde_json::json!(found as u64));
+            }
+            "list_windows" => {
+                result.insert("count".to_string(), serde_json::json!(0));
+            }
+            "click" if cmd.parameters.get("name") == Some(&serde_json::json!("Missing")) => {
+                return CommandResult::failure(&cmd.command_id, "element not found");
+            }
+            _ => {}
+        }
+        CommandResult::success(&cmd.command_id, result)
+    }
+
+    const EXAMPLE: &str = r#"
+description: Print the current document
+inputs:
+  - name: copies
+    default: 2
+  - name: file_name
+  - name: duplex
+    default: false
+steps:
+  - action: send_keys
+    parameters: {keys: "ctrl+p"}
+  - wait_ms: 500
+  - name: printer ready
+    if: {exists: {name: "Print"}, wait_ms: 1000}
+    then:
+      - action: set_range_value
+        parameters: {name: "Copies", value: "{{copies}}"}
+      - if: {input: duplex}
+        then:
+          - action: click
+            parameters: {name: "Two-sided"}
+    else:
+      - action: click
+        parameters: {name: "Cancel"}
+  - if: {window: {title: "Save As"}, not: true}
+    then:
+      - action: type_text
+        parameters: {text: "{{file_name}} ({{copies}} copies).pdf"}
+"#;
+
+    fn inputs(json: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        json.as_object().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_parse_and_resolve_inputs() {
+        let workflow = Workflow::parse(EXAMPLE, true).unwrap();
+        assert_eq!(workflow.description, "Print the current document");
+        assert_eq!(workflow.step_count(), 9);
+
+        let mut fake = Fake::new(answer);
+        assert_eq!(
+            workflow.resolve_inputs("print", &inputs(serde_json::json!({})), &mut fake),
+            Err("missing input 'file_name'".to_string())
+        );
+        assert_eq!(
+            workflow.resolve_inputs("print", &inputs(serde_json::json!({"file_name": "a", "colour": true})), &mut fake),
+            Err("unknown input 'colour'".to_string())
+        );
+        let resolved = workflow.resolve_inputs("print", &inputs(serde_json::json!({"file_name": "report"})), &mut fake).unwrap();
+        assert_eq!(resolved.unwrap()["copies"], serde_json::json!(2));
+        assert!(fake.prompted.is_empty());
+
+        let json = r#"{"steps": [{"action": "observe"}]}"#;
+        assert_eq!(Workflow::parse(json, false).unwrap().steps[0].action.as_deref(), Some("observe"));
+    }
+
+    #[test]
+    fn test_inputs_from_clipboard_and_prompt() {
+        let text = r#"
+description: Look up an invoice
+inputs:
+  - name: invoice_number
+    from: [clipboard, prompt]
+    prompt: Invoice number
+  - name: note
+    description: Note to add
+    from: prompt
+    default: 3
+  - name: customer
+    from: clipboard
+    default: unknown
+steps:
+  - action: type_text
+    parameters: {text: "{{invoice_number}}: {{note}} ({{customer}})"}
+"#;
+        let workflow = Workflow::parse(text, true).unwrap();
+        assert!(workflow.inputs.iter().all(|input| !input.required()));
+
+        // The clipboard answers both clipboard inputs; only `note` is asked for
+        let mut fake = Fake::new(answer);
+        fake.clipboard = Some("INV-7".to_string());
+        fake.answers = Some(HashMap::from([("note".to_string(), "paid".to_string())]));
+        let resolved = workflow.resolve_inputs("invoice", &serde_json::Map::new(), &mut fake).unwrap().unwrap();
+        assert_eq!(resolved["invoice_number"], serde_json::json!("INV-7"));
+        assert_eq!(resolved["note"], serde_json::json!("paid"));
+        assert_eq!(resolved["customer"], serde_json::json!("INV-7"));
+        let request = &fake.prompted[0];
+        assert_eq!((request.workflow.as_str(), request.description.as_str()), ("invoice", "Look up an invoice"));
+        assert_eq!(request.timeout_ms, 60_000);
+        assert_eq!(request.fields, vec![PromptField { name: "note".to_string(), label: "Note to add".to_string(), default: "3".to_string() }]);
+
+        // An empty clipboard falls through to the prompt; blank answers take the default
+        let mut fake = Fake::new(answer);
+        fake.clipboard = Some(String::new());
+        fake.answers = Some(HashMap::from([("invoice_number".to_string(), "INV-9".to_string())]));
+        let given = inputs(serde_json::json!({"customer": "Acme"}));
+        let resolved = workflow.resolve_inputs("invoice", &given, &mut fake).unwrap().unwrap();
+        assert_eq!(resolved["invoice_number"], serde_json::json!("INV-9"));
+        assert_eq!(resolved["note"], serde_json::json!(3));
+        assert_eq!(resolved["customer"], serde_json::json!("Acme"));
+        let labels: Vec<&str> = fake.prompted[0].fields.iter().map(|field| field.label.as_str()).collect();
+        assert_eq!(labels, vec!["Invoice number", "Note to add"]);
+
+        // A blank required answer fails; a dismissed prompt gives no inputs
+        fake.answers = Some(HashMap::new());
+        assert_eq!(
+            workflow.resolve_inputs("invoice", &given, &mut fake),
+            Err("missing input 'invoice_number'".to_string())
+        );
+        fake.answers = None;
+        assert_eq!(workflow.resolve_inputs("invoice", &given, &mut fake), Ok(None));
+        let given = inputs(serde_json::json!({"invoice_number": "INV-1", "note": ""}));
+        assert_eq!(fake.prompted.len(), 3);
+        assert!(workflow.resolve_inputs("invoice", &given, &mut fake).unwrap().is_some());
+        assert_eq!(fake.prompted.len(), 3);
+
+        let error = Workflow::parse("inputs: [{name: a, prompt: A?}]\nsteps: [{wait_ms: 1}]", true).unwrap_err();
+        assert!(error.contains("not read 'from: prompt'"));
+        assert!(Workflow::parse("inputs: [{name: a, from: keyboard}]\nsteps: [{wait_ms: 1}]", true).is_err());
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let error = |text: &str| Workflow::parse(text, true).unwrap_err();
+        assert_eq!(error("steps: []"), "workflow has no steps");
+        assert!(error("steps: [{action: click, wait_ms: 5}]").contains("exactly one of"));
+        assert!(error("steps: [{wait_ms: 120000}]").contains("over 60000"));
+        assert!(error("steps: [{action: run_workflow}]").contains("cannot run other workflows"));
+        assert!(error("steps: [{action: click, then: [{wait_ms: 1}]}]").contains("'then' and 'else'"));
+        assert!(error("steps: [{if: {exists: {name: a}, input: b}}]").contains("exactly one of 'exists'"));
+        assert!(error("steps: [{action: click, parameters: {name: '{{who}}'}}]").contains("unknown input 'who'"));
+        assert_eq!(
+            error("steps: [{if: {input: x}, then: [{action: click, parameters: {name: '{{x}}'}}]}]"),
+            "step 1: unknown input 'x'"
+        );
+        assert!(error("inputs: [{name: a}, {name: a}]\nsteps: [{wait_ms: 1}]").contains("declared twice"));
+        assert!(error("steps: [{action: click, retries: 3}]").contains("unknown field"));
+        assert_eq!(
+            error("inputs: [{name: x}]\nsteps: [{wait_ms: 1}, {if: {input: x}, else: [{action: ''}]}]"),
+            "step 2.else.1: empty 'action'"
+        );
+    }
+
+    #[test]
+    fn test_substitute() {
+        let values = inputs(serde_json::json!({"n": 3, "who": "Ada", "on": true}));
+        assert_eq!(substitute(&serde_json::json!("{{n}}"), &values), serde_json::json!(3));
+        assert_eq!(substitute(&serde_json::json!("{{ who }}"), &values), serde_json::json!("Ada"));
+        assert_eq!(
+            substitute(&serde_json::json!({"text": "{{who}} x{{n}}", "list": ["{{on}}", 1]}), &values),
+            serde_json::json!({"text": "Ada x3", "list": [true, 1]})
+        );
+        assert_eq!(substitute(&serde_json::json!("{{n}}{{n}}"), &values), serde_json::json!("33"));
+    }
+
+    #[test]
+    fn test_run_follows_branches() {
+        let workflow = Workflow::parse(EXAMPLE, true).unwrap();
+        let mut fake = Fake::new(answer);
+        let values = workflow.resolve_inputs("print", &inputs(serde_json::json!({"file_name": "report"})), &mut fake).unwrap().unwrap();
+        let run = run(&workflow, "cmd-1", &values, &mut fake);
+
+        assert_eq!(run.error, None);
+        assert_eq!(
+            fake.actions(),
+            vec!["send_keys", "find_elements", "set_range_value", "list_windows", "type_text"]
+        );
+        assert!(fake.commands.iter().all(|cmd| cmd.command_id == "cmd-1"));
+        assert_eq!(fake.commands[1].parameters["limit"], serde_json::json!(1));
+        assert_eq!(fake.commands[2].parameters["value"], serde_json::json!(2));
+        assert_eq!(fake.commands[4].parameters["text"], serde_json::json!("report (2 copies).pdf"));
+        assert_eq!(fake.paused_ms, 500);
+        let steps: Vec<(&str, Option<&str>)> = run.steps.iter().map(|s| (s.step.as_str(), s.branch)).collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("1", None),
+                ("2", None),
+                ("3", Some("then")),
+                ("3.then.1", None),
+                ("3.then.2", Some("else")),
+                ("4", Some("then")),
+                ("4.then.1", None)
+            ]
+        );
+        assert_eq!(run.steps[2].name.as_deref(), Some("printer ready"));
+    }
+
+    #[test]
+    fn test_run_stops_on_failure_and_cancel() {
+        let text = "steps:\n  - action: click\n    parameters: {name: Missing}\n    continue_on_error: true\n  - action: click\n    parameters: {name: Missing}\n  - action: observe";
+        let workflow = Workflow::parse(text, true).unwrap();
+        let mut fake = Fake::new(answer);
+        let outcome = run(&workflow, "cmd-2", &serde_json::Map::new(), &mut fake);
+        assert_eq!(fake.actions(), vec!["click", "click"]);
+        assert_eq!(outcome.error.as_deref(), Some("step 2 failed: element not found"));
+        assert!(outcome.steps.iter().all(|step| !step.ok));
+
+        let workflow = Workflow::parse("steps: [{action: observe}, {action: observe}, {action: observe}]", true).unwrap();
+        let mut fake = Fake::new(answer);
+        fake.cancel_after = Some(1);
+        let outcome = run(&workflow, "cmd-3", &serde_json::Map::new(), &mut fake);
+        assert!(outcome.cancelled());
+        assert_eq!(outcome.steps.len(), 1);
+    }
+
+    #[test]
+    fn test_run_stays_within_its_time() {
+        let text = "steps:\n  - wait_ms: 500\n  - action: observe\n    timeout_ms: 3000\n  - wait_ms: 200\n  - action: observe";
+        let workflow = Workflow::parse(text, true).unwrap();
+        let mut fake = Fake::new(answer);
+        fake.time_ms = 600;
+        let outcome = run(&workflow, "cmd-4", &serde_json::Map::new(), &mut fake);
+        // The step gets only what is left; the pause that no longer fits ends the run
+        assert_eq!(fake.commands[0].timeout_ms, 100);
+        assert_eq!(outcome.error.as_deref(), Some("ran out of time before step 3"));
+        assert_eq!(outcome.error_code.as_deref(), Some("Timeout"));
+        assert_eq!(outcome.steps.len(), 2);
+
+        fake.time_ms = 0;
+        let outcome = run(&workflow, "cmd-5", &serde_json::Map::new(), &mut fake);
+        assert_eq!(outcome.error.as_deref(), Some("ran out of time before step 1"));
+        assert!(outcome.steps.is_empty());
+    }
+
+    #[test]
+    fn test_list_and_load() {
+        let dir = std::env::temp_dir().join(format!("desktopai_workflows_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("print.yaml"), EXAMPLE).unwrap();
+        std::fs::write(dir.join("broken.json"), "{\"steps\": 1}").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a workflow").unwrap();
+
+        let listed: Vec<(String, bool)> = list(&dir).into_iter().map(|e| (e.name, e.workflow.is_ok())).collect();
+        assert_eq!(listed, vec![("broken".to_string(), false), ("print".to_string(), true)]);
+        assert!(load(&dir, "print").is_ok());
+        assert!(load(&dir, "broken").unwrap_err().starts_with("invalid workflow 'broken'"));
+        assert_eq!(load(&dir, "notes").unwrap_err(), "workflow not found: notes");
+        assert!(load(&dir, "../print").unwrap_err().starts_with("invalid workflow name"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
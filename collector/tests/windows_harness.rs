@@ -0,0 +1,367 @@
+//! Command bridge integration tests against a real window: each test spawns
+//! `desktopai-test-window` (src/bin/test_window.rs), drives it through
+//! `execute_command` and checks the effect from the state lines the window
+//! prints. They inject real mouse and keyboard input, so they take turns and
+//! need an interactive desktop, as on the Windows CI runners.
+
+#![cfg(windows)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use desktopai_collector::command::{execute_command, Command, CommandResult};
+use desktopai_collector::Config;
+use serde_json::json;
+
+// Control ids of the test window, which UIA reports as automation ids
+const SUBMIT: &str = "41001";
+const NAME: &str = "41002";
+const TOGGLE: &str = "41003";
+const LIST: &str = "41004";
+const COMBO: &str = "41005";
+const DIALOG: &str = "41006";
+const LOG: &str = "41007";
+
+const STATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Held by the running test: only one may use the mouse and keyboard.
+static DESKTOP: Mutex<()> = Mutex::new(());
+
+struct Harness {
+    child: Child,
+    lines: Receiver<String>,
+    hwnd: String,
+    config: Config,
+    _desktop: MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    /// Start a test window titled after the test and bring it to the front.
+    fn spawn(test: &str) -> Self {
+        let desktop = DESKTOP.lock().unwrap_or_else(|e| e.into_inner());
+        let title = format!("DesktopAI Harness {} {test}", std::process::id());
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_desktopai-test-window"))
+            .arg(&title)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("spawn desktopai-test-window");
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let first = lines.recv_timeout(Duration::from_secs(10)).expect("test window did not start");
+        let hwnd = first.strip_prefix("hwnd=").expect("first line is the window handle").to_string();
+
+        let mut config = Config::from_env();
+        config.enable_screenshot = false;
+        let harness = Self { child, lines, hwnd, config, _desktop: desktop };
+        let focused = harness.run("focus_window", json!({ "title": title }));
+        assert!(focused.ok, "focus_window failed: {:?}", focused.error);
+        harness
+    }
+
+    fn run(&self, action: &str, parameters: serde_json::Value) -> CommandResult {
+        let parameters: HashMap<String, serde_json::Value> = serde_json::from_value(parameters).unwrap();
+        let cmd = Command {
+            command_id: format!("harness-{action}"),
+            action: action.to_string(),
+            parameters,
+            timeout_ms: 5000,
+            verify_diff: false,
+            capture_before: false,
+            include_screenshot: None,
+            include_uia: None,
+            uia_depth: None,
+            traceparent: None,
+        };
+        execute_command(&cmd, &self.config)
+    }
+
+    /// Run an action that must succeed.
+    fn ok(&self, action: &str, parameters: serde_json::Value) -> CommandResult {
+        let result = self.run(action, parameters);
+        assert!(result.ok, "{action} failed: {:?}", result.error);
+        result
+    }
+
+    /// Wait until the window reports a `key` value accepted by `accept`.
+    fn expect_state(&self, key: &str, accept: impl Fn(&str) -> bool) -> String {
+        let deadline = Instant::now() + STATE_TIMEOUT;
+        let mut seen = Vec::new();
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            let Ok(line) = self.lines.recv_timeout(left) else {
+                break;
+            };
+            if let Some(value) = line.strip_prefix(key).and_then(|rest| rest.strip_prefix('=')) {
+                if accept(value) {
+                    return value.to_string();
+                }
+            }
+            seen.push(line);
+        }
+        panic!("window never reported an expected {key}; saw {seen:?}");
+    }
+
+    fn expect(&self, key: &str, value: &str) {
+        self.expect_state(key, |v| v == value);
+    }
+
+    /// One element of the test window by automation id, via find_elements.
+    fn element(&self, automation_id: &str) -> serde_json::Value {
+        let found = self.ok("find_elements", json!({ "automation_id": automation_id, "hwnd": self.hwnd }));
+        assert_eq!(found.result["count"], 1, "automation id {automation_id} should be unique");
+        found.result["elements"][0].clone()
+    }
+
+    fn center(&self, automation_id: &str) -> (i64, i64) {
+        let rect = &self.element(automation_id)["rect"];
+        let [x, y, w, h] = [0, 1, 2, 3].map(|i| rect[i].as_i64().expect("element has a rect"));
+        (x + w / 2, y + h / 2)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn find_elements_resolves_controls_by_automation_id() {
+    let harness = Harness::spawn("find");
+    let submit = harness.element(SUBMIT);
+    assert_eq!(submit["name"], "Submit");
+    assert_eq!(submit["automation_id"], SUBMIT);
+    assert_eq!(submit["enabled"], true);
+
+    let by_name = harness.ok("find_elements", json!({ "name": "Open dialog", "hwnd": harness.hwnd }));
+    assert_eq!(by_name.result["elements"][0]["automation_id"], DIALOG);
+
+    let missing = harness.ok("find_elements", json!({ "automation_id": "49999", "hwnd": harness.hwnd }));
+    assert_eq!(missing.result["count"], 0);
+}
+
+#[test]
+fn click_invokes_and_injects_mouse_input() {
+    let harness = Harness::spawn("click");
+    let invoked = harness.ok("click", json!({ "automation_id": SUBMIT, "hwnd": harness.hwnd, "index": 0 }));
+    assert_eq!(invoked.result["method"], "invoke");
+    assert_eq!(invoked.result["element"]["automation_id"], SUBMIT);
+    assert_eq!(invoked.result["element"]["rect"], harness.element(SUBMIT)["rect"]);
+    assert_eq!(invoked.result["element"]["pid"], harness.child.id());
+    assert!(invoked.result["element"]["process_exe"].as_str().unwrap().ends_with("desktopai-test-window.exe"));
+    harness.expect("clicks", "1");
+
+    let (x, y) = harness.center(SUBMIT);
+    harness.ok("click", json!({ "x": x, "y": y }));
+    harness.expect("clicks", "2");
+
+    let (x, y) = harness.center(TOGGLE);
+    harness.ok("click", json!({ "x": x, "y": y }));
+    harness.expect("toggle", "1");
+}
+
+#[test]
+fn type_text_reaches_the_focused_edit() {
+    let harness = Harness::spawn("type");
+    let (x, y) = harness.center(NAME);
+    harness.ok("click", json!({ "x": x, "y": y }));
+    let typed = harness.ok("type_text", json!({ "text": "hello harness" }));
+    assert_eq!(typed.result["method"], "send_input");
+    assert_eq!(typed.result["element"]["automation_id"], NAME, "the focused edit");
+    harness.expect("name", "hello harness");
+
+    let replaced = harness.ok("type_text", json!({ "text": "replaced", "clear": true, "delay_ms": 5 }));
+    assert_eq!(replaced.result["cleared"], true);
+    harness.expect("name", "replaced");
+
+    let set = harness.ok("type_text", json!({ "text": "set by pattern", "automation_id": NAME }));
+    assert_eq!(set.result["method"], "value_pattern");
+    assert_eq!(set.result["element"]["enabled"], true);
+    harness.expect("name", "set by pattern");
+}
+
+#[test]
+fn select_item_and_expand_collapse_use_patterns() {
+    let harness = Harness::spawn("patterns");
+    harness.ok("select_item", json!({ "automation_id": LIST, "item": "beta" }));
+    harness.expect("list", "Beta");
+
+    let expanded = harness.ok("expand", json!({ "automation_id": COMBO }));
+    assert_eq!(expanded.result["expand_collapse_state"], "expanded");
+    harness.expect("combo_open", "1");
+    harness.ok("collapse", json!({ "automation_id": COMBO }));
+    harness.expect("combo_open", "0");
+
+    harness.ok("select_item", json!({ "automation_id": COMBO, "item": "Green" }));
+    harness.expect("combo", "Green");
+}
+
+#[test]
+fn modal_dialog_opens_and_closes() {
+    let harness = Harness::spawn("dialog");
+    // A coordinate click: invoking a button that opens a modal blocks until it closes
+    let (x, y) = harness.center(DIALOG);
+    harness.ok("click", json!({ "x": x, "y": y }));
+    harness.expect("dialog", "open");
+
+    let closed = harness.ok("close_window", json!({ "title": "Harness Dialog" }));
+    assert_eq!(closed.result["state"], "closed");
+    harness.expect("dialog", "closed");
+}
+
+#[test]
+fn scroll_wheel_moves_the_control_under_the_point() {
+    let harness = Harness::spawn("scroll");
+    let (x, y) = harness.center(LOG);
+    harness.ok("scroll", json!({ "direction": "down", "amount": 3, "x": x, "y": y }));
+    harness.expect_state("log_line", |line| line.parse::<u32>().is_ok_and(|n| n > 0));
+}
+
+#[test]
+fn if_exists_runs_the_matching_branch() {
+    let harness = Harness::spawn("if_exists");
+    let click_submit = json!({ "action": "click", "parameters": { "automation_id": SUBMIT, "hwnd": harness.hwnd } });
+    let present = harness.ok(
+        "if_exists",
+        json!({ "automation_id": SUBMIT, "hwnd": harness.hwnd, "then": click_submit, "else": { "action": "bogus" } }),
+    );
+    assert_eq!(present.result["exists"], true);
+    assert_eq!(present.result["branch"], "then");
+    assert_eq!(present.result["method"], "invoke");
+    harness.expect("clicks", "1");
+
+    let absent = harness.ok("if_exists", json!({ "automation_id": "49999", "hwnd": harness.hwnd, "then": click_submit }));
+    assert_eq!(absent.result["exists"], false);
+    assert_eq!(absent.result["branch"], "else");
+}
+
+#[test]
+fn element_labels_number_controls_for_click_by_label() {
+    let harness = Harness::spawn("labels");
+    let shown = harness.ok("show_element_labels", json!({ "hwnd": harness.hwnd }));
+    let labels = shown.result["labels"].as_array().unwrap();
+    let submit = labels.iter().find(|label| label["automation_id"] == SUBMIT).expect("Submit is labelled");
+
+    harness.ok("click", json!({ "label": submit["number"] }));
+    harness.expect("clicks", "1");
+
+    let hidden = harness.ok("hide_element_labels", json!({}));
+    assert_eq!(hidden.result["hidden"], true);
+    let stale = harness.run("click", json!({ "label": submit["number"] }));
+    assert!(stale.error.unwrap().contains("is shown"));
+}
+
+#[test]
+fn element_actions_search_only_the_named_window() {
+    let harness = Harness::spawn("scope");
+    let scoped = harness.ok("click", json!({ "automation_id": SUBMIT, "hwnd": harness.hwnd }));
+    assert_eq!(scoped.result["method"], "invoke");
+    harness.expect("clicks", "1");
+
+    let elsewhere = harness.run("click", json!({ "automation_id": SUBMIT, "window_title": "No Such Window 41001" }));
+    assert!(elsewhere.error.unwrap().contains("window not found"));
+    let hovered = harness.ok("hover", json!({ "automation_id": NAME, "hwnd": harness.hwnd }));
+    assert!(hovered.result.contains_key("x"));
+}
+
+#[test]
+fn control_type_and_index_target_unlabeled_controls() {
+    let harness = Harness::spawn("control_type");
+    // Submit is the first button, the name field the first edit
+    let clicked = harness.ok("click", json!({ "control_type": "Button", "index": 0, "hwnd": harness.hwnd }));
+    assert_eq!(clicked.result["clicked"], "Button");
+    harness.expect("clicks", "1");
+
+    let typed = harness.ok("type_text", json!({ "text": "by position", "control_type": "edit", "hwnd": harness.hwnd }));
+    assert_eq!(typed.result["method"], "value_pattern");
+    harness.expect("name", "by position");
+
+    let read = harness.ok("get_element_text", json!({ "control_type": "edit", "index": 0, "hwnd": harness.hwnd }));
+    assert_eq!(read.result["text"], "by position");
+    assert_eq!(read.result["source"], "value");
+    assert_eq!(read.result["automation_id"], NAME);
+
+    let missing = harness.run("get_element_text", json!({ "control_type": "edit", "index": 9, "hwnd": harness.hwnd }));
+    assert!(missing.error.unwrap().contains("out of range"));
+}
+
+#[test]
+fn window_thumbnail_previews_the_window() {
+    let mut harness = Harness::spawn("thumbnail");
+    harness.config.enable_screenshot = true;
+    let thumbnail = harness.ok("get_window_thumbnail", json!({ "hwnd": harness.hwnd, "max_width": 120 }));
+    let window = &thumbnail.result["windows"][0];
+    assert_eq!(window["minimized"], false);
+    assert!(window["width"].as_u64().unwrap() <= 120);
+    assert!(!window["image_b64"].as_str().unwrap().is_empty());
+}
+
+#[test]
+fn wait_for_window_sees_windows_open_and_close() {
+    let harness = Harness::spawn("wait_for_window");
+    let title = format!("DesktopAI Harness {} wait_for_window", std::process::id());
+    let open = harness.ok("wait_for_window", json!({ "title": title }));
+    assert_eq!(open.result["hwnd"], harness.hwnd);
+
+    let still_open = harness.run("wait_for_window", json!({ "hwnd": harness.hwnd, "state": "closed" }));
+    assert_eq!(still_open.error_code.as_deref(), Some("Timeout"));
+    harness.ok("close_window", json!({ "hwnd": harness.hwnd }));
+    let closed = harness.ok("wait_for_window", json!({ "title": title, "state": "closed" }));
+    assert_eq!(closed.result["closed"], true);
+}
+
+#[test]
+fn wait_for_ui_idle_returns_once_the_window_is_still() {
+    let mut harness = Harness::spawn("wait_for_ui_idle");
+    harness.config.enable_screenshot = true;
+    let idle = harness.ok("wait_for_ui_idle", json!({ "quiet_ms": 300 }));
+    assert_eq!(idle.result["hwnd"], harness.hwnd);
+    assert_eq!(idle.result["screen_checked"], true);
+    assert!(idle.result["waited_ms"].as_u64().unwrap() >= 300);
+}
+
+#[test]
+fn focus_window_by_pid_and_hwnd() {
+    let harness = Harness::spawn("focus_window_by_pid");
+    let by_pid = harness.ok("focus_window", json!({ "pid": harness.child.id() }));
+    assert_eq!(by_pid.result["hwnd"], harness.hwnd);
+    assert_eq!(by_pid.result["foreground"], true);
+    let by_hwnd = harness.ok("focus_window", json!({ "hwnd": harness.hwnd }));
+    assert_eq!(by_hwnd.result["pid"], harness.child.id());
+
+    // The test process itself owns no window
+    let missing = harness.run("focus_window", json!({ "pid": std::process::id() }));
+    assert!(missing.error.unwrap().contains("window not found matching: pid"));
+}
+
+#[test]
+fn list_windows_and_ambiguous_focus() {
+    let first = Harness::spawn("list_windows_first");
+    let second = Harness::spawn("list_windows_second");
+    let listed = first.ok("list_windows", json!({ "pid": first.child.id() }));
+    assert_eq!(listed.result["count"], 1);
+    assert_eq!(listed.result["windows"][0]["hwnd"], first.hwnd);
+
+    let everything = first.ok("list_windows", json!({}));
+    let hwnds: Vec<&str> = everything.result["windows"].as_array().unwrap().iter().filter_map(|w| w["hwnd"].as_str()).collect();
+    assert!(hwnds.contains(&first.hwnd.as_str()) && hwnds.contains(&second.hwnd.as_str()));
+
+    // Both windows match; the best is focused and both are offered
+    let prefix = format!("DesktopAI Harness {} list_windows", std::process::id());
+    let focused = first.ok("focus_window", json!({ "title": prefix }));
+    let candidates = focused.result["candidates"].as_array().expect("ambiguous match lists candidates");
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0]["hwnd"], focused.result["hwnd"]);
+    assert!(!first.ok("focus_window", json!({ "hwnd": second.hwnd })).result.contains_key("candidates"));
+}
@@ -0,0 +1,92 @@
+#!/usr/bin/env python3
+"""Create an encrypted DesktopAI diagnostic bundle to attach to a bug report.
+
+Asks about each data category in turn; only the ones answered "yes" are
+bundled. The collector writes the AES-256 encrypted zip and this prints
+where. Share the password with the maintainers separately from the file.
+"""
+from __future__ import annotations
+
+import argparse
+import getpass
+import json
+import sys
+from urllib import error, request
+
+CATEGORIES = [
+    ("events", "the last 50 desktop events (window titles and UI text, redacted; no screenshots)"),
+    ("logs", "the last 1000 collector log lines (redacted)"),
+    ("config", "the collector settings you changed (webhooks are never included)"),
+    ("metrics", "hook health, power profile, usage counters and event counts"),
+    ("screenshots", "the screenshots still held in memory (up to 5, unredacted)"),
+]
+MIN_PASSWORD_CHARS = 8
+
+
+def ask(question: str) -> bool:
+    answer = input(f"{question} [y/N] ").strip().lower()
+    return answer in ("y", "yes")
+
+
+def main() -> int:
+    parser = argparse.ArgumentParser(description="Create an encrypted DesktopAI diagnostic bundle")
+    parser.add_argument(
+        "--base-url",
+        default="http://127.0.0.1:8000",
+        help="Backend base URL (default: http://127.0.0.1:8000)",
+    )
+    parser.add_argument(
+        "--path",
+        help="Absolute path for the bundle on the collector's machine (default: its data directory)",
+    )
+    args = parser.parse_args()
+
+    print("Choose what goes into the bundle. Nothing is included unless you say yes.")
+    include = [name for name, description in CATEGORIES if ask(f"Include {name}: {description}?")]
+    if not include:
+        print("Nothing selected; no bundle created.")
+        return 1
+
+    password = getpass.getpass(f"Bundle password (at least {MIN_PASSWORD_CHARS} characters): ")
+    if len(password) < MIN_PASSWORD_CHARS:
+        print(f"password must be at least {MIN_PASSWORD_CHARS} characters", file=sys.stderr)
+        return 2
+    if getpass.getpass("Repeat password: ") != password:
+        print("passwords do not match", file=sys.stderr)
+        return 2
+
+    body = {"include": include, "password": password}
+    if args.path:
+        body["path"] = args.path
+    req = request.Request(
+        f"{args.base_url.rstrip('/')}/api/agent/bridge/diagnostic-bundle",
+        data=json.dumps(body).encode("utf-8"),
+        headers={"Content-Type": "application/json"},
+        method="POST",
+    )
+    try:
+        with request.urlopen(req, timeout=60) as resp:
+            payload = json.loads(resp.read().decode("utf-8"))
+    except error.HTTPError as exc:
+        detail = exc.read().decode("utf-8", "replace")
+        print(f"request failed: HTTP {exc.code} {detail}", file=sys.stderr)
+        return 2
+    except error.URLError as exc:
+        print(f"request failed: {exc}", file=sys.stderr)
+        return 2
+    except json.JSONDecodeError as exc:
+        print(f"invalid json: {exc}", file=sys.stderr)
+        return 3
+
+    if not payload.get("ok"):
+        print(f"bundle failed: {payload.get('error', 'unknown error')}", file=sys.stderr)
+        return 4
+    result = payload.get("result") or {}
+    print(f"Bundle written: {result.get('path')} ({result.get('bytes', 0)} bytes)")
+    print(f"Included: {', '.join(result.get('included') or include)}")
+    print("Attach the file to your issue and send the password separately.")
+    return 0
+
+
+if __name__ == "__main__":
+    raise SystemExit(main())
@@ -0,0 +1,143 @@
+//! High-contrast and reduced-motion display modes for the app's windows.
+//!
+//! Each mode follows the Windows setting (high contrast theme, "show
+//! animations in Windows") unless the user overrides it. Overrides are stored
+//! in the app data directory, and every change is broadcast to the webviews
+//! as `accessibility-changed` so the avatar and palette restyle themselves.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+const PREFS_FILE: &str = "accessibility.json";
+
+/// User overrides; `None` follows the system setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessibilityPrefs {
+    pub high_contrast: Option<bool>,
+    pub reduced_motion: Option<bool>,
+}
+
+/// The modes the webviews should apply.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AccessibilityMode {
+    pub high_contrast: bool,
+    pub reduced_motion: bool,
+}
+
+impl AccessibilityPrefs {
+    pub fn resolve(&self, system: AccessibilityMode) -> AccessibilityMode {
+        AccessibilityMode {
+            high_contrast: self.high_contrast.unwrap_or(system.high_contrast),
+            reduced_motion: self.reduced_motion.unwrap_or(system.reduced_motion),
+        }
+    }
+}
+
+/// Managed state: the stored overrides.
+pub struct Accessibility {
+    path: Option<PathBuf>,
+    prefs: Mutex<AccessibilityPrefs>,
+}
+
+impl Accessibility {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        let path = app.path().app_data_dir().ok().map(|dir| dir.join(PREFS_FILE));
+        let prefs = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, prefs: Mutex::new(prefs) }
+    }
+
+    pub fn mode(&self) -> AccessibilityMode {
+        self.prefs.lock().unwrap_or_else(|e| e.into_inner()).resolve(system_mode())
+    }
+
+    fn update(&self, prefs: AccessibilityPrefs) {
+        *self.prefs.lock().unwrap_or_else(|e| e.into_inner()) = prefs;
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string(&prefs).unwrap_or_default()));
+        if let Err(e) = written {
+            log::warn!("Failed to save accessibility preferences: {e}");
+        }
+    }
+}
+
+/// The Windows high contrast and animation settings.
+#[cfg(target_os = "windows")]
+fn system_mode() -> AccessibilityMode {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    let mut contrast = HIGHCONTRASTW { cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32, ..Default::default() };
+    let mut animations = BOOL(1);
+    unsafe {
+        let high_contrast = SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            contrast.cbSize,
+            Some(&mut contrast as *mut _ as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .is_ok()
+            && contrast.dwFlags.contains(HCF_HIGHCONTRASTON);
+        let _ = SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut animations as *mut _ as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        );
+        AccessibilityMode { high_contrast, reduced_motion: !animations.as_bool() }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn system_mode() -> AccessibilityMode {
+    AccessibilityMode { high_contrast: false, reduced_motion: false }
+}
+
+/// The display modes currently in effect.
+#[tauri::command]
+pub fn get_accessibility_mode(state: tauri::State<'_, Accessibility>) -> AccessibilityMode {
+    state.mode()
+}
+
+/// Override high contrast and/or reduced motion; `null` returns a mode to
+/// following the system setting. Applies to all windows immediately.
+#[tauri::command]
+pub fn set_accessibility_mode(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, Accessibility>,
+    high_contrast: Option<bool>,
+    reduced_motion: Option<bool>,
+) -> AccessibilityMode {
+    state.update(AccessibilityPrefs { high_contrast, reduced_motion });
+    let mode = state.mode();
+    let _ = app.emit("accessibility-changed", mode);
+    mode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_take_precedence_over_system() {
+        let system = AccessibilityMode { high_contrast: true, reduced_motion: false };
+        assert_eq!(AccessibilityPrefs::default().resolve(system), system);
+
+        let prefs = AccessibilityPrefs { high_contrast: Some(false), reduced_motion: Some(true) };
+        assert_eq!(prefs.resolve(system), AccessibilityMode { high_contrast: false, reduced_motion: true });
+    }
+}
@@ -0,0 +1,81 @@
+//! Working directory context for the palette conversation.
+//!
+//! Dropping a folder on the palette (or calling `set_context_directory`)
+//! makes it the directory that "this folder" refers to. The palette shows it
+//! and sends it with each chat message, and the collector scopes file
+//! operations to it: relative paths resolve there and anything outside is
+//! refused. The embedded collector gets it directly; the palette also hands
+//! it to the backend, which forwards it to a standalone collector whenever
+//! it connects. `context-directory-changed` tells the webviews.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::Emitter;
+
+use crate::local_mode::LocalMode;
+
+/// Managed state: the current context directory, if any.
+#[derive(Default)]
+pub struct ContextDirectory(Mutex<Option<PathBuf>>);
+
+/// Validate a requested context directory; empty or missing clears it.
+fn resolve_directory(path: Option<&str>) -> Result<Option<PathBuf>, String> {
+    let Some(path) = path.map(str::trim).filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+    let dir = crate::file_drop::absolute(Path::new(path)).ok_or("invalid path")?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a folder", dir.display()));
+    }
+    Ok(Some(dir))
+}
+
+fn display(dir: &Option<PathBuf>) -> Option<String> {
+    dir.as_ref().map(|d| d.to_string_lossy().into_owned())
+}
+
+/// Set (or with no `path`, clear) the conversation's working directory.
+/// Returns the absolute path now in effect.
+#[tauri::command]
+pub fn set_context_directory(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ContextDirectory>,
+    local: tauri::State<'_, LocalMode>,
+    path: Option<String>,
+) -> Result<Option<String>, String> {
+    let dir = resolve_directory(path.as_deref())?;
+    local.set_context_directory(dir.as_deref());
+    let shown = display(&dir);
+    *state.0.lock().unwrap_or_else(|e| e.into_inner()) = dir;
+    let _ = app.emit("context-directory-changed", &shown);
+    Ok(shown)
+}
+
+/// The current working directory context, if one is set.
+#[tauri::command]
+pub fn get_context_directory(state: tauri::State<'_, ContextDirectory>) -> Option<String> {
+    display(&state.0.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_directory() {
+        assert_eq!(resolve_directory(None), Ok(None));
+        assert_eq!(resolve_directory(Some("  ")), Ok(None));
+
+        let dir = std::env::temp_dir().join(format!("desktopai-context-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("shot.png");
+        std::fs::write(&file, b"x").unwrap();
+
+        let resolved = resolve_directory(Some(&dir.to_string_lossy())).unwrap().unwrap();
+        assert!(resolved.is_absolute() && resolved.is_dir());
+        assert!(resolve_directory(Some(&file.to_string_lossy())).unwrap_err().contains("not a folder"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
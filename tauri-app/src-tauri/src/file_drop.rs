@@ -0,0 +1,112 @@
+//! Files dragged onto and out of the assistant.
+//!
+//! Dropping files on the avatar or palette resolves them to absolute paths
+//! and hands them to that window as `files-dropped`; the webview attaches
+//! them to the next chat message. `drag-hover` lets it highlight the drop
+//! target. Going the other way, `reveal_file` shows a result file in
+//! Explorer so it can be dragged from there.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{DragDropEvent, Emitter, WindowEvent};
+
+/// Windows that accept dropped files.
+const DROP_TARGETS: [&str; 2] = ["avatar", "palette"];
+
+/// A dropped file as sent to the backend with a chat message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attachment {
+    pub path: String,
+    pub name: String,
+    pub size: Option<u64>,
+    pub is_dir: bool,
+}
+
+/// Absolute path without the `\\?\` prefix, which the backend and
+/// Explorer would otherwise treat as part of the name.
+pub(crate) fn absolute(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let text = path.to_string_lossy();
+    Some(match text.strip_prefix(r"\\?\") {
+        Some(stripped) if !stripped.starts_with("UNC\\") => PathBuf::from(stripped),
+        _ => path,
+    })
+}
+
+/// Attachment for a dropped path; `None` if it no longer exists.
+fn attachment(path: &Path) -> Option<Attachment> {
+    let path = absolute(path)?;
+    let metadata = std::fs::metadata(&path).ok()?;
+    Some(Attachment {
+        name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        size: metadata.is_file().then_some(metadata.len()),
+        is_dir: metadata.is_dir(),
+        path: path.to_string_lossy().into_owned(),
+    })
+}
+
+/// Window event hook: forward drops on the avatar and palette to their webview.
+pub fn on_window_event(window: &tauri::Window, event: &WindowEvent) {
+    let WindowEvent::DragDrop(drag) = event else {
+        return;
+    };
+    let label = window.label();
+    if !DROP_TARGETS.contains(&label) {
+        return;
+    }
+    match drag {
+        DragDropEvent::Enter { .. } => {
+            let _ = window.emit_to(label, "drag-hover", true);
+        }
+        DragDropEvent::Leave => {
+            let _ = window.emit_to(label, "drag-hover", false);
+        }
+        DragDropEvent::Drop { paths, .. } => {
+            let _ = window.emit_to(label, "drag-hover", false);
+            let attachments: Vec<Attachment> = paths.iter().filter_map(|p| attachment(p)).collect();
+            if attachments.is_empty() {
+                return;
+            }
+            log::info!("{} file(s) dropped on {label}", attachments.len());
+            let _ = window.emit_to(label, "files-dropped", attachments);
+        }
+        _ => {}
+    }
+}
+
+/// Select a file in Explorer (Finder, file manager) so the user can open or
+/// drag it onward.
+#[tauri::command]
+pub fn reveal_file(path: String) -> Result<(), String> {
+    let path = absolute(Path::new(&path)).ok_or("invalid path")?;
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    tauri_plugin_opener::reveal_item_in_dir(&path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_resolves_dropped_paths() {
+        let dir = std::env::temp_dir().join(format!("desktopai-drop-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("report.txt");
+        std::fs::write(&file, b"quarterly").unwrap();
+
+        let dropped = attachment(&file).unwrap();
+        assert!(Path::new(&dropped.path).is_absolute());
+        assert_eq!(dropped.name, "report.txt");
+        assert_eq!(dropped.size, Some(9));
+        assert!(!dropped.is_dir);
+
+        let folder = attachment(&dir).unwrap();
+        assert_eq!((folder.size, folder.is_dir), (None, true));
+        assert_eq!(attachment(&dir.join("missing.txt")), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,31 @@
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem},
     tray::TrayIconBuilder,
     Emitter, Manager,
 };
 use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 
+mod accessibility;
+mod context_dir;
+mod file_drop;
+mod local_mode;
+mod palette_opacity;
+mod palette_warm;
+mod permissions;
+mod privacy_preview;
+mod quick_intent;
+mod scheduler;
+#[cfg(target_os = "windows")]
+mod shell_watchdog;
+mod shutdown;
+#[cfg(target_os = "linux")]
+mod trigger_socket;
+mod window_picker;
+mod workflow_prompt;
+
+#[cfg(target_os = "linux")]
+pub use trigger_socket::forward_cli_trigger;
+
 #[cfg(target_os = "windows")]
 mod win_focus {
     use windows::Win32::Foundation::HWND;
@@ -39,6 +60,32 @@ mod win_focus {
 
 }
 
+/// The Win32 handle of one of our webview windows.
+#[cfg(target_os = "windows")]
+pub(crate) fn window_hwnd(window: &tauri::WebviewWindow) -> Option<windows::Win32::Foundation::HWND> {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::Win32::Foundation::HWND;
+
+    match window.window_handle().ok()?.as_raw() {
+        RawWindowHandle::Win32(win32) => Some(HWND(win32.hwnd.get() as *mut _)),
+        _ => None,
+    }
+}
+
+/// Hide an overlay window from screen capture so it never shows up in the
+/// collector's screenshots, including captures of other monitors.
+#[cfg(target_os = "windows")]
+fn exclude_from_capture(window: &tauri::WebviewWindow) {
+    use windows::Win32::UI::WindowsAndMessaging::{SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE};
+
+    let Some(hwnd) = window_hwnd(window) else {
+        return;
+    };
+    if let Err(e) = unsafe { SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) } {
+        log::warn!("Failed to exclude window '{}' from capture: {e}", window.label());
+    }
+}
+
 #[tauri::command]
 fn toggle_visibility(window: tauri::Window) {
     if window.is_visible().unwrap_or(false) {
@@ -59,21 +106,19 @@ fn set_compact_mode(window: tauri::Window, compact: bool) {
 }
 
 /// Toggle the command palette: save foreground, show, or hide + restore focus.
-fn toggle_palette(app: &tauri::AppHandle) {
+pub(crate) fn toggle_palette(app: &tauri::AppHandle) {
     let Some(palette) = app.get_webview_window("palette") else {
         return;
     };
 
-    if palette.is_visible().unwrap_or(false) {
-        let _ = palette.hide();
+    if palette_warm::is_open(app) {
+        palette_warm::close(app, &palette);
         #[cfg(target_os = "windows")]
         win_focus::restore_foreground();
     } else {
         #[cfg(target_os = "windows")]
         win_focus::save_foreground();
-        let _ = palette.center();
-        let _ = palette.show();
-        let _ = palette.set_focus();
+        palette_warm::open(app, &palette);
     }
 }
 
@@ -81,12 +126,41 @@ fn toggle_palette(app: &tauri::AppHandle) {
 #[tauri::command]
 fn dismiss_palette(app: tauri::AppHandle) {
     if let Some(palette) = app.get_webview_window("palette") {
-        let _ = palette.hide();
+        palette_warm::close(&app, &palette);
     }
     #[cfg(target_os = "windows")]
     win_focus::restore_foreground();
 }
 
+/// Marker file the collector checks before executing input-injecting commands.
+/// Must match the collector's `SAFE_MODE_FLAG_PATH` default.
+fn safe_mode_flag_path() -> Option<std::path::PathBuf> {
+    if let Ok(path) = std::env::var("SAFE_MODE_FLAG_PATH") {
+        return Some(path.into());
+    }
+    std::env::var("LOCALAPPDATA")
+        .ok()
+        .map(|dir| std::path::Path::new(&dir).join("DesktopAI").join("safe_mode"))
+}
+
+/// Create or remove the safe mode marker file.
+fn set_safe_mode_flag(enabled: bool) -> std::io::Result<()> {
+    let Some(path) = safe_mode_flag_path() else {
+        return Ok(());
+    };
+    if enabled {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, b"")
+    } else {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Kill all running actions by POSTing to the backend.
 #[tauri::command]
 async fn kill_all_actions() -> Result<String, String> {
@@ -104,9 +178,32 @@ async fn kill_all_actions() -> Result<String, String> {
     }
 }
 
+/// Tray icon id, so the shell watchdog can find the icon again.
+pub(crate) const TRAY_ID: &str = "main";
+
+fn palette_shortcut() -> Shortcut {
+    Shortcut::new(Some(Modifiers::CONTROL), Code::Space)
+}
+
+fn kill_shortcut() -> Shortcut {
+    Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyX)
+}
+
+/// Register the global shortcuts, unregistering first to clear stale
+/// registrations (e.g. after the shell restarted).
+pub(crate) fn register_shortcuts(app: &tauri::AppHandle) {
+    let gs = app.global_shortcut();
+    for (shortcut, label) in [(palette_shortcut(), "Ctrl+Space"), (kill_shortcut(), "Ctrl+Shift+X")] {
+        let _ = gs.unregister(shortcut);
+        if let Err(e) = gs.register(shortcut) {
+            log::warn!("Failed to register {label}: {e}");
+        }
+    }
+}
+
 pub fn run() {
-    let ctrl_space = Shortcut::new(Some(Modifiers::CONTROL), Code::Space);
-    let ctrl_shift_x = Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyX);
+    let ctrl_space = palette_shortcut();
+    let ctrl_shift_x = kill_shortcut();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -121,25 +218,44 @@ pub fn run() {
                         toggle_palette(app);
                     } else if *shortcut == ctrl_shift_x {
                         let handle = app.clone();
-                        tauri::async_runtime::spawn(async move {
+                        shutdown::spawn_request(app, async move {
                             let _ = kill_all_actions_internal(&handle).await;
                         });
                     }
                 })
                 .build(),
         )
+        .on_window_event(|window, event| {
+            file_drop::on_window_event(window, event);
+            privacy_preview::on_window_event(window, event);
+            permissions::on_window_event(window, event);
+            workflow_prompt::on_window_event(window, event);
+        })
         .setup(move |app| {
-            // Register global shortcuts (unregister first to handle stale registrations)
-            let gs = app.global_shortcut();
-            let _ = gs.unregister(ctrl_space);
-            let _ = gs.unregister(ctrl_shift_x);
-            if let Err(e) = gs.register(ctrl_space) {
-                log::warn!("Failed to register Ctrl+Space: {e}");
-            }
-            if let Err(e) = gs.register(ctrl_shift_x) {
-                log::warn!("Failed to register Ctrl+Shift+X: {e}");
+            app.manage(shutdown::Shutdown::default());
+            register_shortcuts(app.handle());
+            #[cfg(target_os = "windows")]
+            shell_watchdog::start(app.handle());
+            #[cfg(target_os = "linux")]
+            trigger_socket::start(app.handle());
+
+            #[cfg(target_os = "windows")]
+            for label in ["avatar", "palette", "preview", "permissions", "workflow-prompt"] {
+                if let Some(window) = app.get_webview_window(label) {
+                    exclude_from_capture(&window);
+                }
             }
 
+            app.manage(workflow_prompt::WorkflowPrompt::register(app.handle()));
+            app.manage(local_mode::LocalMode::start(app.handle()));
+            scheduler::start(app.handle());
+            app.manage(accessibility::Accessibility::load(app.handle()));
+            app.manage(palette_opacity::PaletteOpacity::default());
+            app.manage(palette_warm::PaletteWarm::default());
+            app.manage(context_dir::ContextDirectory::default());
+            app.manage(window_picker::WindowPicker::default());
+            palette_warm::prewarm(app.handle());
+
             // System tray
             let show = MenuItem::with_id(app, "show", "Show DesktopAI", true, None::<&str>)?;
             let hide = MenuItem::with_id(app, "hide", "Hide", true, None::<&str>)?;
@@ -152,13 +268,30 @@ pub fn run() {
             )?;
             let dashboard =
                 MenuItem::with_id(app, "dashboard", "Open Dashboard", true, None::<&str>)?;
+            let preview =
+                MenuItem::with_id(app, "preview", "Privacy Preview", true, None::<&str>)?;
+            let permissions_item =
+                MenuItem::with_id(app, "permissions", "Permissions", true, None::<&str>)?;
+            let safe_mode_on = safe_mode_flag_path().is_some_and(|p| p.exists());
+            let safe_mode = CheckMenuItem::with_id(
+                app,
+                "safe_mode",
+                "Safe Mode (observe only)",
+                safe_mode_flag_path().is_some(),
+                safe_mode_on,
+                None::<&str>,
+            )?;
             let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show, &hide, &palette_item, &dashboard, &quit])?;
+            let menu = Menu::with_items(
+                app,
+                &[&show, &hide, &palette_item, &dashboard, &preview, &permissions_item, &safe_mode, &quit],
+            )?;
 
-            TrayIconBuilder::new()
+            let safe_mode_item = safe_mode.clone();
+            TrayIconBuilder::with_id(TRAY_ID)
                 .menu(&menu)
                 .tooltip("DesktopAI")
-                .on_menu_event(|app, event| match event.id.as_ref() {
+                .on_menu_event(move |app, event| match event.id.as_ref() {
                     "show" => {
                         if let Some(window) = app.get_webview_window("avatar") {
                             let _ = window.show();
@@ -175,7 +308,15 @@ pub fn run() {
                         let _ = tauri_plugin_opener::OpenerExt::opener(app)
                             .open_url("http://localhost:8000", None::<&str>);
                     }
-                    "quit" => app.exit(0),
+                    "preview" => privacy_preview::show(app),
+                    "permissions" => permissions::show(app),
+                    "safe_mode" => {
+                        let enabled = safe_mode_item.is_checked().unwrap_or(false);
+                        if let Err(e) = set_safe_mode_flag(enabled) {
+                            log::warn!("Failed to update safe mode flag: {e}");
+                        }
+                    }
+                    "quit" => shutdown::quit(app),
                     _ => {}
                 })
                 .build(app)?;
@@ -187,12 +328,33 @@ pub fn run() {
             set_compact_mode,
             dismiss_palette,
             kill_all_actions,
+            local_mode::local_current_window,
+            local_mode::local_execute,
+            quick_intent::resolve_quick_intent,
+            scheduler::schedule_task,
+            scheduler::list_scheduled_tasks,
+            scheduler::cancel_scheduled_task,
+            accessibility::get_accessibility_mode,
+            accessibility::set_accessibility_mode,
+            palette_opacity::set_palette_opacity,
+            palette_opacity::set_palette_peek,
+            palette_warm::palette_ready,
+            palette_warm::palette_latency,
+            file_drop::reveal_file,
+            context_dir::set_context_directory,
+            context_dir::get_context_directory,
+            permissions::get_permissions,
+            permissions::set_permission,
+            window_picker::list_windows_for_picker,
+            window_picker::activate_window,
+            workflow_prompt::workflow_prompt_request,
+            workflow_prompt::answer_workflow_prompt,
         ])
         .run(tauri::generate_context!())
         .expect("error while running DesktopAI");
 }
 
-async fn kill_all_actions_internal(app: &tauri::AppHandle) -> Result<(), String> {
+pub(crate) async fn kill_all_actions_internal(app: &tauri::AppHandle) -> Result<(), String> {
     let client = reqwest::Client::new();
     let resp = client
         .post("http://localhost:8000/api/autonomy/cancel-all")
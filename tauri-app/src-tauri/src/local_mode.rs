@@ -0,0 +1,176 @@
+//! Direct local mode: the collector embedded in the app process.
+//!
+//! Built with the `local-collector` feature, the app observes the desktop and
+//! runs palette commands itself instead of going through the collector
+//! executable and the backend. Observed events are re-emitted to the webviews
+//! as `local-event`. Without the feature the commands report that local mode
+//! is unavailable, so the frontend can fall back to the backend.
+
+#[cfg(feature = "local-collector")]
+use desktopai_collector::LocalCollector;
+#[cfg(feature = "local-collector")]
+use tauri::Emitter;
+
+/// Actions a webview may run through `local_execute`: the palette's
+/// observation, window switching, launcher and workflow commands. Anything
+/// that changes files, processes or collector settings is refused here.
+const PALETTE_ACTIONS: &[&str] = &[
+    "observe",
+    "list_windows",
+    "list_monitors",
+    "get_window_thumbnail",
+    "get_taskbar_state",
+    "focus_window",
+    "restore_window",
+    "start_menu_search",
+    "list_workflows",
+    "run_workflow",
+    "preview_events",
+];
+
+/// Refuse `action` unless the palette may run it locally.
+fn check_palette_action(action: &str) -> Result<(), String> {
+    if PALETTE_ACTIONS.contains(&action) {
+        Ok(())
+    } else {
+        Err(format!("{action} cannot be run from the palette"))
+    }
+}
+
+/// Managed state: the embedded collector, if it could be started.
+#[derive(Default)]
+pub struct LocalMode {
+    #[cfg(feature = "local-collector")]
+    collector: Option<LocalCollector>,
+}
+
+impl LocalMode {
+    /// Start the embedded collector and forward its events to the webviews.
+    #[cfg(feature = "local-collector")]
+    pub fn start(app: &tauri::AppHandle) -> Self {
+        let collector = LocalCollector::start(desktopai_collector::Config::from_env());
+        match &collector {
+            Some(collector) => {
+                let events = collector.subscribe();
+                let app = app.clone();
+                std::thread::spawn(move || {
+                    for event in events {
+                        let _ = app.emit("local-event", &event);
+                    }
+                });
+            }
+            None => log::warn!("Local collector could not be started"),
+        }
+        Self { collector }
+    }
+
+    #[cfg(not(feature = "local-collector"))]
+    pub fn start(_app: &tauri::AppHandle) -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "local-collector")]
+    fn collector(&self) -> Result<&LocalCollector, String> {
+        self.collector.as_ref().ok_or_else(|| "local collector is not running".to_string())
+    }
+
+    /// Whether the collector is embedded and running in this process.
+    pub fn available(&self) -> bool {
+        #[cfg(feature = "local-collector")]
+        return self.collector.is_some();
+        #[cfg(not(feature = "local-collector"))]
+        false
+    }
+
+    /// Run a collector action in-process and return its result fields, or
+    /// its error when it failed or the collector is not embedded.
+    pub fn execute(
+        &self,
+        action: &str,
+        parameters: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<std::collections::HashMap<String, serde_json::Value>, String> {
+        #[cfg(feature = "local-collector")]
+        {
+            let result = self.collector()?.execute_action(action, parameters.into_iter().collect());
+            if result.ok {
+                Ok(result.result)
+            } else {
+                Err(result.error.unwrap_or_else(|| format!("{action} failed")))
+            }
+        }
+        #[cfg(not(feature = "local-collector"))]
+        {
+            let _ = (action, parameters);
+            Err("local mode is not available in this build".to_string())
+        }
+    }
+
+    /// Scope the embedded collector's file operations to `dir`. A no-op
+    /// without the embedded collector; a standalone collector gets the
+    /// folder from the backend (see context_dir.rs).
+    pub fn set_context_directory(&self, dir: Option<&std::path::Path>) {
+        #[cfg(feature = "local-collector")]
+        if let Ok(collector) = self.collector() {
+            let path = dir.map(|d| d.to_string_lossy().into_owned());
+            let parameters = [("path".to_string(), serde_json::json!(path))].into_iter().collect();
+            let result = collector.execute_action("set_context_directory", parameters);
+            if !result.ok {
+                log::warn!("Local collector rejected context directory: {:?}", result.error);
+            }
+        }
+        #[cfg(not(feature = "local-collector"))]
+        let _ = dir;
+    }
+}
+
+/// The window currently in front of the user, as seen by the embedded collector.
+#[tauri::command]
+pub fn local_current_window(state: tauri::State<'_, LocalMode>) -> Result<Option<serde_json::Value>, String> {
+    #[cfg(feature = "local-collector")]
+    {
+        let window = state.collector()?.current_window();
+        window.map(|w| serde_json::to_value(w).map_err(|e| e.to_string())).transpose()
+    }
+    #[cfg(not(feature = "local-collector"))]
+    {
+        let _ = state;
+        Err("local mode is not available in this build".to_string())
+    }
+}
+
+/// Run a palette action (`PALETTE_ACTIONS`) in-process and return its
+/// command result. Runs off the main thread: actions may wait, e.g.
+/// `run_workflow` on the workflow prompt window.
+#[tauri::command(async)]
+pub fn local_execute(
+    state: tauri::State<'_, LocalMode>,
+    action: String,
+    parameters: Option<serde_json::Map<String, serde_json::Value>>,
+) -> Result<serde_json::Value, String> {
+    check_palette_action(&action)?;
+    #[cfg(feature = "local-collector")]
+    {
+        let parameters = parameters.unwrap_or_default().into_iter().collect();
+        let result = state.collector()?.execute_action(&action, parameters);
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    }
+    #[cfg(not(feature = "local-collector"))]
+    {
+        let _ = (state, action, parameters);
+        Err("local mode is not available in this build".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_palette_actions_run_locally() {
+        assert!(check_palette_action("list_windows").is_ok());
+        assert!(check_palette_action("preview_events").is_ok());
+        for action in ["run_shell", "purge_data", "kill_process", "set_safe_mode", "export_state"] {
+            assert_eq!(check_palette_action(action).unwrap_err(), format!("{action} cannot be run from the palette"));
+        }
+    }
+}
@@ -1,5 +1,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    #[cfg(target_os = "linux")]
+    if let Some(code) = desktopai::forward_cli_trigger() {
+        std::process::exit(code);
+    }
     desktopai::run();
 }
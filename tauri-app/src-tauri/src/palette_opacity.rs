@@ -0,0 +1,130 @@
+//! Palette opacity and "peek" mode.
+//!
+//! The palette sits on top of whatever the user is working in. Its opacity
+//! is adjustable, and while a long answer streams the palette can "peek":
+//! it fades out and becomes click-through, so the content beneath stays
+//! readable and clickable. On Windows both use layered-window attributes.
+
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+/// Opacity floor, so the palette never disappears entirely.
+const MIN_OPACITY: f64 = 0.2;
+/// Opacity while peeking, regardless of the configured opacity.
+const PEEK_OPACITY: f64 = 0.35;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PaletteLook {
+    opacity: f64,
+    peeking: bool,
+}
+
+impl PaletteLook {
+    /// Opacity to apply and whether clicks pass through.
+    fn effective(self) -> (f64, bool) {
+        if self.peeking {
+            (self.opacity.min(PEEK_OPACITY), true)
+        } else {
+            (self.opacity, false)
+        }
+    }
+}
+
+/// Managed state: configured opacity and whether peek mode is on.
+pub struct PaletteOpacity(Mutex<PaletteLook>);
+
+impl Default for PaletteOpacity {
+    fn default() -> Self {
+        Self(Mutex::new(PaletteLook { opacity: 1.0, peeking: false }))
+    }
+}
+
+impl PaletteOpacity {
+    fn update(&self, app: &tauri::AppHandle, change: impl FnOnce(&mut PaletteLook)) -> Result<PaletteLook, String> {
+        let mut look = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut look);
+        let palette = app.get_webview_window("palette").ok_or("palette window not found")?;
+        let (opacity, click_through) = look.effective();
+        apply(&palette, opacity, click_through)?;
+        Ok(*look)
+    }
+}
+
+fn clamp_opacity(opacity: f64) -> f64 {
+    if opacity.is_nan() {
+        1.0
+    } else {
+        opacity.clamp(MIN_OPACITY, 1.0)
+    }
+}
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn alpha(opacity: f64) -> u8 {
+    (opacity * 255.0).round() as u8
+}
+
+#[cfg(target_os = "windows")]
+fn apply(window: &tauri::WebviewWindow, opacity: f64, click_through: bool) -> Result<(), String> {
+    use windows::Win32::Foundation::COLORREF;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA, WS_EX_LAYERED,
+        WS_EX_TRANSPARENT,
+    };
+
+    let hwnd = crate::window_hwnd(window).ok_or("palette window has no HWND")?;
+    unsafe {
+        let mut style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE) | WS_EX_LAYERED.0 as isize;
+        if click_through {
+            style |= WS_EX_TRANSPARENT.0 as isize;
+        } else {
+            style &= !(WS_EX_TRANSPARENT.0 as isize);
+        }
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style);
+        SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha(opacity), LWA_ALPHA)
+            .map_err(|e| format!("SetLayeredWindowAttributes failed: {e}"))
+    }
+}
+
+/// Without layered windows only click-through is available.
+#[cfg(not(target_os = "windows"))]
+fn apply(window: &tauri::WebviewWindow, _opacity: f64, click_through: bool) -> Result<(), String> {
+    window.set_ignore_cursor_events(click_through).map_err(|e| e.to_string())
+}
+
+/// Set the palette's opacity (clamped to 0.2–1.0); returns the value applied.
+/// While peeking, the new opacity takes effect when peek mode ends.
+#[tauri::command]
+pub fn set_palette_opacity(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, PaletteOpacity>,
+    opacity: f64,
+) -> Result<f64, String> {
+    state.update(&app, |look| look.opacity = clamp_opacity(opacity)).map(|look| look.opacity)
+}
+
+/// Enter or leave peek mode: semi-transparent and click-through.
+#[tauri::command]
+pub fn set_palette_peek(app: tauri::AppHandle, state: tauri::State<'_, PaletteOpacity>, enabled: bool) -> Result<(), String> {
+    state.update(&app, |look| look.peeking = enabled).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opacity_clamped_and_peek_overrides() {
+        assert_eq!(clamp_opacity(0.05), MIN_OPACITY);
+        assert_eq!(clamp_opacity(3.0), 1.0);
+        assert_eq!(clamp_opacity(f64::NAN), 1.0);
+        assert_eq!(alpha(1.0), 255);
+        assert_eq!(alpha(0.5), 128);
+
+        let look = PaletteLook { opacity: 0.9, peeking: false };
+        assert_eq!(look.effective(), (0.9, false));
+        assert_eq!(PaletteLook { peeking: true, ..look }.effective(), (PEEK_OPACITY, true));
+        // A palette already fainter than the peek level stays as it is
+        assert_eq!(PaletteLook { opacity: 0.25, peeking: true }.effective(), (0.25, true));
+    }
+}
@@ -0,0 +1,180 @@
+//! Pre-warmed palette and toggle latency tracking.
+//!
+//! Showing a hidden webview window wakes its renderer, which can take long
+//! enough on slow machines to make the hotkey feel laggy. Instead the palette
+//! is shown once at startup and parked off-screen; opening it is then just a
+//! move and a focus. The webview acknowledges each open with `palette_ready`
+//! once its input has focus, and the hotkey-to-focus time is kept for the
+//! `palette_latency` perf command.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, PhysicalPosition};
+
+/// Far outside any monitor layout; the parked palette renders there.
+const PARKED_POSITION: PhysicalPosition<i32> = PhysicalPosition { x: -32000, y: -32000 };
+/// Latency samples kept for the perf command.
+const MAX_SAMPLES: usize = 100;
+
+/// Managed state: whether the palette is open and recent open latencies.
+#[derive(Default)]
+pub struct PaletteWarm {
+    prewarmed: AtomicBool,
+    open: AtomicBool,
+    opened_at: Mutex<Option<Instant>>,
+    samples: Mutex<VecDeque<f64>>,
+}
+
+/// Toggle latency summary in milliseconds, hotkey to focused input.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub prewarmed: bool,
+    pub samples: usize,
+    pub last_ms: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+impl LatencyStats {
+    fn from_samples(prewarmed: bool, samples: &VecDeque<f64>) -> Self {
+        let mut sorted: Vec<f64> = samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = ((p * sorted.len() as f64).ceil() as usize).max(1);
+            sorted.get(rank - 1).copied()
+        };
+        Self {
+            prewarmed,
+            samples: sorted.len(),
+            last_ms: samples.back().copied(),
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: sorted.last().copied(),
+        }
+    }
+}
+
+/// Top-left position that centers a window of `size` in `area`.
+fn centered(area_pos: (i32, i32), area_size: (u32, u32), size: (u32, u32)) -> (i32, i32) {
+    let offset = |area: u32, len: u32| (area.saturating_sub(len) / 2) as i32;
+    (area_pos.0 + offset(area_size.0, size.0), area_pos.1 + offset(area_size.1, size.1))
+}
+
+/// Center of the monitor under the cursor, falling back to the primary one.
+fn open_position(palette: &tauri::WebviewWindow) -> Option<PhysicalPosition<i32>> {
+    let monitor = palette
+        .cursor_position()
+        .ok()
+        .and_then(|c| palette.monitor_from_point(c.x, c.y).ok().flatten())
+        .or_else(|| palette.primary_monitor().ok().flatten())?;
+    let area = monitor.work_area();
+    let size = palette.outer_size().ok()?;
+    let (x, y) = centered(
+        (area.position.x, area.position.y),
+        (area.size.width, area.size.height),
+        (size.width, size.height),
+    );
+    Some(PhysicalPosition { x, y })
+}
+
+/// Show the palette parked off-screen so its webview stays rendered.
+pub fn prewarm(app: &tauri::AppHandle) {
+    let Some(palette) = app.get_webview_window("palette") else {
+        return;
+    };
+    let parked = palette.set_position(PARKED_POSITION).is_ok() && palette.show().is_ok();
+    if !parked {
+        log::warn!("Palette pre-warm failed; falling back to show/hide");
+        let _ = palette.hide();
+    }
+    app.state::<PaletteWarm>().prewarmed.store(parked, Ordering::SeqCst);
+}
+
+pub fn is_open(app: &tauri::AppHandle) -> bool {
+    app.state::<PaletteWarm>().open.load(Ordering::SeqCst)
+}
+
+/// Bring the palette up centered and focused.
+pub fn open(app: &tauri::AppHandle, palette: &tauri::WebviewWindow) {
+    let state = app.state::<PaletteWarm>();
+    *state.opened_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    state.open.store(true, Ordering::SeqCst);
+    match open_position(palette) {
+        Some(position) => {
+            let _ = palette.set_position(position);
+        }
+        None => {
+            let _ = palette.center();
+        }
+    }
+    if !state.prewarmed.load(Ordering::SeqCst) {
+        let _ = palette.show();
+    }
+    let _ = palette.set_focus();
+    let _ = palette.emit("palette-opened", ());
+}
+
+/// Park (or hide, without pre-warm) the palette.
+pub fn close(app: &tauri::AppHandle, palette: &tauri::WebviewWindow) {
+    let state = app.state::<PaletteWarm>();
+    state.open.store(false, Ordering::SeqCst);
+    if state.prewarmed.load(Ordering::SeqCst) {
+        let _ = palette.set_position(PARKED_POSITION);
+    } else {
+        let _ = palette.hide();
+    }
+}
+
+/// Called by the palette once its input has focus after `palette-opened`.
+#[tauri::command]
+pub fn palette_ready(state: tauri::State<'_, PaletteWarm>) {
+    let Some(opened_at) = state.opened_at.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let ms = opened_at.elapsed().as_secs_f64() * 1000.0;
+    let mut samples = state.samples.lock().unwrap_or_else(|e| e.into_inner());
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(ms);
+}
+
+/// Recent palette toggle latencies.
+#[tauri::command]
+pub fn palette_latency(state: tauri::State<'_, PaletteWarm>) -> LatencyStats {
+    let samples = state.samples.lock().unwrap_or_else(|e| e.into_inner());
+    LatencyStats::from_samples(state.prewarmed.load(Ordering::SeqCst), &samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let empty = LatencyStats::from_samples(true, &VecDeque::new());
+        assert_eq!((empty.samples, empty.p50_ms, empty.max_ms), (0, None, None));
+
+        let samples: VecDeque<f64> = (1..=20).rev().map(f64::from).collect();
+        let stats = LatencyStats::from_samples(true, &samples);
+        assert_eq!(stats.samples, 20);
+        assert_eq!(stats.last_ms, Some(1.0));
+        assert_eq!(stats.p50_ms, Some(10.0));
+        assert_eq!(stats.p95_ms, Some(19.0));
+        assert_eq!(stats.max_ms, Some(20.0));
+    }
+
+    #[test]
+    fn test_centered_in_work_area() {
+        assert_eq!(centered((0, 0), (1920, 1040), (640, 72)), (640, 484));
+        // Secondary monitor left of the primary
+        assert_eq!(centered((-1280, 0), (1280, 1024), (640, 72)), (-960, 476));
+        // Window larger than the area pins to its corner
+        assert_eq!(centered((0, 0), (500, 50), (640, 72)), (0, 0));
+    }
+}
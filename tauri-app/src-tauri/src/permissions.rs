@@ -0,0 +1,168 @@
+//! Permissions window: which categories of actions the collector may run on
+//! this machine.
+//!
+//! Choices are written to the collector's permission manifest
+//! (`PERMISSIONS_PATH`, by default `permissions.json` in the DesktopAI data
+//! directory). The collector refuses disabled actions whatever the backend
+//! asks, and tells the backend about every change. Opened from the tray;
+//! closing it only hides it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, WindowEvent};
+
+const LABEL: &str = "permissions";
+
+/// The collector's categories, in the order the window lists them.
+const CATEGORIES: &[(&str, &str, &str)] = &[
+    ("observe", "Observe", "Read windows, UI elements, screenshots and on-screen text"),
+    ("input", "Mouse and keyboard", "Click, type, press keys, scroll and touch gestures"),
+    ("ui_automation", "Controls", "Select items, expand and collapse, set values, open menus"),
+    ("windows", "Windows", "Focus, close, move and resize windows; switch desktops"),
+    ("applications", "Applications", "Launch apps and end processes"),
+    ("files", "Files", "Scope file operations to a folder and use the Recycle Bin"),
+    ("shell", "Programs", "Run programs with arguments"),
+    ("maintenance", "Maintenance", "Import settings, purge collector data and self-test"),
+];
+
+/// The manifest file; categories it does not mention are allowed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    categories: BTreeMap<String, bool>,
+}
+
+/// One category as the window shows it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Permission {
+    pub category: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    pub enabled: bool,
+}
+
+/// Must match the collector's `PERMISSIONS_PATH` default.
+fn manifest_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("PERMISSIONS_PATH") {
+        return Some(path.into());
+    }
+    if let Some(dir) = std::env::var("DATA_DIR").ok().filter(|dir| !dir.is_empty()) {
+        return Some(Path::new(&dir).join("permissions.json"));
+    }
+    std::env::var("LOCALAPPDATA")
+        .ok()
+        .map(|dir| Path::new(&dir).join("DesktopAI").join("permissions.json"))
+}
+
+fn read(path: &Path) -> Result<Manifest, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| {
+            format!(
+                "{} is not a valid permission manifest ({e}); the collector allows nothing until it is fixed or deleted",
+                path.display()
+            )
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(e) => Err(format!("cannot read {}: {e}", path.display())),
+    }
+}
+
+/// Replace the file in one step so the collector never reads half of it.
+fn write(path: &Path, manifest: &Manifest) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
+    }
+    let staged = path.with_extension("json.tmp");
+    std::fs::write(&staged, text)
+        .and_then(|_| std::fs::rename(&staged, path))
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))
+}
+
+fn list(manifest: &Manifest) -> Vec<Permission> {
+    CATEGORIES
+        .iter()
+        .map(|&(category, label, description)| Permission {
+            category,
+            label,
+            description,
+            enabled: manifest.categories.get(category).copied().unwrap_or(true),
+        })
+        .collect()
+}
+
+fn update(path: &Path, category: &str, enabled: bool) -> Result<Vec<Permission>, String> {
+    if !CATEGORIES.iter().any(|(name, _, _)| *name == category) {
+        return Err(format!("unknown permission category: {category}"));
+    }
+    let mut manifest = read(path)?;
+    manifest.categories.insert(category.to_string(), enabled);
+    write(path, &manifest)?;
+    Ok(list(&manifest))
+}
+
+/// Every category and whether the collector may run its actions.
+#[tauri::command]
+pub fn get_permissions() -> Result<Vec<Permission>, String> {
+    let path = manifest_path().ok_or("no data directory for the permission manifest")?;
+    read(&path).map(|manifest| list(&manifest))
+}
+
+/// Enable or disable one category; returns the updated list.
+#[tauri::command]
+pub fn set_permission(category: String, enabled: bool) -> Result<Vec<Permission>, String> {
+    let path = manifest_path().ok_or("no data directory for the permission manifest")?;
+    let permissions = update(&path, &category, enabled)?;
+    log::info!("Permission '{category}' {}", if enabled { "enabled" } else { "disabled" });
+    Ok(permissions)
+}
+
+/// Bring the permissions window to the front.
+pub fn show(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Window event hook: hide the window instead of destroying it.
+pub fn on_window_event(window: &tauri::Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == LABEL {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_manifest() {
+        let dir = std::env::temp_dir().join(format!("desktopai-permissions-{}", std::process::id()));
+        let path = dir.join("permissions.json");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(list(&read(&path).unwrap()).iter().all(|p| p.enabled), "no file allows everything");
+        let updated = update(&path, "shell", false).unwrap();
+        assert!(!updated.iter().find(|p| p.category == "shell").unwrap().enabled);
+        assert!(updated.iter().find(|p| p.category == "input").unwrap().enabled);
+
+        // Entries the window does not know about survive an edit
+        std::fs::write(&path, r#"{"categories": {"shell": false, "future": false}}"#).unwrap();
+        update(&path, "input", false).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["categories"], serde_json::json!({"future": false, "input": false, "shell": false}));
+
+        assert!(update(&path, "bogus", true).unwrap_err().contains("unknown"));
+        std::fs::write(&path, "{").unwrap();
+        assert!(update(&path, "shell", true).unwrap_err().contains("not a valid permission manifest"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
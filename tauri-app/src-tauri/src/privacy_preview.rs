@@ -0,0 +1,29 @@
+//! Privacy preview window.
+//!
+//! Opened from the tray, it shows what the collector's next outgoing events
+//! contain after redaction (see `preview.js` and the collector's
+//! `preview_events`). Closing it only hides it, so a capture in progress
+//! survives until the window is opened again.
+
+use tauri::{Manager, WindowEvent};
+
+const LABEL: &str = "preview";
+
+/// Bring the preview window to the front.
+pub fn show(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Window event hook: hide the preview instead of destroying it.
+pub fn on_window_event(window: &tauri::Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == LABEL {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Local palette intents: trivial inputs answered natively and instantly.
+//!
+//! Arithmetic ("12*(3+4)"), unit conversions ("10 km to miles", "72 f in c")
+//! and a few system quick actions ("empty recycle bin", "open downloads") are
+//! resolved here without a backend round trip, as are reminders ("remind me
+//! when I open excel to ...") which go to the local scheduler. Anything else returns `None`
+//! and the palette sends it to `/api/chat` as before.
+
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::scheduler;
+
+/// What the palette shows for a locally handled input.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuickReply {
+    /// `math`, `conversion` or `action`.
+    pub kind: &'static str,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Folder {
+    Downloads,
+    Documents,
+    Desktop,
+    Pictures,
+}
+
+impl Folder {
+    fn label(self) -> &'static str {
+        match self {
+            Folder::Downloads => "Downloads",
+            Folder::Documents => "Documents",
+            Folder::Desktop => "Desktop",
+            Folder::Pictures => "Pictures",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Intent {
+    Math(f64),
+    Conversion { value: f64, from: &'static str, result: f64, to: &'static str },
+    EmptyRecycleBin,
+    OpenFolder(Folder),
+}
+
+/// Resolve `input` locally, running the system action if it names one.
+#[tauri::command]
+pub fn resolve_quick_intent(app: tauri::AppHandle, input: String) -> Option<QuickReply> {
+    if let Some((trigger, action)) = scheduler::parse_reminder(&input, chrono::Local::now()) {
+        let task = app.state::<scheduler::Scheduler>().add(trigger, action, false);
+        return Some(QuickReply { kind: "action", text: describe_reminder(&task) });
+    }
+    let reply = match parse_intent(&input)? {
+        Intent::Math(value) => QuickReply { kind: "math", text: format!("= {}", format_number(value)) },
+        Intent::Conversion { value, from, result, to } => QuickReply {
+            kind: "conversion",
+            text: format!("{} {from} = {} {to}", format_number(value), format_number(result)),
+        },
+        Intent::EmptyRecycleBin => QuickReply {
+            kind: "action",
+            text: match empty_recycle_bin() {
+                Ok(()) => "Recycle bin emptied.".to_string(),
+                Err(e) => format!("Recycle bin not emptied: {e}"),
+            },
+        },
+        Intent::OpenFolder(folder) => QuickReply { kind: "action", text: open_folder(&app, folder) },
+    };
+    Some(reply)
+}
+
+fn describe_reminder(task: &scheduler::ScheduledTask) -> String {
+    let when = match &task.trigger {
+        scheduler::Trigger::At { at_ms } => chrono::DateTime::from_timestamp_millis(*at_ms)
+            .map(|at| format!("at {}", at.with_timezone(&chrono::Local).format("%H:%M")))
+            .unwrap_or_default(),
+        scheduler::Trigger::IdleReturn => "when you're back".to_string(),
+        scheduler::Trigger::AppLaunch { process } => format!("when {process} starts"),
+    };
+    format!("Reminder set {when}.")
+}
+
+fn parse_intent(input: &str) -> Option<Intent> {
+    let text = input.trim().trim_end_matches(['?', '!', '.']).trim().to_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    parse_system_action(&text)
+        .or_else(|| parse_conversion(&text))
+        .or_else(|| parse_math(&text).map(Intent::Math))
+}
+
+fn parse_system_action(text: &str) -> Option<Intent> {
+    let text = text.strip_prefix("please ").unwrap_or(text);
+    let text = text.strip_suffix(" please").unwrap_or(text);
+    if let Some(rest) = text.strip_prefix("empty ") {
+        let rest = rest.strip_prefix("the ").or_else(|| rest.strip_prefix("my ")).unwrap_or(rest);
+        return matches!(rest, "recycle bin" | "recyclebin" | "trash").then_some(Intent::EmptyRecycleBin);
+    }
+    let rest = text.strip_prefix("open ")?;
+    let rest = rest.strip_prefix("the ").or_else(|| rest.strip_prefix("my ")).unwrap_or(rest);
+    let rest = rest.strip_suffix(" folder").unwrap_or(rest);
+    let folder = match rest {
+        "downloads" | "download" => Folder::Downloads,
+        "documents" | "docs" => Folder::Documents,
+        "desktop" => Folder::Desktop,
+        "pictures" | "photos" => Folder::Pictures,
+        _ => return None,
+    };
+    Some(Intent::OpenFolder(folder))
+}
+
+// ── Unit conversion ──
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Length,
+    Mass,
+    Volume,
+    Data,
+    Time,
+    Speed,
+    Temperature,
+}
+
+/// A unit: symbol shown in replies, accepted spellings, dimension and size
+/// in the dimension's base unit (unused for temperature).
+struct Unit {
+    symbol: &'static str,
+    names: &'static [&'static str],
+    dimension: Dimension,
+    factor: f64,
+}
+
+const fn unit(symbol: &'static str, names: &'static [&'static str], dimension: Dimension, factor: f64) -> Unit {
+    Unit { symbol, names, dimension, factor }
+}
+
+const UNITS: &[Unit] = &[
+    unit("mm", &["mm", "millimeter", "millimeters", "millimetre", "millimetres"], Dimension::Length, 0.001),
+    unit("cm", &["cm", "centimeter", "centimeters", "centimetre", "centimetres"], Dimension::Length, 0.01),
+    unit("m", &["m", "meter", "meters", "metre", "metres"], Dimension::Length, 1.0),
+    unit("km", &["km", "kilometer", "kilometers", "kilometre", "kilometres"], Dimension::Length, 1000.0),
+    unit("in", &["in", "inch", "inches", "\""], Dimension::Length, 0.0254),
+    unit("ft", &["ft", "foot", "feet", "'"], Dimension::Length, 0.3048),
+    unit("yd", &["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    unit("mi", &["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    unit("mg", &["mg", "milligram", "milligrams"], Dimension::Mass, 1e-6),
+    unit("g", &["g", "gram", "grams"], Dimension::Mass, 0.001),
+    unit("kg", &["kg", "kilogram", "kilograms", "kilo", "kilos"], Dimension::Mass, 1.0),
+    unit("oz", &["oz", "ounce", "ounces"], Dimension::Mass, 0.028_349_523_125),
+    unit("lb", &["lb", "lbs", "pound", "pounds"], Dimension::Mass, 0.453_592_37),
+    unit("st", &["st", "stone", "stones"], Dimension::Mass, 6.350_293_18),
+    unit("ml", &["ml", "milliliter", "milliliters", "millilitre", "millilitres"], Dimension::Volume, 0.001),
+    unit("l", &["l", "liter", "liters", "litre", "litres"], Dimension::Volume, 1.0),
+    unit("fl oz", &["fl oz", "floz", "fluid ounce", "fluid ounces"], Dimension::Volume, 0.029_573_529_562_5),
+    unit("cup", &["cup", "cups"], Dimension::Volume, 0.236_588_236_5),
+    unit("pt", &["pt", "pint", "pints"], Dimension::Volume, 0.473_176_473),
+    unit("qt", &["qt", "quart", "quarts"], Dimension::Volume, 0.946_352_946),
+    unit("gal", &["gal", "gallon", "gallons"], Dimension::Volume, 3.785_411_784),
+    unit("B", &["b", "byte", "bytes"], Dimension::Data, 1.0),
+    unit("KB", &["kb", "kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    unit("MB", &["mb", "megabyte", "megabytes"], Dimension::Data, 1e6),
+    unit("GB", &["gb", "gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    unit("TB", &["tb", "terabyte", "terabytes"], Dimension::Data, 1e12),
+    unit("KiB", &["kib", "kibibyte", "kibibytes"], Dimension::Data, 1024.0),
+    unit("MiB", &["mib", "mebibyte", "mebibytes"], Dimension::Data, 1_048_576.0),
+    unit("GiB", &["gib", "gibibyte", "gibibytes"], Dimension::Data, 1_073_741_824.0),
+    unit("TiB", &["tib", "tebibyte", "tebibytes"], Dimension::Data, 1_099_511_627_776.0),
+    unit("ms", &["ms", "millisecond", "milliseconds"], Dimension::Time, 0.001),
+    unit("s", &["s", "sec", "secs", "second", "seconds"], Dimension::Time, 1.0),
+    unit("min", &["min", "mins", "minute", "minutes"], Dimension::Time, 60.0),
+    unit("h", &["h", "hr", "hrs", "hour", "hours"], Dimension::Time, 3600.0),
+    unit("days", &["d", "day", "days"], Dimension::Time, 86_400.0),
+    unit("weeks", &["wk", "week", "weeks"], Dimension::Time, 604_800.0),
+    unit("m/s", &["m/s", "mps"], Dimension::Speed, 1.0),
+    unit("km/h", &["km/h", "kmh", "kph"], Dimension::Speed, 1.0 / 3.6),
+    unit("mph", &["mph"], Dimension::Speed, 0.447_04),
+    unit("knots", &["kn", "kt", "knot", "knots"], Dimension::Speed, 0.514_444),
+    unit("°C", &["c", "°c", "celsius", "degc"], Dimension::Temperature, 0.0),
+    unit("°F", &["f", "°f", "fahrenheit", "degf"], Dimension::Temperature, 0.0),
+    unit("K", &["k", "kelvin"], Dimension::Temperature, 0.0),
+];
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim();
+    let name = name.strip_prefix("degrees ").unwrap_or(name);
+    UNITS.iter().find(|u| u.names.contains(&name))
+}
+
+fn to_kelvin(value: f64, symbol: &str) -> f64 {
+    match symbol {
+        "°C" => value + 273.15,
+        "°F" => (value - 32.0) * 5.0 / 9.0 + 273.15,
+        _ => value,
+    }
+}
+
+fn from_kelvin(kelvin: f64, symbol: &str) -> f64 {
+    match symbol {
+        "°C" => kelvin - 273.15,
+        "°F" => (kelvin - 273.15) * 9.0 / 5.0 + 32.0,
+        _ => kelvin,
+    }
+}
+
+/// `<number> <unit> to|in <unit>`, optionally prefixed with "convert".
+fn parse_conversion(text: &str) -> Option<Intent> {
+    let text = text.strip_prefix("convert ").unwrap_or(text);
+    let (source, target) = text.rsplit_once(" to ").or_else(|| text.rsplit_once(" in "))?;
+    let source = source.trim();
+    let number_len = source
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || c == ',' || (i == 0 && c == '-')))
+        .map_or(source.len(), |(i, _)| i);
+    let value: f64 = source[..number_len].replace(',', "").parse().ok()?;
+    let from = find_unit(&source[number_len..])?;
+    let to = find_unit(target)?;
+    if from.dimension != to.dimension {
+        return None;
+    }
+    let result = if from.dimension == Dimension::Temperature {
+        from_kelvin(to_kelvin(value, from.symbol), to.symbol)
+    } else {
+        value * from.factor / to.factor
+    };
+    Some(Intent::Conversion { value, from: from.symbol, result, to: to.symbol })
+}
+
+// ── Arithmetic ──
+
+/// Evaluate an arithmetic expression with `+ - * / % ^` and parentheses.
+/// Requires at least one operator so plain numbers still go to the backend.
+fn parse_math(text: &str) -> Option<f64> {
+    let text = text
+        .strip_prefix("what is ")
+        .or_else(|| text.strip_prefix("calc "))
+        .or_else(|| text.strip_prefix('='))
+        .unwrap_or(text);
+    let text = text.trim().trim_end_matches('=');
+    let mut parser = MathParser { chars: text.chars().filter(|c| !c.is_whitespace()).collect(), pos: 0, operators: 0 };
+    let value = parser.expr()?;
+    (parser.pos == parser.chars.len() && parser.operators > 0 && value.is_finite()).then_some(value)
+}
+
+struct MathParser {
+    chars: Vec<char>,
+    pos: usize,
+    operators: usize,
+}
+
+impl MathParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, options: &[char]) -> Option<char> {
+        let c = self.peek().filter(|c| options.contains(c))?;
+        self.pos += 1;
+        self.operators += 1;
+        Some(c)
+    }
+
+    fn expr(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(op) = self.eat(&['+', '-']) {
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.power()?;
+        while let Some(op) = self.eat(&['*', '/', '%', '×', '÷', 'x']) {
+            let rhs = self.power()?;
+            value = match op {
+                '/' | '÷' if rhs == 0.0 => return None,
+                '/' | '÷' => value / rhs,
+                '%' if rhs == 0.0 => return None,
+                '%' => value % rhs,
+                _ => value * rhs,
+            };
+        }
+        Some(value)
+    }
+
+    fn power(&mut self) -> Option<f64> {
+        let base = self.unary()?;
+        if self.eat(&['^']).is_some() {
+            // Right-associative: 2^3^2 = 2^9
+            return Some(base.powf(self.power()?));
+        }
+        Some(base)
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        match self.peek()? {
+            '-' => {
+                self.pos += 1;
+                Some(-self.unary()?)
+            }
+            '+' => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Option<f64> {
+        if self.peek()? == '(' {
+            self.pos += 1;
+            let value = self.expr()?;
+            if self.peek()? != ')' {
+                return None;
+            }
+            self.pos += 1;
+            return Some(value);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+}
+
+/// Up to six decimals, without trailing zeros.
+fn format_number(value: f64) -> String {
+    let text = format!("{value:.6}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+// ── System actions ──
+
+fn open_folder(app: &tauri::AppHandle, folder: Folder) -> String {
+    let paths = app.path();
+    let dir = match folder {
+        Folder::Downloads => paths.download_dir(),
+        Folder::Documents => paths.document_dir(),
+        Folder::Desktop => paths.desktop_dir(),
+        Folder::Pictures => paths.picture_dir(),
+    };
+    let opened = dir.map_err(|e| e.to_string()).and_then(|dir| {
+        tauri_plugin_opener::OpenerExt::opener(app)
+            .open_path(dir.to_string_lossy(), None::<&str>)
+            .map_err(|e| e.to_string())
+    });
+    match opened {
+        Ok(()) => format!("Opened {}.", folder.label()),
+        Err(e) => format!("Could not open {}: {e}", folder.label()),
+    }
+}
+
+/// Empty the recycle bin on all drives. Windows still asks for confirmation.
+#[cfg(target_os = "windows")]
+fn empty_recycle_bin() -> Result<(), String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::{SHEmptyRecycleBinW, SHERB_NOPROGRESSUI};
+
+    unsafe { SHEmptyRecycleBinW(HWND::default(), PCWSTR::null(), SHERB_NOPROGRESSUI) }
+        .map_err(|e| e.message().to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn empty_recycle_bin() -> Result<(), String> {
+    Err("only supported on Windows".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn math(text: &str) -> Option<f64> {
+        match parse_intent(text)? {
+            Intent::Math(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(math("2+3*4"), Some(14.0));
+        assert_eq!(math("(2 + 3) * 4"), Some(20.0));
+        assert_eq!(math("= 2^3^2"), Some(512.0));
+        assert_eq!(math("what is -7 / 2?"), Some(-3.5));
+        assert_eq!(math("10 % 4"), Some(2.0));
+    }
+
+    #[test]
+    fn test_non_math_falls_through() {
+        assert_eq!(math("42"), None);
+        assert_eq!(math("1/0"), None);
+        assert_eq!(math("(2+3"), None);
+        assert!(parse_intent("open notepad and type hello").is_none());
+        assert!(parse_intent("what is the weather").is_none());
+    }
+
+    #[test]
+    fn test_conversions() {
+        let Some(Intent::Conversion { result, from, to, .. }) = parse_intent("10 km to miles") else {
+            panic!("expected conversion");
+        };
+        assert_eq!((from, to), ("km", "mi"));
+        assert!((result - 6.213_712).abs() < 1e-6);
+
+        let Some(Intent::Conversion { result, .. }) = parse_intent("convert 212 F in celsius") else {
+            panic!("expected conversion");
+        };
+        assert!((result - 100.0).abs() < 1e-9);
+
+        let Some(Intent::Conversion { result, .. }) = parse_intent("1.5gb to mb") else {
+            panic!("expected conversion");
+        };
+        assert!((result - 1500.0).abs() < 1e-9);
+
+        // Mismatched dimensions are not conversions
+        assert!(parse_intent("5 kg to km").is_none());
+    }
+
+    #[test]
+    fn test_system_actions() {
+        assert_eq!(parse_intent("Empty the recycle bin"), Some(Intent::EmptyRecycleBin));
+        assert_eq!(parse_intent("empty trash please"), Some(Intent::EmptyRecycleBin));
+        assert_eq!(parse_intent("open downloads"), Some(Intent::OpenFolder(Folder::Downloads)));
+        assert_eq!(parse_intent("Open my Documents folder."), Some(Intent::OpenFolder(Folder::Documents)));
+        assert!(parse_intent("open spotify").is_none());
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(14.0), "14");
+        assert_eq!(format_number(6.213_711_922), "6.213712");
+        assert_eq!(format_number(-0.000_000_1), "0");
+    }
+}
@@ -0,0 +1,448 @@
+//! Local scheduled tasks and reminders.
+//!
+//! Tasks fire at a time, when the user returns from idle, or when an app is
+//! launched, and either show a reminder in the avatar window or send a
+//! predefined message to the backend. They are stored as JSON in the app data
+//! directory, so reminders survive restarts and work without the backend.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+const TASKS_FILE: &str = "scheduled_tasks.json";
+
+/// How often triggers are checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Inactivity after which the next input counts as returning from idle.
+const IDLE_THRESHOLD_MS: u64 = 5 * 60 * 1000;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Wall-clock time in epoch milliseconds.
+    At { at_ms: i64 },
+    /// The user becomes active again after being idle.
+    IdleReturn,
+    /// A process with this executable name (e.g. `excel.exe`) starts.
+    AppLaunch { process: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskAction {
+    /// Show `message` as a reminder in the avatar window.
+    Notify { message: String },
+    /// Send `message` to the backend chat endpoint as if typed in the palette.
+    BackendCommand { message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub id: String,
+    pub trigger: Trigger,
+    pub action: TaskAction,
+    /// Event-triggered tasks fire every time when set; otherwise once.
+    #[serde(default)]
+    pub repeat: bool,
+    pub created_ms: i64,
+}
+
+/// What changed since the last poll.
+#[derive(Debug, Default)]
+pub struct Signals {
+    pub returned_from_idle: bool,
+    /// Lower-case executable names of processes started since the last poll.
+    pub launched: Vec<String>,
+}
+
+impl Trigger {
+    fn fires(&self, now_ms: i64, signals: &Signals) -> bool {
+        match self {
+            Trigger::At { at_ms } => now_ms >= *at_ms,
+            Trigger::IdleReturn => signals.returned_from_idle,
+            Trigger::AppLaunch { process } => {
+                let wanted = normalize_process(process);
+                signals.launched.contains(&wanted)
+            }
+        }
+    }
+}
+
+/// Lower-case executable name with `.exe`, so "Excel" matches `EXCEL.EXE`.
+fn normalize_process(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    if name.ends_with(".exe") { name } else { format!("{name}.exe") }
+}
+
+/// Persistent task list.
+pub struct Scheduler {
+    path: Option<PathBuf>,
+    tasks: Mutex<Vec<ScheduledTask>>,
+}
+
+impl Scheduler {
+    /// Load tasks from `path` (missing or unreadable files start empty).
+    /// `None` keeps tasks in memory only.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let tasks = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|text| match serde_json::from_str(&text) {
+                Ok(tasks) => Some(tasks),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable scheduled tasks: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self { path, tasks: Mutex::new(tasks) }
+    }
+
+    fn save(&self, tasks: &[ScheduledTask]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string_pretty(tasks).unwrap_or_default()));
+        if let Err(e) = written {
+            log::warn!("Failed to save scheduled tasks: {e}");
+        }
+    }
+
+    pub fn add(&self, trigger: Trigger, action: TaskAction, repeat: bool) -> ScheduledTask {
+        let now_ms = Local::now().timestamp_millis();
+        let task = ScheduledTask {
+            id: format!("task-{now_ms}-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+            trigger,
+            action,
+            repeat,
+            created_ms: now_ms,
+        };
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.push(task.clone());
+        self.save(&tasks);
+        task
+    }
+
+    pub fn list(&self) -> Vec<ScheduledTask> {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        let before = tasks.len();
+        tasks.retain(|t| t.id != id);
+        let removed = tasks.len() != before;
+        if removed {
+            self.save(&tasks);
+        }
+        removed
+    }
+
+    /// Tasks whose trigger fired. One-shot tasks are removed from the list.
+    pub fn take_due(&self, now_ms: i64, signals: &Signals) -> Vec<ScheduledTask> {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        let due: Vec<ScheduledTask> = tasks.iter().filter(|t| t.trigger.fires(now_ms, signals)).cloned().collect();
+        if due.is_empty() {
+            return due;
+        }
+        tasks.retain(|t| {
+            let fired = due.iter().any(|d| d.id == t.id);
+            !fired || (t.repeat && !matches!(t.trigger, Trigger::At { .. }))
+        });
+        self.save(&tasks);
+        due
+    }
+}
+
+/// Parse palette phrasing into a reminder: "remind me in 10 minutes to
+/// stretch", "remind me at 3:30pm to call Sam", "remind me when I open excel
+/// to fill in the timesheet", "remind me when I'm back to check mail".
+pub fn parse_reminder(text: &str, now: chrono::DateTime<Local>) -> Option<(Trigger, TaskAction)> {
+    let text = text.trim().trim_end_matches(['.', '!']);
+    let lower = text.to_lowercase();
+    let rest_lower = lower.strip_prefix("remind me ")?;
+    let offset = text.len() - rest_lower.len();
+    // Split "<when> to <what>", keeping the message's original casing
+    let (when, message) = match rest_lower.find(" to ") {
+        Some(i) => (&rest_lower[..i], text[offset + i + 4..].trim().to_string()),
+        None => (rest_lower, String::new()),
+    };
+
+    let trigger = if let Some(app) = when.strip_prefix("when i open ").or_else(|| when.strip_prefix("when i launch ")) {
+        Trigger::AppLaunch { process: normalize_process(app) }
+    } else if matches!(when, "when i'm back" | "when i am back" | "when i return" | "when i get back") {
+        Trigger::IdleReturn
+    } else if let Some(delay) = when.strip_prefix("in ") {
+        let (amount, unit) = delay.split_once(' ')?;
+        let amount: i64 = amount.parse().ok()?;
+        let minutes = match unit.trim_end_matches('s') {
+            "minute" | "min" => amount,
+            "hour" | "hr" => amount * 60,
+            _ => return None,
+        };
+        Trigger::At { at_ms: (now + chrono::Duration::minutes(minutes)).timestamp_millis() }
+    } else if let Some(time) = when.strip_prefix("at ") {
+        let time = parse_clock_time(time)?;
+        let mut at = Local.from_local_datetime(&now.date_naive().and_time(time)).earliest()?;
+        if at <= now {
+            at += chrono::Duration::days(1);
+        }
+        Trigger::At { at_ms: at.timestamp_millis() }
+    } else {
+        return None;
+    };
+
+    let message = if message.is_empty() {
+        match &trigger {
+            Trigger::AppLaunch { process } => format!("You opened {process}."),
+            _ => "Reminder".to_string(),
+        }
+    } else {
+        message
+    };
+    Some((trigger, TaskAction::Notify { message }))
+}
+
+/// "15:30", "3pm", "3:30 pm", "9am".
+fn parse_clock_time(text: &str) -> Option<NaiveTime> {
+    let text = text.replace(' ', "");
+    let (clock, pm) = if let Some(c) = text.strip_suffix("pm") {
+        (c, Some(true))
+    } else if let Some(c) = text.strip_suffix("am") {
+        (c, Some(false))
+    } else {
+        (text.as_str(), None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(true) => hour % 12 + 12,
+        Some(false) => hour % 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+// ── Runtime ──
+
+/// Create the scheduler from the app data directory and start its worker.
+pub fn start(app: &tauri::AppHandle) {
+    let path = app.path().app_data_dir().ok().map(|dir| dir.join(TASKS_FILE));
+    app.manage(Scheduler::load(path));
+    let app = app.clone();
+    std::thread::spawn(move || worker(app));
+}
+
+fn worker(app: tauri::AppHandle) {
+    let mut watcher = SignalWatcher::default();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let signals = watcher.poll();
+        let due = app.state::<Scheduler>().take_due(Local::now().timestamp_millis(), &signals);
+        for task in due {
+            run_action(&app, &task);
+        }
+    }
+}
+
+fn run_action(app: &tauri::AppHandle, task: &ScheduledTask) {
+    match &task.action {
+        TaskAction::Notify { message } => notify(app, message),
+        TaskAction::BackendCommand { message } => {
+            let handle = app.clone();
+            let message = message.clone();
+            crate::shutdown::spawn_request(app, async move {
+                let sent = reqwest::Client::new()
+                    .post("http://localhost:8000/api/chat")
+                    .json(&serde_json::json!({ "message": message, "allow_actions": true }))
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status());
+                if let Err(e) = sent {
+                    notify(&handle, &format!("Scheduled command \"{message}\" failed: {e}"));
+                }
+            });
+        }
+    }
+}
+
+/// Show a reminder in the avatar window.
+fn notify(app: &tauri::AppHandle, message: &str) {
+    if let Some(window) = app.get_webview_window("avatar") {
+        let _ = window.show();
+    }
+    let _ = app.emit("reminder", serde_json::json!({ "message": message }));
+}
+
+/// Derives idle-return and app-launch signals between polls.
+#[derive(Default)]
+struct SignalWatcher {
+    was_idle: bool,
+    processes: Option<std::collections::HashSet<String>>,
+}
+
+impl SignalWatcher {
+    fn poll(&mut self) -> Signals {
+        let mut signals = Signals::default();
+        if let Some(idle_ms) = platform::idle_ms() {
+            let idle = idle_ms >= IDLE_THRESHOLD_MS;
+            signals.returned_from_idle = self.was_idle && !idle;
+            self.was_idle = idle;
+        }
+        if let Some(current) = platform::process_names() {
+            if let Some(previous) = &self.processes {
+                signals.launched = current.difference(previous).cloned().collect();
+            }
+            self.processes = Some(current);
+        }
+        signals
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::collections::HashSet;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    pub fn idle_ms() -> Option<u64> {
+        let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+        unsafe {
+            if !GetLastInputInfo(&mut info).as_bool() {
+                return None;
+            }
+            Some(GetTickCount().wrapping_sub(info.dwTime) as u64)
+        }
+    }
+
+    /// Lower-case executable names of running processes.
+    pub fn process_names() -> Option<HashSet<String>> {
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).ok()?;
+            let mut entry = PROCESSENTRY32W { dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32, ..Default::default() };
+            let mut names = HashSet::new();
+            let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+            while more {
+                let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+                names.insert(String::from_utf16_lossy(&entry.szExeFile[..len]).to_lowercase());
+                more = Process32NextW(snapshot, &mut entry).is_ok();
+            }
+            let _ = CloseHandle(snapshot);
+            Some(names)
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    pub fn idle_ms() -> Option<u64> {
+        None
+    }
+
+    pub fn process_names() -> Option<std::collections::HashSet<String>> {
+        None
+    }
+}
+
+// ── Commands ──
+
+#[tauri::command]
+pub fn schedule_task(
+    scheduler: tauri::State<'_, Scheduler>,
+    trigger: Trigger,
+    action: TaskAction,
+    repeat: Option<bool>,
+) -> ScheduledTask {
+    scheduler.add(trigger, action, repeat.unwrap_or(false))
+}
+
+#[tauri::command]
+pub fn list_scheduled_tasks(scheduler: tauri::State<'_, Scheduler>) -> Vec<ScheduledTask> {
+    scheduler.list()
+}
+
+#[tauri::command]
+pub fn cancel_scheduled_task(scheduler: tauri::State<'_, Scheduler>, id: String) -> bool {
+    scheduler.cancel(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notify(message: &str) -> TaskAction {
+        TaskAction::Notify { message: message.to_string() }
+    }
+
+    #[test]
+    fn test_one_shot_and_repeating_tasks() {
+        let scheduler = Scheduler::load(None);
+        let at = scheduler.add(Trigger::At { at_ms: 1_000 }, notify("time"), true);
+        let excel = scheduler.add(Trigger::AppLaunch { process: "Excel".to_string() }, notify("excel"), true);
+        let back = scheduler.add(Trigger::IdleReturn, notify("back"), false);
+
+        assert!(scheduler.take_due(999, &Signals::default()).is_empty());
+        let signals = Signals { returned_from_idle: true, launched: vec!["excel.exe".to_string()] };
+        let due: Vec<String> = scheduler.take_due(1_000, &signals).into_iter().map(|t| t.id).collect();
+        assert_eq!(due, vec![at.id, excel.id.clone(), back.id]);
+        // Timed and one-shot tasks are gone; the repeating launch trigger stays
+        let remaining: Vec<String> = scheduler.list().into_iter().map(|t| t.id).collect();
+        assert_eq!(remaining, vec![excel.id]);
+    }
+
+    #[test]
+    fn test_tasks_persist() {
+        let path = std::env::temp_dir().join(format!("desktopai_tasks_{}.json", std::process::id()));
+        let scheduler = Scheduler::load(Some(path.clone()));
+        let task = scheduler.add(Trigger::IdleReturn, notify("hello"), false);
+        assert_eq!(Scheduler::load(Some(path.clone())).list(), vec![task.clone()]);
+        assert!(scheduler.cancel(&task.id));
+        assert!(Scheduler::load(Some(path.clone())).list().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_parse_reminders() {
+        let now = Local.with_ymd_and_hms(2026, 3, 2, 14, 0, 0).unwrap();
+        let (trigger, action) = parse_reminder("Remind me when I open Excel to fill in the Timesheet", now).unwrap();
+        assert_eq!(trigger, Trigger::AppLaunch { process: "excel.exe".to_string() });
+        assert_eq!(action, notify("fill in the Timesheet"));
+
+        let (trigger, _) = parse_reminder("remind me in 10 minutes to stretch", now).unwrap();
+        assert_eq!(trigger, Trigger::At { at_ms: (now + chrono::Duration::minutes(10)).timestamp_millis() });
+
+        let (trigger, _) = parse_reminder("remind me at 3:30pm to call Sam", now).unwrap();
+        let expected = Local.with_ymd_and_hms(2026, 3, 2, 15, 30, 0).unwrap();
+        assert_eq!(trigger, Trigger::At { at_ms: expected.timestamp_millis() });
+
+        // A time already past today means tomorrow
+        let (trigger, _) = parse_reminder("remind me at 9am to stand up", now).unwrap();
+        let expected = Local.with_ymd_and_hms(2026, 3, 3, 9, 0, 0).unwrap();
+        assert_eq!(trigger, Trigger::At { at_ms: expected.timestamp_millis() });
+
+        let (trigger, action) = parse_reminder("remind me when I'm back", now).unwrap();
+        assert_eq!(trigger, Trigger::IdleReturn);
+        assert_eq!(action, notify("Reminder"));
+
+        assert!(parse_reminder("remind me about the thing", now).is_none());
+        assert!(parse_reminder("what time is it", now).is_none());
+    }
+}
@@ -0,0 +1,81 @@
+//! Re-registers shell integration after explorer.exe restarts.
+//!
+//! When the shell restarts, hotkeys can stop firing and the tray icon
+//! disappears. Explorer broadcasts the registered `TaskbarCreated` message
+//! to top-level windows once the new taskbar exists, so a hidden window
+//! listens for it and re-registers the global shortcuts and the tray icon.
+
+use std::sync::OnceLock;
+
+use windows::core::w;
+use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, RegisterWindowMessageW,
+    TranslateMessage, MSG, WINDOW_STYLE, WNDCLASSW, WS_EX_TOOLWINDOW,
+};
+
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+static TASKBAR_CREATED: OnceLock<u32> = OnceLock::new();
+
+/// Start the watchdog thread. Safe to call once per process.
+pub fn start(app: &tauri::AppHandle) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+    std::thread::spawn(|| unsafe {
+        TASKBAR_CREATED.get_or_init(|| RegisterWindowMessageW(w!("TaskbarCreated")));
+        let instance: HINSTANCE = GetModuleHandleW(None).map(Into::into).unwrap_or_default();
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: w!("DesktopAIShellWatchdog"),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            log::warn!("Shell watchdog: failed to register window class");
+            return;
+        }
+        // Hidden top-level window: message-only windows miss broadcasts
+        let _ = CreateWindowExW(
+            WS_EX_TOOLWINDOW,
+            class.lpszClassName,
+            w!("DesktopAI shell watchdog"),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            instance,
+            None,
+        );
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    });
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if TASKBAR_CREATED.get() == Some(&msg) {
+        if let Some(app) = APP.get() {
+            log::info!("Shell restarted; re-registering shortcuts and tray icon");
+            let handle = app.clone();
+            let _ = app.run_on_main_thread(move || restore_shell_integration(&handle));
+        }
+        return LRESULT(0);
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+fn restore_shell_integration(app: &tauri::AppHandle) {
+    crate::register_shortcuts(app);
+    if let Some(tray) = app.tray_by_id(crate::TRAY_ID) {
+        // Hiding and showing re-adds the notification icon to the new taskbar
+        let _ = tray.set_visible(false);
+        let _ = tray.set_visible(true);
+    }
+}
@@ -0,0 +1,81 @@
+//! Coordinated shutdown for the tray "Quit" item.
+//!
+//! Quitting cancels backend requests still in flight, tells the webviews to
+//! abort theirs and flush what they hold (`app-shutdown`), releases the
+//! global shortcuts, and only then exits. A watchdog forces the process down
+//! if teardown hangs. Scheduler and accessibility state are written as they
+//! change, so there is nothing left to flush on the Rust side. The collector
+//! is a separate process the app does not start, so there is no child to
+//! signal; an embedded `local-collector` ends with this process.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{Emitter, Manager};
+use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+/// Time the webviews get to react to `app-shutdown` before exiting.
+const WEBVIEW_GRACE: Duration = Duration::from_millis(300);
+/// Exit unconditionally if a graceful exit has not finished by then.
+const FORCED_EXIT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Managed state: backend requests started from Rust that quitting cancels.
+#[derive(Default)]
+pub struct Shutdown {
+    quitting: AtomicBool,
+    in_flight: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Shutdown {
+    fn track(&self, handle: JoinHandle<()>) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight.retain(|h| !h.inner().is_finished());
+        in_flight.push(handle);
+    }
+
+    /// Abort tracked requests; returns how many were still running.
+    fn cancel_in_flight(&self) -> usize {
+        let in_flight = std::mem::take(&mut *self.in_flight.lock().unwrap_or_else(|e| e.into_inner()));
+        in_flight.iter().filter(|h| !h.inner().is_finished()).map(|h| h.abort()).count()
+    }
+}
+
+/// Spawn a backend request that quitting cancels instead of waiting for.
+pub fn spawn_request<F>(app: &tauri::AppHandle, request: F)
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let handle = tauri::async_runtime::spawn(request);
+    match app.try_state::<Shutdown>() {
+        Some(shutdown) => shutdown.track(handle),
+        None => drop(handle),
+    }
+}
+
+/// Shut down in order and exit. Repeated calls while quitting are ignored.
+pub fn quit(app: &tauri::AppHandle) {
+    let shutdown = app.state::<Shutdown>();
+    if shutdown.quitting.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| {
+        std::thread::sleep(FORCED_EXIT_TIMEOUT);
+        log::warn!("Shutdown did not finish in {FORCED_EXIT_TIMEOUT:?}; forcing exit");
+        std::process::exit(0);
+    });
+
+    let cancelled = shutdown.cancel_in_flight();
+    if cancelled > 0 {
+        log::info!("Cancelled {cancelled} in-flight backend request(s)");
+    }
+    let _ = app.global_shortcut().unregister_all();
+    let _ = app.emit("app-shutdown", ());
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(WEBVIEW_GRACE);
+        app.exit(0);
+    });
+}
@@ -0,0 +1,138 @@
+//! Shortcut fallback for Linux: a local socket and `desktopai <trigger>` CLI.
+//!
+//! The global-shortcut plugin grabs keys through X11, which most Wayland
+//! compositors do not forward, so Ctrl+Space may never fire. The running app
+//! listens on a per-user Unix socket instead, and starting the binary with a
+//! trigger argument (`desktopai toggle-palette`) sends it there and exits.
+//! Users bind that command to a key in their compositor's settings.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SOCKET_NAME: &str = "desktopai.sock";
+/// How long the CLI waits for the running app to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Actions that can be triggered from outside the app.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    TogglePalette,
+    KillAll,
+}
+
+impl Trigger {
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "toggle-palette" => Some(Trigger::TogglePalette),
+            "kill-all" => Some(Trigger::KillAll),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Trigger::TogglePalette => "toggle-palette",
+            Trigger::KillAll => "kill-all",
+        }
+    }
+}
+
+/// Socket in the user's runtime directory, which only they can access.
+fn socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(SOCKET_NAME)
+}
+
+/// If the process was started as `desktopai <trigger>`, forward the trigger
+/// to the running app and return the exit code; `None` means start normally.
+pub fn forward_cli_trigger() -> Option<i32> {
+    let trigger = Trigger::parse(&std::env::args().nth(1)?)?;
+    let sent = UnixStream::connect(socket_path()).and_then(|mut stream| {
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        writeln!(stream, "{}", trigger.name())?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply)
+    });
+    match sent {
+        Ok(reply) if reply.trim() == "ok" => Some(0),
+        Ok(reply) => {
+            eprintln!("DesktopAI: {}", reply.trim());
+            Some(1)
+        }
+        Err(e) => {
+            eprintln!("DesktopAI is not running ({e})");
+            Some(1)
+        }
+    }
+}
+
+/// Listen for triggers from the CLI for the rest of the process.
+pub fn start(app: &tauri::AppHandle) {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        log::info!("Wayland session: if Ctrl+Space does not open the palette, bind `desktopai toggle-palette` to a key");
+    }
+    let path = socket_path();
+    // A socket left by a previous run refuses connections; replace it
+    if UnixStream::connect(&path).is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("Trigger socket unavailable at {}: {e}", path.display());
+            return;
+        }
+    };
+    let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            let reply = match Trigger::parse(&line) {
+                Some(trigger) => {
+                    let handle = app.clone();
+                    let _ = app.run_on_main_thread(move || run_trigger(&handle, trigger));
+                    "ok".to_string()
+                }
+                None => format!("unknown trigger '{}'", line.trim()),
+            };
+            let _ = writeln!(&stream, "{reply}");
+        }
+    });
+}
+
+fn run_trigger(app: &tauri::AppHandle, trigger: Trigger) {
+    match trigger {
+        Trigger::TogglePalette => crate::toggle_palette(app),
+        Trigger::KillAll => {
+            let handle = app.clone();
+            crate::shutdown::spawn_request(app, async move {
+                let _ = crate::kill_all_actions_internal(&handle).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigger_names_round_trip() {
+        for trigger in [Trigger::TogglePalette, Trigger::KillAll] {
+            assert_eq!(Trigger::parse(trigger.name()), Some(trigger));
+        }
+        assert_eq!(Trigger::parse("toggle-palette\n"), Some(Trigger::TogglePalette));
+        assert_eq!(Trigger::parse("quit"), None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Palette window switcher: "Spotlight for windows".
+//!
+//! Typing `>` in the palette lists the open windows with thumbnails, as the
+//! collector sees them (`get_window_thumbnail`, or `list_windows` when
+//! screenshots are off), fuzzy-filtered by the rest of the input.
+//! The listing is fetched once when the switcher opens and re-ranked on
+//! each keystroke; choosing a window closes the palette and has the
+//! collector bring it to the front (`focus_window`). The embedded collector
+//! is asked directly; without one the actions go through the backend's
+//! bridge to the standalone collector.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::local_mode::LocalMode;
+
+/// Most windows the switcher lists.
+const MAX_WINDOWS: u64 = 40;
+/// Thumbnail bounds in pixels.
+const THUMBNAIL_SIZE: (u32, u32) = (240, 150);
+/// Backend route relaying switcher actions to the standalone collector.
+const BRIDGE_URL: &str = "http://localhost:8000/api/agent/bridge/window-action";
+/// How long a relayed action may take, thumbnails included.
+const BRIDGE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// One window the switcher offers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PickerWindow {
+    pub hwnd: String,
+    pub title: String,
+    /// Image name of the owning process, e.g. `chrome.exe`.
+    pub process: String,
+    pub minimized: bool,
+    /// `data:` URL of the thumbnail, when one could be rendered.
+    pub thumbnail: Option<String>,
+}
+
+/// Managed state: the listing the switcher is filtering.
+#[derive(Default)]
+pub struct WindowPicker(Mutex<Vec<PickerWindow>>);
+
+/// Parse one window of a `get_window_thumbnail` or `list_windows` result;
+/// untitled windows are skipped.
+fn picker_window(value: &serde_json::Value) -> Option<PickerWindow> {
+    let text = |key: &str| value.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let title = text("title").trim();
+    if title.is_empty() {
+        return None;
+    }
+    let process = text("process_exe");
+    Some(PickerWindow {
+        hwnd: text("hwnd").to_string(),
+        title: title.to_string(),
+        process: process.rsplit(['\\', '/']).next().unwrap_or(process).to_string(),
+        minimized: value.get("minimized").and_then(|v| v.as_bool()).unwrap_or(false),
+        thumbnail: value
+            .get("image_b64")
+            .and_then(|v| v.as_str())
+            .map(|image| format!("data:image/jpeg;base64,{image}")),
+    })
+}
+
+/// How well `query` fuzzy-matches `text`: every query character must
+/// appear in order (case-insensitive). Runs of consecutive characters,
+/// matches at word starts and an early first match score higher. `None`
+/// when it does not match; an empty query matches everything with 0.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if previous.is_none() {
+            score -= found.min(10) as i32;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// The windows matching `query` by title or process, best first; equal
+/// scores keep the collector's front-to-back order.
+fn rank(windows: &[PickerWindow], query: &str) -> Vec<PickerWindow> {
+    let mut scored: Vec<(i32, &PickerWindow)> = windows
+        .iter()
+        .filter_map(|w| {
+            let best = [fuzzy_score(query, &w.title), fuzzy_score(query, &w.process).map(|s| s - 1)]
+                .into_iter()
+                .flatten()
+                .max()?;
+            Some((best, w))
+        })
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+    scored.into_iter().map(|(_, w)| w.clone()).collect()
+}
+
+type ActionResult = std::collections::HashMap<String, serde_json::Value>;
+
+/// Run a switcher action in the embedded collector or, without one, through
+/// the backend's bridge; returns the action's result fields.
+async fn execute(
+    local: &LocalMode,
+    action: &str,
+    parameters: serde_json::Map<String, serde_json::Value>,
+) -> Result<ActionResult, String> {
+    if local.available() {
+        return local.execute(action, parameters);
+    }
+    let response = reqwest::Client::new()
+        .post(BRIDGE_URL)
+        .timeout(BRIDGE_TIMEOUT)
+        .json(&serde_json::json!({ "action": action, "parameters": parameters }))
+        .send()
+        .await
+        .map_err(|e| format!("backend unreachable: {e}"))?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.map_err(|e| format!("bad backend reply: {e}"))?;
+    bridge_result(action, status.is_success(), body)
+}
+
+/// The result fields of a relayed action, or why it failed: the backend's
+/// `detail` when the request was refused, the collector's `error` otherwise.
+fn bridge_result(action: &str, accepted: bool, body: serde_json::Value) -> Result<ActionResult, String> {
+    let text = |key: &str| body.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    if !accepted {
+        return Err(text("detail").unwrap_or_else(|| "backend refused the request".to_string()));
+    }
+    if body.get("ok").and_then(|v| v.as_bool()) != Some(true) {
+        return Err(text("error").unwrap_or_else(|| format!("{action} failed")));
+    }
+    Ok(body.get("result").cloned().and_then(|r| serde_json::from_value(r).ok()).unwrap_or_default())
+}
+
+/// Fetch the open windows from the collector, with thumbnails unless
+/// screenshots are disabled.
+async fn list_windows(local: &LocalMode) -> Result<Vec<PickerWindow>, String> {
+    let parameters = [
+        ("limit", serde_json::json!(MAX_WINDOWS)),
+        ("max_width", serde_json::json!(THUMBNAIL_SIZE.0)),
+        ("max_height", serde_json::json!(THUMBNAIL_SIZE.1)),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+    let result = match execute(local, "get_window_thumbnail", parameters).await {
+        Ok(result) => result,
+        Err(_) => execute(local, "list_windows", serde_json::Map::new()).await?,
+    };
+    let windows = result.get("windows").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    Ok(windows.iter().filter_map(picker_window).take(MAX_WINDOWS as usize).collect())
+}
+
+/// The open windows matching `query`, best first. With `refresh` (when the
+/// switcher opens) the listing is fetched anew; otherwise the last one is
+/// re-ranked, which is instant.
+#[tauri::command]
+pub async fn list_windows_for_picker(
+    state: tauri::State<'_, WindowPicker>,
+    local: tauri::State<'_, LocalMode>,
+    query: Option<String>,
+    refresh: Option<bool>,
+) -> Result<Vec<PickerWindow>, String> {
+    let listing = || state.0.lock().unwrap_or_else(|e| e.into_inner());
+    if refresh.unwrap_or(true) || listing().is_empty() {
+        let windows = list_windows(&local).await?;
+        *listing() = windows;
+    }
+    Ok(rank(&listing(), query.as_deref().unwrap_or("")))
+}
+
+/// Bring window `hwnd` to the front and close the palette. Returns the
+/// window's title. Focus is not handed back to the window the palette was
+/// opened over; on failure the palette stays open to say so.
+#[tauri::command]
+pub async fn activate_window(
+    app: tauri::AppHandle,
+    local: tauri::State<'_, LocalMode>,
+    hwnd: String,
+) -> Result<String, String> {
+    // The palette holds the foreground, so the collector may hand it over
+    let mut parameters = serde_json::Map::new();
+    parameters.insert("hwnd".to_string(), serde_json::json!(hwnd));
+    let result = execute(&local, "focus_window", parameters).await?;
+    if result.get("foreground").and_then(|v| v.as_bool()) == Some(false) {
+        return Err("Windows did not let the window come to the front".to_string());
+    }
+    if let Some(palette) = app.get_webview_window("palette") {
+        crate::palette_warm::close(&app, &palette);
+    }
+    Ok(result.get("title").and_then(|v| v.as_str()).unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(title: &str, process: &str) -> PickerWindow {
+        PickerWindow {
+            hwnd: format!("0x{:x}", title.len()),
+            title: title.to_string(),
+            process: process.to_string(),
+            minimized: false,
+            thumbnail: None,
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "Notepad"), None);
+        assert_eq!(fuzzy_score("pad note", "Notepad"), None);
+        assert!(fuzzy_score("note", "Notepad") > fuzzy_score("ntpd", "Notepad"));
+        assert!(fuzzy_score("vsc", "Visual Studio Code") > fuzzy_score("vsc", "Devices"));
+        assert!(fuzzy_score("CHR", "Google Chrome").is_some());
+    }
+
+    #[test]
+    fn test_rank_and_parse() {
+        let windows = [
+            window("Inbox - Outlook", "outlook.exe"),
+            window("report.docx - Word", "winword.exe"),
+            window("New Tab - Google Chrome", "chrome.exe"),
+        ];
+        let titles = |query: &str| rank(&windows, query).into_iter().map(|w| w.title).collect::<Vec<_>>();
+        assert_eq!(titles("").len(), 3, "no query keeps every window in order");
+        assert_eq!(titles("")[0], "Inbox - Outlook");
+        assert_eq!(titles("chrome"), ["New Tab - Google Chrome"]);
+        assert_eq!(titles("winword"), ["report.docx - Word"]);
+        assert!(titles("zzz").is_empty());
+
+        let parsed = picker_window(&serde_json::json!({
+            "hwnd": "0x1a2b",
+            "title": " Inbox - Outlook ",
+            "process_exe": "C:\\Program Files\\Microsoft Office\\OUTLOOK.EXE",
+            "minimized": false,
+            "image_b64": "AAAA",
+        }))
+        .unwrap();
+        assert_eq!(parsed.title, "Inbox - Outlook");
+        assert_eq!(parsed.process, "OUTLOOK.EXE");
+        assert_eq!(parsed.thumbnail.as_deref(), Some("data:image/jpeg;base64,AAAA"));
+        assert!(picker_window(&serde_json::json!({ "hwnd": "0x1", "title": "  " })).is_none());
+    }
+
+    #[test]
+    fn test_bridge_result() {
+        let ok = serde_json::json!({ "ok": true, "result": { "title": "Inbox - Outlook" } });
+        let fields = bridge_result("focus_window", true, ok).unwrap();
+        assert_eq!(fields["title"], "Inbox - Outlook");
+
+        let failed = serde_json::json!({ "ok": false, "error": "window not found" });
+        assert_eq!(bridge_result("focus_window", true, failed).unwrap_err(), "window not found");
+        let refused = serde_json::json!({ "detail": "collector bridge not connected" });
+        assert_eq!(bridge_result("list_windows", false, refused).unwrap_err(), "collector bridge not connected");
+    }
+}
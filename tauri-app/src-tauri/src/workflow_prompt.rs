@@ -0,0 +1,189 @@
+//! Workflow prompt window: asks the user for the inputs of a workflow the
+//! embedded collector is about to run (`from: prompt` in the workflow file,
+//! see the collector's workflow.rs).
+//!
+//! The collector calls the registered prompter on the thread running
+//! `run_workflow`. It shows this window and blocks until the user submits
+//! or dismisses it, the command is cancelled or `TIMEOUT` or the time left
+//! in the run (`timeout_ms` of the request) passes; prompts take turns. Closing the window dismisses the prompt, which cancels the
+//! run. Without the `local-collector` feature nothing is ever asked.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::{mpsc, Arc, Mutex};
+
+use tauri::{Manager, WindowEvent};
+
+const LABEL: &str = "workflow-prompt";
+
+/// How long a prompt waits for the user.
+#[cfg(feature = "local-collector")]
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How often a waiting prompt checks whether its command was cancelled.
+#[cfg(feature = "local-collector")]
+const POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+type Answer = Option<HashMap<String, String>>;
+
+/// The prompt on screen and where its answer goes.
+struct Pending {
+    id: u64,
+    request: serde_json::Value,
+    reply: mpsc::Sender<Answer>,
+}
+
+#[derive(Default)]
+#[cfg_attr(not(feature = "local-collector"), allow(dead_code))]
+struct Shared {
+    /// Held while a prompt is shown, so prompts take turns.
+    turn: Mutex<()>,
+    pending: Mutex<Option<Pending>>,
+    next_id: AtomicU64,
+}
+
+impl Shared {
+    fn pending(&self) -> std::sync::MutexGuard<'_, Option<Pending>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send `values` to prompt `id` if it is still waiting.
+    fn answer(&self, id: u64, values: Answer) -> Result<(), String> {
+        let mut pending = self.pending();
+        match pending.take() {
+            Some(prompt) if prompt.id == id => {
+                let _ = prompt.reply.send(values);
+                Ok(())
+            }
+            other => {
+                *pending = other;
+                Err("this prompt is no longer waiting for an answer".to_string())
+            }
+        }
+    }
+
+    fn dismiss(&self) {
+        if let Some(prompt) = self.pending().take() {
+            let _ = prompt.reply.send(None);
+        }
+    }
+}
+
+/// Managed state shared with the prompter the collector calls.
+#[derive(Clone, Default)]
+pub struct WorkflowPrompt(Arc<Shared>);
+
+impl WorkflowPrompt {
+    /// Register this window as the embedded collector's prompter.
+    #[cfg(feature = "local-collector")]
+    pub fn register(app: &tauri::AppHandle) -> Self {
+        let prompt = Self::default();
+        let (shared, app) = (prompt.0.clone(), app.clone());
+        desktopai_collector::workflow::set_prompter(Some(Arc::new(move |request| {
+            ask(&app, &shared, serde_json::to_value(request).unwrap_or_default())
+        })));
+        prompt
+    }
+
+    #[cfg(not(feature = "local-collector"))]
+    pub fn register(_app: &tauri::AppHandle) -> Self {
+        Self::default()
+    }
+}
+
+/// Show `request` and wait for the answer.
+#[cfg(feature = "local-collector")]
+fn ask(app: &tauri::AppHandle, shared: &Shared, request: serde_json::Value) -> Answer {
+    use std::sync::mpsc::RecvTimeoutError;
+    use tauri::Emitter;
+
+    let wait = request["timeout_ms"].as_u64().map_or(TIMEOUT, |ms| TIMEOUT.min(std::time::Duration::from_millis(ms)));
+    let deadline = std::time::Instant::now() + wait;
+    let _turn = shared.turn.lock().unwrap_or_else(|e| e.into_inner());
+    let (reply, answers) = mpsc::channel();
+    let id = shared.next_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+    *shared.pending() = Some(Pending { id, request, reply });
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = window.emit("workflow-prompt", id);
+    }
+
+    let answer = loop {
+        match answers.recv_timeout(POLL) {
+            Ok(answer) => break answer,
+            Err(RecvTimeoutError::Timeout)
+                if std::time::Instant::now() < deadline && !desktopai_collector::cancel::cancelled() =>
+            {
+                continue
+            }
+            Err(_) => {
+                log::info!("Workflow prompt {id} ended without an answer");
+                break None;
+            }
+        }
+    };
+    shared.pending().take();
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.hide();
+    }
+    answer
+}
+
+/// The prompt waiting for an answer, with its `id`, if any.
+#[tauri::command]
+pub fn workflow_prompt_request(state: tauri::State<'_, WorkflowPrompt>) -> Option<serde_json::Value> {
+    state.0.pending().as_ref().map(|prompt| {
+        let mut request = prompt.request.clone();
+        request["id"] = serde_json::json!(prompt.id);
+        request
+    })
+}
+
+/// Answer prompt `id` with input values by name, or dismiss it with none.
+#[tauri::command]
+pub fn answer_workflow_prompt(
+    state: tauri::State<'_, WorkflowPrompt>,
+    id: u64,
+    values: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    state.0.answer(id, values)
+}
+
+/// Window event hook: closing the window dismisses the prompt and only
+/// hides it.
+pub fn on_window_event(window: &tauri::Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == LABEL {
+            api.prevent_close();
+            let _ = window.hide();
+            if let Some(state) = window.app_handle().try_state::<WorkflowPrompt>() {
+                state.0.dismiss();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_and_dismiss() {
+        let shared = Shared::default();
+        let (reply, answers) = mpsc::channel();
+        *shared.pending() = Some(Pending { id: 2, request: serde_json::json!({}), reply });
+
+        assert!(shared.answer(1, None).is_err(), "a stale prompt id is refused");
+        let values = HashMap::from([("invoice".to_string(), "INV-7".to_string())]);
+        shared.answer(2, Some(values.clone())).unwrap();
+        assert_eq!(answers.recv().unwrap(), Some(values));
+        assert!(shared.answer(2, None).is_err(), "a prompt is answered once");
+
+        let (reply, answers) = mpsc::channel();
+        *shared.pending() = Some(Pending { id: 3, request: serde_json::json!({}), reply });
+        shared.dismiss();
+        assert_eq!(answers.recv().unwrap(), None);
+        assert!(shared.pending().is_none());
+    }
+}
@@ -33,6 +33,8 @@ let isCompact = false;
 let desktopContext = null;
 let isSending = false;
 let conversationId = null;
+let chatController = null;
+let pendingAttachments = [];
 
 // ── Avatar Engine (Three.js) ────────────────────────────────────────
 const STATUS_COLORS = {
@@ -66,6 +68,7 @@ class AvatarEngine {
     this.currentColor = new THREE.Color(STATUS_COLORS.connecting);
     this.connection = "connecting";
     this.isIdle = false;
+    this.reducedMotion = false;
 
     this._setupLights();
     this._setupMeshes();
@@ -191,7 +194,9 @@ class AvatarEngine {
   }
 
   _animate() {
-    const t = (performance.now() - this.timeStart) / 1000;
+    // Reduced motion holds the orb still; colour changes still show state
+    const t = this.reducedMotion ? 0 : (performance.now() - this.timeStart) / 1000;
+    const spin = this.reducedMotion ? 0 : 1;
     this.energy = Math.max(0, this.energy * 0.965);
     this.listenLevel += (this.listenLevelTarget - this.listenLevel) * 0.15;
     this.currentColor.lerp(this.targetColor, 0.06);
@@ -210,19 +215,19 @@ class AvatarEngine {
     const voiceKick = this.listenLevel * 0.14;
     const basePulse = this.isIdle ? 0.96 : 1.0;
     const wave = Math.sin(t * (this.isIdle ? 1.2 : 2.8)) * (this.isIdle ? 0.03 : 0.06);
-    const kick = this.energy * 0.08 + speechKick + voiceKick;
+    const kick = (this.energy * 0.08 + speechKick + voiceKick) * spin;
     const scale = basePulse + wave + kick;
     this.orb.scale.setScalar(scale);
     this.shell.scale.setScalar(1.12 + wave * 0.6 + kick * 0.7);
 
     // Rings spin
-    this.ringA.rotation.z += 0.004 + this.energy * 0.003 + this.listenLevel * 0.01;
-    this.ringA.rotation.x += 0.0012 + this.listenLevel * 0.002;
-    this.ringB.rotation.y -= 0.005 + this.energy * 0.004 + this.listenLevel * 0.012;
-    this.ringB.rotation.x -= 0.001 + this.listenLevel * 0.002;
+    this.ringA.rotation.z += (0.004 + this.energy * 0.003 + this.listenLevel * 0.01) * spin;
+    this.ringA.rotation.x += (0.0012 + this.listenLevel * 0.002) * spin;
+    this.ringB.rotation.y -= (0.005 + this.energy * 0.004 + this.listenLevel * 0.012) * spin;
+    this.ringB.rotation.x -= (0.001 + this.listenLevel * 0.002) * spin;
 
     // Pulse disc
-    const ps = 1 + Math.sin(t * 2) * 0.06 + this.energy * 0.12 + this.listenLevel * 0.24;
+    const ps = 1 + Math.sin(t * 2) * 0.06 + (this.energy * 0.12 + this.listenLevel * 0.24) * spin;
     this.pulse.scale.setScalar(ps);
     this.pulseMat.opacity = 0.08 + this.energy * 0.15 + this.listenLevel * 0.22;
 
@@ -663,8 +668,12 @@ let personalityMode = "assistant";
 
 document.querySelectorAll(".persona-pill").forEach((pill) => {
   pill.addEventListener("click", () => {
-    document.querySelectorAll(".persona-pill").forEach((p) => p.classList.remove("active"));
+    document.querySelectorAll(".persona-pill").forEach((p) => {
+      p.classList.remove("active");
+      p.setAttribute("aria-pressed", "false");
+    });
     pill.classList.add("active");
+    pill.setAttribute("aria-pressed", "true");
     personalityMode = pill.dataset.mode;
   });
 });
@@ -820,20 +829,38 @@ function hideTyping() {
   if (el) el.remove();
 }
 
+// ── Dropped files ───────────────────────────────────────────────────
+const CHAT_PLACEHOLDER = chatInput.placeholder;
+
+function setAttachments(list) {
+  pendingAttachments = list;
+  chatInput.placeholder = list.length
+    ? `${list.length} file(s) attached \u2014 ask about them...`
+    : CHAT_PLACEHOLDER;
+}
+
+function describeAttachments(list) {
+  return list.map((a) => `\u{1F4CE} ${a.name || a.path}`).join("\n");
+}
+
 async function sendMessage(text) {
   if (!text.trim() || isSending) return;
 
   isSending = true;
   sendBtn.disabled = true;
   chatInput.value = "";
+  const attachments = pendingAttachments;
+  setAttachments([]);
 
-  appendMessage("user", text);
+  appendMessage("user", attachments.length ? `${text}\n${describeAttachments(attachments)}` : text);
   avatar.bump(0.3);
   showTyping();
   avatar.setSpeaking(true);
 
+  chatController = new AbortController();
   try {
     const res = await fetch(`${API_BASE}/api/chat`, {
+      signal: chatController.signal,
       method: "POST",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify({
@@ -842,6 +869,7 @@ async function sendMessage(text) {
         conversation_id: conversationId,
         personality_mode: personalityMode,
         stream: true,
+        attachments,
       }),
     });
 
@@ -948,6 +976,7 @@ function startNewChat() {
   });
   if (chatWelcome) chatWelcome.style.display = "";
   chatInput.value = "";
+  setAttachments([]);
   chatInput.focus();
 }
 
@@ -1003,12 +1032,14 @@ closeBtn.addEventListener("click", () => {
 });
 
 // ── Cleanup ──────────────────────────────────────────────────────────
-window.addEventListener("beforeunload", () => {
+function cleanup() {
   stopListening();
   if (hasSpeechSynthesis) speechSynthesis.cancel();
   if (audioContext) { try { audioContext.close(); } catch {} }
   if (mediaStream) { mediaStream.getTracks().forEach((t) => t.stop()); }
-});
+}
+
+window.addEventListener("beforeunload", cleanup);
 
 // ── Health Chips ────────────────────────────────────────────────────
 async function pollHealth() {
@@ -1072,4 +1103,52 @@ if (window.__TAURI__) {
     activeRunIds.clear();
     if (killBtn) killBtn.classList.add("hidden");
   });
+
+  // High contrast / reduced motion, from the system or the user's override
+  const applyAccessibility = ({ high_contrast, reduced_motion }) => {
+    document.documentElement.classList.toggle("high-contrast", high_contrast);
+    document.documentElement.classList.toggle("reduced-motion", reduced_motion);
+    avatar.reducedMotion = reduced_motion;
+  };
+  invoke("get_accessibility_mode").then(applyAccessibility).catch(() => {});
+  window.__TAURI__.event.listen("accessibility-changed", (event) => applyAccessibility(event.payload));
+
+  // Quit from the tray: abort the chat request, stop audio, drop the socket
+  window.__TAURI__.event.listen("app-shutdown", () => {
+    if (chatController) chatController.abort();
+    cleanup();
+    if (ws) {
+      ws.onclose = null;
+      ws.close();
+    }
+  });
+
+  // Files dropped on the avatar ride along with the next message
+  window.__TAURI__.event.listen("files-dropped", (event) => {
+    const dropped = event.payload || [];
+    const known = new Set(pendingAttachments.map((a) => a.path));
+    setAttachments([...pendingAttachments, ...dropped.filter((a) => !known.has(a.path))]);
+    chatInput.focus();
+  });
+  window.__TAURI__.event.listen("drag-hover", (event) => {
+    document.getElementById("app")?.classList.toggle("drop-hover", event.payload === true);
+  });
+
+  // Clicking a path in an answer shows the file in Explorer
+  chatMessages.addEventListener("click", (e) => {
+    const code = e.target.closest(".msg.agent code");
+    const path = code?.textContent.trim();
+    if (!path || !/^([a-zA-Z]:[\\/]|\\\\|\/)/.test(path)) return;
+    invoke("reveal_file", { path }).catch((err) => {
+      appendMessage("agent", `Cannot show ${path}: ${err}`, { source: "error" });
+    });
+  });
+
+  // Reminders fired by the local scheduler
+  window.__TAURI__.event.listen("reminder", (event) => {
+    const { message } = event.payload || {};
+    if (!message) return;
+    appendMessage("agent", `Reminder: ${message}`, { source: "local" });
+    speakText(message);
+  });
 }
@@ -2,7 +2,9 @@
  * DesktopAI Command Palette
  *
  * Ctrl+Space → type → Enter → response → Escape → focus returns.
- * Connects to /api/chat endpoint.
+ * Trivial inputs (math, unit conversions, quick actions) are answered by the
+ * Rust side; everything else goes to the /api/chat endpoint. Input starting
+ * with ">" switches windows instead.
  */
 
 const BACKEND = "http://localhost:8000";
@@ -13,26 +15,187 @@ const responseText = document.getElementById("response-text");
 
 let conversationId = null;
 
+// Streamed answers longer than this switch the palette to peek mode
+// (faded, click-through) until the answer completes.
+const PEEK_AFTER_CHARS = 280;
+let peeking = false;
+let chatController = null;
+
+// Working folder for this conversation ("this folder"), set by dropping a
+// folder on the palette; click the chip to clear it
+const contextChip = document.getElementById("palette-context");
+const contextName = document.getElementById("palette-context-name");
+let contextDirectory = null;
+
+function showContextDirectory(path) {
+  contextDirectory = path || null;
+  contextName.textContent = contextDirectory ? contextDirectory.split(/[\\/]/).filter(Boolean).pop() : "";
+  contextChip.title = contextDirectory ? `Working in ${contextDirectory} (click to clear)` : "";
+  contextChip.classList.toggle("hidden", !contextDirectory);
+}
+
+async function setContextDirectory(path) {
+  if (!window.__TAURI__) return;
+  try {
+    showContextDirectory(await window.__TAURI__.core.invoke("set_context_directory", { path }));
+  } catch (err) {
+    showResponse(`Cannot use that folder: ${err}`);
+    return;
+  }
+  // The backend forwards it to the standalone collector (and again on reconnect)
+  fetch(`${BACKEND}/api/agent/bridge/context-directory`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ path: contextDirectory }),
+  }).catch(() => {});
+}
+
+contextChip.addEventListener("click", () => {
+  setContextDirectory(null);
+  input.focus();
+});
+
+// Files dropped on the palette, sent with the next message
+const INPUT_PLACEHOLDER = input.placeholder;
+let pendingAttachments = [];
+
+function setAttachments(list) {
+  pendingAttachments = list;
+  input.placeholder = list.length
+    ? `${list.length} file(s) attached \u2014 ask about them...`
+    : INPUT_PLACEHOLDER;
+}
+
 // ── Mic state ──
 const micBtn = document.getElementById("palette-mic-btn");
 let micStream = null, micRecorder = null, micChunks = [], micRecording = false;
 
+function resetPalette() {
+  closeSwitcher();
+  input.value = "";
+  responseEl.classList.add("hidden");
+  palette.classList.remove("loading");
+  if (micRecording) stopPaletteMic();
+  input.focus();
+  resizeForResponse(false);
+}
+
 // Auto-focus and reset when the window becomes visible
 document.addEventListener("visibilitychange", () => {
-  if (!document.hidden) {
-    input.value = "";
-    responseEl.classList.add("hidden");
-    palette.classList.remove("loading");
-    if (micRecording) stopPaletteMic();
-    input.focus();
-    resizeForResponse(false);
-  }
+  if (!document.hidden) resetPalette();
 });
 
+// The pre-warmed palette stays rendered off-screen, so it is never hidden;
+// Rust announces each open instead. Acknowledge once the input has focus so
+// the hotkey-to-focus latency can be measured.
+if (window.__TAURI__) {
+  window.__TAURI__.event.listen("palette-opened", () => {
+    resetPalette();
+    window.__TAURI__.core.invoke("palette_ready").catch(() => {});
+  });
+}
+
 window.addEventListener("DOMContentLoaded", () => {
   input.focus();
 });
 
+// Window switcher: ">" then part of a title or app name lists the open
+// windows with thumbnails, as the embedded collector sees them; Enter (or a
+// click) brings the selected one to the front
+const SWITCHER_PREFIX = ">";
+const switcherList = document.getElementById("window-switcher");
+let switcherWindows = null; // null while the switcher is closed
+let switcherIndex = 0;
+let switcherRequest = 0;
+
+function closeSwitcher() {
+  switcherWindows = null;
+  switcherRequest++;
+  switcherList.replaceChildren();
+  switcherList.classList.add("hidden");
+}
+
+/** Re-rank the windows for the current input; the first call fetches them. */
+async function updateSwitcher() {
+  if (!window.__TAURI__) return;
+  const query = input.value.slice(SWITCHER_PREFIX.length).trim();
+  const refresh = switcherWindows === null;
+  if (refresh) switcherWindows = [];
+  const request = ++switcherRequest;
+  try {
+    const windows = await window.__TAURI__.core.invoke("list_windows_for_picker", { query, refresh });
+    if (request !== switcherRequest) return;
+    switcherWindows = windows;
+    switcherIndex = 0;
+    renderSwitcher();
+  } catch (err) {
+    if (request !== switcherRequest) return;
+    closeSwitcher();
+    showResponse(`Window switcher unavailable: ${err}`);
+  }
+}
+
+function renderSwitcher() {
+  responseText.textContent = switcherWindows.length ? "" : "No matching windows";
+  switcherList.replaceChildren(
+    ...switcherWindows.map((win, i) => {
+      const item = document.createElement("li");
+      item.className = "switcher-item";
+      item.setAttribute("role", "option");
+      item.setAttribute("aria-selected", String(i === switcherIndex));
+      const thumb = document.createElement(win.thumbnail ? "img" : "div");
+      thumb.className = "switcher-thumb";
+      if (win.thumbnail) {
+        thumb.src = win.thumbnail;
+        thumb.alt = "";
+      }
+      const title = document.createElement("span");
+      title.className = "switcher-title";
+      title.textContent = win.title;
+      const process = document.createElement("span");
+      process.className = "switcher-process";
+      process.textContent = win.minimized ? `${win.process} \u00b7 minimized` : win.process;
+      const label = document.createElement("div");
+      label.className = "switcher-label";
+      label.append(title, process);
+      item.append(thumb, label);
+      item.addEventListener("click", () => activateWindow(win));
+      return item;
+    }),
+  );
+  switcherList.classList.remove("hidden");
+  responseEl.classList.remove("hidden");
+  resizeForResponse(true);
+}
+
+function moveSwitcherSelection(step) {
+  const count = switcherWindows.length;
+  if (!count) return;
+  switcherIndex = (switcherIndex + step + count) % count;
+  [...switcherList.children].forEach((item, i) => item.setAttribute("aria-selected", String(i === switcherIndex)));
+  switcherList.children[switcherIndex].scrollIntoView({ block: "nearest" });
+}
+
+async function activateWindow(win) {
+  try {
+    await window.__TAURI__.core.invoke("activate_window", { hwnd: win.hwnd });
+    resetPalette();
+  } catch (err) {
+    closeSwitcher();
+    showResponse(`Cannot switch to "${win.title}": ${err}`);
+  }
+}
+
+input.addEventListener("input", () => {
+  if (input.value.startsWith(SWITCHER_PREFIX)) {
+    updateSwitcher();
+  } else if (switcherWindows !== null) {
+    closeSwitcher();
+    responseEl.classList.add("hidden");
+    resizeForResponse(false);
+  }
+});
+
 // Keyboard handling
 input.addEventListener("keydown", async (e) => {
   if (e.key === "Escape") {
@@ -40,6 +203,17 @@ input.addEventListener("keydown", async (e) => {
     dismiss();
     return;
   }
+  if (switcherWindows !== null && (e.key === "ArrowDown" || e.key === "ArrowUp")) {
+    e.preventDefault();
+    moveSwitcherSelection(e.key === "ArrowDown" ? 1 : -1);
+    return;
+  }
+  if (switcherWindows !== null && e.key === "Enter") {
+    e.preventDefault();
+    const win = switcherWindows[switcherIndex];
+    if (win) await activateWindow(win);
+    return;
+  }
   if (e.key === "Enter" && !e.shiftKey) {
     e.preventDefault();
     const message = input.value.trim();
@@ -48,16 +222,49 @@ input.addEventListener("keydown", async (e) => {
   }
 });
 
+/** Ask the Rust side to answer trivial inputs locally; null means "send to backend". */
+async function resolveQuickIntent(message) {
+  if (!window.__TAURI__) return null;
+  try {
+    return await window.__TAURI__.core.invoke("resolve_quick_intent", { input: message });
+  } catch {
+    return null;
+  }
+}
+
 async function sendCommand(message) {
   palette.classList.add("loading");
 
   try {
+    const quick = pendingAttachments.length ? null : await resolveQuickIntent(message);
+    if (quick) {
+      input.value = "";
+      showResponse(quick.text);
+      window.__TAURI__.event.emit("palette-message", {
+        user: message,
+        agent: quick.text,
+        source: "local",
+      });
+      if (quick.kind === "action") {
+        setTimeout(() => dismiss(), 1200);
+      }
+      return;
+    }
+
     const body = { message, allow_actions: true, stream: true };
+    if (pendingAttachments.length) {
+      body.attachments = pendingAttachments;
+    }
+    if (contextDirectory) {
+      body.context_directory = contextDirectory;
+    }
     if (conversationId) {
       body.conversation_id = conversationId;
     }
 
+    chatController = new AbortController();
     const resp = await fetch(`${BACKEND}/api/chat`, {
+      signal: chatController.signal,
       method: "POST",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify(body),
@@ -70,6 +277,7 @@ async function sendCommand(message) {
 
     // Always clear input after successful response
     input.value = "";
+    setAttachments([]);
 
     // SSE streaming response
     if (resp.headers.get("content-type")?.includes("text/event-stream")) {
@@ -94,6 +302,7 @@ async function sendCommand(message) {
               break;
             }
             if (event.token) responseText.textContent += event.token;
+            if (!peeking && responseText.textContent.length > PEEK_AFTER_CHARS) setPeek(true);
             if (event.done) finalMeta = event;
           } catch { /* skip bad JSON */ }
         }
@@ -152,6 +361,18 @@ async function sendCommand(message) {
     showResponse(`Connection error: ${err.message}`);
   } finally {
     palette.classList.remove("loading");
+    setPeek(false);
+  }
+}
+
+/** Fade the palette and let clicks through to the window beneath it. */
+async function setPeek(enabled) {
+  if (peeking === enabled || !window.__TAURI__) return;
+  peeking = enabled;
+  try {
+    await window.__TAURI__.core.invoke("set_palette_peek", { enabled });
+  } catch {
+    // Non-critical
   }
 }
 
@@ -192,7 +413,7 @@ function stopPaletteMic() {
 
 async function handleMicStop() {
   if (micBtn) micBtn.classList.remove("processing");
-  input.placeholder = "Ask DesktopAI anything...";
+  setAttachments(pendingAttachments);
   if (micChunks.length === 0) return;
   const blob = new Blob(micChunks, { type: micRecorder?.mimeType || "audio/webm" });
   const form = new FormData();
@@ -255,8 +476,11 @@ function showResponse(text) {
 
 async function dismiss() {
   if (micRecording) stopPaletteMic();
+  closeSwitcher();
+  setPeek(false);
   responseEl.classList.add("hidden");
   input.value = "";
+  setAttachments([]);
   palette.classList.remove("loading");
   resizeForResponse(false);
 
@@ -288,8 +512,36 @@ async function resizeForResponse(expanded) {
   }
 }
 
+// Quit from the tray: abort the chat request and stop recording
+if (window.__TAURI__) {
+  window.__TAURI__.event.listen("app-shutdown", () => {
+    if (chatController) chatController.abort();
+    if (micRecording) stopPaletteMic();
+  });
+}
+
+if (window.__TAURI__) {
+  window.__TAURI__.event.listen("files-dropped", (event) => {
+    const dropped = event.payload || [];
+    if (dropped.length === 1 && dropped[0].is_dir) {
+      setContextDirectory(dropped[0].path);
+      input.focus();
+      return;
+    }
+    const known = new Set(pendingAttachments.map((a) => a.path));
+    setAttachments([...pendingAttachments, ...dropped.filter((a) => !known.has(a.path))]);
+    input.focus();
+  });
+  window.__TAURI__.event.listen("drag-hover", (event) => {
+    palette.classList.toggle("drop-hover", event.payload === true);
+  });
+  window.__TAURI__.core.invoke("get_context_directory").then(showContextDirectory).catch(() => {});
+  window.__TAURI__.event.listen("context-directory-changed", (event) => showContextDirectory(event.payload));
+}
+
 // Kill-confirmed visual feedback: flash palette border red
 if (window.__TAURI__) {
+
   window.__TAURI__.event.listen("kill-confirmed", () => {
     const pal = document.getElementById("palette");
     if (pal) {
@@ -298,3 +550,13 @@ if (window.__TAURI__) {
     }
   });
 }
+
+// High contrast / reduced motion, from the system or the user's override
+if (window.__TAURI__) {
+  const applyAccessibility = ({ high_contrast, reduced_motion }) => {
+    document.documentElement.classList.toggle("high-contrast", high_contrast);
+    document.documentElement.classList.toggle("reduced-motion", reduced_motion);
+  };
+  window.__TAURI__.core.invoke("get_accessibility_mode").then(applyAccessibility).catch(() => {});
+  window.__TAURI__.event.listen("accessibility-changed", (event) => applyAccessibility(event.payload));
+}
@@ -0,0 +1,82 @@
+/**
+ * DesktopAI Permissions
+ *
+ * One switch per action category. Each change is saved to the collector's
+ * permission manifest, which the collector enforces on every command and
+ * reports to the backend within a few seconds.
+ */
+
+const list = document.getElementById("categories");
+const status = document.getElementById("permissions-status");
+
+function element(tag, className, text) {
+  const el = document.createElement(tag);
+  if (className) el.className = className;
+  if (text !== undefined) el.textContent = text;
+  return el;
+}
+
+function renderCategory(permission) {
+  const item = element("li", "category");
+  const label = element("label", "category-label");
+  const toggle = element("input");
+  toggle.type = "checkbox";
+  toggle.setAttribute("role", "switch");
+  toggle.checked = permission.enabled;
+  toggle.addEventListener("change", () => save(permission, toggle));
+
+  const text = element("span", "category-text");
+  text.append(element("span", "category-name", permission.label));
+  text.append(element("span", "category-description", permission.description));
+  label.append(toggle, text);
+  item.append(label);
+  return item;
+}
+
+function render(permissions) {
+  list.replaceChildren(...permissions.map(renderCategory));
+}
+
+async function save(permission, toggle) {
+  toggle.disabled = true;
+  try {
+    render(await window.__TAURI__.core.invoke("set_permission", {
+      category: permission.category,
+      enabled: toggle.checked,
+    }));
+    status.textContent = `${permission.label} ${toggle.checked ? "allowed" : "blocked"}`;
+  } catch (err) {
+    toggle.checked = !toggle.checked;
+    toggle.disabled = false;
+    status.textContent = String(err);
+  }
+}
+
+async function load() {
+  if (!window.__TAURI__) {
+    status.textContent = "Permissions can only be changed from the DesktopAI app";
+    return;
+  }
+  try {
+    render(await window.__TAURI__.core.invoke("get_permissions"));
+    status.textContent = "";
+  } catch (err) {
+    status.textContent = String(err);
+  }
+}
+
+// The manifest may have been edited elsewhere while the window was hidden
+document.addEventListener("visibilitychange", () => {
+  if (!document.hidden) load();
+});
+load();
+
+// High contrast / reduced motion, from the system or the user's override
+if (window.__TAURI__) {
+  const applyAccessibility = ({ high_contrast, reduced_motion }) => {
+    document.documentElement.classList.toggle("high-contrast", high_contrast);
+    document.documentElement.classList.toggle("reduced-motion", reduced_motion);
+  };
+  window.__TAURI__.core.invoke("get_accessibility_mode").then(applyAccessibility).catch(() => {});
+  window.__TAURI__.event.listen("accessibility-changed", (event) => applyAccessibility(event.payload));
+}
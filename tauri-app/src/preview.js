@@ -0,0 +1,153 @@
+/**
+ * DesktopAI Privacy Preview
+ *
+ * Capture → the collector records its next N outgoing events → each is shown
+ * as sent: title, text snippets, redactions and the screenshot. A candidate
+ * redaction pattern can be tried on the captured events before adopting it
+ * as REDACT_PATTERN. Uses the embedded collector in local mode, otherwise
+ * the backend's bridge to the running collector.
+ */
+
+const BACKEND = "http://localhost:8000";
+const POLL_MS = 1000;
+const REDACTED = "[REDACTED]";
+
+const countInput = document.getElementById("capture-count");
+const status = document.getElementById("capture-status");
+const patternInput = document.getElementById("pattern-input");
+const rawToggle = document.getElementById("raw-toggle");
+const list = document.getElementById("events");
+
+let pollTimer = null;
+
+async function previewEvents(parameters) {
+  if (window.__TAURI__) {
+    try {
+      return await window.__TAURI__.core.invoke("local_execute", { action: "preview_events", parameters });
+    } catch {
+      // No embedded collector: ask the backend
+    }
+  }
+  const resp = await fetch(`${BACKEND}/api/agent/bridge/preview`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(parameters),
+  });
+  const data = await resp.json().catch(() => ({}));
+  if (!resp.ok) throw new Error(data.detail || `HTTP ${resp.status}`);
+  return data;
+}
+
+function element(tag, className, text) {
+  const el = document.createElement(tag);
+  if (className) el.className = className;
+  if (text !== undefined) el.textContent = text;
+  return el;
+}
+
+/** Text with each redaction marker highlighted. */
+function withRedactions(tag, className, text) {
+  const el = element(tag, className);
+  text.split(REDACTED).forEach((part, i) => {
+    if (i > 0) el.append(element("span", "redacted", REDACTED));
+    el.append(part);
+  });
+  return el;
+}
+
+function imageType(b64) {
+  return b64.startsWith("iVBOR") ? "image/png" : "image/jpeg";
+}
+
+function renderEvent(entry) {
+  const item = element("li", "event");
+  const time = entry.timestamp ? new Date(entry.timestamp).toLocaleTimeString() : "";
+  item.append(element("div", "event-meta", [entry.event_type, entry.process, time].filter(Boolean).join(" · ")));
+  item.append(withRedactions("div", "event-title", entry.title || "(no title)"));
+
+  if (entry.snippets.length) {
+    const snippets = element("ul", "event-snippets");
+    entry.snippets.forEach((snippet) => snippets.append(withRedactions("li", "", snippet)));
+    item.append(snippets);
+  }
+
+  const facts = [`${entry.uia_elements} UI elements`, `${entry.redactions} redacted`];
+  if (entry.newly_redacted !== undefined) facts.push(`${entry.newly_redacted} more with the pattern`);
+  facts.push(`${(entry.bytes / 1024).toFixed(1)} KB`);
+  item.append(element("div", "event-meta", facts.join(" · ")));
+
+  if (entry.screenshot_b64) {
+    const thumb = element("img", "event-thumb");
+    thumb.src = `data:${imageType(entry.screenshot_b64)};base64,${entry.screenshot_b64}`;
+    thumb.alt = `Screenshot sent with "${entry.title}"`;
+    item.append(thumb);
+  }
+
+  if (entry.event) {
+    const raw = element("details", "event-raw");
+    raw.append(element("summary", "", "Event JSON"));
+    raw.append(element("pre", "", JSON.stringify(entry.event, null, 2)));
+    item.append(raw);
+  }
+  return item;
+}
+
+async function refresh(parameters = {}) {
+  const pattern = patternInput.value.trim();
+  if (pattern) parameters.redact_pattern = pattern;
+  if (rawToggle.checked) parameters.raw = true;
+  let data;
+  try {
+    data = await previewEvents(parameters);
+  } catch (err) {
+    stopPolling();
+    status.textContent = `Preview unavailable: ${err.message}`;
+    return;
+  }
+  if (!data.ok) {
+    stopPolling();
+    status.textContent = data.error || "Preview failed";
+    return;
+  }
+  const { events, remaining } = data.result;
+  list.replaceChildren(...events.map(renderEvent));
+  status.textContent = remaining > 0
+    ? `Waiting for ${remaining} more event${remaining === 1 ? "" : "s"}…`
+    : `${events.length} event${events.length === 1 ? "" : "s"} captured`;
+  if (remaining === 0) stopPolling();
+  else if (!pollTimer && !document.hidden) pollTimer = setInterval(() => refresh(), POLL_MS);
+}
+
+function stopPolling() {
+  clearInterval(pollTimer);
+  pollTimer = null;
+}
+
+document.getElementById("capture-form").addEventListener("submit", async (e) => {
+  e.preventDefault();
+  stopPolling();
+  const count = Math.min(20, Math.max(1, parseInt(countInput.value, 10) || 5));
+  await refresh({ count });
+});
+
+document.getElementById("pattern-form").addEventListener("submit", (e) => {
+  e.preventDefault();
+  refresh();
+});
+rawToggle.addEventListener("change", () => refresh());
+
+// Nothing is fetched while hidden; reopening resumes where it left off
+document.addEventListener("visibilitychange", () => {
+  if (document.hidden) stopPolling();
+  else refresh();
+});
+
+// High contrast / reduced motion, from the system or the user's override
+if (window.__TAURI__) {
+  const applyAccessibility = ({ high_contrast, reduced_motion }) => {
+    document.documentElement.classList.toggle("high-contrast", high_contrast);
+    document.documentElement.classList.toggle("reduced-motion", reduced_motion);
+  };
+  window.__TAURI__.core.invoke("get_accessibility_mode").then(applyAccessibility).catch(() => {});
+  window.__TAURI__.event.listen("accessibility-changed", (event) => applyAccessibility(event.payload));
+}
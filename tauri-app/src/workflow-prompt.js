@@ -0,0 +1,95 @@
+/**
+ * DesktopAI Workflow Input
+ *
+ * Asks for the inputs a workflow reads `from: prompt` before the embedded
+ * collector runs it. Run answers the prompt, Cancel or Escape dismisses it
+ * and cancels the run.
+ */
+
+const form = document.getElementById("workflow-prompt");
+const title = document.getElementById("prompt-title");
+const description = document.getElementById("prompt-description");
+const fields = document.getElementById("fields");
+const status = document.getElementById("prompt-status");
+const cancelButton = document.getElementById("prompt-cancel");
+
+let current = null;
+
+function element(tag, className, text) {
+  const el = document.createElement(tag);
+  if (className) el.className = className;
+  if (text !== undefined) el.textContent = text;
+  return el;
+}
+
+function renderField(field) {
+  const label = element("label", "field");
+  const input = element("input");
+  input.name = field.name;
+  input.value = field.default;
+  input.autocomplete = "off";
+  label.append(element("span", "field-label", field.label), input);
+  return label;
+}
+
+function render(request) {
+  current = request;
+  title.textContent = request ? `Run "${request.workflow}"` : "Workflow input";
+  description.textContent = request ? request.description || "" : "No workflow is waiting for input";
+  fields.replaceChildren(...(request ? request.fields.map(renderField) : []));
+  form.querySelectorAll("button").forEach((button) => (button.disabled = !request));
+  status.textContent = "";
+  fields.querySelector("input")?.focus();
+}
+
+async function answer(values) {
+  if (!current) return;
+  const id = current.id;
+  render(null);
+  try {
+    await window.__TAURI__.core.invoke("answer_workflow_prompt", { id, values });
+  } catch (err) {
+    status.textContent = String(err);
+  }
+}
+
+async function load() {
+  if (!window.__TAURI__) {
+    status.textContent = "Workflow input can only be given from the DesktopAI app";
+    return;
+  }
+  try {
+    render(await window.__TAURI__.core.invoke("workflow_prompt_request"));
+  } catch (err) {
+    status.textContent = String(err);
+  }
+}
+
+form.addEventListener("submit", (event) => {
+  event.preventDefault();
+  const values = {};
+  fields.querySelectorAll("input").forEach((input) => (values[input.name] = input.value));
+  answer(values);
+});
+cancelButton.addEventListener("click", () => answer(null));
+document.addEventListener("keydown", (event) => {
+  if (event.key === "Escape") answer(null);
+});
+
+document.addEventListener("visibilitychange", () => {
+  if (!document.hidden) load();
+});
+if (window.__TAURI__) {
+  window.__TAURI__.event.listen("workflow-prompt", load);
+}
+load();
+
+// High contrast / reduced motion, from the system or the user's override
+if (window.__TAURI__) {
+  const applyAccessibility = ({ high_contrast, reduced_motion }) => {
+    document.documentElement.classList.toggle("high-contrast", high_contrast);
+    document.documentElement.classList.toggle("reduced-motion", reduced_motion);
+  };
+  window.__TAURI__.core.invoke("get_accessibility_mode").then(applyAccessibility).catch(() => {});
+  window.__TAURI__.event.listen("accessibility-changed", (event) => applyAccessibility(event.payload));
+}